@@ -0,0 +1,28 @@
+// Benchmarks for the vertex cache optimization of meshes. Run with `cargo bench --bench reorder`.
+//
+// Rendering is approximated by the computation of the vertex normals, which visits the vertices in face order like a GPU does.
+// The average cache miss ratio of the simulated GPU vertex cache is printed before the benchmarks.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use neuroformats::read_surf;
+use neuroformats::reorder::DEFAULT_VERTEX_CACHE_SIZE;
+
+
+fn bench_vertex_cache(c: &mut Criterion) {
+    let original = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap().mesh;
+    let (optimized, _) = original.optimize_vertex_cache(DEFAULT_VERTEX_CACHE_SIZE).unwrap();
+    println!("ACMR of lh.white with a cache of {} vertices: original {:.3}, optimized {:.3}", DEFAULT_VERTEX_CACHE_SIZE,
+        original.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE), optimized.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE));
+
+    let mut group = c.benchmark_group("vertex_cache");
+    group.sample_size(20);
+    group.bench_function("optimize_vertex_cache", |b| b.iter(|| original.optimize_vertex_cache(DEFAULT_VERTEX_CACHE_SIZE).unwrap()));
+    group.bench_function("vertex_normals/original", |b| b.iter(|| original.vertex_normals()));
+    group.bench_function("vertex_normals/optimized", |b| b.iter(|| optimized.vertex_normals()));
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_vertex_cache);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+// Benchmarks for the file writers. Run with `cargo bench --bench write`.
+//
+// They compare the buffer sizes of `neuroformats::WriteOptions`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tempfile::tempdir;
+
+use neuroformats::{read_surf, read_mgh, WriteOptions};
+use neuroformats::fs_surface::{write_surf_with_options, write_surf_from_iter};
+use neuroformats::fs_mgh::write_mgh_with_options;
+
+
+const BUFFER_SIZES: [usize; 4] = [8 * 1024, 64 * 1024, 1 << 20, 8 << 20];
+
+
+fn bench_write_surf(c: &mut Criterion) {
+    let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lh.white");
+
+    let mut group = c.benchmark_group("write_surf");
+    for buffer_size in BUFFER_SIZES.iter() {
+        let options = WriteOptions::default().with_buffer_size(*buffer_size);
+        group.bench_with_input(BenchmarkId::from_parameter(buffer_size), &options, |b, options| {
+            b.iter(|| write_surf_with_options(&path, &surf, options).unwrap())
+        });
+    }
+    group.finish();
+}
+
+
+fn bench_write_mgh(c: &mut Criterion) {
+    let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("brain.mgh");
+
+    let mut group = c.benchmark_group("write_mgh");
+    group.sample_size(10);
+    for buffer_size in BUFFER_SIZES.iter() {
+        let options = WriteOptions::default().with_buffer_size(*buffer_size);
+        group.bench_with_input(BenchmarkId::from_parameter(buffer_size), &options, |b, options| {
+            b.iter(|| write_mgh_with_options(&path, &mgh, options).unwrap())
+        });
+    }
+    group.finish();
+}
+
+
+fn bench_write_surf_from_iter(c: &mut Criterion) {
+    let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+    let mesh = &surf.mesh;
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lh.white");
+
+    c.bench_function("write_surf_from_iter", |b| {
+        b.iter(|| {
+            let vertices = mesh.vertices.chunks_exact(3).map(|v| [v[0], v[1], v[2]]);
+            let faces = mesh.faces.chunks_exact(3).map(|f| [f[0], f[1], f[2]]);
+            write_surf_from_iter(&path, mesh.num_vertices(), mesh.num_faces(), vertices, faces, &WriteOptions::default()).unwrap()
+        })
+    });
+}
+
+
+criterion_group!(benches, bench_write_surf, bench_write_mgh, bench_write_surf_from_iter);
+criterion_main!(benches);
@@ -0,0 +1,307 @@
+//! Command line tool for inspecting and converting neuroimaging files with the neuroformats crate.
+//!
+//! ```sh
+//! neuroformats-cli info subjects_dir/subject1/surf/lh.white
+//! neuroformats-cli convert subjects_dir/subject1/surf/lh.white lh_white.glb --annot subjects_dir/subject1/label/lh.aparc.annot
+//! neuroformats-cli curv-to-csv subjects_dir/subject1/surf/lh.thickness lh_thickness.csv
+//! neuroformats-cli annot-regions subjects_dir/subject1/label/lh.aparc.annot
+//! ```
+
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+
+use neuroformats::{BrainMesh, FsSurface, FsSurfaceHeader, FsAnnot};
+use neuroformats::formats::Format;
+use neuroformats::util::open_maybe_compressed;
+
+
+type CliResult = std::result::Result<(), Box<dyn Error>>;
+
+
+/// A file type the tool can read: one of the formats of the library, or a Wavefront OBJ mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileType {
+    Neuroformat(Format),
+    Obj,
+}
+
+
+/// Determine the type of a file from its extension or, for curv and surf files which typically have none, from its magic number.
+fn detect_file_type(path: &Path) -> Result<FileType, Box<dyn Error>> {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let by_extension = [
+        (".mgh", FileType::Neuroformat(Format::Mgh)),
+        (".mgz", FileType::Neuroformat(Format::Mgh)),
+        (".annot", FileType::Neuroformat(Format::Annot)),
+        (".label", FileType::Neuroformat(Format::Label)),
+        (".lta", FileType::Neuroformat(Format::Lta)),
+        (".xfm", FileType::Neuroformat(Format::Xfm)),
+        (".obj", FileType::Obj),
+    ];
+    if let Some((_, file_type)) = by_extension.iter().find(|(ext, _)| name.ends_with(ext)) {
+        return Ok(*file_type);
+    }
+
+    let mut magic = [0u8; 3];
+    open_maybe_compressed(path)?.read_exact(&mut magic)?;
+    match magic {
+        [255, 255, 254] => Ok(FileType::Neuroformat(Format::Surf)),
+        [255, 255, 255] => Ok(FileType::Neuroformat(Format::Curv)),
+        _ => Err(format!("Cannot determine the file format of '{}'.", path.display()).into()),
+    }
+}
+
+
+/// Print a summary of a file, see [`neuroformats::describe::Summary`], as text or JSON.
+fn info(path: &Path, json: bool, out: &mut dyn Write) -> CliResult {
+    let file_type = detect_file_type(path)?;
+    let summary = match file_type {
+        FileType::Obj => BrainMesh::from_obj_file(path)?.describe(),
+        FileType::Neuroformat(Format::Surf) => neuroformats::read_surf(path)?.describe(),
+        FileType::Neuroformat(Format::Curv) => neuroformats::read_curv(path)?.describe(),
+        FileType::Neuroformat(Format::Annot) => neuroformats::read_annot(path)?.describe(),
+        FileType::Neuroformat(Format::Label) => neuroformats::read_label(path)?.describe(),
+        FileType::Neuroformat(Format::Mgh) => neuroformats::read_mgh(path)?.describe(),
+        FileType::Neuroformat(format @ (Format::Lta | Format::Xfm | Format::ColorLut)) => {
+            if json {
+                return Err(format!("JSON output is not available for files in {} format.", format).into());
+            }
+            writeln!(out, "Format: {}", format)?;
+            match format {
+                Format::Lta => writeln!(out, "{}", neuroformats::transforms::read_lta(path)?)?,
+                Format::ColorLut => writeln!(out, "{}", neuroformats::read_color_lut(path)?)?,
+                _ => writeln!(out, "{}", neuroformats::transforms::read_xfm(path)?)?,
+            }
+            return Ok(());
+        }
+    };
+    if json {
+        writeln!(out, "{}", summary.to_json())?;
+    } else {
+        match file_type {
+            FileType::Obj => writeln!(out, "Format: Wavefront OBJ")?,
+            FileType::Neuroformat(format) => writeln!(out, "Format: {}", format)?,
+        }
+        write!(out, "{}", summary)?;
+    }
+    Ok(())
+}
+
+
+/// Convert a mesh or volume. The output format is determined from the extension of the output file.
+fn convert(input: &Path, output: &Path, annot: Option<&Path>) -> CliResult {
+    let output_name = output.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match detect_file_type(input)? {
+        FileType::Neuroformat(Format::Mgh) => {
+            if !(output_name.ends_with(".mgh") || output_name.ends_with(".mgz")) {
+                return Err("Volumes can only be converted to MGH or MGZ files.".into());
+            }
+            neuroformats::write_mgh(output, &neuroformats::read_mgh(input)?)?;
+        }
+        file_type @ (FileType::Neuroformat(Format::Surf) | FileType::Obj) => {
+            let mesh = if file_type == FileType::Obj { BrainMesh::from_obj_file(input)? } else { neuroformats::read_surf(input)?.mesh };
+            if output_name.ends_with(".obj") {
+                std::fs::write(output, mesh.to_obj()?)?;
+            } else if output_name.ends_with(".glb") {
+                let colors = match annot {
+                    Some(annot_path) => Some(annot_colors(&neuroformats::read_annot(annot_path)?, &mesh)?),
+                    None => None,
+                };
+                neuroformats::gltf::write_gltf(output, &mesh, colors.as_deref())?;
+            } else {
+                let header = FsSurfaceHeader { num_vertices: mesh.num_vertices() as i32, num_faces: mesh.num_faces() as i32, ..FsSurfaceHeader::default() };
+                neuroformats::write_surf(output, &FsSurface { header, mesh, tags: Vec::new() })?;
+            }
+        }
+        FileType::Neuroformat(format) => return Err(format!("Cannot convert files in {} format.", format).into()),
+    }
+    Ok(())
+}
+
+
+/// Get the RGB region colors of the vertices of a parcellation, checking that it matches the mesh.
+fn annot_colors(annot: &FsAnnot, mesh: &BrainMesh) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+    if annot.vertex_labels.len() != mesh.num_vertices() {
+        return Err(format!("The annotation has {} vertices, but the mesh has {}.", annot.vertex_labels.len(), mesh.num_vertices()).into());
+    }
+    if annot.num_regions() == 0 {
+        return Err("The annotation contains no regions.".into());
+    }
+    Ok(annot.vertex_colors(false, 0))
+}
+
+
+/// Write the values of a curv file as CSV with the columns `vertex` and `value`.
+fn curv_to_csv(path: &Path, out: &mut dyn Write) -> CliResult {
+    neuroformats::read_curv(path)?.write_csv(out)?;
+    Ok(())
+}
+
+
+/// Write the regions of an annot file as CSV, with their colors and vertex counts.
+fn annot_regions(path: &Path, out: &mut dyn Write) -> CliResult {
+    let annot = neuroformats::read_annot(path)?;
+    writeln!(out, "id,name,label,r,g,b,a,num_vertices")?;
+    for region in annot.colortable.regions.iter() {
+        let num_vertices = annot.vertex_labels.iter().filter(|l| **l == region.label).count();
+        writeln!(out, "{},{},{},{},{},{},{},{}", region.id, region.name, region.label, region.r, region.g, region.b, region.a, num_vertices)?;
+    }
+    Ok(())
+}
+
+
+/// Open the output file if one is given, or stdout otherwise.
+fn output_writer(matches: &ArgMatches) -> io::Result<Box<dyn Write>> {
+    Ok(match matches.get_one::<PathBuf>("output") {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    })
+}
+
+
+fn cli() -> Command {
+    let input = |help: &'static str| Arg::new("input").required(true).value_parser(value_parser!(PathBuf)).help(help);
+    let output = Arg::new("output").value_parser(value_parser!(PathBuf)).help("The output CSV file. Defaults to stdout.");
+    Command::new("neuroformats-cli")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Inspect and convert FreeSurfer neuroimaging files.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(Command::new("info")
+            .about("Print a summary of a file: counts, value ranges, NaN counts, and the data type and RAS information of volumes.")
+            .arg(input("A surf, curv, annot, label, MGH/MGZ, LTA, xfm or OBJ file."))
+            .arg(Arg::new("json").long("json").action(ArgAction::SetTrue).help("Print the summary as JSON.")))
+        .subcommand(Command::new("convert")
+            .about("Convert a mesh to surf, OBJ or glTF (.glb) format, or a volume to MGH or MGZ format. The output format is determined from the file extension.")
+            .arg(input("A surf or OBJ mesh file, or an MGH/MGZ volume."))
+            .arg(Arg::new("output").required(true).value_parser(value_parser!(PathBuf)).help("The output file."))
+            .arg(Arg::new("annot").long("annot").value_parser(value_parser!(PathBuf)).help("An annot file whose region colors are used as vertex colors in glTF output.")))
+        .subcommand(Command::new("curv-to-csv")
+            .about("Write the per-vertex values of a curv file as CSV.")
+            .arg(input("A curv file, like lh.thickness."))
+            .arg(output.clone()))
+        .subcommand(Command::new("annot-regions")
+            .about("Write the regions of an annot file with their colors and vertex counts as CSV.")
+            .arg(input("An annot file, like lh.aparc.annot."))
+            .arg(output))
+}
+
+
+fn run(matches: &ArgMatches) -> CliResult {
+    let (name, sub) = matches.subcommand().expect("A subcommand is required.");
+    let input = sub.get_one::<PathBuf>("input").expect("The input is required.");
+    match name {
+        "info" => info(input, sub.get_flag("json"), &mut io::stdout())?,
+        "convert" => convert(input, sub.get_one::<PathBuf>("output").expect("The output is required."), sub.get_one::<PathBuf>("annot").map(|p| p.as_path()))?,
+        "curv-to-csv" => {
+            let mut out = output_writer(sub)?;
+            curv_to_csv(input, &mut out)?;
+            out.flush()?;
+        }
+        "annot-regions" => {
+            let mut out = output_writer(sub)?;
+            annot_regions(input, &mut out)?;
+            out.flush()?;
+        }
+        _ => unreachable!("Unknown subcommand."),
+    }
+    Ok(())
+}
+
+
+fn main() {
+    if let Err(err) = run(&cli().get_matches()) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::tempdir;
+
+    const SUBJECT_DIR: &str = "../resources/subjects_dir/subject1";
+
+    fn subject_file(rel_path: &str) -> PathBuf {
+        Path::new(SUBJECT_DIR).join(rel_path)
+    }
+
+    #[test]
+    fn the_file_types_of_the_demo_files_can_be_detected() {
+        assert_eq!(FileType::Neuroformat(Format::Surf), detect_file_type(&subject_file("surf/lh.white")).unwrap());
+        assert_eq!(FileType::Neuroformat(Format::Curv), detect_file_type(&subject_file("surf/lh.thickness")).unwrap());
+        assert_eq!(FileType::Neuroformat(Format::Annot), detect_file_type(&subject_file("label/lh.aparc.annot")).unwrap());
+        assert_eq!(FileType::Neuroformat(Format::Mgh), detect_file_type(&subject_file("mri/brain.mgz")).unwrap());
+        assert_eq!(FileType::Obj, detect_file_type(Path::new("../resources/mesh/cube.obj")).unwrap());
+        assert!(detect_file_type(Path::new("../resources/img/brainmesh.jpg")).is_err());
+    }
+
+    #[test]
+    fn compressed_files_are_detected_by_content() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lh.thickness_compressed");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&std::fs::read(subject_file("surf/lh.thickness")).unwrap()).unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+        assert_eq!(FileType::Neuroformat(Format::Curv), detect_file_type(&path).unwrap());
+    }
+
+    #[test]
+    fn info_reports_the_mesh_size() {
+        let mut out = Vec::new();
+        info(&subject_file("surf/lh.white"), false, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Format: FreeSurfer surf"));
+        assert!(out.contains("vertices: 149244"));
+        assert!(out.contains("faces: 298484"));
+
+        let mut out = Vec::new();
+        info(&subject_file("mri/brain.mgz"), true, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(r#"{"kind":"FsMgh","dims":[256,256,256,1],"dtype":"MRI_UCHAR","has_ras":true,"#));
+    }
+
+    #[test]
+    fn a_curv_file_can_be_written_as_csv() {
+        let mut out = Vec::new();
+        curv_to_csv(&subject_file("surf/lh.thickness"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(149244 + 1, out.lines().count());
+        assert_eq!("vertex,value", out.lines().next().unwrap());
+        assert!(out.lines().nth(1).unwrap().starts_with("0,"));
+    }
+
+    #[test]
+    fn the_regions_of_an_annot_file_can_be_listed() {
+        let mut out = Vec::new();
+        annot_regions(&subject_file("label/lh.aparc.annot"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(36 + 1, out.lines().count());
+        assert!(out.lines().nth(2).unwrap().starts_with("1,bankssts,"));
+        let total: usize = out.lines().skip(1).map(|l| l.rsplit(',').next().unwrap().parse::<usize>().unwrap()).sum();
+        assert!(total <= 149244);
+    }
+
+    #[test]
+    fn a_surface_can_be_converted_to_gltf_and_back_to_surf() {
+        let dir = tempdir().unwrap();
+        let glb = dir.path().join("lh_white.glb");
+        convert(&subject_file("surf/lh.white"), &glb, Some(&subject_file("label/lh.aparc.annot"))).unwrap();
+        assert_eq!(&b"glTF"[..], &std::fs::read(&glb).unwrap()[0..4]);
+
+        let surf = dir.path().join("lh.white");
+        convert(&subject_file("surf/lh.white"), &surf, None).unwrap();
+        assert_eq!(neuroformats::read_surf(subject_file("surf/lh.white")).unwrap().mesh, neuroformats::read_surf(&surf).unwrap().mesh);
+
+        assert!(convert(&subject_file("surf/lh.tinysurface"), &glb, Some(&subject_file("label/lh.aparc.annot"))).is_err());
+        assert!(convert(&subject_file("surf/lh.thickness"), &glb, None).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+/* C bindings for the neuroformats crate. Generated by cbindgen, do not edit. */
+
+#ifndef NEUROFORMATS_H
+#define NEUROFORMATS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/*
+ The version of the C ABI. It is increased whenever the layout of a struct or the signature of a function changes.
+ */
+#define NF_ABI_VERSION 1
+
+/*
+ A brain mesh read from a FreeSurfer surf file.
+ */
+typedef struct NfSurface {
+  /*
+   The vertex coordinates as x,y,z triplets, `3 * num_vertices` values.
+   */
+  float *vertices;
+  /*
+   The number of vertices.
+   */
+  size_t num_vertices;
+  /*
+   The faces as triplets of 0-based vertex indices, `3 * num_faces` values.
+   */
+  int32_t *faces;
+  /*
+   The number of faces.
+   */
+  size_t num_faces;
+} NfSurface;
+
+/*
+ Per-vertex data read from a FreeSurfer curv file.
+ */
+typedef struct NfCurv {
+  /*
+   The value of each vertex, `num_vertices` values.
+   */
+  float *data;
+  /*
+   The number of vertices.
+   */
+  size_t num_vertices;
+} NfCurv;
+
+/*
+ A brain surface parcellation read from a FreeSurfer annot file.
+ */
+typedef struct NfAnnot {
+  /*
+   The label of each vertex, `num_vertices` values. Compare with `region_labels` to find the region of a vertex.
+   */
+  int32_t *vertex_labels;
+  /*
+   The number of vertices.
+   */
+  size_t num_vertices;
+  /*
+   The names of the regions in the colortable as 0-terminated strings, `num_regions` values.
+   */
+  char **region_names;
+  /*
+   The unique labels of the regions in the colortable, `num_regions` values.
+   */
+  int32_t *region_labels;
+  /*
+   The colors of the regions in the colortable as r,g,b,a quadruplets, `4 * num_regions` values.
+   */
+  int32_t *region_colors;
+  /*
+   The number of regions in the colortable.
+   */
+  size_t num_regions;
+} NfAnnot;
+
+/*
+ A label read from a FreeSurfer label file.
+ */
+typedef struct NfLabel {
+  /*
+   The 0-based vertex indices, `num_vertices` values.
+   */
+  int32_t *vertex_indices;
+  /*
+   The coordinates of the vertices as x,y,z triplets, `3 * num_vertices` values.
+   */
+  float *coords;
+  /*
+   The value of each vertex, `num_vertices` values.
+   */
+  float *values;
+  /*
+   The number of vertices in the label.
+   */
+  size_t num_vertices;
+} NfLabel;
+
+/*
+ A volume read from a FreeSurfer MGH or MGZ file.
+ */
+typedef struct NfMgh {
+  /*
+   The 4 dimensions of the volume: columns, rows, slices and frames.
+   */
+  size_t dims[4];
+  /*
+   The voxel values, converted to float from the data type of the file, in the order in which they are stored in the file.
+   */
+  float *data;
+  /*
+   The number of voxel values, the product of the `dims`.
+   */
+  size_t num_values;
+  /*
+   The vox2ras matrix in row-major order. Only valid if `has_vox2ras` is true.
+   */
+  float vox2ras[16];
+  /*
+   Whether the file contains valid RAS information.
+   */
+  bool has_vox2ras;
+} NfMgh;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Get the version of the C ABI of this library, see [`NF_ABI_VERSION`].
+ */
+uint32_t abi_version_c(void);
+
+/*
+ Get a description of the last error that occurred in a reader called from the current thread, or `NULL` if there was none.
+
+ The string is owned by the library and valid until the next reader fails on this thread.
+ */
+const char *last_error_c(void);
+
+/*
+ Read a brain mesh from a FreeSurfer surf file, like `lh.white`. Returns `NULL` on error.
+
+ # Safety
+
+ `path` must be `NULL` or a valid 0-terminated string.
+ */
+NfSurface *read_surf_c(const char *path);
+
+/*
+ Release a surface returned by [`read_surf_c`]. Does nothing for `NULL`.
+
+ # Safety
+
+ `surf` must be `NULL` or a pointer returned by [`read_surf_c`] that has not been released yet.
+ */
+void free_surf_c(NfSurface *surf);
+
+/*
+ Read per-vertex data from a FreeSurfer curv file, like `lh.thickness`. Returns `NULL` on error.
+
+ # Safety
+
+ `path` must be `NULL` or a valid 0-terminated string.
+ */
+NfCurv *read_curv_c(const char *path);
+
+/*
+ Release per-vertex data returned by [`read_curv_c`]. Does nothing for `NULL`.
+
+ # Safety
+
+ `curv` must be `NULL` or a pointer returned by [`read_curv_c`] that has not been released yet.
+ */
+void free_curv_c(NfCurv *curv);
+
+/*
+ Read a brain surface parcellation from a FreeSurfer annot file, like `lh.aparc.annot`. Returns `NULL` on error.
+
+ # Safety
+
+ `path` must be `NULL` or a valid 0-terminated string.
+ */
+NfAnnot *read_annot_c(const char *path);
+
+/*
+ Release a parcellation returned by [`read_annot_c`]. Does nothing for `NULL`.
+
+ # Safety
+
+ `annot` must be `NULL` or a pointer returned by [`read_annot_c`] that has not been released yet.
+ */
+void free_annot_c(NfAnnot *annot);
+
+/*
+ Read a label from a FreeSurfer label file, like `lh.cortex.label`. Returns `NULL` on error.
+
+ # Safety
+
+ `path` must be `NULL` or a valid 0-terminated string.
+ */
+NfLabel *read_label_c(const char *path);
+
+/*
+ Release a label returned by [`read_label_c`]. Does nothing for `NULL`.
+
+ # Safety
+
+ `label` must be `NULL` or a pointer returned by [`read_label_c`] that has not been released yet.
+ */
+void free_label_c(NfLabel *label);
+
+/*
+ Read a volume from a FreeSurfer MGH or MGZ file, like `brain.mgz`. Returns `NULL` on error.
+
+ # Safety
+
+ `path` must be `NULL` or a valid 0-terminated string.
+ */
+NfMgh *read_mgh_c(const char *path);
+
+/*
+ Release a volume returned by [`read_mgh_c`]. Does nothing for `NULL`.
+
+ # Safety
+
+ `mgh` must be `NULL` or a pointer returned by [`read_mgh_c`] that has not been released yet.
+ */
+void free_mgh_c(NfMgh *mgh);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NEUROFORMATS_H */
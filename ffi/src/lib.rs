@@ -0,0 +1,476 @@
+//! C bindings for the neuroformats crate.
+//!
+//! The readers take the path of a file as a 0-terminated string and return a pointer to a struct which holds the data
+//! as flat arrays and their lengths, or `NULL` if the file could not be read. In that case, [`last_error_c`] describes
+//! the error. Every struct returned by a reader must be released with the matching free function, e.g., a surface
+//! returned by [`read_surf_c`] with [`free_surf_c`]. The C header for these functions is `include/neuroformats.h`.
+//!
+//! ```c
+//! #include "neuroformats.h"
+//!
+//! NfSurface *surf = read_surf_c("subjects_dir/subject1/surf/lh.white");
+//! if (surf == NULL) {
+//!     fprintf(stderr, "%s\n", last_error_c());
+//!     return 1;
+//! }
+//! printf("%zu vertices, %zu faces\n", surf->num_vertices, surf->num_faces);
+//! free_surf_c(surf);
+//! ```
+
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::ptr;
+
+use neuroformats_rs::{FsSurface, FsCurv, FsAnnot, FsLabel, FsMgh};
+use neuroformats_rs::error::Result;
+
+
+/// The version of the C ABI. It is increased whenever the layout of a struct or the signature of a function changes.
+pub const NF_ABI_VERSION: u32 = 1;
+
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+
+/// Store the message of the last error of the current thread.
+fn set_last_error(message: String) {
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(message.replace('\0', "")).ok());
+}
+
+
+/// Move a vector into a heap-allocated array, which must be released with [`free_array`] and the same length.
+fn into_array<T>(values: Vec<T>) -> *mut T {
+    Box::into_raw(values.into_boxed_slice()) as *mut T
+}
+
+
+/// Release an array created by [`into_array`].
+unsafe fn free_array<T>(array: *mut T, len: usize) {
+    if !array.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(array, len)));
+    }
+}
+
+
+/// Convert a 0-terminated path from C. On Unix, the path can contain arbitrary bytes, on other systems it must be valid UTF-8.
+unsafe fn path_from_c(path: *const c_char) -> Option<PathBuf> {
+    if path.is_null() {
+        return None;
+    }
+    let bytes = CStr::from_ptr(path).to_bytes();
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        std::str::from_utf8(bytes).ok().map(PathBuf::from)
+    }
+}
+
+
+/// Read a file with the given reader and convert the result into a heap-allocated C struct, or store the error and return `NULL`.
+unsafe fn read_with<T, C, F>(path: *const c_char, reader: F) -> *mut C
+where
+    F: FnOnce(PathBuf) -> Result<T>,
+    C: From<T>,
+{
+    let path = match path_from_c(path) {
+        Some(path) => path,
+        None => {
+            set_last_error("The path is NULL or not valid UTF-8.".to_string());
+            return ptr::null_mut();
+        }
+    };
+    match reader(path) {
+        Ok(value) => Box::into_raw(Box::new(C::from(value))),
+        Err(err) => {
+            set_last_error(err.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+
+/// A brain mesh read from a FreeSurfer surf file.
+#[repr(C)]
+pub struct NfSurface {
+    /// The vertex coordinates as x,y,z triplets, `3 * num_vertices` values.
+    pub vertices: *mut f32,
+    /// The number of vertices.
+    pub num_vertices: usize,
+    /// The faces as triplets of 0-based vertex indices, `3 * num_faces` values.
+    pub faces: *mut i32,
+    /// The number of faces.
+    pub num_faces: usize,
+}
+
+impl From<FsSurface> for NfSurface {
+    fn from(surf: FsSurface) -> NfSurface {
+        NfSurface {
+            num_vertices: surf.mesh.num_vertices(),
+            num_faces: surf.mesh.num_faces(),
+            vertices: into_array(surf.mesh.vertices),
+            faces: into_array(surf.mesh.faces),
+        }
+    }
+}
+
+
+/// Per-vertex data read from a FreeSurfer curv file.
+#[repr(C)]
+pub struct NfCurv {
+    /// The value of each vertex, `num_vertices` values.
+    pub data: *mut f32,
+    /// The number of vertices.
+    pub num_vertices: usize,
+}
+
+impl From<FsCurv> for NfCurv {
+    fn from(curv: FsCurv) -> NfCurv {
+        NfCurv {
+            num_vertices: curv.data.len(),
+            data: into_array(curv.data),
+        }
+    }
+}
+
+
+/// A brain surface parcellation read from a FreeSurfer annot file.
+#[repr(C)]
+pub struct NfAnnot {
+    /// The label of each vertex, `num_vertices` values. Compare with `region_labels` to find the region of a vertex.
+    pub vertex_labels: *mut i32,
+    /// The number of vertices.
+    pub num_vertices: usize,
+    /// The names of the regions in the colortable as 0-terminated strings, `num_regions` values.
+    pub region_names: *mut *mut c_char,
+    /// The unique labels of the regions in the colortable, `num_regions` values.
+    pub region_labels: *mut i32,
+    /// The colors of the regions in the colortable as r,g,b,a quadruplets, `4 * num_regions` values.
+    pub region_colors: *mut i32,
+    /// The number of regions in the colortable.
+    pub num_regions: usize,
+}
+
+impl From<FsAnnot> for NfAnnot {
+    fn from(annot: FsAnnot) -> NfAnnot {
+        let regions = &annot.colortable.regions;
+        let names: Vec<*mut c_char> = regions.iter()
+            .map(|r| CString::new(r.name.replace('\0', "")).unwrap_or_default().into_raw())
+            .collect();
+        NfAnnot {
+            num_vertices: annot.vertex_labels.len(),
+            num_regions: regions.len(),
+            region_names: into_array(names),
+            region_labels: into_array(regions.iter().map(|r| r.label).collect()),
+            region_colors: into_array(regions.iter().flat_map(|r| vec![r.r, r.g, r.b, r.a]).collect()),
+            vertex_labels: into_array(annot.vertex_labels),
+        }
+    }
+}
+
+
+/// A label read from a FreeSurfer label file.
+#[repr(C)]
+pub struct NfLabel {
+    /// The 0-based vertex indices, `num_vertices` values.
+    pub vertex_indices: *mut i32,
+    /// The coordinates of the vertices as x,y,z triplets, `3 * num_vertices` values.
+    pub coords: *mut f32,
+    /// The value of each vertex, `num_vertices` values.
+    pub values: *mut f32,
+    /// The number of vertices in the label.
+    pub num_vertices: usize,
+}
+
+impl From<FsLabel> for NfLabel {
+    fn from(label: FsLabel) -> NfLabel {
+        let vertexes = &label.vertexes;
+        NfLabel {
+            num_vertices: vertexes.len(),
+            vertex_indices: into_array(vertexes.iter().map(|v| v.index).collect()),
+            coords: into_array(vertexes.iter().flat_map(|v| vec![v.coord1, v.coord2, v.coord3]).collect()),
+            values: into_array(vertexes.iter().map(|v| v.value).collect()),
+        }
+    }
+}
+
+
+/// A volume read from a FreeSurfer MGH or MGZ file.
+#[repr(C)]
+pub struct NfMgh {
+    /// The 4 dimensions of the volume: columns, rows, slices and frames.
+    pub dims: [usize; 4],
+    /// The voxel values, converted to float from the data type of the file, in the order in which they are stored in the file.
+    pub data: *mut f32,
+    /// The number of voxel values, the product of the `dims`.
+    pub num_values: usize,
+    /// The vox2ras matrix in row-major order. Only valid if `has_vox2ras` is true.
+    pub vox2ras: [f32; 16],
+    /// Whether the file contains valid RAS information.
+    pub has_vox2ras: bool,
+}
+
+impl From<(FsMgh, Vec<f32>)> for NfMgh {
+    fn from((mgh, values): (FsMgh, Vec<f32>)) -> NfMgh {
+        let mut vox2ras = [0.0; 16];
+        let has_vox2ras = match mgh.vox2ras() {
+            Ok(m) => {
+                vox2ras.iter_mut().zip(m.iter()).for_each(|(dst, src)| *dst = *src);
+                true
+            }
+            Err(_) => false,
+        };
+        NfMgh {
+            dims: mgh.dim(),
+            num_values: values.len(),
+            data: into_array(values),
+            vox2ras,
+            has_vox2ras,
+        }
+    }
+}
+
+
+/// Get the version of the C ABI of this library, see [`NF_ABI_VERSION`].
+#[no_mangle]
+pub extern "C" fn abi_version_c() -> u32 {
+    NF_ABI_VERSION
+}
+
+
+/// Get a description of the last error that occurred in a reader called from the current thread, or `NULL` if there was none.
+///
+/// The string is owned by the library and valid until the next reader fails on this thread.
+#[no_mangle]
+pub extern "C" fn last_error_c() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+
+/// Read a brain mesh from a FreeSurfer surf file, like `lh.white`. Returns `NULL` on error.
+///
+/// # Safety
+///
+/// `path` must be `NULL` or a valid 0-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn read_surf_c(path: *const c_char) -> *mut NfSurface {
+    read_with(path, neuroformats_rs::read_surf)
+}
+
+
+/// Release a surface returned by [`read_surf_c`]. Does nothing for `NULL`.
+///
+/// # Safety
+///
+/// `surf` must be `NULL` or a pointer returned by [`read_surf_c`] that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn free_surf_c(surf: *mut NfSurface) {
+    if surf.is_null() {
+        return;
+    }
+    let surf = Box::from_raw(surf);
+    free_array(surf.vertices, surf.num_vertices * 3);
+    free_array(surf.faces, surf.num_faces * 3);
+}
+
+
+/// Read per-vertex data from a FreeSurfer curv file, like `lh.thickness`. Returns `NULL` on error.
+///
+/// # Safety
+///
+/// `path` must be `NULL` or a valid 0-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn read_curv_c(path: *const c_char) -> *mut NfCurv {
+    read_with(path, neuroformats_rs::read_curv)
+}
+
+
+/// Release per-vertex data returned by [`read_curv_c`]. Does nothing for `NULL`.
+///
+/// # Safety
+///
+/// `curv` must be `NULL` or a pointer returned by [`read_curv_c`] that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn free_curv_c(curv: *mut NfCurv) {
+    if curv.is_null() {
+        return;
+    }
+    let curv = Box::from_raw(curv);
+    free_array(curv.data, curv.num_vertices);
+}
+
+
+/// Read a brain surface parcellation from a FreeSurfer annot file, like `lh.aparc.annot`. Returns `NULL` on error.
+///
+/// # Safety
+///
+/// `path` must be `NULL` or a valid 0-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn read_annot_c(path: *const c_char) -> *mut NfAnnot {
+    read_with(path, neuroformats_rs::read_annot)
+}
+
+
+/// Release a parcellation returned by [`read_annot_c`]. Does nothing for `NULL`.
+///
+/// # Safety
+///
+/// `annot` must be `NULL` or a pointer returned by [`read_annot_c`] that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn free_annot_c(annot: *mut NfAnnot) {
+    if annot.is_null() {
+        return;
+    }
+    let annot = Box::from_raw(annot);
+    for i in 0..annot.num_regions {
+        drop(CString::from_raw(*annot.region_names.add(i)));
+    }
+    free_array(annot.region_names, annot.num_regions);
+    free_array(annot.region_labels, annot.num_regions);
+    free_array(annot.region_colors, annot.num_regions * 4);
+    free_array(annot.vertex_labels, annot.num_vertices);
+}
+
+
+/// Read a label from a FreeSurfer label file, like `lh.cortex.label`. Returns `NULL` on error.
+///
+/// # Safety
+///
+/// `path` must be `NULL` or a valid 0-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn read_label_c(path: *const c_char) -> *mut NfLabel {
+    read_with(path, neuroformats_rs::read_label)
+}
+
+
+/// Release a label returned by [`read_label_c`]. Does nothing for `NULL`.
+///
+/// # Safety
+///
+/// `label` must be `NULL` or a pointer returned by [`read_label_c`] that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn free_label_c(label: *mut NfLabel) {
+    if label.is_null() {
+        return;
+    }
+    let label = Box::from_raw(label);
+    free_array(label.vertex_indices, label.num_vertices);
+    free_array(label.coords, label.num_vertices * 3);
+    free_array(label.values, label.num_vertices);
+}
+
+
+/// Read a volume from a FreeSurfer MGH or MGZ file, like `brain.mgz`. Returns `NULL` on error.
+///
+/// # Safety
+///
+/// `path` must be `NULL` or a valid 0-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn read_mgh_c(path: *const c_char) -> *mut NfMgh {
+    read_with(path, |path| {
+        let mgh = neuroformats_rs::read_mgh(path)?;
+        let values = mgh.data_as_f32()?;
+        Ok((mgh, values))
+    })
+}
+
+
+/// Release a volume returned by [`read_mgh_c`]. Does nothing for `NULL`.
+///
+/// # Safety
+///
+/// `mgh` must be `NULL` or a pointer returned by [`read_mgh_c`] that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn free_mgh_c(mgh: *mut NfMgh) {
+    if mgh.is_null() {
+        return;
+    }
+    let mgh = Box::from_raw(mgh);
+    free_array(mgh.data, mgh.num_values);
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::slice;
+
+    fn c_path(path: &str) -> CString {
+        CString::new(format!("../{}", path)).unwrap()
+    }
+
+    #[test]
+    fn a_surface_can_be_read_and_released() {
+        let path = c_path("resources/subjects_dir/subject1/surf/lh.tinysurface");
+        unsafe {
+            let surf = read_surf_c(path.as_ptr());
+            assert!(!surf.is_null());
+            let expected = neuroformats_rs::read_surf("../resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap();
+            assert_eq!(expected.mesh.num_vertices(), (*surf).num_vertices);
+            assert_eq!(&expected.mesh.faces[..], slice::from_raw_parts((*surf).faces, (*surf).num_faces * 3));
+            free_surf_c(surf);
+        }
+    }
+
+    #[test]
+    fn an_annot_can_be_read_and_released() {
+        let path = c_path("resources/subjects_dir/subject1/label/lh.aparc.annot");
+        unsafe {
+            let annot = read_annot_c(path.as_ptr());
+            assert!(!annot.is_null());
+            assert_eq!(149244, (*annot).num_vertices);
+            assert_eq!(36, (*annot).num_regions);
+            let name = CStr::from_ptr(*(*annot).region_names.add(1));
+            assert_eq!("bankssts", name.to_str().unwrap());
+            free_annot_c(annot);
+        }
+    }
+
+    #[test]
+    fn curv_label_and_mgh_files_can_be_read_and_released() {
+        unsafe {
+            let curv = read_curv_c(c_path("resources/subjects_dir/subject1/surf/lh.thickness").as_ptr());
+            assert_eq!(149244, (*curv).num_vertices);
+            free_curv_c(curv);
+
+            let label = read_label_c(c_path("resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").as_ptr());
+            assert_eq!(1085, (*label).num_vertices);
+            free_label_c(label);
+
+            let mgh = read_mgh_c(c_path("resources/subjects_dir/subject1/mri/brain.mgz").as_ptr());
+            assert_eq!([256, 256, 256, 1], (*mgh).dims);
+            assert_eq!(256 * 256 * 256, (*mgh).num_values);
+            assert!((*mgh).has_vox2ras);
+            free_mgh_c(mgh);
+        }
+    }
+
+    #[test]
+    fn reading_a_missing_file_returns_null_and_sets_the_last_error() {
+        unsafe {
+            assert!(read_surf_c(c_path("resources/no_such_file").as_ptr()).is_null());
+            assert!(!last_error_c().is_null());
+            assert!(read_curv_c(ptr::null()).is_null());
+            assert_eq!("The path is NULL or not valid UTF-8.", CStr::from_ptr(last_error_c()).to_str().unwrap());
+            free_surf_c(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn the_header_declares_all_exported_functions() {
+        let header = include_str!("../include/neuroformats.h");
+        let source = include_str!("lib.rs");
+        for line in source.lines().filter(|l| l.starts_with("pub ") && l.contains("extern \"C\" fn ")) {
+            let name = line.split("fn ").nth(1).unwrap().split('(').next().unwrap();
+            assert!(header.contains(&format!("{}(", name)), "Function {} missing in header.", name);
+        }
+    }
+}
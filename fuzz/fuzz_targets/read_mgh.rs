@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use flate2::bufread::GzDecoder;
+use std::io::BufReader;
+
+fuzz_target!(|data: &[u8]| {
+    // Inputs with the gzip magic bytes are treated as MGZ files.
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let _ = neuroformats::FsMgh::from_reader(&mut BufReader::new(GzDecoder::new(data)));
+    } else {
+        let _ = neuroformats::FsMgh::from_reader(&mut &data[..]);
+    }
+});
@@ -0,0 +1,205 @@
+//! Python bindings for the neuroformats crate.
+//!
+//! The readers return Python classes that hold the data as numpy arrays. Except for the vertex labels of annotations, which
+//! are also kept by the `Annot` class, the arrays take ownership of the vectors read by Rust, so the data is not copied.
+
+// The `#[pyfunction]` macro of pyo3 0.22 converts the returned `PyResult` into itself, which clippy reports for each function.
+#![allow(clippy::useless_conversion)]
+
+
+use numpy::ndarray::IntoDimension;
+use numpy::{PyArray, PyArray1, PyArray2, PyArrayMethods, PyUntypedArrayMethods, Element};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+
+use neuroformats_rs::FsAnnot;
+use neuroformats_rs::error::NeuroformatsError;
+
+
+fn to_py_err(err: NeuroformatsError) -> PyErr {
+    match err {
+        NeuroformatsError::Io(_) => PyIOError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+
+/// Move a flat vector into a numpy array with the given shape.
+fn to_array<'py, T: Element, D: IntoDimension>(py: Python<'py>, values: Vec<T>, shape: D) -> PyResult<Bound<'py, PyArray<T, D::Dim>>> {
+    PyArray1::from_vec_bound(py, values).reshape(shape)
+}
+
+
+/// Get the values of an array in row-major order, without copying them if the array is already stored in that order.
+fn into_values<T: Clone, D: ndarray::Dimension>(array: ndarray::Array<T, D>) -> Vec<T> {
+    if array.is_standard_layout() {
+        array.into_raw_vec()
+    } else {
+        array.iter().cloned().collect()
+    }
+}
+
+
+/// A brain mesh read from a FreeSurfer surf file.
+#[pyclass(module = "neuroformats", frozen)]
+struct Surface {
+    /// The vertex coordinates, a float32 array with shape (num_vertices, 3).
+    #[pyo3(get)]
+    vertices: Py<PyArray2<f32>>,
+    /// The faces, an int32 array with shape (num_faces, 3) of 0-based vertex indices.
+    #[pyo3(get)]
+    faces: Py<PyArray2<i32>>,
+}
+
+#[pymethods]
+impl Surface {
+    #[getter]
+    fn num_vertices(&self, py: Python<'_>) -> usize {
+        self.vertices.bind(py).shape()[0]
+    }
+
+    #[getter]
+    fn num_faces(&self, py: Python<'_>) -> usize {
+        self.faces.bind(py).shape()[0]
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("Surface(num_vertices={}, num_faces={})", self.num_vertices(py), self.num_faces(py))
+    }
+}
+
+
+/// A brain surface parcellation read from a FreeSurfer annot file.
+#[pyclass(module = "neuroformats", frozen)]
+struct Annot {
+    /// The label of each vertex, an int32 array. Compare with `region_labels` to find the region of a vertex. This is a copy of the labels used by the methods.
+    #[pyo3(get)]
+    vertex_labels: Py<PyArray1<i32>>,
+    /// The names of the regions in the colortable.
+    #[pyo3(get)]
+    region_names: Vec<String>,
+    /// The unique labels of the regions in the colortable, an int32 array.
+    #[pyo3(get)]
+    region_labels: Py<PyArray1<i32>>,
+    /// The RGBA colors of the regions in the colortable, an int32 array with shape (num_regions, 4).
+    #[pyo3(get)]
+    region_colors: Py<PyArray2<i32>>,
+    /// The path of the colortable file the parcellation was created from, as stored in the annot file.
+    #[pyo3(get)]
+    colortable_filename: String,
+    annot: FsAnnot,
+}
+
+#[pymethods]
+impl Annot {
+    /// Get the region name for each vertex. Vertices which are not assigned to any region get the name `"unknown"`.
+    fn vertex_regions(&self) -> Vec<String> {
+        self.annot.vertex_regions()
+    }
+
+    /// Get the 0-based indices of all vertices in the region with the given name.
+    fn region_vertices(&self, region: String) -> Vec<usize> {
+        self.annot.region_vertices(region)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Annot(num_vertices={}, num_regions={})", self.annot.vertex_labels.len(), self.annot.num_regions())
+    }
+}
+
+
+/// A volume read from a FreeSurfer MGH or MGZ file.
+#[pyclass(module = "neuroformats", frozen)]
+struct Mgh {
+    /// The voxel data, a 4D array with the data type of the file (uint8, int32, float32 or int16).
+    #[pyo3(get)]
+    data: PyObject,
+    /// The vox2ras matrix, a float32 array with shape (4, 4), or `None` if the file does not contain valid RAS information.
+    #[pyo3(get)]
+    vox2ras: Option<Py<PyArray2<f32>>>,
+}
+
+#[pymethods]
+impl Mgh {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("Mgh(shape={})", self.data.bind(py).getattr("shape")?))
+    }
+}
+
+
+/// Read a FreeSurfer brain mesh from a surf file, like `lh.white`.
+#[pyfunction]
+fn read_surf(py: Python<'_>, path: std::path::PathBuf) -> PyResult<Surface> {
+    let surf = py.allow_threads(|| neuroformats_rs::read_surf(path)).map_err(to_py_err)?;
+    let num_vertices = surf.mesh.num_vertices();
+    let num_faces = surf.mesh.num_faces();
+    Ok(Surface {
+        vertices: to_array(py, surf.mesh.vertices, [num_vertices, 3])?.unbind(),
+        faces: to_array(py, surf.mesh.faces, [num_faces, 3])?.unbind(),
+    })
+}
+
+
+/// Read per-vertex data from a FreeSurfer curv file, like `lh.thickness`, as a float32 array.
+#[pyfunction]
+fn read_curv(py: Python<'_>, path: std::path::PathBuf) -> PyResult<Py<PyArray1<f32>>> {
+    let curv = py.allow_threads(|| neuroformats_rs::read_curv(path)).map_err(to_py_err)?;
+    Ok(PyArray1::from_vec_bound(py, curv.data).unbind())
+}
+
+
+/// Read a brain surface parcellation from a FreeSurfer annot file, like `lh.aparc.annot`.
+#[pyfunction]
+fn read_annot(py: Python<'_>, path: std::path::PathBuf) -> PyResult<Annot> {
+    let annot = py.allow_threads(|| neuroformats_rs::read_annot(path)).map_err(to_py_err)?;
+    let regions = &annot.colortable.regions;
+    let colors: Vec<i32> = regions.iter().flat_map(|r| vec![r.r, r.g, r.b, r.a]).collect();
+    Ok(Annot {
+        vertex_labels: PyArray1::from_slice_bound(py, &annot.vertex_labels).unbind(),
+        region_names: regions.iter().map(|r| r.name.clone()).collect(),
+        region_labels: PyArray1::from_iter_bound(py, regions.iter().map(|r| r.label)).unbind(),
+        region_colors: to_array(py, colors, [regions.len(), 4])?.unbind(),
+        colortable_filename: annot.colortable.orig_filename.clone(),
+        annot,
+    })
+}
+
+
+/// Read a volume from a FreeSurfer MGH or MGZ file, like `brain.mgz`.
+#[pyfunction]
+fn read_mgh(py: Python<'_>, path: std::path::PathBuf) -> PyResult<Mgh> {
+    let mgh = py.allow_threads(|| neuroformats_rs::read_mgh(path)).map_err(to_py_err)?;
+    let shape = mgh.header.dim();
+    let vox2ras = match mgh.header.vox2ras() {
+        Ok(m) => Some(to_array(py, into_values(m), [4, 4])?.unbind()),
+        Err(_) => None,
+    };
+    let data = mgh.data;
+    let data: PyObject = if let Some(d) = data.mri_uchar {
+        to_array(py, into_values(d), shape)?.into_any().unbind()
+    } else if let Some(d) = data.mri_int {
+        to_array(py, into_values(d), shape)?.into_any().unbind()
+    } else if let Some(d) = data.mri_float {
+        to_array(py, into_values(d), shape)?.into_any().unbind()
+    } else if let Some(d) = data.mri_short {
+        to_array(py, into_values(d), shape)?.into_any().unbind()
+    } else {
+        return Err(to_py_err(NeuroformatsError::UnsupportedMriDataTypeInMgh(mgh.header.dtype, shape)));
+    };
+    Ok(Mgh { data, vox2ras })
+}
+
+
+/// Fast readers for FreeSurfer neuroimaging file formats.
+#[pymodule]
+#[pyo3(name = "neuroformats")]
+fn neuroformats_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(read_surf, m)?)?;
+    m.add_function(wrap_pyfunction!(read_curv, m)?)?;
+    m.add_function(wrap_pyfunction!(read_annot, m)?)?;
+    m.add_function(wrap_pyfunction!(read_mgh, m)?)?;
+    m.add_class::<Surface>()?;
+    m.add_class::<Annot>()?;
+    m.add_class::<Mgh>()?;
+    Ok(())
+}
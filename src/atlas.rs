@@ -0,0 +1,189 @@
+//! The region names of the cortical atlases which are computed by FreeSurfer's `recon-all`.
+//!
+//! The region names can be used to check that a loaded parcellation is the expected one, and to map the regions of the parcellations
+//! of different subjects to stable indices, which do not depend on the order of the colortable of an annot file.
+
+
+use std::fmt;
+
+use crate::fs_annot::FsAnnot;
+
+
+/// The region names of the Desikan-Killiany atlas, `?h.aparc.annot`, in the order of its colortable.
+pub const APARC_REGIONS: [&str; 36] = [
+    "unknown", "bankssts", "caudalanteriorcingulate", "caudalmiddlefrontal", "corpuscallosum", "cuneus", "entorhinal", "fusiform",
+    "inferiorparietal", "inferiortemporal", "isthmuscingulate", "lateraloccipital", "lateralorbitofrontal", "lingual", "medialorbitofrontal",
+    "middletemporal", "parahippocampal", "paracentral", "parsopercularis", "parsorbitalis", "parstriangularis", "pericalcarine", "postcentral",
+    "posteriorcingulate", "precentral", "precuneus", "rostralanteriorcingulate", "rostralmiddlefrontal", "superiorfrontal", "superiorparietal",
+    "superiortemporal", "supramarginal", "frontalpole", "temporalpole", "transversetemporal", "insula",
+];
+
+
+/// The region names of the Destrieux atlas, `?h.aparc.a2009s.annot`, in the order of its colortable.
+pub const APARC_A2009S_REGIONS: [&str; 76] = [
+    "Unknown", "G_and_S_frontomargin", "G_and_S_occipital_inf", "G_and_S_paracentral", "G_and_S_subcentral", "G_and_S_transv_frontopol",
+    "G_and_S_cingul-Ant", "G_and_S_cingul-Mid-Ant", "G_and_S_cingul-Mid-Post", "G_cingul-Post-dorsal", "G_cingul-Post-ventral", "G_cuneus",
+    "G_front_inf-Opercular", "G_front_inf-Orbital", "G_front_inf-Triangul", "G_front_middle", "G_front_sup", "G_Ins_lg_and_S_cent_ins",
+    "G_insular_short", "G_occipital_middle", "G_occipital_sup", "G_oc-temp_lat-fusifor", "G_oc-temp_med-Lingual", "G_oc-temp_med-Parahip",
+    "G_orbital", "G_pariet_inf-Angular", "G_pariet_inf-Supramar", "G_parietal_sup", "G_postcentral", "G_precentral", "G_precuneus", "G_rectus",
+    "G_subcallosal", "G_temp_sup-G_T_transv", "G_temp_sup-Lateral", "G_temp_sup-Plan_polar", "G_temp_sup-Plan_tempo", "G_temporal_inf",
+    "G_temporal_middle", "Lat_Fis-ant-Horizont", "Lat_Fis-ant-Vertical", "Lat_Fis-post", "Medial_wall", "Pole_occipital", "Pole_temporal",
+    "S_calcarine", "S_central", "S_cingul-Marginalis", "S_circular_insula_ant", "S_circular_insula_inf", "S_circular_insula_sup",
+    "S_collat_transv_ant", "S_collat_transv_post", "S_front_inf", "S_front_middle", "S_front_sup", "S_interm_prim-Jensen",
+    "S_intrapariet_and_P_trans", "S_oc_middle_and_Lunatus", "S_oc_sup_and_transversal", "S_occipital_ant", "S_oc-temp_lat",
+    "S_oc-temp_med_and_Lingual", "S_orbital_lateral", "S_orbital_med-olfact", "S_orbital-H_Shaped", "S_parieto_occipital", "S_pericallosal",
+    "S_postcentral", "S_precentral-inf-part", "S_precentral-sup-part", "S_suborbital", "S_subparietal", "S_temporal_inf", "S_temporal_sup",
+    "S_temporal_transverse",
+];
+
+
+/// A cortical atlas computed by `recon-all`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Atlas {
+    /// The Desikan-Killiany atlas, `?h.aparc.annot`.
+    DesikanKilliany,
+    /// The Destrieux atlas, `?h.aparc.a2009s.annot`.
+    Destrieux,
+}
+
+
+impl Atlas {
+    /// All atlases.
+    pub const ALL: [Atlas; 2] = [Atlas::DesikanKilliany, Atlas::Destrieux];
+
+
+    /// Get the name of the annot files of the atlas, e.g., `"aparc.a2009s"` for `?h.aparc.a2009s.annot`.
+    pub fn annot_name(&self) -> &'static str {
+        match self {
+            Atlas::DesikanKilliany => "aparc",
+            Atlas::Destrieux => "aparc.a2009s",
+        }
+    }
+
+
+    /// Get the atlas with the given annot file name, e.g., `"aparc"`, or `None` if there is none.
+    pub fn from_annot_name(name: &str) -> Option<Atlas> {
+        Atlas::ALL.iter().copied().find(|atlas| atlas.annot_name() == name)
+    }
+
+
+    /// Get the stable index of a region in the atlas, i.e., its position in [`expected_regions`], or `None` if the atlas has no such region.
+    pub fn region_index(&self, name: &str) -> Option<usize> {
+        expected_regions(*self).iter().position(|region| *region == name)
+    }
+}
+
+
+impl fmt::Display for Atlas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.annot_name())
+    }
+}
+
+
+/// Get the region names of an atlas, in the order of the colortable of its annot files.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::atlas::{Atlas, expected_regions};
+/// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+/// assert_eq!(expected_regions(Atlas::DesikanKilliany).len(), annot.num_regions());
+/// ```
+pub fn expected_regions(atlas: Atlas) -> &'static [&'static str] {
+    match atlas {
+        Atlas::DesikanKilliany => &APARC_REGIONS,
+        Atlas::Destrieux => &APARC_A2009S_REGIONS,
+    }
+}
+
+
+/// The differences between the regions of a parcellation and an atlas, see [`FsAnnot::compare_to_atlas`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtlasMismatch {
+    /// The regions of the atlas which are not in the colortable of the parcellation.
+    pub missing: Vec<String>,
+    /// The regions in the colortable of the parcellation which are not part of the atlas.
+    pub unexpected: Vec<String>,
+}
+
+
+impl AtlasMismatch {
+    /// Whether the parcellation has exactly the regions of the atlas.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+
+impl FsAnnot {
+    /// Compare the region names of this parcellation to the regions of an atlas. The order of the regions is ignored.
+    pub fn compare_to_atlas(&self, atlas: Atlas) -> AtlasMismatch {
+        let names = self.regions();
+        let expected = expected_regions(atlas);
+        AtlasMismatch {
+            missing: expected.iter().filter(|region| !names.iter().any(|name| name == *region)).map(|region| region.to_string()).collect(),
+            unexpected: names.iter().filter(|name| !expected.contains(&name.as_str())).cloned().collect(),
+        }
+    }
+
+
+    /// Whether this parcellation has exactly the regions of the given atlas, see [`FsAnnot::compare_to_atlas`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::atlas::Atlas;
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.a2009s.annot").unwrap();
+    /// assert!(annot.matches_atlas(Atlas::Destrieux));
+    /// ```
+    pub fn matches_atlas(&self, atlas: Atlas) -> bool {
+        self.compare_to_atlas(atlas).is_empty()
+    }
+
+
+    /// Get the stable index of the atlas region of each vertex, see [`Atlas::region_index`].
+    ///
+    /// Vertices which are not assigned to a region, or whose region is not part of the atlas, get `None`.
+    pub fn vertex_atlas_indices(&self, atlas: Atlas) -> Vec<Option<usize>> {
+        let region_indices: Vec<Option<usize>> = self.colortable.regions.iter().map(|region| atlas.region_index(&region.name)).collect();
+        let lookup = self.region_lookup();
+        self.vertex_labels.iter().map(|label| lookup.region_index(*label).and_then(|region_idx| region_indices[region_idx])).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::read_annot;
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_demo_parcellation_matches_the_desikan_killiany_atlas() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        assert!(annot.matches_atlas(Atlas::DesikanKilliany));
+        assert_eq!(APARC_REGIONS.to_vec(), annot.regions());
+
+        let mismatch = annot.compare_to_atlas(Atlas::Destrieux);
+        assert_eq!(APARC_A2009S_REGIONS.len(), mismatch.missing.len());
+        assert_eq!(APARC_REGIONS.len(), mismatch.unexpected.len());
+
+        let indices = annot.vertex_atlas_indices(Atlas::DesikanKilliany);
+        assert_eq!(annot.vertex_labels.len(), indices.len());
+        let vertex_regions = annot.vertex_regions();
+        assert_eq!(Atlas::DesikanKilliany.region_index(&vertex_regions[0]), indices[0]);
+        assert!(annot.vertex_atlas_indices(Atlas::Destrieux).iter().all(|idx| idx.is_none()));
+    }
+
+    #[test]
+    fn atlases_can_be_found_by_annot_name() {
+        for atlas in Atlas::ALL.iter() {
+            assert_eq!(Some(*atlas), Atlas::from_annot_name(&atlas.to_string()));
+        }
+        assert_eq!(None, Atlas::from_annot_name("HCP-MMP1"));
+        assert_eq!(Some(42), Atlas::Destrieux.region_index("Medial_wall"));
+        assert_eq!(None, Atlas::Destrieux.region_index("insula"));
+    }
+}
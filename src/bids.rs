@@ -0,0 +1,177 @@
+//! Locating FreeSurfer outputs in the derivatives of a BIDS dataset.
+//!
+//! In a BIDS dataset, the FreeSurfer runs of all participants live in a derivatives directory, which acts as the SUBJECTS_DIR.
+//! The subject directories are named after the participant, like `sub-01`, or after the participant and session for datasets
+//! with several sessions, like `sub-01_ses-02`. The functions in this module find these directories and return [`Subject`]
+//! handles for them, which can be used with the rest of the crate.
+
+
+use std::path::{Path, PathBuf};
+
+use crate::subject::Subject;
+use crate::error::{NeuroformatsError, Result};
+
+
+/// The locations of the FreeSurfer derivatives relative to the root of a BIDS dataset, in the order in which they are searched.
+///
+/// The first one is used by running FreeSurfer directly, the others by fMRIPrep and sMRIPrep.
+pub const FREESURFER_DERIVATIVES_DIRS: [&str; 3] = ["derivatives/freesurfer", "derivatives/fmriprep/sourcedata/freesurfer", "derivatives/smriprep/sourcedata/freesurfer"];
+
+
+/// A BIDS dataset, used to locate the FreeSurfer derivatives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BidsDataset {
+    /// The root directory of the dataset, which contains the `dataset_description.json` file.
+    pub root: PathBuf,
+}
+
+
+/// A FreeSurfer subject in the derivatives of a BIDS dataset, see [`BidsDataset::subjects`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BidsSubject {
+    /// The participant label, without the `sub-` prefix.
+    pub participant: String,
+    /// The session label, without the `ses-` prefix, or `None` if the subject directory is not specific to a session.
+    pub session: Option<String>,
+    /// The FreeSurfer subject.
+    pub subject: Subject,
+}
+
+
+/// Build the FreeSurfer subject identifier for a participant and optional session, like `sub-01` or `sub-01_ses-02`.
+///
+/// The labels may be given with or without their `sub-` and `ses-` prefixes.
+pub fn bids_subject_id(participant: &str, session: Option<&str>) -> String {
+    let participant = participant.strip_prefix("sub-").unwrap_or(participant);
+    match session {
+        Some(session) => format!("sub-{}_ses-{}", participant, session.strip_prefix("ses-").unwrap_or(session)),
+        None => format!("sub-{}", participant),
+    }
+}
+
+
+/// Split a FreeSurfer subject identifier like `sub-01_ses-02` into the participant and session labels.
+///
+/// Returns `None` if the identifier does not start with `sub-`, e.g., for template subjects like `fsaverage`.
+pub fn parse_bids_subject_id(subject_id: &str) -> Option<(String, Option<String>)> {
+    let label = subject_id.strip_prefix("sub-")?;
+    let (participant, session) = match label.find("_ses-") {
+        Some(pos) => (&label[..pos], Some(label[pos + 5..].to_string())),
+        None => (label, None),
+    };
+    if participant.is_empty() || session.as_deref() == Some("") {
+        return None;
+    }
+    Some((participant.to_string(), session))
+}
+
+
+impl BidsDataset {
+    /// Create a new dataset with the given root directory. This does not access the file system.
+    pub fn new<P: AsRef<Path>>(root: P) -> BidsDataset {
+        BidsDataset { root: root.as_ref().to_path_buf() }
+    }
+
+
+    /// Find the directory with the FreeSurfer derivatives, i.e., the SUBJECTS_DIR, see [`FREESURFER_DERIVATIVES_DIRS`].
+    ///
+    /// Returns `None` if none of the directories exists.
+    pub fn freesurfer_dir(&self) -> Option<PathBuf> {
+        FREESURFER_DERIVATIVES_DIRS.iter().map(|dir| self.root.join(dir)).find(|dir| dir.is_dir())
+    }
+
+
+    /// Get the FreeSurfer subject of a participant and optional session.
+    ///
+    /// # Parameters
+    ///
+    /// * `participant`: the participant label, with or without the `sub-` prefix.
+    /// * `session`: the session label, with or without the `ses-` prefix. Use `None` for datasets without sessions.
+    ///
+    /// # Return value
+    ///
+    /// The subject, or [`NeuroformatsError::BidsSubjectNotFound`] if the dataset contains no FreeSurfer derivatives or the subject directory does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::hemi::Hemisphere;
+    /// use neuroformats::subject::SurfaceKind;
+    /// let dataset = neuroformats::bids::BidsDataset::new("/path/to/bids_dataset");
+    /// let subject = dataset.subject("01", Some("02")).unwrap();
+    /// let white = neuroformats::read_surf(subject.surf_path(Hemisphere::Left, SurfaceKind::White)).unwrap();
+    /// ```
+    pub fn subject(&self, participant: &str, session: Option<&str>) -> Result<Subject> {
+        let subject_id = bids_subject_id(participant, session);
+        match self.freesurfer_dir() {
+            Some(dir) if dir.join(&subject_id).is_dir() => Ok(Subject::new(dir, &subject_id)),
+            _ => Err(NeuroformatsError::BidsSubjectNotFound(subject_id)),
+        }
+    }
+
+
+    /// Get all FreeSurfer subjects of the dataset, sorted by participant and session.
+    ///
+    /// Directories in the FreeSurfer derivatives which are not named after a participant, like `fsaverage`, are ignored. The result is empty
+    /// if the dataset contains no FreeSurfer derivatives.
+    pub fn subjects(&self) -> Result<Vec<BidsSubject>> {
+        let dir = match self.freesurfer_dir() {
+            Some(dir) => dir,
+            None => return Ok(Vec::new()),
+        };
+        let mut subjects = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+            if let Some(subject_id) = entry.file_name().to_str() {
+                if let Some((participant, session)) = parse_bids_subject_id(subject_id) {
+                    subjects.push(BidsSubject { participant, session, subject: Subject::new(&dir, subject_id) });
+                }
+            }
+        }
+        subjects.sort_by(|a, b| (&a.participant, &a.session).cmp(&(&b.participant, &b.session)));
+        Ok(subjects)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn bids_subject_ids_can_be_built_and_parsed() {
+        assert_eq!("sub-01", bids_subject_id("01", None));
+        assert_eq!("sub-01_ses-02", bids_subject_id("sub-01", Some("ses-02")));
+        assert_eq!(Some((String::from("01"), Some(String::from("02")))), parse_bids_subject_id("sub-01_ses-02"));
+        assert_eq!(Some((String::from("01"), None)), parse_bids_subject_id("sub-01"));
+        assert_eq!(None, parse_bids_subject_id("fsaverage"));
+        assert_eq!(None, parse_bids_subject_id("sub-"));
+    }
+
+    #[test]
+    fn freesurfer_subjects_can_be_found_in_a_bids_dataset() {
+        let root = tempdir().unwrap();
+        let dataset = BidsDataset::new(root.path());
+        assert_eq!(None, dataset.freesurfer_dir());
+        assert!(dataset.subjects().unwrap().is_empty());
+
+        let fs_dir = root.path().join("derivatives/fmriprep/sourcedata/freesurfer");
+        for subject_id in ["sub-02", "sub-01_ses-02", "sub-01_ses-01", "fsaverage"] {
+            std::fs::create_dir_all(fs_dir.join(subject_id)).unwrap();
+        }
+        assert_eq!(Some(fs_dir.clone()), dataset.freesurfer_dir());
+
+        let subject = dataset.subject("sub-01", Some("01")).unwrap();
+        assert_eq!(fs_dir.join("sub-01_ses-01"), subject.dir());
+        assert!(matches!(dataset.subject("03", None), Err(NeuroformatsError::BidsSubjectNotFound(_))));
+
+        let subjects = dataset.subjects().unwrap();
+        let ids: Vec<&str> = subjects.iter().map(|s| s.subject.subject_id.as_str()).collect();
+        assert_eq!(vec!["sub-01_ses-01", "sub-01_ses-02", "sub-02"], ids);
+        assert_eq!(None, subjects[2].session);
+    }
+}
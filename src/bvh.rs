@@ -0,0 +1,441 @@
+//! A bounding volume hierarchy over the faces of brain meshes.
+//!
+//! The hierarchy speeds up geometric queries against the surface itself, as opposed to its vertices (see [`crate::spatial`]):
+//! ray intersection for picking (see [`crate::raycast`]), the closest point on the surface, e.g., for thickness estimation, and
+//! the faces intersecting an axis-aligned box, e.g., a voxel when mapping between volumes and surfaces.
+
+
+use std::cmp::Ordering;
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::{vec3_sub, vec3_dot, vec3_cross};
+
+
+/// The maximal number of faces in a leaf node of the bounding volume hierarchy.
+const MAX_LEAF_FACES: usize = 4;
+
+
+/// A node of the bounding volume hierarchy. Leaves have a positive `count` of faces starting at `start` in the face order,
+/// inner nodes have their children at `start` and `start + 1`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BvhNode {
+    pub(crate) min: [f32; 3],
+    pub(crate) max: [f32; 3],
+    pub(crate) start: usize,
+    pub(crate) count: usize,
+}
+
+
+/// The point on a mesh closest to a query point, see [`MeshBvh::closest_point`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosestPoint {
+    /// The index of the face containing the closest point.
+    pub face: usize,
+    /// The barycentric coordinates of the closest point with respect to the three vertices of the face, in the order of the face definition.
+    pub barycentric: [f32; 3],
+    /// The closest point.
+    pub point: [f32; 3],
+    /// The index of the vertex of the face closest to the point, i.e., the one with the largest barycentric coordinate.
+    pub vertex: usize,
+    /// The Euclidean distance from the query point to the closest point.
+    pub distance: f32,
+}
+
+
+/// A bounding volume hierarchy over the faces of a [`BrainMesh`].
+///
+/// Use [`BrainMesh::build_bvh`] to create one. Building the hierarchy takes about as long as a few hundred brute-force queries over all
+/// faces, so it pays off for repeated queries. The hierarchy stores a copy of the face coordinates and does not borrow the mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshBvh {
+    pub(crate) triangles: Vec<[[f32; 3]; 3]>,
+    pub(crate) faces: Vec<[usize; 3]>,
+    pub(crate) order: Vec<usize>,
+    pub(crate) nodes: Vec<BvhNode>,
+}
+
+
+impl MeshBvh {
+    /// Build a bounding volume hierarchy over the faces of the mesh.
+    pub fn new(mesh: &BrainMesh) -> MeshBvh {
+        let faces: Vec<[usize; 3]> = mesh.faces.chunks_exact(3).map(|f| [f[0] as usize, f[1] as usize, f[2] as usize]).collect();
+        let triangles: Vec<[[f32; 3]; 3]> = faces.iter().map(|f| f.map(|v| mesh.vertex_coords(v))).collect();
+        let mut bvh = MeshBvh { order: (0..faces.len()).collect(), triangles, faces, nodes: Vec::new() };
+        if !bvh.faces.is_empty() {
+            bvh.nodes.push(BvhNode { min: [0.0; 3], max: [0.0; 3], start: 0, count: 0 });
+            bvh.build(0, 0, bvh.faces.len());
+        }
+        bvh
+    }
+
+
+    /// Get the number of faces in the hierarchy.
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+
+    /// Whether the hierarchy contains no faces.
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+
+    /// Find the point on the faces of the mesh closest to the given point.
+    ///
+    /// Unlike [`crate::spatial::VertexIndex::nearest_vertex`], this also finds points inside faces and on edges, so the distance does not
+    /// depend on the resolution of the mesh.
+    ///
+    /// # Return value
+    ///
+    /// The closest point, or `None` if the hierarchy is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let pial = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+    /// let bvh = pial.mesh.build_bvh();
+    /// let closest = bvh.closest_point([-40.0, 10.0, 30.0]).unwrap();
+    /// println!("The pial surface is {} mm away, closest to vertex {}.", closest.distance, closest.vertex);
+    /// ```
+    pub fn closest_point(&self, point: [f32; 3]) -> Option<ClosestPoint> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut best: Option<(usize, [f32; 3], f32)> = None;
+        let mut stack = vec![0];
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            let best_dist_sq = best.map(|b| b.2).unwrap_or(f32::INFINITY);
+            if box_squared_distance(point, node.min, node.max) > best_dist_sq {
+                continue;
+            }
+            if node.count > 0 {
+                for &face_idx in self.order[node.start..node.start + node.count].iter() {
+                    let barycentric = closest_point_on_triangle(point, &self.triangles[face_idx]);
+                    let closest = self.barycentric_to_point(face_idx, barycentric);
+                    let dist_sq = vec3_dot(vec3_sub(closest, point), vec3_sub(closest, point));
+                    if dist_sq < best.map(|b| b.2).unwrap_or(f32::INFINITY) {
+                        best = Some((face_idx, barycentric, dist_sq));
+                    }
+                }
+            } else {
+                // Visit the nearer child first, so that the farther one can often be pruned.
+                let (left, right) = (node.start, node.start + 1);
+                let left_dist = box_squared_distance(point, self.nodes[left].min, self.nodes[left].max);
+                let right_dist = box_squared_distance(point, self.nodes[right].min, self.nodes[right].max);
+                if left_dist < right_dist {
+                    stack.push(right);
+                    stack.push(left);
+                } else {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+
+        best.map(|(face, barycentric, dist_sq)| ClosestPoint {
+            face,
+            barycentric,
+            point: self.barycentric_to_point(face, barycentric),
+            vertex: self.closest_corner(face, barycentric),
+            distance: dist_sq.sqrt(),
+        })
+    }
+
+
+    /// Whether any face of the mesh intersects the axis-aligned box with the given corners, e.g., a voxel.
+    pub fn intersects_aabb(&self, min: [f32; 3], max: [f32; 3]) -> bool {
+        let mut found = false;
+        self.visit_aabb(min, max, &mut |_| { found = true; false });
+        found
+    }
+
+
+    /// Find all faces of the mesh which intersect the axis-aligned box with the given corners.
+    ///
+    /// The returned face indices are sorted in ascending order.
+    pub fn faces_intersecting_aabb(&self, min: [f32; 3], max: [f32; 3]) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.visit_aabb(min, max, &mut |face_idx| { found.push(face_idx); true });
+        found.sort_unstable();
+        found
+    }
+
+
+    /// Call `visit` for the faces intersecting the box until it returns false.
+    fn visit_aabb<F: FnMut(usize) -> bool>(&self, min: [f32; 3], max: [f32; 3], visit: &mut F) {
+        if self.is_empty() {
+            return;
+        }
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, (min[2] + max[2]) / 2.0];
+        let half_extents = [(max[0] - min[0]) / 2.0, (max[1] - min[1]) / 2.0, (max[2] - min[2]) / 2.0];
+        let mut stack = vec![0];
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            if (0..3).any(|axis| node.min[axis] > max[axis] || node.max[axis] < min[axis]) {
+                continue;
+            }
+            if node.count > 0 {
+                for &face_idx in self.order[node.start..node.start + node.count].iter() {
+                    if triangle_intersects_box(&self.triangles[face_idx], center, half_extents) && !visit(face_idx) {
+                        return;
+                    }
+                }
+            } else {
+                stack.push(node.start);
+                stack.push(node.start + 1);
+            }
+        }
+    }
+
+
+    /// Get the point with the given barycentric coordinates in the face.
+    pub(crate) fn barycentric_to_point(&self, face_idx: usize, barycentric: [f32; 3]) -> [f32; 3] {
+        let [a, b, c] = self.triangles[face_idx];
+        [0, 1, 2].map(|axis| barycentric[0] * a[axis] + barycentric[1] * b[axis] + barycentric[2] * c[axis])
+    }
+
+
+    /// Get the mesh vertex index of the corner of the face with the largest barycentric coordinate.
+    pub(crate) fn closest_corner(&self, face_idx: usize, barycentric: [f32; 3]) -> usize {
+        let corner = (0..3).max_by(|a, b| barycentric[*a].partial_cmp(&barycentric[*b]).unwrap_or(Ordering::Equal)).unwrap();
+        self.faces[face_idx][corner]
+    }
+
+
+    /// Compute the bounds of the faces `order[start..end]` for the node, and split it into children if it has too many faces.
+    fn build(&mut self, node_idx: usize, start: usize, end: usize) {
+        let (mut min, mut max) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+        for &face_idx in self.order[start..end].iter() {
+            for corner in self.triangles[face_idx].iter() {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(corner[axis]);
+                    max[axis] = max[axis].max(corner[axis]);
+                }
+            }
+        }
+        self.nodes[node_idx] = BvhNode { min, max, start, count: end - start };
+        if end - start <= MAX_LEAF_FACES {
+            return;
+        }
+
+        let axis = (0..3).max_by(|a, b| (max[*a] - min[*a]).partial_cmp(&(max[*b] - min[*b])).unwrap_or(Ordering::Equal)).unwrap();
+        let triangles = &self.triangles;
+        let centroid = |face_idx: usize| triangles[face_idx].iter().map(|c| c[axis]).sum::<f32>();
+        let mid = (end - start) / 2;
+        self.order[start..end].select_nth_unstable_by(mid, |a, b| centroid(*a).partial_cmp(&centroid(*b)).unwrap_or(Ordering::Equal));
+
+        let left = self.nodes.len();
+        self.nodes.push(BvhNode { min, max, start: 0, count: 0 });
+        self.nodes.push(BvhNode { min, max, start: 0, count: 0 });
+        self.nodes[node_idx].start = left;
+        self.nodes[node_idx].count = 0;
+        self.build(left, start, start + mid);
+        self.build(left + 1, start + mid, end);
+    }
+}
+
+
+/// Interpolate per-vertex data at arbitrary points, e.g., electrode positions.
+///
+/// Each point is projected onto the closest point on the surface, see [`MeshBvh::closest_point`], and the data of the three vertices of the
+/// face containing it is interpolated with barycentric weights. Use [`MeshBvh::closest_point`] to check how far the points are from the surface.
+///
+/// # Parameters
+///
+/// * `mesh`: the mesh, in the same coordinate system as the points, e.g., surface RAS for FreeSurfer surfaces.
+/// * `data`: the per-vertex data, one value per vertex of the mesh.
+/// * `coords`: the coordinates of the points, as a flat vector of x,y,z triplets, like [`BrainMesh::vertices`].
+///
+/// # Return value
+///
+/// One interpolated value per point, or [`NeuroformatsError::VertexCountMismatch`] if the length of `data` does not match the vertex count of the mesh.
+/// All values are NaN if the mesh has no faces.
+///
+/// # Examples
+///
+/// ```no_run
+/// let pial = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+/// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let electrodes = vec![-45.2, 12.1, 30.5, -50.0, -20.3, 8.9];
+/// let values = neuroformats::bvh::interpolate_at_points(&pial.mesh, &thickness.data, &electrodes).unwrap();
+/// ```
+pub fn interpolate_at_points(mesh: &BrainMesh, data: &[f32], coords: &[f32]) -> Result<Vec<f32>> {
+    if data.len() != mesh.num_vertices() {
+        return Err(NeuroformatsError::VertexCountMismatch(mesh.num_vertices(), data.len()));
+    }
+    let bvh = mesh.build_bvh();
+    Ok(coords.chunks_exact(3).map(|c| match bvh.closest_point([c[0], c[1], c[2]]) {
+        Some(closest) => bvh.faces[closest.face].iter().zip(closest.barycentric.iter())
+            .filter(|(_, weight)| **weight > 0.0)
+            .map(|(vidx, weight)| weight * data[*vidx])
+            .sum(),
+        None => f32::NAN,
+    }).collect())
+}
+
+
+/// Compute the squared distance from the point to the axis-aligned box, which is `0.0` for points inside the box.
+fn box_squared_distance(point: [f32; 3], min: [f32; 3], max: [f32; 3]) -> f32 {
+    (0..3).map(|axis| (min[axis] - point[axis]).max(point[axis] - max[axis]).max(0.0).powi(2)).sum()
+}
+
+
+/// Compute the barycentric coordinates of the point on the triangle closest to the given point.
+///
+/// This follows the region-based algorithm from Ericson, Real-Time Collision Detection, section 5.1.5.
+fn closest_point_on_triangle(p: [f32; 3], triangle: &[[f32; 3]; 3]) -> [f32; 3] {
+    let [a, b, c] = *triangle;
+    let (ab, ac, ap) = (vec3_sub(b, a), vec3_sub(c, a), vec3_sub(p, a));
+    let (d1, d2) = (vec3_dot(ab, ap), vec3_dot(ac, ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return [1.0, 0.0, 0.0];
+    }
+    let bp = vec3_sub(p, b);
+    let (d3, d4) = (vec3_dot(ab, bp), vec3_dot(ac, bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return [0.0, 1.0, 0.0];
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return [1.0 - v, v, 0.0];
+    }
+    let cp = vec3_sub(p, c);
+    let (d5, d6) = (vec3_dot(ab, cp), vec3_dot(ac, cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return [0.0, 0.0, 1.0];
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return [1.0 - w, 0.0, w];
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return [0.0, 1.0 - w, w];
+    }
+    let denom = va + vb + vc;
+    if denom == 0.0 {
+        // A degenerate triangle, use its first corner.
+        return [1.0, 0.0, 0.0];
+    }
+    let (v, w) = (vb / denom, vc / denom);
+    [1.0 - v - w, v, w]
+}
+
+
+/// Whether the triangle intersects the axis-aligned box with the given center and half extents, using the separating axis theorem.
+fn triangle_intersects_box(triangle: &[[f32; 3]; 3], center: [f32; 3], half_extents: [f32; 3]) -> bool {
+    let v = triangle.map(|corner| vec3_sub(corner, center));
+    let edges = [vec3_sub(v[1], v[0]), vec3_sub(v[2], v[1]), vec3_sub(v[0], v[2])];
+    let box_axes = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    let mut axes: Vec<[f32; 3]> = box_axes.to_vec();
+    axes.push(vec3_cross(edges[0], edges[1]));
+    for edge in edges.iter() {
+        for box_axis in box_axes.iter() {
+            axes.push(vec3_cross(*edge, *box_axis));
+        }
+    }
+    axes.iter().all(|axis| {
+        let projections = v.map(|corner| vec3_dot(corner, *axis));
+        let radius: f32 = (0..3).map(|i| half_extents[i] * axis[i].abs()).sum();
+        let (min, max) = projections.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), p| (lo.min(*p), hi.max(*p)));
+        // Axes from parallel edges are zero and never separate.
+        !(min > radius || max < -radius)
+    })
+}
+
+
+impl BrainMesh {
+    /// Build a bounding volume hierarchy over the faces of this mesh, see [`MeshBvh`].
+    pub fn build_bvh(&self) -> MeshBvh {
+        MeshBvh::new(self)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    #[cfg(feature = "fs")]
+    use crate::read_surf;
+
+    fn triangle() -> BrainMesh {
+        BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2] }
+    }
+
+    #[test]
+    fn the_closest_point_on_a_triangle_can_be_found() {
+        let bvh = triangle().build_bvh();
+        let above = bvh.closest_point([0.2, 0.3, 2.0]).unwrap();
+        assert_eq!([0.2, 0.3, 0.0], above.point);
+        assert_abs_diff_eq!(2.0, above.distance, epsilon = 1e-6);
+        assert_abs_diff_eq!(0.5, above.barycentric[0], epsilon = 1e-6);
+
+        let beyond_edge = bvh.closest_point([1.0, 1.0, 0.0]).unwrap();
+        assert_abs_diff_eq!(0.5, beyond_edge.point[0], epsilon = 1e-6);
+        assert_abs_diff_eq!(0.5, beyond_edge.point[1], epsilon = 1e-6);
+
+        let beyond_corner = bvh.closest_point([-1.0, -1.0, 1.0]).unwrap();
+        assert_eq!([0.0, 0.0, 0.0], beyond_corner.point);
+        assert_eq!(0, beyond_corner.vertex);
+        assert!(BrainMesh { vertices: vec![], faces: vec![] }.build_bvh().closest_point([0.0; 3]).is_none());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_closest_point_on_a_brain_surface_matches_brute_force() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let bvh = surf.mesh.build_bvh();
+        for point in vec![[-30.0, 0.0, 10.0], [-40.0, -20.0, 40.0], [100.0, 100.0, 100.0]].into_iter() {
+            let brute_force = bvh.triangles.iter()
+                .map(|tri| { let closest = closest_point_on_triangle(point, tri); let p = [0, 1, 2].map(|a| closest[0] * tri[0][a] + closest[1] * tri[1][a] + closest[2] * tri[2][a]); vec3_dot(vec3_sub(p, point), vec3_sub(p, point)).sqrt() })
+                .fold(f32::INFINITY, f32::min);
+            let closest = bvh.closest_point(point).unwrap();
+            assert_abs_diff_eq!(brute_force, closest.distance, epsilon = 1e-4);
+            let (_, nearest_vertex_dist) = surf.mesh.build_spatial_index().nearest_vertex(point).unwrap();
+            assert!(closest.distance <= nearest_vertex_dist + 1e-4);
+        }
+    }
+
+    #[test]
+    fn data_can_be_interpolated_at_arbitrary_points() {
+        let mesh = triangle();
+        let data = vec![1.0, 2.0, 4.0];
+        let values = interpolate_at_points(&mesh, &data, &[0.0, 0.0, 1.0, 0.5, 0.0, -1.0, 0.25, 0.25, 0.0, 5.0, 5.0, 0.0]).unwrap();
+        assert_abs_diff_eq!(1.0, values[0], epsilon = 1e-6);
+        assert_abs_diff_eq!(1.5, values[1], epsilon = 1e-6);
+        assert_abs_diff_eq!(0.5 * 1.0 + 0.25 * 2.0 + 0.25 * 4.0, values[2], epsilon = 1e-6);
+        assert_abs_diff_eq!(3.0, values[3], epsilon = 1e-6);
+
+        // Vertices with zero weight do not propagate NaN values.
+        assert_eq!(vec![1.0], interpolate_at_points(&mesh, &[1.0, f32::NAN, 2.0], &[0.0, 0.0, 0.0]).unwrap());
+        assert!(interpolate_at_points(&mesh, &[1.0], &[0.0; 3]).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn faces_intersecting_a_box_can_be_found() {
+        let bvh = triangle().build_bvh();
+        assert!(bvh.intersects_aabb([0.1, 0.1, -0.1], [0.2, 0.2, 0.1]));
+        assert!(!bvh.intersects_aabb([0.1, 0.1, 0.1], [0.2, 0.2, 0.2]));
+        // The box overlaps the bounding box of the triangle, but lies beyond its hypotenuse.
+        assert!(!bvh.intersects_aabb([0.7, 0.7, -0.1], [0.9, 0.9, 0.1]));
+        // The triangle crosses the box without any corner inside it.
+        assert!(bvh.intersects_aabb([0.4, 0.4, -0.1], [0.6, 0.6, 0.1]));
+
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let bvh = surf.mesh.build_bvh();
+        let vertex = surf.mesh.vertex_coords(0);
+        let faces = bvh.faces_intersecting_aabb(vertex.map(|c| c - 0.1), vertex.map(|c| c + 0.1));
+        let faces_of_vertex: Vec<usize> = surf.mesh.faces.chunks_exact(3).enumerate().filter(|(_, f)| f.contains(&0)).map(|(i, _)| i).collect();
+        assert!(faces_of_vertex.iter().all(|f| faces.contains(f)));
+        assert!(!bvh.intersects_aabb([500.0; 3], [501.0; 3]));
+    }
+}
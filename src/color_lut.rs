@@ -0,0 +1,142 @@
+//! Functions for reading FreeSurfer color lookup tables, like the `FreeSurferColorLUT.txt` file in the FreeSurfer home directory.
+//!
+//! A color lookup table assigns names and display colors to the integer labels of segmentation volumes, like `aseg.mgz`.
+//! Each line of the file contains the label, the name and the red, green, blue and alpha values of a region. Empty lines
+//! and comments starting with `#` are ignored.
+
+
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::{NeuroformatsError, Result};
+use crate::util::gunzip_if_compressed;
+#[cfg(feature = "fs")]
+use crate::util::open_maybe_compressed;
+
+
+/// Models an entry of a color lookup table.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorLutEntry {
+    pub label: i32,
+    pub name: String,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+/// Models a color lookup table, in the order of the entries in the file.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorLut {
+    pub entries: Vec<ColorLutEntry>,
+}
+
+
+/// Read a color lookup table from a file in FreeSurfer format.
+///
+/// # Examples
+///
+/// ```no_run
+/// let lut = neuroformats::read_color_lut("/path/to/freesurfer/FreeSurferColorLUT.txt").unwrap();
+/// assert_eq!(Some("Left-Hippocampus"), lut.name(17));
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_color_lut<P: AsRef<Path>>(path: P) -> Result<ColorLut> {
+    ColorLut::from_file(path)
+}
+
+
+impl ColorLut {
+    /// Read a color lookup table from a file in FreeSurfer format, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ColorLut> {
+        ColorLut::from_reader(open_maybe_compressed(path)?)
+    }
+
+
+    /// Read a color lookup table from the bytes of a file, which may be gzip-compressed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ColorLut> {
+        ColorLut::from_reader(&gunzip_if_compressed(bytes)?[..])
+    }
+
+
+    /// Read a color lookup table from the given text input in FreeSurfer format.
+    pub fn from_reader<S: BufRead>(input: S) -> Result<ColorLut> {
+        let mut entries = Vec::new();
+        for line in input.lines() {
+            let line = line?;
+            let content = line.split('#').next().unwrap_or("").trim();
+            if content.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = content.split_whitespace().collect();
+            if fields.len() != 6 {
+                return Err(NeuroformatsError::InvalidColorLutFormat);
+            }
+            let color = |idx: usize| fields[idx].parse::<u8>().map_err(|_| NeuroformatsError::InvalidColorLutFormat);
+            entries.push(ColorLutEntry {
+                label: fields[0].parse::<i32>().map_err(|_| NeuroformatsError::InvalidColorLutFormat)?,
+                name: fields[1].to_string(),
+                r: color(2)?,
+                g: color(3)?,
+                b: color(4)?,
+                a: color(5)?,
+            });
+        }
+        Ok(ColorLut { entries })
+    }
+
+
+    /// Get the entry for the given label, if any.
+    pub fn entry(&self, label: i32) -> Option<&ColorLutEntry> {
+        self.entries.iter().find(|entry| entry.label == label)
+    }
+
+
+    /// Get the name of the region with the given label, if any.
+    pub fn name(&self, label: i32) -> Option<&str> {
+        self.entry(label).map(|entry| entry.name.as_str())
+    }
+
+
+    /// Get a map from the labels to the entries, for fast lookups of many labels.
+    pub fn by_label(&self) -> HashMap<i32, &ColorLutEntry> {
+        self.entries.iter().map(|entry| (entry.label, entry)).collect()
+    }
+}
+
+
+impl fmt::Display for ColorLut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Color lookup table with {} entries.", self.entries.len())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TINY_LUT: &str = "#$Id: FreeSurferColorLUT.txt\n\n#No. Label Name:                R   G   B   A\n\n0   Unknown                     0   0   0   0\n17  Left-Hippocampus            220 216 20  0   # comment\n53  Right-Hippocampus           220 216 20  0\n";
+
+    #[test]
+    fn a_color_lut_can_be_read() {
+        let lut = ColorLut::from_bytes(TINY_LUT.as_bytes()).unwrap();
+        assert_eq!(3, lut.entries.len());
+        assert_eq!(Some("Left-Hippocampus"), lut.name(17));
+        assert_eq!(216, lut.entry(53).unwrap().g);
+        assert!(lut.name(18).is_none());
+        assert_eq!(3, lut.by_label().len());
+    }
+
+    #[test]
+    fn reading_an_invalid_color_lut_returns_an_error() {
+        assert!(ColorLut::from_bytes(b"17 Left-Hippocampus 220 216\n").is_err());
+        assert!(ColorLut::from_bytes(b"17 Left-Hippocampus 220 216 300 0\n").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+//! Mapping of per-vertex data to colors, e.g., to color a mesh by cortical thickness for export with [`BrainMesh::to_gltf`](crate::BrainMesh::to_gltf).
+//!
+//! The values are mapped to the viridis colormap. The gradient is computed once and cached as a lookup table with
+//! 256 entries, so mapping is cheap even when called repeatedly for large meshes, e.g., for every frame of an animation.
+//! With the optional `rayon` feature, parallel variants of the mapping functions are available. RGBA colors, e.g., for
+//! formats like glTF that require them, can be created with [`values_to_colors_rgba`], which can also make NaN values and
+//! masked vertices like the medial wall transparent.
+
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::fs_label::FsLabel;
+use crate::util::splitmix64;
+
+
+/// The number of entries in the colormap lookup table.
+pub const COLORMAP_SIZE: usize = 256;
+
+/// The RGB color assigned to NaN values.
+pub const NAN_COLOR: [u8; 3] = [128, 128, 128];
+
+/// Evenly spaced control points of the viridis colormap, from low to high values.
+const VIRIDIS_STOPS: [[u8; 3]; 9] = [
+    [0x44, 0x01, 0x54], [0x47, 0x2D, 0x7B], [0x3B, 0x52, 0x8B], [0x2C, 0x72, 0x8E], [0x21, 0x90, 0x8C],
+    [0x27, 0xAD, 0x81], [0x5D, 0xC8, 0x63], [0xAA, 0xDC, 0x32], [0xFD, 0xE7, 0x25],
+];
+
+
+/// Get the cached viridis lookup table, computing it on first use by linear interpolation between the control points.
+pub fn viridis() -> &'static [[u8; 3]; COLORMAP_SIZE] {
+    static LUT: OnceLock<[[u8; 3]; COLORMAP_SIZE]> = OnceLock::new();
+    LUT.get_or_init(|| {
+        let mut lut = [[0u8; 3]; COLORMAP_SIZE];
+        let segments = (VIRIDIS_STOPS.len() - 1) as f32;
+        for (idx, color) in lut.iter_mut().enumerate() {
+            let pos = idx as f32 / (COLORMAP_SIZE - 1) as f32 * segments;
+            let lower = (pos.floor() as usize).min(VIRIDIS_STOPS.len() - 2);
+            let frac = pos - lower as f32;
+            for channel in 0..3 {
+                let (a, b) = (VIRIDIS_STOPS[lower][channel] as f32, VIRIDIS_STOPS[lower + 1][channel] as f32);
+                color[channel] = (a + (b - a) * frac).round() as u8;
+            }
+        }
+        lut
+    })
+}
+
+
+/// Map a single value to a color from the lookup table. Values outside of [`min`, `max`] are clamped.
+#[inline]
+fn value_to_color(lut: &[[u8; 3]; COLORMAP_SIZE], value: f32, min: f32, scale: f32) -> [u8; 3] {
+    lut_index(value, min, scale).map_or(NAN_COLOR, |idx| lut[idx])
+}
+
+
+/// Map a single value to an index into the lookup table, or `None` for NaN values. Values outside of [`min`, `max`] are clamped.
+#[inline]
+fn lut_index(value: f32, min: f32, scale: f32) -> Option<usize> {
+    if value.is_nan() {
+        return None;
+    }
+    Some(((value - min) * scale).clamp(0.0, (COLORMAP_SIZE - 1) as f32).round() as usize)
+}
+
+
+/// Map values to indices into the lookup table, like [`values_to_colors`] does. NaN values are mapped to `None`.
+#[cfg(feature = "image")]
+pub(crate) fn values_to_lut_indices(values: &[f32], min: f32, max: f32) -> Vec<Option<usize>> {
+    let scale = index_scale(min, max);
+    values.iter().map(|value| lut_index(*value, min, scale)).collect()
+}
+
+
+/// Compute the factor that maps the range [`min`, `max`] to the lookup table indices. A range of width zero maps all values to the first color.
+fn index_scale(min: f32, max: f32) -> f32 {
+    let range = max - min;
+    if range > 0.0 { (COLORMAP_SIZE - 1) as f32 / range } else { 0.0 }
+}
+
+
+/// Map per-vertex values to RGB colors using the viridis colormap.
+///
+/// # Parameters
+///
+/// * `values`: the values to map, e.g., the `data` of an [`FsCurv`](crate::FsCurv).
+/// * `min`: the value mapped to the lowest color. Smaller values are clamped.
+/// * `max`: the value mapped to the highest color. Larger values are clamped.
+///
+/// # Return value
+///
+/// The colors, 3 values (R, G, B) per input value. NaN values get the [`NAN_COLOR`].
+///
+/// # Examples
+///
+/// ```no_run
+/// let curv = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let (min, max) = neuroformats::vec32minmax(curv.data.iter().cloned(), true);
+/// let colors = neuroformats::colormap::values_to_colors(&curv.data, min, max);
+/// ```
+pub fn values_to_colors(values: &[f32], min: f32, max: f32) -> Vec<u8> {
+    let mut colors = vec![0u8; values.len() * 3];
+    values_to_colors_into(values, min, max, &mut colors);
+    colors
+}
+
+
+/// Map per-vertex values to RGB colors using the viridis colormap, writing into the given buffer.
+///
+/// This avoids allocating a new vector on every call. See [`values_to_colors`] for details.
+///
+/// # Panics
+///
+/// If the length of `colors` is not 3 times the length of `values`.
+pub fn values_to_colors_into(values: &[f32], min: f32, max: f32, colors: &mut [u8]) {
+    assert_eq!(values.len() * 3, colors.len(), "Expected a color buffer with 3 values per input value.");
+    let lut = viridis();
+    let scale = index_scale(min, max);
+    for (value, color) in values.iter().zip(colors.chunks_exact_mut(3)) {
+        color.copy_from_slice(&value_to_color(lut, *value, min, scale));
+    }
+}
+
+
+/// Options for mapping values to RGBA colors with [`values_to_colors_rgba_with_options`].
+///
+/// # Examples
+///
+/// ```no_run
+/// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let cortex = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.cortex.label").unwrap();
+/// let mask = cortex.is_surface_vertex_in_label(thickness.data.len());
+/// let opts = neuroformats::colormap::RgbaOptions { mask: Some(&mask), ..Default::default() };
+/// let colors = neuroformats::colormap::values_to_colors_rgba_with_options(&thickness.data, 1.0, 4.0, &opts);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaOptions<'a> {
+    /// The alpha value of the colors of visible vertices.
+    pub alpha: u8,
+    /// Whether NaN values are fully transparent. If `false`, they get the [`NAN_COLOR`] with the regular `alpha`.
+    pub nan_transparent: bool,
+    /// Optional visibility mask with one value per vertex. Vertices for which the mask is `false` are fully transparent, e.g., the medial wall when the mask is computed from a cortex label with [`FsLabel::is_surface_vertex_in_label`](crate::FsLabel::is_surface_vertex_in_label).
+    pub mask: Option<&'a [bool]>,
+    /// Optional cortex label, like `label/?h.cortex.label`. Vertices outside of it, i.e., the medial wall, are fully transparent.
+    pub exclude_medial_wall: Option<&'a FsLabel>,
+}
+
+
+impl Default for RgbaOptions<'_> {
+    fn default() -> Self {
+        RgbaOptions {
+            alpha: 255,
+            nan_transparent: true,
+            mask: None,
+            exclude_medial_wall: None,
+        }
+    }
+}
+
+
+/// Map per-vertex values to RGBA colors using the viridis colormap.
+///
+/// All colors get the given `alpha` value, including those of NaN values. Use [`values_to_colors_rgba_with_options`] to make NaN values or masked vertices transparent.
+///
+/// # Return value
+///
+/// The colors, 4 values (R, G, B, A) per input value. See [`values_to_colors`] for details on the mapping.
+pub fn values_to_colors_rgba(values: &[f32], min: f32, max: f32, alpha: u8) -> Vec<u8> {
+    values_to_colors_rgba_with_options(values, min, max, &RgbaOptions { alpha, nan_transparent: false, mask: None, exclude_medial_wall: None })
+}
+
+
+/// Map per-vertex values to RGBA colors using the viridis colormap and the given [`RgbaOptions`].
+///
+/// Transparent vertices get an alpha value of `0`, their RGB values are kept.
+///
+/// # Panics
+///
+/// If a mask is given in the options and its length differs from the length of `values`.
+pub fn values_to_colors_rgba_with_options(values: &[f32], min: f32, max: f32, options: &RgbaOptions) -> Vec<u8> {
+    if let Some(mask) = options.mask {
+        assert_eq!(values.len(), mask.len(), "Expected one mask value per input value.");
+    }
+    let in_cortex = options.exclude_medial_wall.map(|cortex| cortex.vertex_mask(values.len()));
+    let lut = viridis();
+    let scale = index_scale(min, max);
+    let mut colors = Vec::with_capacity(values.len() * 4);
+    for (idx, value) in values.iter().enumerate() {
+        let visible = !(options.nan_transparent && value.is_nan()) && options.mask.is_none_or(|mask| mask[idx])
+            && in_cortex.as_ref().is_none_or(|in_cortex| in_cortex[idx]);
+        colors.extend_from_slice(&value_to_color(lut, *value, min, scale));
+        colors.push(if visible { options.alpha } else { 0 });
+    }
+    colors
+}
+
+
+/// Convert RGB colors with 3 values per vertex to RGBA colors with 4 values per vertex, using the given alpha value for all vertices.
+///
+/// # Panics
+///
+/// If the length of `colors` is not a multiple of 3.
+pub fn rgb_to_rgba(colors: &[u8], alpha: u8) -> Vec<u8> {
+    assert_eq!(0, colors.len() % 3, "Expected 3 color values per vertex.");
+    colors.chunks_exact(3).flat_map(|c| [c[0], c[1], c[2], alpha]).collect()
+}
+
+
+/// Generate clearly distinguishable RGB colors, e.g., for the regions of a colortable built from an integer label map.
+///
+/// The hues of consecutive colors differ by the golden angle, so each new color has a hue far from those of all previous colors, and
+/// the saturation and brightness vary between colors as well. The colors are deterministic: the same `seed` always gives the same colors.
+///
+/// All colors differ from each other and from the colors in `exclude`, and so do their labels `r + g * 256 + b * 65536`, which identify
+/// the regions of annot files, see [`crate::fs_annot::FsAnnotColorRegion::color_label`].
+///
+/// # Parameters
+///
+/// * `n`: the number of colors.
+/// * `seed`: selects the hue of the first color, so different seeds give different colors.
+/// * `exclude`: colors which must not be generated, e.g., colors which are already in use in a colortable.
+///
+/// # Panics
+///
+/// If `n` exceeds the number of RGB colors which are not excluded.
+///
+/// # Examples
+///
+/// ```
+/// let colors = neuroformats::colormap::distinct_colors(10, 42, &[[0, 0, 0]]);
+/// assert_eq!(10, colors.len());
+/// assert_eq!(colors, neuroformats::colormap::distinct_colors(10, 42, &[[0, 0, 0]]));
+/// ```
+pub fn distinct_colors(n: usize, seed: u64, exclude: &[[u8; 3]]) -> Vec<[u8; 3]> {
+    let color_label = |c: [u8; 3]| c[0] as u32 | (c[1] as u32) << 8 | (c[2] as u32) << 16;
+    let mut used: HashSet<u32> = exclude.iter().map(|c| color_label(*c)).collect();
+    assert!(n <= (1 << 24) - used.len(), "Cannot generate {} distinct colors.", n);
+    let hue_offset = (splitmix64(seed) >> 11) as f64 / (1u64 << 53) as f64 * 360.0;
+
+    // The rounding to 8 bit values limits the number of distinct colors in the sequence, so for very many colors, the remaining
+    // colors are taken in order of their labels, starting at a position given by the seed.
+    let max_attempts = 16 * n + 1024;
+    let mut fallback = splitmix64(seed.wrapping_add(1)) as u32;
+    let mut colors = Vec::with_capacity(n);
+    let mut idx = 0;
+    while colors.len() < n {
+        let color = if idx < max_attempts {
+            golden_color(idx, hue_offset)
+        } else {
+            fallback = fallback.wrapping_add(1);
+            let label = fallback & 0xFF_FFFF;
+            [label as u8, (label >> 8) as u8, (label >> 16) as u8]
+        };
+        idx += 1;
+        if used.insert(color_label(color)) {
+            colors.push(color);
+        }
+    }
+    colors
+}
+
+
+/// Get the color with the given index in the golden angle sequence of [`distinct_colors`].
+fn golden_color(idx: usize, hue_offset: f64) -> [u8; 3] {
+    let hue = (hue_offset + idx as f64 * 137.507_764) % 360.0;
+    let saturation = [0.75, 0.55, 0.95][(idx / 3) % 3];
+    let value = [0.9, 0.7, 0.8][(idx / 9) % 3];
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as usize {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [r, g, b].map(|c| ((c + m) * 255.0).round() as u8)
+}
+
+
+/// Map per-vertex values to RGB colors using the viridis colormap, in parallel.
+///
+/// Requires the `rayon` feature. See [`values_to_colors`] for details.
+#[cfg(feature = "rayon")]
+pub fn values_to_colors_par(values: &[f32], min: f32, max: f32) -> Vec<u8> {
+    let mut colors = vec![0u8; values.len() * 3];
+    values_to_colors_into_par(values, min, max, &mut colors);
+    colors
+}
+
+
+/// Map per-vertex values to RGB colors using the viridis colormap in parallel, writing into the given buffer.
+///
+/// Requires the `rayon` feature. See [`values_to_colors_into`] for details.
+///
+/// # Panics
+///
+/// If the length of `colors` is not 3 times the length of `values`.
+#[cfg(feature = "rayon")]
+pub fn values_to_colors_into_par(values: &[f32], min: f32, max: f32, colors: &mut [u8]) {
+    use rayon::prelude::*;
+    assert_eq!(values.len() * 3, colors.len(), "Expected a color buffer with 3 values per input value.");
+    let lut = viridis();
+    let scale = index_scale(min, max);
+    values.par_iter().zip(colors.par_chunks_exact_mut(3)).for_each(|(value, color)| {
+        color.copy_from_slice(&value_to_color(lut, *value, min, scale));
+    });
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_viridis_lookup_table_starts_and_ends_at_the_control_points() {
+        let lut = viridis();
+        assert_eq!(VIRIDIS_STOPS[0], lut[0]);
+        assert_eq!(VIRIDIS_STOPS[8], lut[COLORMAP_SIZE - 1]);
+    }
+
+    #[test]
+    fn values_can_be_mapped_to_colors() {
+        let values = [0.0, 1.0, 2.0, -5.0, f32::NAN];
+        let colors = values_to_colors(&values, 0.0, 2.0);
+
+        assert_eq!(15, colors.len());
+        assert_eq!(&VIRIDIS_STOPS[0], &colors[0..3]);
+        assert_eq!(&viridis()[128], &colors[3..6]);
+        assert_eq!(&VIRIDIS_STOPS[8], &colors[6..9]);
+        assert_eq!(&VIRIDIS_STOPS[0], &colors[9..12]);
+        assert_eq!(&NAN_COLOR, &colors[12..15]);
+    }
+
+    #[test]
+    fn values_can_be_mapped_to_colors_in_a_given_buffer() {
+        let mut colors = vec![0u8; 6];
+        values_to_colors_into(&[3.0, 3.0], 3.0, 3.0, &mut colors);
+        assert_eq!(vec![0x44, 0x01, 0x54, 0x44, 0x01, 0x54], colors);
+    }
+
+    #[test]
+    #[should_panic]
+    fn mapping_values_to_a_buffer_of_the_wrong_size_panics() {
+        values_to_colors_into(&[1.0, 2.0], 0.0, 1.0, &mut [0u8; 3]);
+    }
+
+    #[test]
+    fn values_can_be_mapped_to_rgba_colors() {
+        let colors = values_to_colors_rgba(&[0.0, f32::NAN], 0.0, 1.0, 200);
+        assert_eq!(vec![0x44, 0x01, 0x54, 200, 128, 128, 128, 200], colors);
+    }
+
+    #[test]
+    fn nan_values_and_masked_vertices_can_be_transparent() {
+        let mask = [true, true, false];
+        let opts = RgbaOptions { mask: Some(&mask), ..Default::default() };
+        let colors = values_to_colors_rgba_with_options(&[1.0, f32::NAN, 1.0], 0.0, 1.0, &opts);
+
+        assert_eq!(12, colors.len());
+        assert_eq!(255, colors[3]);
+        assert_eq!(0, colors[7]);
+        assert_eq!(0, colors[11]);
+        assert_eq!(&VIRIDIS_STOPS[8], &colors[8..11]);
+    }
+
+    #[test]
+    fn the_medial_wall_can_be_transparent() {
+        let cortex = FsLabel::from_bytes(b"#!ascii label\n2\n0 0.0 0.0 0.0 0.0\n2 0.0 0.0 0.0 0.0\n").unwrap();
+        let opts = RgbaOptions { exclude_medial_wall: Some(&cortex), ..Default::default() };
+        let colors = values_to_colors_rgba_with_options(&[1.0, 1.0, 1.0], 0.0, 1.0, &opts);
+        assert_eq!(vec![255, 0, 255], vec![colors[3], colors[7], colors[11]]);
+    }
+
+    #[test]
+    fn rgb_colors_can_be_converted_to_rgba() {
+        assert_eq!(vec![1, 2, 3, 255, 4, 5, 6, 255], rgb_to_rgba(&[1, 2, 3, 4, 5, 6], 255));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn the_parallel_mapping_gives_the_same_colors() {
+        let values: Vec<f32> = (0..1000).map(|v| v as f32 / 10.0).collect();
+        assert_eq!(values_to_colors(&values, 10.0, 90.0), values_to_colors_par(&values, 10.0, 90.0));
+    }
+
+    #[test]
+    fn distinct_colors_are_deterministic_and_unique() {
+        let colors = distinct_colors(300, 7, &[]);
+        assert_eq!(colors, distinct_colors(300, 7, &[]));
+        assert_ne!(colors, distinct_colors(300, 8, &[]));
+        let excluded = distinct_colors(10, 7, &[colors[3]]);
+        assert!(!excluded.contains(&colors[3]));
+        assert_eq!(colors[..3], excluded[..3]);
+
+        let many = distinct_colors(50000, 0, &[]);
+        let mut labels: Vec<u32> = many.iter().map(|c| c[0] as u32 + c[1] as u32 * 256 + c[2] as u32 * 65536).collect();
+        labels.sort_unstable();
+        labels.dedup();
+        assert_eq!(50000, labels.len());
+    }
+}
@@ -0,0 +1,363 @@
+//! Batch conversion of all surfaces and per-vertex data files in a directory tree, e.g., a subjects dir, to common exchange formats.
+//!
+//! This is intended for dataset publication pipelines: [`convert_tree`] converts every FreeSurfer surface to glTF or PLY and every
+//! curv file to CSV or MGH, mirroring the directory structure of the input in the output directory, and reports what was converted.
+
+
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::fs_curv::read_curv;
+use crate::fs_mgh::{write_mgh, FsMgh, FsMghHeader};
+use crate::fs_surface::read_surf;
+use crate::gltf::write_gltf;
+use crate::util::open_maybe_compressed;
+
+
+/// The magic number at the start of FreeSurfer surf files.
+const SURF_MAGIC: [u8; 3] = [255, 255, 254];
+
+/// The magic number at the start of FreeSurfer curv files.
+const CURV_MAGIC: [u8; 3] = [255, 255, 255];
+
+
+/// An output format for the surfaces found by [`convert_tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// Binary glTF 2.0 files with the extension `.glb`, see [`crate::BrainMesh::to_gltf`].
+    Gltf,
+    /// Binary PLY files with the extension `.ply`, see [`crate::BrainMesh::to_ply`].
+    Ply,
+}
+
+
+/// An output format for the curv files found by [`convert_tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayFormat {
+    /// CSV files with the extension `.csv`, see [`crate::FsCurv::write_csv`].
+    Csv,
+    /// MGH files with the extension `.mgh`, with dimensions `num_vertices x 1 x 1 x 1` like those written by `mri_surf2surf`.
+    Mgh,
+}
+
+
+/// The output formats used by [`convert_tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// The formats to convert surfaces to. Surfaces are not converted if this is empty. Defaults to glTF.
+    pub mesh_formats: Vec<MeshFormat>,
+    /// The formats to convert curv files to. Curv files are not converted if this is empty. Defaults to CSV.
+    pub overlay_formats: Vec<OverlayFormat>,
+}
+
+
+impl Default for ConvertOptions {
+    fn default() -> ConvertOptions {
+        ConvertOptions { mesh_formats: vec![MeshFormat::Gltf], overlay_formats: vec![OverlayFormat::Csv] }
+    }
+}
+
+
+/// A file converted by [`convert_tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertedFile {
+    /// The input file.
+    pub input: PathBuf,
+    /// The files written for it, in the order of the formats in the [`ConvertOptions`].
+    pub outputs: Vec<PathBuf>,
+}
+
+
+/// A summary of a batch conversion, see [`convert_tree`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConvertReport {
+    /// The converted files, sorted by path.
+    pub converted: Vec<ConvertedFile>,
+    /// The files whose magic number could not be read, and the surfaces and curv files which could not be read or converted, with the error message.
+    pub failed: Vec<(PathBuf, String)>,
+    /// The number of files which are neither surfaces nor curv files, or for which no output format was requested.
+    pub num_skipped: usize,
+}
+
+
+impl ConvertReport {
+    /// Whether all surfaces and curv files were converted.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+
+    /// Get the total number of files written.
+    pub fn num_outputs(&self) -> usize {
+        self.converted.iter().map(|c| c.outputs.len()).sum()
+    }
+}
+
+
+impl fmt::Display for ConvertReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Converted {} files to {} outputs, skipped {} files, {} failed.", self.converted.len(), self.num_outputs(), self.num_skipped, self.failed.len())?;
+        for (path, error) in self.failed.iter() {
+            writeln!(f, "  failed: {}: {}", path.display(), error)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Convert all FreeSurfer surfaces and curv files in a directory tree to the formats given in the options.
+///
+/// The directory tree is searched recursively, and surfaces and curv files are recognized by their magic number, so their names do not
+/// matter. Gzip-compressed files are recognized by their content as well. Symbolic links to directories are not followed. The outputs are written to the same relative path in the output directory, with the extension of the format appended,
+/// e.g., `subject1/surf/lh.white` is converted to `subject1/surf/lh.white.glb`. Missing directories are created. The list of files is
+/// built before any output is written, so the output directory may be inside of the input directory.
+///
+/// # Parameters
+///
+/// * `input_dir`: the directory to search, e.g., a subjects dir.
+/// * `output_dir`: the directory to write the outputs to.
+/// * `options`: the output formats.
+///
+/// # Return value
+///
+/// A report of the converted, failed and skipped files. Files which cannot be read or written do not stop the conversion, they are
+/// listed in [`ConvertReport::failed`]. This includes files which cannot be opened or are too short to contain a magic number. Only
+/// files with an unknown magic number are skipped. Returns an error only if the directory tree cannot be searched.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::convert::{convert_tree, ConvertOptions, MeshFormat, OverlayFormat};
+/// let options = ConvertOptions { mesh_formats: vec![MeshFormat::Gltf, MeshFormat::Ply], overlay_formats: vec![OverlayFormat::Mgh] };
+/// let report = convert_tree("/path/to/subjects_dir", "/tmp/published", &options).unwrap();
+/// print!("{}", report);
+/// ```
+pub fn convert_tree<P: AsRef<Path>, Q: AsRef<Path>>(input_dir: P, output_dir: Q, options: &ConvertOptions) -> Result<ConvertReport> {
+    let files = files_in_tree(input_dir.as_ref())?;
+    let results: Vec<Option<std::result::Result<ConvertedFile, String>>> = files.iter()
+        .map(|file| convert_file(input_dir.as_ref(), output_dir.as_ref(), file, options))
+        .collect();
+    Ok(collect_report(input_dir.as_ref(), &files, results))
+}
+
+
+/// Convert all FreeSurfer surfaces and curv files in a directory tree in parallel.
+///
+/// Requires the `rayon` feature. The files are converted on the rayon thread pool, and the result is the same as for [`convert_tree`].
+#[cfg(feature = "rayon")]
+pub fn convert_tree_parallel<P: AsRef<Path>, Q: AsRef<Path>>(input_dir: P, output_dir: Q, options: &ConvertOptions) -> Result<ConvertReport> {
+    use rayon::prelude::*;
+    let (input_dir, output_dir) = (input_dir.as_ref(), output_dir.as_ref());
+    let files = files_in_tree(input_dir)?;
+    let results: Vec<Option<std::result::Result<ConvertedFile, String>>> = files.par_iter()
+        .map(|file| convert_file(input_dir, output_dir, file, options))
+        .collect();
+    Ok(collect_report(input_dir, &files, results))
+}
+
+
+/// Get the paths of all files in a directory tree, relative to its root and sorted. Symbolic links to directories are left out.
+fn files_in_tree(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(rel_dir) = dirs.pop() {
+        for entry in std::fs::read_dir(root.join(&rel_dir))? {
+            let entry = entry?;
+            let rel_path = rel_dir.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(rel_path);
+            } else if !(file_type.is_symlink() && entry.path().is_dir()) {
+                files.push(rel_path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+
+/// Build the report from the conversion results of the files, where `None` marks a skipped file.
+fn collect_report(input_dir: &Path, files: &[PathBuf], results: Vec<Option<std::result::Result<ConvertedFile, String>>>) -> ConvertReport {
+    let mut report = ConvertReport::default();
+    for (file, result) in files.iter().zip(results) {
+        match result {
+            Some(Ok(converted)) => report.converted.push(converted),
+            Some(Err(error)) => report.failed.push((input_dir.join(file), error)),
+            None => report.num_skipped += 1,
+        }
+    }
+    report
+}
+
+
+/// Convert a single file, given by its path relative to the input directory. Returns `None` if the file is skipped.
+fn convert_file(input_dir: &Path, output_dir: &Path, rel_path: &Path, options: &ConvertOptions) -> Option<std::result::Result<ConvertedFile, String>> {
+    let input = input_dir.join(rel_path);
+    let magic = match read_magic(&input) {
+        Ok(magic) => magic,
+        Err(error) => return Some(Err(error.to_string())),
+    };
+    let is_surf = magic == SURF_MAGIC;
+    let requested = match magic {
+        SURF_MAGIC => !options.mesh_formats.is_empty(),
+        CURV_MAGIC => !options.overlay_formats.is_empty(),
+        _ => false,
+    };
+    if !requested {
+        return None;
+    }
+    let output_base = output_dir.join(rel_path);
+    let output = |extension: &str| -> PathBuf {
+        let mut name = output_base.clone().into_os_string();
+        name.push(extension);
+        PathBuf::from(name)
+    };
+
+    let convert = || -> Result<Vec<PathBuf>> {
+        let mut outputs = Vec::new();
+        if let Some(parent) = output_base.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if is_surf {
+            let mesh = read_surf(&input)?.mesh;
+            for format in options.mesh_formats.iter() {
+                let path = match format {
+                    MeshFormat::Gltf => {
+                        let path = output(".glb");
+                        write_gltf(&path, &mesh, None)?;
+                        path
+                    }
+                    MeshFormat::Ply => {
+                        let path = output(".ply");
+                        std::fs::write(&path, mesh.to_ply(None)?)?;
+                        path
+                    }
+                };
+                outputs.push(path);
+            }
+        } else {
+            let curv = read_curv(&input)?;
+            for format in options.overlay_formats.iter() {
+                let path = match format {
+                    OverlayFormat::Csv => {
+                        let path = output(".csv");
+                        curv.to_csv(&path)?;
+                        path
+                    }
+                    OverlayFormat::Mgh => {
+                        let path = output(".mgh");
+                        let header = FsMghHeader { dim1len: curv.data.len() as i32, dim2len: 1, dim3len: 1, dim4len: 1, delta: [1.0; 3], ..FsMghHeader::default() };
+                        write_mgh(&path, &FsMgh::from_f32_values(&header, curv.data.clone())?)?;
+                        path
+                    }
+                };
+                outputs.push(path);
+            }
+        }
+        Ok(outputs)
+    };
+    Some(convert().map(|outputs| ConvertedFile { input, outputs }).map_err(|e| e.to_string()))
+}
+
+
+/// Read the magic number at the start of a file, which may be gzip-compressed.
+fn read_magic(path: &Path) -> Result<[u8; 3]> {
+    let mut magic = [0u8; 3];
+    open_maybe_compressed(path)?.read_exact(&mut magic)?;
+    Ok(magic)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+    use crate::{read_mgh, FsSurface};
+
+    #[test]
+    fn the_surfaces_and_curv_files_of_a_subjects_dir_can_be_converted() {
+        let dir = tempdir().unwrap();
+        let output_dir = dir.path();
+        let options = ConvertOptions { mesh_formats: vec![MeshFormat::Gltf, MeshFormat::Ply], overlay_formats: vec![OverlayFormat::Csv, OverlayFormat::Mgh] };
+        let report = convert_tree("resources/subjects_dir", output_dir, &options).unwrap();
+
+        assert!(report.is_success(), "{}", report);
+        let inputs: Vec<PathBuf> = report.converted.iter().map(|c| c.input.strip_prefix("resources/subjects_dir").unwrap().to_path_buf()).collect();
+        let surf = |name: &str| Path::new("subject1/surf").join(name);
+        assert_eq!(vec![surf("lh.thickness"), surf("lh.tinysurface"), surf("lh.white"), surf("rh.white")], inputs);
+        assert_eq!(8, report.num_outputs());
+        assert!(report.num_skipped > 0);
+
+        let white_glb = output_dir.join("subject1/surf/lh.white.glb");
+        assert_eq!(vec![white_glb.clone(), output_dir.join("subject1/surf/lh.white.ply")], report.converted[2].outputs);
+        assert!(std::fs::read(white_glb).unwrap().starts_with(b"glTF"));
+        let thickness = read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        let mgh = read_mgh(output_dir.join("subject1/surf/lh.thickness.mgh")).unwrap();
+        assert_eq!(thickness.data, mgh.data_as_f32().unwrap());
+        assert!(report.to_string().starts_with("Converted 4 files to 8 outputs"));
+    }
+
+    #[test]
+    fn files_which_cannot_be_read_are_reported() {
+        let (input, output) = (tempdir().unwrap(), tempdir().unwrap());
+        let (input_dir, output_dir) = (input.path(), output.path());
+        std::fs::create_dir_all(input_dir.join("surf")).unwrap();
+        std::fs::write(input_dir.join("surf/lh.broken"), [255, 255, 254, 0]).unwrap();
+        std::fs::write(input_dir.join("notes.txt"), "not a surface").unwrap();
+        let header = crate::FsSurfaceHeader { num_vertices: 3, num_faces: 1, ..Default::default() };
+        let mesh = crate::BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2] };
+        crate::write_surf(input_dir.join("surf/lh.tri"), &FsSurface { header, mesh, tags: Vec::new() }).unwrap();
+
+        let report = convert_tree(input_dir, output_dir, &ConvertOptions { mesh_formats: vec![MeshFormat::Ply], overlay_formats: Vec::new() }).unwrap();
+        assert_eq!(1, report.converted.len());
+        assert!(output_dir.join("surf/lh.tri.ply").is_file());
+        assert_eq!(vec![input_dir.join("surf/lh.broken")], report.failed.iter().map(|f| f.0.clone()).collect::<Vec<_>>());
+        assert_eq!(1, report.num_skipped);
+        assert!(!report.is_success());
+        assert!(convert_tree(input_dir.join("missing"), output_dir, &ConvertOptions::default()).is_err());
+
+        #[cfg(feature = "rayon")]
+        assert_eq!(report, convert_tree_parallel(input_dir, output_dir, &ConvertOptions { mesh_formats: vec![MeshFormat::Ply], overlay_formats: Vec::new() }).unwrap());
+    }
+
+    #[test]
+    fn compressed_files_are_converted_and_linked_directories_are_not_followed() {
+        let (input, output) = (tempdir().unwrap(), tempdir().unwrap());
+        let (input_dir, output_dir) = (input.path(), output.path());
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&std::fs::read("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap()).unwrap();
+        std::fs::write(input_dir.join("lh.tinysurface.gz"), encoder.finish().unwrap()).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(input_dir, input_dir.join("loop")).unwrap();
+
+        let report = convert_tree(input_dir, output_dir, &ConvertOptions { mesh_formats: vec![MeshFormat::Ply], overlay_formats: Vec::new() }).unwrap();
+        assert!(report.is_success(), "{}", report);
+        assert_eq!(vec![output_dir.join("lh.tinysurface.gz.ply")], report.converted[0].outputs);
+        assert_eq!(1, report.converted.len());
+    }
+
+    #[test]
+    fn truncated_and_unreadable_files_are_reported_instead_of_skipped() {
+        let (input, output) = (tempdir().unwrap(), tempdir().unwrap());
+        let (input_dir, output_dir) = (input.path(), output.path());
+        std::fs::write(input_dir.join("lh.short"), [255, 255]).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&std::fs::read("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap()).unwrap();
+        std::fs::write(input_dir.join("lh.truncated.gz"), &encoder.finish().unwrap()[..12]).unwrap();
+        std::fs::write(input_dir.join("notes.txt"), "not a surface").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(input_dir.join("missing"), input_dir.join("lh.dangling")).unwrap();
+
+        let report = convert_tree(input_dir, output_dir, &ConvertOptions::default()).unwrap();
+        let mut expected = vec![input_dir.join("lh.short"), input_dir.join("lh.truncated.gz")];
+        #[cfg(unix)]
+        expected.insert(0, input_dir.join("lh.dangling"));
+        assert_eq!(expected, report.failed.iter().map(|f| f.0.clone()).collect::<Vec<_>>());
+        assert_eq!(1, report.num_skipped);
+        assert!(report.converted.is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Tagging of mesh coordinates with their coordinate space and unit.
+//!
+//! FreeSurfer surfaces are stored in surface RAS (tkregister) coordinates, while volumes, other software and atlases use scanner RAS
+//! or MNI305 coordinates. Both are in millimeters and look plausible, so mixing them up is a common bug which shifts meshes by a few
+//! centimeters. A [`SpatialMesh`] keeps the space and unit with the mesh, refuses to merge meshes in different spaces, and converts
+//! between the spaces of a subject with [`SpatialMesh::to_space`].
+
+
+use std::fmt;
+
+use ndarray::Array2;
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_surface::{BrainMesh, FsSurface};
+use crate::gltf::GltfOptions;
+use crate::transforms::apply_affine_to_points;
+#[cfg(feature = "fs")]
+use crate::fs_mgh::{affine_inverse, FsMghHeader};
+#[cfg(feature = "fs")]
+use crate::subject::Subject;
+#[cfg(feature = "fs")]
+use crate::transforms::read_xfm;
+
+
+/// The coordinate space of the vertex coordinates of a mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordSpace {
+    /// Surface RAS, also called tkregister RAS, the space of FreeSurfer surfaces. Its origin is the center of the `mri/orig.mgz` volume.
+    SurfaceRas,
+    /// Scanner RAS, the world space of the volumes of a subject, e.g., as used by NIfTI files and most other software.
+    ScannerRas,
+    /// MNI305 space, reached from scanner RAS with `mri/transforms/talairach.xfm`.
+    Mni305,
+    /// The voxel indices (column, row, slice) of the `mri/orig.mgz` volume of a subject.
+    Voxel,
+}
+
+
+impl CoordSpace {
+    /// Get the natural unit of this space: voxels for [`CoordSpace::Voxel`], and millimeters for all others.
+    pub fn default_unit(self) -> CoordUnit {
+        if self == CoordSpace::Voxel { CoordUnit::Voxel } else { CoordUnit::Millimeter }
+    }
+}
+
+
+impl fmt::Display for CoordSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CoordSpace::SurfaceRas => "surface RAS",
+            CoordSpace::ScannerRas => "scanner RAS",
+            CoordSpace::Mni305 => "MNI305",
+            CoordSpace::Voxel => "voxel",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+
+/// The unit of the vertex coordinates of a mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordUnit {
+    /// Millimeters, the unit of FreeSurfer surfaces and of the RAS spaces.
+    Millimeter,
+    /// Meters, the unit of glTF and of most 3D software.
+    Meter,
+    /// Voxel indices, which have no physical unit.
+    Voxel,
+}
+
+
+impl CoordUnit {
+    /// Get the length of this unit in meters, or `None` for [`CoordUnit::Voxel`].
+    pub fn in_meters(self) -> Option<f32> {
+        match self {
+            CoordUnit::Millimeter => Some(0.001),
+            CoordUnit::Meter => Some(1.0),
+            CoordUnit::Voxel => None,
+        }
+    }
+}
+
+
+impl fmt::Display for CoordUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CoordUnit::Millimeter => "mm",
+            CoordUnit::Meter => "m",
+            CoordUnit::Voxel => "voxels",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+
+/// A mesh with the coordinate space and unit of its vertex coordinates.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::coord_space::{CoordSpace, SpatialMesh};
+/// let lh: SpatialMesh = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap().into();
+/// let rh: SpatialMesh = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/rh.white").unwrap().into();
+/// let both = lh.merge(&rh).unwrap();
+/// assert_eq!(CoordSpace::SurfaceRas, both.space);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpatialMesh {
+    /// The mesh.
+    pub mesh: BrainMesh,
+    /// The coordinate space of the vertex coordinates.
+    pub space: CoordSpace,
+    /// The unit of the vertex coordinates.
+    pub unit: CoordUnit,
+}
+
+
+impl SpatialMesh {
+    /// Tag a mesh with the coordinate space and unit of its vertex coordinates.
+    pub fn new(mesh: BrainMesh, space: CoordSpace, unit: CoordUnit) -> SpatialMesh {
+        SpatialMesh { mesh, space, unit }
+    }
+
+
+    /// Check that the other mesh has the same coordinate space and unit as this one, and return [`NeuroformatsError::CoordSpaceMismatch`] otherwise.
+    pub fn check_same_space(&self, other: &SpatialMesh) -> Result<()> {
+        if (self.space, self.unit) != (other.space, other.unit) {
+            return Err(NeuroformatsError::CoordSpaceMismatch(format!("{} ({})", self.space, self.unit), format!("{} ({})", other.space, other.unit)));
+        }
+        Ok(())
+    }
+
+
+    /// Merge this mesh with another one, see [`BrainMesh::merge`].
+    ///
+    /// # Return value
+    ///
+    /// The merged mesh, or [`NeuroformatsError::CoordSpaceMismatch`] if the meshes are not in the same coordinate space and unit.
+    pub fn merge(&self, other: &SpatialMesh) -> Result<SpatialMesh> {
+        self.check_same_space(other)?;
+        Ok(SpatialMesh { mesh: self.mesh.merge(&other.mesh), ..*self })
+    }
+
+
+    /// Apply a 4x4 affine matrix to the vertex coordinates, which maps them to the given space and unit.
+    ///
+    /// The matrix is not checked, so the caller is responsible for it matching the spaces. Use [`SpatialMesh::to_space`] to convert
+    /// between the spaces of a subject.
+    pub fn transform(&self, matrix: &Array2<f32>, space: CoordSpace, unit: CoordUnit) -> SpatialMesh {
+        let mesh = BrainMesh { vertices: apply_affine_to_points(matrix, &self.mesh.vertices), faces: self.mesh.faces.clone() };
+        SpatialMesh { mesh, space, unit }
+    }
+
+
+    /// Scale the vertex coordinates to another unit, e.g., from millimeters to meters.
+    ///
+    /// # Return value
+    ///
+    /// The scaled mesh, or [`NeuroformatsError::CoordSpaceMismatch`] if one of the units is [`CoordUnit::Voxel`] and the other is not,
+    /// as voxel indices cannot be scaled to physical units.
+    pub fn to_unit(&self, unit: CoordUnit) -> Result<SpatialMesh> {
+        if unit == self.unit {
+            return Ok(self.clone());
+        }
+        match (self.unit.in_meters(), unit.in_meters()) {
+            (Some(from), Some(to)) => {
+                let factor = from / to;
+                let mesh = BrainMesh { vertices: self.mesh.vertices.iter().map(|v| v * factor).collect(), faces: self.mesh.faces.clone() };
+                Ok(SpatialMesh { mesh, unit, ..*self })
+            }
+            _ => Err(NeuroformatsError::CoordSpaceMismatch(unit.to_string(), self.unit.to_string())),
+        }
+    }
+
+
+    /// Export this mesh as binary glTF in its real size, see [`BrainMesh::to_gltf_with_options`].
+    ///
+    /// glTF uses meters, so the `scale` of the options is multiplied with the length of the unit of this mesh in meters.
+    ///
+    /// # Return value
+    ///
+    /// The GLB file contents, or [`NeuroformatsError::CoordSpaceMismatch`] if the mesh is in voxel coordinates.
+    pub fn to_gltf_with_options(&self, vertex_colors: Option<&[u8]>, options: &GltfOptions) -> Result<Vec<u8>> {
+        let meters = self.unit.in_meters().ok_or_else(|| NeuroformatsError::CoordSpaceMismatch(CoordUnit::Meter.to_string(), self.unit.to_string()))?;
+        let options = GltfOptions { scale: options.scale * meters, ..options.clone() };
+        self.mesh.to_gltf_with_options(vertex_colors, &options)
+    }
+
+
+    /// Convert this mesh to another coordinate space of a subject, like from surface RAS to scanner RAS.
+    ///
+    /// The spaces are related by the geometry of `mri/orig.mgz` and, for [`CoordSpace::Mni305`], by `mri/transforms/talairach.xfm`
+    /// of the subject, see [`Subject::vertex_mni_coords`]. Meshes in meters are converted to millimeters first, and the result is in
+    /// the default unit of the target space, see [`CoordSpace::default_unit`].
+    ///
+    /// # Return value
+    ///
+    /// The converted mesh, or an error if the files of the subject cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::coord_space::{CoordSpace, SpatialMesh};
+    /// use neuroformats::subject::Subject;
+    /// let subject = Subject::new("/path/to/subjects_dir", "subject1");
+    /// let white: SpatialMesh = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap().into();
+    /// let scanner = white.to_space(CoordSpace::ScannerRas, &subject).unwrap();
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn to_space(&self, space: CoordSpace, subject: &Subject) -> Result<SpatialMesh> {
+        let source = if self.unit == CoordUnit::Meter { self.to_unit(CoordUnit::Millimeter)? } else { self.clone() };
+        if space == source.space {
+            return Ok(source);
+        }
+        let to_scanner = |space: CoordSpace| -> Result<Array2<f32>> {
+            let orig = || FsMghHeader::from_file(subject.dir().join("mri").join("orig.mgz"));
+            match space {
+                CoordSpace::SurfaceRas => subject.tkr_to_scanner_ras(),
+                CoordSpace::ScannerRas => Ok(Array2::eye(4)),
+                CoordSpace::Mni305 => Ok(affine_inverse(&read_xfm(subject.dir().join("mri").join("transforms").join("talairach.xfm"))?.matrix)),
+                CoordSpace::Voxel => orig()?.vox2ras(),
+            }
+        };
+        let matrix = affine_inverse(&to_scanner(space)?).dot(&to_scanner(source.space)?);
+        Ok(source.transform(&matrix, space, space.default_unit()))
+    }
+}
+
+
+impl From<FsSurface> for SpatialMesh {
+    /// Tag the mesh of a FreeSurfer surface, which is always in surface RAS coordinates in millimeters.
+    fn from(surf: FsSurface) -> SpatialMesh {
+        SpatialMesh { mesh: surf.mesh, space: CoordSpace::SurfaceRas, unit: CoordUnit::Millimeter }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn triangle(space: CoordSpace, unit: CoordUnit) -> SpatialMesh {
+        SpatialMesh::new(BrainMesh { vertices: vec![0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 0.0, 20.0, 0.0], faces: vec![0, 1, 2] }, space, unit)
+    }
+
+    #[test]
+    fn meshes_in_different_spaces_cannot_be_merged() {
+        let tkr = triangle(CoordSpace::SurfaceRas, CoordUnit::Millimeter);
+        let merged = tkr.merge(&tkr).unwrap();
+        assert_eq!((6, CoordSpace::SurfaceRas), (merged.mesh.num_vertices(), merged.space));
+
+        let scanner = triangle(CoordSpace::ScannerRas, CoordUnit::Millimeter);
+        let err = tkr.merge(&scanner).unwrap_err();
+        assert_eq!("Expected coordinates in surface RAS (mm), but found scanner RAS (mm).", err.to_string());
+        assert!(tkr.merge(&triangle(CoordSpace::SurfaceRas, CoordUnit::Meter)).is_err());
+    }
+
+    #[test]
+    fn meshes_can_be_transformed_and_scaled() {
+        let tkr = triangle(CoordSpace::SurfaceRas, CoordUnit::Millimeter);
+        let mut matrix = Array2::eye(4);
+        matrix[[0, 3]] = 5.0;
+        let scanner = tkr.transform(&matrix, CoordSpace::ScannerRas, CoordUnit::Millimeter);
+        assert_eq!((CoordSpace::ScannerRas, 15.0), (scanner.space, scanner.mesh.vertices[3]));
+
+        let meters = tkr.to_unit(CoordUnit::Meter).unwrap();
+        assert_eq!((CoordSpace::SurfaceRas, CoordUnit::Meter), (meters.space, meters.unit));
+        assert_abs_diff_eq!(0.02, meters.mesh.vertices[7], epsilon = 1e-7);
+        assert_abs_diff_eq!(20.0, meters.to_unit(CoordUnit::Millimeter).unwrap().mesh.vertices[7], epsilon = 1e-4);
+        assert!(tkr.to_unit(CoordUnit::Voxel).is_err());
+
+        let glb = tkr.to_gltf_with_options(None, &GltfOptions::default()).unwrap();
+        assert_eq!(tkr.mesh.to_gltf_with_options(None, &GltfOptions { scale: 0.001, ..Default::default() }).unwrap(), glb);
+        assert!(triangle(CoordSpace::Voxel, CoordUnit::Voxel).to_gltf_with_options(None, &GltfOptions::default()).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn surfaces_can_be_converted_between_the_spaces_of_a_subject() {
+        // The demo subject has no orig.mgz, so we use the brain.mgz, which has the same geometry.
+        let subjects_dir = tempfile::tempdir().unwrap();
+        let subject = Subject::new(subjects_dir.path(), "subject1");
+        std::fs::create_dir_all(subject.dir().join("mri").join("transforms")).unwrap();
+        let src_dir = std::path::Path::new("resources/subjects_dir/subject1");
+        std::fs::copy(src_dir.join("mri/brain.mgz"), subject.dir().join("mri/orig.mgz")).unwrap();
+        std::fs::copy(src_dir.join("mri/transforms/talairach.xfm"), subject.dir().join("mri/transforms/talairach.xfm")).unwrap();
+
+        let white: SpatialMesh = crate::read_surf(src_dir.join("surf/lh.white")).unwrap().into();
+        let scanner = white.to_space(CoordSpace::ScannerRas, &subject).unwrap();
+        assert_eq!((CoordSpace::ScannerRas, CoordUnit::Millimeter), (scanner.space, scanner.unit));
+        assert_abs_diff_eq!(white.mesh.vertices[0] - 0.49995422, scanner.mesh.vertices[0], epsilon = 1e-3);
+
+        let mni305 = white.to_unit(CoordUnit::Meter).unwrap().to_space(CoordSpace::Mni305, &subject).unwrap();
+        let back = mni305.to_space(CoordSpace::SurfaceRas, &subject).unwrap();
+        for (expected, found) in white.mesh.vertices.iter().zip(back.mesh.vertices.iter()).take(30) {
+            assert_abs_diff_eq!(expected, found, epsilon = 1e-2);
+        }
+
+        let voxels = scanner.to_space(CoordSpace::Voxel, &subject).unwrap();
+        assert_eq!(CoordUnit::Voxel, voxels.unit);
+        assert!(voxels.mesh.vertices.iter().all(|v| (0.0..256.0).contains(v)));
+        assert!(white.to_space(CoordSpace::Mni305, &Subject::new(subjects_dir.path(), "missing")).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Cropping and padding of MGH volumes, with updates of the header geometry so that the RAS coordinates of the voxels are kept.
+//!
+//! Cropping a volume to the bounding box of a region of interest, see [`FsMgh::bounding_box`], reduces the memory needed to process it.
+
+
+use ndarray::{Array, Array4, Dim};
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_mgh::{check_shape, FsMgh, FsMghData, FsMghHeader};
+
+
+impl FsMgh {
+    /// Compute the bounding box of all voxels of the first frame which do not have the background value.
+    ///
+    /// # Return value
+    ///
+    /// The minimal and maximal column, row and slice indices of the foreground voxels, both inclusive, or `None` if all voxels have the
+    /// background value or are NaN. Returns an error if the data part matching the `dtype` of the header is not set.
+    pub fn bounding_box(&self, background: f32) -> Result<Option<([usize; 3], [usize; 3])>> {
+        let dim = self.dim();
+        let mut bbox: Option<([usize; 3], [usize; 3])> = None;
+        for (idx, value) in self.frame_as_f32(0)?.iter().enumerate() {
+            if value.is_nan() || *value == background {
+                continue;
+            }
+            let crs = [idx % dim[0], idx / dim[0] % dim[1], idx / (dim[0] * dim[1])];
+            bbox = Some(match bbox {
+                None => (crs, crs),
+                Some((min, max)) => ([0, 1, 2].map(|a| min[a].min(crs[a])), [0, 1, 2].map(|a| max[a].max(crs[a]))),
+            });
+        }
+        Ok(bbox)
+    }
+
+
+    /// Crop the volume to the given box of voxels, keeping all frames.
+    ///
+    /// The header geometry is updated, so that the voxels keep their RAS coordinates.
+    ///
+    /// # Parameters
+    ///
+    /// * `min`: the minimal column, row and slice indices of the box, inclusive.
+    /// * `max`: the maximal column, row and slice indices of the box, inclusive, like the result of [`FsMgh::bounding_box`].
+    ///
+    /// # Return value
+    ///
+    /// The cropped volume. Returns an error if the box is empty or exceeds the volume, or if the data part matching the `dtype` of the header is not set
+    /// or does not match the dimensions of the header.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mgh = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/brain.mgz").unwrap();
+    /// let (min, max) = mgh.bounding_box(0.0).unwrap().expect("Volume is empty");
+    /// let brain_only = mgh.crop(min, max).unwrap();
+    /// ```
+    pub fn crop(&self, min: [usize; 3], max: [usize; 3]) -> Result<FsMgh> {
+        let dim = self.dim();
+        if (0..3).any(|a| min[a] > max[a] || max[a] >= dim[a]) {
+            return Err(NeuroformatsError::InvalidVoxelBox(min, max, dim));
+        }
+        let new_dim = [max[0] - min[0] + 1, max[1] - min[1] + 1, max[2] - min[2] + 1];
+        self.resampled_grid([min[0] as i64, min[1] as i64, min[2] as i64], new_dim, 0.0)
+    }
+
+
+    /// Pad the volume with voxels of the given value on all sides, keeping all frames.
+    ///
+    /// The header geometry is updated, so that the voxels keep their RAS coordinates.
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: the number of voxels to add before the first column, row and slice.
+    /// * `after`: the number of voxels to add after the last column, row and slice.
+    /// * `value`: the value of the new voxels. It is converted to the data type of the volume.
+    ///
+    /// # Return value
+    ///
+    /// The padded volume, or an error if the data part matching the `dtype` of the header is not set or does not match the dimensions of the header.
+    pub fn pad(&self, before: [usize; 3], after: [usize; 3], value: f32) -> Result<FsMgh> {
+        let dim = self.dim();
+        let new_dim = [0, 1, 2].map(|a| dim[a] + before[a] + after[a]);
+        self.resampled_grid([0, 1, 2].map(|a| -(before[a] as i64)), new_dim, value)
+    }
+
+
+    /// Create a volume with the given dimensions, in which voxel `(0, 0, 0)` is at the voxel indices `origin` of this volume.
+    /// Voxels outside of this volume get the `fill` value.
+    fn resampled_grid(&self, origin: [i64; 3], new_dim: [usize; 3], fill: f32) -> Result<FsMgh> {
+        self.check_data()?;
+        let dim = self.dim();
+        let frames = dim[3];
+        let header = &self.header;
+        let source_index = |idx: usize| -> Option<usize> {
+            let (c, r, s, t) = (idx % new_dim[0], idx / new_dim[0] % new_dim[1], idx / (new_dim[0] * new_dim[1]) % new_dim[2], idx / (new_dim[0] * new_dim[1] * new_dim[2]));
+            let crs = [origin[0] + c as i64, origin[1] + r as i64, origin[2] + s as i64];
+            if (0..3).any(|a| crs[a] < 0 || crs[a] >= dim[a] as i64) {
+                return None;
+            }
+            Some(header.voxel_flat_index([crs[0] as usize, crs[1] as usize, crs[2] as usize], t))
+        };
+        let num_new = new_dim[0] * new_dim[1] * new_dim[2] * frames;
+        let vol_dim = Dim([new_dim[0], new_dim[1], new_dim[2], frames]);
+        fn remap<T: Copy>(data: &Option<Array4<T>>, dim: [usize; 4], num_new: usize, vol_dim: Dim<[usize; 4]>, fill: T, source_index: &dyn Fn(usize) -> Option<usize>) -> Result<Option<Array4<T>>> {
+            data.as_ref().map(|d| {
+                check_shape(d, dim)?;
+                let values = d.as_slice().expect("MGH data is contiguous");
+                let new_values: Vec<T> = (0..num_new).map(|idx| source_index(idx).map_or(fill, |src| values[src])).collect();
+                Ok(Array::from_shape_vec(vol_dim, new_values).unwrap())
+            }).transpose()
+        }
+        let data = FsMghData {
+            mri_uchar: remap(&self.data.mri_uchar, dim, num_new, vol_dim, fill as u8, &source_index)?,
+            mri_int: remap(&self.data.mri_int, dim, num_new, vol_dim, fill as i32, &source_index)?,
+            mri_float: remap(&self.data.mri_float, dim, num_new, vol_dim, fill, &source_index)?,
+            mri_short: remap(&self.data.mri_short, dim, num_new, vol_dim, fill as i16, &source_index)?,
+        };
+        Ok(FsMgh { header: self.header_for_grid(origin, new_dim)?, data, footer: self.footer.clone() })
+    }
+
+
+    /// Compute the header of a volume with the given dimensions, in which voxel `(0, 0, 0)` is at the voxel indices `origin` of this volume.
+    fn header_for_grid(&self, origin: [i64; 3], new_dim: [usize; 3]) -> Result<FsMghHeader> {
+        let mut header = self.header.clone();
+        header.dim1len = new_dim[0] as i32;
+        header.dim2len = new_dim[1] as i32;
+        header.dim3len = new_dim[2] as i32;
+        if header.is_ras_good == 1 {
+            // The center voxel of the new volume, in voxel indices of this volume.
+            let center = [0, 1, 2].map(|a| (origin[a] + (new_dim[a] / 2) as i64) as f32);
+            let vox2ras = self.header.vox2ras()?;
+            header.p_xyz_c = [0, 1, 2].map(|i| vox2ras[[i, 0]] * center[0] + vox2ras[[i, 1]] * center[1] + vox2ras[[i, 2]] * center[2] + vox2ras[[i, 3]]);
+        }
+        Ok(header)
+    }
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+    use crate::read_mgh;
+    use ndarray::array;
+
+    fn ras(mgh: &FsMgh, crs: [usize; 3]) -> Vec<f32> {
+        mgh.vox2ras().unwrap().dot(&array![crs[0] as f32, crs[1] as f32, crs[2] as f32, 1.0]).to_vec()
+    }
+
+    #[test]
+    fn a_volume_can_be_cropped_to_its_bounding_box() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let (min, max) = mgh.bounding_box(0.0).unwrap().unwrap();
+        let cropped = mgh.crop(min, max).unwrap();
+
+        assert_eq!([max[0] - min[0] + 1, max[1] - min[1] + 1, max[2] - min[2] + 1, 1], cropped.dim());
+        assert!(cropped.dim()[0] < 256);
+        assert_eq!(mgh.stats(0, Some(0.0)).unwrap(), cropped.stats(0, Some(0.0)).unwrap());
+
+        let (orig, new) = (mgh.data_as_f32().unwrap(), cropped.data_as_f32().unwrap());
+        let crs = [99, 99, 99];
+        let new_crs = [crs[0] - min[0], crs[1] - min[1], crs[2] - min[2]];
+        assert_eq!(orig[mgh.header.voxel_flat_index(crs, 0)], new[cropped.header.voxel_flat_index(new_crs, 0)]);
+
+        for (a, b) in ras(&mgh, crs).iter().zip(ras(&cropped, new_crs).iter()) {
+            assert!((a - b).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn a_volume_can_be_padded() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let small = mgh.crop([90, 90, 90], [109, 104, 99]).unwrap();
+        let padded = small.pad([1, 2, 3], [0, 1, 5], 7.0).unwrap();
+
+        assert_eq!([21, 18, 18, 1], padded.dim());
+        let values = padded.data_as_f32().unwrap();
+        assert_eq!(7.0, values[padded.header.voxel_flat_index([0, 0, 0], 0)]);
+        assert_eq!(7.0, values[padded.header.voxel_flat_index([20, 17, 17], 0)]);
+        assert_eq!(77.0, values[padded.header.voxel_flat_index([10, 11, 12], 0)]);
+
+        for (a, b) in ras(&mgh, [99, 99, 99]).iter().zip(ras(&padded, [10, 11, 12]).iter()) {
+            assert!((a - b).abs() < 1e-3);
+        }
+        assert_eq!(mgh.footer, padded.footer);
+    }
+
+    #[test]
+    fn cropping_to_an_invalid_box_is_an_error() {
+        let mgh = read_mgh("resources/mgh/tiny.mgh").unwrap();
+        assert!(mgh.crop([0, 0, 0], [3, 2, 2]).is_err());
+        assert!(mgh.crop([2, 0, 0], [1, 2, 2]).is_err());
+        assert_eq!([1, 3, 3, 1], mgh.crop([2, 0, 0], [2, 2, 2]).unwrap().dim());
+    }
+
+    #[test]
+    fn padding_a_volume_with_inconsistent_data_is_an_error() {
+        let mut mgh = read_mgh("resources/mgh/tiny.mgh").unwrap();
+        mgh.header.dim1len = 4;
+        assert!(matches!(mgh.pad([1, 1, 1], [1, 1, 1], 0.0), Err(NeuroformatsError::VoxelCountMismatch(36, 27))));
+        mgh.data.mri_int = None;
+        assert!(matches!(mgh.pad([1, 1, 1], [1, 1, 1], 0.0), Err(NeuroformatsError::UnsupportedMriDataTypeInMgh(..))));
+    }
+}
@@ -0,0 +1,153 @@
+//! Functions for computing curvature descriptors from brain meshes.
+//!
+//! This allows generating curv-like per-vertex data for any surface, instead of relying on the precomputed
+//! `?h.curv` files written by FreeSurfer.
+
+
+use std::f32::consts::PI;
+
+use crate::fs_surface::BrainMesh;
+use crate::util::{vec3_sub, vec3_dot, vec3_cross, vec3_norm as norm};
+
+
+/// Per-vertex curvature descriptors of a mesh, see [`BrainMesh::principal_curvatures`].
+///
+/// All vectors contain one value per mesh vertex.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curvatures {
+    /// The mean curvature `H`, which is the mean of the principal curvatures. Positive for convex regions (e.g., a sphere with radius `r` has `H = 1/r`) and negative for concave ones.
+    pub mean: Vec<f32>,
+    /// The Gaussian curvature `K`, which is the product of the principal curvatures.
+    pub gaussian: Vec<f32>,
+    /// The first (larger) principal curvature.
+    pub k1: Vec<f32>,
+    /// The second (smaller) principal curvature.
+    pub k2: Vec<f32>,
+}
+
+
+impl BrainMesh {
+    /// Compute per-vertex mean, Gaussian and principal curvatures of the mesh.
+    ///
+    /// The mean curvature is computed from the cotangent Laplace-Beltrami operator, and the Gaussian curvature from the
+    /// angle deficit at each vertex, both normalized by the barycentric vertex area (one third of the area of all faces around the vertex).
+    /// The principal curvatures are derived from them as `k1,k2 = H +- sqrt(H^2 - K)`. Values at vertices on the mesh boundary
+    /// are not meaningful, and vertices which are not part of any face get curvature `0.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let curvatures = surf.mesh.principal_curvatures();
+    /// let mean_curv_at_vertex_0 = curvatures.mean[0];
+    /// ```
+    pub fn principal_curvatures(&self) -> Curvatures {
+        let nv = self.num_vertices();
+        let mut laplacian: Vec<[f32; 3]> = vec![[0.0; 3]; nv];
+        let mut angle_sum: Vec<f32> = vec![0.0; nv];
+        let mut area: Vec<f32> = vec![0.0; nv];
+
+        for face in self.faces.chunks_exact(3) {
+            let v = [face[0] as usize, face[1] as usize, face[2] as usize];
+            let p = [self.vertex_coords(v[0]), self.vertex_coords(v[1]), self.vertex_coords(v[2])];
+            let face_area = 0.5 * norm(vec3_cross(vec3_sub(p[1], p[0]), vec3_sub(p[2], p[0])));
+            for corner in 0..3 {
+                let (i, j, k) = (corner, (corner + 1) % 3, (corner + 2) % 3);
+                area[v[i]] += face_area / 3.0;
+
+                // The angle at corner i, opposite to edge (j, k).
+                let e1 = vec3_sub(p[j], p[i]);
+                let e2 = vec3_sub(p[k], p[i]);
+                let cos = vec3_dot(e1, e2);
+                let sin = norm(vec3_cross(e1, e2));
+                angle_sum[v[i]] += sin.atan2(cos);
+
+                if sin > 0.0 {
+                    let half_cot = 0.5 * cos / sin;
+                    let edge = vec3_sub(p[k], p[j]);
+                    for axis in 0..3 {
+                        laplacian[v[j]][axis] += half_cot * edge[axis];
+                        laplacian[v[k]][axis] -= half_cot * edge[axis];
+                    }
+                }
+            }
+        }
+
+        let normals = self.vertex_normals();
+        let mut curv = Curvatures { mean: vec![0.0; nv], gaussian: vec![0.0; nv], k1: vec![0.0; nv], k2: vec![0.0; nv] };
+        for vertex in 0..nv {
+            if area[vertex] <= 0.0 {
+                continue;
+            }
+            // The Laplacian of the coordinates is -2 * H * n, so it points inwards for convex regions.
+            let lap = laplacian[vertex];
+            let n = [normals[vertex * 3], normals[vertex * 3 + 1], normals[vertex * 3 + 2]];
+            let h_abs = 0.5 * norm(lap) / area[vertex];
+            let h = if vec3_dot(lap, n) > 0.0 { -h_abs } else { h_abs };
+            let k = (2.0 * PI - angle_sum[vertex]) / area[vertex];
+            let discriminant = (h * h - k).max(0.0).sqrt();
+            curv.mean[vertex] = h;
+            curv.gaussian[vertex] = k;
+            curv.k1[vertex] = h + discriminant;
+            curv.k2[vertex] = h - discriminant;
+        }
+        curv
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Create a UV sphere with the given radius, with faces oriented counter-clockwise when seen from outside.
+    fn uv_sphere(radius: f32, num_lat: usize, num_lon: usize) -> BrainMesh {
+        let mut vertices = vec![0.0, 0.0, radius];
+        for lat in 1..num_lat {
+            let theta = PI * lat as f32 / num_lat as f32;
+            for lon in 0..num_lon {
+                let phi = 2.0 * PI * lon as f32 / num_lon as f32;
+                vertices.extend_from_slice(&[radius * theta.sin() * phi.cos(), radius * theta.sin() * phi.sin(), radius * theta.cos()]);
+            }
+        }
+        vertices.extend_from_slice(&[0.0, 0.0, -radius]);
+        let south = (vertices.len() / 3 - 1) as i32;
+        let ring = |lat: usize, lon: usize| (1 + (lat - 1) * num_lon + lon % num_lon) as i32;
+
+        let mut faces = Vec::new();
+        for lon in 0..num_lon {
+            faces.extend_from_slice(&[0, ring(1, lon), ring(1, lon + 1)]);
+            for lat in 1..num_lat - 1 {
+                faces.extend_from_slice(&[ring(lat, lon), ring(lat + 1, lon), ring(lat + 1, lon + 1)]);
+                faces.extend_from_slice(&[ring(lat, lon), ring(lat + 1, lon + 1), ring(lat, lon + 1)]);
+            }
+            faces.extend_from_slice(&[south, ring(num_lat - 1, lon + 1), ring(num_lat - 1, lon)]);
+        }
+        BrainMesh { vertices, faces }
+    }
+
+    #[test]
+    fn the_curvatures_of_a_sphere_can_be_computed() {
+        let radius = 2.0;
+        let sphere = uv_sphere(radius, 40, 80);
+        let curv = sphere.principal_curvatures();
+        assert_eq!(sphere.num_vertices(), curv.mean.len());
+
+        // Check a vertex at the equator, where the UV sphere is most regular.
+        let vertex = 1 + 19 * 80;
+        assert!((curv.mean[vertex] - 1.0 / radius).abs() < 0.02);
+        assert!((curv.gaussian[vertex] - 1.0 / (radius * radius)).abs() < 0.02);
+        assert!((curv.k1[vertex] - curv.k2[vertex]).abs() < 0.1);
+    }
+
+    #[test]
+    fn the_curvatures_of_a_flat_mesh_are_zero() {
+        let mesh = BrainMesh {
+            vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, -1.0, 0.0],
+            faces: vec![0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 4, 1],
+        };
+        let curv = mesh.principal_curvatures();
+        assert!(curv.mean[0].abs() < 1e-6);
+        assert!(curv.gaussian[0].abs() < 1e-6);
+    }
+}
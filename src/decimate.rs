@@ -0,0 +1,349 @@
+//! Mesh simplification by quadric error edge collapse.
+//!
+//! Full resolution FreeSurfer meshes have about 300,000 faces per hemisphere, which is too heavy for many web viewers. The
+//! functions in this module reduce the face count while preserving the shape of the surface as well as possible.
+
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::{vec3_sub, vec3_dot, vec3_cross, vec3_norm};
+
+
+/// A symmetric 4x4 error quadric, storing the upper triangle row by row.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+
+impl Quadric {
+    /// The quadric of the plane through point `p` with unit normal `n`, weighted by `weight`.
+    fn from_plane(n: [f32; 3], p: [f32; 3], weight: f64) -> Quadric {
+        let d = -vec3_dot(n, p) as f64;
+        let v = [n[0] as f64, n[1] as f64, n[2] as f64, d];
+        let mut q = [0.0; 10];
+        let mut k = 0;
+        for i in 0..4 {
+            for j in i..4 {
+                q[k] = weight * v[i] * v[j];
+                k += 1;
+            }
+        }
+        Quadric(q)
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a += b;
+        }
+    }
+
+    /// The squared distance error of the position `p` under this quadric.
+    fn error(&self, p: [f32; 3]) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p[0] as f64, p[1] as f64, p[2] as f64);
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+
+/// A candidate edge collapse in the priority queue, ordered by ascending cost.
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    /// The versions of both vertices when the collapse was computed, used to discard outdated candidates.
+    versions: (usize, usize),
+}
+
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+
+impl Eq for Collapse {}
+
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+impl Ord for Collapse {
+    // Reversed, so that the max-heap `BinaryHeap` pops the cheapest collapse first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+
+/// The mutable state of a decimation run.
+struct Decimator {
+    coords: Vec<[f32; 3]>,
+    faces: Vec<[usize; 3]>,
+    face_alive: Vec<bool>,
+    /// The faces around each vertex. May contain dead faces, which are skipped.
+    vertex_faces: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<usize>,
+    vertex_alive: Vec<bool>,
+}
+
+
+impl Decimator {
+    fn new(mesh: &BrainMesh) -> Decimator {
+        let coords: Vec<[f32; 3]> = (0..mesh.num_vertices()).map(|i| mesh.vertex_coords(i)).collect();
+        let faces: Vec<[usize; 3]> = mesh.faces.chunks_exact(3).map(|f| [f[0] as usize, f[1] as usize, f[2] as usize]).collect();
+        let mut vertex_faces = vec![Vec::new(); coords.len()];
+        let mut quadrics = vec![Quadric::default(); coords.len()];
+        for (face_idx, face) in faces.iter().enumerate() {
+            let normal = face_normal(&coords, face);
+            let len = vec3_norm(normal);
+            // Weight the plane by the face area, so that tiny faces do not dominate the error.
+            let plane = if len > 0.0 { Quadric::from_plane([normal[0] / len, normal[1] / len, normal[2] / len], coords[face[0]], 0.5 * len as f64) } else { Quadric::default() };
+            for &v in face.iter() {
+                vertex_faces[v].push(face_idx);
+                quadrics[v].add(&plane);
+            }
+        }
+        let num_faces = faces.len();
+        let num_vertices = coords.len();
+        Decimator { coords, faces, face_alive: vec![true; num_faces], vertex_faces, quadrics, versions: vec![0; num_vertices], vertex_alive: vec![true; num_vertices] }
+    }
+
+
+    fn alive_faces_of(&self, vertex: usize) -> impl Iterator<Item = usize> + '_ {
+        self.vertex_faces[vertex].iter().copied().filter(move |&f| self.face_alive[f])
+    }
+
+
+    fn neighbors(&self, vertex: usize) -> Vec<usize> {
+        let mut neighbors: Vec<usize> = self.alive_faces_of(vertex).flat_map(|f| self.faces[f].iter().copied()).filter(|&v| v != vertex).collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+
+    /// Compute the collapse of the edge `(a, b)`, keeping the endpoint or midpoint with the smallest quadric error.
+    fn candidate(&self, a: usize, b: usize) -> (Collapse, [f32; 3]) {
+        let mut q = self.quadrics[a];
+        q.add(&self.quadrics[b]);
+        let (pa, pb) = (self.coords[a], self.coords[b]);
+        let mid = [0.5 * (pa[0] + pb[0]), 0.5 * (pa[1] + pb[1]), 0.5 * (pa[2] + pb[2])];
+        let (pos, cost) = [pa, pb, mid].iter().map(|&p| (p, q.error(p))).min_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(Ordering::Equal)).unwrap();
+        (Collapse { cost: cost.max(0.0), keep: a, remove: b, versions: (self.versions[a], self.versions[b]) }, pos)
+    }
+
+
+    /// Check whether collapsing the edge `(keep, remove)` to `pos` keeps the mesh manifold and does not flip any faces.
+    fn is_valid_collapse(&self, keep: usize, remove: usize, pos: [f32; 3]) -> bool {
+        // The link condition: the common neighbors of the endpoints must be exactly the opposite vertices of the faces sharing the edge.
+        let na = self.neighbors(keep);
+        let nb = self.neighbors(remove);
+        let num_common = na.iter().filter(|v| nb.binary_search(v).is_ok()).count();
+        let num_shared_faces = self.alive_faces_of(keep).filter(|&f| self.faces[f].contains(&remove)).count();
+        if num_common != num_shared_faces {
+            return false;
+        }
+
+        for vertex in [keep, remove] {
+            for f in self.alive_faces_of(vertex) {
+                let face = self.faces[f];
+                if face.contains(&keep) && face.contains(&remove) {
+                    continue;
+                }
+                let before = face_normal(&self.coords, &face);
+                let mut moved = [self.coords[face[0]], self.coords[face[1]], self.coords[face[2]]];
+                for (i, &v) in face.iter().enumerate() {
+                    if v == keep || v == remove {
+                        moved[i] = pos;
+                    }
+                }
+                let after = vec3_cross(vec3_sub(moved[1], moved[0]), vec3_sub(moved[2], moved[0]));
+                if vec3_dot(before, after) <= 0.0 {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+
+    fn collapse(&mut self, keep: usize, remove: usize, pos: [f32; 3]) -> usize {
+        let mut num_removed = 0;
+        let remove_faces = std::mem::take(&mut self.vertex_faces[remove]);
+        for f in remove_faces {
+            if !self.face_alive[f] {
+                continue;
+            }
+            if self.faces[f].contains(&keep) {
+                self.face_alive[f] = false;
+                num_removed += 1;
+            } else {
+                for v in self.faces[f].iter_mut() {
+                    if *v == remove {
+                        *v = keep;
+                    }
+                }
+                self.vertex_faces[keep].push(f);
+            }
+        }
+        let face_alive = &self.face_alive;
+        self.vertex_faces[keep].retain(|&f| face_alive[f]);
+        self.coords[keep] = pos;
+        let q = self.quadrics[remove];
+        self.quadrics[keep].add(&q);
+        self.vertex_alive[remove] = false;
+        self.versions[keep] += 1;
+        self.versions[remove] += 1;
+        num_removed
+    }
+
+
+    fn push_candidates(&self, vertex: usize, heap: &mut BinaryHeap<Collapse>) {
+        for neighbor in self.neighbors(vertex) {
+            heap.push(self.candidate(vertex, neighbor).0);
+        }
+    }
+
+
+    fn into_mesh(self) -> BrainMesh {
+        let mut new_index = vec![usize::MAX; self.coords.len()];
+        let mut num_vertices = 0;
+        let mut vertices = Vec::new();
+        for (v, coords) in self.coords.iter().enumerate() {
+            if self.vertex_alive[v] && !self.vertex_faces[v].is_empty() {
+                new_index[v] = num_vertices;
+                num_vertices += 1;
+                vertices.extend_from_slice(coords);
+            }
+        }
+        let faces = self.faces.iter().zip(self.face_alive.iter()).filter(|(_, &alive)| alive).flat_map(|(face, _)| face.iter().map(|&v| new_index[v] as i32).collect::<Vec<i32>>()).collect();
+        BrainMesh { vertices, faces }
+    }
+}
+
+
+/// The (non-normalized) normal of a face, with a length of twice the face area.
+fn face_normal(coords: &[[f32; 3]], face: &[usize; 3]) -> [f32; 3] {
+    let (a, b, c) = (coords[face[0]], coords[face[1]], coords[face[2]]);
+    vec3_cross(vec3_sub(b, a), vec3_sub(c, a))
+}
+
+
+impl BrainMesh {
+    /// Simplify the mesh to (at most roughly) the given number of faces by quadric error edge collapse.
+    ///
+    /// Edges are collapsed in the order of increasing quadric error (Garland and Heckbert 1997), placing the merged vertex
+    /// at the endpoint or midpoint with the smallest error. Collapses that would make the mesh non-manifold or flip faces
+    /// are skipped, so the target may not be reached for meshes with few faces. See [`BrainMesh::decimate_with_data`] to
+    /// carry per-vertex data along.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let preview = surf.mesh.decimate(20000);
+    /// ```
+    pub fn decimate(&self, target_faces: usize) -> BrainMesh {
+        let mut dec = Decimator::new(self);
+        let mut num_faces = dec.faces.len();
+        let mut heap = BinaryHeap::new();
+        for face in dec.faces.iter() {
+            for corner in 0..3 {
+                let (a, b) = (face[corner], face[(corner + 1) % 3]);
+                if a < b {
+                    heap.push(dec.candidate(a, b).0);
+                }
+            }
+        }
+
+        while num_faces > target_faces {
+            let collapse = match heap.pop() {
+                Some(c) => c,
+                None => break,
+            };
+            let (keep, remove) = (collapse.keep, collapse.remove);
+            if !dec.vertex_alive[keep] || !dec.vertex_alive[remove] || collapse.versions != (dec.versions[keep], dec.versions[remove]) {
+                continue;
+            }
+            let (_, pos) = dec.candidate(keep, remove);
+            if !dec.is_valid_collapse(keep, remove, pos) {
+                continue;
+            }
+            num_faces -= dec.collapse(keep, remove, pos);
+            dec.push_candidates(keep, &mut heap);
+        }
+        dec.into_mesh()
+    }
+
+
+    /// Simplify the mesh like [`BrainMesh::decimate`], and map per-vertex data to the simplified mesh.
+    ///
+    /// Each vertex of the simplified mesh gets the value of the closest vertex of the original mesh. Returns
+    /// [`NeuroformatsError::VertexCountMismatch`] if the data length does not match the vertex count of this mesh.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// let (preview, preview_thickness) = surf.mesh.decimate_with_data(20000, &thickness.data).unwrap();
+    /// ```
+    pub fn decimate_with_data(&self, target_faces: usize, data: &[f32]) -> Result<(BrainMesh, Vec<f32>)> {
+        if data.len() != self.num_vertices() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.num_vertices(), data.len()));
+        }
+        let mesh = self.decimate(target_faces);
+        let index = self.build_spatial_index();
+        let mapped = mesh.vertices.chunks_exact(3).map(|c| data[index.nearest_vertex([c[0], c[1], c[2]]).unwrap().0]).collect();
+        Ok((mesh, mapped))
+    }
+
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_demo_surface_can_be_decimated() {
+        let surf = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let decimated = surf.mesh.decimate(20000);
+        assert!(decimated.num_faces() <= 20000);
+        assert!(decimated.num_faces() >= 19990);
+        assert_eq!(2, decimated.euler_characteristic());
+        assert!(decimated.is_closed());
+        assert!(decimated.faces.iter().all(|&v| (v as usize) < decimated.num_vertices()));
+
+        // The shape is preserved, so the bounding box should hardly change.
+        let orig = crate::coord_extrema(&surf.mesh.vertices).unwrap();
+        let dec = crate::coord_extrema(&decimated.vertices).unwrap();
+        assert!((orig.0 - dec.0).abs() < 2.0);
+        assert!((orig.5 - dec.5).abs() < 2.0);
+    }
+
+    #[test]
+    fn per_vertex_data_can_be_carried_along_when_decimating() {
+        let surf = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let thickness = crate::read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        let (decimated, data) = surf.mesh.decimate_with_data(5000, &thickness.data).unwrap();
+        assert_eq!(decimated.num_vertices(), data.len());
+        let (min, max) = crate::vec32minmax(thickness.data.iter().copied(), false);
+        assert!(data.iter().all(|&t| t >= min && t <= max));
+
+        assert!(matches!(surf.mesh.decimate_with_data(5000, &data), Err(NeuroformatsError::VertexCountMismatch(149244, _))));
+    }
+}
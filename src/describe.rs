@@ -0,0 +1,352 @@
+//! Structured summaries of the data read from neuroimaging files.
+//!
+//! The `describe()` methods of the core structs return a [`Summary`] with counts, value ranges, NaN counts and, for volumes, the data
+//! type and availability of RAS information. Unlike the `Display` implementations, summaries can be inspected programmatically, printed
+//! by command line tools, and included in QC reports via [`Summary::to_json`].
+
+
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+
+use crate::fs_curv::FsCurv;
+use crate::fs_surface::{FsSurface, BrainMesh};
+use crate::fs_annot::FsAnnot;
+use crate::fs_label::FsLabel;
+use crate::util::{json_number, json_array};
+use crate::fs_mgh::{FsMgh, MRI_UCHAR, MRI_INT, MRI_FLOAT, MRI_SHORT, MRI_LONG, MRI_TENSOR};
+
+
+/// Summary statistics of a sequence of values. NaN values are counted, but ignored for all other statistics.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ValueSummary {
+    /// The number of values, including NaN values.
+    pub count: usize,
+    /// The number of NaN values.
+    pub num_nan: usize,
+    /// The minimal value, or `None` if there are no values besides NaN values.
+    pub min: Option<f32>,
+    /// The maximal value, or `None` if there are no values besides NaN values.
+    pub max: Option<f32>,
+    /// The mean value, or `None` if there are no values besides NaN values.
+    pub mean: Option<f64>,
+}
+
+
+impl ValueSummary {
+    /// Compute the summary statistics of the given values.
+    pub fn from_values<I: IntoIterator<Item = f32>>(values: I) -> ValueSummary {
+        let mut count = 0;
+        let mut num_nan = 0;
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        let mut sum = 0.0f64;
+        for v in values {
+            count += 1;
+            if v.is_nan() {
+                num_nan += 1;
+                continue;
+            }
+            min = min.min(v);
+            max = max.max(v);
+            sum += v as f64;
+        }
+        let num_valid = count - num_nan;
+        ValueSummary {
+            count,
+            num_nan,
+            min: if num_valid > 0 { Some(min) } else { None },
+            max: if num_valid > 0 { Some(max) } else { None },
+            mean: if num_valid > 0 { Some(sum / num_valid as f64) } else { None },
+        }
+    }
+
+
+    /// Get a JSON representation of this summary, omitting the statistics which are `None`.
+    pub(crate) fn to_json(&self) -> String {
+        let mut json = format!(r#"{{"count":{},"num_nan":{}"#, self.count, self.num_nan);
+        if let (Some(min), Some(max), Some(mean)) = (self.min, self.max, self.mean) {
+            write!(json, r#","min":{},"max":{},"mean":{}"#, json_number(min as f64), json_number(max as f64), json_number(mean)).unwrap();
+        }
+        json.push('}');
+        json
+    }
+}
+
+
+impl fmt::Display for ValueSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.min, self.max, self.mean) {
+            (Some(min), Some(max), Some(mean)) => write!(f, "{} values ({} NaN) in range {} to {}, mean {:.4}", self.count, self.num_nan, min, max, mean),
+            _ => write!(f, "{} values ({} NaN)", self.count, self.num_nan),
+        }
+    }
+}
+
+
+/// A structured summary of a brain mesh, per-vertex data, parcellation, label or volume.
+///
+/// The fields which do not apply to the kind of data are `None`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Summary {
+    /// The kind of data, the name of the struct that was described, like `"FsCurv"`.
+    pub kind: &'static str,
+    /// The number of vertices of a mesh, per-vertex data, parcellation or label.
+    pub num_vertices: Option<usize>,
+    /// The number of faces of a mesh.
+    pub num_faces: Option<usize>,
+    /// The number of regions in the colortable of a parcellation.
+    pub num_regions: Option<usize>,
+    /// The number of vertices of a parcellation which are not assigned to any region of the colortable.
+    pub num_unassigned_vertices: Option<usize>,
+    /// The coordinate ranges of a mesh or label, as (min_x, max_x, min_y, max_y, min_z, max_z). `None` for meshes without vertices.
+    pub coord_ranges: Option<[f32; 6]>,
+    /// The 4 dimensions of a volume.
+    pub dims: Option<[usize; 4]>,
+    /// The name of the MRI data type of a volume, like `"MRI_UCHAR"`.
+    pub dtype: Option<&'static str>,
+    /// Whether a volume contains valid RAS information, i.e., whether its vox2ras matrix is available.
+    pub has_ras: Option<bool>,
+    /// Statistics of the per-vertex values, label values or voxel values.
+    pub values: Option<ValueSummary>,
+}
+
+
+impl Summary {
+    fn new(kind: &'static str) -> Summary {
+        Summary {
+            kind,
+            num_vertices: None,
+            num_faces: None,
+            num_regions: None,
+            num_unassigned_vertices: None,
+            coord_ranges: None,
+            dims: None,
+            dtype: None,
+            has_ras: None,
+            values: None,
+        }
+    }
+
+
+    /// Get a JSON representation of this summary. Fields which do not apply are omitted, and NaN or infinite values are written as `null`.
+    ///
+    /// This does not need the optional `serde` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let curv = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// println!("{}", curv.describe().to_json());
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![format!(r#""kind":"{}""#, self.kind)];
+        let mut push = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                fields.push(format!(r#""{}":{}"#, name, value));
+            }
+        };
+        push("num_vertices", self.num_vertices.map(|v| v.to_string()));
+        push("num_faces", self.num_faces.map(|v| v.to_string()));
+        push("num_regions", self.num_regions.map(|v| v.to_string()));
+        push("num_unassigned_vertices", self.num_unassigned_vertices.map(|v| v.to_string()));
+        push("coord_ranges", self.coord_ranges.map(|r| json_array(r.iter().map(|v| json_number(*v as f64)))));
+        push("dims", self.dims.map(|d| json_array(d.iter().map(|v| v.to_string()))));
+        push("dtype", self.dtype.map(|d| format!(r#""{}""#, d)));
+        push("has_ras", self.has_ras.map(|v| v.to_string()));
+        push("values", self.values.as_ref().map(|v| v.to_json()));
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.kind)?;
+        if let Some(v) = self.num_vertices { writeln!(f, "  vertices: {}", v)?; }
+        if let Some(v) = self.num_faces { writeln!(f, "  faces: {}", v)?; }
+        if let Some(v) = self.num_regions { writeln!(f, "  regions: {}", v)?; }
+        if let Some(v) = self.num_unassigned_vertices { writeln!(f, "  unassigned vertices: {}", v)?; }
+        if let Some(r) = self.coord_ranges { writeln!(f, "  coordinates: x {} to {}, y {} to {}, z {} to {}", r[0], r[1], r[2], r[3], r[4], r[5])?; }
+        if let Some(d) = self.dims { writeln!(f, "  dims: {} x {} x {} x {}", d[0], d[1], d[2], d[3])?; }
+        if let Some(v) = self.dtype { writeln!(f, "  dtype: {}", v)?; }
+        if let Some(v) = self.has_ras { writeln!(f, "  RAS information: {}", if v { "yes" } else { "no" })?; }
+        if let Some(v) = &self.values { writeln!(f, "  values: {}", v)?; }
+        Ok(())
+    }
+}
+
+
+/// Compute the coordinate ranges of x,y,z triplets, or `None` if there are none.
+fn coord_ranges<I: Iterator<Item = [f32; 3]>>(coords: I) -> Option<[f32; 6]> {
+    let mut ranges: Option<[f32; 6]> = None;
+    for c in coords {
+        let r = ranges.get_or_insert([c[0], c[0], c[1], c[1], c[2], c[2]]);
+        for axis in 0..3 {
+            r[axis * 2] = r[axis * 2].min(c[axis]);
+            r[axis * 2 + 1] = r[axis * 2 + 1].max(c[axis]);
+        }
+    }
+    ranges
+}
+
+
+/// Get the name of an MGH data type, like `"MRI_FLOAT"`, or `"unknown"` for unsupported types.
+pub fn mri_dtype_name(dtype: i32) -> &'static str {
+    match dtype {
+        MRI_UCHAR => "MRI_UCHAR",
+        MRI_INT => "MRI_INT",
+        MRI_FLOAT => "MRI_FLOAT",
+        MRI_SHORT => "MRI_SHORT",
+        MRI_LONG => "MRI_LONG",
+        MRI_TENSOR => "MRI_TENSOR",
+        _ => "unknown",
+    }
+}
+
+
+impl BrainMesh {
+    /// Get a structured summary of this mesh, see [`Summary`].
+    pub fn describe(&self) -> Summary {
+        let mut summary = Summary::new("BrainMesh");
+        summary.num_vertices = Some(self.num_vertices());
+        summary.num_faces = Some(self.num_faces());
+        summary.coord_ranges = coord_ranges(self.vertices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]));
+        summary
+    }
+}
+
+
+impl FsSurface {
+    /// Get a structured summary of the mesh of this surface, see [`Summary`].
+    pub fn describe(&self) -> Summary {
+        Summary { kind: "FsSurface", ..self.mesh.describe() }
+    }
+}
+
+
+impl FsCurv {
+    /// Get a structured summary of this per-vertex data, see [`Summary`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let curv = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// let summary = curv.describe();
+    /// println!("Thickness range: {:?} to {:?} mm.", summary.values.as_ref().unwrap().min, summary.values.as_ref().unwrap().max);
+    /// ```
+    pub fn describe(&self) -> Summary {
+        let mut summary = Summary::new("FsCurv");
+        summary.num_vertices = Some(self.data.len());
+        summary.values = Some(ValueSummary::from_values(self.data.iter().copied()));
+        summary
+    }
+}
+
+
+impl FsAnnot {
+    /// Get a structured summary of this parcellation, see [`Summary`].
+    pub fn describe(&self) -> Summary {
+        let mut summary = Summary::new("FsAnnot");
+        let regions = &self.colortable.regions;
+        summary.num_vertices = Some(self.vertex_labels.len());
+        summary.num_regions = Some(regions.len());
+        summary.num_unassigned_vertices = Some(self.vertex_labels.iter().filter(|l| !regions.iter().any(|r| r.label == **l)).count());
+        summary
+    }
+}
+
+
+impl FsLabel {
+    /// Get a structured summary of this label, see [`Summary`].
+    pub fn describe(&self) -> Summary {
+        let mut summary = Summary::new("FsLabel");
+        summary.num_vertices = Some(self.vertexes.len());
+        summary.coord_ranges = coord_ranges(self.vertexes.iter().map(|v| [v.coord1, v.coord2, v.coord3]));
+        summary.values = Some(ValueSummary::from_values(self.vertexes.iter().map(|v| v.value)));
+        summary
+    }
+}
+
+
+impl FsMgh {
+    /// Get a structured summary of this volume, see [`Summary`]. The `values` are `None` if the data matching the `dtype` of the header is not set.
+    pub fn describe(&self) -> Summary {
+        let mut summary = Summary::new("FsMgh");
+        summary.dims = Some(self.dim());
+        summary.dtype = Some(mri_dtype_name(self.header.dtype));
+        summary.has_ras = Some(self.vox2ras().is_ok());
+        summary.values = self.data_as_f32().ok().map(ValueSummary::from_values);
+        summary
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn value_summaries_ignore_nan_values() {
+        let summary = ValueSummary::from_values(vec![1.0, f32::NAN, 3.0]);
+        assert_eq!(3, summary.count);
+        assert_eq!(1, summary.num_nan);
+        assert_eq!(Some(1.0), summary.min);
+        assert_eq!(Some(3.0), summary.max);
+        assert_eq!(Some(2.0), summary.mean);
+
+        let summary = ValueSummary::from_values(vec![f32::NAN]);
+        assert_eq!(None, summary.min);
+        assert_eq!(None, summary.mean);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_demo_files_can_be_described() {
+        let surf = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap().describe();
+        assert_eq!("FsSurface", surf.kind);
+        assert_eq!(Some(149244), surf.num_vertices);
+        assert_eq!(Some(298484), surf.num_faces);
+        assert!(surf.values.is_none());
+
+        let curv = crate::read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap().describe();
+        assert_eq!(Some(149244), curv.num_vertices);
+        assert_eq!(0, curv.values.unwrap().num_nan);
+
+        let annot = crate::read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap().describe();
+        assert_eq!(Some(36), annot.num_regions);
+        assert_eq!(Some(8394), annot.num_unassigned_vertices);
+
+        let label = crate::read_label("resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").unwrap().describe();
+        assert_eq!(Some(1085), label.num_vertices);
+        assert!(label.coord_ranges.is_some());
+
+        let mgh = crate::read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap().describe();
+        assert_eq!(Some([256, 256, 256, 1]), mgh.dims);
+        assert_eq!(Some("MRI_UCHAR"), mgh.dtype);
+        assert_eq!(Some(true), mgh.has_ras);
+        assert_eq!(Some(156.0), mgh.values.unwrap().max);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_summary_can_be_converted_to_json() {
+        let curv = crate::read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        let json: serde_json::Value = serde_json::from_str(&curv.describe().to_json()).unwrap();
+        assert_eq!("FsCurv", json["kind"]);
+        assert_eq!(149244, json["num_vertices"]);
+        assert_eq!(149244, json["values"]["count"]);
+        assert!(json.get("dims").is_none());
+
+        let empty = BrainMesh { vertices: vec![], faces: vec![] }.describe();
+        let json: serde_json::Value = serde_json::from_str(&empty.to_json()).unwrap();
+        assert_eq!(0, json["num_vertices"]);
+        assert!(json.get("coord_ranges").is_none());
+
+        let nan_label = FsLabel::from_bytes(b"#!ascii label\n1\n5 1.0 2.0 3.0 nan\n").unwrap().describe();
+        let json: serde_json::Value = serde_json::from_str(&nan_label.to_json()).unwrap();
+        assert_eq!(1, json["values"]["num_nan"]);
+        assert!(json["values"].get("min").is_none());
+    }
+}
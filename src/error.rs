@@ -37,14 +37,173 @@ quick_error! {
             display("Invalid FreeSurfer MGH file")
         }
 
-        UnsupportedMriDataTypeInMgh {
-            display("Invalid or unsupported MRI_DTYPE")
+        /// The MGH file uses an MRI data type which is not supported, or the data matching the data type of the header is missing.
+        UnsupportedMriDataTypeInMgh(dtype: i32, dim: [usize; 4]) {
+            display("Invalid or unsupported MRI_DTYPE {} for MGH data with dimensions {:?}", dtype, dim)
         }
 
         NoRasInformationInHeader {
             display("The MGH header does not contain valid RAS information.")
         }
 
+        InvalidColorLutFormat {
+            display("Invalid FreeSurfer color lookup table file")
+        }
+
+        InvalidTransformFormat {
+            display("Invalid or unsupported FreeSurfer transform file")
+        }
+
+        /// Per-vertex data does not match the vertex count of the mesh it is used with.
+        VertexCountMismatch(expected: usize, found: usize) {
+            display("Expected data for {} vertices, but found {}.", expected, found)
+        }
+
+        /// A vertex index refers to a vertex which does not exist in the mesh it is used with.
+        VertexIndexOutOfRange(index: i64, num_vertices: usize) {
+            display("Vertex index {} is out of range for a mesh with {} vertices.", index, num_vertices)
+        }
+
+        /// The number of face indices of a mesh is not a multiple of 3, so they do not describe triangles.
+        InvalidFaceIndexCount(num_indices: usize) {
+            display("The mesh has {} face indices, which is not a multiple of 3.", num_indices)
+        }
+
+        /// A mesh does not have the number of faces expected, e.g., the number of faces of another mesh it is used with.
+        FaceCountMismatch(expected: usize, found: usize) {
+            display("Expected {} faces, but found {}.", expected, found)
+        }
+
+        /// The faces of two meshes which must have the same faces differ, e.g., a white and a pial surface.
+        FaceMismatch(face: usize) {
+            display("The face {} differs between the meshes.", face)
+        }
+
+        /// Color data does not contain the expected number of values, e.g., 3 RGB values per vertex.
+        ColorCountMismatch(expected: usize, found: usize) {
+            display("Expected {} color values, but found {}.", expected, found)
+        }
+
+        /// Voxel data does not match the dimensions of the volume it is used with.
+        VoxelCountMismatch(expected: usize, found: usize) {
+            display("Expected data for {} voxels, but found {}.", expected, found)
+        }
+
+        /// A box of voxels is empty or exceeds the volume it is used with.
+        InvalidVoxelBox(min: [usize; 3], max: [usize; 3], dim: [usize; 4]) {
+            display("The voxel box from {:?} to {:?} is empty or exceeds the volume with dimensions {:?}.", min, max, dim)
+        }
+
+        /// The regions of an annotation cannot be changed as requested, e.g., because a region does not exist.
+        InvalidAnnotRegions(reason: String) {
+            display("Invalid annotation regions: {}.", reason)
+        }
+
+        /// Volumes cannot be combined, e.g., because their geometry differs.
+        IncompatibleVolumes(reason: String) {
+            display("Incompatible volumes: {}.", reason)
+        }
+
+        /// The info line of a surf file header is invalid, e.g., because it contains non-ASCII chars.
+        InvalidFsSurfaceInfoLine(reason: String) {
+            display("Invalid info line for FreeSurfer surf file: {}.", reason)
+        }
+
+        /// Per-vertex data cannot be downsampled to an icosphere of the requested order, e.g., because it does not belong to an icosphere.
+        InvalidIcoData(num_vertices: usize, order: usize) {
+            display("Data for {} vertices cannot be downsampled to an icosphere of order {}.", num_vertices, order)
+        }
+
+        /// A statistical model cannot be fitted, e.g., because the design matrix is singular or there are too few subjects.
+        InvalidDesign(reason: String) {
+            display("Invalid design: {}.", reason)
+        }
+
+        /// A string is not a valid hemisphere identifier, see [`crate::hemi::Hemisphere`].
+        InvalidHemisphere(name: String) {
+            display("Invalid hemisphere '{}', expected 'lh' or 'rh'.", name)
+        }
+
+        /// A string is not a known surface kind, see [`crate::subject::SurfaceKind`].
+        InvalidSurfaceKind(name: String) {
+            display("Invalid surface kind '{}'.", name)
+        }
+
+        /// The geometry of a mesh does not allow the requested computation, e.g., a convex hull of coplanar vertices.
+        DegenerateGeometry(reason: String) {
+            display("Degenerate geometry: {}.", reason)
+        }
+
+        /// No timepoints of a longitudinal base subject were found, see [`crate::subject::LongitudinalSubject::discover`].
+        NoTimepoints(base_id: String) {
+            display("No longitudinal timepoints found for base subject '{}'.", base_id)
+        }
+
+        /// A subject was not found in the FreeSurfer derivatives of a BIDS dataset, see [`crate::bids::BidsDataset::subject`].
+        BidsSubjectNotFound(subject_id: String) {
+            display("No FreeSurfer output found for BIDS subject '{}'.", subject_id)
+        }
+
+        /// A coordinate list contains no coordinates, see [`crate::fs_surface::coord_extrema`].
+        EmptyCoordinates {
+            display("The coordinate list is empty.")
+        }
+
+        /// The length of a coordinate list is not a multiple of 3, so it does not consist of x, y and z coordinates.
+        InvalidCoordinateCount(num_values: usize) {
+            display("The coordinate list has {} values, which is not a multiple of 3.", num_values)
+        }
+
+        /// A coordinate list contains a `NAN` value, so its extrema are undefined.
+        NanCoordinate(index: usize) {
+            display("The coordinate at index {} is NAN.", index)
+        }
+
+        /// An animation or time series was requested without any frames, see [`crate::BrainMesh::to_gltf_animated`].
+        NoFrames {
+            display("No frames given.")
+        }
+
+        /// A per-vertex integer label map is invalid, see [`crate::label_map`].
+        InvalidLabelMap(reason: String) {
+            display("Invalid label map: {}.", reason)
+        }
+
+        /// A vertex order is not a permutation of the vertex indices of a mesh, see [`crate::reorder::VertexPermutation`].
+        InvalidVertexPermutation(reason: String) {
+            display("Invalid vertex permutation: {}.", reason)
+        }
+
+        /// The label of a vertex does not match any region of the colortable of an annotation, see [`crate::FsAnnot::vertex_region_indices`].
+        UnmatchedVertexLabel(vertex: usize, label: i32) {
+            display("The label {} of vertex {} does not match any region of the colortable.", label, vertex)
+        }
+
+        /// The number of regions does not match the number of rows of a matrix, see [`crate::roi::connectivity_matrix`].
+        RegionCountMismatch(expected: usize, found: usize) {
+            display("Expected {} regions, but found {}.", expected, found)
+        }
+
+        /// Per-vertex data contains a NaN value where none is allowed, see [`crate::laplace::LaplaceBeltrami::heat_kernel_smooth`].
+        NanDataValue(index: usize) {
+            display("The data contains a NaN value at index {}.", index)
+        }
+
+        /// A per-vertex value cannot be represented in the requested value type, see [`crate::overlay::SurfaceOverlay::convert`].
+        InvalidOverlayValue(index: usize, value: f64, value_type: &'static str) {
+            display("The value {} at index {} cannot be represented as {}.", value, index, value_type)
+        }
+
+        /// Mesh coordinates are in another coordinate space or unit than required, see [`crate::coord_space::SpatialMesh`].
+        CoordSpaceMismatch(expected: String, found: String) {
+            display("Expected coordinates in {}, but found {}.", expected, found)
+        }
+
+        /// A frame index exceeds the frames of a volume, see [`crate::FsMgh::frame`].
+        FrameOutOfRange(frame: usize, num_frames: usize) {
+            display("The frame {} is out of range for a volume with {} frames.", frame, num_frames)
+        }
+
         /// I/O Error
         Io(err: IOError) {
             from()
@@ -0,0 +1,205 @@
+//! A report of the supported file formats, as structured data.
+//!
+//! Applications can use this to list the supported file types, e.g., in file dialogs, and to check at runtime whether
+//! a format can be read or written by the version of the library they are linked against.
+
+
+use std::fmt;
+
+
+/// A file format supported by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// FreeSurfer binary per-vertex data, like `surf/lh.thickness`.
+    Curv,
+    /// FreeSurfer binary triangle meshes, like `surf/lh.white`.
+    Surf,
+    /// FreeSurfer ASCII label files, like `label/lh.cortex.label`.
+    Label,
+    /// FreeSurfer surface parcellations, like `label/lh.aparc.annot`.
+    Annot,
+    /// FreeSurfer MGH volumes, and their gzip-compressed MGZ variant.
+    Mgh,
+    /// FreeSurfer linear transform arrays, like `mri/transforms/talairach.lta`.
+    Lta,
+    /// MNI linear transforms, like `mri/transforms/talairach.xfm`.
+    Xfm,
+    /// FreeSurfer color lookup tables for segmentations, like `FreeSurferColorLUT.txt`.
+    ColorLut,
+}
+
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.capabilities().name)
+    }
+}
+
+
+impl Format {
+    /// All supported formats.
+    pub const ALL: [Format; 8] = [Format::Curv, Format::Surf, Format::Label, Format::Annot, Format::Mgh, Format::Lta, Format::Xfm, Format::ColorLut];
+
+
+    /// Get the capabilities of this crate for the format.
+    pub fn capabilities(self) -> FormatCapabilities {
+        match self {
+            Format::Curv => FormatCapabilities {
+                format: self,
+                name: "FreeSurfer curv",
+                extensions: &["", ".gz"],
+                example_files: &["lh.thickness", "lh.area", "lh.sulc"],
+                versions: &["new binary curv format (magic number 16777215)"],
+                read: true,
+                write: true,
+                limitations: &[
+                    "The old curv format without magic number is not supported.",
+                    "Gzip-compressed files are detected by their content and can be read, but are always written uncompressed.",
+                ],
+            },
+            Format::Surf => FormatCapabilities {
+                format: self,
+                name: "FreeSurfer surf",
+                extensions: &[""],
+                example_files: &["lh.white", "lh.pial", "lh.inflated", "lh.sphere.reg"],
+                versions: &["binary triangle format (magic number 16777214)"],
+                read: true,
+                write: true,
+                limitations: &[
+                    "Quad meshes and ASCII surfaces are not supported.",
+                    "The optional tags after the face data, like the volume geometry, are kept as raw bytes and only written in strict mode, see `WriteOptions::strict`.",
+                ],
+            },
+            Format::Label => FormatCapabilities {
+                format: self,
+                name: "FreeSurfer label",
+                extensions: &[".label"],
+                example_files: &["lh.cortex.label", "lh.BA1_exvivo.label"],
+                versions: &["ASCII label format"],
+                read: true,
+                write: true,
+                limitations: &["The header comment line, which may contain the subject name and coordinate space, is not preserved."],
+            },
+            Format::Annot => FormatCapabilities {
+                format: self,
+                name: "FreeSurfer annot",
+                extensions: &[".annot"],
+                example_files: &["lh.aparc.annot", "lh.aparc.a2009s.annot"],
+                versions: &["binary annot format with colortable version 2"],
+                read: true,
+                write: true,
+                limitations: &[
+                    "Annot files with the old colortable format or without colortable are not supported.",
+                    "Files are written with colortable version 2. The colortable size is computed from the region ids unless `WriteOptions::strict` is set.",
+                ],
+            },
+            Format::Mgh => FormatCapabilities {
+                format: self,
+                name: "FreeSurfer MGH/MGZ",
+                extensions: &[".mgh", ".mgz"],
+                example_files: &["brain.mgz", "aseg.mgz", "lh.thickness.fwhm10.fsaverage.mgh"],
+                versions: &["MGH version 1"],
+                read: true,
+                write: true,
+                limitations: &[
+                    "Only the data types uchar, int, float, short and tensor are supported. Tensor data is read as float data with 9 frames.",
+                    "Old footer tags without a length field are ignored when reading.",
+                ],
+            },
+            Format::Lta => FormatCapabilities {
+                format: self,
+                name: "FreeSurfer LTA",
+                extensions: &[".lta"],
+                example_files: &["talairach.lta"],
+                versions: &["LTA with a single linear transform"],
+                read: true,
+                write: false,
+                limitations: &[
+                    "Only the first transform of files with several transforms is read.",
+                    "Writing LTA files is not supported.",
+                ],
+            },
+            Format::Xfm => FormatCapabilities {
+                format: self,
+                name: "MNI xfm",
+                extensions: &[".xfm"],
+                example_files: &["talairach.xfm"],
+                versions: &["MNI transform file with a linear transform"],
+                read: true,
+                write: false,
+                limitations: &[
+                    "Non-linear grid transforms are not supported.",
+                    "Writing xfm files is not supported.",
+                ],
+            },
+            Format::ColorLut => FormatCapabilities {
+                format: self,
+                name: "FreeSurfer color lookup table",
+                extensions: &[".txt", ".ctab"],
+                example_files: &["FreeSurferColorLUT.txt"],
+                versions: &["ASCII table with label, name, red, green, blue and alpha columns"],
+                read: true,
+                write: false,
+                limitations: &[
+                    "Writing color lookup tables is not supported.",
+                ],
+            },
+        }
+    }
+}
+
+
+/// The capabilities of this crate for a file format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatCapabilities {
+    /// The format.
+    pub format: Format,
+    /// A human-readable name of the format.
+    pub name: &'static str,
+    /// The file extensions used for the format, including the dot. An empty string means that files typically have no extension.
+    pub extensions: &'static [&'static str],
+    /// Typical file names of the format in a FreeSurfer subject directory.
+    pub example_files: &'static [&'static str],
+    /// The supported versions or variants of the format.
+    pub versions: &'static [&'static str],
+    /// Whether files in this format can be read.
+    pub read: bool,
+    /// Whether files in this format can be written.
+    pub write: bool,
+    /// Known limitations of the support for this format.
+    pub limitations: &'static [&'static str],
+}
+
+
+/// Get the capabilities of this crate for all supported file formats.
+///
+/// # Examples
+///
+/// ```
+/// let writable: Vec<&str> = neuroformats::formats::capabilities().iter().filter(|c| c.write).map(|c| c.name).collect();
+/// assert!(writable.contains(&"FreeSurfer MGH/MGZ"));
+/// ```
+pub fn capabilities() -> Vec<FormatCapabilities> {
+    Format::ALL.iter().map(|f| f.capabilities()).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_capabilities_of_all_formats_can_be_listed() {
+        let caps = capabilities();
+        assert_eq!(Format::ALL.len(), caps.len());
+        for (format, cap) in Format::ALL.iter().zip(caps.iter()) {
+            assert_eq!(*format, cap.format);
+            assert!(cap.read);
+            assert!(!cap.versions.is_empty());
+        }
+        assert!(Format::Mgh.capabilities().write);
+        assert!(Format::Annot.capabilities().write);
+        assert!(!Format::Lta.capabilities().write);
+        assert_eq!("FreeSurfer annot", Format::Annot.to_string());
+    }
+}
@@ -0,0 +1,226 @@
+//! Frame-wise operations for 4D MGH volumes, like fMRI time series or longitudinal data stacked into frames.
+
+
+use ndarray::{Array, Array4, Dim};
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_mgh::{check_shape, FsMgh, FsMghData, FsMghHeader, MRI_FLOAT};
+
+
+/// The maximal difference of the voxel sizes, direction cosines and center coordinates of volumes that are considered to have the same geometry.
+const GEOMETRY_TOLERANCE: f32 = 1e-4;
+
+
+impl FsMgh {
+    /// Extract a single frame of the volume as a 3D volume with the same data type and geometry.
+    ///
+    /// # Return value
+    ///
+    /// The frame as a volume with a single frame. Returns [`NeuroformatsError::FrameOutOfRange`] if `frame` is out of range for this volume,
+    /// and [`NeuroformatsError::VoxelCountMismatch`] if the data does not match the dimensions of the header.
+    pub fn frame(&self, frame: usize) -> Result<FsMgh> {
+        let dim = self.dim();
+        if frame >= dim[3] {
+            return Err(NeuroformatsError::FrameOutOfRange(frame, dim[3]));
+        }
+        self.check_data()?;
+        fn frame_of<T: Copy>(data: &Option<Array4<T>>, dim: [usize; 4], frame: usize) -> Result<Option<Array4<T>>> {
+            data.as_ref().map(|d| {
+                check_shape(d, dim)?;
+                let n = dim[0] * dim[1] * dim[2];
+                let values = d.iter().skip(frame * n).take(n).copied().collect();
+                Ok(Array::from_shape_vec(Dim([dim[0], dim[1], dim[2], 1]), values).expect("a frame has the shape of a 3D volume"))
+            }).transpose()
+        }
+        let data = FsMghData {
+            mri_uchar: frame_of(&self.data.mri_uchar, dim, frame)?,
+            mri_int: frame_of(&self.data.mri_int, dim, frame)?,
+            mri_float: frame_of(&self.data.mri_float, dim, frame)?,
+            mri_short: frame_of(&self.data.mri_short, dim, frame)?,
+        };
+        let mut header = self.header.clone();
+        header.dim4len = 1;
+        Ok(FsMgh { header, data, footer: self.footer.clone() })
+    }
+
+
+    /// Compute the voxel-wise mean over all frames, as a 3D `MRI_FLOAT` volume with the geometry of this volume.
+    pub fn mean_over_frames(&self) -> Result<FsMgh> {
+        let (n, frames) = (self.header.num_voxels_per_frame(), self.header.dim4len as usize);
+        let values = self.data_as_f32()?;
+        let mean: Vec<f32> = (0..n).map(|idx| {
+            ((0..frames).map(|t| values[t * n + idx] as f64).sum::<f64>() / frames as f64) as f32
+        }).collect();
+        self.single_frame_float_volume(mean)
+    }
+
+
+    /// Compute the voxel-wise sample standard deviation over all frames, as a 3D `MRI_FLOAT` volume with the geometry of this volume.
+    ///
+    /// The standard deviation of volumes with a single frame is `0` for all voxels.
+    pub fn std_over_frames(&self) -> Result<FsMgh> {
+        let (n, frames) = (self.header.num_voxels_per_frame(), self.header.dim4len as usize);
+        let values = self.data_as_f32()?;
+        let std: Vec<f32> = (0..n).map(|idx| {
+            if frames < 2 {
+                return 0.0;
+            }
+            let mean = (0..frames).map(|t| values[t * n + idx] as f64).sum::<f64>() / frames as f64;
+            let sum_sq: f64 = (0..frames).map(|t| (values[t * n + idx] as f64 - mean).powi(2)).sum();
+            (sum_sq / (frames - 1) as f64).sqrt() as f32
+        }).collect();
+        self.single_frame_float_volume(std)
+    }
+
+
+    /// Create a 3D `MRI_FLOAT` volume with the geometry of this volume from voxel values in file order.
+    fn single_frame_float_volume(&self, values: Vec<f32>) -> Result<FsMgh> {
+        let mut header = self.header.clone();
+        header.dim4len = 1;
+        FsMgh::from_f32_values(&header, values)
+    }
+}
+
+
+/// Check whether two headers describe volumes with the same voxel grid, i.e., the same dimensions and RAS geometry. The number of frames and data types may differ.
+pub fn same_geometry(a: &FsMghHeader, b: &FsMghHeader) -> bool {
+    let close = |x: &[f32], y: &[f32]| x.iter().zip(y.iter()).all(|(u, v)| (u - v).abs() <= GEOMETRY_TOLERANCE || (u.is_nan() && v.is_nan()));
+    a.dim()[..3] == b.dim()[..3]
+        && a.is_ras_good == b.is_ras_good
+        && (a.is_ras_good != 1 || (close(&a.delta, &b.delta) && close(&a.mdc_raw, &b.mdc_raw) && close(&a.p_xyz_c, &b.p_xyz_c)))
+}
+
+
+/// Concatenate the frames of several volumes with the same geometry into a single volume, e.g., to create a time series.
+///
+/// The data type is kept if all volumes have the same data type, otherwise the result has the data type `MRI_FLOAT`. The header and footer of
+/// the first volume are used for the result.
+///
+/// # Return value
+///
+/// The volume with the frames of all volumes, in order. Returns [`NeuroformatsError::IncompatibleVolumes`] if `volumes` is empty or their geometry
+/// differs, see [`same_geometry`], and [`NeuroformatsError::VoxelCountMismatch`] if the data of a volume does not match the dimensions of its header.
+///
+/// # Examples
+///
+/// ```no_run
+/// let sessions: Vec<neuroformats::FsMgh> = ["ses1.mgz", "ses2.mgz"].iter().map(|f| neuroformats::read_mgh(f).unwrap()).collect();
+/// let series = neuroformats::frames::concat_frames(&sessions).unwrap();
+/// let mean = series.mean_over_frames().unwrap();
+/// ```
+pub fn concat_frames(volumes: &[FsMgh]) -> Result<FsMgh> {
+    let first = volumes.first().ok_or_else(|| NeuroformatsError::IncompatibleVolumes("no volumes given".to_string()))?;
+    if let Some(idx) = volumes.iter().position(|v| !same_geometry(&first.header, &v.header)) {
+        return Err(NeuroformatsError::IncompatibleVolumes(format!("the geometry of volume {} differs from the first volume", idx)));
+    }
+    let frames: usize = volumes.iter().map(|v| v.header.dim4len as usize).sum();
+    let mut header = first.header.clone();
+    header.dim4len = frames as i32;
+
+    if volumes.iter().all(|v| v.header.dtype == first.header.dtype) {
+        for v in volumes {
+            v.check_data()?;
+        }
+        fn concat<T: Copy>(parts: Vec<(&Option<Array4<T>>, [usize; 4])>, dim: [usize; 4]) -> Result<Option<Array4<T>>> {
+            let mut values = Vec::new();
+            for (part, part_dim) in parts {
+                let part = match part {
+                    Some(part) => part,
+                    None => return Ok(None),
+                };
+                check_shape(part, part_dim)?;
+                values.extend(part.iter().copied());
+            }
+            let num_values = values.len();
+            Array::from_shape_vec(Dim(dim), values).map(Some).map_err(|_| NeuroformatsError::VoxelCountMismatch(dim.iter().product(), num_values))
+        }
+        let dim = header.dim();
+        let data = FsMghData {
+            mri_uchar: concat(volumes.iter().map(|v| (&v.data.mri_uchar, v.dim())).collect(), dim)?,
+            mri_int: concat(volumes.iter().map(|v| (&v.data.mri_int, v.dim())).collect(), dim)?,
+            mri_float: concat(volumes.iter().map(|v| (&v.data.mri_float, v.dim())).collect(), dim)?,
+            mri_short: concat(volumes.iter().map(|v| (&v.data.mri_short, v.dim())).collect(), dim)?,
+        };
+        Ok(FsMgh { header, data, footer: first.footer.clone() })
+    } else {
+        let mut values = Vec::with_capacity(header.num_voxels_per_frame() * frames);
+        for v in volumes {
+            values.extend(v.data_as_f32()?);
+        }
+        header.dtype = MRI_FLOAT;
+        let mut volume = FsMgh::from_f32_values(&header, values)?;
+        volume.footer = first.footer.clone();
+        Ok(volume)
+    }
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+    use crate::read_mgh;
+    use crate::fs_mgh::MRI_INT;
+
+    fn series() -> FsMgh {
+        let mut header = read_mgh("resources/mgh/tiny.mgh").unwrap().header;
+        header.dim4len = 3;
+        FsMgh::from_f32_values(&header, (0..81).map(|v| (v / 27 * 2) as f32 + (v % 27) as f32).collect()).unwrap()
+    }
+
+    #[test]
+    fn frames_can_be_extracted() {
+        let tiny = read_mgh("resources/mgh/tiny.mgh").unwrap();
+        assert_eq!(tiny.data, tiny.frame(0).unwrap().data);
+
+        let frame = series().frame(2).unwrap();
+        assert_eq!([3, 3, 3, 1], frame.dim());
+        assert_eq!(4.0, frame.data_as_f32().unwrap()[0]);
+    }
+
+    #[test]
+    fn invalid_frames_and_inconsistent_volumes_are_errors() {
+        assert!(matches!(series().frame(3), Err(NeuroformatsError::FrameOutOfRange(3, 3))));
+
+        let mut inconsistent = series();
+        inconsistent.header.dim4len = 4;
+        assert!(matches!(inconsistent.frame(3), Err(NeuroformatsError::VoxelCountMismatch(108, 81))));
+        assert!(matches!(concat_frames(&[series(), inconsistent.clone()]), Err(NeuroformatsError::VoxelCountMismatch(108, 81))));
+
+        inconsistent.data.mri_float = None;
+        assert!(matches!(inconsistent.frame(0), Err(NeuroformatsError::UnsupportedMriDataTypeInMgh(..))));
+    }
+
+    #[test]
+    fn the_mean_and_std_over_frames_can_be_computed() {
+        let mean = series().mean_over_frames().unwrap();
+        assert_eq!([3, 3, 3, 1], mean.dim());
+        assert_eq!(MRI_FLOAT, mean.header.dtype);
+        assert_eq!((0..27).map(|v| v as f32 + 2.0).collect::<Vec<f32>>(), mean.data_as_f32().unwrap());
+
+        let std = series().std_over_frames().unwrap();
+        assert!(std.data_as_f32().unwrap().iter().all(|v| (v - 2.0).abs() < 1e-6));
+        assert!(read_mgh("resources/mgh/tiny.mgh").unwrap().std_over_frames().unwrap().data_as_f32().unwrap().iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn volumes_can_be_concatenated() {
+        let tiny = read_mgh("resources/mgh/tiny.mgh").unwrap();
+        let same_type = concat_frames(&[tiny.clone(), tiny.clone()]).unwrap();
+        assert_eq!(MRI_INT, same_type.header.dtype);
+        assert_eq!([3, 3, 3, 2], same_type.dim());
+        assert_eq!(tiny.data, same_type.frame(1).unwrap().data);
+
+        let mixed = concat_frames(&[tiny.clone(), series()]).unwrap();
+        assert_eq!(MRI_FLOAT, mixed.header.dtype);
+        assert_eq!([3, 3, 3, 4], mixed.dim());
+        assert_eq!(series().frame(0).unwrap().data_as_f32().unwrap(), mixed.frame(1).unwrap().data_as_f32().unwrap());
+    }
+
+    #[test]
+    fn volumes_with_different_geometry_cannot_be_concatenated() {
+        let tiny = read_mgh("resources/mgh/tiny.mgh").unwrap();
+        let brain = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        assert!(concat_frames(&[tiny, brain]).is_err());
+        assert!(concat_frames(&[]).is_err());
+    }
+}
@@ -5,18 +5,31 @@
 //! name, an RGB display color, and a unique identifier.
 
 use byteordered::{ByteOrdered};
-
-use std::fs::File;
-use std::io::{BufReader, BufRead};
+#[cfg(feature = "fs")]
+use byteordered::Endianness;
+
+use std::convert::TryFrom;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::Write;
+#[cfg(feature = "fs")]
 use std::path::{Path};
 use std::fmt;
+use std::collections::HashMap;
 
-use crate::util::read_fixed_length_string;
+use crate::util::{read_fixed_length_string, prealloc_capacity, gunzip_if_compressed, skip_bytes};
+#[cfg(feature = "fs")]
+use crate::util::{latin1_bytes, open_maybe_compressed, WriteOptions};
 use crate::error::{NeuroformatsError, Result};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsAnnotColortable {
     pub regions: Vec<FsAnnotColorRegion>,
+    /// The path of the colortable file the annotation was created from, as stored in the annot file.
+    pub orig_filename: String,
+    /// The size of the colortable as stored in the annot file, i.e., the largest region id plus one. It can be larger than the number of regions.
+    pub max_structures: i32,
 }
 
 impl FsAnnotColortable {
@@ -29,7 +42,7 @@ impl FsAnnotColortable {
         let mut input = ByteOrdered::be(input);
 
         let num_chars_orig_filename: i32 = input.read_i32()?; // Length of following string.
-        let _orig_filename = read_fixed_length_string(&mut input, num_chars_orig_filename as usize);
+        let orig_filename = read_fixed_length_string(&mut input, checked_len(num_chars_orig_filename)?)?;
         let num_colortable_entries: i32 = input.read_i32()?; // Yes, it is stored twice. Once here, once before.
 
         let entries = (0..num_colortable_entries)
@@ -38,7 +51,7 @@ impl FsAnnotColortable {
                 FsAnnotColorRegion::from_reader(input.inner_mut())
             })
             .collect::<Result<Vec<_>>>()?;
-        Ok(FsAnnotColortable{regions: entries})
+        Ok(FsAnnotColortable{ max_structures: num_colortable_entries, regions: entries, orig_filename })
     }
 }
 
@@ -48,7 +61,33 @@ impl fmt::Display for FsAnnotColortable {
     }
 }
 
+/// Read the colortable section at the end of an annot file, after the per-vertex labels. Only colortables in format version 2 are supported.
+fn colortable_section_from_reader<S>(input: &mut S) -> Result<FsAnnotColortable>
+where
+    S: BufRead,
+{
+    let mut file = ByteOrdered::be(input);
+    let has_colortable: i32 = file.read_i32()?;
+
+    if has_colortable != 1 {
+        return Err(NeuroformatsError::UnsupportedFsAnnotFormatVersion);
+    }
+
+    let num_colortable_entries: i32 = file.read_i32()?;
+
+    if num_colortable_entries == -2 { // If this is negative, the absolute value encodes the file format version. We only support version 2.
+        let max_structures: i32 = file.read_i32()?;  // For version 2, the next i32 stores the size of the colortable.
+
+        let mut colortable: FsAnnotColortable = FsAnnotColortable::from_reader(file.inner_mut())?;
+        colortable.max_structures = max_structures;
+        Ok(colortable)
+    } else {
+        Err(NeuroformatsError::UnsupportedFsAnnotFormatVersion)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsAnnotColorRegion {
     pub id: i32,  // A region index, not really needed. The 'label' is unique as well and more relevant, see below.
     pub name: String,
@@ -67,13 +106,13 @@ impl FsAnnotColorRegion {
         let mut input = ByteOrdered::be(input);
         let id = input.read_i32()?;
         let num_chars_region_name: i32 = input.read_i32()?; // Length of following string.
-        let name = read_fixed_length_string(&mut input, num_chars_region_name as usize)?;
+        let name = read_fixed_length_string(&mut input, checked_len(num_chars_region_name)?)?;
         let r = input.read_i32()?;
         let g = input.read_i32()?;
         let b = input.read_i32()?;
         let a = input.read_i32()?;
 
-        let label = r + g * 2i32.pow(8) + b * 2i32.pow(16) + a * 2i32.pow(24);
+        let label = FsAnnotColorRegion::color_label(r, g, b, a);
         Ok(FsAnnotColorRegion {
             id,
             name,
@@ -84,55 +123,139 @@ impl FsAnnotColorRegion {
             label,
         })
     }
+
+
+    /// Compute the unique label of a region from its color, as used in the `vertex_labels` of an [`FsAnnot`].
+    pub fn color_label(r: i32, g: i32, b: i32, a: i32) -> i32 {
+        // Wrapping arithmetic, as in FreeSurfer. Valid files use color values from 0 to 255 and alpha 0, so this does not overflow.
+        r.wrapping_add(g.wrapping_shl(8)).wrapping_add(b.wrapping_shl(16)).wrapping_add(a.wrapping_shl(24))
+    }
+}
+
+/// The number of vertices and the colortable of an annot file, read without the per-vertex labels, see [`read_annot_header_info`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FsAnnotHeaderInfo {
+    /// The number of vertices the annotation assigns to regions, which matches the number of vertices of the surface.
+    pub num_vertices: usize,
+    /// The colortable with the regions.
+    pub colortable: FsAnnotColortable,
+}
+
+impl FsAnnotHeaderInfo {
+    /// Read the header info from an annot file, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsAnnotHeaderInfo> {
+        FsAnnotHeaderInfo::from_reader(&mut open_maybe_compressed(path)?)
+    }
+
+
+    /// Read the header info from the given byte stream, which must be at the start of the file. The per-vertex labels are skipped.
+    ///
+    /// This function returns an error and does not panic for arbitrary input.
+    pub fn from_reader<S>(input: &mut S) -> Result<FsAnnotHeaderInfo>
+    where
+        S: BufRead,
+    {
+        let num_vertices = checked_len(ByteOrdered::be(&mut *input).read_i32()?)?;
+        skip_bytes(input, num_vertices.checked_mul(8).ok_or(NeuroformatsError::UnsupportedFsAnnotFormatVersion)?)?;
+        let colortable = colortable_section_from_reader(input)?;
+        Ok(FsAnnotHeaderInfo { num_vertices, colortable })
+    }
 }
 
 /// Models a FreeSurfer brain surface parcellation from an annot file. This is the result of applying a brain atlas (like Desikan-Killiani) to a subject. The `vertex_indices` are the 0-based indices used in FreeSurfer and should be ignored. The `vertex_labels` field contains the mesh vertices in order, and assigns to each vertex a brain region using the `label` field (not the `id` field!) from the `colortable`. The field `colortable` contains an [`FsAnnotColortable`] struct that describes the brain regions.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsAnnot {
     pub vertex_indices: Vec<i32>, // 0-based indices, not really needed as all vertices need to be covered in order.
     pub vertex_labels: Vec<i32>,
     pub colortable: FsAnnotColortable,
 }
 
-impl FsAnnot {
-    /// Read an FsSurface instance from a file.
-    pub fn from_file<P: AsRef<Path> + Copy>(path: P) -> Result<FsAnnot> {
 
-        let file = BufReader::new(File::open(path)?);
-        let mut file = ByteOrdered::be(file);
+/// A lookup from region labels to the indices of the regions in the colortable of an [`FsAnnot`], see [`FsAnnot::region_lookup`].
+///
+/// It borrows the annotation, so the colortable cannot change while the lookup is in use. If several regions share a label, the
+/// first one is used.
+#[derive(Debug, Clone)]
+pub struct RegionLookup<'a> {
+    regions: &'a [FsAnnotColorRegion],
+    indices: HashMap<i32, usize>,
+}
 
-        let num_vertices: i32 = file.read_i32()?;
+impl<'a> RegionLookup<'a> {
+    /// Get the index of the colortable region with the given label, or `None` if no region has this label.
+    pub fn region_index(&self, label: i32) -> Option<usize> {
+        self.indices.get(&label).copied()
+    }
 
-        let mut vertex_indices : Vec<i32> = Vec::with_capacity(num_vertices as usize);
-        let mut vertex_labels : Vec<i32> = Vec::with_capacity(num_vertices as usize);
-        for _ in 1..=num_vertices {
-            vertex_indices.push(file.read_i32()?);
-            vertex_labels.push(file.read_i32()?);
-        }
 
-        let has_colortable: i32 = file.read_i32()?;
+    /// Get the colortable region with the given label, or `None` if no region has this label.
+    pub fn region(&self, label: i32) -> Option<&'a FsAnnotColorRegion> {
+        self.region_index(label).map(|region_idx| &self.regions[region_idx])
+    }
+}
 
-        if has_colortable != 1 {
-            return Err(NeuroformatsError::UnsupportedFsAnnotFormatVersion);
-        }
 
-        let num_colortable_entries: i32 = file.read_i32()?;
+/// How vertices whose label does not match any region of the colortable are handled by [`FsAnnot::vertex_region_indices`].
+///
+/// Such vertices are not assigned to any region. FreeSurfer displays them like the `unknown` region, but that is a convention of the
+/// viewer, and the colortable of an annot does not need to contain an `unknown` region at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmatchedLabelPolicy {
+    /// Assign unmatched vertices to the region with the given index in the colortable, typically `0` for the `unknown` region.
+    MapToIndex(usize),
+    /// Return `None` for unmatched vertices.
+    MarkAsNone,
+    /// Return an error if any vertex is unmatched.
+    Error,
+}
 
-        if num_colortable_entries == -2 { // If this is negative, the absolute value encodes the file format version. We only support version 2.
-            let _num_colortable_entries: i32 = file.read_i32()?;  // For version 2, the next i32 stores the actual number of entries.
+impl FsAnnot {
+    /// Create an FsAnnot instance from vertex indices, vertex labels and a colortable.
+    pub fn new(vertex_indices: Vec<i32>, vertex_labels: Vec<i32>, colortable: FsAnnotColortable) -> FsAnnot {
+        FsAnnot { vertex_indices, vertex_labels, colortable }
+    }
 
-            let colortable: FsAnnotColortable = FsAnnotColortable::from_reader(&mut file)?;
 
-            let annot = FsAnnot {
-                vertex_indices: vertex_indices,
-                vertex_labels: vertex_labels,
-                colortable: colortable,
-            };
+    /// Read an FsAnnot instance from a file, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsAnnot> {
+        FsAnnot::from_reader(&mut open_maybe_compressed(path)?)
+    }
+
+
+    /// Read an FsAnnot instance from the bytes of an annot file, which may be gzip-compressed.
+    ///
+    /// This does not need file system access, e.g., for use in WebAssembly. It returns an error and does not panic for arbitrary input.
+    pub fn from_bytes(bytes: &[u8]) -> Result<FsAnnot> {
+        FsAnnot::from_reader(&mut &gunzip_if_compressed(bytes)?[..])
+    }
+
+
+    /// Read an FsAnnot instance from the given byte stream, which must be at the start of the file.
+    ///
+    /// This function returns an error and does not panic for arbitrary input.
+    pub fn from_reader<S>(input: &mut S) -> Result<FsAnnot>
+    where
+        S: BufRead,
+    {
+        let mut file = ByteOrdered::be(input);
+
+        let num_vertices = checked_len(file.read_i32()?)?;
 
-            Ok(annot)
-        } else {
-            Err(NeuroformatsError::UnsupportedFsAnnotFormatVersion)
+        let mut vertex_indices : Vec<i32> = Vec::with_capacity(prealloc_capacity(num_vertices));
+        let mut vertex_labels : Vec<i32> = Vec::with_capacity(prealloc_capacity(num_vertices));
+        for _ in 0..num_vertices {
+            vertex_indices.push(file.read_i32()?);
+            vertex_labels.push(file.read_i32()?);
         }
+        vertex_indices.shrink_to_fit();
+        vertex_labels.shrink_to_fit();
+
+        let colortable = colortable_section_from_reader(file.inner_mut())?;
+        Ok(FsAnnot::new(vertex_indices, vertex_labels, colortable))
     }
 
     /// Get the region names contained in the [`FsAnnot`] struct.
@@ -194,17 +317,93 @@ impl FsAnnot {
     /// annot.vertex_regions();
     /// ```
     pub fn vertex_regions(&self) -> Vec<String> {
-        let mut vert_regions: Vec<String> = vec![String::new(); self.vertex_labels.len()];
-        for region in self.colortable.regions.iter() {
-            let region_label = region.label;
-            let region_name = &region.name;
-            for (idx, vlabel) in self.vertex_labels.iter().enumerate() {
-                if vlabel == &region_label {
-                    vert_regions[idx] = region_name.clone();
-                }
+        // If several regions share a label, the last one is used.
+        let region_names: HashMap<i32, &str> = self.colortable.regions.iter().map(|region| (region.label, region.name.as_str())).collect();
+        self.vertex_labels.iter()
+            .map(|label| region_names.get(label).map_or_else(String::new, |name| name.to_string()))
+            .collect()
+    }
+
+
+    /// Get the colortable region of a vertex, including its name, color, id and label.
+    ///
+    /// # Return value
+    ///
+    /// The region, or `None` if the vertex index is out of range or the label of the vertex does not match any region.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// if let Some(region) = annot.vertex_region_info(0) {
+    ///     println!("Vertex 0 is in region {} with color ({}, {}, {}).", region.name, region.r, region.g, region.b);
+    /// }
+    /// ```
+    pub fn vertex_region_info(&self, idx: usize) -> Option<&FsAnnotColorRegion> {
+        let label = *self.vertex_labels.get(idx)?;
+        self.region_index_of_label(label).map(|region_idx| &self.colortable.regions[region_idx])
+    }
+
+
+    /// Get the index of the first colortable region with the given label.
+    ///
+    /// This is a linear search over the regions. Use [`FsAnnot::region_lookup`] to look up the regions of many vertices.
+    pub(crate) fn region_index_of_label(&self, label: i32) -> Option<usize> {
+        self.colortable.regions.iter().position(|region| region.label == label)
+    }
+
+
+    /// Build a lookup from region labels to the indices of the regions in the colortable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let lookup = annot.region_lookup();
+    /// let region_names: Vec<&str> = annot.vertex_labels.iter().map(|label| lookup.region(*label).map_or("", |region| region.name.as_str())).collect();
+    /// ```
+    pub fn region_lookup(&self) -> RegionLookup<'_> {
+        let mut indices = HashMap::with_capacity(self.colortable.regions.len());
+        for (region_idx, region) in self.colortable.regions.iter().enumerate() {
+            indices.entry(region.label).or_insert(region_idx);
+        }
+        RegionLookup { regions: &self.colortable.regions, indices }
+    }
+
+
+    /// Get the index of the colortable region of each vertex.
+    ///
+    /// # Parameters
+    ///
+    /// * `policy`: how vertices with a label that does not match any region label are handled, see [`UnmatchedLabelPolicy`].
+    ///
+    /// # Return value
+    ///
+    /// The region index of each vertex, which is `None` only for unmatched vertices with [`UnmatchedLabelPolicy::MarkAsNone`].
+    /// Returns [`NeuroformatsError::UnmatchedVertexLabel`] for the first unmatched vertex with [`UnmatchedLabelPolicy::Error`], and
+    /// [`NeuroformatsError::InvalidAnnotRegions`] if the index of [`UnmatchedLabelPolicy::MapToIndex`] is out of range.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::fs_annot::UnmatchedLabelPolicy;
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let region_indices = annot.vertex_region_indices(UnmatchedLabelPolicy::MarkAsNone).unwrap();
+    /// let num_unassigned = region_indices.iter().filter(|idx| idx.is_none()).count();
+    /// ```
+    pub fn vertex_region_indices(&self, policy: UnmatchedLabelPolicy) -> Result<Vec<Option<usize>>> {
+        if let UnmatchedLabelPolicy::MapToIndex(region_idx) = policy {
+            if region_idx >= self.num_regions() {
+                return Err(NeuroformatsError::InvalidAnnotRegions(format!("region index {} is out of range for {} regions", region_idx, self.num_regions())));
             }
         }
-        return vert_regions;
+        let lookup = self.region_lookup();
+        self.vertex_labels.iter().enumerate().map(|(vertex, label)| match (lookup.region_index(*label), policy) {
+            (Some(region_idx), _) => Ok(Some(region_idx)),
+            (None, UnmatchedLabelPolicy::MapToIndex(region_idx)) => Ok(Some(region_idx)),
+            (None, UnmatchedLabelPolicy::MarkAsNone) => Ok(None),
+            (None, UnmatchedLabelPolicy::Error) => Err(NeuroformatsError::UnmatchedVertexLabel(vertex, *label)),
+        }).collect()
     }
 
 
@@ -218,21 +417,11 @@ impl FsAnnot {
     ///
     /// If the `unmatched_region_index` is not a valid index for the [`FsAnnot`] struct, i.e., it is out of range.
     fn vertex_colortable_indices(&self, unmatched_region_index : usize) -> Vec<usize> {
-        let mut vert_colortable_indices: Vec<usize> = Vec::with_capacity(self.vertex_labels.len());
-        for vlabel in self.vertex_labels.iter() {
-            let mut found = false;
-            for (region_idx, region) in self.colortable.regions.iter().enumerate() {
-                if vlabel == &region.label {
-                    vert_colortable_indices.push(region_idx);
-                    found = true;
-                    break;
-                }
-            }
-            if ! found {
-                vert_colortable_indices.push(unmatched_region_index);
-            }
-        }
-        return vert_colortable_indices;
+        let lookup = self.region_lookup();
+        self.vertex_labels
+            .iter()
+            .map(|vlabel| lookup.region_index(*vlabel).unwrap_or(unmatched_region_index))
+            .collect()
     }
 
 
@@ -274,6 +463,87 @@ impl FsAnnot {
         vert_colors
     }
 
+
+    /// Merge regions of the annotation into larger regions, e.g., all frontal regions into a frontal lobe.
+    ///
+    /// The colortable of the result contains one region per distinct new region name, in the order of their first occurrence in the
+    /// colortable, with ids starting at `0`. A new region keeps the color and label of the existing region with its name if that region is
+    /// not renamed itself, otherwise those of the first region merged into it. The vertices of merged regions are assigned to the new region.
+    ///
+    /// # Parameters
+    ///
+    /// * `map`: maps region names to new region names. Regions which are not in the map keep their name, and are merged with other regions mapped to it.
+    ///
+    /// # Return value
+    ///
+    /// The annotation with the merged regions. Returns [`NeuroformatsError::InvalidAnnotRegions`] if a key of the `map` is not a region name of this annotation.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::collections::HashMap;
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let map: HashMap<String, String> = ["superiorfrontal", "rostralmiddlefrontal", "caudalmiddlefrontal"].iter()
+    ///     .map(|name| (name.to_string(), String::from("frontal"))).collect();
+    /// let lobes = annot.merge_regions(&map).unwrap();
+    /// ```
+    pub fn merge_regions(&self, map: &HashMap<String, String>) -> Result<FsAnnot> {
+        if let Some(name) = map.keys().find(|name| !self.colortable.regions.iter().any(|region| &region.name == *name)) {
+            return Err(NeuroformatsError::InvalidAnnotRegions(format!("no region named '{}'", name)));
+        }
+        let mut regions: Vec<FsAnnotColorRegion> = Vec::new();
+        let mut new_labels: HashMap<i32, i32> = HashMap::new();
+        for region in self.colortable.regions.iter() {
+            let target = map.get(&region.name).unwrap_or(&region.name);
+            let new_label = match regions.iter().find(|r| &r.name == target) {
+                Some(existing) => existing.label,
+                None => {
+                    let template = self.colortable.regions.iter()
+                        .find(|r| &r.name == target && !map.contains_key(&r.name))
+                        .unwrap_or(region);
+                    let mut merged = template.clone();
+                    merged.id = regions.len() as i32;
+                    merged.name = target.clone();
+                    regions.push(merged);
+                    template.label
+                }
+            };
+            new_labels.insert(region.label, new_label);
+        }
+        Ok(FsAnnot::new(
+            self.vertex_indices.clone(),
+            self.vertex_labels.iter().map(|label| *new_labels.get(label).unwrap_or(label)).collect(),
+            FsAnnotColortable { max_structures: regions.len() as i32, regions, orig_filename: self.colortable.orig_filename.clone() },
+        ))
+    }
+
+
+    /// Change the color of a region, and thus its label, e.g., to give a region created by [`FsAnnot::merge_regions`] a distinct color.
+    ///
+    /// The vertices of the region are assigned the new label. The alpha value of the region is kept.
+    ///
+    /// # Return value
+    ///
+    /// The annotation with the new region color. Returns [`NeuroformatsError::InvalidAnnotRegions`] if there is no region with the given name,
+    /// or if another region already has the label of the new color.
+    pub fn relabel(&self, region: &str, rgb: [i32; 3]) -> Result<FsAnnot> {
+        let region_idx = self.colortable.regions.iter().position(|r| r.name == region)
+            .ok_or_else(|| NeuroformatsError::InvalidAnnotRegions(format!("no region named '{}'", region)))?;
+        let old = &self.colortable.regions[region_idx];
+        let new_label = FsAnnotColorRegion::color_label(rgb[0], rgb[1], rgb[2], old.a);
+        if let Some(other) = self.colortable.regions.iter().enumerate().find(|(idx, r)| *idx != region_idx && r.label == new_label) {
+            return Err(NeuroformatsError::InvalidAnnotRegions(format!("region '{}' already has the label {}", other.1.name, new_label)));
+        }
+        let mut annot = self.clone();
+        annot.vertex_labels.iter_mut().filter(|label| **label == old.label).for_each(|label| *label = new_label);
+        let new_region = &mut annot.colortable.regions[region_idx];
+        new_region.r = rgb[0];
+        new_region.g = rgb[1];
+        new_region.b = rgb[2];
+        new_region.label = new_label;
+        Ok(annot)
+    }
+
 }
 
 
@@ -302,15 +572,88 @@ impl fmt::Display for FsAnnot {
 /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
 /// println!("Annotation assigns the {} brain mesh vertices to {} different regions.", annot.vertex_indices.len(), annot.regions().len());
 /// ```
-pub fn read_annot<P: AsRef<Path> + Copy>(path: P) -> Result<FsAnnot> {
+#[cfg(feature = "fs")]
+pub fn read_annot<P: AsRef<Path>>(path: P) -> Result<FsAnnot> {
     FsAnnot::from_file(path)
 }
 
 
+/// Read the number of vertices and the colortable of an annot file, without loading the per-vertex labels.
+///
+/// This is much faster than [`read_annot`] for large files, e.g., to index the parcellations of a dataset.
+///
+/// # Examples
+///
+/// ```no_run
+/// let info = neuroformats::read_annot_header_info("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+/// println!("Annotation of {} vertices with {} regions.", info.num_vertices, info.colortable.regions.len());
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_annot_header_info<P: AsRef<Path>>(path: P) -> Result<FsAnnotHeaderInfo> {
+    FsAnnotHeaderInfo::from_file(path)
+}
+
+
+/// Write a brain parcellation to a file in FreeSurfer annot format, version 2.
+#[cfg(feature = "fs")]
+pub fn write_annot<P: AsRef<Path>>(path: P, annot: &FsAnnot) -> std::io::Result<()> {
+    write_annot_with_options(path, annot, &WriteOptions::default())
+}
+
+
+/// Write a brain parcellation to a file in FreeSurfer annot format, version 2, using the given [`WriteOptions`].
+///
+/// Unless [`WriteOptions::strict`] is set, the size of the colortable is computed from the region ids instead of using the
+/// `max_structures` of the colortable.
+#[cfg(feature = "fs")]
+pub fn write_annot_with_options<P: AsRef<Path>>(path: P, annot: &FsAnnot, options: &WriteOptions) -> std::io::Result<()> {
+    let f = options.create(path)?;
+    let mut f = ByteOrdered::runtime(f, Endianness::Big);
+    f.write_i32(annot.vertex_labels.len() as i32)?;
+    for (vidx, vlabel) in annot.vertex_indices.iter().zip(annot.vertex_labels.iter()) {
+        f.write_i32(*vidx)?;
+        f.write_i32(*vlabel)?;
+    }
+
+    let colortable = &annot.colortable;
+    f.write_i32(1)?; // The annot has a colortable.
+    f.write_i32(-2)?; // The colortable format version, as a negative number.
+    let max_structures = if options.strict { colortable.max_structures } else { colortable.regions.iter().map(|r| r.id + 1).max().unwrap_or(0) };
+    f.write_i32(max_structures)?;
+    write_nul_terminated_string(&mut f, &colortable.orig_filename)?;
+    f.write_i32(colortable.regions.len() as i32)?;
+    for region in colortable.regions.iter() {
+        f.write_i32(region.id)?;
+        write_nul_terminated_string(&mut f, &region.name)?;
+        for value in [region.r, region.g, region.b, region.a] {
+            f.write_i32(value)?;
+        }
+    }
+    options.finish(f.into_inner())
+}
+
+
+/// Write a string preceded by its length including the trailing NUL byte, as used for the names in annot files.
+#[cfg(feature = "fs")]
+fn write_nul_terminated_string<W: Write>(f: &mut ByteOrdered<W, Endianness>, value: &str) -> std::io::Result<()> {
+    let mut bytes = latin1_bytes(value);
+    bytes.push(0);
+    f.write_i32(bytes.len() as i32)?;
+    f.write_all(&bytes)
+}
+
+
+/// Convert a length or count read from an annot file to `usize`, rejecting negative values.
+fn checked_len(len: i32) -> Result<usize> {
+    usize::try_from(len).map_err(|_| NeuroformatsError::UnsupportedFsAnnotFormatVersion)
+}
+
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[cfg(feature = "fs")]
     #[test]
     fn the_demo_annot_file_can_be_read() {
         const ANNOT_FILE: &str = "resources/subjects_dir/subject1/label/lh.aparc.annot";
@@ -331,6 +674,7 @@ mod test {
         assert_eq!(1639705, first_region.label);
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn annot_region_names_are_read_correctly() {
         const ANNOT_FILE: &str = "resources/subjects_dir/subject1/label/lh.aparc.annot";
@@ -344,6 +688,7 @@ mod test {
         assert_eq!(regions[35], "insula");
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn annot_region_vertices_are_computed_correctly() {
         const ANNOT_FILE: &str = "resources/subjects_dir/subject1/label/lh.aparc.annot";
@@ -353,6 +698,7 @@ mod test {
         assert_eq!(1722, region_verts.len());
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn annot_region_indices_are_computed_correctly() {
         const ANNOT_FILE: &str = "resources/subjects_dir/subject1/label/lh.aparc.annot";
@@ -368,6 +714,29 @@ mod test {
         assert_eq!(149244, region_indices.len());
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn annot_vertex_region_indices_handle_unmatched_labels_as_requested() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+
+        let marked = annot.vertex_region_indices(UnmatchedLabelPolicy::MarkAsNone).unwrap();
+        assert_eq!(149244, marked.len());
+        assert_eq!(8394, marked.iter().filter(|idx| idx.is_none()).count());
+        let mapped = annot.vertex_region_indices(UnmatchedLabelPolicy::MapToIndex(0)).unwrap();
+        assert_eq!(annot.vertex_colortable_indices(0), mapped.iter().map(|idx| idx.unwrap()).collect::<Vec<usize>>());
+
+        let first_unmatched = marked.iter().position(|idx| idx.is_none()).unwrap();
+        match annot.vertex_region_indices(UnmatchedLabelPolicy::Error) {
+            Err(NeuroformatsError::UnmatchedVertexLabel(vertex, label)) => {
+                assert_eq!(first_unmatched, vertex);
+                assert_eq!(annot.vertex_labels[vertex], label);
+            },
+            other => panic!("Expected an UnmatchedVertexLabel error, got {:?}.", other),
+        }
+        assert!(matches!(annot.vertex_region_indices(UnmatchedLabelPolicy::MapToIndex(36)), Err(NeuroformatsError::InvalidAnnotRegions(_))));
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn annot_vertex_colors_are_computed_correctly() {
         let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
@@ -380,5 +749,168 @@ mod test {
         assert_eq!(col_rgb.len(), annot.vertex_indices.len() * 3);
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_region_of_a_vertex_can_be_looked_up() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let bankssts = annot.region_vertices(String::from("bankssts"));
+        let region = annot.vertex_region_info(bankssts[0]).unwrap();
+        assert_eq!("bankssts", region.name);
+        assert_eq!(1, region.id);
+        assert!(annot.vertex_region_info(149244).is_none());
+
+        let vertex_regions = annot.vertex_regions();
+        assert_eq!(149244, vertex_regions.len());
+        assert_eq!(1722, vertex_regions.iter().filter(|r| *r == "bankssts").count());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_header_info_of_an_annot_can_be_read_without_the_labels() {
+        const ANNOT_FILE: &str = "resources/subjects_dir/subject1/label/lh.aparc.annot";
+        let annot = read_annot(ANNOT_FILE).unwrap();
+        let info = read_annot_header_info(ANNOT_FILE).unwrap();
+        assert_eq!(149244, info.num_vertices);
+        assert_eq!(annot.colortable, info.colortable);
+
+        let bytes = std::fs::read(ANNOT_FILE).unwrap();
+        assert!(FsAnnotHeaderInfo::from_reader(&mut &bytes[..1000]).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_region_lookup_is_updated_when_the_colortable_changes() {
+        let mut annot = read_annot("resources/annot/tiny.annot").unwrap();
+        assert_eq!("bankssts", annot.vertex_region_info(2).unwrap().name);
+
+        annot.colortable.regions.swap(0, 1);
+        assert_eq!("bankssts", annot.vertex_region_info(2).unwrap().name);
+        annot.colortable.regions[0].label = -1;
+        assert!(annot.vertex_region_info(2).is_none());
+        assert_eq!(String::new(), annot.vertex_regions()[2]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_regions_of_many_vertices_can_be_looked_up() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let lookup = annot.region_lookup();
+        for (vidx, label) in annot.vertex_labels.iter().enumerate().step_by(1000) {
+            assert_eq!(annot.vertex_region_info(vidx), lookup.region(*label));
+        }
+        assert_eq!(None, lookup.region_index(-12345));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_last_region_with_a_duplicate_label_names_the_vertices() {
+        let mut annot = read_annot("resources/annot/tiny.annot").unwrap();
+        let label = annot.vertex_labels[2];
+        let mut duplicate = annot.colortable.regions.iter().find(|region| region.label == label).unwrap().clone();
+        duplicate.name = String::from("duplicate");
+        annot.colortable.regions.push(duplicate);
+        assert_eq!("duplicate", annot.vertex_regions()[2]);
+        assert_ne!("duplicate", annot.vertex_region_info(2).unwrap().name);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn annot_regions_can_be_merged() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let frontal = ["superiorfrontal", "rostralmiddlefrontal", "caudalmiddlefrontal", "parsopercularis", "parstriangularis", "parsorbitalis"];
+        let map: HashMap<String, String> = frontal.iter().map(|name| (name.to_string(), String::from("frontal"))).collect();
+        let merged = annot.merge_regions(&map).unwrap();
+
+        assert_eq!(31, merged.num_regions());
+        assert_eq!((0..31).collect::<Vec<i32>>(), merged.colortable.regions.iter().map(|r| r.id).collect::<Vec<i32>>());
+        let num_frontal: usize = frontal.iter().map(|name| annot.region_vertices(name.to_string()).len()).sum();
+        assert_eq!(num_frontal, merged.region_vertices(String::from("frontal")).len());
+        assert_eq!(1722, merged.region_vertices(String::from("bankssts")).len());
+        assert_eq!(annot.vertex_regions().iter().filter(|r| *r == "unknown").count(), merged.vertex_regions().iter().filter(|r| *r == "unknown").count());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn annot_regions_can_be_merged_into_an_existing_region() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let map: HashMap<String, String> = vec![(String::from("bankssts"), String::from("superiortemporal"))].into_iter().collect();
+        let merged = annot.merge_regions(&map).unwrap();
+
+        let superiortemporal = annot.colortable.regions.iter().find(|r| r.name == "superiortemporal").unwrap();
+        let merged_region = merged.colortable.regions.iter().find(|r| r.name == "superiortemporal").unwrap();
+        assert_eq!(superiortemporal.label, merged_region.label);
+        assert_eq!(35, merged.num_regions());
+        assert_eq!(annot.region_vertices(String::from("superiortemporal")).len() + 1722, merged.region_vertices(String::from("superiortemporal")).len());
+
+        let unknown: HashMap<String, String> = vec![(String::from("nosuchregion"), String::from("frontal"))].into_iter().collect();
+        assert!(annot.merge_regions(&unknown).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn annot_regions_can_be_relabeled() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let relabeled = annot.relabel("bankssts", [1, 2, 3]).unwrap();
+        let region = &relabeled.colortable.regions[1];
+        assert_eq!((1, 2, 3), (region.r, region.g, region.b));
+        assert_eq!(FsAnnotColorRegion::color_label(1, 2, 3, region.a), region.label);
+        assert_eq!(annot.region_vertices(String::from("bankssts")), relabeled.region_vertices(String::from("bankssts")));
+
+        assert!(annot.relabel("bankssts", [25, 5, 25]).is_err());
+        assert!(annot.relabel("nosuchregion", [1, 2, 3]).is_err());
+    }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_tiny_annot_file_can_be_read() {
+        let annot = read_annot("resources/annot/tiny.annot").unwrap();
+        assert_eq!(5, annot.vertex_labels.len());
+        assert_eq!(vec!["unknown", "bankssts"], annot.regions());
+        assert_eq!(annot.colortable.regions[1].label, annot.vertex_labels[2]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn annot_files_are_written_byte_identical_in_strict_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let strict = WriteOptions { strict: true, ..Default::default() };
+        for path in ["resources/subjects_dir/subject1/label/lh.aparc.annot", "resources/subjects_dir/subject1/label/rh.aparc.annot", "resources/annot/tiny.annot"] {
+            let tfile_path = dir.path().join("temp.annot");
+            write_annot_with_options(&tfile_path, &read_annot(path).unwrap(), &strict).unwrap();
+            assert_eq!(std::fs::read(path).unwrap(), std::fs::read(&tfile_path).unwrap());
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn an_annot_file_can_be_written_and_reread() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        assert_eq!("/autofs/space/tanha_002/users/greve/fsdev.build/average/colortable_desikan_killiany.txt", annot.colortable.orig_filename);
+        let merged = annot.merge_regions(&vec![(String::from("bankssts"), String::from("superiortemporal"))].into_iter().collect()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let tfile_path = dir.path().join("merged.annot");
+        write_annot(&tfile_path, &merged).unwrap();
+        let merged_re = read_annot(&tfile_path).unwrap();
+        assert_eq!(merged.vertex_labels, merged_re.vertex_labels);
+        assert_eq!(merged.colortable.regions, merged_re.colortable.regions);
+        assert_eq!(35, merged_re.colortable.max_structures);
+    }
+
+    #[test]
+    fn reading_corrupted_annot_files_does_not_panic() {
+        let bytes = std::fs::read("resources/annot/tiny.annot").unwrap();
+        for variant in crate::util::corrupted_variants(&bytes) {
+            let _ = FsAnnot::from_reader(&mut &variant[..]);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "fs"))]
+    fn an_annot_can_be_serialized_and_deserialized() {
+        let annot = read_annot("resources/annot/tiny.annot").unwrap();
+        let json = serde_json::to_string(&annot).unwrap();
+        let annot_re: FsAnnot = serde_json::from_str(&json).unwrap();
+        assert_eq!(annot, annot_re);
+    }
 }
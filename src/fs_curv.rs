@@ -4,21 +4,32 @@
 //! for each vertex of the respective brain surface mesh.
 
 
-use byteordered::{ByteOrdered, Endianness};
+use byteordered::ByteOrdered;
+#[cfg(feature = "fs")]
+use byteordered::Endianness;
+#[cfg(feature = "fs")]
 use flate2::bufread::GzDecoder;
 
+use std::convert::TryFrom;
+#[cfg(feature = "fs")]
 use std::fs::File;
-use std::io::{BufReader, BufRead, BufWriter};
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
 use std::path::{Path};
 use std::fmt;
 
-use crate::util::{is_gz_file, vec32minmax};
+use crate::util::{vec32minmax, vec32robust_range, read_values, gunzip_if_compressed, sorted_without_nan, percentile_of_sorted};
+#[cfg(feature = "fs")]
+use crate::util::{is_gz_data, open_maybe_compressed, skip_bytes, WriteOptions};
 use crate::error::{NeuroformatsError, Result};
 
 
 pub const CURV_MAGIC_CODE_U8: u8 = 255;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsCurvHeader {
     pub curv_magic: [u8; 3],
     pub num_vertices: i32,
@@ -41,16 +52,11 @@ impl Default for FsCurvHeader {
 impl FsCurvHeader {
     
     /// Read a Curv header from a file.
-    /// If the file's name ends with ".gz", the file is assumed to need GZip decoding. This is not typically the case
+    /// If the file is gzip-compressed, it is decompressed on the fly. This is not typically the case
     /// for FreeSurfer Curv files, but very handy (and it helps us to reduce the size of our test data).
+    #[cfg(feature = "fs")]
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsCurvHeader> {
-        let gz = is_gz_file(&path);
-        let file = BufReader::new(File::open(path)?);
-        if gz {
-            FsCurvHeader::from_reader(BufReader::new(GzDecoder::new(file)))
-        } else {
-            FsCurvHeader::from_reader(file)
-        }
+        FsCurvHeader::from_reader(open_maybe_compressed(path)?)
     }
 
 
@@ -73,24 +79,63 @@ impl FsCurvHeader {
         hdr.num_faces = input.read_i32()?;
         hdr.num_values_per_vertex = input.read_i32()?;
 
-        if !(hdr.curv_magic[0] == CURV_MAGIC_CODE_U8 && hdr.curv_magic[1] == CURV_MAGIC_CODE_U8 && hdr.curv_magic[2] == CURV_MAGIC_CODE_U8) {
+        if !(hdr.curv_magic[0] == CURV_MAGIC_CODE_U8 && hdr.curv_magic[1] == CURV_MAGIC_CODE_U8 && hdr.curv_magic[2] == CURV_MAGIC_CODE_U8) || hdr.num_vertices < 0 {
             Err(NeuroformatsError::InvalidCurvFormat)
         } else {
             Ok(hdr)
         }
     }
 
+
+    /// Estimate the number of bytes of memory needed to hold the per-vertex data of a curv file with this header, i.e., one `f32` per vertex.
+    ///
+    /// Returns `None` if the number of vertices is negative.
+    pub fn estimated_memory_bytes(&self) -> Option<usize> {
+        let num_vertices = usize::try_from(self.num_vertices).ok()?;
+        Some(num_vertices.saturating_mul(std::mem::size_of::<f32>()))
+    }
+
 }
 
 
 /// An FsCurv object, models a FreeSurfer per-vertex data file in curv format.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsCurv {
     pub header: FsCurvHeader,
     pub data: Vec<f32>, 
 }
 
 
+/// Descriptive statistics of per-vertex data, see [`FsCurv::stats`]. NaN values are ignored for all statistics but `num_nan`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CurvStats {
+    /// The number of values which are not NaN.
+    pub num_values: usize,
+    /// The number of NaN values.
+    pub num_nan: usize,
+    /// The minimal value.
+    pub min: f32,
+    /// The maximal value.
+    pub max: f32,
+    /// The mean value.
+    pub mean: f64,
+    /// The population standard deviation.
+    pub std: f64,
+    /// The median, the 50th percentile.
+    pub median: f32,
+    /// The 5th percentile.
+    pub p5: f32,
+    /// The 25th percentile, the lower quartile.
+    pub p25: f32,
+    /// The 75th percentile, the upper quartile.
+    pub p75: f32,
+    /// The 95th percentile.
+    pub p95: f32,
+}
+
+
 impl fmt::Display for FsCurv {    
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {        
         let (min, max) = vec32minmax(self.data.iter().copied(), false);
@@ -112,74 +157,234 @@ impl fmt::Display for FsCurv {
 /// let curv = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
 /// let thickness_at_vertex_0 : f32 = curv.data[0];
 /// ```
-pub fn read_curv<P: AsRef<Path> + Copy>(path: P) -> Result<FsCurv> {
+#[cfg(feature = "fs")]
+pub fn read_curv<P: AsRef<Path>>(path: P) -> Result<FsCurv> {
     FsCurv::from_file(path)
 }
 
+
+/// Read only the header of a curv file, with the number of vertices, without loading the per-vertex data.
+///
+/// # Examples
+///
+/// ```no_run
+/// let header = neuroformats::read_curv_header("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// println!("Data for {} vertices.", header.num_vertices);
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_curv_header<P: AsRef<Path>>(path: P) -> Result<FsCurvHeader> {
+    FsCurvHeader::from_file(path)
+}
+
+/// Read the values of selected vertices from a FreeSurfer curv file, without loading the whole file.
+///
+/// The data of all other vertices is skipped (by seeking for plain files), which makes this fast for extracting a region of interest,
+/// e.g., the vertices of a label, from the files of many subjects.
+///
+/// # Return value
+///
+/// The values of the vertices in the order of `indices`. Indices may be repeated. Returns [`NeuroformatsError::VertexIndexOutOfRange`] if an
+/// index is not smaller than the vertex count in the file header.
+///
+/// # Examples
+///
+/// ```no_run
+/// let label = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").unwrap();
+/// let indices: Vec<usize> = label.vertexes.iter().map(|v| v.index as usize).collect();
+/// let thickness = neuroformats::fs_curv::read_curv_subset("/path/to/subjects_dir/subject1/surf/lh.thickness", &indices).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_curv_subset<P: AsRef<Path>>(path: P, indices: &[usize]) -> Result<Vec<f32>> {
+    let mut file = BufReader::new(File::open(path)?);
+    if is_gz_data(file.fill_buf()?) {
+        curv_subset_from_reader(BufReader::new(GzDecoder::new(file)), indices, skip_bytes)
+    } else {
+        curv_subset_from_reader(file, indices, |input, n| Ok(input.seek_relative(n as i64)?))
+    }
+}
+
+
+/// Read the values of selected vertices from a curv file stream, using the `skip` function to skip a number of bytes.
+#[cfg(feature = "fs")]
+fn curv_subset_from_reader<S, F>(mut input: S, indices: &[usize], mut skip: F) -> Result<Vec<f32>>
+where
+    S: BufRead,
+    F: FnMut(&mut S, usize) -> Result<()>,
+{
+    let hdr = FsCurvHeader::from_reader(&mut input)?;
+    let num_vertices = hdr.num_vertices as usize;
+    let mut order: Vec<usize> = (0..indices.len()).collect();
+    order.sort_unstable_by_key(|&i| indices[i]);
+
+    let mut values = vec![0.0; indices.len()];
+    let mut position = 0;
+    let mut last: Option<(usize, f32)> = None;
+    for i in order {
+        let index = indices[i];
+        if index >= num_vertices {
+            return Err(NeuroformatsError::VertexIndexOutOfRange(index as i64, num_vertices));
+        }
+        values[i] = match last {
+            Some((last_index, value)) if last_index == index => value,
+            _ => {
+                skip(&mut input, (index - position) * 4)?;
+                let value = ByteOrdered::be(&mut input).read_f32()?;
+                position = index + 1;
+                last = Some((index, value));
+                value
+            },
+        };
+    }
+    Ok(values)
+}
+
+
 /// Write an FsCurv struct to a file in FreeSurfer curv format.
-pub fn write_curv<P: AsRef<Path> + Copy>(path: P, curv : &FsCurv) {
-    let f = File::create(path).expect("Unable to create curv file");
-    let f = BufWriter::new(f);  
+///
+/// # Panics
+///
+/// If the file cannot be written. Use [`write_curv_with_options`] to handle errors instead.
+#[cfg(feature = "fs")]
+pub fn write_curv<P: AsRef<Path>>(path: P, curv : &FsCurv) {
+    write_curv_with_options(path, curv, &WriteOptions::default()).expect("Unable to write curv file");
+}
+
+
+/// Write an FsCurv struct to a file in FreeSurfer curv format, using the given [`WriteOptions`].
+#[cfg(feature = "fs")]
+pub fn write_curv_with_options<P: AsRef<Path>>(path: P, curv : &FsCurv, options: &WriteOptions) -> std::io::Result<()> {
+    let f = options.create(path)?;
     let mut f  =  ByteOrdered::runtime(f, Endianness::Big); 
-    f.write_u8(CURV_MAGIC_CODE_U8).unwrap();
-    f.write_u8(CURV_MAGIC_CODE_U8).unwrap();
-    f.write_u8(CURV_MAGIC_CODE_U8).unwrap();
-    f.write_i32(curv.header.num_vertices).unwrap();
-    f.write_i32(curv.header.num_faces).unwrap();
-    f.write_i32(curv.header.num_values_per_vertex).unwrap();
+    f.write_u8(CURV_MAGIC_CODE_U8)?;
+    f.write_u8(CURV_MAGIC_CODE_U8)?;
+    f.write_u8(CURV_MAGIC_CODE_U8)?;
+    f.write_i32(curv.header.num_vertices)?;
+    f.write_i32(curv.header.num_faces)?;
+    f.write_i32(curv.header.num_values_per_vertex)?;
 
     for v in &curv.data {
-        f.write_f32(*v).unwrap();
+        f.write_f32(*v)?;
     }
+    options.finish(f.into_inner())
 }
 
 
 impl FsCurv {
+    /// Create per-vertex data from the given values, e.g., to write computed data with [`write_curv`]. The number of faces in the header is set to `0`.
+    pub fn from_data(data: Vec<f32>) -> FsCurv {
+        let header = FsCurvHeader { num_vertices: data.len() as i32, ..FsCurvHeader::default() };
+        FsCurv { header, data }
+    }
+
+
+    /// Compute descriptive statistics of the per-vertex values, like the mean, standard deviation and percentiles. NaN values are ignored.
+    ///
+    /// # Return value
+    ///
+    /// The statistics, or `None` if there are no values besides NaN values.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let curv = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// let stats = curv.stats().unwrap();
+    /// println!("Thickness {:.2} +- {:.2} mm, median {:.2} mm.", stats.mean, stats.std, stats.median);
+    /// ```
+    pub fn stats(&self) -> Option<CurvStats> {
+        let sorted = sorted_without_nan(&self.data);
+        let percentile = |p: f32| percentile_of_sorted(&sorted, p);
+        let num_values = sorted.len();
+        let mean = sorted.iter().map(|v| *v as f64).sum::<f64>() / num_values as f64;
+        let variance = sorted.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>() / num_values as f64;
+        Some(CurvStats {
+            num_values,
+            num_nan: self.data.len() - num_values,
+            min: *sorted.first()?,
+            max: *sorted.last()?,
+            mean,
+            std: variance.sqrt(),
+            median: percentile(50.0)?,
+            p5: percentile(5.0)?,
+            p25: percentile(25.0)?,
+            p75: percentile(75.0)?,
+            p95: percentile(95.0)?,
+        })
+    }
+
+
+    /// Determine a value range given by two percentiles of the per-vertex values, e.g., to scale a colormap. See [`crate::util::vec32robust_range`].
+    ///
+    /// # Panics
+    ///
+    /// If `p_low` or `p_high` is not in the range 0 to 100, or if `p_low` is larger than `p_high`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let curv = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// let (min, max) = curv.robust_range(2.0, 98.0).unwrap();
+    /// let colors = neuroformats::colormap::values_to_colors(&curv.data, min, max);
+    /// ```
+    pub fn robust_range(&self, p_low: f32, p_high: f32) -> Option<(f32, f32)> {
+        vec32robust_range(&self.data, p_low, p_high)
+    }
+
+
     /// Read a Curvfile.
-    /// If the file's name ends with ".gz", the file is assumed to need GZip decoding. This is not typically the case
+    /// If the file is gzip-compressed, it is decompressed on the fly. This is not typically the case
     /// for FreeSurfer Curv files, but very handy (and it helps us to reduce the size of our test data).
-    pub fn from_file<P: AsRef<Path> + Copy>(path: P) -> Result<FsCurv> {
-        let gz = is_gz_file(&path);
-
-        let hdr = FsCurvHeader::from_file(path)?;
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsCurv> {
+        FsCurv::from_reader(open_maybe_compressed(path)?)
+    }
 
-        let file = BufReader::new(File::open(path)?);
 
-        let data: Vec<f32> = if gz {
-            FsCurv::curv_data_from_reader(BufReader::new(GzDecoder::new(file)), &hdr)
-        } else {
-            FsCurv::curv_data_from_reader(file, &hdr)
-        };
+    /// Read per-vertex data from the bytes of a curv file, which may be gzip-compressed.
+    ///
+    /// This does not need file system access, e.g., for use in WebAssembly. It returns an error and does not panic for arbitrary input.
+    pub fn from_bytes(bytes: &[u8]) -> Result<FsCurv> {
+        FsCurv::from_reader(&gunzip_if_compressed(bytes)?[..])
+    }
 
-        let curv = FsCurv { 
-            header : hdr,
-            data: data,
-        };
 
-        Ok(curv)
+    /// Read a curv file from the given byte stream, which must be at the start of the header.
+    ///
+    /// The stream is not GZip decoded. This function returns an error and does not panic for arbitrary input.
+    pub fn from_reader<S>(mut input: S) -> Result<FsCurv>
+    where
+        S: BufRead,
+    {
+        let hdr = FsCurvHeader::from_reader(&mut input)?;
+        let data = FsCurv::values_from_reader(input, &hdr)?;
+        Ok(FsCurv { header: hdr, data })
     }
 
 
-    pub fn curv_data_from_reader<S>(input: S, hdr: &FsCurvHeader) -> Vec<f32>
+    /// Read the per-vertex data of a curv file from a byte stream, which must be at the start of the header (the header is skipped).
+    pub fn curv_data_from_reader<S>(input: S, hdr: &FsCurvHeader) -> Result<Vec<f32>>
     where
         S: BufRead,
     {
-    
         let mut input = ByteOrdered::be(input);
 
         let hdr_size = 15;
-        
+
         // This is only read because we cannot seek in a GZ stream.
-        let mut hdr_data : Vec<u8> = Vec::with_capacity(hdr_size as usize);
         for _ in 1..=hdr_size {
-            hdr_data.push(input.read_u8().unwrap());
+            input.read_u8()?;
         }
+        FsCurv::values_from_reader(input.into_inner(), hdr)
+    }
 
-        let mut data : Vec<f32> = Vec::with_capacity(hdr.num_vertices as usize);
-        for _ in 1..=hdr.num_vertices {
-            data.push(input.read_f32().unwrap());
-        }
-        data
+
+    /// Read the per-vertex data of a curv file from a byte stream positioned directly after the header.
+    fn values_from_reader<S>(input: S, hdr: &FsCurvHeader) -> Result<Vec<f32>>
+    where
+        S: BufRead,
+    {
+        let mut input = ByteOrdered::be(input);
+        let num_vertices = usize::try_from(hdr.num_vertices).map_err(|_| NeuroformatsError::InvalidCurvFormat)?;
+        Ok(read_values(num_vertices, || input.read_f32())?)
     }
 }
 
@@ -187,9 +392,12 @@ impl FsCurv {
 #[cfg(test)]
 mod test { 
     use super::*;
+    #[cfg(feature = "fs")]
     use approx::assert_abs_diff_eq;
+    #[cfg(feature = "fs")]
     use tempfile::{tempdir};
 
+    #[cfg(feature = "fs")]
     #[test]
     fn the_demo_curv_file_can_be_read() {
         const CURV_FILE: &str = "resources/subjects_dir/subject1/surf/lh.thickness";
@@ -206,6 +414,7 @@ mod test {
         assert_abs_diff_eq!(5.0, max, epsilon = 1e-10);
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn a_curv_file_can_be_written_and_reread() {
         const CURV_FILE: &str = "resources/subjects_dir/subject1/surf/lh.thickness";
@@ -214,10 +423,9 @@ mod test {
         let dir = tempdir().unwrap();
 
         let tfile_path = dir.path().join("temp-curv-file.curv");
-        let tfile_path = tfile_path.to_str().unwrap();
-        write_curv(tfile_path, &curv);
+        write_curv(&tfile_path, &curv);
 
-        let curv_re = read_curv(tfile_path).unwrap();
+        let curv_re = read_curv(&tfile_path).unwrap();
 
         assert_eq!(149244, curv_re.header.num_vertices);
         assert_eq!(298484, curv_re.header.num_faces);
@@ -229,4 +437,83 @@ mod test {
         assert_abs_diff_eq!(0.0, min, epsilon = 1e-10);
         assert_abs_diff_eq!(5.0, max, epsilon = 1e-10);
     }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn curv_files_are_written_byte_identical() {
+        let dir = tempdir().unwrap();
+        for path in ["resources/subjects_dir/subject1/surf/lh.thickness", "resources/curv/tiny.curv"] {
+            let tfile_path = dir.path().join("temp-curv-file.curv");
+            write_curv_with_options(&tfile_path, &read_curv(path).unwrap(), &WriteOptions { strict: true, ..Default::default() }).unwrap();
+            assert_eq!(std::fs::read(path).unwrap(), std::fs::read(&tfile_path).unwrap());
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn computed_data_can_be_written_as_a_curv_file() {
+        let curv = FsCurv::from_data(vec![1.5, 2.0, 2.5]);
+        assert_eq!(3, curv.header.num_vertices);
+        let dir = tempdir().unwrap();
+        let tfile_path = dir.path().join("computed.curv");
+        write_curv(&tfile_path, &curv);
+        assert_eq!(curv, read_curv(&tfile_path).unwrap());
+    }
+
+    #[test]
+    fn a_tiny_curv_file_can_be_read_from_a_reader() {
+        let bytes = std::fs::read("resources/curv/tiny.curv").unwrap();
+        let curv = FsCurv::from_reader(&bytes[..]).unwrap();
+        assert_eq!(vec![1.5, 2.0, 2.5, 3.0, 0.5], curv.data);
+    }
+
+    #[test]
+    fn a_tiny_curv_file_can_be_read_from_bytes() {
+        let bytes = std::fs::read("resources/curv/tiny.curv").unwrap();
+        let curv = FsCurv::from_bytes(&bytes).unwrap();
+        assert_eq!(vec![1.5, 2.0, 2.5, 3.0, 0.5], curv.data);
+    }
+
+    #[test]
+    fn reading_corrupted_curv_files_does_not_panic() {
+        let bytes = std::fs::read("resources/curv/tiny.curv").unwrap();
+        for variant in crate::util::corrupted_variants(&bytes) {
+            let _ = FsCurv::from_reader(&variant[..]);
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_subset_of_a_curv_file_can_be_read() {
+        const CURV_FILE: &str = "resources/subjects_dir/subject1/surf/lh.thickness";
+        let curv = read_curv(CURV_FILE).unwrap();
+        let indices = [149243, 5, 0, 5, 70000];
+        let subset = read_curv_subset(CURV_FILE, &indices).unwrap();
+        let expected: Vec<f32> = indices.iter().map(|&i| curv.data[i]).collect();
+        assert_eq!(expected, subset);
+
+        assert!(read_curv_subset(CURV_FILE, &[]).unwrap().is_empty());
+        assert!(matches!(read_curv_subset(CURV_FILE, &[149244]), Err(NeuroformatsError::VertexIndexOutOfRange(149244, 149244))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn statistics_of_curv_data_can_be_computed() {
+        let curv = FsCurv::from_data(vec![2.0, f32::NAN, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        let stats = curv.stats().unwrap();
+        assert_eq!(8, stats.num_values);
+        assert_eq!(1, stats.num_nan);
+        assert_eq!((2.0, 9.0), (stats.min, stats.max));
+        assert_abs_diff_eq!(5.0, stats.mean);
+        assert_abs_diff_eq!(2.0, stats.std);
+        assert_eq!(4.5, stats.median);
+        assert_eq!(Some((stats.p5, stats.p95)), curv.robust_range(5.0, 95.0));
+        assert!(stats.p25 <= stats.median && stats.median <= stats.p75);
+        assert_eq!(None, FsCurv::from_data(vec![f32::NAN]).stats());
+
+        let thickness = read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        let (min, max) = vec32minmax(thickness.data.iter().copied(), true);
+        let (low, high) = thickness.robust_range(2.0, 98.0).unwrap();
+        assert!(min <= low && low < high && high <= max);
+    }
 }
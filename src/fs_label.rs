@@ -5,16 +5,22 @@
 //! vertices of a label form a spatially adjacent patch. Each vertex or voxel that is part of the label can be assigned a scalar value.
 
 
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write, LineWriter};
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::Write;
+#[cfg(feature = "fs")]
 use std::path::{Path};
 use std::fmt;
 
 
 use crate::error::{NeuroformatsError, Result};
-use crate::util::vec32minmax;
+use crate::util::{vec32minmax, prealloc_capacity, gunzip_if_compressed};
+#[cfg(feature = "fs")]
+use crate::util::{open_maybe_compressed, WriteOptions};
+use crate::fs_surface::BrainMesh;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsLabel {
     pub vertexes: Vec<FsLabelVertex>,
 }
@@ -74,6 +80,212 @@ impl FsLabel {
         }
         surface_data
     }
+
+
+    /// Check whether the coordinates stored in this surface label match the vertex coordinates of the given mesh.
+    ///
+    /// Labels store the coordinates of their vertices, which can get out of sync with the surface they refer to, e.g., when a
+    /// label was created on a different surface of the hemisphere. Use [`FsLabel::regenerate_coords`] to fix them.
+    ///
+    /// # Parameters
+    ///
+    /// * `mesh`: the mesh the label refers to.
+    /// * `tolerance`: the maximal allowed absolute difference per coordinate axis. Note that labels are stored as text, so use a small positive value like `1e-3`.
+    ///
+    /// # Return value
+    ///
+    /// The positions (in `self.vertexes`, not vertex indices) of the label vertices with coordinates that do not match the mesh. Returns
+    /// [`NeuroformatsError::VertexIndexOutOfRange`] if the label contains a vertex index that does not exist in the mesh.
+    pub fn validate_coords(&self, mesh: &BrainMesh, tolerance: f32) -> Result<Vec<usize>> {
+        let mut mismatches = Vec::new();
+        for (position, vertex) in self.vertexes.iter().enumerate() {
+            let coords = mesh.vertex_coords(checked_vertex_index(vertex.index, mesh)?);
+            let label_coords = [vertex.coord1, vertex.coord2, vertex.coord3];
+            if coords.iter().zip(label_coords.iter()).any(|(a, b)| (a - b).abs() > tolerance) {
+                mismatches.push(position);
+            }
+        }
+        Ok(mismatches)
+    }
+
+
+    /// Replace the coordinates stored in this surface label with the vertex coordinates of the given mesh.
+    ///
+    /// The vertex indices and values are not changed. Returns [`NeuroformatsError::VertexIndexOutOfRange`] and leaves the label unchanged
+    /// if the label contains a vertex index that does not exist in the mesh.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mut label = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").unwrap();
+    /// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// label.regenerate_coords(&white.mesh).unwrap();
+    /// neuroformats::write_label("/tmp/lh.entorhinal_exvivo.label", &label).unwrap();
+    /// ```
+    pub fn regenerate_coords(&mut self, mesh: &BrainMesh) -> Result<()> {
+        for vertex in self.vertexes.iter() {
+            checked_vertex_index(vertex.index, mesh)?;
+        }
+        for vertex in self.vertexes.iter_mut() {
+            let coords = mesh.vertex_coords(vertex.index as usize);
+            vertex.coord1 = coords[0];
+            vertex.coord2 = coords[1];
+            vertex.coord3 = coords[2];
+        }
+        Ok(())
+    }
+
+
+    /// Create a copy of this surface label with the coordinates of another surface of the same hemisphere.
+    ///
+    /// The vertex indices and values are kept, and the coordinates are taken from `mesh`. This is used to move a label between the white, pial
+    /// and inflated representations of a hemisphere, which share their vertex indices. Returns [`NeuroformatsError::VertexIndexOutOfRange`] if
+    /// the label contains a vertex index that does not exist in the mesh.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let white_label = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").unwrap();
+    /// let pial = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+    /// let pial_label = white_label.for_surface(&pial.mesh).unwrap();
+    /// ```
+    pub fn for_surface(&self, mesh: &BrainMesh) -> Result<FsLabel> {
+        let mut label = self.clone();
+        label.regenerate_coords(mesh)?;
+        Ok(label)
+    }
+
+
+    /// Find the border vertices of this surface label, i.e., the label vertices with at least one neighbor in the mesh that is not part of the label.
+    ///
+    /// Returns the vertex indices in ascending order, or [`NeuroformatsError::VertexIndexOutOfRange`] if the label contains a vertex index that
+    /// does not exist in the mesh.
+    pub fn border(&self, mesh: &BrainMesh) -> Result<Vec<usize>> {
+        let in_label = self.mesh_mask(mesh)?;
+        Ok(border_vertices(&in_label, &mesh.vertex_neighbors()))
+    }
+
+
+    /// Grow this surface label by the given number of rings of neighboring vertices.
+    ///
+    /// The vertices of the label are kept unchanged. The added vertices get their coordinates from `mesh` and the value `0.0`, and are
+    /// appended in ascending order of their index. Returns [`NeuroformatsError::VertexIndexOutOfRange`] if the label contains a vertex
+    /// index that does not exist in the mesh.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let cortex = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.cortex.label").unwrap();
+    /// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let rim = cortex.dilate(&white.mesh, 2).unwrap();
+    /// ```
+    pub fn dilate(&self, mesh: &BrainMesh, n_rings: usize) -> Result<FsLabel> {
+        let mut in_label = self.mesh_mask(mesh)?;
+        let neighbors = mesh.vertex_neighbors();
+        let mut added = vec![false; in_label.len()];
+        for _ in 0..n_rings {
+            let ring: Vec<usize> = border_vertices(&in_label, &neighbors).into_iter()
+                .flat_map(|v| neighbors[v].iter().copied())
+                .filter(|n| !in_label[*n])
+                .collect();
+            if ring.is_empty() {
+                break;
+            }
+            for v in ring {
+                in_label[v] = true;
+                added[v] = true;
+            }
+        }
+
+        let mut vertexes = self.vertexes.clone();
+        vertexes.extend(added.iter().enumerate().filter(|(_, a)| **a).map(|(v, _)| {
+            let coords = mesh.vertex_coords(v);
+            FsLabelVertex { index: v as i32, coord1: coords[0], coord2: coords[1], coord3: coords[2], value: 0.0 }
+        }));
+        Ok(FsLabel { vertexes })
+    }
+
+
+    /// Shrink this surface label by removing the given number of rings of border vertices, see [`FsLabel::border`].
+    ///
+    /// The remaining vertices are kept unchanged and in their order. Returns [`NeuroformatsError::VertexIndexOutOfRange`] if the label
+    /// contains a vertex index that does not exist in the mesh.
+    pub fn erode(&self, mesh: &BrainMesh, n_rings: usize) -> Result<FsLabel> {
+        let mut in_label = self.mesh_mask(mesh)?;
+        let neighbors = mesh.vertex_neighbors();
+        for _ in 0..n_rings {
+            let border = border_vertices(&in_label, &neighbors);
+            if border.is_empty() {
+                break;
+            }
+            for v in border {
+                in_label[v] = false;
+            }
+        }
+        Ok(FsLabel { vertexes: self.vertexes.iter().filter(|v| in_label[v.index as usize]).cloned().collect() })
+    }
+
+
+    /// Determine for each of the given number of vertices whether it is part of this label, ignoring label vertices with an index that is out of range.
+    pub(crate) fn vertex_mask(&self, num_vertices: usize) -> Vec<bool> {
+        let mut in_label = vec![false; num_vertices];
+        for vertex in self.vertexes.iter() {
+            if vertex.index >= 0 && (vertex.index as usize) < num_vertices {
+                in_label[vertex.index as usize] = true;
+            }
+        }
+        in_label
+    }
+
+
+    /// Determine for each vertex of the mesh whether it is part of this label, checking that all label vertex indices are valid for the mesh.
+    fn mesh_mask(&self, mesh: &BrainMesh) -> Result<Vec<bool>> {
+        let mut in_label = vec![false; mesh.num_vertices()];
+        for vertex in self.vertexes.iter() {
+            in_label[checked_vertex_index(vertex.index, mesh)?] = true;
+        }
+        Ok(in_label)
+    }
+}
+
+
+/// Set the per-vertex data of all vertices outside of the cortex label to the `fill` value, e.g., to exclude the medial wall.
+///
+/// FreeSurfer stores the vertices of the cortex in `label/?h.cortex.label`, all other vertices are part of the medial wall, where
+/// measures like the cortical thickness are meaningless. Use `f32::NAN` as the `fill` value to have them ignored by functions like
+/// [`FsAnnot::region_table`](crate::FsAnnot::region_table) or made transparent by the [`colormap`](crate::colormap) functions. Label vertices
+/// with an index that is out of range for the data are ignored.
+///
+/// # Examples
+///
+/// ```no_run
+/// let mut thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let cortex = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.cortex.label").unwrap();
+/// neuroformats::apply_cortex_mask(&mut thickness.data, &cortex, f32::NAN);
+/// ```
+pub fn apply_cortex_mask(data: &mut [f32], cortex: &FsLabel, fill: f32) {
+    let in_cortex = cortex.vertex_mask(data.len());
+    for (value, keep) in data.iter_mut().zip(in_cortex) {
+        if !keep {
+            *value = fill;
+        }
+    }
+}
+
+
+/// Find the vertices in the mask with at least one neighbor outside of it, in ascending order.
+fn border_vertices(in_label: &[bool], neighbors: &[Vec<usize>]) -> Vec<usize> {
+    (0..in_label.len()).filter(|v| in_label[*v] && neighbors[*v].iter().any(|n| !in_label[*n])).collect()
+}
+
+
+/// Convert a label vertex index into a mesh vertex index, checking that it is valid for the mesh.
+fn checked_vertex_index(index: i32, mesh: &BrainMesh) -> Result<usize> {
+    let num_vertices = mesh.num_vertices();
+    if index < 0 || index as usize >= num_vertices {
+        return Err(NeuroformatsError::VertexIndexOutOfRange(index as i64, num_vertices));
+    }
+    Ok(index as usize)
 }
 
 impl fmt::Display for FsLabel {    
@@ -84,6 +296,7 @@ impl fmt::Display for FsLabel {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsLabelVertex {
     pub index: i32,
     pub coord1: f32,
@@ -97,11 +310,12 @@ impl std::str::FromStr for FsLabelVertex {
     type Err = NeuroformatsError;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let mut iter = s.split_whitespace();
-        let index = iter.next().unwrap().parse::<i32>().expect("Expected vertex index of type i32.");
-        let coord1 = iter.next().unwrap().parse::<f32>().expect("Expected coord1 of type f32.");
-        let coord2 = iter.next().unwrap().parse::<f32>().expect("Expected coord2 of type f32.");
-        let coord3 = iter.next().unwrap().parse::<f32>().expect("Expected coord3 of type f32.");
-        let value = iter.next().unwrap().parse::<f32>().expect("Expected vertex value of type f32.");
+        let mut next = || iter.next().ok_or(NeuroformatsError::InvalidFsLabelFormat);
+        let index = next()?.parse::<i32>().map_err(|_| NeuroformatsError::InvalidFsLabelFormat)?;
+        let coord1 = next()?.parse::<f32>().map_err(|_| NeuroformatsError::InvalidFsLabelFormat)?;
+        let coord2 = next()?.parse::<f32>().map_err(|_| NeuroformatsError::InvalidFsLabelFormat)?;
+        let coord3 = next()?.parse::<f32>().map_err(|_| NeuroformatsError::InvalidFsLabelFormat)?;
+        let value = next()?.parse::<f32>().map_err(|_| NeuroformatsError::InvalidFsLabelFormat)?;
         Ok(FsLabelVertex{ index, coord1, coord2, coord3, value })
     }
 }
@@ -118,35 +332,64 @@ impl std::str::FromStr for FsLabelVertex {
 /// let first = &label.vertexes[0];
 /// println!("Vertex #{} has coordinates {} {} {} and is assigned value {}.", first.index, first.coord1, first.coord2, first.coord3, first.value);
 /// ```
+#[cfg(feature = "fs")]
 pub fn read_label<P: AsRef<Path>>(path: P) -> Result<FsLabel> {
+    FsLabel::from_file(path)
+}
 
-    let reader = BufReader::new(File::open(path)?);
 
-    // Read the file line by line using the lines() iterator from std::io::BufRead.
-    let mut lines = reader.lines();
-    // We ignore the first line at index 0: it is a comment line.
-    let _comment_line = lines.next().transpose()?;
-    // The line 1 (after comment) is the header
-    let hdr_num_entries: i32 = lines.next().transpose()?.and_then(|header| header.parse::<i32>().ok()).expect("Could not parse label header line.");
-    let mut vertexes = Vec::with_capacity(hdr_num_entries as usize);
-    for line in lines {
-        let line = line?;
-        let vertex = line.parse()?;
-        vertexes.push(vertex);
+impl FsLabel {
+    /// Read a label from a file in FreeSurfer label format, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsLabel> {
+        FsLabel::from_reader(open_maybe_compressed(path)?)
     }
 
-    if hdr_num_entries as usize != vertexes.len() {
-        Err(NeuroformatsError::InvalidFsLabelFormat)
-    } else {
-        Ok(FsLabel{ vertexes })
+
+    /// Read a label from the bytes of a label file, which may be gzip-compressed.
+    ///
+    /// This does not need file system access, e.g., for use in WebAssembly. It returns an error and does not panic for arbitrary input.
+    pub fn from_bytes(bytes: &[u8]) -> Result<FsLabel> {
+        FsLabel::from_reader(&gunzip_if_compressed(bytes)?[..])
+    }
+
+
+    /// Read a label from the given text input in FreeSurfer label format.
+    pub fn from_reader<S: BufRead>(input: S) -> Result<FsLabel> {
+        // Read the input line by line using the lines() iterator from std::io::BufRead.
+        let mut lines = input.lines();
+        // We ignore the first line at index 0: it is a comment line.
+        let _comment_line = lines.next().transpose()?;
+        // The line 1 (after comment) is the header
+        let hdr_num_entries: usize = lines.next().transpose()?.and_then(|header| header.trim().parse::<usize>().ok()).ok_or(NeuroformatsError::InvalidFsLabelFormat)?;
+        let mut vertexes = Vec::with_capacity(prealloc_capacity(hdr_num_entries));
+        for line in lines {
+            let line = line?;
+            let vertex = line.parse()?;
+            vertexes.push(vertex);
+        }
+        vertexes.shrink_to_fit();
+
+        if hdr_num_entries != vertexes.len() {
+            Err(NeuroformatsError::InvalidFsLabelFormat)
+        } else {
+            Ok(FsLabel{ vertexes })
+        }
     }
 }
 
 
 /// Write an FsLabel struct to a new file.
-pub fn write_label<P: AsRef<Path> + Copy>(path: P, label : &FsLabel) -> std::io::Result<()> {
-    let file = File::create(path)?;
-    let mut file = LineWriter::new(file);
+#[cfg(feature = "fs")]
+pub fn write_label<P: AsRef<Path>>(path: P, label : &FsLabel) -> std::io::Result<()> {
+    write_label_with_options(path, label, &WriteOptions::default())
+}
+
+
+/// Write an FsLabel struct to a new file, using the given [`WriteOptions`].
+#[cfg(feature = "fs")]
+pub fn write_label_with_options<P: AsRef<Path>>(path: P, label : &FsLabel, options: &WriteOptions) -> std::io::Result<()> {
+    let mut file = options.create(path)?;
 
     let header_lines = format!("# FreeSurfer label.\n{}\n", label.vertexes.len());
     let header_lines = header_lines.as_bytes();
@@ -158,17 +401,17 @@ pub fn write_label<P: AsRef<Path> + Copy>(path: P, label : &FsLabel) -> std::io:
         file.write_all(vline)?;
     }
 
-    file.flush()?;
-
-    Ok(())
+    options.finish(file)
 }
 
 
 #[cfg(test)]
 mod test { 
     use super::*;
+    #[cfg(feature = "fs")]
     use tempfile::{tempdir};
 
+    #[cfg(feature = "fs")]
     #[test]
     fn the_demo_surface_label_file_can_be_read() {
         const LABEL_FILE: &str = "resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label";
@@ -178,6 +421,20 @@ mod test {
         assert_eq!(expected_vertex_count, label.vertexes.len());
     }
 
+    #[test]
+    fn a_label_can_be_read_from_bytes() {
+        let label = FsLabel::from_bytes(b"#!ascii label\n2\n5 1.0 2.0 3.0 0.5\n7 4.0 5.0 6.0 1.5\n").unwrap();
+        assert_eq!(2, label.vertexes.len());
+        assert_eq!(7, label.vertexes[1].index);
+        assert_eq!(1.5, label.vertexes[1].value);
+
+        assert!(FsLabel::from_bytes(b"#!ascii label\nnot a count\n").is_err());
+        assert!(FsLabel::from_bytes(b"#!ascii label\n3\n5 1.0 2.0 3.0 0.5\n").is_err());
+        assert!(FsLabel::from_bytes(b"#!ascii label\n1\n5 1.0 2.0\n").is_err());
+        assert!(FsLabel::from_bytes(b"#!ascii label\n1\n5 1.0 x 3.0 0.5\n").is_err());
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn the_label_utility_functions_work() {
         const LABEL_FILE: &str = "resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label";
@@ -193,6 +450,75 @@ mod test {
         assert_eq!(false, label.is_binary());
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_coordinates_of_a_label_can_be_validated_and_regenerated() {
+        const LABEL_FILE: &str = "resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label";
+        let mut label = read_label(LABEL_FILE).unwrap();
+        let white = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        assert!(label.validate_coords(&white.mesh, 1e-3).unwrap().is_empty());
+
+        label.vertexes[3].coord2 += 1.0;
+        assert_eq!(vec![3], label.validate_coords(&white.mesh, 1e-3).unwrap());
+        label.regenerate_coords(&white.mesh).unwrap();
+        assert!(label.validate_coords(&white.mesh, 1e-3).unwrap().is_empty());
+
+        label.vertexes[0].index = 149244;
+        assert!(matches!(label.validate_coords(&white.mesh, 1e-3), Err(NeuroformatsError::VertexIndexOutOfRange(149244, 149244))));
+        assert!(label.regenerate_coords(&white.mesh).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_label_can_be_moved_to_another_surface() {
+        const LABEL_FILE: &str = "resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label";
+        let label = read_label(LABEL_FILE).unwrap();
+        let white = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let shifted = BrainMesh { vertices: white.mesh.vertices.iter().map(|c| c + 10.0).collect(), faces: white.mesh.faces.clone() };
+
+        let shifted_label = label.for_surface(&shifted).unwrap();
+        assert_eq!(label.vertexes.len(), shifted_label.vertexes.len());
+        assert_eq!(label.vertexes[0].index, shifted_label.vertexes[0].index);
+        assert_eq!(label.vertexes[0].value, shifted_label.vertexes[0].value);
+        assert!(shifted_label.validate_coords(&shifted, 1e-3).unwrap().is_empty());
+        assert_eq!(label.vertexes.len(), shifted_label.validate_coords(&white.mesh, 1e-3).unwrap().len());
+    }
+
+    #[test]
+    fn the_border_of_a_label_can_be_found_and_the_label_dilated_and_eroded() {
+        // A strip of 6 vertices: 0 - 1 - 2 - 3 - 4 - 5, triangulated with a second row of vertices 6 to 11.
+        let vertices = (0..12).flat_map(|v| vec![(v % 6) as f32, (v / 6) as f32, 0.0]).collect();
+        let faces = (0..5).flat_map(|i| vec![i, i + 1, i + 6, i + 1, i + 7, i + 6]).collect();
+        let mesh = BrainMesh { vertices, faces };
+        let label = FsLabel { vertexes: vec![0, 1, 2, 6, 7, 8].into_iter().map(|v| FsLabelVertex { index: v, coord1: 0.0, coord2: 0.0, coord3: 0.0, value: 1.0 }).collect() };
+
+        assert_eq!(vec![2, 8], label.border(&mesh).unwrap());
+
+        let dilated = label.dilate(&mesh, 1).unwrap();
+        let indices: Vec<i32> = dilated.vertexes.iter().map(|v| v.index).collect();
+        assert_eq!(vec![0, 1, 2, 6, 7, 8, 3, 9], indices);
+        assert_eq!(3.0, dilated.vertexes[6].coord1);
+        assert_eq!(0.0, dilated.vertexes[6].value);
+        assert_eq!(12, label.dilate(&mesh, 10).unwrap().vertexes.len());
+
+        let eroded = label.erode(&mesh, 1).unwrap();
+        assert_eq!(vec![0, 1, 6, 7], eroded.vertexes.iter().map(|v| v.index).collect::<Vec<i32>>());
+        assert!(label.erode(&mesh, 3).unwrap().vertexes.is_empty());
+        assert_eq!(label, label.dilate(&mesh, 0).unwrap());
+
+        let invalid = FsLabel { vertexes: vec![FsLabelVertex { index: 12, coord1: 0.0, coord2: 0.0, coord3: 0.0, value: 0.0 }] };
+        assert!(invalid.border(&mesh).is_err());
+    }
+
+    #[test]
+    fn a_cortex_mask_can_be_applied_to_data() {
+        let cortex = FsLabel { vertexes: vec![1, 3, 7].into_iter().map(|v| FsLabelVertex { index: v, coord1: 0.0, coord2: 0.0, coord3: 0.0, value: 0.0 }).collect() };
+        let mut data = vec![1.0, 2.0, 3.0, 4.0];
+        apply_cortex_mask(&mut data, &cortex, -1.0);
+        assert_eq!(vec![-1.0, 2.0, -1.0, 4.0], data);
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn a_label_file_can_be_written_and_reread() {
         const LABEL_FILE: &str = "resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label";
@@ -201,10 +527,9 @@ mod test {
         let dir = tempdir().unwrap();
 
         let tfile_path = dir.path().join("temp-file.label");
-        let tfile_path = tfile_path.to_str().unwrap();
-        write_label(tfile_path, &label).unwrap();
+        write_label(&tfile_path, &label).unwrap();
 
-        let label_re = read_label(tfile_path).unwrap();
+        let label_re = read_label(&tfile_path).unwrap();
         let expected_vertex_count: usize = 1085;
         assert_eq!(expected_vertex_count, label_re.vertexes.len());
     }
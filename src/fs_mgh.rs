@@ -1,17 +1,24 @@
 //! Functions for managing FreeSurfer brain volumes or other 3D or 4D data in binary 'MGH' files.
 
-use flate2::bufread::GzDecoder;
+#[cfg(feature = "fs")]
 use flate2::Compression;
 use byteordered::{ByteOrdered, Endianness};
 use ndarray::{Array, Array1, Array2, Array4, Dim, array};
 
 
+#[cfg(feature = "fs")]
 use std::{fs::File};
-use std::io::{BufReader, BufRead, BufWriter, Write};
+use std::convert::TryFrom;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::{BufWriter, Write};
 use std::path::{Path};
 use std::fmt;
 
 use crate::error::{NeuroformatsError, Result};
+use crate::util::{read_values, gunzip_if_compressed};
+#[cfg(feature = "fs")]
+use crate::util::{open_maybe_compressed, write_block, WriteOptions};
 
 const MGH_VERSION_CODE: i32 = 1;
 
@@ -23,11 +30,25 @@ pub const MRI_INT : i32 = 1;
 pub const MRI_FLOAT : i32 = 3;
 /// FreeSurfer MRI data type for `i16`, used in the `dtype` field of [`FsMghHeader`].
 pub const MRI_SHORT : i32 = 4;
+/// FreeSurfer MRI data type for `long`, used in the `dtype` field of [`FsMghHeader`]. Not supported in MGH files, as its size depends on the platform that wrote the file.
+pub const MRI_LONG : i32 = 2;
+/// FreeSurfer MRI data type for tensors with 9 `f32` values per voxel, used in the `dtype` field of [`FsMghHeader`]. Volumes of this type are read as `MRI_FLOAT` data with 9 frames, like FreeSurfer does.
+pub const MRI_TENSOR : i32 = 6;
 
 const MGH_DATA_START : i32 = 284; // The index in bytes where the data part starts in an MGH file.
 
+/// MGH footer tag containing a command line that was used to create or modify the file. Files can contain several of these tags.
+pub const MGH_TAG_CMDLINE : i32 = 3;
+/// MGH footer tag containing the path of the Talairach transform file of the subject.
+pub const MGH_TAG_MGH_XFORM : i32 = 31;
+/// MGH footer tag containing the phase encoding direction as a string.
+pub const MGH_TAG_PEDIR : i32 = 41;
+/// MGH footer tag containing the field strength of the scanner in Tesla as a big endian `f32`.
+pub const MGH_TAG_FIELDSTRENGTH : i32 = 43;
+
 /// Models the header of a FreeSurfer MGH file.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsMghHeader {
     pub mgh_format_version: i32,
     pub dim1len: i32,
@@ -44,6 +65,7 @@ pub struct FsMghHeader {
 
 /// Models the data part of a FreeSurfer MGH file.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsMghData {
     pub mri_uchar: Option<Array4<u8>>,
     pub mri_float: Option<Array4<f32>>,
@@ -51,11 +73,33 @@ pub struct FsMghData {
     pub mri_short: Option<Array4<i16>>,
 }
 
+/// Models a tag in the footer of a FreeSurfer MGH file. The meaning of the data depends on the tag `id`, see [`MGH_TAG_CMDLINE`] and the other tag constants.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FsMghTag {
+    pub id: i32,
+    pub data: Vec<u8>,
+}
+
+/// Models the optional footer of a FreeSurfer MGH file, which follows the data part and contains the MR acquisition parameters and tags.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FsMghFooter {
+    pub tr: f32,  // Repetition time in ms.
+    pub flip_angle: f32,  // Flip angle in radians.
+    pub te: f32,  // Echo time in ms.
+    pub ti: f32,  // Inversion time in ms.
+    pub fov: f32,  // Field of view in mm.
+    pub tags: Vec<FsMghTag>,
+}
+
 /// Models a FreeSurfer MGH file.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsMgh {
     pub header: FsMghHeader,
-    pub data: FsMghData
+    pub data: FsMghData,
+    pub footer: Option<FsMghFooter>,
 }
 
 
@@ -80,40 +124,38 @@ impl Default for FsMghHeader {
 /// The header of an MGH/MGZ file.
 impl FsMghHeader {
     
-    /// Read an MGH header from a file.
+    /// Read an MGH header from an MGH or MGZ file. Compression is detected from the file contents.
+    #[cfg(feature = "fs")]
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsMghHeader> {
-        let gz = is_mgz_file(&path);
-        let mut file = BufReader::new(File::open(path)?);
-
-        if gz {
-            FsMghHeader::from_reader(&mut BufReader::new(GzDecoder::new(file)))
-        } else {
-            FsMghHeader::from_reader(&mut file)
-        }
-        
+        FsMghHeader::from_reader(&mut open_maybe_compressed(path)?)
     }
 
 
     /// Read an MGH header from the given byte stream.
     /// It is assumed that the input is currently at the start of the
     /// header.
+    ///
+    /// MGH files are big endian. Files written in little endian byte order by some third-party tools are detected from the version field and read as well.
     pub fn from_reader<S>(input: &mut S) -> Result<FsMghHeader> where S: BufRead,
     {
         let mut hdr = FsMghHeader::default();
-    
-        let mut input = ByteOrdered::be(input);
 
-        hdr.mgh_format_version = input.read_i32()?;
+        let mut version_bytes = [0u8; 4];
+        input.read_exact(&mut version_bytes)?;
+        let endianness = mgh_endianness(version_bytes)?;
+        hdr.mgh_format_version = MGH_VERSION_CODE;
 
-        if hdr.mgh_format_version != MGH_VERSION_CODE {
-            return Err(NeuroformatsError::InvalidFsMghFormat);
-        }
+        let mut input = ByteOrdered::runtime(input, endianness);
 
         hdr.dim1len = input.read_i32()?;
         hdr.dim2len = input.read_i32()?;
         hdr.dim3len = input.read_i32()?;
         hdr.dim4len = input.read_i32()?;
 
+        if hdr.checked_num_voxels().is_none() {
+            return Err(NeuroformatsError::InvalidFsMghFormat);
+        }
+
         hdr.dtype = input.read_i32()?;
         hdr.dof = input.read_i32()?;
 
@@ -137,6 +179,17 @@ impl FsMghHeader {
     }
 
 
+    /// Get the header describing the data as it is stored in an [`FsMgh`] after reading. `MRI_TENSOR` volumes are stored as `MRI_FLOAT` data with 9 frames.
+    fn as_read(&self) -> FsMghHeader {
+        let mut hdr = self.clone();
+        if hdr.dtype == MRI_TENSOR {
+            hdr.dtype = MRI_FLOAT;
+            hdr.dim4len = 9;
+        }
+        hdr
+    }
+
+
     /// Compute the vox2ras matrix from the RAS data in the header, if available.
     ///
     /// The vox2ras matrix is a 4x4 f32 matrix. You can use it to find the RAS coordinates of a voxel
@@ -152,6 +205,95 @@ impl FsMghHeader {
     /// let my_voxel_ras = vox2ras.dot(&my_voxel_ijk);
     /// ```
     pub fn vox2ras(&self) -> Result<Array2<f32>> {
+        self.vox2ras_with_center(self.p_xyz_c)
+    }
+
+
+    /// Compute the tkregister vox2ras matrix, also known as the 'vox2ras-tkr' or 'torig' matrix.
+    ///
+    /// Like FreeSurfer, this ignores the direction cosines of the volume and always uses the orientation of conformed (LIA)
+    /// volumes, with the center of the volume at RAS coordinate `(0, 0, 0)`. The coordinates of FreeSurfer surfaces (the 'surface RAS'
+    /// space) are defined in this space, so use this matrix when you need to relate surface vertices to voxels of the volumes of the
+    /// same subject. For conformed volumes, this is [`FsMghHeader::vox2ras`] with the center moved to the origin.
+    pub fn vox2ras_tkr(&self) -> Result<Array2<f32>> {
+        if self.is_ras_good != 1 {
+            return Err(NeuroformatsError::NoRasInformationInHeader);
+        }
+        let [dc, dr, ds] = self.delta;
+        let (nc, nr, ns) = (self.dim1len as f32, self.dim2len as f32, self.dim3len as f32);
+        Ok(array![
+            [-dc, 0., 0., dc * nc / 2.],
+            [0., 0., ds, -ds * ns / 2.],
+            [0., -dr, 0., dr * nr / 2.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+
+    /// Compute the ras2vox matrix, the inverse of [`FsMghHeader::vox2ras`].
+    pub fn ras2vox(&self) -> Result<Array2<f32>> {
+        Ok(affine_inverse(&self.vox2ras()?))
+    }
+
+
+    /// Compute the tkregister ras2vox matrix, the inverse of [`FsMghHeader::vox2ras_tkr`].
+    ///
+    /// Use this matrix to find the voxel that contains a vertex of a surface of the same subject.
+    pub fn ras2vox_tkr(&self) -> Result<Array2<f32>> {
+        Ok(affine_inverse(&self.vox2ras_tkr()?))
+    }
+
+
+    /// Get the number of voxels per frame, i.e., the product of the first 3 dimensions.
+    pub fn num_voxels_per_frame(&self) -> usize {
+        self.dim1len as usize * self.dim2len as usize * self.dim3len as usize
+    }
+
+
+    /// Get the total number of voxels over all frames, or `None` if a dimension is negative or the dimensions are too large for an array.
+    fn checked_num_voxels(&self) -> Option<usize> {
+        let mut num_voxels: usize = 1;
+        // Like ndarray, we also require that the product of the non-zero dimensions fits into an isize.
+        let mut nonzero_product: isize = 1;
+        for len in [self.dim1len, self.dim2len, self.dim3len, self.dim4len] {
+            num_voxels = num_voxels.checked_mul(usize::try_from(len).ok()?)?;
+            nonzero_product = nonzero_product.checked_mul(len.max(1) as isize)?;
+        }
+        Some(num_voxels)
+    }
+
+
+    /// Estimate the number of bytes of memory needed to hold the data of a volume with this header, e.g., to check whether a file fits into memory before reading it.
+    ///
+    /// This is the number of voxels over all frames times the size of the data type, and saturates at `usize::MAX` for volumes that are too large.
+    /// Returns `None` if the header is invalid or the data type is not supported.
+    pub fn estimated_memory_bytes(&self) -> Option<usize> {
+        let hdr = self.as_read();
+        let bytes_per_voxel = match hdr.dtype {
+            MRI_UCHAR => 1,
+            MRI_SHORT => 2,
+            MRI_INT | MRI_FLOAT => 4,
+            _ => return None,
+        };
+        let mut num_voxels: usize = 1;
+        for len in [hdr.dim1len, hdr.dim2len, hdr.dim3len, hdr.dim4len] {
+            num_voxels = num_voxels.saturating_mul(usize::try_from(len).ok()?);
+        }
+        Some(num_voxels.saturating_mul(bytes_per_voxel))
+    }
+
+
+    /// Compute the flat index of a voxel in the data of an MGH file.
+    ///
+    /// The data in MGH files is stored with the first dimension (the column index) varying fastest. This is also the logical
+    /// iteration order of the data arrays in [`FsMghData`], so the index can be used with the arrays' `as_slice()` or `iter()`.
+    pub fn voxel_flat_index(&self, crs: [usize; 3], frame: usize) -> usize {
+        crs[0] + self.dim1len as usize * (crs[1] + self.dim2len as usize * (crs[2] + self.dim3len as usize * frame))
+    }
+
+
+    /// Compute a vox2ras matrix for the volume, mapping the center voxel to the given RAS coordinates.
+    fn vox2ras_with_center(&self, center_ras: [f32; 3]) -> Result<Array2<f32>> {
         if self.is_ras_good != 1 as i16 {
             return Err(NeuroformatsError::NoRasInformationInHeader);
         }
@@ -169,7 +311,7 @@ impl FsMghHeader {
         let p_crs_c : Array1<f32> = array![(self.dim1len/2) as f32, (self.dim2len/2) as f32, (self.dim3len/2) as f32];
 
         // The RAS coordinates (aka x,y,z) of the center.
-        let p_xyz_c : Array1<f32> = array![self.p_xyz_c[0], self.p_xyz_c[1], self.p_xyz_c[2]];
+        let p_xyz_c : Array1<f32> = array![center_ras[0], center_ras[1], center_ras[2]];
 
         // The x,y,z location at CRS=0,0,0 (also known as P0 RAS or 'first voxel RAS').
         let p_xyz_0 : Array1<f32> = p_xyz_c - (mdc_scaled.t().dot(&p_crs_c));
@@ -194,40 +336,142 @@ impl FsMghHeader {
 }
 
 
+impl FsMghTag {
+    /// Interpret the tag data as a string, without the terminating zero bytes. Invalid UTF-8 sequences are replaced.
+    pub fn text(&self) -> String {
+        let end = self.data.iter().rposition(|b| *b != 0).map_or(0, |idx| idx + 1);
+        String::from_utf8_lossy(&self.data[..end]).into_owned()
+    }
+}
+
+
+impl FsMghFooter {
+
+    /// Read an MGH footer from the given byte stream, which must be positioned directly after the data part.
+    ///
+    /// # Return value
+    ///
+    /// `None` if the stream ends before the MR parameters are complete, as the footer is optional. Parsing of the tags stops at the first tag
+    /// with an invalid length or an old tag format without a length, and the remaining data is ignored.
+    pub fn from_reader<S>(input: &mut S) -> Result<Option<FsMghFooter>> where S: BufRead, {
+        FsMghFooter::from_reader_with_endianness(input, Endianness::Big)
+    }
+
+
+    /// Read an MGH footer in the given byte order, see [`FsMghFooter::from_reader`]. The data of the tags is not converted.
+    fn from_reader_with_endianness<S>(input: &mut S, endianness: Endianness) -> Result<Option<FsMghFooter>> where S: BufRead, {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        if bytes.len() < 20 {
+            return Ok(None);
+        }
+        let big = endianness == Endianness::Big;
+        let read_f32 = |offset: usize| {
+            let b = [bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]];
+            if big { f32::from_be_bytes(b) } else { f32::from_le_bytes(b) }
+        };
+        let mut footer = FsMghFooter { tr: read_f32(0), flip_angle: read_f32(4), te: read_f32(8), ti: read_f32(12), fov: read_f32(16), tags: Vec::new() };
+
+        let mut rest = &bytes[20..];
+        while rest.len() >= 12 {
+            let id_bytes = [rest[0], rest[1], rest[2], rest[3]];
+            let id = if big { i32::from_be_bytes(id_bytes) } else { i32::from_le_bytes(id_bytes) };
+            let mut len_bytes = [0u8; 8];
+            len_bytes.copy_from_slice(&rest[4..12]);
+            let len = if big { i64::from_be_bytes(len_bytes) } else { i64::from_le_bytes(len_bytes) };
+            match usize::try_from(len) {
+                Ok(len) if len <= rest.len() - 12 => {
+                    footer.tags.push(FsMghTag { id, data: rest[12..12 + len].to_vec() });
+                    rest = &rest[12 + len..];
+                },
+                _ => break,
+            }
+        }
+        Ok(Some(footer))
+    }
+
+
+    /// Get the first tag with the given id, if any.
+    pub fn tag(&self, id: i32) -> Option<&FsMghTag> {
+        self.tags.iter().find(|tag| tag.id == id)
+    }
+
+
+    /// Get the command lines stored in the [`MGH_TAG_CMDLINE`] tags, i.e., the processing history of the file.
+    pub fn cmdlines(&self) -> Vec<String> {
+        self.tags.iter().filter(|tag| tag.id == MGH_TAG_CMDLINE).map(|tag| tag.text()).collect()
+    }
+
+
+    /// Get the scanner field strength in Tesla from the [`MGH_TAG_FIELDSTRENGTH`] tag, if available.
+    pub fn field_strength(&self) -> Option<f32> {
+        self.tag(MGH_TAG_FIELDSTRENGTH).filter(|tag| tag.data.len() == 4).map(|tag| f32::from_be_bytes([tag.data[0], tag.data[1], tag.data[2], tag.data[3]]))
+    }
+}
+
+
 impl FsMgh {
 
     /// Read an MGH or MGZ file.
-    pub fn from_file<P: AsRef<Path> + Copy>(path: P) -> Result<FsMgh> {
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsMgh> {
+        let path = path.as_ref();
 
         let hdr : FsMghHeader = FsMghHeader::from_file(path)?;
 
-        let gz = is_mgz_file(&path);
-        let mut file = BufReader::new(File::open(path)?);
-
-        let data = 
-        if gz {
-            FsMgh::data_from_reader(&mut BufReader::new(GzDecoder::new(file)), &hdr)?
-        } else {
-            FsMgh::data_from_reader(&mut file, &hdr)?
-        };
+        let (data, footer) = FsMgh::data_and_footer_from_reader(&mut open_maybe_compressed(path)?, &hdr)?;
 
         let mgh = FsMgh {
-            header : hdr,
+            header : hdr.as_read(),
             data : data,
+            footer,
         };
         Ok(mgh)
     }
 
 
+    /// Read an MGH or MGZ volume from the bytes of an MGH or MGZ file, which may be gzip-compressed.
+    ///
+    /// This does not need file system access, e.g., for use in WebAssembly. It returns an error and does not panic for arbitrary input.
+    pub fn from_bytes(bytes: &[u8]) -> Result<FsMgh> {
+        FsMgh::from_reader(&mut &gunzip_if_compressed(bytes)?[..])
+    }
+
+
+    /// Read an uncompressed MGH file from the given byte stream, which must be at the start of the header.
+    ///
+    /// Wrap the stream in a [`flate2::bufread::GzDecoder`] to read MGZ data. This function returns an error and does not panic for arbitrary input.
+    pub fn from_reader<S>(input: &mut S) -> Result<FsMgh> where S: BufRead, {
+        // The header is parsed twice (see data_from_reader), so we buffer the stream, which cannot be rewound in general.
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        let hdr = FsMghHeader::from_reader(&mut &bytes[..])?;
+        let (data, footer) = FsMgh::data_and_footer_from_reader(&mut &bytes[..], &hdr)?;
+        Ok(FsMgh { header: hdr.as_read(), data, footer })
+    }
+
+
     /// Read MGH data from a reader. It is assumed that position is before the header.
+    ///
+    /// Data of `MRI_TENSOR` volumes is returned as `MRI_FLOAT` data with 9 frames. Other data types than those with constants in this module are not supported.
     pub fn data_from_reader<S>(file: &mut S, hdr: &FsMghHeader) -> Result<FsMghData> where S: BufRead, {
+        Ok(FsMgh::data_and_footer_from_reader(file, hdr)?.0)
+    }
+
+
+    /// Read MGH data and the optional footer from a reader. It is assumed that position is before the header.
+    fn data_and_footer_from_reader<S>(file: &mut S, hdr: &FsMghHeader) -> Result<(FsMghData, Option<FsMghFooter>)> where S: BufRead, {
+        let hdr = &hdr.as_read();
 
         let vol_dim = Dim([hdr.dim1len as usize, hdr.dim2len as usize, hdr.dim3len as usize, hdr.dim4len as usize]);
 
-        let mut file = ByteOrdered::be(file);
+        let mut version_bytes = [0u8; 4];
+        file.read_exact(&mut version_bytes)?;
+        let endianness = mgh_endianness(version_bytes)?;
+        let mut file = ByteOrdered::runtime(file, endianness);
 
         // Skip or read to end of header.
-        for _ in 1..=MGH_DATA_START {
+        for _ in 5..=MGH_DATA_START {
             let _discarded = file.read_u8()?;
         }
 
@@ -236,34 +480,22 @@ impl FsMgh {
         let mut data_mri_float = None;
         let mut data_mri_short = None;
 
-        let num_voxels : usize = (hdr.dim1len * hdr.dim2len * hdr.dim3len * hdr.dim4len) as usize; 
+        let num_voxels : usize = hdr.checked_num_voxels().ok_or(NeuroformatsError::InvalidFsMghFormat)?;
 
         if hdr.dtype == MRI_UCHAR {
-            let mut mgh_data : Vec<u8> = Vec::with_capacity(num_voxels);
-            for _ in 1..=num_voxels {
-                mgh_data.push(file.read_u8()?);
-            }
+            let mgh_data : Vec<u8> = read_values(num_voxels, || file.read_u8())?;
             data_mri_uchar = Some(Array::from_shape_vec(vol_dim, mgh_data).unwrap());
         } else if hdr.dtype == MRI_INT {
-            let mut mgh_data : Vec<i32> = Vec::with_capacity(num_voxels);
-            for _ in 1..=num_voxels {
-                mgh_data.push(file.read_i32()?);
-            }
+            let mgh_data : Vec<i32> = read_values(num_voxels, || file.read_i32())?;
             data_mri_int = Some(Array::from_shape_vec(vol_dim, mgh_data).unwrap());
         } else if hdr.dtype == MRI_FLOAT {
-            let mut mgh_data : Vec<f32> = Vec::with_capacity(num_voxels);
-            for _ in 1..=num_voxels {
-                mgh_data.push(file.read_f32()?);
-            }
+            let mgh_data : Vec<f32> = read_values(num_voxels, || file.read_f32())?;
             data_mri_float = Some(Array::from_shape_vec(vol_dim, mgh_data).unwrap());
         } else if hdr.dtype == MRI_SHORT {
-            let mut mgh_data : Vec<i16> = Vec::with_capacity(num_voxels);
-            for _ in 1..=num_voxels {
-                mgh_data.push(file.read_i16()?);
-            }
+            let mgh_data : Vec<i16> = read_values(num_voxels, || file.read_i16())?;
             data_mri_short = Some(Array::from_shape_vec(vol_dim, mgh_data).unwrap());
         } else {
-            return Err(NeuroformatsError::UnsupportedMriDataTypeInMgh);
+            return Err(NeuroformatsError::UnsupportedMriDataTypeInMgh(hdr.dtype, hdr.dim()));
         }
 
         let mgh_data = FsMghData {
@@ -272,7 +504,8 @@ impl FsMgh {
             mri_float : data_mri_float,
             mri_short : data_mri_short,
         };
-        Ok(mgh_data)
+        let footer = FsMghFooter::from_reader_with_endianness(file.into_inner(), endianness)?;
+        Ok((mgh_data, footer))
     }
 
     /// Get dimensions of the MGH data.
@@ -287,6 +520,166 @@ impl FsMgh {
     pub fn vox2ras(&self) -> Result<Array2<f32>> {
         self.header.vox2ras()
     }
+
+
+    /// Get all voxel values of the volume as `f32`, in the order in which they are stored in the file.
+    ///
+    /// Values of integer data types are converted. See [`FsMghHeader::voxel_flat_index`] to compute the index of a voxel.
+    ///
+    /// # Return value
+    ///
+    /// A vector containing all voxel values, or [`NeuroformatsError::UnsupportedMriDataTypeInMgh`] if the data part matching the `dtype` of the header is not set.
+    pub fn data_as_f32(&self) -> Result<Vec<f32>> {
+        let values = match self.header.dtype {
+            MRI_UCHAR => self.data.mri_uchar.as_ref().map(|d| d.iter().map(|v| *v as f32).collect()),
+            MRI_INT => self.data.mri_int.as_ref().map(|d| d.iter().map(|v| *v as f32).collect()),
+            MRI_FLOAT => self.data.mri_float.as_ref().map(|d| d.iter().copied().collect()),
+            MRI_SHORT => self.data.mri_short.as_ref().map(|d| d.iter().map(|v| *v as f32).collect()),
+            _ => None,
+        };
+        values.ok_or(NeuroformatsError::UnsupportedMriDataTypeInMgh(self.header.dtype, self.header.dim()))
+    }
+
+
+    /// Check that the data part matching the `dtype` of the header is set, and return [`NeuroformatsError::UnsupportedMriDataTypeInMgh`] otherwise.
+    pub(crate) fn check_data(&self) -> Result<()> {
+        let has_data = match self.header.dtype {
+            MRI_UCHAR => self.data.mri_uchar.is_some(),
+            MRI_INT => self.data.mri_int.is_some(),
+            MRI_FLOAT => self.data.mri_float.is_some(),
+            MRI_SHORT => self.data.mri_short.is_some(),
+            _ => false,
+        };
+        if has_data { Ok(()) } else { Err(NeuroformatsError::UnsupportedMriDataTypeInMgh(self.header.dtype, self.header.dim())) }
+    }
+
+
+    /// Get the voxel values of one frame of the volume as `f32`, in the order of [`FsMgh::data_as_f32`]. Use [`FsMghHeader::voxel_flat_index`] with frame `0` to compute the index of a voxel.
+    ///
+    /// # Return value
+    ///
+    /// A vector containing the voxel values of the frame, or [`NeuroformatsError::UnsupportedMriDataTypeInMgh`] if the data part matching the `dtype` of the header is not set.
+    ///
+    /// # Panics
+    ///
+    /// If `frame` is out of range for this volume.
+    pub fn frame_as_f32(&self, frame: usize) -> Result<Vec<f32>> {
+        fn frame_of<T: Copy + Into<f64>>(data: &Option<Array4<T>>, frame: usize, num_voxels: usize) -> Option<Vec<f32>> {
+            data.as_ref().map(|d| d.iter().skip(frame * num_voxels).take(num_voxels).map(|v| (*v).into() as f32).collect())
+        }
+        assert!(frame < self.header.dim4len as usize, "Frame {} is out of range.", frame);
+        let num_voxels = self.header.num_voxels_per_frame();
+        let values = match self.header.dtype {
+            MRI_UCHAR => frame_of(&self.data.mri_uchar, frame, num_voxels),
+            MRI_INT => frame_of(&self.data.mri_int, frame, num_voxels),
+            MRI_FLOAT => frame_of(&self.data.mri_float, frame, num_voxels),
+            MRI_SHORT => frame_of(&self.data.mri_short, frame, num_voxels),
+            _ => None,
+        };
+        values.ok_or(NeuroformatsError::UnsupportedMriDataTypeInMgh(self.header.dtype, self.header.dim()))
+    }
+
+
+    /// Get the voxel values of all frames of the volume as `f32`, one vector per frame, see [`FsMgh::frame_as_f32`].
+    ///
+    /// For per-vertex time series stored as MGH files with dimensions (number of vertices, 1, 1, number of frames), this gives the values
+    /// of each vertex for each frame, e.g., for [`crate::BrainMesh::to_gltf_animated`].
+    ///
+    /// # Return value
+    ///
+    /// A vector with the voxel values of each frame, or [`NeuroformatsError::UnsupportedMriDataTypeInMgh`] if the data part matching the `dtype` of the header is not set.
+    pub fn frames_as_f32(&self) -> Result<Vec<Vec<f32>>> {
+        let values = self.data_as_f32()?;
+        let num_voxels = self.header.num_voxels_per_frame();
+        Ok((0..self.header.dim4len as usize).map(|frame| values[frame * num_voxels..(frame + 1) * num_voxels].to_vec()).collect())
+    }
+
+
+    /// Iterate over all voxels of the volume, as tuples of the column, row, slice and frame indices and the voxel value.
+    ///
+    /// Values of integer data types are converted to `f32`. The voxels are visited in the order of [`FsMgh::data_as_f32`], i.e., the indices
+    /// match [`FsMghHeader::voxel_flat_index`]. If the data part matching the `dtype` of the header is not set, the iterator is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mgh = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/brain.mgz").unwrap();
+    /// let num_brain_voxels = mgh.iter_voxels().filter(|(_i, _j, _k, t, value)| *t == 0 && *value > 0.0).count();
+    /// ```
+    pub fn iter_voxels(&self) -> Box<dyn Iterator<Item = (usize, usize, usize, usize, f32)> + '_> {
+        fn voxels<T: Copy + Into<f64>>(data: &Option<Array4<T>>, dim: [usize; 4]) -> Box<dyn Iterator<Item = (usize, usize, usize, usize, f32)> + '_> {
+            match data {
+                Some(d) => Box::new(d.iter().enumerate().map(move |(idx, v)| {
+                    (idx % dim[0], idx / dim[0] % dim[1], idx / (dim[0] * dim[1]) % dim[2], idx / (dim[0] * dim[1] * dim[2]), (*v).into() as f32)
+                })),
+                None => Box::new(std::iter::empty()),
+            }
+        }
+        let dim = self.dim();
+        match self.header.dtype {
+            MRI_UCHAR => voxels(&self.data.mri_uchar, dim),
+            MRI_INT => voxels(&self.data.mri_int, dim),
+            MRI_FLOAT => voxels(&self.data.mri_float, dim),
+            MRI_SHORT => voxels(&self.data.mri_short, dim),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+
+
+    /// Create an MGH volume with `MRI_FLOAT` data from voxel values in file order.
+    ///
+    /// The dimensions and RAS information are taken from the given header, but the `dtype` is set to `MRI_FLOAT`. The length of `values` must match the dimensions of the header.
+    pub fn from_f32_values(header: &FsMghHeader, values: Vec<f32>) -> Result<FsMgh> {
+        let mut header = header.clone();
+        header.dtype = MRI_FLOAT;
+        let expected = header.num_voxels_per_frame() * header.dim4len as usize;
+        if values.len() != expected {
+            return Err(NeuroformatsError::VoxelCountMismatch(expected, values.len()));
+        }
+        let vol_dim = Dim(header.dim());
+        let data = FsMghData {
+            mri_uchar: None,
+            mri_int: None,
+            mri_float: Some(Array::from_shape_vec(vol_dim, values).unwrap()),
+            mri_short: None,
+        };
+        Ok(FsMgh { header, data, footer: None })
+    }
+}
+
+
+/// Check that the data of a volume has the given dimensions, see [`FsMghHeader::dim`].
+pub(crate) fn check_shape<T>(data: &Array4<T>, dim: [usize; 4]) -> Result<()> {
+    if data.shape() == dim {
+        Ok(())
+    } else {
+        Err(NeuroformatsError::VoxelCountMismatch(dim.iter().product(), data.len()))
+    }
+}
+
+
+/// Invert a 4x4 affine matrix, i.e., a matrix with last row `0, 0, 0, 1`.
+///
+/// The upper left 3x3 matrix must be invertible, which is always the case for valid vox2ras matrices.
+pub(crate) fn affine_inverse(m: &Array2<f32>) -> Array2<f32> {
+    let a = |i: usize, j: usize| m[[i, j]];
+    let det = a(0, 0) * (a(1, 1) * a(2, 2) - a(1, 2) * a(2, 1))
+        - a(0, 1) * (a(1, 0) * a(2, 2) - a(1, 2) * a(2, 0))
+        + a(0, 2) * (a(1, 0) * a(2, 1) - a(1, 1) * a(2, 0));
+    let mut inv : Array2<f32> = Array::zeros((4, 4));
+    for i in 0..3 {
+        for j in 0..3 {
+            // Cofactor of element (j, i), which yields the adjugate matrix.
+            let (r1, r2) = ((j + 1) % 3, (j + 2) % 3);
+            let (c1, c2) = ((i + 1) % 3, (i + 2) % 3);
+            inv[[i, j]] = (a(r1, c1) * a(r2, c2) - a(r1, c2) * a(r2, c1)) / det;
+        }
+    }
+    for i in 0..3 {
+        inv[[i, 3]] = -(inv[[i, 0]] * a(0, 3) + inv[[i, 1]] * a(1, 3) + inv[[i, 2]] * a(2, 3));
+    }
+    inv[[3, 3]] = 1.;
+    inv
 }
 
 
@@ -336,11 +729,28 @@ where
 /// assert_eq!(mgh.header.dtype, neuroformats::MRI_UCHAR);
 /// let voxels = mgh.data.mri_uchar.unwrap();
 /// ```
-pub fn read_mgh<P: AsRef<Path> + Copy>(path: P) -> Result<FsMgh> {
+#[cfg(feature = "fs")]
+pub fn read_mgh<P: AsRef<Path>>(path: P) -> Result<FsMgh> {
     FsMgh::from_file(path)
 }
 
 
+/// Read only the header of an MGH or MGZ file, with the dimensions, data type and RAS information, without loading the voxel data.
+///
+/// For MGZ files, only the start of the file is decompressed.
+///
+/// # Examples
+///
+/// ```no_run
+/// let header = neuroformats::read_mgh_header("/path/to/subjects_dir/subject1/mri/brain.mgz").unwrap();
+/// println!("Volume with dimensions {:?} and data type {}.", header.dim(), header.dtype);
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_mgh_header<P: AsRef<Path>>(path: P) -> Result<FsMghHeader> {
+    FsMghHeader::from_file(path)
+}
+
+
 impl fmt::Display for FsMgh {    
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {        
         write!(f, "FreeSurfer 4D MRI data with dim {}, {}, {}, {} and RAS flag: {}.", self.header.dim1len, self.header.dim2len, self.header.dim3len, self.header.dim4len, self.header.is_ras_good)
@@ -348,71 +758,142 @@ impl fmt::Display for FsMgh {
 }
 
 
+/// Determine the byte order of an MGH file from the bytes of the version field at the start of the file.
+///
+/// Returns [`NeuroformatsError::InvalidFsMghFormat`] if the version is not valid in either byte order.
+fn mgh_endianness(version_bytes: [u8; 4]) -> Result<Endianness> {
+    if i32::from_be_bytes(version_bytes) == MGH_VERSION_CODE {
+        Ok(Endianness::Big)
+    } else if i32::from_le_bytes(version_bytes) == MGH_VERSION_CODE {
+        Ok(Endianness::Little)
+    } else {
+        Err(NeuroformatsError::InvalidFsMghFormat)
+    }
+}
+
+
 /// Write an FsMgh struct to a file in MGH or MGZ format.
 ///
 /// Whether MGH or MGZ format should be used is determined from the file extension according to
 /// the following rule: files ending with `.mgz` are written in MGZ format, all others are
 /// written in MGH format.
-pub fn write_mgh<P: AsRef<Path> + Copy>(path: P, mgh : &FsMgh) -> std::io::Result<()> {
-    if is_mgz_file(path) {
-        write_mgz_compressed(path, mgh)
-    } else {
-        write_mgh_uncompressed(path, mgh)
-    }
+#[cfg(feature = "fs")]
+pub fn write_mgh<P: AsRef<Path>>(path: P, mgh : &FsMgh) -> std::io::Result<()> {
+    write_mgh_with_options(path, mgh, &WriteOptions::default())
 }
 
 
-/// Write an MGH file in the uncompressed file format version.
-fn write_mgh_uncompressed<P: AsRef<Path> + Copy>(path: P, mgh : &FsMgh) -> std::io::Result<()> {
-    let f = File::create(path)?;        
-    let writer = BufWriter::new(&f);        
-    write_mgh_to(writer, mgh)
+/// Write an FsMgh struct to a file in MGH or MGZ format, using the given [`WriteOptions`].
+///
+/// See [`write_mgh`] for details. The `compression` level of the options is used for MGZ files, and the `byte_order` determines the byte order.
+#[cfg(feature = "fs")]
+pub fn write_mgh_with_options<P: AsRef<Path>>(path: P, mgh : &FsMgh, options: &WriteOptions) -> std::io::Result<()> {
+    write_mgh_file(path, options, |f| write_mgh_to(f, mgh, options.byte_order.endianness()))
 }
 
 
-/// Write an MGZ file in the gz-compressed file format version.
-fn write_mgz_compressed<P: AsRef<Path> + Copy>(path: P, mgh : &FsMgh) -> std::io::Result<()> {
-    let f = File::create(path)?;        
-    let writer = BufWriter::new(flate2::write::GzEncoder::new(&f, Compression::default()));            
-    write_mgh_to(writer, mgh)
+/// Write a volume given as an iterator over its voxel values to a file in MGH or MGZ format, without building an [`FsMgh`] first.
+///
+/// This is useful for large 4D volumes which are computed frame by frame, as they never need to be held in memory completely. The data
+/// is written with data type `MRI_FLOAT`, and the dimensions and RAS information are taken from the given header. See [`write_mgh`] for
+/// how the format is determined from the file extension.
+///
+/// # Parameters
+///
+/// * `header`: the header. Its `dtype` is ignored.
+/// * `values`: the voxel values in file order, like for [`FsMgh::from_f32_values`]. Their number must match the dimensions of the header.
+///
+/// # Return value
+///
+/// An error of kind [`std::io::ErrorKind::InvalidInput`] if the number of values does not match the header. The file is incomplete in that case.
+///
+/// # Examples
+///
+/// ```no_run
+/// let brain = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/brain.mgz").unwrap();
+/// let values = brain.data_as_f32().unwrap().into_iter().map(|v| v / 255.0);
+/// neuroformats::fs_mgh::write_mgh_from_iter("/tmp/brain_scaled.mgz", &brain.header, values, &Default::default()).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn write_mgh_from_iter<P, I>(path: P, header: &FsMghHeader, values: I, options: &WriteOptions) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = f32>,
+{
+    let header = FsMghHeader { dtype: MRI_FLOAT, ..header.clone() };
+    let expected = header.checked_num_voxels().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, NeuroformatsError::InvalidFsMghFormat))?;
+    write_mgh_file(path, options, |f| {
+        write_mgh_header_to(f, &header, options.byte_order.endianness())?;
+        let written = write_block(f, values, options.byte_order.endianness())?;
+        if written != expected {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, NeuroformatsError::VoxelCountMismatch(expected, written)));
+        }
+        Ok(())
+    })
 }
 
 
-/// Write an FsMgh struct to a file in FreeSurfer MGH format. MGZ is not supported yet.
-fn write_mgh_to<W>(f : W, mgh : &FsMgh) -> std::io::Result<()> where W : Write {
+/// Create an MGH or MGZ file, depending on the file extension, and write its contents with the given function.
+#[cfg(feature = "fs")]
+fn write_mgh_file<P, F>(path: P, options: &WriteOptions, write: F) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    if is_mgz_file(&path) {
+        let f = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(options.buffer_size, flate2::write::GzEncoder::new(f, Compression::new(options.compression)));
+        write(&mut writer)?;
+        let encoder = writer.into_inner().map_err(|e| e.into_error())?;
+        options.finish_file(encoder.finish()?)
+    } else {
+        let mut writer = options.create(path)?;
+        write(&mut writer)?;
+        options.finish(writer)
+    }
+}
 
-    let mut f  = ByteOrdered::runtime(f, Endianness::Big);
-    
-    f.write_i32(mgh.header.mgh_format_version as i32)?;
-    f.write_i32(mgh.header.dim1len)?;
-    f.write_i32(mgh.header.dim2len)?;
-    f.write_i32(mgh.header.dim3len)?;
-    f.write_i32(mgh.header.dim4len)?;
-    f.write_i32(mgh.header.dtype)?;
-    f.write_i32(mgh.header.dof)?;
-    f.write_i16(mgh.header.is_ras_good)?;
-
-    for v in mgh.header.delta.iter() { f.write_f32(*v)?; }
-    for v in mgh.header.mdc_raw.iter() { f.write_f32(*v)?; }
-    for v in mgh.header.p_xyz_c.iter() { f.write_f32(*v)?; }
+
+/// Write the header of an MGH file in the given byte order, including the unused space up to the start of the data.
+#[cfg(feature = "fs")]
+fn write_mgh_header_to<W: Write + ?Sized>(f : &mut W, header: &FsMghHeader, endianness: Endianness) -> std::io::Result<()> {
+    write_block(f, [header.mgh_format_version, header.dim1len, header.dim2len, header.dim3len, header.dim4len, header.dtype, header.dof], endianness)?;
+    write_block(f, [header.is_ras_good], endianness)?;
+    write_block(f, header.delta.iter().chain(header.mdc_raw.iter()).chain(header.p_xyz_c.iter()).copied(), endianness)?;
 
     // Fill rest of header space.
-    let header_space_left : usize = 194;
-    for _v in 0..header_space_left { f.write_u8(0 as u8)?; }
-    
+    f.write_all(&[0u8; 194])
+}
+
+
+/// Write an FsMgh struct to a writer in FreeSurfer MGH format, in the given byte order.
+#[cfg(feature = "fs")]
+fn write_mgh_to<W: Write + ?Sized>(f : &mut W, mgh : &FsMgh, endianness: Endianness) -> std::io::Result<()> {
+    write_mgh_header_to(f, &mgh.header, endianness)?;
+
     // Write data.
     if mgh.header.dtype == MRI_UCHAR {
-        for v in mgh.data.mri_uchar.as_ref().unwrap().iter() { f.write_u8(*v)?; }
+        write_block(f, mgh.data.mri_uchar.as_ref().unwrap().iter().copied(), endianness)?;
     } else if mgh.header.dtype == MRI_INT {
-        for v in mgh.data.mri_int.as_ref().unwrap().iter() { f.write_i32(*v)?; }
+        write_block(f, mgh.data.mri_int.as_ref().unwrap().iter().copied(), endianness)?;
     } else if mgh.header.dtype == MRI_FLOAT {
-        for v in mgh.data.mri_float.as_ref().unwrap().iter() { f.write_f32(*v)?; }
+        write_block(f, mgh.data.mri_float.as_ref().unwrap().iter().copied(), endianness)?;
     } else if mgh.header.dtype == MRI_SHORT {
-        for v in mgh.data.mri_short.as_ref().unwrap().iter() { f.write_i16(*v)?; }
+        write_block(f, mgh.data.mri_short.as_ref().unwrap().iter().copied(), endianness)?;
     } else {
         panic!("Unsupported MRI data type.");
     }
 
+    if let Some(footer) = &mgh.footer {
+        let mut f = ByteOrdered::runtime(f, endianness);
+        for v in [footer.tr, footer.flip_angle, footer.te, footer.ti, footer.fov].iter() { f.write_f32(*v)?; }
+        for tag in footer.tags.iter() {
+            f.write_i32(tag.id)?;
+            f.write_i64(tag.data.len() as i64)?;
+            f.write_all(&tag.data)?;
+        }
+    }
+
     Ok(())
 }
 
@@ -420,9 +901,37 @@ fn write_mgh_to<W>(f : W, mgh : &FsMgh) -> std::io::Result<()> where W : Write {
 #[cfg(test)]
 mod test { 
     use approx::AbsDiffEq;
+    #[cfg(feature = "fs")]
     use tempfile::{tempdir};
     use super::*;
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_headers_of_all_formats_can_be_read_without_the_data() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        assert_eq!(mgh.header, read_mgh_header("resources/subjects_dir/subject1/mri/brain.mgz").unwrap());
+        let surf_header = crate::read_surf_header("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        assert_eq!((149244, 298484), (surf_header.num_vertices, surf_header.num_faces));
+        assert_eq!(149244, crate::read_curv_header("resources/subjects_dir/subject1/surf/lh.thickness").unwrap().num_vertices);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_memory_use_of_the_data_can_be_estimated_from_the_headers() {
+        let mgh_header = read_mgh_header("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        assert_eq!(Some(256 * 256 * 256), mgh_header.estimated_memory_bytes());
+        let surf_header = crate::read_surf_header("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        assert_eq!(Some(149244 * 12 + 298484 * 12), surf_header.estimated_memory_bytes());
+        let curv_header = crate::read_curv_header("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        assert_eq!(Some(149244 * 4), curv_header.estimated_memory_bytes());
+
+        let curv = crate::read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        assert_eq!(curv.data.len(), curv.data.capacity());
+        let invalid = FsMghHeader { dtype: MRI_LONG, ..mgh_header };
+        assert_eq!(None, invalid.estimated_memory_bytes());
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn the_brain_mgz_file_can_be_read() {
         const MGZ_FILE: &str = "resources/subjects_dir/subject1/mri/brain.mgz";
@@ -458,6 +967,34 @@ mod test {
         assert_eq!(data.mapv(|a| a as i32).sum(), 121035479);
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_volume_can_be_written_from_an_iterator() {
+        let dir = tempdir().unwrap();
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let values = mgh.data_as_f32().unwrap();
+        let expected = FsMgh::from_f32_values(&mgh.header, values.clone()).unwrap();
+        for file_name in ["iter.mgh", "iter.mgz"] {
+            let path = dir.path().join(file_name);
+            write_mgh_from_iter(&path, &mgh.header, values.iter().copied(), &WriteOptions::default()).unwrap();
+            assert_eq!(expected, read_mgh(&path).unwrap());
+        }
+
+        let path = dir.path().join("short.mgh");
+        let err = write_mgh_from_iter(&path, &mgh.header, values[1..].iter().copied(), &WriteOptions::default()).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_brain_mgz_file_can_be_read_from_compressed_bytes() {
+        const MGZ_FILE: &str = "resources/subjects_dir/subject1/mri/brain.mgz";
+        let bytes = std::fs::read(MGZ_FILE).unwrap();
+        let mgh = FsMgh::from_bytes(&bytes).unwrap();
+        assert_eq!(mgh, read_mgh(MGZ_FILE).unwrap());
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn the_vox2ras_matrix_can_be_computed() {
         const MGZ_FILE: &str = "resources/subjects_dir/subject1/mri/brain.mgz";
@@ -480,6 +1017,37 @@ mod test {
         assert!(my_voxel_ras.abs_diff_eq(&expected_voxel_ras, 1e-2));
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_tkr_vox2ras_and_ras2vox_matrices_can_be_computed() {
+        const MGZ_FILE: &str = "resources/subjects_dir/subject1/mri/brain.mgz";
+        let mgh = read_mgh(MGZ_FILE).unwrap();
+
+        // The tkr vox2ras of a conformed 256^3 volume with 1 mm voxels is fixed, see `mri_info --vox2ras-tkr`.
+        let vox2ras_tkr = mgh.header.vox2ras_tkr().unwrap();
+        let expected_ar : Vec<f32> = [-1., 0., 0., 128., 0., 0., 1., -128., 0., -1., 0., 128., 0., 0., 0., 1.].to_vec();
+        let expected = Array2::from_shape_vec((4, 4), expected_ar).unwrap();
+        assert!(vox2ras_tkr.abs_diff_eq(&expected, 1e-5));
+
+        let identity : Array2<f32> = Array2::eye(4);
+        assert!(mgh.header.ras2vox_tkr().unwrap().dot(&vox2ras_tkr).abs_diff_eq(&identity, 1e-5));
+        assert!(mgh.header.ras2vox().unwrap().dot(&mgh.vox2ras().unwrap()).abs_diff_eq(&identity, 1e-4));
+    }
+
+    #[test]
+    fn the_tkr_vox2ras_matrix_uses_the_conformed_orientation_for_all_volumes() {
+        // An RAS oriented volume with anisotropic voxels, like a native space scan.
+        let header = FsMghHeader {
+            dim1len: 100, dim2len: 120, dim3len: 50, dim4len: 1,
+            is_ras_good: 1, delta: [1.0, 2.0, 3.0], mdc_raw: [1., 0., 0., 0., 1., 0., 0., 0., 1.], p_xyz_c: [5.0, -3.0, 12.0],
+            ..FsMghHeader::default()
+        };
+        let expected = array![[-1., 0., 0., 50.], [0., 0., 3., -75.], [0., -2., 0., 120.], [0., 0., 0., 1.]];
+        assert!(header.vox2ras_tkr().unwrap().abs_diff_eq(&expected, 1e-5));
+        assert!(FsMghHeader { is_ras_good: 0, ..header }.vox2ras_tkr().is_err());
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn the_demo_mgh_file_can_be_read() {
         const MGH_FILE: &str = "resources/mgh/tiny.mgh";
@@ -496,6 +1064,35 @@ mod test {
         assert_eq!(mgh.header.is_ras_good, -1);
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_voxels_of_a_volume_can_be_iterated() {
+        const MGZ_FILE: &str = "resources/subjects_dir/subject1/mri/brain.mgz";
+        let mgh = read_mgh(MGZ_FILE).unwrap();
+
+        assert_eq!(256 * 256 * 256, mgh.iter_voxels().count());
+        let voxel = mgh.iter_voxels().find(|(i, j, k, _t, _v)| (*i, *j, *k) == (99, 99, 99)).unwrap();
+        assert_eq!((99, 99, 99, 0, 77.0), voxel);
+        let values: Vec<f32> = mgh.iter_voxels().map(|voxel| voxel.4).collect();
+        assert_eq!(mgh.data_as_f32().unwrap(), values);
+
+        let mut header = read_mgh("resources/mgh/tiny.mgh").unwrap().header;
+        header.dim1len = 2;
+        header.dim2len = 3;
+        header.dim3len = 4;
+        header.dim4len = 2;
+        let mgh = FsMgh::from_f32_values(&header, (0..48).map(|v| v as f32).collect()).unwrap();
+        assert_eq!(48, mgh.iter_voxels().count());
+        for (c, r, s, t, value) in mgh.iter_voxels() {
+            assert_eq!(header.voxel_flat_index([c, r, s], t) as f32, value);
+        }
+        assert_eq!((24..48).map(|v| v as f32).collect::<Vec<f32>>(), mgh.frame_as_f32(1).unwrap());
+        let frames = mgh.frames_as_f32().unwrap();
+        assert_eq!(2, frames.len());
+        assert_eq!(mgh.frame_as_f32(1).unwrap(), frames[1]);
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn an_mgh_file_can_be_written_as_mgh_and_reread() {
         const MGZ_FILE: &str = "resources/subjects_dir/subject1/mri/brain.mgz";
@@ -504,10 +1101,9 @@ mod test {
         let dir = tempdir().unwrap();
 
         let tfile_path = dir.path().join("temp-file.mgh");
-        let tfile_path = tfile_path.to_str().unwrap();
-        write_mgh(tfile_path, &mgh).unwrap();
+        write_mgh(&tfile_path, &mgh).unwrap();
 
-        let mgh_re = read_mgh(tfile_path).unwrap();
+        let mgh_re = read_mgh(&tfile_path).unwrap();
 
         // Test vox2ras computation
         let vox2ras = mgh_re.header.vox2ras().unwrap();
@@ -555,6 +1151,7 @@ mod test {
         assert_eq!(data.mapv(|a| a as i32).sum(), 121035479);        
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn an_mgh_file_can_be_written_as_mgz_and_reread() {
         const MGZ_FILE: &str = "resources/subjects_dir/subject1/mri/brain.mgz";
@@ -563,10 +1160,9 @@ mod test {
         let dir = tempdir().unwrap();
 
         let tfile_path = dir.path().join("temp-file.mgz");
-        let tfile_path = tfile_path.to_str().unwrap();
-        write_mgh(tfile_path, &mgh).unwrap();
+        write_mgh(&tfile_path, &mgh).unwrap();
 
-        let mgh_re = read_mgh(tfile_path).unwrap();
+        let mgh_re = read_mgh(&tfile_path).unwrap();
 
         // Test vox2ras computation
         let vox2ras = mgh_re.header.vox2ras().unwrap();
@@ -614,4 +1210,120 @@ mod test {
         assert_eq!(data.mapv(|a| a as i32).sum(), 121035479);        
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_footer_of_the_brain_mgz_file_can_be_read() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let footer = mgh.footer.unwrap();
+
+        assert_eq!(2300.0, footer.tr);
+        assert!((footer.flip_angle - 0.15707964).abs() < 1e-6);
+        assert_eq!(900.0, footer.ti);
+        assert_eq!(256.0, footer.fov);
+        assert_eq!(9, footer.tags.len());
+        assert_eq!(4, footer.cmdlines().len());
+        assert!(footer.cmdlines()[0].starts_with("mri_convert.bin"));
+        assert_eq!("UNKNOWN", footer.tag(MGH_TAG_PEDIR).unwrap().text());
+        assert_eq!(Some(0.0), footer.field_strength());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_footer_is_preserved_when_writing_mgh_files() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let dir = tempdir().unwrap();
+        let tfile_path = dir.path().join("temp-file.mgz");
+        write_mgh(&tfile_path, &mgh).unwrap();
+        assert_eq!(mgh.footer, read_mgh(&tfile_path).unwrap().footer);
+
+        let tiny = read_mgh("resources/mgh/tiny.mgh").unwrap();
+        let footer = tiny.footer.as_ref().unwrap();
+        assert!(footer.tags.is_empty());
+        let tfile_path = dir.path().join("tiny.mgh");
+        write_mgh(&tfile_path, &tiny).unwrap();
+        let (orig, written) = (std::fs::read("resources/mgh/tiny.mgh").unwrap(), std::fs::read(&tfile_path).unwrap());
+        assert_eq!(orig.len(), written.len());
+        assert_eq!(&orig[orig.len() - 20..], &written[written.len() - 20..]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn little_endian_mgh_files_can_be_written_and_read() {
+        let dir = tempdir().unwrap();
+        let options = crate::WriteOptions::default().with_byte_order(crate::ByteOrder::Little);
+        for (orig_path, name) in vec![("resources/mgh/tiny.mgh", "tiny_le.mgh"), ("resources/subjects_dir/subject1/mri/brain.mgz", "brain_le.mgz")].into_iter() {
+            let mgh = read_mgh(orig_path).unwrap();
+            let tfile_path = dir.path().join(name);
+            write_mgh_with_options(&tfile_path, &mgh, &options).unwrap();
+
+            let reread = read_mgh(&tfile_path).unwrap();
+            assert_eq!(mgh.header.dim(), reread.header.dim());
+            assert_eq!(mgh.header.dtype, reread.header.dtype);
+            assert_eq!(mgh.header.is_ras_good, reread.header.is_ras_good);
+            assert_eq!(format!("{:?}", mgh.header.mdc_raw), format!("{:?}", reread.header.mdc_raw));
+            assert_eq!(mgh.data, reread.data);
+            assert_eq!(mgh.footer, reread.footer);
+        }
+
+        let bytes = std::fs::read(dir.path().join("tiny_le.mgh")).unwrap();
+        assert_eq!([1, 0, 0, 0], bytes[0..4]);
+        let mgh = FsMgh::from_bytes(&bytes).unwrap();
+        assert_eq!(read_mgh("resources/mgh/tiny.mgh").unwrap().data, mgh.data);
+    }
+
+    #[test]
+    fn a_missing_footer_is_not_an_error() {
+        let bytes = std::fs::read("resources/mgh/tiny.mgh").unwrap();
+        let mgh = FsMgh::from_bytes(&bytes[..bytes.len() - 20]).unwrap();
+        assert!(mgh.footer.is_none());
+    }
+
+    #[test]
+    fn an_mgh_file_with_tensor_data_is_read_as_float_data_with_9_frames() {
+        let mut bytes = std::fs::read("resources/mgh/tiny.mgh").unwrap()[..MGH_DATA_START as usize].to_vec();
+        bytes[20..24].copy_from_slice(&MRI_TENSOR.to_be_bytes());
+        for idx in 0..27 * 9 {
+            bytes.extend_from_slice(&(idx as f32).to_be_bytes());
+        }
+        let mgh = FsMgh::from_bytes(&bytes).unwrap();
+
+        assert_eq!(MRI_FLOAT, mgh.header.dtype);
+        assert_eq!([3, 3, 3, 9], mgh.dim());
+        assert_eq!(27 * 9, mgh.data_as_f32().unwrap().len());
+        assert_eq!(1.0, mgh.data.mri_float.unwrap()[[0, 0, 0, 1]]);
+    }
+
+    #[test]
+    fn the_error_for_an_unsupported_data_type_contains_the_type_and_dimensions() {
+        let mut bytes = std::fs::read("resources/mgh/tiny.mgh").unwrap();
+        bytes[20..24].copy_from_slice(&MRI_LONG.to_be_bytes());
+        let err = FsMgh::from_bytes(&bytes).unwrap_err();
+        assert!(matches!(err, NeuroformatsError::UnsupportedMriDataTypeInMgh(MRI_LONG, [3, 3, 3, 1])));
+        assert_eq!("Invalid or unsupported MRI_DTYPE 2 for MGH data with dimensions [3, 3, 3, 1]", err.to_string());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn reading_corrupted_mgh_files_does_not_panic() {
+        let bytes = std::fs::read("resources/mgh/tiny.mgh").unwrap();
+        let mgh = FsMgh::from_reader(&mut &bytes[..]).unwrap();
+        assert_eq!(read_mgh("resources/mgh/tiny.mgh").unwrap().data, mgh.data);
+        for variant in crate::util::corrupted_variants(&bytes) {
+            let _ = FsMgh::from_reader(&mut &variant[..]);
+        }
+
+        // Dimensions with a product of zero, but a product of the non-zero dimensions that is too large for an array.
+        let mut huge = bytes.clone();
+        huge[4..20].copy_from_slice(&[0, 0, 0, 0, 127, 255, 255, 255, 127, 255, 255, 255, 127, 255, 255, 255]);
+        assert!(FsMgh::from_reader(&mut &huge[..]).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "fs"))]
+    fn mgh_data_can_be_serialized_and_deserialized() {
+        let mgh = read_mgh("resources/mgh/tiny.mgh").unwrap();
+        let json = serde_json::to_string(&mgh.data).unwrap();
+        let data_re: FsMghData = serde_json::from_str(&json).unwrap();
+        assert_eq!(mgh.data, data_re);
+    }
 }
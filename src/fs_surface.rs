@@ -5,24 +5,33 @@
 //! These vertex indices are zero-based.
 
 
-use byteordered::{ByteOrdered, Endianness};
+use byteordered::ByteOrdered;
+#[cfg(feature = "fs")]
+use byteordered::Endianness;
 
+#[cfg(feature = "fs")]
 use std::{fs::File};
-use std::io::{BufReader, BufRead, BufWriter, Write};
+use std::convert::TryFrom;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::{BufReader, Write};
+#[cfg(feature = "fs")]
 use std::path::{Path};
 use std::fmt;
 
-use crate::util::{read_fs_variable_length_string};
+use crate::util::{read_fs_variable_length_string, read_values, gunzip_if_compressed, vec3_sub, vec3_cross};
+#[cfg(feature = "fs")]
+use crate::util::latin1_bytes;
+#[cfg(feature = "fs")]
+use crate::util::{open_maybe_compressed, write_block, WriteOptions};
 use crate::error::{NeuroformatsError, Result};
 
 
-use ndarray::{Array2, array, s};
-use ndarray_stats::QuantileExt;
-
 pub const TRIS_MAGIC_FILE_TYPE_NUMBER: i32 = 16777214;
 
 /// Models the header of a FreeSurfer surf file containing a brain mesh. Note that the `info_line` must contain only ASCII chars and end with two Unix EOLs, `\n\n`.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsSurfaceHeader {
     pub surf_magic: [u8; 3],
     pub info_line: String,
@@ -44,11 +53,79 @@ impl Default for FsSurfaceHeader {
 
 /// The header of a FreeSurfer brain mesh file in surf format.
 impl FsSurfaceHeader {
+
+    /// Create a new header for a mesh with the given number of vertices and faces.
+    ///
+    /// The `info_line` is a free text description, like `"created by user on date"`. Trailing line feeds are replaced with the
+    /// two Unix EOLs, `\n\n`, which terminate the info line in the file, so they can be omitted.
+    ///
+    /// # Return value
+    ///
+    /// The header, or [`NeuroformatsError::InvalidFsSurfaceInfoLine`] if the `info_line` contains non-ASCII chars or two consecutive
+    /// line feeds before its end, or [`NeuroformatsError::InvalidFsSurfaceFormat`] if the counts do not fit into the file format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let header = neuroformats::FsSurfaceHeader::new("created by me", 3, 1).unwrap();
+    /// assert_eq!("created by me\n\n", header.info_line);
+    /// ```
+    pub fn new(info_line: &str, num_vertices: usize, num_faces: usize) -> Result<FsSurfaceHeader> {
+        let hdr = FsSurfaceHeader {
+            info_line: format!("{}\n\n", info_line.trim_end_matches('\n')),
+            num_vertices: i32::try_from(num_vertices).map_err(|_| NeuroformatsError::InvalidFsSurfaceFormat)?,
+            num_faces: i32::try_from(num_faces).map_err(|_| NeuroformatsError::InvalidFsSurfaceFormat)?,
+            ..FsSurfaceHeader::default()
+        };
+        hdr.validate()?;
+        Ok(hdr)
+    }
+
+
+    /// Estimate the number of bytes of memory needed to hold the mesh of a surface with this header, e.g., to check whether a file fits into memory before reading it.
+    ///
+    /// This is the size of the `f32` vertex coordinates and the `i32` vertex indices of the faces. Returns `None` if a count is negative.
+    pub fn estimated_memory_bytes(&self) -> Option<usize> {
+        let num_vertices = usize::try_from(self.num_vertices).ok()?;
+        let num_faces = usize::try_from(self.num_faces).ok()?;
+        let vertex_bytes = num_vertices.saturating_mul(3 * std::mem::size_of::<f32>());
+        let face_bytes = num_faces.saturating_mul(3 * std::mem::size_of::<i32>());
+        Some(vertex_bytes.saturating_add(face_bytes))
+    }
+
+
+    /// Check whether this header can be written to a surf file which can be read again.
+    ///
+    /// The `info_line` must contain only ASCII chars and end with `\n\n`, which must not occur anywhere else in it, and the counts must not be negative.
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with_charset(true)
+    }
+
+
+    /// Check the header, see [`FsSurfaceHeader::validate`]. If `ascii_only` is false, chars which fit into a single byte are allowed in the `info_line`, as written in strict mode.
+    fn validate_with_charset(&self, ascii_only: bool) -> Result<()> {
+        let info_line = &self.info_line;
+        if !info_line.ends_with("\n\n") {
+            return Err(NeuroformatsError::InvalidFsSurfaceInfoLine(String::from("it does not end with two line feeds")));
+        }
+        if info_line.find("\n\n") != Some(info_line.len() - 2) {
+            return Err(NeuroformatsError::InvalidFsSurfaceInfoLine(String::from("it contains two consecutive line feeds before its end")));
+        }
+        let max_char = if ascii_only { 0x7F } else { 0xFF };
+        if let Some(c) = info_line.chars().find(|c| *c as u32 > max_char) {
+            return Err(NeuroformatsError::InvalidFsSurfaceInfoLine(format!("it contains the unsupported char '{}'", c)));
+        }
+        if self.num_vertices < 0 || self.num_faces < 0 {
+            return Err(NeuroformatsError::InvalidFsSurfaceFormat);
+        }
+        Ok(())
+    }
+
     
-    /// Read an FsSurface header from a file.
+    /// Read an FsSurface header from a file, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsSurfaceHeader> {
-        let mut file = BufReader::new(File::open(path)?);
-        FsSurfaceHeader::from_reader(&mut file)
+        FsSurfaceHeader::from_reader(&mut open_maybe_compressed(path)?)
     }
 
 
@@ -72,7 +149,7 @@ impl FsSurfaceHeader {
         
         let magic: i32 = interpret_fs_int24(hdr.surf_magic[0], hdr.surf_magic[1], hdr.surf_magic[2]);
 
-        if !(magic == TRIS_MAGIC_FILE_TYPE_NUMBER) {
+        if !(magic == TRIS_MAGIC_FILE_TYPE_NUMBER) || hdr.num_vertices < 0 || hdr.num_faces < 0 {
             Err(NeuroformatsError::InvalidFsSurfaceFormat)
         } else {
             Ok(hdr)
@@ -83,13 +160,11 @@ impl FsSurfaceHeader {
 
 /// Compute the min and max coordinates for the x, y, and z axes.
 ///
-/// # Panics
-///
-/// If the min and max coordinates for the axes cannot be computed. E.g., when the coordinate vector is empty, has a length that is not a multiple of 3, or contains invalid vertex coordinates like `NAN` values.
-///
 /// # Return value
 ///
-/// The 6 values in the returned tuple are, in the following order: (min_x, max_x, min_y, max_y, min_z, max_z).
+/// The 6 values in the returned tuple are, in the following order: (min_x, max_x, min_y, max_y, min_z, max_z). An error is returned if `coords` is empty
+/// ([`NeuroformatsError::EmptyCoordinates`]), has a length that is not a multiple of 3 ([`NeuroformatsError::InvalidCoordinateCount`]), or contains a
+/// `NAN` value ([`NeuroformatsError::NanCoordinate`]).
 ///
 /// Examples
 ///
@@ -103,22 +178,26 @@ impl FsSurfaceHeader {
 /// assert_eq!(2.0, maxy);
 /// assert_eq!(4.0, maxz);
 /// ```
-pub fn coord_extrema(coords : &Vec<f32>) -> Result<(f32, f32, f32, f32, f32, f32)> {
-    let all_coords = Array2::from_shape_vec((coords.len()/3 as usize, 3 as usize), coords.clone()).unwrap();
-    let x_coords =  all_coords.slice(s![.., 0]);
-    let y_coords =  all_coords.slice(s![.., 1]);
-    let z_coords =  all_coords.slice(s![.., 2]);
-
-    let min_x = x_coords.min().unwrap().clone(); // min() on type ndarray::ArrayBase is available from ndarray-stats Quantile trait
-    let max_x = x_coords.max().unwrap().clone(); 
-
-    let min_y = y_coords.min().unwrap().clone();
-    let max_y = y_coords.max().unwrap().clone(); 
+pub fn coord_extrema(coords : &[f32]) -> Result<(f32, f32, f32, f32, f32, f32)> {
+    if coords.is_empty() {
+        return Err(NeuroformatsError::EmptyCoordinates);
+    }
+    let (vertices, rest) = coords.as_chunks::<3>();
+    if !rest.is_empty() {
+        return Err(NeuroformatsError::InvalidCoordinateCount(coords.len()));
+    }
+    if let Some(index) = coords.iter().position(|c| c.is_nan()) {
+        return Err(NeuroformatsError::NanCoordinate(index));
+    }
 
-    let min_z = z_coords.min().unwrap().clone();
-    let max_z = z_coords.max().unwrap().clone(); 
-    
-    Ok((min_x, max_x, min_y, max_y, min_z, max_z))
+    let (min, max) = vertices.iter().fold(([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]), |(mut min, mut max), v| {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(v[axis]);
+            max[axis] = max[axis].max(v[axis]);
+        }
+        (min, max)
+    });
+    Ok((min[0], max[0], min[1], max[1], min[2], max[2]))
 }
 
 
@@ -126,13 +205,10 @@ pub fn coord_extrema(coords : &Vec<f32>) -> Result<(f32, f32, f32, f32, f32, f32
 ///
 /// The center is simply the mean of the min and max values for the x, y and z axes. So this is NOT the center of mass.
 ///
-/// # Panics
-///
-/// If the `mean` of the min and max coordinates cannot be computed. E.g., when coords vector is empty, has a length that is not a multiple of 3, or contains invalid vertex coordinates like `NAN` values.
-///
 /// # Return value
 ///
-/// The 3 values in the returned tuple are the x, y and z coordinates of the center, in that order.
+/// The 3 values in the returned tuple are the x, y and z coordinates of the center, in that order. The errors are the same as for [`coord_extrema`],
+/// e.g., when `coords` is empty, has a length that is not a multiple of 3, or contains `NAN` values.
 ///
 /// # Examples
 ///
@@ -143,12 +219,9 @@ pub fn coord_extrema(coords : &Vec<f32>) -> Result<(f32, f32, f32, f32, f32, f32
 /// assert_eq!(1.0, cy);
 /// assert_eq!(2.0, cz);
 /// ```
-pub fn coord_center(coords : &Vec<f32>)  -> Result<(f32, f32, f32)> {
+pub fn coord_center(coords : &[f32])  -> Result<(f32, f32, f32)> {
     let (min_x, max_x, min_y, max_y, min_z, max_z) = coord_extrema(coords)?;
-    let cx = array![min_x, max_x].mean().expect("Could not compute mean for x coords.");
-    let cy = array![min_y, max_y].mean().expect("Could not compute mean for y coords.");
-    let cz = array![min_z, max_z].mean().expect("Could not compute mean for z coords.");
-    Ok((cx, cy, cz))
+    Ok(((min_x + max_x) / 2.0, (min_y + max_y) / 2.0, (min_z + max_z) / 2.0))
 }
 
 
@@ -164,35 +237,109 @@ pub fn interpret_fs_int24(b1: u8, b2:u8, b3:u8) -> i32 {
 
 
 /// Write an FsSurface struct to a file in FreeSurfer surf format.
-pub fn write_surf<P: AsRef<Path> + Copy>(path: P, surf : &FsSurface) -> std::io::Result<()> {
-    let f = File::create(path)?;
-    let f = BufWriter::new(f);  
-    let mut f  =  ByteOrdered::runtime(f, Endianness::Big); 
-    f.write_u8(surf.header.surf_magic[0])?;
-    f.write_u8(surf.header.surf_magic[1])?;
-    f.write_u8(surf.header.surf_magic[2])?;
+#[cfg(feature = "fs")]
+pub fn write_surf<P: AsRef<Path>>(path: P, surf : &FsSurface) -> std::io::Result<()> {
+    write_surf_with_options(path, surf, &WriteOptions::default())
+}
 
-    // Write the info line. It is a byte string that ends with 2 Unix linefeeds '\n' or '\x0A' (decimal 10). There is NOT any string terminator (no NUL byte).
-    f.write(surf.header.info_line.as_bytes())?;
-    f.write_i32(surf.header.num_vertices)?;
-    f.write_i32(surf.header.num_faces)?;
 
-    for v in surf.mesh.vertices.iter() { f.write_f32(*v)?; }
-    for v in surf.mesh.faces.iter() { f.write_i32(*v)?; }
-    
-    Ok(())
+/// Write an FsSurface struct to a file in FreeSurfer surf format, using the given [`WriteOptions`].
+///
+/// The header is checked with [`FsSurfaceHeader::validate`] before anything is written, and an error of kind
+/// [`std::io::ErrorKind::InvalidInput`] is returned if it is invalid. In strict mode, the `info_line` may contain all chars which fit into a byte.
+#[cfg(feature = "fs")]
+pub fn write_surf_with_options<P: AsRef<Path>>(path: P, surf : &FsSurface, options: &WriteOptions) -> std::io::Result<()> {
+    surf.header.validate_with_charset(!options.strict).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    let mut f = options.create(path)?;
+    write_surf_header_to(&mut f, &surf.header, options.strict)?;
+    write_block(&mut f, surf.mesh.vertices.iter().copied(), Endianness::Big)?;
+    write_block(&mut f, surf.mesh.faces.iter().copied(), Endianness::Big)?;
+    if options.strict {
+        f.write_all(&surf.tags)?;
+    }
+    options.finish(f)
+}
+
+
+/// Write a mesh given as iterators over its vertices and faces to a file in FreeSurfer surf format, without building an [`FsSurface`] first.
+///
+/// This is useful for meshes which are generated on the fly or converted from other formats, as they never need to be held in memory completely.
+/// The counts are written to the header before the data, so they must be known in advance.
+///
+/// # Parameters
+///
+/// * `num_vertices`: the number of vertices that `vertices` yields.
+/// * `num_faces`: the number of faces that `faces` yields.
+/// * `vertices`: the x,y,z coordinates of the vertices.
+/// * `faces`: the vertex indices of the faces.
+///
+/// # Return value
+///
+/// An error of kind [`std::io::ErrorKind::InvalidInput`] if the iterators do not yield the given number of vertices and faces. Its inner error is a
+/// [`NeuroformatsError::VertexCountMismatch`] or [`NeuroformatsError::FaceCountMismatch`]. The file is incomplete in that case.
+///
+/// # Examples
+///
+/// ```no_run
+/// let coords = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+/// neuroformats::fs_surface::write_surf_from_iter("/tmp/lh.triangle", 3, 1, coords.into_iter(), std::iter::once([0, 1, 2]), &Default::default()).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn write_surf_from_iter<P, V, F>(path: P, num_vertices: usize, num_faces: usize, vertices: V, faces: F, options: &WriteOptions) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+    V: IntoIterator<Item = [f32; 3]>,
+    F: IntoIterator<Item = [i32; 3]>,
+{
+    let too_large = |_| std::io::Error::new(std::io::ErrorKind::InvalidInput, NeuroformatsError::InvalidFsSurfaceFormat);
+    let header = FsSurfaceHeader {
+        num_vertices: i32::try_from(num_vertices).map_err(too_large)?,
+        num_faces: i32::try_from(num_faces).map_err(too_large)?,
+        ..FsSurfaceHeader::default()
+    };
+    let mut f = options.create(path)?;
+    write_surf_header_to(&mut f, &header, false)?;
+    let written = write_block(&mut f, vertices.into_iter().flatten(), Endianness::Big)?;
+    if written != num_vertices * 3 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, NeuroformatsError::VertexCountMismatch(num_vertices, written / 3)));
+    }
+    let written = write_block(&mut f, faces.into_iter().flatten(), Endianness::Big)?;
+    if written != num_faces * 3 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, NeuroformatsError::FaceCountMismatch(num_faces, written / 3)));
+    }
+    options.finish(f)
+}
+
+
+/// Write the header of a surf file. In `strict` mode, the info line is written as raw bytes.
+#[cfg(feature = "fs")]
+fn write_surf_header_to<W: Write>(f: &mut W, header: &FsSurfaceHeader, strict: bool) -> std::io::Result<()> {
+    f.write_all(&header.surf_magic)?;
+    // Write the info line. It is a byte string that ends with 2 Unix linefeeds '\n' or '\x0A' (decimal 10). There is NOT any string terminator (no NUL byte).
+    if strict {
+        f.write_all(&latin1_bytes(&header.info_line))?;
+    } else {
+        f.write_all(header.info_line.as_bytes())?;
+    }
+    f.write_all(&header.num_vertices.to_be_bytes())?;
+    f.write_all(&header.num_faces.to_be_bytes())
 }
 
 
 /// An FsSurface object, models the contents (header and data) of a FreeSurfer surf file.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FsSurface {
     pub header: FsSurfaceHeader,
     pub mesh: BrainMesh, 
+    /// The raw bytes after the faces, which contain optional tags written by FreeSurfer, like the volume geometry and command lines.
+    /// They are only written in strict mode, see [`crate::WriteOptions::strict`].
+    pub tags: Vec<u8>,
 }
 
 /// A brain mesh, or any other triangular mesh. Vertices are stored as a vector of x,y,z coordinates, where triplets of coordinates represent a vertex. The triangular faces are stored in the same way as a vector of vertex indices.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BrainMesh {
     pub vertices: Vec<f32>,
     pub faces: Vec<i32>, 
@@ -200,31 +347,48 @@ pub struct BrainMesh {
 
 
 impl BrainMesh {
+    /// Check that the faces of this mesh are triangles of existing vertices.
+    ///
+    /// Returns [`NeuroformatsError::InvalidFaceIndexCount`] if the number of face indices is not a multiple of 3, and
+    /// [`NeuroformatsError::VertexIndexOutOfRange`] for the first face index which is negative or not smaller than the number of vertices.
+    pub fn validate_faces(&self) -> Result<()> {
+        if !self.faces.len().is_multiple_of(3) {
+            return Err(NeuroformatsError::InvalidFaceIndexCount(self.faces.len()));
+        }
+        let num_vertices = self.num_vertices();
+        match self.faces.iter().find(|v| **v < 0 || **v as usize >= num_vertices) {
+            Some(v) => Err(NeuroformatsError::VertexIndexOutOfRange(*v as i64, num_vertices)),
+            None => Ok(()),
+        }
+    }
+
+
     /// Export a brain mesh to a Wavefront Object (OBJ) format string.
     ///
+    /// # Return value
+    ///
+    /// The OBJ representation, or an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
     /// # Examples
     ///
     /// ```no_run
     /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
-    /// let obj_repr = surf.mesh.to_obj();
+    /// let obj_repr = surf.mesh.to_obj().unwrap();
     /// std::fs::write("/tmp/lhwhite.obj", obj_repr).expect("Unable to write OBJ mesh file");
     /// ```
-    pub fn to_obj(&self) -> String {
+    pub fn to_obj(&self) -> Result<String> {
+        self.validate_faces()?;
         let mut obj_repr = Vec::<String>::new();
 
-        let vertices = Array2::from_shape_vec((self.vertices.len()/3 as usize, 3 as usize), self.vertices.clone()).unwrap();
-        let faces = Array2::from_shape_vec((self.faces.len()/3 as usize, 3 as usize), self.faces.clone()).unwrap();
-
-        for vrow in vertices.genrows() {
+        for vrow in self.vertices_3() {
             obj_repr.push(format!("v {} {} {}\n", vrow[0], vrow[1], vrow[2]));
         }
 
-        for frow in faces.genrows() {
+        for frow in self.faces_3() {
             obj_repr.push(format!("f {} {} {}\n", frow[0]+1, frow[1]+1, frow[2]+1));
         }
         
-        let obj_repr = obj_repr.join("");
-        obj_repr
+        Ok(obj_repr.join(""))
     }
 
 
@@ -240,6 +404,54 @@ impl BrainMesh {
     }
 
 
+    /// Iterate over the vertex coordinates of this mesh, as arrays of x, y and z.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let max_z = surf.mesh.iter_vertices().map(|v| v[2]).fold(f32::MIN, f32::max);
+    /// ```
+    pub fn iter_vertices(&self) -> impl Iterator<Item = [f32; 3]> + '_ {
+        self.vertices_3().iter().copied()
+    }
+
+
+    /// Get the vertex coordinates of this mesh as a slice of x, y and z arrays, without copying them.
+    ///
+    /// Trailing coordinates which do not form a full vertex are not part of the slice.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let vertices = surf.mesh.vertices_3();
+    /// assert_eq!(surf.mesh.num_vertices(), vertices.len());
+    /// let first_z = vertices[0][2];
+    /// ```
+    pub fn vertices_3(&self) -> &[[f32; 3]] {
+        self.vertices.as_chunks::<3>().0
+    }
+
+
+    /// Get the faces of this mesh as a slice of arrays of 3 zero-based vertex indices, without copying them.
+    ///
+    /// The indices are not validated, see [`BrainMesh::validate_faces`]. Trailing indices which do not form a full face are not part of the slice.
+    pub fn faces_3(&self) -> &[[i32; 3]] {
+        self.faces.as_chunks::<3>().0
+    }
+
+
+    /// Iterate over the faces of this mesh, as arrays of 3 zero-based vertex indices.
+    ///
+    /// # Panics
+    ///
+    /// When a face with a negative vertex index is reached. Such faces are invalid.
+    pub fn iter_faces(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        (0..self.num_faces()).map(move |index| self.face(index))
+    }
+
+
     /// Read a brain mesh from a Wavefront object format (.obj) mesh file.
     ///
     /// # Examples
@@ -247,6 +459,7 @@ impl BrainMesh {
     /// let mesh = neuroformats::BrainMesh::from_obj_file("resources/mesh/cube.obj").unwrap();
     /// assert_eq!(24, mesh.vertices.len());
     /// ```
+    #[cfg(feature = "fs")]
     pub fn from_obj_file<P: AsRef<Path>>(path: P) -> Result<BrainMesh> {
     
         let reader = BufReader::new(File::open(path)?);
@@ -298,13 +511,10 @@ impl BrainMesh {
 
     /// Compute the min and max coordinates for the x, y, and z axes of the mesh.
     ///
-    /// # Panics
-    ///
-    /// If the min and max coordinates for the axes cannot be computed. E.g., when the mesh contains no vertices or invalid vertex coordinates like NAN values.
-    ///
     /// # Return value
     ///
-    /// The 6 values in the returned tuple are, in the following order: (min_x, max_x, min_y, max_y, min_z, max_z).
+    /// The 6 values in the returned tuple are, in the following order: (min_x, max_x, min_y, max_y, min_z, max_z). An error is returned if the
+    /// mesh contains no vertices or invalid vertex coordinates like `NAN` values, see [`coord_extrema`].
     pub fn axes_min_max_coords(&self) -> Result<(f32, f32, f32, f32, f32, f32)> {
         coord_extrema(&self.vertices)
     }
@@ -314,15 +524,120 @@ impl BrainMesh {
     ///
     /// The center is simply the mean of the min and max values for the x, y and z axes. So this is NOT the center of mass.
     ///
+    /// # Return value
+    ///
+    /// The 3 values in the returned tuple are the x, y and z coordinates of the center, in that order. An error is returned if the mesh
+    /// contains no vertices or invalid vertex coordinates like `NAN` values, see [`coord_extrema`].
+    pub fn center(&self)  -> Result<(f32, f32, f32)> {
+        coord_center(&self.vertices)
+    }
+
+
+    /// Get the coordinates of the vertex at the given index as an array of x, y and z.
+    ///
     /// # Panics
     ///
-    /// If the `mean` of the min and max coordinates cannot be computed. E.g., when the mesh contains no vertices or invalid vertex coordinates like NAN values.
+    /// If `vertex` is out of range for this mesh.
+    #[doc(alias = "vertex")]
+    pub fn vertex_coords(&self, vertex: usize) -> [f32; 3] {
+        [self.vertices[vertex * 3], self.vertices[vertex * 3 + 1], self.vertices[vertex * 3 + 2]]
+    }
+
+
+    /// Get the zero-based vertex indices of the face at the given index.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of range for this mesh, or if the face contains a negative vertex index.
+    pub fn face(&self, index: usize) -> [usize; 3] {
+        let idx = |v: i32| usize::try_from(v).expect("Face contains negative vertex index.");
+        [idx(self.faces[index * 3]), idx(self.faces[index * 3 + 1]), idx(self.faces[index * 3 + 2])]
+    }
+
+
+    /// Compute the vertex neighborhoods of the mesh.
     ///
     /// # Return value
     ///
-    /// The 3 values in the returned tuple are the x, y and z coordinates of the center, in that order.
-    pub fn center(&self)  -> Result<(f32, f32, f32)> {
-        coord_center(&self.vertices)
+    /// A vector with one entry per vertex. Each entry holds the indices of all vertices which share an edge with that vertex, in ascending order.
+    pub fn vertex_neighbors(&self) -> Vec<Vec<usize>> {
+        let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); self.num_vertices()];
+        for face in self.faces.chunks_exact(3) {
+            for (v1, v2) in [(face[0], face[1]), (face[1], face[2]), (face[2], face[0])] {
+                neighbors[v1 as usize].push(v2 as usize);
+                neighbors[v2 as usize].push(v1 as usize);
+            }
+        }
+        for nb in neighbors.iter_mut() {
+            nb.sort_unstable();
+            nb.dedup();
+        }
+        neighbors
+    }
+
+
+    /// Compute the faces each vertex is part of.
+    ///
+    /// # Return value
+    ///
+    /// A vector with one entry per vertex. Each entry holds the indices of all faces which contain that vertex, in ascending order.
+    pub fn vertex_faces(&self) -> Vec<Vec<usize>> {
+        let mut vertex_faces: Vec<Vec<usize>> = vec![Vec::new(); self.num_vertices()];
+        for (face_idx, face) in self.faces.chunks_exact(3).enumerate() {
+            for v in face {
+                vertex_faces[*v as usize].push(face_idx);
+            }
+        }
+        for vf in vertex_faces.iter_mut() {
+            vf.dedup();
+        }
+        vertex_faces
+    }
+
+
+    /// Merge this mesh with another one into a single mesh.
+    ///
+    /// The vertices of `other` are appended to the vertices of this mesh, and the vertex indices of its faces are offset accordingly. This
+    /// is typically used to combine the meshes of the left and right hemispheres for visualization, see [`crate::hemi::HemiPair::merged_mesh`].
+    pub fn merge(&self, other: &BrainMesh) -> BrainMesh {
+        let offset = self.num_vertices() as i32;
+        let mut vertices = Vec::with_capacity(self.vertices.len() + other.vertices.len());
+        vertices.extend_from_slice(&self.vertices);
+        vertices.extend_from_slice(&other.vertices);
+        let mut faces = Vec::with_capacity(self.faces.len() + other.faces.len());
+        faces.extend_from_slice(&self.faces);
+        faces.extend(other.faces.iter().map(|v| v + offset));
+        BrainMesh { vertices, faces }
+    }
+
+
+    /// Compute the vertex normals of the mesh.
+    ///
+    /// The normal of a vertex is the normalized sum of the normals of the faces it is part of, weighted by face area. For FreeSurfer
+    /// surfaces, the normals point outwards. Vertices which are not part of any face get a zero vector.
+    ///
+    /// # Return value
+    ///
+    /// A vector with one x,y,z normal per vertex, in the same layout as [`BrainMesh::vertices`].
+    pub fn vertex_normals(&self) -> Vec<f32> {
+        let mut normals: Vec<f32> = vec![0.0; self.vertices.len()];
+        for face in self.faces.chunks_exact(3) {
+            let (v0, v1, v2) = (face[0] as usize, face[1] as usize, face[2] as usize);
+            let p0 = self.vertex_coords(v0);
+            let n = vec3_cross(vec3_sub(self.vertex_coords(v1), p0), vec3_sub(self.vertex_coords(v2), p0));
+            for v in [v0, v1, v2] {
+                for axis in 0..3 {
+                    normals[v * 3 + axis] += n[axis];
+                }
+            }
+        }
+        for n in normals.chunks_exact_mut(3) {
+            let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            if len > 0.0 {
+                n.iter_mut().for_each(|c| *c /= len);
+            }
+        }
+        normals
     }
 
 }
@@ -350,25 +665,58 @@ impl fmt::Display for BrainMesh {
 /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
 /// let num_verts = surf.mesh.vertices.len();
 /// ```
-pub fn read_surf<P: AsRef<Path> + Copy>(path: P) -> Result<FsSurface> {
+#[cfg(feature = "fs")]
+pub fn read_surf<P: AsRef<Path>>(path: P) -> Result<FsSurface> {
     FsSurface::from_file(path)
 }
 
 
+/// Read only the header of a surf file, with the number of vertices and faces, without loading the mesh.
+///
+/// # Examples
+///
+/// ```no_run
+/// let header = neuroformats::read_surf_header("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// println!("Mesh with {} vertices and {} faces.", header.num_vertices, header.num_faces);
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_surf_header<P: AsRef<Path>>(path: P) -> Result<FsSurfaceHeader> {
+    FsSurfaceHeader::from_file(path)
+}
+
+
 impl FsSurface {
-    /// Read an FsSurface instance from a file.
-    pub fn from_file<P: AsRef<Path> + Copy>(path: P) -> Result<FsSurface> {
+    /// Read an FsSurface instance from a file, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsSurface> {
+        FsSurface::from_reader(&mut open_maybe_compressed(path)?)
+    }
 
-        let mut file = BufReader::new(File::open(path)?);
 
-        let hdr = FsSurfaceHeader::from_reader(&mut file).unwrap();
+    /// Read an FsSurface instance from the bytes of a surf file, which may be gzip-compressed.
+    ///
+    /// This does not need file system access, e.g., for use in WebAssembly. It returns an error and does not panic for arbitrary input.
+    pub fn from_bytes(bytes: &[u8]) -> Result<FsSurface> {
+        FsSurface::from_reader(&mut &gunzip_if_compressed(bytes)?[..])
+    }
 
 
-        let mesh: BrainMesh = FsSurface::mesh_from_reader(&mut file, &hdr);
+    /// Read an FsSurface instance from the given byte stream, which must be at the start of the header.
+    ///
+    /// This function returns an error and does not panic for arbitrary input.
+    pub fn from_reader<S>(input: &mut S) -> Result<FsSurface>
+    where
+        S: BufRead,
+    {
+        let hdr = FsSurfaceHeader::from_reader(input)?;
+        let mesh: BrainMesh = FsSurface::mesh_from_reader(input, &hdr)?;
+        let mut tags = Vec::new();
+        input.read_to_end(&mut tags)?;
 
         let surf = FsSurface { 
             header : hdr,
             mesh: mesh,
+            tags,
         };
 
         Ok(surf)
@@ -376,34 +724,25 @@ impl FsSurface {
 
 
     /// Read a brain mesh, i.e., the data part of an FsSurface instance, from a reader.
-    pub fn mesh_from_reader<S>(input: &mut S, hdr: &FsSurfaceHeader) -> BrainMesh
+    pub fn mesh_from_reader<S>(input: &mut S, hdr: &FsSurfaceHeader) -> Result<BrainMesh>
     where
         S: BufRead,
     {
     
         let mut input = ByteOrdered::be(input);
 
-        let num_vert_coords: i32 = hdr.num_vertices * 3;
-        let mut vertex_data : Vec<f32> = Vec::with_capacity(num_vert_coords as usize);
-        for _ in 1..=hdr.num_vertices * 3 {
-            vertex_data.push(input.read_f32().unwrap());
-        }
-
-        //let vertices = Array::from_shape_vec((hdr.num_vertices as usize, 3 as usize), vertex_data).unwrap();
+        let num_vert_coords = usize::try_from(hdr.num_vertices).map_err(|_| NeuroformatsError::InvalidFsSurfaceFormat)? * 3;
+        let vertex_data : Vec<f32> = read_values(num_vert_coords, || input.read_f32())?;
 
-        let mut face_data : Vec<i32> = Vec::with_capacity((hdr.num_faces * 3) as usize);
-        for _ in 1..=hdr.num_faces * 3 {
-            face_data.push(input.read_i32().unwrap());
-        }
-
-        //let faces = Array::from_shape_vec((hdr.num_faces as usize, 3 as usize), face_data).unwrap();
+        let num_face_indices = usize::try_from(hdr.num_faces).map_err(|_| NeuroformatsError::InvalidFsSurfaceFormat)? * 3;
+        let face_data : Vec<i32> = read_values(num_face_indices, || input.read_i32())?;
 
         let mesh = BrainMesh {
             vertices : vertex_data,
             faces : face_data
         };
 
-        mesh
+        Ok(mesh)
     }
 }
 
@@ -417,9 +756,12 @@ impl fmt::Display for FsSurface {
 #[cfg(test)]
 mod test { 
     use super::*;
+    #[cfg(feature = "fs")]
     use tempfile::{tempdir};
+    #[cfg(feature = "fs")]
     use approx::assert_abs_diff_eq;
 
+    #[cfg(feature = "fs")]
     #[test]
     fn the_demo_surf_file_can_be_read() {
         const SURF_FILE: &str = "resources/subjects_dir/subject1/surf/lh.white";
@@ -436,6 +778,7 @@ mod test {
         assert_eq!(298484 * 3, surf.mesh.faces.len());
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn the_center_and_min_max_coords_of_a_brainmesh_can_be_computed() {
         const SURF_FILE: &str = "resources/subjects_dir/subject1/surf/lh.white";
@@ -457,6 +800,7 @@ mod test {
         assert_abs_diff_eq!(expected_center.2, cz, epsilon = 1e-8);
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn the_tiny_demo_surf_file_can_be_exported_to_obj_format() {
         const SURF_FILE: &str = "resources/subjects_dir/subject1/surf/lh.tinysurface";
@@ -468,10 +812,60 @@ mod test {
         assert_eq!(5 * 3, surf.mesh.vertices.len());
         assert_eq!(3 * 3, surf.mesh.faces.len());
 
-        let obj_repr: String = surf.mesh.to_obj();
+        let obj_repr: String = surf.mesh.to_obj().unwrap();
         assert_eq!(String::from("v 0.3 0.3 0.3\nv 0.3 0.3 0.3\nv 0.3 0.3 0.3\nv 0.3 0.3 0.3\nv 0.3 0.3 0.3\nf 1 2 4\nf 2 4 5\nf 3 3 3\n"), obj_repr);
     }
 
+    #[test]
+    fn meshes_with_invalid_faces_cannot_be_exported_to_obj_format() {
+        let vertices = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let mesh = BrainMesh { vertices: vertices.clone(), faces: vec![0, 1, 3] };
+        assert!(matches!(mesh.to_obj(), Err(NeuroformatsError::VertexIndexOutOfRange(3, 3))));
+        let mesh = BrainMesh { vertices: vertices.clone(), faces: vec![0, -1, 2] };
+        assert!(matches!(mesh.to_obj(), Err(NeuroformatsError::VertexIndexOutOfRange(-1, 3))));
+        let mesh = BrainMesh { vertices, faces: vec![0, 1] };
+        assert!(matches!(mesh.to_obj(), Err(NeuroformatsError::InvalidFaceIndexCount(2))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_mesh_can_be_written_from_iterators() {
+        let dir = tempdir().unwrap();
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap();
+        let mesh = &surf.mesh;
+        let path = dir.path().join("lh.iter");
+        let vertices = mesh.vertices_3().iter().copied();
+        let faces = mesh.faces_3().iter().copied();
+        write_surf_from_iter(&path, mesh.num_vertices(), mesh.num_faces(), vertices.clone(), faces.clone(), &WriteOptions::default()).unwrap();
+        assert_eq!(surf.mesh, read_surf(&path).unwrap().mesh);
+
+        let err = write_surf_from_iter(&path, mesh.num_vertices(), mesh.num_faces() + 1, vertices.clone(), faces, &WriteOptions::default()).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+        let inner = err.into_inner().unwrap().downcast::<NeuroformatsError>().unwrap();
+        assert!(matches!(*inner, NeuroformatsError::FaceCountMismatch(expected, found) if expected == mesh.num_faces() + 1 && found == mesh.num_faces()));
+
+        let err = write_surf_from_iter(&path, mesh.num_vertices() + 1, mesh.num_faces(), vertices, std::iter::empty(), &WriteOptions::default()).unwrap_err();
+        let inner = err.into_inner().unwrap().downcast::<NeuroformatsError>().unwrap();
+        assert!(matches!(*inner, NeuroformatsError::VertexCountMismatch(..)));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_mesh_buffers_can_be_viewed_as_vertex_and_face_arrays() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let mesh = &surf.mesh;
+        assert_eq!(mesh.num_vertices(), mesh.vertices_3().len());
+        assert_eq!(mesh.num_faces(), mesh.faces_3().len());
+        assert_eq!(mesh.vertex_coords(5), mesh.vertices_3()[5]);
+        assert_eq!(mesh.face(7), mesh.faces_3()[7].map(|v| v as usize));
+        assert_eq!(mesh.vertices.as_ptr(), mesh.vertices_3().as_ptr() as *const f32);
+
+        let partial = BrainMesh { vertices: vec![0.0, 1.0, 2.0, 3.0], faces: vec![0, 0] };
+        assert_eq!(&[[0.0, 1.0, 2.0]], partial.vertices_3());
+        assert!(partial.faces_3().is_empty());
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn an_obj_file_can_be_parsed_into_a_brainmesh() {
         const OBJ_FILE: &str = "resources/mesh/cube.obj";
@@ -484,6 +878,39 @@ mod test {
         assert_eq!(known_face_count * 3, mesh.faces.len());
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_vertices_and_faces_of_a_mesh_can_be_iterated() {
+        const SURF_FILE: &str = "resources/subjects_dir/subject1/surf/lh.tinysurface";
+        let mesh = read_surf(SURF_FILE).unwrap().mesh;
+
+        let vertices: Vec<[f32; 3]> = mesh.iter_vertices().collect();
+        assert_eq!(mesh.num_vertices(), vertices.len());
+        assert_eq!(mesh.vertex_coords(4), vertices[4]);
+
+        let faces: Vec<[usize; 3]> = mesh.iter_faces().collect();
+        assert_eq!(vec![[0, 1, 3], [1, 3, 4], [2, 2, 2]], faces);
+        assert_eq!([1, 3, 4], mesh.face(1));
+        assert_eq!(vertices[3], mesh.vertex_coords(3));
+    }
+
+    #[test]
+    fn the_vertex_normals_of_a_mesh_can_be_computed() {
+        let mesh = BrainMesh {
+            vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 5.0, 5.0, 5.0],
+            faces: vec![0, 1, 2],
+        };
+        let normals = mesh.vertex_normals();
+        assert_eq!(12, normals.len());
+        assert_eq!(vec![0.0, 0.0, 1.0], normals[0..3].to_vec());
+        assert_eq!(vec![0.0, 0.0, 0.0], normals[9..12].to_vec());
+
+        let neighbors = mesh.vertex_neighbors();
+        assert_eq!(vec![1, 2], neighbors[0]);
+        assert!(neighbors[3].is_empty());
+        assert_eq!(vec![0], mesh.vertex_faces()[2]);
+    }
+
     #[test]
     fn the_coord_center_can_be_computed() {
         let coords: Vec<f32> = vec![0.0, 0.0, 0.0, 0.1, 0.1, 0.1, 0.5, 0.5, 0.5, 0.9, 0.9, 0.9, 0.95, 0.95, 0.95, 1.0, 2.0, 4.0];
@@ -506,6 +933,14 @@ mod test {
         assert_eq!(4.0, maxz);
     }
 
+    #[test]
+    fn the_coord_extrema_of_invalid_coords_are_errors() {
+        assert!(matches!(crate::fs_surface::coord_extrema(&[]), Err(NeuroformatsError::EmptyCoordinates)));
+        assert!(matches!(crate::fs_surface::coord_extrema(&[0.0, 1.0, 2.0, 3.0]), Err(NeuroformatsError::InvalidCoordinateCount(4))));
+        assert!(matches!(crate::fs_surface::coord_center(&[0.0, 1.0, 2.0, 3.0, f32::NAN, 5.0]), Err(NeuroformatsError::NanCoordinate(4))));
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn a_surface_file_can_be_written_and_reread() {
         const SURF_FILE: &str = "resources/subjects_dir/subject1/surf/lh.white";
@@ -514,10 +949,9 @@ mod test {
         let dir = tempdir().unwrap();
 
         let tfile_path = dir.path().join("temp-file.surface");
-        let tfile_path = tfile_path.to_str().unwrap();
-        write_surf(tfile_path, &surf).unwrap();
+        write_surf(&tfile_path, &surf).unwrap();
 
-        let surf_re = read_surf(tfile_path).unwrap();
+        let surf_re = read_surf(&tfile_path).unwrap();
 
         assert_eq!(149244, surf_re.header.num_vertices);
         assert_eq!(298484, surf_re.header.num_faces);
@@ -526,6 +960,73 @@ mod test {
         assert_eq!(298484, surf_re.mesh.num_faces());
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn surface_files_are_written_byte_identical_in_strict_mode() {
+        let dir = tempdir().unwrap();
+        let strict = WriteOptions { strict: true, ..Default::default() };
+        for file in ["lh.white", "rh.white", "lh.tinysurface"] {
+            let path = format!("resources/subjects_dir/subject1/surf/{}", file);
+            let tfile_path = dir.path().join(file);
+            write_surf_with_options(&tfile_path, &read_surf(&path).unwrap(), &strict).unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), std::fs::read(&tfile_path).unwrap());
+        }
+
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        assert_eq!(1336, surf.tags.len());
+        let tfile_path = dir.path().join("lh.white.nonstrict");
+        write_surf(&tfile_path, &surf).unwrap();
+        assert!(read_surf(&tfile_path).unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn a_surface_header_can_be_created_with_a_fixed_info_line() {
+        let header = FsSurfaceHeader::new("created by me\n", 3, 1).unwrap();
+        assert_eq!("created by me\n\n", header.info_line);
+        assert_eq!(3, header.num_vertices);
+        assert_eq!(1, header.num_faces);
+        assert_eq!("two lines\nof text\n\n", FsSurfaceHeader::new("two lines\nof text", 0, 0).unwrap().info_line);
+
+        assert!(matches!(FsSurfaceHeader::new("created by m\u{e9}", 3, 1), Err(NeuroformatsError::InvalidFsSurfaceInfoLine(_))));
+        assert!(matches!(FsSurfaceHeader::new("early\n\nend", 3, 1), Err(NeuroformatsError::InvalidFsSurfaceInfoLine(_))));
+        assert!(FsSurfaceHeader::new("too many vertices", usize::MAX, 1).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn writing_a_surface_with_an_invalid_info_line_is_an_error() {
+        let mut surf = read_surf("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap();
+        surf.header.info_line = String::from("no terminator");
+        let dir = tempdir().unwrap();
+        let tfile_path = dir.path().join("invalid.surface");
+        let err = write_surf(&tfile_path, &surf).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+        assert!(!tfile_path.exists());
+
+        surf.header.info_line = String::from("created by m\u{e9}\n\n");
+        assert!(write_surf(&tfile_path, &surf).is_err());
+        let strict = WriteOptions { strict: true, ..Default::default() };
+        write_surf_with_options(&tfile_path, &surf, &strict).unwrap();
+        assert_eq!(surf.header.info_line, read_surf(&tfile_path).unwrap().header.info_line);
+    }
+
+    #[test]
+    fn reading_corrupted_surf_files_does_not_panic() {
+        let bytes = std::fs::read("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap();
+        assert_eq!(5, FsSurface::from_reader(&mut &bytes[..]).unwrap().mesh.num_vertices());
+        for variant in crate::util::corrupted_variants(&bytes) {
+            let _ = FsSurface::from_reader(&mut &variant[..]);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "fs"))]
+    fn a_surface_can_be_serialized_and_deserialized() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap();
+        let json = serde_json::to_string(&surf).unwrap();
+        let surf_re: FsSurface = serde_json::from_str(&json).unwrap();
+        assert_eq!(surf, surf_re);
+    }
 }
 
 
@@ -0,0 +1,828 @@
+//! Export of brain meshes to the binary glTF 2.0 format (GLB).
+//!
+//! GLB files can be displayed by web viewers like three.js and Babylon.js and imported into 3D software like Blender.
+//! Per-vertex data can be included as vertex colors, e.g., the region colors of a parcellation from [`crate::FsAnnot::vertex_colors`].
+//! Time series of per-vertex data, like task activation time courses, can be exported as animations with [`BrainMesh::to_gltf_animated`].
+//! For web delivery, the vertex data can be quantized with the `KHR_mesh_quantization` extension, see [`GltfOptions::quantize`].
+
+
+use std::fmt::Write as FmtWrite;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::colormap::{rgb_to_rgba, values_to_colors};
+#[cfg(feature = "image")]
+use crate::colormap::{values_to_lut_indices, viridis, COLORMAP_SIZE, NAN_COLOR};
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::json_string;
+#[cfg(feature = "fs")]
+use crate::util::WriteOptions;
+
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_TYPE_JSON: u32 = 0x4E4F_534A;
+const CHUNK_TYPE_BIN: u32 = 0x004E_4942;
+
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const COMPONENT_BYTE: u32 = 5120;
+const COMPONENT_UNSIGNED_BYTE: u32 = 5121;
+const COMPONENT_SHORT: u32 = 5122;
+const COMPONENT_UNSIGNED_SHORT: u32 = 5123;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const COMPONENT_FLOAT: u32 = 5126;
+const FILTER_NEAREST: u32 = 9728;
+const WRAP_CLAMP_TO_EDGE: u32 = 33071;
+
+/// The largest quantized coordinate, the coordinates are stored as 16 bit integers in the range `-QUANTIZED_MAX` to `QUANTIZED_MAX`.
+const QUANTIZED_MAX: f32 = 32767.0;
+const IDENTITY_MATRIX: [f32; 16] = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+
+
+/// Options for the material and the scene of exported glTF files, see [`BrainMesh::to_gltf_with_options`].
+///
+/// The defaults give a white, non-metallic, double-sided material, so meshes without vertex colors are shown in light gray by most viewers,
+/// and vertex colors are shown unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfOptions {
+    /// The RGBA base color of the material, with values in the range `0.0` to `1.0`. It is multiplied with the vertex colors or the texture.
+    pub base_color: [f32; 4],
+    /// The metalness of the material, from `0.0` (dielectric) to `1.0` (metal).
+    pub metallic: f32,
+    /// The roughness of the material, from `0.0` (smooth, shiny) to `1.0` (rough, matte).
+    pub roughness: f32,
+    /// Whether the back sides of the faces are rendered, too. Useful for meshes which are viewed from the inside, like the medial wall.
+    pub double_sided: bool,
+    /// An optional name of the mesh, which is shown by viewers and 3D software.
+    pub mesh_name: Option<String>,
+    /// An optional 4x4 transformation matrix for the node of the mesh, in column-major order as required by glTF.
+    pub transform: Option<[f32; 16]>,
+    /// A uniform scale factor applied after the `transform`. FreeSurfer coordinates are in millimeters and glTF uses meters, so use `0.001`
+    /// to export meshes in their real size.
+    pub scale: f32,
+    /// Whether to store the vertex data with the `KHR_mesh_quantization` extension, to reduce the file size, e.g., for web delivery.
+    ///
+    /// The vertex coordinates are stored as 16 bit integers, which gives a precision of about 0.003 mm for a brain mesh, and are scaled
+    /// back by the transformation of the node. The normals are stored as 8 bit integers and texture coordinates as 16 bit integers.
+    /// This reduces the size of the vertex data to about half, but the faces are not changed. Viewers which do not support the
+    /// extension, like old versions of some 3D software, refuse to load these files. Three.js and Babylon.js support it.
+    pub quantize: bool,
+}
+
+
+impl Default for GltfOptions {
+    fn default() -> Self {
+        GltfOptions {
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            metallic: 0.0,
+            roughness: 0.7,
+            double_sided: true,
+            mesh_name: None,
+            transform: None,
+            scale: 1.0,
+            quantize: false,
+        }
+    }
+}
+
+
+impl GltfOptions {
+    /// Get the JSON properties of the node, i.e., its transformation matrix, or an empty string if the node is not transformed.
+    ///
+    /// For quantized meshes, the `dequantization` gives the offset and the step size of the quantized coordinates, see
+    /// [`BrainMesh::position_quantization`], and is applied before the transform.
+    fn node_transform_json(&self, dequantization: Option<([f32; 3], f32)>) -> String {
+        let mut matrix = match self.transform {
+            Some(matrix) => matrix,
+            None if self.scale == 1.0 && dequantization.is_none() => return String::new(),
+            None => IDENTITY_MATRIX,
+        };
+        // Scaling after the transform scales the first 3 rows, which are every 4th value in column-major order.
+        for (idx, value) in matrix.iter_mut().enumerate() {
+            if idx % 4 != 3 {
+                *value *= self.scale;
+            }
+        }
+        // Dequantizing before the transform scales the first 3 columns by the step size and moves the last column by the offset.
+        if let Some((offset, step)) = dequantization {
+            let transform = matrix;
+            for value in matrix[..12].iter_mut() {
+                *value *= step;
+            }
+            for row in 0..4 {
+                matrix[12 + row] = transform[row] * offset[0] + transform[4 + row] * offset[1] + transform[8 + row] * offset[2] + transform[12 + row];
+            }
+        }
+        format!(r#","matrix":[{}]"#, matrix.iter().map(|v| json_f32(*v)).collect::<Vec<_>>().join(","))
+    }
+
+
+    /// Get the JSON property with the material, with the given reference to the base color texture, which may be empty.
+    fn material_json(&self, texture_ref: &str) -> String {
+        let [r, g, b, a] = self.base_color.map(json_f32);
+        format!(r#""materials":[{{"pbrMetallicRoughness":{{"baseColorFactor":[{},{},{},{}],"metallicFactor":{},"roughnessFactor":{}{}}},"doubleSided":{}}}],"#,
+            r, g, b, a, json_f32(self.metallic), json_f32(self.roughness), texture_ref, self.double_sided)
+    }
+
+
+    /// Get the JSON properties which declare the used extensions, or an empty string if no extension is used.
+    fn extensions_json(&self) -> String {
+        if self.quantize {
+            String::from(r#""extensionsUsed":["KHR_mesh_quantization"],"extensionsRequired":["KHR_mesh_quantization"],"#)
+        } else {
+            String::new()
+        }
+    }
+}
+
+
+/// The ways in which the frames of a per-vertex time series are stored in animated glTF exports, see [`BrainMesh::to_gltf_animated`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GltfAnimationMode {
+    /// Store the mesh once per frame, with the vertex colors of the frame, and show one frame at a time by scaling the others to zero.
+    /// The geometry is shared, so this adds only the colors per frame. It is supported by all viewers which play animations.
+    FrameNodes,
+    /// Store the colors of the frames as `COLOR_0` morph targets of a single mesh, and blend smoothly between consecutive frames. The morph
+    /// targets store the color differences as floats, which needs 4 times the space of the colors of [`GltfAnimationMode::FrameNodes`].
+    /// Morphed vertex colors are optional in glTF and not supported by all viewers, e.g., three.js supports them, but Blender does not.
+    MorphTargets,
+}
+
+
+/// Options for the animation of per-vertex time series in glTF exports, see [`BrainMesh::to_gltf_animated`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfAnimationOptions {
+    /// How the frames are stored in the file.
+    pub mode: GltfAnimationMode,
+    /// The time each frame is shown, in seconds.
+    pub frame_duration: f32,
+}
+
+
+impl Default for GltfAnimationOptions {
+    fn default() -> Self {
+        GltfAnimationOptions { mode: GltfAnimationMode::FrameNodes, frame_duration: 0.5 }
+    }
+}
+
+
+impl BrainMesh {
+    /// Export this mesh in binary glTF 2.0 format (GLB).
+    ///
+    /// The vertex coordinates, vertex normals (see [`BrainMesh::vertex_normals`]) and faces are stored in the file. FreeSurfer
+    /// coordinates are in millimeters, while glTF uses meters, so scale the scene in the viewer if needed.
+    ///
+    /// # Parameters
+    ///
+    /// * `vertex_colors`: optional RGB colors for the vertices, 3 values per vertex, like the output of [`crate::FsAnnot::vertex_colors`] with `alpha` set to `false`.
+    ///
+    /// # Return value
+    ///
+    /// The GLB file contents. Returns [`NeuroformatsError::ColorCountMismatch`] if `vertex_colors` is given and does not contain 3 values per vertex,
+    /// and an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let glb = surf.mesh.to_gltf(Some(&annot.vertex_colors(false, 0))).unwrap();
+    /// std::fs::write("/tmp/lh_aparc.glb", glb).expect("Unable to write glTF file");
+    /// ```
+    pub fn to_gltf(&self, vertex_colors: Option<&[u8]>) -> Result<Vec<u8>> {
+        self.to_gltf_with_options(vertex_colors, &GltfOptions::default())
+    }
+
+
+    /// Export this mesh in binary glTF 2.0 format (GLB), with the material and scene settings from the given [`GltfOptions`].
+    ///
+    /// See [`BrainMesh::to_gltf`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::gltf::GltfOptions;
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+    /// let options = GltfOptions { base_color: [0.9, 0.8, 0.7, 1.0], mesh_name: Some(String::from("lh.pial")), scale: 0.001, ..Default::default() };
+    /// std::fs::write("/tmp/lh_pial.glb", surf.mesh.to_gltf_with_options(None, &options).unwrap()).expect("Unable to write glTF file");
+    /// ```
+    pub fn to_gltf_with_options(&self, vertex_colors: Option<&[u8]>, options: &GltfOptions) -> Result<Vec<u8>> {
+        self.validate_faces()?;
+        if let Some(colors) = vertex_colors {
+            if colors.len() != self.vertices.len() {
+                return Err(NeuroformatsError::ColorCountMismatch(self.vertices.len(), colors.len()));
+            }
+        }
+        Ok(self.build_gltf(vertex_colors, None, options))
+    }
+
+
+    /// Export this mesh with per-vertex values in binary glTF 2.0 format (GLB), encoding the values as a texture instead of vertex colors.
+    ///
+    /// Some engines ignore the `COLOR_0` vertex colors written by [`BrainMesh::to_gltf`]. This function embeds the viridis colormap (see
+    /// [`crate::colormap`]) as a small PNG texture, and stores the position of the color of each vertex in the texture as its texture
+    /// coordinate `TEXCOORD_0`, so all viewers which support textured materials show the same colors. The texture is sampled with nearest
+    /// neighbor filtering, so the colors match [`crate::colormap::values_to_colors`] at the vertices.
+    ///
+    /// Requires the `image` feature.
+    ///
+    /// # Parameters
+    ///
+    /// * `values`: the per-vertex values, e.g., the `data` of an [`FsCurv`](crate::FsCurv). NaN values get the [`crate::colormap::NAN_COLOR`].
+    /// * `min`: the value mapped to the lowest color. Smaller values are clamped.
+    /// * `max`: the value mapped to the highest color. Larger values are clamped.
+    ///
+    /// # Return value
+    ///
+    /// The GLB file contents. Returns [`NeuroformatsError::VertexCountMismatch`] if `values` does not contain one value per vertex, and an error
+    /// if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// let glb = surf.mesh.to_gltf_textured(&thickness.data, 1.0, 4.0).unwrap();
+    /// std::fs::write("/tmp/lh_thickness.glb", glb).expect("Unable to write glTF file");
+    /// ```
+    #[cfg(feature = "image")]
+    pub fn to_gltf_textured(&self, values: &[f32], min: f32, max: f32) -> Result<Vec<u8>> {
+        self.to_gltf_textured_with_options(values, min, max, &GltfOptions::default())
+    }
+
+
+    /// Export this mesh with per-vertex values encoded as a texture in binary glTF 2.0 format (GLB), with the material and scene settings
+    /// from the given [`GltfOptions`].
+    ///
+    /// Requires the `image` feature. See [`BrainMesh::to_gltf_textured`] for details.
+    #[cfg(feature = "image")]
+    pub fn to_gltf_textured_with_options(&self, values: &[f32], min: f32, max: f32, options: &GltfOptions) -> Result<Vec<u8>> {
+        self.validate_faces()?;
+        if values.len() != self.num_vertices() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.num_vertices(), values.len()));
+        }
+        // The texture holds the colormap, followed by one texel with the color for NaN values.
+        let width = COLORMAP_SIZE + 1;
+        let mut texels: Vec<u8> = viridis().iter().flatten().copied().collect();
+        texels.extend_from_slice(&NAN_COLOR);
+        let mut png = Vec::new();
+        image::RgbImage::from_raw(width as u32, 1, texels).expect("Texture size matches its data")
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .expect("Encoding a PNG in memory does not fail");
+
+        let texcoords: Vec<f32> = values_to_lut_indices(values, min, max).into_iter()
+            .flat_map(|idx| [(idx.unwrap_or(COLORMAP_SIZE) as f32 + 0.5) / width as f32, 0.5])
+            .collect();
+        Ok(self.build_gltf(None, Some((&texcoords, &png)), options))
+    }
+
+
+    /// Assemble the GLB file for this mesh, with optional RGB vertex colors and an optional texture, given as the texture coordinates
+    /// (2 values per vertex) and the PNG image.
+    fn build_gltf(&self, vertex_colors: Option<&[u8]>, texture: Option<(&[f32], &[u8])>, options: &GltfOptions) -> Vec<u8> {
+        let num_vertices = self.num_vertices();
+
+        let mut bin: Vec<u8> = Vec::with_capacity(self.vertices.len() * 8 + num_vertices * 4 + self.faces.len() * 4);
+        let mut views = Vec::new();
+        let mut accessors = Vec::new();
+        let dequantization = options.quantize.then(|| self.position_quantization());
+        let mut attributes = self.push_positions_and_normals(&mut bin, &mut views, &mut accessors, dequantization);
+
+        if let Some(colors) = vertex_colors {
+            attributes.push(format!(r#""COLOR_0":{}"#, push_colors(&mut bin, &mut views, &mut accessors, colors)));
+        }
+
+        let mut texture_ref = String::new();
+        let mut texture_json = String::new();
+        if let Some((texcoords, png)) = texture {
+            attributes.push(format!(r#""TEXCOORD_0":{}"#, accessors.len()));
+            if options.quantize {
+                let view = push_view(&mut bin, &mut views, texcoords.iter().flat_map(|v| ((v * 65535.0).round() as u16).to_le_bytes()), TARGET_ARRAY_BUFFER);
+                accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"normalized":true,"count":{},"type":"VEC2"}}"#, view, COMPONENT_UNSIGNED_SHORT, num_vertices));
+            } else {
+                let view = push_view(&mut bin, &mut views, texcoords.iter().flat_map(|v| v.to_le_bytes()), TARGET_ARRAY_BUFFER);
+                accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"VEC2"}}"#, view, COMPONENT_FLOAT, num_vertices));
+            }
+
+            // Images are not vertex data, so their buffer view has no target.
+            let image_view = push_view(&mut bin, &mut views, png.iter().copied(), 0);
+            texture_ref = String::from(r#","baseColorTexture":{"index":0}"#);
+            texture_json = format!(concat!(r#""textures":[{{"sampler":0,"source":0}}],"images":[{{"bufferView":{},"mimeType":"image/png"}}],"#,
+                r#""samplers":[{{"magFilter":{},"minFilter":{},"wrapS":{},"wrapT":{}}}],"#),
+                image_view, FILTER_NEAREST, FILTER_NEAREST, WRAP_CLAMP_TO_EDGE, WRAP_CLAMP_TO_EDGE);
+        }
+
+        let indices = self.push_indices(&mut bin, &mut views, &mut accessors);
+
+        let mut json = asset_json();
+        json.push_str(&options.extensions_json());
+        let name = options.mesh_name.as_ref().map(|name| format!(r#""name":{},"#, json_string(name))).unwrap_or_default();
+        write!(json, r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{{}"mesh":0{}}}],"#, name, options.node_transform_json(dequantization)).unwrap();
+        write!(json, r#""meshes":[{{{}"primitives":[{{"attributes":{{{}}},"indices":{},"mode":4,"material":0}}]}}],"#, name, attributes.join(","), indices).unwrap();
+        json.push_str(&options.material_json(&texture_ref));
+        json.push_str(&texture_json);
+        write!(json, r#""buffers":[{{"byteLength":{}}}],"bufferViews":[{}],"accessors":[{}]}}"#, bin.len(), views.join(","), accessors.join(",")).unwrap();
+
+        glb_container(json.into_bytes(), bin)
+    }
+
+
+    /// Export this mesh with a time series of per-vertex values, e.g., a task activation time course, as an animation in binary glTF 2.0
+    /// format (GLB).
+    ///
+    /// The values of each frame are mapped to vertex colors with the viridis colormap (see [`crate::colormap::values_to_colors`]), and
+    /// the animation shows the frames one after another in a loop. See [`GltfAnimationMode`] for the ways in which the animation can be
+    /// stored. The animation is played by viewers like three.js, Babylon.js and the glTF viewers of most 3D software.
+    ///
+    /// # Parameters
+    ///
+    /// * `frames`: the per-vertex values of each frame, e.g., from [`crate::FsMgh::frames_as_f32`] for a surface overlay with dimensions
+    ///   (number of vertices, 1, 1, number of frames). NaN values get the [`crate::colormap::NAN_COLOR`].
+    /// * `min`: the value mapped to the lowest color. Smaller values are clamped.
+    /// * `max`: the value mapped to the highest color. Larger values are clamped.
+    /// * `animation`: the settings of the animation.
+    ///
+    /// # Return value
+    ///
+    /// The GLB file contents. Returns [`NeuroformatsError::NoFrames`] if `frames` is empty, [`NeuroformatsError::VertexCountMismatch`] if a frame
+    /// does not contain one value per vertex, and an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::gltf::GltfAnimationOptions;
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.inflated").unwrap();
+    /// let series = neuroformats::read_mgh("/path/to/subjects_dir/subject1/surf/lh.task.fsnative.mgh").unwrap();
+    /// let glb = surf.mesh.to_gltf_animated(&series.frames_as_f32().unwrap(), -3.0, 3.0, &GltfAnimationOptions::default()).unwrap();
+    /// std::fs::write("/tmp/lh_task.glb", glb).expect("Unable to write glTF file");
+    /// ```
+    pub fn to_gltf_animated<F: AsRef<[f32]>>(&self, frames: &[F], min: f32, max: f32, animation: &GltfAnimationOptions) -> Result<Vec<u8>> {
+        self.to_gltf_animated_with_options(frames, min, max, animation, &GltfOptions::default())
+    }
+
+
+    /// Export this mesh with a time series of per-vertex values as an animation in binary glTF 2.0 format (GLB), with the material and
+    /// scene settings from the given [`GltfOptions`].
+    ///
+    /// See [`BrainMesh::to_gltf_animated`] for details.
+    pub fn to_gltf_animated_with_options<F: AsRef<[f32]>>(&self, frames: &[F], min: f32, max: f32, animation: &GltfAnimationOptions, options: &GltfOptions) -> Result<Vec<u8>> {
+        self.validate_faces()?;
+        if frames.is_empty() {
+            return Err(NeuroformatsError::NoFrames);
+        }
+        if let Some(frame) = frames.iter().find(|frame| frame.as_ref().len() != self.num_vertices()) {
+            return Err(NeuroformatsError::VertexCountMismatch(self.num_vertices(), frame.as_ref().len()));
+        }
+        let frame_colors: Vec<Vec<u8>> = frames.iter().map(|frame| values_to_colors(frame.as_ref(), min, max)).collect();
+        Ok(self.build_animated_gltf(&frame_colors, animation, options))
+    }
+
+
+    /// Assemble the GLB file for this mesh with an animation of the given RGB vertex colors, 3 values per vertex for each frame.
+    fn build_animated_gltf(&self, frame_colors: &[Vec<u8>], animation: &GltfAnimationOptions, options: &GltfOptions) -> Vec<u8> {
+        let num_vertices = self.num_vertices();
+        let num_frames = frame_colors.len();
+
+        let mut bin: Vec<u8> = Vec::new();
+        let mut views = Vec::new();
+        let mut accessors = Vec::new();
+        let dequantization = options.quantize.then(|| self.position_quantization());
+        let mut attributes = self.push_positions_and_normals(&mut bin, &mut views, &mut accessors, dequantization);
+        let indices = self.push_indices(&mut bin, &mut views, &mut accessors);
+
+        // The animation has one keyframe per frame, plus a final keyframe which repeats the last frame, so it is shown as long as the others.
+        let times: Vec<f32> = (0..=num_frames).map(|k| k as f32 * animation.frame_duration).collect();
+        let view = push_view(&mut bin, &mut views, times.iter().flat_map(|t| t.to_le_bytes()), 0);
+        let input = accessors.len();
+        accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"SCALAR","min":[0],"max":[{}]}}"#,
+            view, COMPONENT_FLOAT, times.len(), json_f32(times[num_frames])));
+        let frame_of_keyframe = |k: usize| k.min(num_frames - 1);
+
+        let name = options.mesh_name.as_ref().map(|name| format!(r#""name":{},"#, json_string(name))).unwrap_or_default();
+        let mut samplers = Vec::new();
+        let mut channels = Vec::new();
+        let (nodes, meshes) = match animation.mode {
+            GltfAnimationMode::FrameNodes => {
+                // One child node per frame, which is only visible (has a scale of 1) during its frame.
+                let mut nodes = vec![format!(r#"{{{}"children":[{}]{}}}"#, name, (1..=num_frames).map(|n| n.to_string()).collect::<Vec<_>>().join(","), options.node_transform_json(dequantization))];
+                let mut meshes = Vec::new();
+                for (frame, colors) in frame_colors.iter().enumerate() {
+                    let color = push_colors(&mut bin, &mut views, &mut accessors, colors);
+                    meshes.push(format!(r#"{{{}"primitives":[{{"attributes":{{{},"COLOR_0":{}}},"indices":{},"mode":4,"material":0}}]}}"#, name, attributes.join(","), color, indices));
+                    let scale = if frame == 0 { "" } else { r#","scale":[0,0,0]"# };
+                    nodes.push(format!(r#"{{"mesh":{}{}}}"#, frame, scale));
+
+                    let scales: Vec<f32> = (0..=num_frames).flat_map(|k| [if frame_of_keyframe(k) == frame { 1.0f32 } else { 0.0 }; 3]).collect();
+                    let view = push_view(&mut bin, &mut views, scales.iter().flat_map(|v| v.to_le_bytes()), 0);
+                    samplers.push(format!(r#"{{"input":{},"output":{},"interpolation":"STEP"}}"#, input, accessors.len()));
+                    accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"VEC3"}}"#, view, COMPONENT_FLOAT, times.len()));
+                    channels.push(format!(r#"{{"sampler":{},"target":{{"node":{},"path":"scale"}}}}"#, frame, frame + 1));
+                }
+                (nodes, meshes)
+            },
+            GltfAnimationMode::MorphTargets => {
+                // The first frame is the base color, the other frames are morph targets which store the difference to it.
+                attributes.push(format!(r#""COLOR_0":{}"#, push_colors(&mut bin, &mut views, &mut accessors, &frame_colors[0])));
+                let base = rgb_to_rgba(&frame_colors[0], 255);
+                let mut targets = Vec::new();
+                for colors in &frame_colors[1..] {
+                    let deltas = rgb_to_rgba(colors, 255).iter().zip(base.iter()).flat_map(|(c, b)| ((*c as f32 - *b as f32) / 255.0).to_le_bytes()).collect::<Vec<u8>>();
+                    let view = push_view(&mut bin, &mut views, deltas.into_iter(), TARGET_ARRAY_BUFFER);
+                    targets.push(format!(r#"{{"COLOR_0":{}}}"#, accessors.len()));
+                    accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"VEC4"}}"#, view, COMPONENT_FLOAT, num_vertices));
+                }
+                let num_targets = num_frames - 1;
+                let mut primitive = format!(r#"{{"attributes":{{{}}},"indices":{},"mode":4,"material":0"#, attributes.join(","), indices);
+                let mut weights = String::new();
+                if num_targets > 0 {
+                    write!(primitive, r#","targets":[{}]"#, targets.join(",")).unwrap();
+                    write!(weights, r#","weights":[{}]"#, vec!["0"; num_targets].join(",")).unwrap();
+
+                    let outputs: Vec<f32> = (0..=num_frames).flat_map(|k| (1..num_frames).map(move |target| if frame_of_keyframe(k) == target { 1.0f32 } else { 0.0 })).collect();
+                    let view = push_view(&mut bin, &mut views, outputs.iter().flat_map(|v| v.to_le_bytes()), 0);
+                    samplers.push(format!(r#"{{"input":{},"output":{},"interpolation":"LINEAR"}}"#, input, accessors.len()));
+                    accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"SCALAR"}}"#, view, COMPONENT_FLOAT, outputs.len()));
+                    channels.push(String::from(r#"{"sampler":0,"target":{"node":0,"path":"weights"}}"#));
+                }
+                primitive.push('}');
+                let nodes = vec![format!(r#"{{{}"mesh":0{}}}"#, name, options.node_transform_json(dequantization))];
+                (nodes, vec![format!(r#"{{{}"primitives":[{}]{}}}"#, name, primitive, weights)])
+            },
+        };
+
+        let mut json = asset_json();
+        json.push_str(&options.extensions_json());
+        write!(json, r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{}],"meshes":[{}],"#, nodes.join(","), meshes.join(",")).unwrap();
+        if !channels.is_empty() {
+            write!(json, r#""animations":[{{"samplers":[{}],"channels":[{}]}}],"#, samplers.join(","), channels.join(",")).unwrap();
+        }
+        json.push_str(&options.material_json(""));
+        write!(json, r#""buffers":[{{"byteLength":{}}}],"bufferViews":[{}],"accessors":[{}]}}"#, bin.len(), views.join(","), accessors.join(",")).unwrap();
+
+        glb_container(json.into_bytes(), bin)
+    }
+
+
+    /// Append the vertex coordinates and vertex normals of this mesh to the binary buffer. Returns the `POSITION` and `NORMAL` vertex attributes.
+    ///
+    /// If a `dequantization` from [`BrainMesh::position_quantization`] is given, the coordinates are stored as 16 bit integers and the normals
+    /// as 8 bit integers, see [`GltfOptions::quantize`]. Each vertex is padded to 4 bytes, as required for vertex attributes.
+    fn push_positions_and_normals(&self, bin: &mut Vec<u8>, views: &mut Vec<String>, accessors: &mut Vec<String>, dequantization: Option<([f32; 3], f32)>) -> Vec<String> {
+        let num_vertices = self.num_vertices();
+        let mut attributes = Vec::new();
+        attributes.push(format!(r#""POSITION":{}"#, accessors.len()));
+        if let Some((offset, step)) = dequantization {
+            let quantized: Vec<[i16; 3]> = self.vertices_3().iter()
+                .map(|v| [0, 1, 2].map(|axis| ((v[axis] - offset[axis]) / step).round().clamp(-QUANTIZED_MAX, QUANTIZED_MAX) as i16)).collect();
+            let (min, max) = bounds(quantized.iter().map(|v| v.map(|c| c as f32)));
+            let view = push_vertex_view(bin, views, quantized.iter().flat_map(|v| [v[0], v[1], v[2], 0]).flat_map(|c| c.to_le_bytes()), 8);
+            accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"VEC3"{}}}"#, view, COMPONENT_SHORT, num_vertices, bounds_json(num_vertices, min, max)));
+
+            let normals = self.vertex_normals().into_iter().map(|n| (n * 127.0).round() as i8);
+            let view = push_vertex_view(bin, views, normals.collect::<Vec<i8>>().chunks_exact(3).flat_map(|n| [n[0], n[1], n[2], 0]).map(|n| n as u8), 4);
+            attributes.push(format!(r#""NORMAL":{}"#, accessors.len()));
+            accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"normalized":true,"count":{},"type":"VEC3"}}"#, view, COMPONENT_BYTE, num_vertices));
+        } else {
+            let (min, max) = bounds(self.vertices_3().iter().copied());
+            let view = push_view(bin, views, self.vertices.iter().flat_map(|v| v.to_le_bytes()), TARGET_ARRAY_BUFFER);
+            accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"VEC3"{}}}"#, view, COMPONENT_FLOAT, num_vertices, bounds_json(num_vertices, min, max)));
+
+            let view = push_view(bin, views, self.vertex_normals().iter().flat_map(|v| v.to_le_bytes()), TARGET_ARRAY_BUFFER);
+            attributes.push(format!(r#""NORMAL":{}"#, accessors.len()));
+            accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"VEC3"}}"#, view, COMPONENT_FLOAT, num_vertices));
+        }
+        attributes
+    }
+
+
+    /// Compute the offset and step size for the quantization of the vertex coordinates to 16 bit integers, see [`GltfOptions::quantize`].
+    ///
+    /// The offset is the center of the bounding box. The step size is the same for all axes, so the normals are not distorted by the node
+    /// transform which scales the coordinates back.
+    fn position_quantization(&self) -> ([f32; 3], f32) {
+        let (min, max) = bounds(self.vertices_3().iter().copied());
+        if self.num_vertices() == 0 {
+            return ([0.0; 3], 1.0);
+        }
+        let offset = [0, 1, 2].map(|axis| (min[axis] + max[axis]) / 2.0);
+        let half_extent = (0..3).map(|axis| (max[axis] - min[axis]) / 2.0).fold(0.0f32, f32::max);
+        (offset, if half_extent > 0.0 { half_extent / QUANTIZED_MAX } else { 1.0 })
+    }
+
+
+    /// Append the face indices of this mesh to the binary buffer. Returns the index of their accessor.
+    fn push_indices(&self, bin: &mut Vec<u8>, views: &mut Vec<String>, accessors: &mut Vec<String>) -> usize {
+        let view = push_view(bin, views, self.faces.iter().flat_map(|f| (*f as u32).to_le_bytes()), TARGET_ELEMENT_ARRAY_BUFFER);
+        accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"count":{},"type":"SCALAR"}}"#, view, COMPONENT_UNSIGNED_INT, self.faces.len()));
+        accessors.len() - 1
+    }
+}
+
+
+/// Append RGB vertex colors, 3 values per vertex, to the binary buffer. Returns the index of their accessor.
+fn push_colors(bin: &mut Vec<u8>, views: &mut Vec<String>, accessors: &mut Vec<String>, colors: &[u8]) -> usize {
+    // glTF requires vertex attributes to be aligned to 4 bytes, so the colors are stored as RGBA.
+    let view = push_view(bin, views, rgb_to_rgba(colors, 255).into_iter(), TARGET_ARRAY_BUFFER);
+    accessors.push(format!(r#"{{"bufferView":{},"componentType":{},"normalized":true,"count":{},"type":"VEC4"}}"#, view, COMPONENT_UNSIGNED_BYTE, colors.len() / 3));
+    accessors.len() - 1
+}
+
+
+/// Compute the minimum and maximum of each axis of 3D points. The result is `f32::MAX` and `f32::MIN` if there are no points.
+fn bounds<I: Iterator<Item = [f32; 3]>>(points: I) -> ([f32; 3], [f32; 3]) {
+    points.fold(([f32::MAX; 3], [f32::MIN; 3]), |(mut min, mut max), v| {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(v[axis]);
+            max[axis] = max[axis].max(v[axis]);
+        }
+        (min, max)
+    })
+}
+
+
+/// Get the JSON properties with the bounds of the `POSITION` accessor, which glTF requires, or an empty string if there are no vertices.
+fn bounds_json(num_vertices: usize, min: [f32; 3], max: [f32; 3]) -> String {
+    if num_vertices > 0 { format!(r#","min":[{},{},{}],"max":[{},{},{}]"#, min[0], min[1], min[2], max[0], max[1], max[2]) } else { String::new() }
+}
+
+
+/// Get the start of the JSON chunk, with the asset information.
+fn asset_json() -> String {
+    format!(r#"{{"asset":{{"version":"2.0","generator":"neuroformats {}"}},"#, env!("CARGO_PKG_VERSION"))
+}
+
+
+/// Append the bytes of a buffer view to the binary buffer and register the view. Returns the index of the view.
+///
+/// The view starts at a multiple of 4 bytes, as required for vertex attributes. A `target` of `0` registers the view without a target.
+fn push_view<I: Iterator<Item = u8>>(bin: &mut Vec<u8>, views: &mut Vec<String>, bytes: I, target: u32) -> usize {
+    let target = if target == 0 { String::new() } else { format!(r#","target":{}"#, target) };
+    push_view_with_properties(bin, views, bytes, &target)
+}
+
+
+/// Append the bytes of a vertex attribute with padded elements to the binary buffer and register the view, with the given stride in bytes.
+/// Returns the index of the view.
+fn push_vertex_view<I: Iterator<Item = u8>>(bin: &mut Vec<u8>, views: &mut Vec<String>, bytes: I, byte_stride: usize) -> usize {
+    push_view_with_properties(bin, views, bytes, &format!(r#","byteStride":{},"target":{}"#, byte_stride, TARGET_ARRAY_BUFFER))
+}
+
+
+/// Append the bytes of a buffer view to the binary buffer and register the view with the given additional JSON properties.
+fn push_view_with_properties<I: Iterator<Item = u8>>(bin: &mut Vec<u8>, views: &mut Vec<String>, bytes: I, properties: &str) -> usize {
+    bin.resize(bin.len() + (4 - bin.len() % 4) % 4, 0);
+    let offset = bin.len();
+    bin.extend(bytes);
+    views.push(format!(r#"{{"buffer":0,"byteOffset":{},"byteLength":{}{}}}"#, offset, bin.len() - offset, properties));
+    views.len() - 1
+}
+
+
+/// Format a number for the JSON chunk, using the shortest representation of the `f32` value. NaN and infinite values are written as `null`.
+fn json_f32(value: f32) -> String {
+    if value.is_finite() { value.to_string() } else { String::from("null") }
+}
+
+
+/// Assemble a GLB file from the JSON and binary chunks, padding both to 4 bytes as required.
+fn glb_container(mut json: Vec<u8>, mut bin: Vec<u8>) -> Vec<u8> {
+    json.resize(json.len() + (4 - json.len() % 4) % 4, b' ');
+    bin.resize(bin.len() + (4 - bin.len() % 4) % 4, 0);
+    let total_len = 12 + 8 + json.len() + 8 + bin.len();
+    let mut glb = Vec::with_capacity(total_len);
+    for value in [GLB_MAGIC, GLB_VERSION, total_len as u32, json.len() as u32, CHUNK_TYPE_JSON] {
+        glb.extend_from_slice(&value.to_le_bytes());
+    }
+    glb.extend_from_slice(&json);
+    for value in [bin.len() as u32, CHUNK_TYPE_BIN] {
+        glb.extend_from_slice(&value.to_le_bytes());
+    }
+    glb.extend_from_slice(&bin);
+    glb
+}
+
+
+/// Write a brain mesh to a file in binary glTF 2.0 format (GLB), optionally with per-vertex RGB colors.
+///
+/// See [`BrainMesh::to_gltf`] for details.
+#[cfg(feature = "fs")]
+pub fn write_gltf<P: AsRef<Path>>(path: P, mesh: &BrainMesh, vertex_colors: Option<&[u8]>) -> Result<()> {
+    write_gltf_with_options(path, mesh, vertex_colors, &WriteOptions::default())
+}
+
+
+/// Write a brain mesh to a file in binary glTF 2.0 format (GLB), using the given [`WriteOptions`].
+#[cfg(feature = "fs")]
+pub fn write_gltf_with_options<P: AsRef<Path>>(path: P, mesh: &BrainMesh, vertex_colors: Option<&[u8]>, options: &WriteOptions) -> Result<()> {
+    use std::io::Write;
+    let glb = mesh.to_gltf(vertex_colors)?;
+    let mut writer = options.create(path)?;
+    writer.write_all(&glb)?;
+    options.finish(writer)?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_meshes::tetrahedron;
+
+    fn json_chunk(glb: &[u8]) -> serde_json::Value {
+        let json_len = u32::from_le_bytes([glb[12], glb[13], glb[14], glb[15]]) as usize;
+        serde_json::from_slice(&glb[20..20 + json_len]).unwrap()
+    }
+
+    #[test]
+    fn a_mesh_can_be_exported_to_gltf() {
+        let mesh = tetrahedron();
+        let glb = mesh.to_gltf(None).unwrap();
+
+        assert_eq!(&b"glTF"[..], &glb[0..4]);
+        assert_eq!(glb.len() as u32, u32::from_le_bytes([glb[8], glb[9], glb[10], glb[11]]));
+        assert_eq!(0, glb.len() % 4);
+
+        let json = json_chunk(&glb);
+        let accessors = json["accessors"].as_array().unwrap();
+        assert_eq!(3, accessors.len());
+        assert_eq!(4, accessors[0]["count"]);
+        assert_eq!(1.0, accessors[0]["max"][2].as_f64().unwrap());
+        assert_eq!(mesh.faces.len(), accessors[2]["count"].as_u64().unwrap() as usize);
+        assert!(json["meshes"][0]["primitives"][0]["attributes"].get("COLOR_0").is_none());
+
+        let buffer_len = json["buffers"][0]["byteLength"].as_u64().unwrap() as usize;
+        assert_eq!(4 * 3 * 4 * 2 + 12 * 4, buffer_len);
+    }
+
+    #[test]
+    fn a_mesh_can_be_exported_to_gltf_with_vertex_colors() {
+        let mesh = tetrahedron();
+        let colors = vec![200; mesh.vertices.len()];
+        let json = json_chunk(&mesh.to_gltf(Some(&colors)).unwrap());
+
+        let color_accessor = json["meshes"][0]["primitives"][0]["attributes"]["COLOR_0"].as_u64().unwrap() as usize;
+        assert_eq!("VEC4", json["accessors"][color_accessor]["type"]);
+        assert_eq!(4, json["accessors"].as_array().unwrap().len());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn a_mesh_can_be_exported_to_gltf_with_a_texture() {
+        let mesh = tetrahedron();
+        let glb = mesh.to_gltf_textured(&[0.0, 1.0, 2.0, f32::NAN], 0.0, 2.0).unwrap();
+        assert!(matches!(mesh.to_gltf_textured(&[0.0], 0.0, 2.0), Err(NeuroformatsError::VertexCountMismatch(4, 1))));
+        let json = json_chunk(&glb);
+
+        let primitive = &json["meshes"][0]["primitives"][0];
+        assert_eq!(0, primitive["material"]);
+        assert!(primitive["attributes"].get("COLOR_0").is_none());
+        let texcoord_accessor = primitive["attributes"]["TEXCOORD_0"].as_u64().unwrap() as usize;
+        assert_eq!("VEC2", json["accessors"][texcoord_accessor]["type"]);
+        assert_eq!("image/png", json["images"][0]["mimeType"]);
+
+        let json_len = u32::from_le_bytes([glb[12], glb[13], glb[14], glb[15]]) as usize;
+        let bin = &glb[20 + json_len + 8..];
+        let view = |idx: usize| {
+            let view = &json["bufferViews"][idx];
+            let offset = view["byteOffset"].as_u64().unwrap() as usize;
+            &bin[offset..offset + view["byteLength"].as_u64().unwrap() as usize]
+        };
+        let texcoords: Vec<f32> = view(json["accessors"][texcoord_accessor]["bufferView"].as_u64().unwrap() as usize)
+            .chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        let texture = image::load_from_memory(view(json["images"][0]["bufferView"].as_u64().unwrap() as usize)).unwrap().to_rgb8();
+        assert_eq!((257, 1), texture.dimensions());
+
+        let colors = crate::colormap::values_to_colors(&[0.0, 1.0, 2.0, f32::NAN], 0.0, 2.0);
+        for (v, color) in colors.chunks_exact(3).enumerate() {
+            let texel = texture.get_pixel((texcoords[v * 2] * 257.0) as u32, 0);
+            assert_eq!(color, &texel.0[..]);
+        }
+    }
+
+    #[test]
+    fn the_material_and_scene_of_a_gltf_export_can_be_configured() {
+        let mesh = tetrahedron();
+        let json = json_chunk(&mesh.to_gltf(None).unwrap());
+        let material = &json["materials"][0];
+        assert_eq!(0, json["meshes"][0]["primitives"][0]["material"]);
+        assert_eq!(1.0, material["pbrMetallicRoughness"]["baseColorFactor"][0]);
+        assert_eq!(true, material["doubleSided"]);
+        assert!(json["nodes"][0].get("matrix").is_none());
+
+        let options = GltfOptions {
+            base_color: [0.5, 0.25, 1.0, 1.0],
+            metallic: 0.5,
+            double_sided: false,
+            mesh_name: Some(String::from("lh \"white\"")),
+            transform: Some([1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 10.0, 20.0, 30.0, 1.0]),
+            scale: 0.5,
+            ..Default::default()
+        };
+        let json = json_chunk(&mesh.to_gltf_with_options(None, &options).unwrap());
+        let material = &json["materials"][0];
+        assert_eq!(0.25, material["pbrMetallicRoughness"]["baseColorFactor"][1]);
+        assert_eq!(0.5, material["pbrMetallicRoughness"]["metallicFactor"]);
+        assert_eq!(false, material["doubleSided"]);
+        assert_eq!("lh \"white\"", json["meshes"][0]["name"]);
+        assert_eq!("lh \"white\"", json["nodes"][0]["name"]);
+        let matrix: Vec<f64> = json["nodes"][0]["matrix"].as_array().unwrap().iter().map(|v| v.as_f64().unwrap()).collect();
+        assert_eq!(vec![0.5, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 5.0, 10.0, 15.0, 1.0], matrix);
+
+        let json = json_chunk(&mesh.to_gltf_with_options(None, &GltfOptions { scale: 0.001, ..Default::default() }).unwrap());
+        assert_eq!(0.001, json["nodes"][0]["matrix"][0]);
+    }
+
+    #[test]
+    fn a_mesh_can_be_exported_to_gltf_with_quantized_vertex_data() {
+        let mesh = BrainMesh { vertices: tetrahedron().vertices.iter().map(|v| v * 50.0 - 10.0).collect(), faces: tetrahedron().faces };
+        let options = GltfOptions { quantize: true, scale: 0.001, ..Default::default() };
+        let glb = mesh.to_gltf_with_options(Some(&[128; 12]), &options).unwrap();
+        let json = json_chunk(&glb);
+        let unquantized = json_chunk(&mesh.to_gltf_with_options(Some(&[128; 12]), &GltfOptions { scale: 0.001, ..Default::default() }).unwrap());
+        assert_eq!(4 * (8 + 4 + 4) + 12 * 4, json["buffers"][0]["byteLength"]);
+        assert_eq!(4 * (12 + 12 + 4) + 12 * 4, unquantized["buffers"][0]["byteLength"]);
+        assert_eq!("KHR_mesh_quantization", json["extensionsRequired"][0]);
+
+        let attributes = &json["meshes"][0]["primitives"][0]["attributes"];
+        let position = &json["accessors"][attributes["POSITION"].as_u64().unwrap() as usize];
+        let normal = &json["accessors"][attributes["NORMAL"].as_u64().unwrap() as usize];
+        assert_eq!(COMPONENT_SHORT, position["componentType"]);
+        assert_eq!(8, json["bufferViews"][position["bufferView"].as_u64().unwrap() as usize]["byteStride"]);
+        assert_eq!(COMPONENT_BYTE, normal["componentType"]);
+        assert_eq!(true, normal["normalized"]);
+
+        // The node transform maps the quantized bounds back to the scaled coordinates.
+        let matrix: Vec<f64> = json["nodes"][0]["matrix"].as_array().unwrap().iter().map(|v| v.as_f64().unwrap()).collect();
+        for (quantized, expected) in [(&position["min"], -0.01), (&position["max"], 0.04)] {
+            let x = quantized[0].as_f64().unwrap();
+            assert!((matrix[0] * x + matrix[12] - expected).abs() < 1e-6);
+        }
+
+        let json = json_chunk(&mesh.to_gltf_animated_with_options(&[vec![0.0; 4]], 0.0, 1.0, &GltfAnimationOptions::default(), &options).unwrap());
+        assert_eq!("KHR_mesh_quantization", json["extensionsUsed"][0]);
+        assert!(json["nodes"][0].get("matrix").is_some());
+    }
+
+    #[test]
+    fn exporting_an_invalid_mesh_to_gltf_returns_an_error() {
+        let mut mesh = tetrahedron();
+        assert!(matches!(mesh.to_gltf(Some(&[0, 0, 0])), Err(NeuroformatsError::ColorCountMismatch(12, 3))));
+        mesh.faces[5] = 4;
+        assert!(matches!(mesh.to_gltf(None), Err(NeuroformatsError::VertexIndexOutOfRange(4, 4))));
+    }
+
+    #[test]
+    fn a_time_series_can_be_exported_to_gltf_as_an_animation_of_frame_nodes() {
+        let mesh = tetrahedron();
+        let frames = vec![vec![0.0, 1.0, 2.0, 3.0], vec![3.0, 2.0, 1.0, 0.0], vec![f32::NAN; 4]];
+        let json = json_chunk(&mesh.to_gltf_animated(&frames, 0.0, 3.0, &GltfAnimationOptions::default()).unwrap());
+
+        assert_eq!(4, json["nodes"].as_array().unwrap().len());
+        assert_eq!(3, json["nodes"][0]["children"].as_array().unwrap().len());
+        assert_eq!(3, json["meshes"].as_array().unwrap().len());
+        assert!(json["nodes"][1].get("scale").is_none());
+        assert_eq!(0.0, json["nodes"][2]["scale"][0]);
+        let primitives: Vec<&serde_json::Value> = (0..3).map(|m| &json["meshes"][m]["primitives"][0]).collect();
+        assert_eq!(primitives[0]["attributes"]["POSITION"], primitives[2]["attributes"]["POSITION"]);
+        assert_ne!(primitives[0]["attributes"]["COLOR_0"], primitives[2]["attributes"]["COLOR_0"]);
+
+        let animation = &json["animations"][0];
+        assert_eq!(3, animation["channels"].as_array().unwrap().len());
+        assert_eq!("scale", animation["channels"][2]["target"]["path"]);
+        assert_eq!(3, animation["channels"][2]["target"]["node"]);
+        let input = animation["samplers"][0]["input"].as_u64().unwrap() as usize;
+        assert_eq!(4, json["accessors"][input]["count"]);
+        assert_eq!(1.5, json["accessors"][input]["max"][0]);
+    }
+
+    #[test]
+    fn a_time_series_can_be_exported_to_gltf_as_an_animation_of_morph_targets() {
+        let mesh = tetrahedron();
+        let frames = [[0.0f32; 4], [1.0; 4], [2.0; 4]];
+        let animation = GltfAnimationOptions { mode: GltfAnimationMode::MorphTargets, frame_duration: 1.0 };
+        let json = json_chunk(&mesh.to_gltf_animated(&frames, 0.0, 2.0, &animation).unwrap());
+
+        assert_eq!(1, json["meshes"].as_array().unwrap().len());
+        assert_eq!(2, json["meshes"][0]["weights"].as_array().unwrap().len());
+        let targets = json["meshes"][0]["primitives"][0]["targets"].as_array().unwrap();
+        assert_eq!(2, targets.len());
+        assert_eq!("VEC4", json["accessors"][targets[0]["COLOR_0"].as_u64().unwrap() as usize]["type"]);
+        let sampler = &json["animations"][0]["samplers"][0];
+        assert_eq!("LINEAR", sampler["interpolation"]);
+        assert_eq!(4 * 2, json["accessors"][sampler["output"].as_u64().unwrap() as usize]["count"]);
+
+        let json = json_chunk(&mesh.to_gltf_animated(&frames[..1], 0.0, 2.0, &animation).unwrap());
+        assert!(json["meshes"][0]["primitives"][0].get("targets").is_none());
+        assert!(json.get("animations").is_none());
+    }
+
+    #[test]
+    fn exporting_an_invalid_time_series_to_gltf_returns_an_error() {
+        let mesh = tetrahedron();
+        let no_frames: Vec<Vec<f32>> = Vec::new();
+        assert!(matches!(mesh.to_gltf_animated(&no_frames, 0.0, 1.0, &GltfAnimationOptions::default()), Err(NeuroformatsError::NoFrames)));
+        assert!(matches!(mesh.to_gltf_animated(&[vec![0.0; 4], vec![0.0; 3]], 0.0, 1.0, &GltfAnimationOptions::default()), Err(NeuroformatsError::VertexCountMismatch(4, 3))));
+    }
+}
@@ -0,0 +1,157 @@
+//! Computation of the gradient of per-vertex data on brain meshes.
+//!
+//! The gradient of a map, like a functional connectivity map or a myelin map, points in the direction of its steepest increase
+//! along the surface. High gradient magnitudes mark sharp transitions of the map, which are used to find the boundaries between
+//! cortical areas. The magnitudes have one value per vertex and can be written in curv format with [`crate::FsCurv::from_data`]
+//! and [`crate::write_curv`].
+
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_surface::BrainMesh;
+use crate::util::{vec3_cross, vec3_dot, vec3_norm, vec3_sub};
+
+
+/// The gradient of per-vertex data on a mesh, see [`surface_gradient`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SurfaceGradient {
+    /// The gradient vector of each vertex, in the same layout as [`BrainMesh::vertices`]. It is tangential to the surface, and its
+    /// unit is the unit of the data per unit of the mesh coordinates, e.g., per mm for FreeSurfer surfaces.
+    pub vectors: Vec<f32>,
+    /// The magnitude of the gradient vector of each vertex.
+    pub magnitude: Vec<f32>,
+}
+
+
+impl SurfaceGradient {
+    /// Get the unit direction of the steepest increase of each vertex, in the same layout as [`BrainMesh::vertices`].
+    ///
+    /// Vertices with a gradient magnitude of zero get a zero vector, and vertices with a NaN gradient get NaN values.
+    pub fn direction(&self) -> Vec<f32> {
+        self.vectors.chunks_exact(3).zip(self.magnitude.iter())
+            .flat_map(|(v, m)| if *m > 0.0 || m.is_nan() { [v[0] / m, v[1] / m, v[2] / m] } else { [0.0; 3] })
+            .collect()
+    }
+}
+
+
+/// Compute the gradient of per-vertex data on a mesh.
+///
+/// The data is interpolated linearly within each face, which gives a constant gradient per face. The gradient of a vertex is the
+/// mean of the gradients of its faces, weighted by face area, and projected onto the tangent plane of the vertex, see
+/// [`BrainMesh::vertex_normals`]. Faces with a NaN value at one of their vertices and degenerate faces without area are ignored.
+///
+/// # Parameters
+///
+/// * `mesh`: the mesh. The white or midthickness surface gives distances closest to those on the cortex.
+/// * `data`: the per-vertex data, e.g., the `data` of an [`FsCurv`](crate::FsCurv).
+///
+/// # Return value
+///
+/// The gradient, which is NaN for vertices without any faces that are not ignored. Returns [`NeuroformatsError::VertexCountMismatch`] if
+/// `data` does not contain one value per vertex, and an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+///
+/// # Examples
+///
+/// ```no_run
+/// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let myelin = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.myelin").unwrap();
+/// let gradient = neuroformats::gradient::surface_gradient(&surf.mesh, &myelin.data).unwrap();
+/// neuroformats::write_curv("/tmp/lh.myelin_gradient", &neuroformats::FsCurv::from_data(gradient.magnitude));
+/// ```
+pub fn surface_gradient(mesh: &BrainMesh, data: &[f32]) -> Result<SurfaceGradient> {
+    mesh.validate_faces()?;
+    if data.len() != mesh.num_vertices() {
+        return Err(NeuroformatsError::VertexCountMismatch(mesh.num_vertices(), data.len()));
+    }
+    let mut sums = vec![[0.0f32; 3]; mesh.num_vertices()];
+    let mut areas = vec![0.0f32; mesh.num_vertices()];
+    for face in mesh.iter_faces() {
+        let [i, j, k] = face;
+        if face.iter().any(|v| data[*v].is_nan()) {
+            continue;
+        }
+        let (pi, pj, pk) = (mesh.vertex_coords(i), mesh.vertex_coords(j), mesh.vertex_coords(k));
+        let normal = vec3_cross(vec3_sub(pj, pi), vec3_sub(pk, pi));
+        let double_area = vec3_norm(normal);
+        if double_area <= 0.0 {
+            continue;
+        }
+        let n = normal.map(|c| c / double_area);
+        // The gradient of the linear interpolation: the edges rotated by 90 degrees within the face, weighted by the value differences.
+        let rot_ki = vec3_cross(n, vec3_sub(pi, pk));
+        let rot_ij = vec3_cross(n, vec3_sub(pj, pi));
+        let (df_j, df_k) = (data[j] - data[i], data[k] - data[i]);
+        let grad = [0, 1, 2].map(|axis| (df_j * rot_ki[axis] + df_k * rot_ij[axis]) / double_area);
+        for v in face {
+            for axis in 0..3 {
+                sums[v][axis] += grad[axis] * double_area;
+            }
+            areas[v] += double_area;
+        }
+    }
+
+    let normals = mesh.vertex_normals();
+    let mut vectors = Vec::with_capacity(mesh.vertices.len());
+    let mut magnitude = Vec::with_capacity(mesh.num_vertices());
+    for (v, (sum, area)) in sums.iter().zip(areas.iter()).enumerate() {
+        if *area <= 0.0 {
+            vectors.extend_from_slice(&[f32::NAN; 3]);
+            magnitude.push(f32::NAN);
+            continue;
+        }
+        let mean = sum.map(|c| c / area);
+        let n = [normals[v * 3], normals[v * 3 + 1], normals[v * 3 + 2]];
+        let normal_part = vec3_dot(mean, n);
+        let tangential = [0, 1, 2].map(|axis| mean[axis] - normal_part * n[axis]);
+        vectors.extend_from_slice(&tangential);
+        magnitude.push(vec3_norm(tangential));
+    }
+    Ok(SurfaceGradient { vectors, magnitude })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    /// A flat grid of 4x4 vertices with a spacing of 1 in the z = 0 plane.
+    fn grid() -> BrainMesh {
+        let vertices = (0..16).flat_map(|v| [(v % 4) as f32, (v / 4) as f32, 0.0]).collect();
+        let faces = (0..3).flat_map(|row| (0..3).flat_map(move |col| {
+            let v = row * 4 + col;
+            [v, v + 1, v + 5, v, v + 5, v + 4]
+        })).collect();
+        BrainMesh { vertices, faces }
+    }
+
+    #[test]
+    fn the_gradient_of_a_linear_map_is_constant() {
+        let mesh = grid();
+        let data: Vec<f32> = mesh.iter_vertices().map(|p| 2.0 * p[0] - p[1]).collect();
+        let gradient = surface_gradient(&mesh, &data).unwrap();
+        for v in 0..mesh.num_vertices() {
+            assert_abs_diff_eq!(5.0f32.sqrt(), gradient.magnitude[v], epsilon = 1e-5);
+            assert_abs_diff_eq!(&[2.0, -1.0, 0.0][..], &gradient.vectors[v * 3..v * 3 + 3], epsilon = 1e-5);
+        }
+        let direction = gradient.direction();
+        assert_abs_diff_eq!(2.0 / 5.0f32.sqrt(), direction[0], epsilon = 1e-5);
+
+        let constant = surface_gradient(&mesh, &[1.0; 16]).unwrap();
+        assert!(constant.magnitude.iter().all(|m| *m == 0.0));
+        assert!(constant.direction().iter().all(|c| *c == 0.0));
+    }
+
+    #[test]
+    fn nan_values_are_ignored_for_the_gradient() {
+        let mesh = grid();
+        let mut data = vec![0.0; 16];
+        data[0] = f32::NAN;
+        let gradient = surface_gradient(&mesh, &data).unwrap();
+        assert!(gradient.magnitude[0].is_nan());
+        assert_eq!(0.0, gradient.magnitude[1]);
+        assert!(gradient.direction()[0].is_nan());
+        assert!(matches!(surface_gradient(&mesh, &[0.0]), Err(NeuroformatsError::VertexCountMismatch(16, 1))));
+    }
+}
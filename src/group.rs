@@ -0,0 +1,169 @@
+//! Loading of per-vertex data of several subjects into a group data matrix.
+//!
+//! Vertex-wise group statistics require the data of all subjects on the same mesh, typically the fsaverage template. The
+//! matrix built by [`build_group_matrix`] has one row per subject and one column per vertex, which is the standard input
+//! for vertex-wise statistics.
+
+
+use std::path::Path;
+
+use ndarray::Array2;
+
+use crate::fs_curv::read_curv;
+use crate::fs_surface::read_surf;
+use crate::resample::{resample, ResampleMethod};
+use crate::subject::{Subject, SurfaceKind};
+use crate::hemi::Hemisphere;
+use crate::error::{NeuroformatsError, Result};
+
+
+/// The mesh on which the data of all subjects is combined in a group matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupTarget {
+    /// Use the data as it is stored for each subject. All subjects must have the same number of vertices, e.g., because the
+    /// measure was already resampled to a template, like `thickness.fwhm10.fsaverage` files.
+    Native,
+    /// Resample the data of each subject to the `?h.sphere` of the template subject with the given id in the same subjects
+    /// directory, using the `?h.sphere.reg` of each subject. See [`crate::resample`].
+    Template {
+        /// The template subject, like `fsaverage`.
+        subject_id: String,
+        /// The interpolation method.
+        method: ResampleMethod,
+    },
+}
+
+
+/// Per-vertex data of several subjects, see [`build_group_matrix`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupMatrix {
+    /// The subject identifiers, in the order of the rows of the `data`.
+    pub subjects: Vec<String>,
+    /// The data, with one row per subject and one column per vertex.
+    pub data: Array2<f32>,
+}
+
+
+impl GroupMatrix {
+    /// Get the number of subjects, i.e., rows.
+    pub fn num_subjects(&self) -> usize {
+        self.data.nrows()
+    }
+
+
+    /// Get the number of vertices, i.e., columns.
+    pub fn num_vertices(&self) -> usize {
+        self.data.ncols()
+    }
+}
+
+
+/// Load per-vertex data in curv format for several subjects into a group data matrix.
+///
+/// # Parameters
+///
+/// * `subjects_dir`: the FreeSurfer SUBJECTS_DIR.
+/// * `subjects`: the subject identifiers, which define the order of the rows.
+/// * `hemi`: the hemisphere.
+/// * `measure`: the measure, e.g., `"thickness"`. The data is read from `surf/<hemi>.<measure>` of each subject.
+/// * `target`: the mesh on which the data is combined, see [`GroupTarget`].
+///
+/// # Return value
+///
+/// The group matrix, or [`NeuroformatsError::VertexCountMismatch`] if the subjects have different numbers of vertices for [`GroupTarget::Native`],
+/// or the data of a subject does not match its sphere.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::group::{build_group_matrix, GroupTarget};
+/// use neuroformats::hemi::Hemisphere;
+/// use neuroformats::resample::ResampleMethod;
+/// let target = GroupTarget::Template { subject_id: String::from("fsaverage"), method: ResampleMethod::Barycentric };
+/// let group = build_group_matrix("/path/to/subjects_dir", &["subject1", "subject2"], Hemisphere::Left, "thickness", &target).unwrap();
+/// assert_eq!(2, group.num_subjects());
+/// ```
+pub fn build_group_matrix<P: AsRef<Path>>(subjects_dir: P, subjects: &[&str], hemi: Hemisphere, measure: &str, target: &GroupTarget) -> Result<GroupMatrix> {
+    let subjects_dir = subjects_dir.as_ref();
+    let target_sphere = match target {
+        GroupTarget::Native => None,
+        GroupTarget::Template { subject_id, method } => Some((read_surf(Subject::new(subjects_dir, subject_id).surf_path(hemi, SurfaceKind::Sphere))?.mesh, *method)),
+    };
+
+    let mut values = Vec::new();
+    let mut num_vertices = None;
+    for subject_id in subjects {
+        let subject = Subject::new(subjects_dir, subject_id);
+        let data = read_curv(subject.measure_path(hemi, measure))?.data;
+        let data = match &target_sphere {
+            None => data,
+            Some((sphere, method)) => {
+                let sphere_reg = read_surf(subject.surf_path(hemi, SurfaceKind::SphereReg))?;
+                resample(&sphere_reg.mesh, sphere, &data, *method)?
+            }
+        };
+        let expected = *num_vertices.get_or_insert(data.len());
+        if data.len() != expected {
+            return Err(NeuroformatsError::VertexCountMismatch(expected, data.len()));
+        }
+        values.extend(data);
+    }
+
+    let data = Array2::from_shape_vec((subjects.len(), num_vertices.unwrap_or(0)), values).unwrap();
+    Ok(GroupMatrix { subjects: subjects.iter().map(|s| s.to_string()).collect(), data })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::{tempdir};
+
+    /// Create a subjects dir with copies of the demo subject, which use the white surface as their registered sphere.
+    fn create_subjects_dir(subjects: &[&str]) -> tempfile::TempDir {
+        let subjects_dir = tempdir().unwrap();
+        for subject_id in subjects {
+            let subject = Subject::new(subjects_dir.path(), subject_id);
+            std::fs::create_dir_all(subject.dir().join("surf")).unwrap();
+            std::fs::copy("resources/subjects_dir/subject1/surf/lh.thickness", subject.measure_path(Hemisphere::Left, "thickness")).unwrap();
+            std::fs::copy("resources/subjects_dir/subject1/surf/lh.white", subject.surf_path(Hemisphere::Left, SurfaceKind::SphereReg)).unwrap();
+        }
+        subjects_dir
+    }
+
+    #[test]
+    fn a_group_matrix_can_be_built_from_native_data() {
+        let subjects_dir = create_subjects_dir(&["s1", "s2"]);
+        let group = build_group_matrix(subjects_dir.path(), &["s2", "s1"], Hemisphere::Left, "thickness", &GroupTarget::Native).unwrap();
+        assert_eq!(vec!["s2", "s1"], group.subjects);
+        assert_eq!((2, 149244), group.data.dim());
+        let thickness = read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        assert_eq!(thickness.data[..], group.data.row(1).to_vec()[..]);
+
+        assert!(build_group_matrix(subjects_dir.path(), &["s1", "missing"], Hemisphere::Left, "thickness", &GroupTarget::Native).is_err());
+    }
+
+    #[test]
+    fn a_group_matrix_can_be_built_from_data_resampled_to_a_template() {
+        let subjects_dir = create_subjects_dir(&["s1", "s2"]);
+        let template = Subject::new(subjects_dir.path(), "template");
+        std::fs::create_dir_all(template.dir().join("surf")).unwrap();
+        std::fs::copy("resources/subjects_dir/subject1/surf/lh.tinysurface", template.surf_path(Hemisphere::Left, SurfaceKind::Sphere)).unwrap();
+
+        let target = GroupTarget::Template { subject_id: String::from("template"), method: ResampleMethod::NearestNeighbor };
+        let group = build_group_matrix(subjects_dir.path(), &["s1", "s2"], Hemisphere::Left, "thickness", &target).unwrap();
+        let tiny = read_surf("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap();
+        assert_eq!(2, group.num_subjects());
+        assert_eq!(tiny.mesh.num_vertices(), group.num_vertices());
+        assert_eq!(group.data.row(0), group.data.row(1));
+    }
+
+    #[test]
+    fn subjects_with_different_vertex_counts_cannot_be_combined_without_resampling() {
+        let subjects_dir = create_subjects_dir(&["s1", "s2"]);
+        let s2 = Subject::new(subjects_dir.path(), "s2");
+        std::fs::copy("resources/curv/tiny.curv", s2.measure_path(Hemisphere::Left, "thickness")).unwrap();
+        let err = build_group_matrix(subjects_dir.path(), &["s1", "s2"], Hemisphere::Left, "thickness", &GroupTarget::Native).unwrap_err();
+        assert!(matches!(err, NeuroformatsError::VertexCountMismatch(149244, 5)));
+    }
+}
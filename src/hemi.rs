@@ -0,0 +1,325 @@
+//! Functions for loading the files of both brain hemispheres in parallel.
+//!
+//! Almost all FreeSurfer surface files come in pairs, one for the left hemisphere (`lh.*`) and one for
+//! the right hemisphere (`rh.*`). The functions in this module read both files of a pair concurrently.
+
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fs_surface::{FsSurface, BrainMesh};
+use crate::fs_curv::FsCurv;
+use crate::fs_annot::FsAnnot;
+#[cfg(feature = "fs")]
+use crate::{read_surf, read_curv, read_annot};
+use crate::error::{NeuroformatsError, Result};
+
+
+/// A brain hemisphere. FreeSurfer files of the left and right hemisphere are prefixed with `lh` and `rh`, respectively.
+///
+/// Use [`Hemisphere::prefix`] or the `Display` implementation to get the prefix, and `str::parse` to parse it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hemisphere {
+    Left,
+    Right,
+}
+
+
+impl Hemisphere {
+    /// Both hemispheres, left first.
+    pub const BOTH: [Hemisphere; 2] = [Hemisphere::Left, Hemisphere::Right];
+
+
+    /// Get the file name prefix of the hemisphere, `"lh"` or `"rh"`.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Hemisphere::Left => "lh",
+            Hemisphere::Right => "rh",
+        }
+    }
+
+
+    /// Get the other hemisphere.
+    pub fn other(&self) -> Hemisphere {
+        match self {
+            Hemisphere::Left => Hemisphere::Right,
+            Hemisphere::Right => Hemisphere::Left,
+        }
+    }
+}
+
+
+impl fmt::Display for Hemisphere {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.prefix())
+    }
+}
+
+
+impl FromStr for Hemisphere {
+    type Err = NeuroformatsError;
+
+    /// Parse a hemisphere from its prefix, `"lh"` or `"rh"`. Returns [`NeuroformatsError::InvalidHemisphere`] for other strings.
+    fn from_str(s: &str) -> Result<Hemisphere> {
+        match s {
+            "lh" => Ok(Hemisphere::Left),
+            "rh" => Ok(Hemisphere::Right),
+            _ => Err(NeuroformatsError::InvalidHemisphere(s.to_string())),
+        }
+    }
+}
+
+
+/// A pair of left and right hemisphere data, like the lh and rh white surfaces of a subject.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HemiPair<T> {
+    pub lh: T,
+    pub rh: T,
+}
+
+
+impl<T> HemiPair<T> {
+    /// Apply a function to both hemispheres.
+    pub fn map<U, F: Fn(&T) -> U>(&self, f: F) -> HemiPair<U> {
+        HemiPair { lh: f(&self.lh), rh: f(&self.rh) }
+    }
+
+
+    /// Get the data of the given hemisphere.
+    pub fn get(&self, hemi: Hemisphere) -> &T {
+        match hemi {
+            Hemisphere::Left => &self.lh,
+            Hemisphere::Right => &self.rh,
+        }
+    }
+}
+
+
+impl HemiPair<FsSurface> {
+    /// Get the vertex counts of the left and right hemisphere meshes.
+    pub fn num_vertices(&self) -> HemiPair<usize> {
+        self.map(|s| s.mesh.num_vertices())
+    }
+
+
+    /// Get the index of the first right hemisphere vertex in the merged mesh, see [`HemiPair::merged_mesh`].
+    pub fn rh_vertex_offset(&self) -> usize {
+        self.lh.mesh.num_vertices()
+    }
+
+
+    /// Merge the meshes of both hemispheres into a single mesh. The left hemisphere vertices come first.
+    pub fn merged_mesh(&self) -> BrainMesh {
+        self.lh.mesh.merge(&self.rh.mesh)
+    }
+
+
+    /// Merge per-vertex data of both hemispheres so that it matches the merged mesh, see [`HemiPair::merged_mesh`].
+    ///
+    /// Returns [`NeuroformatsError::VertexCountMismatch`] if the length of the data does not match the vertex count of the respective hemisphere.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let white = neuroformats::hemi::read_hemi_pair("/path/to/subjects_dir/subject1/surf/lh.white", "/path/to/subjects_dir/subject1/surf/rh.white").unwrap();
+    /// let thickness = neuroformats::hemi::read_curv_pair("/path/to/subjects_dir/subject1/surf/lh.thickness", "/path/to/subjects_dir/subject1/surf/rh.thickness").unwrap();
+    /// let mesh = white.merged_mesh();
+    /// let data = white.merge_overlays(&thickness.lh.data, &thickness.rh.data).unwrap();
+    /// assert_eq!(mesh.num_vertices(), data.len());
+    /// ```
+    pub fn merge_overlays(&self, lh: &[f32], rh: &[f32]) -> Result<Vec<f32>> {
+        check_count(self.lh.mesh.num_vertices(), lh.len())?;
+        check_count(self.rh.mesh.num_vertices(), rh.len())?;
+        Ok(merge_overlays(lh, rh))
+    }
+
+
+    /// Merge per-vertex colors of both hemispheres so that they match the merged mesh, see [`HemiPair::merged_mesh`].
+    ///
+    /// The colors are given as `channels` values per vertex, e.g., 3 for RGB or 4 for RGBA colors as returned by [`FsAnnot::vertex_colors`].
+    /// Returns [`NeuroformatsError::VertexCountMismatch`] if the number of colors does not match the vertex count of the respective hemisphere.
+    pub fn merge_colors(&self, lh: &[u8], rh: &[u8], channels: usize) -> Result<Vec<u8>> {
+        check_count(self.lh.mesh.num_vertices() * channels, lh.len())?;
+        check_count(self.rh.mesh.num_vertices() * channels, rh.len())?;
+        Ok([lh, rh].concat())
+    }
+}
+
+
+/// Merge per-vertex data of the left and right hemisphere into a single vector, left hemisphere first.
+///
+/// This matches the vertex order of meshes merged with [`BrainMesh::merge`]. Use [`HemiPair::merge_overlays`] to check
+/// the data lengths against the meshes.
+pub fn merge_overlays(lh: &[f32], rh: &[f32]) -> Vec<f32> {
+    [lh, rh].concat()
+}
+
+
+impl HemiPair<FsCurv> {
+    /// Check that the per-vertex data of both hemispheres matches the vertex counts of the given surfaces.
+    ///
+    /// Returns [`NeuroformatsError::VertexCountMismatch`] for the first hemisphere that does not match.
+    pub fn check_matches(&self, surfaces: &HemiPair<FsSurface>) -> Result<()> {
+        check_count(surfaces.lh.mesh.num_vertices(), self.lh.data.len())?;
+        check_count(surfaces.rh.mesh.num_vertices(), self.rh.data.len())
+    }
+}
+
+
+impl HemiPair<FsAnnot> {
+    /// Check that the parcellations of both hemispheres match the vertex counts of the given surfaces.
+    ///
+    /// Returns [`NeuroformatsError::VertexCountMismatch`] for the first hemisphere that does not match.
+    pub fn check_matches(&self, surfaces: &HemiPair<FsSurface>) -> Result<()> {
+        check_count(surfaces.lh.mesh.num_vertices(), self.lh.vertex_labels.len())?;
+        check_count(surfaces.rh.mesh.num_vertices(), self.rh.vertex_labels.len())
+    }
+}
+
+
+impl<T: fmt::Display> fmt::Display for HemiPair<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hemisphere pair. lh: {} rh: {}", self.lh, self.rh)
+    }
+}
+
+
+fn check_count(expected: usize, found: usize) -> Result<()> {
+    if expected != found {
+        return Err(NeuroformatsError::VertexCountMismatch(expected, found));
+    }
+    Ok(())
+}
+
+
+/// Read the files of a hemisphere pair concurrently, using the given reader function for both files.
+///
+/// This is the generic function behind [`read_hemi_pair`], [`read_curv_pair`] and [`read_annot_pair`]. If both files fail to load, the error for the left hemisphere is returned.
+#[cfg(feature = "fs")]
+pub fn read_pair_with<T, F, P>(lh_path: P, rh_path: P, reader: F) -> Result<HemiPair<T>>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T> + Sync,
+    P: AsRef<Path>,
+{
+    let (lh_path, rh_path) = (lh_path.as_ref(), rh_path.as_ref());
+    let reader = &reader;
+    let (lh, rh) = std::thread::scope(|scope| {
+        let rh_handle = scope.spawn(move || reader(rh_path));
+        let lh = reader(lh_path);
+        let rh = rh_handle.join().expect("Thread reading right hemisphere file panicked.");
+        (lh, rh)
+    });
+    Ok(HemiPair { lh: lh?, rh: rh? })
+}
+
+
+/// Read the brain meshes of both hemispheres concurrently.
+///
+/// The vertex counts of the meshes are checked against their headers.
+///
+/// # Examples
+///
+/// ```no_run
+/// let white = neuroformats::hemi::read_hemi_pair("/path/to/subjects_dir/subject1/surf/lh.white", "/path/to/subjects_dir/subject1/surf/rh.white").unwrap();
+/// println!("The lh has {} vertices, the rh has {}.", white.lh.mesh.num_vertices(), white.rh.mesh.num_vertices());
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_hemi_pair<P: AsRef<Path>>(lh_path: P, rh_path: P) -> Result<HemiPair<FsSurface>> {
+    read_pair_with(lh_path, rh_path, |path| {
+        let surf = read_surf(path)?;
+        check_count(surf.header.num_vertices as usize, surf.mesh.num_vertices())?;
+        Ok(surf)
+    })
+}
+
+
+/// Read the per-vertex data of both hemispheres concurrently.
+///
+/// The lengths of the data are checked against their headers. Use [`HemiPair::check_matches`] to check that the data matches a pair of surfaces.
+#[cfg(feature = "fs")]
+pub fn read_curv_pair<P: AsRef<Path>>(lh_path: P, rh_path: P) -> Result<HemiPair<FsCurv>> {
+    read_pair_with(lh_path, rh_path, |path| {
+        let curv = read_curv(path)?;
+        check_count(curv.header.num_vertices as usize, curv.data.len())?;
+        Ok(curv)
+    })
+}
+
+
+/// Read the parcellations of both hemispheres concurrently.
+///
+/// Use [`HemiPair::check_matches`] to check that the parcellations match a pair of surfaces.
+#[cfg(feature = "fs")]
+pub fn read_annot_pair<P: AsRef<Path>>(lh_path: P, rh_path: P) -> Result<HemiPair<FsAnnot>> {
+    read_pair_with(lh_path, rh_path, |path| read_annot(path))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hemispheres_can_be_parsed_and_displayed() {
+        assert_eq!(Hemisphere::Left, "lh".parse().unwrap());
+        assert_eq!(Hemisphere::Right, "rh".parse().unwrap());
+        assert!(matches!("left".parse::<Hemisphere>(), Err(NeuroformatsError::InvalidHemisphere(_))));
+        assert_eq!("rh", Hemisphere::Right.to_string());
+        assert_eq!(Hemisphere::Right, Hemisphere::Left.other());
+
+        let pair = HemiPair { lh: 1, rh: 2 };
+        assert_eq!(vec![1, 2], Hemisphere::BOTH.iter().map(|h| *pair.get(*h)).collect::<Vec<i32>>());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_pair_of_surfaces_and_annots_can_be_read() {
+        let surfs = read_hemi_pair("resources/subjects_dir/subject1/surf/lh.white", "resources/subjects_dir/subject1/surf/rh.white").unwrap();
+        assert_eq!(149244, surfs.num_vertices().lh);
+        assert_eq!(153333, surfs.num_vertices().rh);
+
+        let annots = read_annot_pair("resources/subjects_dir/subject1/label/lh.aparc.annot", "resources/subjects_dir/subject1/label/rh.aparc.annot").unwrap();
+        annots.check_matches(&surfs).unwrap();
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn hemisphere_meshes_and_overlays_can_be_merged() {
+        let surfs = read_hemi_pair("resources/subjects_dir/subject1/surf/lh.white", "resources/subjects_dir/subject1/surf/rh.white").unwrap();
+        let merged = surfs.merged_mesh();
+        assert_eq!(149244 + 153333, merged.num_vertices());
+        assert_eq!(298484 + 306662, merged.num_faces());
+        assert_eq!(149244, surfs.rh_vertex_offset());
+        assert_eq!(149244, merged.faces[298484 * 3..].iter().copied().min().unwrap());
+
+        let lh_data = vec![1.0; 149244];
+        let rh_data = vec![2.0; 153333];
+        let data = surfs.merge_overlays(&lh_data, &rh_data).unwrap();
+        assert_eq!(merged.num_vertices(), data.len());
+        assert_eq!(2.0, data[149244]);
+        assert!(surfs.merge_overlays(&rh_data, &lh_data).is_err());
+
+        let annots = read_annot_pair("resources/subjects_dir/subject1/label/lh.aparc.annot", "resources/subjects_dir/subject1/label/rh.aparc.annot").unwrap();
+        let colors = surfs.merge_colors(&annots.lh.vertex_colors(true, 0), &annots.rh.vertex_colors(true, 0), 4).unwrap();
+        assert_eq!(merged.num_vertices() * 4, colors.len());
+        assert!(surfs.merge_colors(&annots.lh.vertex_colors(false, 0), &annots.rh.vertex_colors(false, 0), 4).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn mismatching_per_vertex_data_is_detected() {
+        let surfs = read_hemi_pair("resources/subjects_dir/subject1/surf/lh.white", "resources/subjects_dir/subject1/surf/rh.white").unwrap();
+        // There is no rh.thickness in the demo data, so we use the lh file for both hemispheres.
+        let curvs = read_curv_pair("resources/subjects_dir/subject1/surf/lh.thickness", "resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        assert!(matches!(curvs.check_matches(&surfs), Err(NeuroformatsError::VertexCountMismatch(153333, 149244))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn reading_a_pair_with_a_missing_file_fails() {
+        assert!(read_hemi_pair("resources/subjects_dir/subject1/surf/lh.white", "resources/subjects_dir/subject1/surf/rh.nosuchfile").is_err());
+    }
+}
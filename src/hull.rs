@@ -0,0 +1,418 @@
+//! Convex hulls and bounding volumes of brain meshes.
+//!
+//! The convex hull is used for sulcal depth proxies (see [`BrainMesh::hull_depth`]), and the bounding volumes for picking
+//! and collision tests in viewers and for quick checks of the position and size of a mesh, e.g., after a spatial normalization.
+
+
+use std::collections::HashMap;
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::symmetric_eigen;
+
+
+/// A sphere enclosing all vertices of a mesh, see [`BrainMesh::bounding_sphere`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+
+impl BoundingSphere {
+    /// Whether the point lies inside or on the sphere.
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        dist(self.center, point) <= self.radius
+    }
+}
+
+
+/// A box enclosing all vertices of a mesh, with edges along arbitrary orthogonal axes, see [`BrainMesh::oriented_bounding_box`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientedBoundingBox {
+    /// The center of the box.
+    pub center: [f32; 3],
+    /// The directions of the edges of the box as unit vectors, sorted by decreasing extent.
+    pub axes: [[f32; 3]; 3],
+    /// Half the edge lengths of the box along the respective axes.
+    pub half_extents: [f32; 3],
+}
+
+
+impl OrientedBoundingBox {
+    /// Compute the volume of the box.
+    pub fn volume(&self) -> f32 {
+        8.0 * self.half_extents.iter().product::<f32>()
+    }
+
+
+    /// Whether the point lies inside or on the box, with a tolerance of `1e-4` times the size of the box for rounding errors.
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        let tolerance = 1e-4 * self.half_extents[0];
+        let offset = [point[0] - self.center[0], point[1] - self.center[1], point[2] - self.center[2]];
+        (0..3).all(|i| dot(self.axes[i], offset).abs() <= self.half_extents[i] + tolerance)
+    }
+
+
+    /// Get the 8 corners of the box.
+    pub fn corners(&self) -> Vec<[f32; 3]> {
+        (0..8).map(|corner| {
+            let mut point = self.center;
+            for (i, axis) in self.axes.iter().enumerate() {
+                let sign = if corner & (1 << i) == 0 { -1.0 } else { 1.0 };
+                for (p, a) in point.iter_mut().zip(axis.iter()) {
+                    *p += sign * self.half_extents[i] * a;
+                }
+            }
+            point
+        }).collect()
+    }
+}
+
+
+/// A face of the hull during construction, see [`BrainMesh::convex_hull`].
+struct HullFace {
+    vertices: [usize; 3],
+    normal: [f64; 3],
+    offset: f64,
+    /// The points above this face which are not yet part of the hull.
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+
+impl HullFace {
+    fn new(vertices: [usize; 3], points: &[[f64; 3]]) -> HullFace {
+        let [a, b, c] = vertices.map(|v| points[v]);
+        let n = cross64(sub64(b, a), sub64(c, a));
+        let length = dot64(n, n).sqrt();
+        let normal = [n[0] / length, n[1] / length, n[2] / length];
+        HullFace { vertices, normal, offset: dot64(normal, a), outside: Vec::new(), alive: true }
+    }
+
+
+    /// The signed distance of the point from the plane of the face, positive above the face.
+    fn distance(&self, point: [f64; 3]) -> f64 {
+        dot64(self.normal, point) - self.offset
+    }
+
+
+    /// The directed edges of the face.
+    fn edges(&self) -> [(usize, usize); 3] {
+        let [a, b, c] = self.vertices;
+        [(a, b), (b, c), (c, a)]
+    }
+}
+
+
+impl BrainMesh {
+    /// Compute the convex hull of the vertices of the mesh with the quickhull algorithm.
+    ///
+    /// # Return value
+    ///
+    /// A closed mesh which only contains the vertices on the hull, with all faces oriented outwards. Vertices on the faces or edges of
+    /// the hull are not included. Returns [`NeuroformatsError::DegenerateGeometry`] if the mesh has less than 4 vertices or all of them lie
+    /// in a common plane, so that the hull has no volume.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let pial = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+    /// let hull = pial.mesh.convex_hull().unwrap();
+    /// println!("The hull has {} vertices and an area of {}.", hull.num_vertices(), hull.total_area());
+    /// ```
+    pub fn convex_hull(&self) -> Result<BrainMesh> {
+        let points: Vec<[f64; 3]> = (0..self.num_vertices()).map(|v| self.vertex_coords(v).map(|c| c as f64)).collect();
+        let scale = points.iter().flat_map(|p| p.iter()).fold(0.0_f64, |m, c| m.max(c.abs()));
+        let eps = 1e-7 * scale.max(f64::MIN_POSITIVE);
+
+        let simplex = initial_simplex(&points, eps)?;
+        let centroid = simplex.iter().fold([0.0; 3], |acc, v| [acc[0] + points[*v][0] / 4.0, acc[1] + points[*v][1] / 4.0, acc[2] + points[*v][2] / 4.0]);
+        let mut faces: Vec<HullFace> = Vec::new();
+        for [a, b, c] in [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]] {
+            let face = HullFace::new([simplex[a], simplex[b], simplex[c]], &points);
+            faces.push(if face.distance(centroid) > 0.0 { HullFace::new([simplex[a], simplex[c], simplex[b]], &points) } else { face });
+        }
+        let mut edge_faces: HashMap<(usize, usize), usize> = HashMap::new();
+        for (face_idx, face) in faces.iter().enumerate() {
+            for edge in face.edges() {
+                edge_faces.insert(edge, face_idx);
+            }
+        }
+        for p in (0..points.len()).filter(|p| !simplex.contains(p)) {
+            assign_to_faces(p, &points, &mut faces, 0, eps);
+        }
+
+        let mut pending: Vec<usize> = (0..4).collect();
+        while let Some(face_idx) = pending.pop() {
+            if !faces[face_idx].alive || faces[face_idx].outside.is_empty() {
+                continue;
+            }
+            let apex = *faces[face_idx].outside.iter().max_by(|a, b| faces[face_idx].distance(points[**a]).total_cmp(&faces[face_idx].distance(points[**b]))).unwrap();
+
+            // Find all faces visible from the apex, which are connected to the current face.
+            let mut visible = vec![face_idx];
+            faces[face_idx].alive = false;
+            let mut horizon = Vec::new();
+            let mut i = 0;
+            while i < visible.len() {
+                for (a, b) in faces[visible[i]].edges() {
+                    let neighbor = edge_faces[&(b, a)];
+                    if faces[neighbor].alive && faces[neighbor].distance(points[apex]) > eps {
+                        faces[neighbor].alive = false;
+                        visible.push(neighbor);
+                    } else if faces[neighbor].alive {
+                        horizon.push((a, b));
+                    }
+                }
+                i += 1;
+            }
+
+            let orphans: Vec<usize> = visible.iter().flat_map(|f| std::mem::take(&mut faces[*f].outside)).filter(|p| *p != apex).collect();
+            for f in visible.iter() {
+                for edge in faces[*f].edges() {
+                    edge_faces.remove(&edge);
+                }
+            }
+            let first_new = faces.len();
+            for (a, b) in horizon {
+                let face = HullFace::new([a, b, apex], &points);
+                for edge in face.edges() {
+                    edge_faces.insert(edge, faces.len());
+                }
+                pending.push(faces.len());
+                faces.push(face);
+            }
+            for p in orphans {
+                assign_to_faces(p, &points, &mut faces, first_new, eps);
+            }
+        }
+
+        let mut new_index: HashMap<usize, i32> = HashMap::new();
+        let mut vertices = Vec::new();
+        let mut hull_faces = Vec::new();
+        for face in faces.iter().filter(|f| f.alive) {
+            for v in face.vertices {
+                let idx = *new_index.entry(v).or_insert_with(|| {
+                    vertices.extend_from_slice(&self.vertex_coords(v));
+                    (vertices.len() / 3 - 1) as i32
+                });
+                hull_faces.push(idx);
+            }
+        }
+        Ok(BrainMesh { vertices, faces: hull_faces })
+    }
+
+
+    /// Compute a sphere that encloses all vertices of the mesh with Ritter's algorithm.
+    ///
+    /// The sphere is not the smallest enclosing sphere, but its radius is typically at most 5% larger. For an empty mesh, the sphere is
+    /// located at the origin and has radius `0.0`.
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        if self.num_vertices() == 0 {
+            return BoundingSphere { center: [0.0; 3], radius: 0.0 };
+        }
+        let farthest_from = |p: [f32; 3]| (0..self.num_vertices()).map(|v| self.vertex_coords(v)).max_by(|a, b| dist(p, *a).total_cmp(&dist(p, *b))).unwrap();
+        let a = farthest_from(self.vertex_coords(0));
+        let b = farthest_from(a);
+        let mut center = [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0];
+        let mut radius = dist(a, b) / 2.0;
+        for v in 0..self.num_vertices() {
+            let p = self.vertex_coords(v);
+            let d = dist(center, p);
+            if d > radius {
+                // Grow the sphere just enough to include the point, keeping the opposite side fixed.
+                let new_radius = (radius + d) / 2.0;
+                let shift = (new_radius - radius) / d;
+                for i in 0..3 {
+                    center[i] += (p[i] - center[i]) * shift;
+                }
+                radius = new_radius;
+            }
+        }
+        BoundingSphere { center, radius: radius * (1.0 + 1e-6) }
+    }
+
+
+    /// Compute a box that encloses all vertices of the mesh, with edges along the principal axes of the vertex coordinates.
+    ///
+    /// The axes are the eigenvectors of the covariance matrix of the coordinates. The box is not the smallest enclosing box, but
+    /// it is a good fit for elongated meshes like brain hemispheres. For an empty mesh, the box is located at the origin and has size `0.0`.
+    pub fn oriented_bounding_box(&self) -> OrientedBoundingBox {
+        let n = self.num_vertices();
+        let identity = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        if n == 0 {
+            return OrientedBoundingBox { center: [0.0; 3], axes: identity, half_extents: [0.0; 3] };
+        }
+        let mut mean = [0.0_f64; 3];
+        for v in 0..n {
+            for (m, c) in mean.iter_mut().zip(self.vertex_coords(v).iter()) {
+                *m += *c as f64 / n as f64;
+            }
+        }
+        let mut covariance = [[0.0_f64; 3]; 3];
+        for v in 0..n {
+            let p = self.vertex_coords(v);
+            for i in 0..3 {
+                for j in 0..3 {
+                    covariance[i][j] += (p[i] as f64 - mean[i]) * (p[j] as f64 - mean[j]) / n as f64;
+                }
+            }
+        }
+        // The eigenvalues are in ascending order, and the axes are sorted by descending variance.
+        let (_, eigenvectors) = symmetric_eigen(covariance.iter().map(|row| row.to_vec()).collect());
+        let axes = [2, 1, 0].map(|k| [eigenvectors[k][0] as f32, eigenvectors[k][1] as f32, eigenvectors[k][2] as f32]);
+
+        let mut center = [0.0; 3];
+        let mut half_extents = [0.0; 3];
+        for (i, axis) in axes.iter().enumerate() {
+            let projections = (0..n).map(|v| dot(*axis, self.vertex_coords(v)));
+            let (min, max) = projections.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), p| (lo.min(p), hi.max(p)));
+            half_extents[i] = (max - min) / 2.0;
+            for (c, a) in center.iter_mut().zip(axis.iter()) {
+                *c += (max + min) / 2.0 * a;
+            }
+        }
+        OrientedBoundingBox { center, axes, half_extents }
+    }
+}
+
+
+/// Find 4 points which span a tetrahedron with a volume that is large compared to the extent of the point cloud.
+fn initial_simplex(points: &[[f64; 3]], eps: f64) -> Result<[usize; 4]> {
+    let degenerate = || NeuroformatsError::DegenerateGeometry(String::from("the vertices do not span a volume"));
+    if points.len() < 4 {
+        return Err(degenerate());
+    }
+    let mut extremes = Vec::new();
+    for axis in 0..3 {
+        extremes.push((0..points.len()).min_by(|a, b| points[*a][axis].total_cmp(&points[*b][axis])).unwrap());
+        extremes.push((0..points.len()).max_by(|a, b| points[*a][axis].total_cmp(&points[*b][axis])).unwrap());
+    }
+    let dist64 = |a: [f64; 3], b: [f64; 3]| { let d = sub64(a, b); dot64(d, d).sqrt() };
+    let (p0, p1) = extremes.iter().flat_map(|a| extremes.iter().map(move |b| (*a, *b)))
+        .max_by(|(a, b), (c, d)| dist64(points[*a], points[*b]).total_cmp(&dist64(points[*c], points[*d]))).unwrap();
+    let line = sub64(points[p1], points[p0]);
+    let line_dist = |p: usize| { let c = cross64(line, sub64(points[p], points[p0])); dot64(c, c).sqrt() / dot64(line, line).sqrt() };
+    let p2 = (0..points.len()).max_by(|a, b| line_dist(*a).total_cmp(&line_dist(*b))).unwrap();
+    if dist64(points[p0], points[p1]) <= eps || line_dist(p2) <= eps {
+        return Err(degenerate());
+    }
+    let plane = HullFace::new([p0, p1, p2], points);
+    let p3 = (0..points.len()).max_by(|a, b| plane.distance(points[*a]).abs().total_cmp(&plane.distance(points[*b]).abs())).unwrap();
+    if plane.distance(points[p3]).abs() <= eps {
+        return Err(degenerate());
+    }
+    Ok([p0, p1, p2, p3])
+}
+
+
+/// Add the point to the outside set of the first face starting at index `first_face` which it lies above. Points below all these faces are inside the hull.
+fn assign_to_faces(p: usize, points: &[[f64; 3]], faces: &mut [HullFace], first_face: usize, eps: f64) {
+    if let Some(face) = faces[first_face..].iter_mut().find(|f| f.alive && f.distance(points[p]) > eps) {
+        face.outside.push(p);
+    }
+}
+
+
+fn sub64(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+
+fn dot64(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+
+fn cross64(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+
+fn dist(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use crate::subdivision::generate_icosphere;
+    #[cfg(feature = "fs")]
+    use crate::read_surf;
+
+    /// The 8 corners of a unit cube, plus its center and the centers of its faces, which are not on the hull.
+    fn cube_with_inner_points() -> BrainMesh {
+        let mut vertices: Vec<f32> = (0..8).flat_map(|c| vec![(c & 1) as f32, ((c >> 1) & 1) as f32, ((c >> 2) & 1) as f32]).collect();
+        vertices.extend_from_slice(&[0.5, 0.5, 0.5, 0.5, 0.5, 0.0, 0.5, 0.0, 0.5]);
+        BrainMesh { vertices, faces: vec![] }
+    }
+
+    #[test]
+    fn the_convex_hull_of_a_cube_can_be_computed() {
+        let hull = cube_with_inner_points().convex_hull().unwrap();
+        assert_eq!(8, hull.num_vertices());
+        assert_eq!(12, hull.num_faces());
+        assert_abs_diff_eq!(6.0, hull.total_area(), epsilon = 1e-5);
+        assert!(hull.inverted_faces().is_empty());
+        assert!(hull.is_closed());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_convex_hull_of_a_brain_surface_contains_all_its_vertices() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let hull = surf.mesh.convex_hull().unwrap();
+        assert!(hull.num_vertices() < surf.mesh.num_vertices() / 10);
+        assert!(hull.is_closed());
+        assert!(hull.inverted_faces().is_empty());
+        assert!(hull.total_area() < surf.mesh.total_area());
+        assert!(surf.mesh.hull_depth().iter().all(|d| *d >= 0.0));
+    }
+
+    #[test]
+    fn the_convex_hull_of_a_flat_mesh_is_an_error() {
+        let flat = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0], faces: vec![0, 1, 2, 1, 3, 2] };
+        assert!(matches!(flat.convex_hull(), Err(NeuroformatsError::DegenerateGeometry(_))));
+        assert!(BrainMesh { vertices: vec![], faces: vec![] }.convex_hull().is_err());
+    }
+
+    #[test]
+    fn a_bounding_sphere_contains_all_vertices() {
+        let mut ico = generate_icosphere(3);
+        ico.vertices.iter_mut().for_each(|c| *c = *c * 10.0 + 5.0);
+        let sphere = ico.bounding_sphere();
+        assert!((0..ico.num_vertices()).all(|v| sphere.contains(ico.vertex_coords(v))));
+        assert!(sphere.radius < 10.0 * 1.05);
+        assert_abs_diff_eq!(5.0, sphere.center[0], epsilon = 0.5);
+        assert_eq!(0.0, BrainMesh { vertices: vec![], faces: vec![] }.bounding_sphere().radius);
+    }
+
+    #[test]
+    fn an_oriented_bounding_box_follows_the_principal_axes() {
+        // A box of size 4 x 2 x 1, rotated by 45 degrees around the z axis.
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        let vertices = (0..8).flat_map(|c| {
+            let (x, y, z) = ((c & 1) as f32 * 4.0 - 2.0, ((c >> 1) & 1) as f32 * 2.0 - 1.0, ((c >> 2) & 1) as f32 - 0.5);
+            vec![s * x - s * y, s * x + s * y, z]
+        }).collect();
+        let mesh = BrainMesh { vertices, faces: vec![] };
+        let obb = mesh.oriented_bounding_box();
+        assert_abs_diff_eq!(2.0, obb.half_extents[0], epsilon = 1e-4);
+        assert_abs_diff_eq!(1.0, obb.half_extents[1], epsilon = 1e-4);
+        assert_abs_diff_eq!(0.5, obb.half_extents[2], epsilon = 1e-4);
+        assert_abs_diff_eq!(8.0, obb.volume(), epsilon = 1e-3);
+        assert_abs_diff_eq!(s, obb.axes[0][0].abs(), epsilon = 1e-4);
+        assert_abs_diff_eq!(1.0, obb.axes[2][2].abs(), epsilon = 1e-4);
+        assert!((0..8).all(|v| obb.contains(mesh.vertex_coords(v))));
+        assert!(!obb.contains([3.0, 0.0, 0.0]));
+        assert_eq!(8, obb.corners().len());
+    }
+}
@@ -0,0 +1,144 @@
+//! Generation of smoothed and inflated surfaces, similar to FreeSurfer's `mris_inflate`.
+//!
+//! Inflated surfaces make the sulci visible, which is useful for displaying per-vertex data that would be hidden in the
+//! folds of the white or pial surface.
+
+
+use crate::fs_surface::BrainMesh;
+use crate::util::vec3_sub;
+
+
+/// The result of [`BrainMesh::inflate_with_sulc`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inflation {
+    /// The inflated mesh.
+    pub mesh: BrainMesh,
+    /// The accumulated displacement of each vertex along its normal during inflation, in mesh units. Vertices in sulci move outwards and get positive values, vertices on gyral crowns get negative values, like in FreeSurfer `?h.sulc` files.
+    pub sulc: Vec<f32>,
+}
+
+
+impl BrainMesh {
+    /// Compute a smoothed, inflated copy of the mesh by iterative Laplacian smoothing.
+    ///
+    /// In each iteration, every vertex moves by `lambda` times the vector to the centroid of its neighbors, and the mesh is then rescaled
+    /// around its centroid to the original total surface area, which counteracts the shrinking of plain Laplacian smoothing.
+    ///
+    /// # Parameters
+    ///
+    /// * `iterations`: the number of smoothing iterations. A few hundred iterations give a result similar to FreeSurfer inflated surfaces.
+    /// * `lambda`: the step size, in the range `0.0` to `1.0`. Values around `0.5` are typical.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let inflated = surf.mesh.inflate(200, 0.5);
+    /// ```
+    pub fn inflate(&self, iterations: usize, lambda: f32) -> BrainMesh {
+        self.inflate_with_sulc(iterations, lambda).mesh
+    }
+
+
+    /// Inflate the mesh like [`BrainMesh::inflate`], and also compute a sulc-like per-vertex value from the vertex displacements.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let inflation = surf.mesh.inflate_with_sulc(200, 0.5);
+    /// let sulc_at_vertex_0 = inflation.sulc[0];
+    /// ```
+    pub fn inflate_with_sulc(&self, iterations: usize, lambda: f32) -> Inflation {
+        let neighbors = self.vertex_neighbors();
+        let target_area = self.total_area();
+        let mut mesh = self.clone();
+        let mut sulc = vec![0.0; self.num_vertices()];
+
+        for _ in 0..iterations {
+            let normals = mesh.vertex_normals();
+            let mut smoothed = mesh.vertices.clone();
+            for (vertex, neigh) in neighbors.iter().enumerate() {
+                if neigh.is_empty() {
+                    continue;
+                }
+                let p = mesh.vertex_coords(vertex);
+                let mut centroid = [0.0f32; 3];
+                for &n in neigh.iter() {
+                    let q = mesh.vertex_coords(n);
+                    for axis in 0..3 {
+                        centroid[axis] += q[axis] / neigh.len() as f32;
+                    }
+                }
+                let step = vec3_sub(centroid, p);
+                for axis in 0..3 {
+                    smoothed[vertex * 3 + axis] = p[axis] + lambda * step[axis];
+                    sulc[vertex] += lambda * step[axis] * normals[vertex * 3 + axis];
+                }
+            }
+            mesh.vertices = smoothed;
+            rescale_to_area(&mut mesh, target_area);
+        }
+        Inflation { mesh, sulc }
+    }
+}
+
+
+/// Scale the mesh around its vertex centroid, so that its total surface area becomes `target_area`.
+fn rescale_to_area(mesh: &mut BrainMesh, target_area: f32) {
+    let area = mesh.total_area();
+    if area <= 0.0 || mesh.num_vertices() == 0 {
+        return;
+    }
+    let scale = (target_area / area).sqrt();
+    let mut centroid = [0.0f32; 3];
+    for c in mesh.vertices.chunks_exact(3) {
+        for axis in 0..3 {
+            centroid[axis] += c[axis];
+        }
+    }
+    let num_vertices = mesh.num_vertices() as f32;
+    centroid.iter_mut().for_each(|c| *c /= num_vertices);
+    for c in mesh.vertices.chunks_exact_mut(3) {
+        for axis in 0..3 {
+            c[axis] = centroid[axis] + scale * (c[axis] - centroid[axis]);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "fs")]
+    use super::*;
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_surface_can_be_inflated() {
+        let surf = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let inflation = surf.mesh.inflate_with_sulc(20, 0.5);
+        assert_eq!(surf.mesh.num_vertices(), inflation.mesh.num_vertices());
+        assert_eq!(surf.mesh.faces, inflation.mesh.faces);
+        assert_eq!(surf.mesh.num_vertices(), inflation.sulc.len());
+        assert!((surf.mesh.total_area() - inflation.mesh.total_area()).abs() / surf.mesh.total_area() < 1e-3);
+
+        // Inflation flattens the folds, so the mean curvature magnitude decreases.
+        let mean_abs = |m: &BrainMesh| m.principal_curvatures().mean.iter().map(|h| h.abs()).sum::<f32>() / m.num_vertices() as f32;
+        assert!(mean_abs(&inflation.mesh) < mean_abs(&surf.mesh));
+
+        // Sulcal (concave, negative mean curvature) vertices move outwards, gyral (convex) ones inwards.
+        let curv = surf.mesh.principal_curvatures().mean;
+        let sulcal_sulc: f32 = (0..curv.len()).filter(|&i| curv[i] < -0.2).map(|i| inflation.sulc[i]).sum();
+        let gyral_sulc: f32 = (0..curv.len()).filter(|&i| curv[i] > 0.2).map(|i| inflation.sulc[i]).sum();
+        assert!(sulcal_sulc > 0.0);
+        assert!(gyral_sulc < 0.0);
+    }
+
+    #[test]
+    fn inflating_with_zero_iterations_does_not_change_the_mesh() {
+        let ico = crate::subdivision::generate_icosphere(2);
+        let inflation = ico.inflate_with_sulc(0, 0.5);
+        assert_eq!(ico, inflation.mesh);
+        assert!(inflation.sulc.iter().all(|&s| s == 0.0));
+    }
+}
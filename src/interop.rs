@@ -0,0 +1,102 @@
+//! Zero-copy views of mesh vertices as types of the nalgebra and glam math libraries.
+//!
+//! These are available with the optional `nalgebra` and `glam` features. The views share their memory with
+//! [`BrainMesh::vertices`], so no data is copied, and changes made through the mutable views change the mesh.
+
+
+use crate::fs_surface::BrainMesh;
+
+
+#[cfg(feature = "nalgebra")]
+impl BrainMesh {
+    /// View the vertex coordinates of this mesh as nalgebra points.
+    ///
+    /// # Panics
+    ///
+    /// If the length of [`BrainMesh::vertices`] is not a multiple of 3.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let dist = nalgebra::distance(&surf.mesh.vertices_as_points()[0], &surf.mesh.vertices_as_points()[1]);
+    /// ```
+    pub fn vertices_as_points(&self) -> &[nalgebra::Point3<f32>] {
+        bytemuck::cast_slice(&self.vertices)
+    }
+
+
+    /// View the vertex coordinates of this mesh as mutable nalgebra points, e.g., to transform them in place.
+    ///
+    /// # Panics
+    ///
+    /// If the length of [`BrainMesh::vertices`] is not a multiple of 3.
+    pub fn vertices_as_points_mut(&mut self) -> &mut [nalgebra::Point3<f32>] {
+        bytemuck::cast_slice_mut(&mut self.vertices)
+    }
+}
+
+
+#[cfg(feature = "glam")]
+impl BrainMesh {
+    /// View the vertex coordinates of this mesh as glam vectors, e.g., to create a bevy mesh.
+    ///
+    /// # Panics
+    ///
+    /// If the length of [`BrainMesh::vertices`] is not a multiple of 3.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let centroid = surf.mesh.vertices_as_vec3().iter().sum::<glam::Vec3>() / surf.mesh.num_vertices() as f32;
+    /// ```
+    pub fn vertices_as_vec3(&self) -> &[glam::Vec3] {
+        bytemuck::cast_slice(&self.vertices)
+    }
+
+
+    /// View the vertex coordinates of this mesh as mutable glam vectors, e.g., to transform them in place.
+    ///
+    /// # Panics
+    ///
+    /// If the length of [`BrainMesh::vertices`] is not a multiple of 3.
+    pub fn vertices_as_vec3_mut(&mut self) -> &mut [glam::Vec3] {
+        bytemuck::cast_slice_mut(&mut self.vertices)
+    }
+
+
+    /// Get the faces of this mesh as glam vectors of vertex indices.
+    pub fn faces_as_ivec3(&self) -> &[glam::IVec3] {
+        bytemuck::cast_slice(&self.faces)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::test_meshes::tetrahedron;
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn the_vertices_of_a_mesh_can_be_viewed_as_nalgebra_points() {
+        let mut mesh = tetrahedron();
+        assert_eq!(4, mesh.vertices_as_points().len());
+        assert_eq!(nalgebra::Point3::new(0.0, 1.0, 0.0), mesh.vertices_as_points()[2]);
+
+        let shift = nalgebra::Vector3::new(1.0, 0.0, 0.0);
+        mesh.vertices_as_points_mut().iter_mut().for_each(|p| *p += shift);
+        assert_eq!([1.0, 1.0, 0.0], mesh.vertex_coords(2));
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn the_vertices_and_faces_of_a_mesh_can_be_viewed_as_glam_vectors() {
+        let mut mesh = tetrahedron();
+        assert_eq!(glam::Vec3::new(0.0, 0.0, 1.0), mesh.vertices_as_vec3()[3]);
+        assert_eq!(glam::IVec3::new(0, 1, 3), mesh.faces_as_ivec3()[1]);
+
+        mesh.vertices_as_vec3_mut().iter_mut().for_each(|v| *v *= 2.0);
+        assert_eq!([0.0, 0.0, 2.0], mesh.vertex_coords(3));
+    }
+}
@@ -0,0 +1,249 @@
+//! Extraction of isosurface meshes from MGH volumes, e.g., to visualize subcortical structures from `aseg.mgz` alongside cortical surfaces.
+//!
+//! The meshes are computed with marching tetrahedra, a variant of marching cubes that splits each cube of 8 neighboring voxel
+//! centers into 6 tetrahedra. It needs no large lookup tables and produces closed meshes without cracks. Voxels outside of the
+//! volume are treated as outside of the surface, so structures touching the border of the volume are closed as well.
+
+
+use std::collections::HashMap;
+
+use ndarray::Array2;
+
+use crate::error::Result;
+use crate::fs_mgh::FsMgh;
+use crate::fs_surface::BrainMesh;
+
+
+/// Determines which voxels are inside of the surface computed by [`volume_to_mesh`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IsoLevel {
+    /// Voxels with a value greater than or equal to the threshold are inside. The surface is interpolated linearly between voxel centers.
+    Threshold(f32),
+    /// Voxels with the given label value are inside, e.g., `17` for the left hippocampus in `aseg.mgz`. The surface passes halfway between voxel centers.
+    Label(i32),
+}
+
+
+/// The 6 tetrahedra of a cube, as indices of the cube corners. Corner `n` is at offset `(n & 1, (n >> 1) & 1, (n >> 2) & 1)`.
+///
+/// All tetrahedra share the diagonal from corner 0 to corner 7, so the faces of neighboring cubes are split consistently.
+const CUBE_TETRAHEDRA: [[usize; 4]; 6] = [[0, 1, 3, 7], [0, 3, 2, 7], [0, 2, 6, 7], [0, 6, 4, 7], [0, 4, 5, 7], [0, 5, 1, 7]];
+
+
+/// The minimal distance of interpolated vertices from the grid points, as a fraction of the edge length.
+const MIN_EDGE_FRACTION: f32 = 0.01;
+
+
+/// Extract an isosurface mesh from the first frame of a volume, in the scanner RAS coordinates of the [`FsMgh::vox2ras`] matrix.
+///
+/// # Parameters
+///
+/// * `mgh`: the volume, e.g., a segmentation like `aseg.mgz` or an intensity volume like `brain.mgz`.
+/// * `level`: determines the voxels inside of the surface, see [`IsoLevel`].
+///
+/// # Return value
+///
+/// The mesh, with faces oriented so that their normals point outwards. The mesh is empty if no voxels are inside. Returns an error if the header contains no RAS information.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::isosurface::{volume_to_mesh_with_matrix, IsoLevel};
+/// let aseg = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/aseg.mgz").unwrap();
+/// // Use the tkregister matrix to get the surface RAS coordinates of the cortical surfaces, like lh.white.
+/// let hippocampus = volume_to_mesh_with_matrix(&aseg, IsoLevel::Label(17), &aseg.header.vox2ras_tkr().unwrap()).unwrap();
+/// ```
+pub fn volume_to_mesh(mgh: &FsMgh, level: IsoLevel) -> Result<BrainMesh> {
+    volume_to_mesh_with_matrix(mgh, level, &mgh.vox2ras()?)
+}
+
+
+/// Extract an isosurface mesh from the first frame of a volume, mapping voxel indices to coordinates with the given 4x4 matrix.
+///
+/// Use [`crate::FsMghHeader::vox2ras_tkr`] to get the coordinates in the surface RAS space of FreeSurfer surfaces, or the identity matrix to get voxel coordinates.
+/// See [`volume_to_mesh`] for details.
+///
+/// # Panics
+///
+/// If `vox2ras` is not a 4x4 matrix.
+pub fn volume_to_mesh_with_matrix(mgh: &FsMgh, level: IsoLevel, vox2ras: &Array2<f32>) -> Result<BrainMesh> {
+    assert_eq!((4, 4), vox2ras.dim(), "Expected a 4x4 matrix.");
+    let values = mgh.frame_as_f32(0)?;
+    let dim = mgh.dim();
+    let (iso, field): (f32, Box<dyn Fn(f32) -> f32>) = match level {
+        IsoLevel::Threshold(threshold) => (threshold, Box::new(|v| v)),
+        IsoLevel::Label(label) => (0.5, Box::new(move |v: f32| if !v.is_nan() && v.round() as i32 == label { 1.0 } else { 0.0 })),
+    };
+
+    // The field value at a grid point, with one layer of points outside of the volume on each side. Points outside are `None`.
+    let value_at = |p: [i64; 3]| -> Option<f32> {
+        if (0..3).any(|axis| p[axis] < 0 || p[axis] >= dim[axis] as i64) {
+            return None;
+        }
+        let v = field(values[mgh.header.voxel_flat_index([p[0] as usize, p[1] as usize, p[2] as usize], 0)]);
+        if v.is_nan() { None } else { Some(v) }
+    };
+    let inside = |v: Option<f32>| v.is_some_and(|v| v >= iso);
+    let point_id = |p: [i64; 3]| -> i64 { ((p[2] + 1) * (dim[1] as i64 + 2) + (p[1] + 1)) * (dim[0] as i64 + 2) + (p[0] + 1) };
+
+    let mut mesh = BrainMesh { vertices: Vec::new(), faces: Vec::new() };
+    let mut edge_vertices: HashMap<(i64, i64), i32> = HashMap::new();
+    let mut edge_vertex = |a: ([i64; 3], Option<f32>), b: ([i64; 3], Option<f32>), mesh: &mut BrainMesh| -> (i32, [f32; 3]) {
+        let key = (point_id(a.0).min(point_id(b.0)), point_id(a.0).max(point_id(b.0)));
+        // Interpolate linearly between the points, or use the midpoint if one of them is outside of the volume. The position is kept
+        // slightly away from the points, as vertices on grid points, e.g., for integer volumes and thresholds, would create faces without area.
+        let t = match (a.1, b.1) {
+            (Some(va), Some(vb)) if va != vb => ((iso - va) / (vb - va)).clamp(MIN_EDGE_FRACTION, 1.0 - MIN_EDGE_FRACTION),
+            _ => 0.5,
+        };
+        let voxel = [0, 1, 2].map(|axis| a.0[axis] as f32 + t * (b.0[axis] - a.0[axis]) as f32);
+        let coords = apply_affine(vox2ras, voxel);
+        let index = *edge_vertices.entry(key).or_insert_with(|| {
+            mesh.vertices.extend_from_slice(&coords);
+            (mesh.vertices.len() / 3 - 1) as i32
+        });
+        (index, voxel)
+    };
+
+    for z in -1..dim[2] as i64 {
+        for y in -1..dim[1] as i64 {
+            for x in -1..dim[0] as i64 {
+                let corners: [([i64; 3], Option<f32>); 8] = std::array::from_fn(|n| {
+                    let n = n as i64;
+                    let p = [x + (n & 1), y + ((n >> 1) & 1), z + ((n >> 2) & 1)];
+                    (p, value_at(p))
+                });
+                let num_inside = corners.iter().filter(|c| inside(c.1)).count();
+                if num_inside == 0 || num_inside == 8 {
+                    continue;
+                }
+                for tet in CUBE_TETRAHEDRA.iter() {
+                    let (ins, outs): (Vec<usize>, Vec<usize>) = tet.iter().partition(|&&c| inside(corners[c].1));
+                    if ins.is_empty() || outs.is_empty() {
+                        continue;
+                    }
+                    // The surface separates the inside from the outside corners, so the outward direction points from the inside to the outside corners.
+                    let centroid = |cs: &[usize]| [0, 1, 2].map(|axis| cs.iter().map(|c| corners[*c].0[axis] as f32).sum::<f32>() / cs.len() as f32);
+                    let (ci, co) = (centroid(&ins), centroid(&outs));
+                    let outward = [co[0] - ci[0], co[1] - ci[1], co[2] - ci[2]];
+                    let mut polygon = Vec::with_capacity(4);
+                    match (ins.len(), outs.len()) {
+                        (1, _) => for o in outs.iter() { polygon.push(edge_vertex(corners[ins[0]], corners[*o], &mut mesh)); },
+                        (_, 1) => for i in ins.iter() { polygon.push(edge_vertex(corners[*i], corners[outs[0]], &mut mesh)); },
+                        _ => {
+                            // Two corners inside and two outside: the intersection is a quad, with its corners in cyclic order.
+                            polygon.push(edge_vertex(corners[ins[0]], corners[outs[0]], &mut mesh));
+                            polygon.push(edge_vertex(corners[ins[0]], corners[outs[1]], &mut mesh));
+                            polygon.push(edge_vertex(corners[ins[1]], corners[outs[1]], &mut mesh));
+                            polygon.push(edge_vertex(corners[ins[1]], corners[outs[0]], &mut mesh));
+                        }
+                    }
+                    for idx in 1..polygon.len() - 1 {
+                        push_oriented_face(&mut mesh, [polygon[0], polygon[idx], polygon[idx + 1]], outward);
+                    }
+                }
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+
+/// Add a face to the mesh, flipping its orientation if needed so that its normal points in the `outward` direction, in voxel space.
+fn push_oriented_face(mesh: &mut BrainMesh, face: [(i32, [f32; 3]); 3], outward: [f32; 3]) {
+    let (a, b, c) = (face[0].1, face[1].1, face[2].1);
+    let normal = crate::util::vec3_cross(crate::util::vec3_sub(b, a), crate::util::vec3_sub(c, a));
+    if crate::util::vec3_dot(normal, outward) >= 0.0 {
+        mesh.faces.extend_from_slice(&[face[0].0, face[1].0, face[2].0]);
+    } else {
+        mesh.faces.extend_from_slice(&[face[0].0, face[2].0, face[1].0]);
+    }
+}
+
+
+/// Apply a 4x4 affine matrix to a 3D point.
+fn apply_affine(m: &Array2<f32>, p: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|i| m[[i, 0]] * p[0] + m[[i, 1]] * p[1] + m[[i, 2]] * p[2] + m[[i, 3]])
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs_mgh::FsMghHeader;
+
+    fn volume(dim: [usize; 3], value: impl Fn(usize, usize, usize) -> f32) -> FsMgh {
+        let header = FsMghHeader {
+            dim1len: dim[0] as i32,
+            dim2len: dim[1] as i32,
+            dim3len: dim[2] as i32,
+            dim4len: 1,
+            is_ras_good: 1,
+            delta: [1.0, 1.0, 1.0],
+            mdc_raw: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            p_xyz_c: [0.0, 0.0, 0.0],
+            ..Default::default()
+        };
+        let mut values = vec![0.0; dim[0] * dim[1] * dim[2]];
+        for s in 0..dim[2] {
+            for r in 0..dim[1] {
+                for c in 0..dim[0] {
+                    values[header.voxel_flat_index([c, r, s], 0)] = value(c, r, s);
+                }
+            }
+        }
+        FsMgh::from_f32_values(&header, values).unwrap()
+    }
+
+    #[test]
+    fn a_closed_mesh_can_be_extracted_for_a_label() {
+        let mgh = volume([6, 6, 6], |c, r, s| if (1..4).contains(&c) && (2..4).contains(&r) && (2..5).contains(&s) { 17.0 } else { 3.0 });
+        let mesh = volume_to_mesh(&mgh, IsoLevel::Label(17)).unwrap();
+
+        assert!(mesh.num_faces() > 0);
+        assert!(mesh.is_closed());
+        assert!(mesh.non_manifold_edges().is_empty());
+        assert_eq!(2, mesh.euler_characteristic());
+
+        // The surface lies halfway between the voxel centers of the label and its neighbors. The center voxel maps to RAS (0, 0, 0).
+        let (xmin, xmax, ..) = mesh.axes_min_max_coords().unwrap();
+        assert!((xmin - (0.5 - 3.0)).abs() < 1e-5);
+        assert!((xmax - (3.5 - 3.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn the_faces_of_an_isosurface_point_outwards() {
+        let mgh = volume([7, 7, 7], |c, r, s| 10.0 - ((c as f32 - 3.0).powi(2) + (r as f32 - 3.0).powi(2) + (s as f32 - 3.0).powi(2)).sqrt());
+        let mesh = volume_to_mesh_with_matrix(&mgh, IsoLevel::Threshold(8.0), &Array2::eye(4)).unwrap();
+
+        assert!(mesh.is_closed());
+        let normals = mesh.vertex_normals();
+        for (v, coords) in mesh.iter_vertices().enumerate() {
+            let radial = [coords[0] - 3.0, coords[1] - 3.0, coords[2] - 3.0];
+            assert!(crate::util::vec3_dot(radial, [normals[v * 3], normals[v * 3 + 1], normals[v * 3 + 2]]) > 0.0);
+            assert!((crate::util::vec3_norm(radial) - 2.0).abs() < 0.3);
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn an_isosurface_of_the_brain_volume_can_be_extracted() {
+        let mgh = crate::read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let mesh = volume_to_mesh(&mgh, IsoLevel::Threshold(40.0)).unwrap();
+        assert!(mesh.num_faces() > 10000);
+        assert!(mesh.is_closed());
+        assert!(mesh.non_manifold_edges().is_empty());
+    }
+
+    #[test]
+    fn structures_touching_the_border_are_closed_and_empty_volumes_give_empty_meshes() {
+        let mgh = volume([3, 3, 3], |_, _, _| 1.0);
+        let mesh = volume_to_mesh(&mgh, IsoLevel::Threshold(0.5)).unwrap();
+        assert!(mesh.is_closed());
+        assert_eq!(2, mesh.euler_characteristic());
+
+        let empty = volume_to_mesh(&mgh, IsoLevel::Label(2)).unwrap();
+        assert_eq!(0, empty.num_vertices());
+        assert_eq!(0, empty.num_faces());
+    }
+}
@@ -0,0 +1,298 @@
+//! Per-vertex integer label maps, like the cluster maps computed by clustering algorithms, and their conversion to parcellations.
+//!
+//! A label map assigns an integer label to each vertex of a brain mesh. Unlike a parcellation in an annot file, it has no region names
+//! or colors, so FreeView cannot show it as a parcellation. [`FsAnnot::from_label_map`] turns a label map into an [`FsAnnot`] with a
+//! generated colortable, which can be written with [`crate::write_annot`], and [`FsAnnot::to_label_map`] converts back. Label maps can
+//! be read from and written to MGH files with `MRI_INT` data and text files with one label per line, like single-column CSV files.
+
+
+use std::collections::HashMap;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use ndarray::{Array, Dim};
+
+use crate::color_lut::ColorLut;
+use crate::colormap::distinct_colors;
+use crate::fs_annot::{FsAnnot, FsAnnotColortable, FsAnnotColorRegion};
+use crate::fs_mgh::{FsMgh, FsMghData, FsMghHeader, MRI_FLOAT, MRI_INT, MRI_SHORT, MRI_UCHAR};
+use crate::error::{NeuroformatsError, Result};
+#[cfg(feature = "fs")]
+use crate::fs_mgh::{read_mgh, write_mgh};
+#[cfg(feature = "fs")]
+use crate::util::{open_maybe_compressed, WriteOptions};
+
+
+/// The header line of label map text files written by [`write_label_map`].
+pub const LABEL_MAP_CSV_HEADER: &str = "label";
+
+
+impl FsAnnot {
+    /// Create a parcellation from a per-vertex integer label map, with one region per distinct label.
+    ///
+    /// The region ids in the colortable are the labels, so [`FsAnnot::to_label_map`] restores the label map. Vertices with negative labels
+    /// are not assigned to any region.
+    ///
+    /// # Parameters
+    ///
+    /// * `labels`: the label of each vertex.
+    /// * `lut`: an optional color lookup table with the names and colors of the labels, e.g., the one used by the tool which computed the
+    ///   label map. Labels which are not in the table are named `label<N>`, or `unknown` for label `0`, and get generated colors which differ
+    ///   from those of all other regions.
+    ///
+    /// # Return value
+    ///
+    /// The parcellation, or [`NeuroformatsError::InvalidLabelMap`] if two labels have the same color in the `lut`, which would make them
+    /// indistinguishable in the annot format.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let clusters = neuroformats::label_map::read_label_map("/path/to/lh.clusters.mgh").unwrap();
+    /// let annot = neuroformats::FsAnnot::from_label_map(&clusters, None).unwrap();
+    /// neuroformats::write_annot("/path/to/lh.clusters.annot", &annot).unwrap();
+    /// ```
+    pub fn from_label_map(labels: &[i32], lut: Option<&ColorLut>) -> Result<FsAnnot> {
+        FsAnnot::from_label_map_with_seed(labels, lut, 0)
+    }
+
+
+    /// Create a parcellation from a per-vertex integer label map, generating the region colors with the given seed.
+    ///
+    /// See [`FsAnnot::from_label_map`] for details. The colors of the labels which are not in the `lut` are generated with
+    /// [`crate::colormap::distinct_colors`], so the same seed always gives the same colors, and other seeds can be tried if neighboring
+    /// regions have similar colors.
+    pub fn from_label_map_with_seed(labels: &[i32], lut: Option<&ColorLut>, seed: u64) -> Result<FsAnnot> {
+        let mut distinct: Vec<i32> = labels.iter().copied().filter(|label| *label >= 0).collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+
+        // Colors from the lookup table are kept, generated colors differ from them.
+        let lut_entries = lut.map(|lut| lut.by_label()).unwrap_or_default();
+        let lut_colors: Vec<[u8; 3]> = distinct.iter().filter_map(|label| lut_entries.get(label)).map(|entry| [entry.r, entry.g, entry.b]).collect();
+        let mut generated = distinct_colors(distinct.len() - lut_colors.len(), seed, &lut_colors).into_iter();
+
+        let mut regions: Vec<FsAnnotColorRegion> = Vec::with_capacity(distinct.len());
+        let mut color_labels: HashMap<i32, i32> = HashMap::with_capacity(distinct.len());
+        let mut labels_by_color: HashMap<i32, i32> = HashMap::with_capacity(distinct.len());
+        for label in distinct.iter() {
+            let (name, rgba) = match lut_entries.get(label) {
+                Some(entry) => (entry.name.clone(), [entry.r, entry.g, entry.b, entry.a].map(i32::from)),
+                None => {
+                    let [r, g, b] = generated.next().expect("One generated color per label which is not in the color lookup table").map(i32::from);
+                    (if *label == 0 { String::from("unknown") } else { format!("label{}", label) }, [r, g, b, 0])
+                },
+            };
+            let color_label = FsAnnotColorRegion::color_label(rgba[0], rgba[1], rgba[2], rgba[3]);
+            if let Some(other) = labels_by_color.insert(color_label, *label) {
+                return Err(NeuroformatsError::InvalidLabelMap(format!("labels {} and {} have the same color in the color lookup table", other, label)));
+            }
+            color_labels.insert(*label, color_label);
+            regions.push(FsAnnotColorRegion { id: *label, name, r: rgba[0], g: rgba[1], b: rgba[2], a: rgba[3], label: color_label });
+        }
+
+        let vertex_labels = labels.iter().map(|label| color_labels.get(label).copied().unwrap_or(-1)).collect();
+        let max_structures = distinct.last().map_or(0, |label| label + 1);
+        let colortable = FsAnnotColortable { regions, orig_filename: String::new(), max_structures };
+        Ok(FsAnnot::new((0..labels.len() as i32).collect(), vertex_labels, colortable))
+    }
+
+
+    /// Convert this parcellation to a per-vertex integer label map, which contains the `id` of the region of each vertex.
+    ///
+    /// For parcellations created with [`FsAnnot::from_label_map`], the ids are the original labels. Vertices which are not assigned to any
+    /// region of the colortable get the label `-1`.
+    pub fn to_label_map(&self) -> Vec<i32> {
+        let lookup = self.region_lookup();
+        self.vertex_labels.iter().map(|label| lookup.region(*label).map_or(-1, |region| region.id)).collect()
+    }
+}
+
+
+/// Get the per-vertex integer label map stored in an MGH volume, e.g., a surface overlay with dimensions (number of vertices, 1, 1, 1).
+///
+/// # Return value
+///
+/// The labels of all voxels, in file order. Returns [`NeuroformatsError::InvalidLabelMap`] if the volume has `MRI_FLOAT` data with values
+/// which are not integers, and [`NeuroformatsError::UnsupportedMriDataTypeInMgh`] for unsupported data types.
+pub fn label_map_from_mgh(mgh: &FsMgh) -> Result<Vec<i32>> {
+    let unsupported = || NeuroformatsError::UnsupportedMriDataTypeInMgh(mgh.header.dtype, mgh.header.dim());
+    match mgh.header.dtype {
+        MRI_INT => Ok(mgh.data.mri_int.as_ref().ok_or_else(unsupported)?.iter().copied().collect()),
+        MRI_UCHAR => Ok(mgh.data.mri_uchar.as_ref().ok_or_else(unsupported)?.iter().map(|v| *v as i32).collect()),
+        MRI_SHORT => Ok(mgh.data.mri_short.as_ref().ok_or_else(unsupported)?.iter().map(|v| *v as i32).collect()),
+        MRI_FLOAT => mgh.data.mri_float.as_ref().ok_or_else(unsupported)?.iter().map(|v| {
+            if v.fract() == 0.0 && *v >= i32::MIN as f32 && *v <= i32::MAX as f32 {
+                Ok(*v as i32)
+            } else {
+                Err(NeuroformatsError::InvalidLabelMap(format!("the value {} is not an integer label", v)))
+            }
+        }).collect(),
+        _ => Err(unsupported()),
+    }
+}
+
+
+/// Create an MGH volume with `MRI_INT` data and dimensions (number of vertices, 1, 1, 1) from a per-vertex integer label map.
+pub fn label_map_to_mgh(labels: &[i32]) -> FsMgh {
+    let header = FsMghHeader { dim1len: labels.len() as i32, dim2len: 1, dim3len: 1, dim4len: 1, dtype: MRI_INT, delta: [1.0; 3], ..FsMghHeader::default() };
+    let data = FsMghData {
+        mri_uchar: None,
+        mri_int: Some(Array::from_shape_vec(Dim(header.dim()), labels.to_vec()).unwrap()),
+        mri_float: None,
+        mri_short: None,
+    };
+    FsMgh { header, data, footer: None }
+}
+
+
+/// Read a per-vertex integer label map from a text stream with one label per line, like a single-column CSV file.
+///
+/// Empty lines are ignored, and the first line may be a header, like the [`LABEL_MAP_CSV_HEADER`] written by [`write_label_map`].
+///
+/// # Return value
+///
+/// The labels, or [`NeuroformatsError::InvalidLabelMap`] if a line does not contain an integer.
+pub fn label_map_from_csv<S: BufRead>(input: S) -> Result<Vec<i32>> {
+    let mut labels = Vec::new();
+    for (line_idx, line) in input.lines().enumerate() {
+        let line = line?;
+        let value = line.trim();
+        if value.is_empty() {
+            continue;
+        }
+        match value.parse::<i32>() {
+            Ok(label) => labels.push(label),
+            Err(_) if line_idx == 0 => continue,
+            Err(_) => return Err(NeuroformatsError::InvalidLabelMap(format!("line {} does not contain an integer label: '{}'", line_idx + 1, value))),
+        }
+    }
+    Ok(labels)
+}
+
+
+/// Whether a label map file is an MGH or MGZ file, judging by its file extension.
+#[cfg(feature = "fs")]
+fn is_mgh_path(path: &Path) -> bool {
+    path.file_name().map(|name| name.to_string_lossy().to_lowercase()).is_some_and(|name| name.ends_with(".mgh") || name.ends_with(".mgz"))
+}
+
+
+/// Read a per-vertex integer label map from a file.
+///
+/// Files with the extension `.mgh` or `.mgz` are read as MGH files, see [`label_map_from_mgh`]. All other files are read as text files with
+/// one label per line, see [`label_map_from_csv`]. Text files may be gzip-compressed.
+#[cfg(feature = "fs")]
+pub fn read_label_map<P: AsRef<Path>>(path: P) -> Result<Vec<i32>> {
+    if is_mgh_path(path.as_ref()) {
+        label_map_from_mgh(&read_mgh(path)?)
+    } else {
+        label_map_from_csv(open_maybe_compressed(path)?)
+    }
+}
+
+
+/// Write a per-vertex integer label map to a file.
+///
+/// Files with the extension `.mgh` or `.mgz` are written as MGH files with `MRI_INT` data, see [`label_map_to_mgh`]. All other files are
+/// written as text files with the header line [`LABEL_MAP_CSV_HEADER`], followed by one label per line.
+#[cfg(feature = "fs")]
+pub fn write_label_map<P: AsRef<Path>>(path: P, labels: &[i32]) -> std::io::Result<()> {
+    if is_mgh_path(path.as_ref()) {
+        return write_mgh(path, &label_map_to_mgh(labels));
+    }
+    let options = WriteOptions::default();
+    let mut f = options.create(path)?;
+    writeln!(f, "{}", LABEL_MAP_CSV_HEADER)?;
+    for label in labels {
+        writeln!(f, "{}", label)?;
+    }
+    options.finish(f)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use tempfile::tempdir;
+    #[cfg(feature = "fs")]
+    use crate::read_annot;
+
+    #[test]
+    fn a_label_map_can_be_converted_to_a_parcellation_and_back() {
+        let labels = vec![0, 3, 3, -1, 7, 0, 3];
+        let annot = FsAnnot::from_label_map(&labels, None).unwrap();
+        assert_eq!(3, annot.num_regions());
+        assert_eq!(vec!["unknown", "label3", "label7"], annot.regions());
+        assert_eq!(8, annot.colortable.max_structures);
+        assert_eq!(annot.colortable.regions[1].label, annot.vertex_labels[1]);
+        assert!(annot.vertex_region_info(3).is_none());
+        assert_eq!(labels, annot.to_label_map());
+
+        let colors: Vec<i32> = annot.colortable.regions.iter().map(|region| region.label).collect();
+        assert!(colors.iter().all(|label| colors.iter().filter(|other| *other == label).count() == 1));
+    }
+
+    #[test]
+    fn many_generated_region_colors_are_distinct() {
+        let labels: Vec<i32> = (0..2000).collect();
+        let annot = FsAnnot::from_label_map(&labels, None).unwrap();
+        let mut colors: Vec<i32> = annot.colortable.regions.iter().map(|region| region.label).collect();
+        colors.sort_unstable();
+        colors.dedup();
+        assert_eq!(2000, colors.len());
+    }
+
+    #[test]
+    fn label_names_and_colors_can_be_taken_from_a_color_lut() {
+        let lut = ColorLut::from_bytes(b"0 Unknown 0 0 0 0\n5 Cluster-A 255 0 0 0\n").unwrap();
+        let annot = FsAnnot::from_label_map(&[5, 5, 6], Some(&lut)).unwrap();
+        assert_eq!(vec!["Cluster-A", "label6"], annot.regions());
+        assert_eq!(255, annot.colortable.regions[0].r);
+
+        let duplicate_colors = ColorLut::from_bytes(b"1 A 255 0 0 0\n2 B 255 0 0 0\n").unwrap();
+        assert!(matches!(FsAnnot::from_label_map(&[1, 2], Some(&duplicate_colors)), Err(NeuroformatsError::InvalidLabelMap(_))));
+    }
+
+    #[test]
+    fn the_generated_region_colors_depend_on_the_seed() {
+        let labels = [1, 2, 3];
+        let colors = |annot: FsAnnot| annot.colortable.regions.iter().map(|region| region.label).collect::<Vec<i32>>();
+        assert_eq!(colors(FsAnnot::from_label_map(&labels, None).unwrap()), colors(FsAnnot::from_label_map_with_seed(&labels, None, 0).unwrap()));
+        assert_ne!(colors(FsAnnot::from_label_map(&labels, None).unwrap()), colors(FsAnnot::from_label_map_with_seed(&labels, None, 1).unwrap()));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn an_annot_can_be_converted_to_a_label_map() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let labels = annot.to_label_map();
+        let vertex_regions = annot.vertex_regions();
+        let converted = FsAnnot::from_label_map(&labels, None).unwrap();
+        assert_eq!(labels, converted.to_label_map());
+        assert_eq!(vertex_regions.iter().filter(|name| *name == "insula").count(), labels.iter().filter(|label| **label == 35).count());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn label_maps_can_be_written_and_reread() {
+        let dir = tempdir().unwrap();
+        let labels = vec![2, 0, -1, 1000000, 2];
+        for file_name in ["lh.clusters.mgh", "lh.clusters.mgz", "lh.clusters.csv"] {
+            let path = dir.path().join(file_name);
+            write_label_map(&path, &labels).unwrap();
+            assert_eq!(labels, read_label_map(&path).unwrap());
+        }
+
+        assert_eq!(vec![1, 2], label_map_from_csv(&b"cluster\n1\n\n2\n"[..]).unwrap());
+        assert!(matches!(label_map_from_csv(&b"1\nx\n"[..]), Err(NeuroformatsError::InvalidLabelMap(_))));
+
+        let header = FsMghHeader { dim1len: 2, dim2len: 1, dim3len: 1, dim4len: 1, ..FsMghHeader::default() };
+        assert_eq!(vec![4, -2], label_map_from_mgh(&FsMgh::from_f32_values(&header, vec![4.0, -2.0]).unwrap()).unwrap());
+        assert!(matches!(label_map_from_mgh(&FsMgh::from_f32_values(&header, vec![4.5, 0.0]).unwrap()), Err(NeuroformatsError::InvalidLabelMap(_))));
+    }
+}
@@ -2,10 +2,16 @@
 //!
 //! The focus of this package is on reading surface-based brain morphometry data as produced from MRI images by FreeSurfer, CAT12 and similar software packages.
 
+// The `quick_error!` macro which defines `NeuroformatsError` needs more than the default recursion limit for its many variants.
+#![recursion_limit = "256"]
+
 
 #[cfg(test)]
 extern crate approx;
 
+#[cfg(test)]
+mod test_meshes;
+
 pub mod util;
 pub mod error;
 pub mod fs_curv;
@@ -13,11 +19,86 @@ pub mod fs_surface;
 pub mod fs_label;
 pub mod fs_annot;
 pub mod fs_mgh;
+pub mod spatial;
+pub mod bvh;
+pub mod raycast;
+pub mod resample;
+pub mod sphere;
+pub mod projection;
+#[cfg(feature = "fs")]
+pub mod subject;
+#[cfg(feature = "fs")]
+pub mod group;
+#[cfg(feature = "fs")]
+pub mod bids;
+pub mod vertexwise;
+pub mod surfdata;
+pub mod overlay;
+pub mod roi;
+pub mod transforms;
+pub mod coord_space;
+pub mod hemi;
+pub mod curvature;
+pub mod gradient;
+pub mod thickness;
+pub mod morphometry;
+pub mod topology;
+pub mod decimate;
+pub mod subdivision;
+pub mod standard_space;
+pub mod weld;
+pub mod reorder;
+pub mod orientation;
+pub mod surf_events;
+pub mod inflate;
+pub mod smoothing;
+pub mod laplace;
+pub mod hull;
+pub mod shape;
+pub mod formats;
+pub mod gltf;
+pub mod vtk;
+pub mod x3d;
+pub mod ply;
+pub mod tables;
+pub mod describe;
+pub mod colormap;
+pub mod slice;
+pub mod volume_stats;
+pub mod color_lut;
+pub mod segmentation;
+pub mod isosurface;
+pub mod crop;
+pub mod frames;
+pub mod parcellation;
+pub mod atlas;
+pub mod label_map;
+pub mod sampling;
+#[cfg(feature = "fs")]
+pub mod qc;
+#[cfg(feature = "fs")]
+pub mod convert;
+#[cfg(any(feature = "nalgebra", feature = "glam"))]
+pub mod interop;
 
 
-pub use fs_curv::{FsCurvHeader, FsCurv, read_curv, write_curv};
-pub use fs_surface::{FsSurfaceHeader, FsSurface, BrainMesh, read_surf, coord_center, coord_extrema, write_surf};
-pub use fs_label::{FsLabel, read_label, write_label};
-pub use fs_annot::{FsAnnot, FsAnnotColortable, read_annot};
-pub use fs_mgh::{FsMgh, FsMghHeader, FsMghData, read_mgh, write_mgh, MRI_UCHAR, MRI_INT, MRI_FLOAT, MRI_SHORT};
-pub use util::{vec32minmax};
+pub use fs_curv::{FsCurvHeader, FsCurv};
+pub use fs_surface::{FsSurfaceHeader, FsSurface, BrainMesh, coord_center, coord_extrema};
+pub use fs_label::{FsLabel, apply_cortex_mask};
+pub use fs_annot::{FsAnnot, FsAnnotColortable, FsAnnotHeaderInfo};
+pub use fs_mgh::{FsMgh, FsMghHeader, FsMghData, FsMghFooter, FsMghTag, MRI_UCHAR, MRI_INT, MRI_FLOAT, MRI_SHORT, MRI_LONG, MRI_TENSOR};
+#[cfg(feature = "fs")]
+pub use fs_curv::{read_curv, read_curv_header, write_curv};
+#[cfg(feature = "fs")]
+pub use fs_surface::{read_surf, read_surf_header, write_surf};
+#[cfg(feature = "fs")]
+pub use fs_label::{read_label, write_label};
+#[cfg(feature = "fs")]
+pub use fs_annot::{read_annot, read_annot_header_info, write_annot};
+#[cfg(feature = "fs")]
+pub use fs_mgh::{read_mgh, read_mgh_header, write_mgh};
+#[cfg(feature = "fs")]
+pub use color_lut::read_color_lut;
+pub use color_lut::{ColorLut, ColorLutEntry};
+pub use spatial::{VertexIndex};
+pub use util::{vec32minmax, ByteOrder, WriteOptions};
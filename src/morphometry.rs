@@ -0,0 +1,156 @@
+//! Functions for computing per-vertex surface area and cortical volume from brain meshes.
+//!
+//! These correspond to the `?h.area` and `?h.volume` files computed by FreeSurfer, so they can be regenerated from the
+//! meshes alone, e.g., for surfaces produced by other software. The results have one value per vertex and can be written
+//! in curv format with [`crate::FsCurv::from_data`] and [`crate::write_curv`].
+
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::{vec3_sub, vec3_dot, vec3_cross, vec3_norm};
+
+
+impl BrainMesh {
+    /// Compute the area of each face of the mesh.
+    pub fn face_areas(&self) -> Vec<f32> {
+        self.faces.chunks_exact(3).map(|f| {
+            let p0 = self.vertex_coords(f[0] as usize);
+            0.5 * vec3_norm(vec3_cross(vec3_sub(self.vertex_coords(f[1] as usize), p0), vec3_sub(self.vertex_coords(f[2] as usize), p0)))
+        }).collect()
+    }
+
+
+    /// Compute the total surface area of the mesh.
+    pub fn total_area(&self) -> f32 {
+        self.face_areas().iter().sum()
+    }
+
+
+    /// Compute the surface area assigned to each vertex, i.e., one third of the area of all faces the vertex is part of.
+    ///
+    /// The values sum up to the total surface area of the mesh. For the white surface, this is similar to the `?h.area` file of FreeSurfer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let area = white.mesh.vertex_areas();
+    /// neuroformats::write_curv("/path/to/subjects_dir/subject1/surf/lh.area.computed", &neuroformats::FsCurv::from_data(area));
+    /// ```
+    pub fn vertex_areas(&self) -> Vec<f32> {
+        let mut areas = vec![0.0; self.num_vertices()];
+        for (face, area) in self.faces.chunks_exact(3).zip(self.face_areas()) {
+            for vidx in face {
+                areas[*vidx as usize] += area / 3.0;
+            }
+        }
+        areas
+    }
+}
+
+
+/// Compute the per-vertex cortical volume enclosed between the white and pial surfaces of a hemisphere.
+///
+/// For each face, the volume of the wedge (truncated prism) between the face on the white surface and the corresponding face on the pial
+/// surface is computed by splitting it into 3 tetrahedra, and one third of it is assigned to each vertex of the face. This is similar to the
+/// `?h.volume` file of FreeSurfer. The values sum up to the total cortical volume of the hemisphere.
+///
+/// The surfaces must have the same number of vertices and the same faces, and vertex `i` of the white surface must correspond to vertex `i` of the pial surface,
+/// which is the case for the surfaces of a hemisphere produced by FreeSurfer. Returns [`NeuroformatsError::VertexCountMismatch`],
+/// [`NeuroformatsError::FaceCountMismatch`] or [`NeuroformatsError::FaceMismatch`] otherwise.
+///
+/// # Examples
+///
+/// ```no_run
+/// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let pial = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+/// let volume = neuroformats::morphometry::compute_volume(&white.mesh, &pial.mesh).unwrap();
+/// let total_volume: f32 = volume.iter().sum();
+/// ```
+pub fn compute_volume(white: &BrainMesh, pial: &BrainMesh) -> Result<Vec<f32>> {
+    if pial.num_vertices() != white.num_vertices() {
+        return Err(NeuroformatsError::VertexCountMismatch(white.num_vertices(), pial.num_vertices()));
+    }
+    if pial.num_faces() != white.num_faces() {
+        return Err(NeuroformatsError::FaceCountMismatch(white.num_faces(), pial.num_faces()));
+    }
+    if pial.faces != white.faces {
+        let face = white.faces.chunks(3).zip(pial.faces.chunks(3)).position(|(w, p)| w != p).unwrap_or(white.num_faces());
+        return Err(NeuroformatsError::FaceMismatch(face));
+    }
+
+    let mut volumes = vec![0.0; white.num_vertices()];
+    for face in white.faces.chunks_exact(3) {
+        let w: Vec<[f32; 3]> = face.iter().map(|vidx| white.vertex_coords(*vidx as usize)).collect();
+        let p: Vec<[f32; 3]> = face.iter().map(|vidx| pial.vertex_coords(*vidx as usize)).collect();
+        let volume = tetrahedron_volume(w[0], w[1], w[2], p[0]) + tetrahedron_volume(w[1], w[2], p[0], p[1]) + tetrahedron_volume(w[2], p[0], p[1], p[2]);
+        for vidx in face {
+            volumes[*vidx as usize] += volume / 3.0;
+        }
+    }
+    Ok(volumes)
+}
+
+
+/// Compute the volume of the tetrahedron with the given corners.
+fn tetrahedron_volume(a: [f32; 3], b: [f32; 3], c: [f32; 3], d: [f32; 3]) -> f32 {
+    vec3_dot(vec3_sub(b, a), vec3_cross(vec3_sub(c, a), vec3_sub(d, a))).abs() / 6.0
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    #[cfg(feature = "fs")]
+    use crate::read_surf;
+
+    fn unit_square() -> BrainMesh {
+        BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2, 0, 2, 3] }
+    }
+
+    #[test]
+    fn the_area_of_faces_and_vertices_can_be_computed() {
+        let mesh = unit_square();
+        assert_eq!(vec![0.5, 0.5], mesh.face_areas());
+        assert_eq!(1.0, mesh.total_area());
+        let areas = mesh.vertex_areas();
+        assert_abs_diff_eq!(1.0 / 3.0, areas[0], epsilon = 1e-6);
+        assert_abs_diff_eq!(1.0 / 6.0, areas[1], epsilon = 1e-6);
+        assert_abs_diff_eq!(1.0, areas.iter().sum::<f32>(), epsilon = 1e-6);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_vertex_areas_of_a_brain_surface_sum_up_to_its_total_area() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let areas = surf.mesh.vertex_areas();
+        assert_eq!(149244, areas.len());
+        assert!(areas.iter().all(|a| *a >= 0.0));
+        let total: f64 = areas.iter().map(|a| *a as f64).sum();
+        assert_abs_diff_eq!(surf.mesh.total_area() as f64, total, epsilon = 1.0);
+    }
+
+    #[test]
+    fn the_volume_between_two_surfaces_can_be_computed() {
+        let white = unit_square();
+        let mut pial = unit_square();
+        for vidx in 0..4 {
+            pial.vertices[vidx * 3 + 2] = 2.0;
+        }
+        let volumes = compute_volume(&white, &pial).unwrap();
+        assert_abs_diff_eq!(2.0, volumes.iter().sum::<f32>(), epsilon = 1e-5);
+        assert_abs_diff_eq!(2.0 / 3.0, volumes[0], epsilon = 1e-5);
+
+        // A sheared prism has the same volume.
+        pial.vertices[0] = 0.5;
+        pial.vertices[3] = 1.5;
+        pial.vertices[6] = 1.5;
+        pial.vertices[9] = 0.5;
+        assert_abs_diff_eq!(2.0, compute_volume(&white, &pial).unwrap().iter().sum::<f32>(), epsilon = 1e-5);
+
+        assert!(matches!(compute_volume(&white, &BrainMesh { vertices: vec![0.0; 3], faces: vec![] }), Err(NeuroformatsError::VertexCountMismatch(4, 1))));
+        assert!(matches!(compute_volume(&white, &BrainMesh { vertices: vec![0.0; 12], faces: vec![0, 1, 2] }), Err(NeuroformatsError::FaceCountMismatch(2, 1))));
+        assert!(matches!(compute_volume(&white, &BrainMesh { vertices: vec![0.0; 12], faces: vec![0, 1, 2, 0, 3, 2] }), Err(NeuroformatsError::FaceMismatch(1))));
+    }
+}
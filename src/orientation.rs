@@ -0,0 +1,153 @@
+//! Checks and repair of the face orientation of meshes.
+//!
+//! The winding order of the vertices of a face determines the direction of its normal. FreeSurfer surfaces use a
+//! counter-clockwise order when seen from outside, so all normals point outwards. Meshes from other sources may use the
+//! opposite order, or even a mix of both, which makes faces render black in viewers that cull back faces or light only the front.
+
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::fs_surface::BrainMesh;
+use crate::util::{vec3_dot, vec3_cross};
+
+
+impl BrainMesh {
+    /// Get a copy of the mesh with the winding order of all faces reversed, so that all normals point in the opposite direction.
+    pub fn flip_normals(&self) -> BrainMesh {
+        let faces = self.faces.chunks_exact(3).flat_map(|face| vec![face[0], face[2], face[1]]).collect();
+        BrainMesh { vertices: self.vertices.clone(), faces }
+    }
+
+
+    /// Find the faces whose winding order is inverted relative to the outward direction of the surface.
+    ///
+    /// The orientation is propagated over shared edges from the first face of each connected part of the mesh, so that neighboring
+    /// faces traverse their shared edge in opposite directions. Each part is then oriented so that its signed volume is positive,
+    /// i.e., its normals point outwards. The result is only meaningful for orientable meshes, and the outward direction is only well
+    /// defined for closed parts.
+    ///
+    /// # Return value
+    ///
+    /// The indices of the inverted faces, in ascending order. Empty for correctly oriented FreeSurfer surfaces.
+    pub fn inverted_faces(&self) -> Vec<usize> {
+        self.faces_to_flip().into_iter().enumerate().filter(|(_, flip)| *flip).map(|(face_idx, _)| face_idx).collect()
+    }
+
+
+    /// Get a copy of the mesh in which all faces have a consistent, outward winding order, see [`BrainMesh::inverted_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mesh = neuroformats::BrainMesh::from_obj_file("/path/to/mesh.obj").unwrap();
+    /// let fixed = mesh.ensure_consistent_winding();
+    /// assert!(fixed.inverted_faces().is_empty());
+    /// ```
+    pub fn ensure_consistent_winding(&self) -> BrainMesh {
+        let flip = self.faces_to_flip();
+        let faces = self.faces.chunks_exact(3).zip(flip.iter())
+            .flat_map(|(face, flip)| if *flip { vec![face[0], face[2], face[1]] } else { face.to_vec() })
+            .collect();
+        BrainMesh { vertices: self.vertices.clone(), faces }
+    }
+
+
+    /// Determine for each face whether its winding order has to be reversed to make the mesh consistently oriented outwards.
+    fn faces_to_flip(&self) -> Vec<bool> {
+        let num_faces = self.num_faces();
+        let mut edge_faces: HashMap<(i32, i32), Vec<usize>> = HashMap::with_capacity(self.faces.len());
+        for (face_idx, face) in self.faces.chunks_exact(3).enumerate() {
+            for corner in 0..3 {
+                let (a, b) = (face[corner], face[(corner + 1) % 3]);
+                edge_faces.entry((a.min(b), a.max(b))).or_default().push(face_idx);
+            }
+        }
+
+        let mut flip: Vec<Option<bool>> = vec![None; num_faces];
+        for seed in 0..num_faces {
+            if flip[seed].is_some() {
+                continue;
+            }
+            flip[seed] = Some(false);
+            let mut component = vec![seed];
+            let mut queue = VecDeque::from(vec![seed]);
+            while let Some(face_idx) = queue.pop_front() {
+                let face = self.oriented_face(face_idx, flip[face_idx] == Some(true));
+                for corner in 0..3 {
+                    let (a, b) = (face[corner], face[(corner + 1) % 3]);
+                    for &neighbor in edge_faces[&(a.min(b), a.max(b))].iter() {
+                        if flip[neighbor].is_none() {
+                            // A consistently oriented neighbor traverses the shared edge from b to a.
+                            flip[neighbor] = Some(has_directed_edge(self.oriented_face(neighbor, false), a, b));
+                            component.push(neighbor);
+                            queue.push_back(neighbor);
+                        }
+                    }
+                }
+            }
+
+            let signed_volume: f64 = component.iter().map(|&face_idx| {
+                let face = self.oriented_face(face_idx, flip[face_idx] == Some(true));
+                let (p0, p1, p2) = (self.vertex_coords(face[0] as usize), self.vertex_coords(face[1] as usize), self.vertex_coords(face[2] as usize));
+                vec3_dot(p0, vec3_cross(p1, p2)) as f64
+            }).sum();
+            if signed_volume < 0.0 {
+                for face_idx in component {
+                    flip[face_idx] = flip[face_idx].map(|f| !f);
+                }
+            }
+        }
+        flip.into_iter().map(|f| f == Some(true)).collect()
+    }
+
+
+    /// Get the vertex indices of a face, with the winding order reversed if `flipped` is true.
+    fn oriented_face(&self, face_idx: usize, flipped: bool) -> [i32; 3] {
+        let face = &self.faces[face_idx * 3..face_idx * 3 + 3];
+        if flipped { [face[0], face[2], face[1]] } else { [face[0], face[1], face[2]] }
+    }
+}
+
+
+/// Whether the face traverses the edge from vertex `a` to vertex `b`.
+fn has_directed_edge(face: [i32; 3], a: i32, b: i32) -> bool {
+    (0..3).any(|corner| face[corner] == a && face[(corner + 1) % 3] == b)
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::subdivision::generate_icosphere;
+    #[cfg(feature = "fs")]
+    use crate::read_surf;
+
+    #[test]
+    fn the_normals_of_a_mesh_can_be_flipped() {
+        let ico = generate_icosphere(1);
+        let flipped = ico.flip_normals();
+        assert_eq!(vec![ico.faces[0], ico.faces[2], ico.faces[1]], flipped.faces[0..3].to_vec());
+        assert_eq!(ico, flipped.flip_normals());
+        assert_eq!((0..ico.num_faces()).collect::<Vec<usize>>(), flipped.inverted_faces());
+        assert_eq!(ico, flipped.ensure_consistent_winding());
+    }
+
+    #[test]
+    fn inverted_faces_can_be_detected_and_fixed() {
+        let ico = generate_icosphere(2);
+        assert!(ico.inverted_faces().is_empty());
+
+        let mut mixed = ico.clone();
+        for face_idx in vec![0, 7, 100].into_iter() {
+            mixed.faces.swap(face_idx * 3 + 1, face_idx * 3 + 2);
+        }
+        assert_eq!(vec![0, 7, 100], mixed.inverted_faces());
+        assert_eq!(ico, mixed.ensure_consistent_winding());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_faces_of_a_freesurfer_surface_are_oriented_outwards() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        assert!(surf.mesh.inverted_faces().is_empty());
+    }
+}
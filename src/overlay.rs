@@ -0,0 +1,302 @@
+//! Per-vertex data of any value type, like `f64` statistical maps, `i32` cluster maps and `bool` masks.
+//!
+//! Curv and MGH files store per-vertex data as `f32`, but statistical outputs are often computed as `f64`, cluster and label maps
+//! are integers, and masks are booleans. A [`SurfaceOverlay`] keeps the values in their natural type and converts between the
+//! types with checks, so an overlay is only converted to `f32` when it is written or displayed.
+
+
+use std::fmt::Debug;
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_curv::FsCurv;
+use crate::fs_label::FsLabel;
+use crate::fs_surface::BrainMesh;
+
+
+/// A value type of per-vertex data in a [`SurfaceOverlay`].
+///
+/// Values are converted between types via `f64`, which represents all values of the implementing types exactly.
+pub trait OverlayValue: Copy + PartialEq + Debug + Send + Sync {
+    /// Convert the value to `f64`. Booleans are converted to `1.0` for `true` and `0.0` for `false`.
+    fn to_f64(self) -> f64;
+
+    /// Convert an `f64` value to this type, or return `None` if it cannot be represented, like `0.5` as an integer.
+    fn from_f64(value: f64) -> Option<Self>;
+}
+
+
+impl OverlayValue for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    /// Values are rounded to the nearest `f32`, so this only fails for finite values out of the `f32` range.
+    fn from_f64(value: f64) -> Option<f32> {
+        let converted = value as f32;
+        if converted.is_infinite() && value.is_finite() { None } else { Some(converted) }
+    }
+}
+
+
+impl OverlayValue for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Option<f64> {
+        Some(value)
+    }
+}
+
+
+impl OverlayValue for i32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Option<i32> {
+        if value.fract() == 0.0 && value >= i32::MIN as f64 && value <= i32::MAX as f64 { Some(value as i32) } else { None }
+    }
+}
+
+
+impl OverlayValue for bool {
+    fn to_f64(self) -> f64 {
+        if self { 1.0 } else { 0.0 }
+    }
+
+    fn from_f64(value: f64) -> Option<bool> {
+        if value == 1.0 {
+            Some(true)
+        } else if value == 0.0 {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+
+/// Per-vertex data with one value of type `T` per vertex of a mesh.
+///
+/// Use `SurfaceOverlay<f32>` for data read from curv files, `SurfaceOverlay<f64>` for statistical maps, `SurfaceOverlay<i32>`
+/// for cluster and label maps and `SurfaceOverlay<bool>` for masks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::overlay::SurfaceOverlay;
+/// let thickness: SurfaceOverlay<f32> = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap().into();
+/// let cortex = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.cortex.label").unwrap();
+/// let mask = SurfaceOverlay::from_label(&cortex, thickness.num_vertices());
+/// let thick = thickness.mask(|t| t > 3.0).and(&mask).unwrap();
+/// println!("{} cortical vertices are thicker than 3 mm.", thick.count());
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SurfaceOverlay<T> {
+    /// The values, one per vertex.
+    pub data: Vec<T>,
+}
+
+
+impl<T: OverlayValue> SurfaceOverlay<T> {
+    /// Create an overlay from per-vertex values.
+    pub fn new(data: Vec<T>) -> SurfaceOverlay<T> {
+        SurfaceOverlay { data }
+    }
+
+
+    /// Get the number of vertices, i.e., values.
+    pub fn num_vertices(&self) -> usize {
+        self.data.len()
+    }
+
+
+    /// Check that this overlay has one value per vertex of the mesh, and return [`NeuroformatsError::VertexCountMismatch`] otherwise.
+    pub fn check_mesh(&self, mesh: &BrainMesh) -> Result<()> {
+        if self.data.len() != mesh.num_vertices() {
+            return Err(NeuroformatsError::VertexCountMismatch(mesh.num_vertices(), self.data.len()));
+        }
+        Ok(())
+    }
+
+
+    /// Apply a function to all values, e.g., to compute a derived overlay of another type.
+    pub fn map<U: OverlayValue, F: Fn(T) -> U>(&self, f: F) -> SurfaceOverlay<U> {
+        SurfaceOverlay { data: self.data.iter().map(|v| f(*v)).collect() }
+    }
+
+
+    /// Convert the values to another type, checking that each value can be represented in it.
+    ///
+    /// # Return value
+    ///
+    /// The converted overlay, or [`NeuroformatsError::InvalidOverlayValue`] for the first value which cannot be represented, like a
+    /// non-integer value when converting to `i32`, or a value other than `0` and `1` when converting to `bool`. Use [`SurfaceOverlay::mask`]
+    /// to compute masks from arbitrary values instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use neuroformats::overlay::SurfaceOverlay;
+    /// let clusters = SurfaceOverlay::new(vec![0.0f32, 1.0, 2.0]);
+    /// assert_eq!(vec![0, 1, 2], clusters.convert::<i32>().unwrap().data);
+    /// assert!(SurfaceOverlay::new(vec![0.5f32]).convert::<i32>().is_err());
+    /// ```
+    pub fn convert<U: OverlayValue>(&self) -> Result<SurfaceOverlay<U>> {
+        let data = self.data.iter().enumerate().map(|(idx, v)| {
+            U::from_f64(v.to_f64()).ok_or_else(|| NeuroformatsError::InvalidOverlayValue(idx, v.to_f64(), std::any::type_name::<U>()))
+        }).collect::<Result<Vec<U>>>()?;
+        Ok(SurfaceOverlay { data })
+    }
+
+
+    /// Convert the values to `f32`, e.g., to write them to curv or MGH files or to map them to colors. This never fails, but
+    /// `f64` values and `i32` values with an absolute value above 2^24 are rounded to the nearest `f32`.
+    pub fn to_f32(&self) -> SurfaceOverlay<f32> {
+        self.map(|v| v.to_f64() as f32)
+    }
+
+
+    /// Convert the values to `f64`, which is exact for all value types.
+    pub fn to_f64(&self) -> SurfaceOverlay<f64> {
+        self.map(|v| v.to_f64())
+    }
+
+
+    /// Convert the values to `f32` like [`SurfaceOverlay::to_f32`], and create per-vertex data which can be written to a curv file.
+    pub fn to_curv(&self) -> FsCurv {
+        FsCurv::from_data(self.to_f32().data)
+    }
+
+
+    /// Compute a mask of the vertices whose value fulfills a predicate, like all vertices with a p value below `0.05`.
+    pub fn mask<F: Fn(T) -> bool>(&self, predicate: F) -> SurfaceOverlay<bool> {
+        self.map(predicate)
+    }
+}
+
+
+impl SurfaceOverlay<bool> {
+    /// Create a mask of the vertices in a label, e.g., to mask the cortex with `label/?h.cortex.label`.
+    ///
+    /// See [`FsLabel::is_surface_vertex_in_label`] for details.
+    pub fn from_label(label: &FsLabel, num_vertices: usize) -> SurfaceOverlay<bool> {
+        SurfaceOverlay { data: label.is_surface_vertex_in_label(num_vertices) }
+    }
+
+
+    /// Count the vertices in this mask.
+    pub fn count(&self) -> usize {
+        self.data.iter().filter(|v| **v).count()
+    }
+
+
+    /// Get the indices of the vertices in this mask, in ascending order.
+    pub fn vertices(&self) -> Vec<usize> {
+        self.data.iter().enumerate().filter(|(_, v)| **v).map(|(idx, _)| idx).collect()
+    }
+
+
+    /// Invert this mask.
+    pub fn invert(&self) -> SurfaceOverlay<bool> {
+        self.map(|v| !v)
+    }
+
+
+    /// Compute the intersection of this mask with another one. Returns [`NeuroformatsError::VertexCountMismatch`] if their lengths differ.
+    pub fn and(&self, other: &SurfaceOverlay<bool>) -> Result<SurfaceOverlay<bool>> {
+        self.combine(other, |a, b| a && b)
+    }
+
+
+    /// Compute the union of this mask with another one. Returns [`NeuroformatsError::VertexCountMismatch`] if their lengths differ.
+    pub fn or(&self, other: &SurfaceOverlay<bool>) -> Result<SurfaceOverlay<bool>> {
+        self.combine(other, |a, b| a || b)
+    }
+
+
+    /// Replace the values of an overlay outside of this mask with a fill value, e.g., `f32::NAN` to hide the medial wall.
+    ///
+    /// # Return value
+    ///
+    /// The masked overlay, or [`NeuroformatsError::VertexCountMismatch`] if the length of the overlay differs from the length of this mask.
+    pub fn apply<T: OverlayValue>(&self, overlay: &SurfaceOverlay<T>, fill: T) -> Result<SurfaceOverlay<T>> {
+        if overlay.data.len() != self.data.len() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.data.len(), overlay.data.len()));
+        }
+        Ok(SurfaceOverlay { data: self.data.iter().zip(overlay.data.iter()).map(|(inside, v)| if *inside { *v } else { fill }).collect() })
+    }
+
+
+    /// Combine this mask with another one of the same length element-wise.
+    fn combine<F: Fn(bool, bool) -> bool>(&self, other: &SurfaceOverlay<bool>, f: F) -> Result<SurfaceOverlay<bool>> {
+        if other.data.len() != self.data.len() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.data.len(), other.data.len()));
+        }
+        Ok(SurfaceOverlay { data: self.data.iter().zip(other.data.iter()).map(|(a, b)| f(*a, *b)).collect() })
+    }
+}
+
+
+impl<T: OverlayValue> From<Vec<T>> for SurfaceOverlay<T> {
+    fn from(data: Vec<T>) -> SurfaceOverlay<T> {
+        SurfaceOverlay { data }
+    }
+}
+
+
+impl From<FsCurv> for SurfaceOverlay<f32> {
+    fn from(curv: FsCurv) -> SurfaceOverlay<f32> {
+        SurfaceOverlay { data: curv.data }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs_label::FsLabelVertex;
+
+    #[test]
+    fn overlays_can_be_converted_between_value_types() {
+        let stats = SurfaceOverlay::new(vec![0.0f64, 1.0, -3.0, 0.25]);
+        assert_eq!(vec![0.0f32, 1.0, -3.0, 0.25], stats.to_f32().data);
+        assert_eq!(stats, stats.to_f32().to_f64());
+        assert!(matches!(stats.convert::<i32>(), Err(NeuroformatsError::InvalidOverlayValue(3, v, "i32")) if v == 0.25));
+        assert!(matches!(stats.convert::<bool>(), Err(NeuroformatsError::InvalidOverlayValue(2, _, "bool"))));
+        assert!(SurfaceOverlay::new(vec![1e300f64]).convert::<f32>().is_err());
+        assert!(SurfaceOverlay::new(vec![f64::NAN]).convert::<f32>().unwrap().data[0].is_nan());
+
+        let clusters = SurfaceOverlay::new(vec![0i32, 2, 1]);
+        assert_eq!(vec![0.0f32, 2.0, 1.0], clusters.to_curv().data);
+        assert_eq!(clusters, SurfaceOverlay::from(clusters.to_curv()).convert::<i32>().unwrap());
+        assert_eq!(vec![false, true, true], clusters.mask(|c| c > 0).data);
+        assert_eq!(vec![false, true], SurfaceOverlay::new(vec![0.0f32, 1.0]).convert::<bool>().unwrap().data);
+    }
+
+    #[test]
+    fn masks_can_be_combined_and_applied() {
+        let a = SurfaceOverlay::new(vec![true, true, false, false]);
+        let b = SurfaceOverlay::from(vec![true, false, true, false]);
+        assert_eq!(vec![true, false, false, false], a.and(&b).unwrap().data);
+        assert_eq!(vec![true, true, true, false], a.or(&b).unwrap().data);
+        assert_eq!(vec![false, false, true, true], a.invert().data);
+        assert_eq!((2, vec![0, 1]), (a.count(), a.vertices()));
+
+        let values = SurfaceOverlay::new(vec![1.0f32, 2.0, 3.0, 4.0]);
+        let masked = a.apply(&values, f32::NAN).unwrap();
+        assert_eq!(vec![1.0, 2.0], masked.data[..2].to_vec());
+        assert!(masked.data[2..].iter().all(|v| v.is_nan()));
+        assert!(matches!(a.and(&SurfaceOverlay::new(vec![true])), Err(NeuroformatsError::VertexCountMismatch(4, 1))));
+        assert!(a.apply(&SurfaceOverlay::new(vec![1i32]), 0).is_err());
+
+        let vertex = |index: i32| FsLabelVertex { index, coord1: 0.0, coord2: 0.0, coord3: 0.0, value: 0.0 };
+        let label = FsLabel { vertexes: vec![vertex(1), vertex(3)] };
+        assert_eq!(vec![false, true, false, true], SurfaceOverlay::from_label(&label, 4).data);
+        let mesh = BrainMesh { vertices: vec![0.0; 12], faces: vec![0, 1, 2] };
+        assert!(values.check_mesh(&mesh).is_ok());
+        assert!(SurfaceOverlay::new(vec![1.0f32]).check_mesh(&mesh).is_err());
+    }
+}
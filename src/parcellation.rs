@@ -0,0 +1,286 @@
+//! Spatial relations between the regions of a surface parcellation, like which regions are adjacent on the mesh.
+//!
+//! These are the basis for graph-based analyses of parcellations, and for rendering region boundaries.
+
+
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_annot::FsAnnot;
+use crate::fs_surface::BrainMesh;
+use crate::util::{vec3_norm, vec3_sub};
+
+
+/// Two adjacent regions of a parcellation, see [`FsAnnot::region_adjacency`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionAdjacency {
+    /// The index of the first region in the colortable. It is smaller than `region_b`.
+    pub region_a: usize,
+    /// The index of the second region in the colortable.
+    pub region_b: usize,
+    /// The number of mesh edges which connect a vertex of the first region with a vertex of the second region.
+    pub num_shared_edges: usize,
+    /// The length of the boundary between the regions on the mesh, in the units of the mesh coordinates (mm for FreeSurfer surfaces).
+    pub boundary_length: f32,
+}
+
+
+/// How the border vertices of the regions are colored by [`FsAnnot::vertex_colors_with_borders`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderColoring {
+    /// Draw the border vertices in the given RGB color, e.g., black or white outlines.
+    Color([u8; 3]),
+    /// Scale the RGB values of the region color of the border vertices by the given factor, which is clamped to the range `[0, 1]`.
+    /// A factor of `0.5` draws the borders in a darker shade of the region color.
+    Darken(f32),
+}
+
+
+impl FsAnnot {
+    /// Get the colortable region index of each vertex, or `None` for vertices with a label that does not match any region.
+    ///
+    /// Returns [`NeuroformatsError::VertexCountMismatch`] if the annotation does not have one label per vertex of the mesh.
+    fn mesh_vertex_regions(&self, mesh: &BrainMesh) -> Result<Vec<Option<usize>>> {
+        if self.vertex_labels.len() != mesh.num_vertices() {
+            return Err(NeuroformatsError::VertexCountMismatch(mesh.num_vertices(), self.vertex_labels.len()));
+        }
+        let lookup = self.region_lookup();
+        Ok(self.vertex_labels.iter().map(|label| lookup.region_index(*label)).collect())
+    }
+
+
+    /// Compute which regions of the parcellation are adjacent on the mesh, i.e., share mesh edges.
+    ///
+    /// The boundary between two regions runs through the faces which contain vertices of both regions. Within such a face, it is modelled
+    /// as the line between the midpoints of the two edges crossing the boundary, or, for faces with vertices of three regions, as the lines
+    /// from the face centroid to the midpoints of the edges. Vertices with a label that does not match any region are ignored.
+    ///
+    /// # Return value
+    ///
+    /// The pairs of adjacent regions, sorted by region indices. Returns [`NeuroformatsError::VertexCountMismatch`] if the annotation does
+    /// not have one label per vertex of the mesh.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// for adj in annot.region_adjacency(&surf.mesh).unwrap() {
+    ///     println!("{} - {}: {} mm", annot.colortable.regions[adj.region_a].name, annot.colortable.regions[adj.region_b].name, adj.boundary_length);
+    /// }
+    /// ```
+    pub fn region_adjacency(&self, mesh: &BrainMesh) -> Result<Vec<RegionAdjacency>> {
+        let vertex_regions = self.mesh_vertex_regions(mesh)?;
+        let region_pair = |a: usize, b: usize| -> Option<(usize, usize)> {
+            match (vertex_regions[a], vertex_regions[b]) {
+                (Some(ra), Some(rb)) if ra != rb => Some((ra.min(rb), ra.max(rb))),
+                _ => None,
+            }
+        };
+
+        let mut shared_edges: HashSet<(usize, usize)> = HashSet::new();
+        let mut adjacency: HashMap<(usize, usize), (usize, f32)> = HashMap::new();
+        for face_idx in 0..mesh.num_faces() {
+            let face = mesh.face(face_idx);
+            let coords = face.map(|v| mesh.vertex_coords(v));
+            let mut cut_edges: Vec<((usize, usize), [f32; 3])> = Vec::with_capacity(3);
+            for corner in 0..3 {
+                let (a, b) = (face[corner], face[(corner + 1) % 3]);
+                if let Some(pair) = region_pair(a, b) {
+                    if shared_edges.insert((a.min(b), a.max(b))) {
+                        adjacency.entry(pair).or_insert((0, 0.0)).0 += 1;
+                    }
+                    let midpoint = [0, 1, 2].map(|i| (coords[corner][i] + coords[(corner + 1) % 3][i]) / 2.0);
+                    cut_edges.push((pair, midpoint));
+                }
+            }
+            if cut_edges.len() == 2 {
+                let length = vec3_norm(vec3_sub(cut_edges[0].1, cut_edges[1].1));
+                adjacency.entry(cut_edges[0].0).or_insert((0, 0.0)).1 += length;
+            } else if cut_edges.len() == 3 {
+                let centroid = [0, 1, 2].map(|i| (coords[0][i] + coords[1][i] + coords[2][i]) / 3.0);
+                for (pair, midpoint) in cut_edges {
+                    adjacency.entry(pair).or_insert((0, 0.0)).1 += vec3_norm(vec3_sub(midpoint, centroid));
+                }
+            }
+        }
+
+        let mut result: Vec<RegionAdjacency> = adjacency.into_iter()
+            .map(|((region_a, region_b), (num_shared_edges, boundary_length))| RegionAdjacency { region_a, region_b, num_shared_edges, boundary_length })
+            .collect();
+        result.sort_unstable_by_key(|adj| (adj.region_a, adj.region_b));
+        Ok(result)
+    }
+
+
+    /// Find the border vertices of each region of the parcellation, i.e., the vertices with at least one neighbor that has a different label.
+    ///
+    /// Drawing the border vertices in a distinct color renders the outlines of the regions over other data, like FreeView does for annotations.
+    ///
+    /// # Return value
+    ///
+    /// A vector with one entry per colortable region, holding the indices of the border vertices of the region in ascending order.
+    /// Vertices with a label that does not match any region are not included. Returns [`NeuroformatsError::VertexCountMismatch`] if the
+    /// annotation does not have one label per vertex of the mesh.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let borders = annot.region_borders(&surf.mesh).unwrap();
+    /// let mut colors = annot.vertex_colors(false, 0);
+    /// for v in borders.iter().flatten() {
+    ///     colors[v * 3..v * 3 + 3].copy_from_slice(&[255, 255, 255]);
+    /// }
+    /// ```
+    pub fn region_borders(&self, mesh: &BrainMesh) -> Result<Vec<Vec<usize>>> {
+        let vertex_regions = self.mesh_vertex_regions(mesh)?;
+        let mut borders: Vec<Vec<usize>> = vec![Vec::new(); self.colortable.regions.len()];
+        for (v, neighbors) in mesh.vertex_neighbors().iter().enumerate() {
+            if let Some(region) = vertex_regions[v] {
+                if neighbors.iter().any(|n| self.vertex_labels[*n] != self.vertex_labels[v]) {
+                    borders[region].push(v);
+                }
+            }
+        }
+        Ok(borders)
+    }
+
+
+    /// Get the vertex colors as u8 RGB(A) values like [`FsAnnot::vertex_colors`], with the region borders in a different color.
+    ///
+    /// The border vertices are those found by [`FsAnnot::region_borders`], so each border is one vertex wide on each side of the
+    /// boundary between two regions. Exported meshes then show crisp region outlines, like the atlas figures in publications. The alpha
+    /// values of border vertices are not changed.
+    ///
+    /// # Parameters
+    ///
+    /// * `mesh`: the mesh the annotation belongs to, used to find the neighbors of the vertices.
+    /// * `alpha`: whether to return the alpha channel value.
+    /// * `unmatched_region_index`: the region index to use for the colors of vertices with a label that does not match any region label, see [`FsAnnot::vertex_colors`].
+    /// * `border`: how to color the border vertices.
+    ///
+    /// # Return value
+    ///
+    /// The colors, with 3 or 4 values per vertex. Returns [`NeuroformatsError::VertexCountMismatch`] if the annotation does not have one
+    /// label per vertex of the mesh.
+    ///
+    /// # Panics
+    ///
+    /// * If the `unmatched_region_index` is out of range for this FsAnnot, see [`FsAnnot::num_regions`] to check before calling this function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::parcellation::BorderColoring;
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let colors = annot.vertex_colors_with_borders(&surf.mesh, false, 0, BorderColoring::Color([0, 0, 0])).unwrap();
+    /// assert_eq!(colors.len(), surf.mesh.num_vertices() * 3);
+    /// ```
+    pub fn vertex_colors_with_borders(&self, mesh: &BrainMesh, alpha: bool, unmatched_region_index: usize, border: BorderColoring) -> Result<Vec<u8>> {
+        let borders = self.region_borders(mesh)?;
+        let mut colors = self.vertex_colors(alpha, unmatched_region_index);
+        let channels = if alpha { 4 } else { 3 };
+        for v in borders.iter().flatten() {
+            let rgb = &mut colors[v * channels..v * channels + 3];
+            match border {
+                BorderColoring::Color(color) => rgb.copy_from_slice(&color),
+                BorderColoring::Darken(factor) => {
+                    let factor = factor.clamp(0.0, 1.0);
+                    rgb.iter_mut().for_each(|c| *c = (*c as f32 * factor).round() as u8);
+                }
+            }
+        }
+        Ok(colors)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs_annot::{FsAnnotColortable, FsAnnotColorRegion};
+    #[cfg(feature = "fs")]
+    use crate::{read_annot, read_surf};
+
+    /// A unit square made of two triangles, with the left vertices in region `0` and the right vertices in region `1`.
+    fn square_with_two_regions() -> (BrainMesh, FsAnnot) {
+        let mesh = BrainMesh {
+            vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+            faces: vec![0, 1, 2, 0, 2, 3],
+        };
+        let region = |id: i32, name: &str, r: i32| FsAnnotColorRegion { id, name: name.to_string(), r, g: 0, b: 0, a: 0, label: r };
+        let colortable = FsAnnotColortable { regions: vec![region(0, "left", 10), region(1, "right", 20)], ..Default::default() };
+        (mesh, FsAnnot::new(vec![0, 1, 2, 3], vec![10, 20, 20, 10], colortable))
+    }
+
+    #[test]
+    fn the_adjacency_of_regions_can_be_computed() {
+        let (mesh, annot) = square_with_two_regions();
+        let adjacency = annot.region_adjacency(&mesh).unwrap();
+        assert_eq!(1, adjacency.len());
+        assert_eq!((0, 1, 3), (adjacency[0].region_a, adjacency[0].region_b, adjacency[0].num_shared_edges));
+        assert!((adjacency[0].boundary_length - 1.0).abs() < 1e-6);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_adjacency_of_the_aparc_regions_can_be_computed() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let adjacency = annot.region_adjacency(&surf.mesh).unwrap();
+
+        assert!(!adjacency.is_empty());
+        assert!(adjacency.iter().all(|adj| adj.region_a < adj.region_b && adj.num_shared_edges > 0 && adj.boundary_length > 0.0));
+        assert!(adjacency.windows(2).all(|w| (w[0].region_a, w[0].region_b) < (w[1].region_a, w[1].region_b)));
+        let index = |name: &str| annot.colortable.regions.iter().position(|r| r.name == name).unwrap();
+        assert!(adjacency.iter().any(|adj| (adj.region_a, adj.region_b) == (index("bankssts"), index("superiortemporal"))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_borders_of_regions_can_be_computed() {
+        let (mesh, annot) = square_with_two_regions();
+        assert_eq!(vec![vec![0, 3], vec![1, 2]], annot.region_borders(&mesh).unwrap());
+
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let borders = annot.region_borders(&surf.mesh).unwrap();
+        assert_eq!(36, borders.len());
+        let bankssts = annot.region_vertices(String::from("bankssts"));
+        assert!(!borders[1].is_empty() && borders[1].len() < bankssts.len());
+        assert!(borders[1].iter().all(|v| bankssts.contains(v)));
+    }
+
+    #[test]
+    fn the_region_borders_can_be_colored() {
+        let mesh = BrainMesh {
+            vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 2.0, 1.0, 0.0],
+            faces: vec![0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4],
+        };
+        let region = |id: i32, r: i32| FsAnnotColorRegion { id, name: format!("region{}", id), r, g: 100, b: 200, a: 7, label: r };
+        let colortable = FsAnnotColortable { regions: vec![region(0, 10), region(1, 20)], ..Default::default() };
+        let annot = FsAnnot::new((0..6).collect(), vec![10, 10, 20, 10, 10, 20], colortable);
+
+        let colors = annot.vertex_colors_with_borders(&mesh, false, 0, BorderColoring::Color([0, 0, 0])).unwrap();
+        assert_eq!(vec![10, 100, 200, 0, 0, 0, 0, 0, 0, 10, 100, 200, 0, 0, 0, 0, 0, 0], colors);
+
+        let colors = annot.vertex_colors_with_borders(&mesh, true, 0, BorderColoring::Darken(0.5)).unwrap();
+        assert_eq!(&[10, 100, 200, 7], &colors[0..4]);
+        assert_eq!(&[5, 50, 100, 7], &colors[4..8]);
+        assert_eq!(&[10, 50, 100, 7], &colors[8..12]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_adjacency_requires_matching_vertex_counts() {
+        let (mesh, _) = square_with_two_regions();
+        let annot = read_annot("resources/annot/tiny.annot").unwrap();
+        assert!(matches!(annot.region_adjacency(&mesh), Err(NeuroformatsError::VertexCountMismatch(4, 5))));
+        assert!(annot.region_borders(&mesh).is_err());
+        assert!(annot.vertex_colors_with_borders(&mesh, false, 0, BorderColoring::Darken(0.5)).is_err());
+    }
+}
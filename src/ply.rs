@@ -0,0 +1,101 @@
+//! Export of brain meshes to the binary Stanford PLY format.
+//!
+//! PLY files can be opened in MeshLab, CloudCompare and Blender, and are a common format for publishing meshes in datasets.
+
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_surface::BrainMesh;
+
+
+impl BrainMesh {
+    /// Export this mesh in the binary little endian PLY format, with optional per-vertex colors.
+    ///
+    /// The vertex coordinates are written as `float` properties `x`, `y` and `z`, the colors as `uchar` properties `red`, `green`
+    /// and `blue`, and the faces as lists of `int` vertex indices.
+    ///
+    /// # Parameters
+    ///
+    /// * `colors`: optional RGB colors for the vertices, 3 values per vertex, like the output of [`crate::FsAnnot::vertex_colors`] with `alpha` set to `false`
+    ///   or of [`crate::colormap::values_to_colors`].
+    ///
+    /// # Return value
+    ///
+    /// The PLY file contents. Returns [`NeuroformatsError::ColorCountMismatch`] if `colors` is given and does not contain 3 values per vertex,
+    /// and an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let ply = surf.mesh.to_ply(Some(&annot.vertex_colors(false, 0))).unwrap();
+    /// std::fs::write("/tmp/lh_aparc.ply", ply).expect("Unable to write PLY file");
+    /// ```
+    pub fn to_ply(&self, colors: Option<&[u8]>) -> Result<Vec<u8>> {
+        self.validate_faces()?;
+        if let Some(colors) = colors {
+            if colors.len() != self.vertices.len() {
+                return Err(NeuroformatsError::ColorCountMismatch(self.vertices.len(), colors.len()));
+            }
+        }
+        let mut header = format!("ply\nformat binary_little_endian 1.0\ncomment neuroformats {}\n", env!("CARGO_PKG_VERSION"));
+        header.push_str(&format!("element vertex {}\nproperty float x\nproperty float y\nproperty float z\n", self.num_vertices()));
+        if colors.is_some() {
+            header.push_str("property uchar red\nproperty uchar green\nproperty uchar blue\n");
+        }
+        header.push_str(&format!("element face {}\nproperty list uchar int vertex_indices\nend_header\n", self.num_faces()));
+
+        let vertex_size = if colors.is_some() { 15 } else { 12 };
+        let mut ply = Vec::with_capacity(header.len() + self.num_vertices() * vertex_size + self.num_faces() * 13);
+        ply.extend_from_slice(header.as_bytes());
+        for (idx, v) in self.vertices.chunks_exact(3).enumerate() {
+            for coord in v {
+                ply.extend_from_slice(&coord.to_le_bytes());
+            }
+            if let Some(colors) = colors {
+                ply.extend_from_slice(&colors[idx * 3..idx * 3 + 3]);
+            }
+        }
+        for f in self.faces.chunks_exact(3) {
+            ply.push(3);
+            for v in f {
+                ply.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        Ok(ply)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_mesh_can_be_exported_to_ply() {
+        let mesh = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2] };
+        let ply = mesh.to_ply(Some(&[255, 0, 0, 0, 255, 0, 0, 0, 51])).unwrap();
+        let header_end = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+        let header = std::str::from_utf8(&ply[..header_end]).unwrap();
+
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert!(header.contains("element vertex 3\n") && header.contains("property uchar red\n") && header.contains("element face 1\n"));
+        let body = &ply[header_end..];
+        assert_eq!(3 * 15 + 13, body.len());
+        assert_eq!(1.0f32.to_le_bytes(), body[15..19]);
+        assert_eq!([255, 0, 0], body[12..15]);
+        assert_eq!([3, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0], body[45..]);
+
+        let ply = mesh.to_ply(None).unwrap();
+        assert!(ply.windows(14).all(|w| w != b"property uchar"));
+        assert!(ply.ends_with(&[3, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]));
+    }
+
+    #[test]
+    fn invalid_colors_and_faces_are_rejected_in_ply_export() {
+        let mesh = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2] };
+        assert!(matches!(mesh.to_ply(Some(&[0, 0, 0])), Err(NeuroformatsError::ColorCountMismatch(9, 3))));
+        let mesh = BrainMesh { faces: vec![0, 1, 3], ..mesh };
+        assert!(mesh.to_ply(None).is_err());
+    }
+}
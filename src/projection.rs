@@ -0,0 +1,476 @@
+//! Functions for projecting data between brain surfaces and volumes.
+//!
+//! Surface coordinates of FreeSurfer meshes are given in surface RAS (tkregister) space, so all functions
+//! in this module use the [`FsMghHeader::ras2vox_tkr`] matrix of a volume of the same subject to map vertices to voxels.
+
+
+use std::collections::HashMap;
+
+use ndarray::{Array, Array2, Array4, Dim};
+
+use crate::fs_surface::BrainMesh;
+use crate::fs_mgh::{FsMgh, FsMghHeader};
+use crate::error::{NeuroformatsError, Result};
+
+
+/// Determines how the value of a vertex is distributed over the voxels close to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionWeighting {
+    /// Assign the full value to the voxel containing the vertex.
+    NearestVoxel,
+    /// Distribute the value over the 8 voxels surrounding the vertex, using trilinear weights. This accounts for partial volume effects.
+    PartialVolume,
+}
+
+
+/// Project per-vertex data of a surface into a volume.
+///
+/// This is the inverse of sampling a volume at the vertices of a surface. Each voxel receives the weighted
+/// mean of the values of all vertices projected into it. Voxels which do not receive any value are set to `0.0`.
+///
+/// # Parameters
+///
+/// * `mesh`: the surface mesh, typically `?h.white` or `?h.pial`.
+/// * `data`: the per-vertex data, one value per vertex of `mesh`.
+/// * `template`: the header of a volume of the same subject, e.g., `orig.mgz`. Defines the dimensions and RAS information of the output volume. Must contain valid RAS information.
+/// * `weighting`: how vertex values are distributed over voxels, see [`ProjectionWeighting`].
+///
+/// # Return value
+///
+/// A single-frame volume with `MRI_FLOAT` data.
+///
+/// # Examples
+///
+/// ```no_run
+/// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let orig = neuroformats::FsMghHeader::from_file("/path/to/subjects_dir/subject1/mri/orig.mgz").unwrap();
+/// let vol = neuroformats::projection::surf_to_vol(&white.mesh, &thickness.data, &orig, neuroformats::projection::ProjectionWeighting::PartialVolume).unwrap();
+/// neuroformats::write_mgh("/tmp/lh.thickness.mgz", &vol).unwrap();
+/// ```
+pub fn surf_to_vol(mesh: &BrainMesh, data: &[f32], template: &FsMghHeader, weighting: ProjectionWeighting) -> Result<FsMgh> {
+    ribbon_to_vol(mesh, mesh, data, template, 1, weighting)
+}
+
+
+/// Project per-vertex data into the cortical ribbon of a volume.
+///
+/// Like [`surf_to_vol`], but each vertex value is projected at `num_steps` points evenly distributed
+/// along the line from the white surface vertex to the corresponding pial surface vertex. This fills
+/// the cortical ribbon instead of only the voxels at a single surface.
+///
+/// # Parameters
+///
+/// * `white`: the white surface mesh.
+/// * `pial`: the pial surface mesh. Must have the same vertex count as `white`.
+/// * `num_steps`: the number of sampling points along each white-to-pial line. A value of `1` projects at the white surface only.
+///
+/// See [`surf_to_vol`] for the other parameters and the return value.
+pub fn ribbon_to_vol(white: &BrainMesh, pial: &BrainMesh, data: &[f32], template: &FsMghHeader, num_steps: usize, weighting: ProjectionWeighting) -> Result<FsMgh> {
+    if data.len() != white.num_vertices() {
+        return Err(NeuroformatsError::VertexCountMismatch(white.num_vertices(), data.len()));
+    }
+    if pial.num_vertices() != white.num_vertices() {
+        return Err(NeuroformatsError::VertexCountMismatch(white.num_vertices(), pial.num_vertices()));
+    }
+
+    let mut header = template.clone();
+    header.dim4len = 1;
+    let ras2vox = header.ras2vox_tkr()?;
+
+    let num_voxels = header.num_voxels_per_frame();
+    let mut weighted_sum: Vec<f32> = vec![0.0; num_voxels];
+    let mut weight_total: Vec<f32> = vec![0.0; num_voxels];
+
+    for (vertex, value) in data.iter().enumerate() {
+        let w = white.vertex_coords(vertex);
+        let p = pial.vertex_coords(vertex);
+        for step in 0..num_steps.max(1) {
+            let frac = if num_steps > 1 { step as f32 / (num_steps - 1) as f32 } else { 0.0 };
+            let point = [w[0] + frac * (p[0] - w[0]), w[1] + frac * (p[1] - w[1]), w[2] + frac * (p[2] - w[2])];
+            let voxel = apply_affine(&ras2vox, point);
+            for (flat_idx, weight) in voxel_weights(&header, voxel, weighting) {
+                weighted_sum[flat_idx] += weight * value;
+                weight_total[flat_idx] += weight;
+            }
+        }
+    }
+
+    let values: Vec<f32> = weighted_sum.iter().zip(weight_total.iter())
+        .map(|(sum, total)| if *total > 0.0 { sum / total } else { 0.0 })
+        .collect();
+    FsMgh::from_f32_values(&header, values)
+}
+
+
+/// Determines which voxels are set by [`surface_to_volume_mask`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelizationMode {
+    /// The voxels intersected by the surface, i.e., a shell about one voxel thick.
+    Shell,
+    /// The voxels whose center lies inside the surface. Requires a closed mesh, like `?h.white` or `?h.pial`.
+    Enclosed,
+    /// The union of [`VoxelizationMode::Shell`] and [`VoxelizationMode::Enclosed`], i.e., the full volume enclosed by the surface including its boundary.
+    ShellAndEnclosed,
+}
+
+
+/// Voxelize a surface into a volume with the geometry of an MGH file, e.g., to check the alignment of a surface with a volume.
+///
+/// The shell consists of the voxels which contain a point of the surface, found by sampling each face at less than half the voxel size.
+/// A voxel is enclosed if a ray from its center along the first voxel axis crosses the surface an odd number of times. Rays which cross
+/// a mesh with holes an odd number of times in total do not enclose any voxels. Masks of two
+/// surfaces can be combined into ribbon-like masks, e.g., the enclosed volume of `?h.pial` minus that of `?h.white` is the cortical ribbon.
+///
+/// # Parameters
+///
+/// * `mesh`: the surface mesh, in surface RAS coordinates.
+/// * `template`: the header of a volume of the same subject, e.g., `orig.mgz`, which defines the dimensions and RAS information of the output. Must contain valid RAS information.
+/// * `fill_value`: the value of the voxels in the mask. All other voxels are `0.0`.
+/// * `mode`: which voxels are in the mask, see [`VoxelizationMode`].
+///
+/// # Return value
+///
+/// The mask, with the dimensions of the `template` and a single frame, in the layout of [`crate::FsMghData`], so it can be used as the
+/// `mri_float` data of an MGH volume with the `template` header. Returns an error if the `template` has no valid RAS information or the
+/// faces of the `mesh` are invalid.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::projection::{surface_to_volume_mask, VoxelizationMode};
+/// use neuroformats::{FsMgh, FsMghData, MRI_FLOAT};
+/// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let orig = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/orig.mgz").unwrap();
+/// let mask = surface_to_volume_mask(&white.mesh, &orig.header, 1.0, VoxelizationMode::Shell).unwrap();
+/// let header = neuroformats::FsMghHeader { dtype: MRI_FLOAT, dim4len: 1, ..orig.header.clone() };
+/// let data = FsMghData { mri_uchar: None, mri_float: Some(mask), mri_int: None, mri_short: None };
+/// neuroformats::write_mgh("/tmp/lh.white.shell.mgz", &FsMgh { header, data, footer: None }).unwrap();
+/// ```
+pub fn surface_to_volume_mask(mesh: &BrainMesh, template: &FsMghHeader, fill_value: f32, mode: VoxelizationMode) -> Result<Array4<f32>> {
+    mesh.validate_faces()?;
+    let mut header = template.clone();
+    header.dim4len = 1;
+    let ras2vox = header.ras2vox_tkr()?;
+    let dim = header.dim();
+    let voxel_coords: Vec<[f32; 3]> = mesh.iter_vertices().map(|v| apply_affine(&ras2vox, v)).collect();
+    let mut values: Vec<f32> = vec![0.0; header.num_voxels_per_frame()];
+    let mut set = |c: i64, r: i64, s: i64| {
+        if c >= 0 && r >= 0 && s >= 0 && (c as usize) < dim[0] && (r as usize) < dim[1] && (s as usize) < dim[2] {
+            values[header.voxel_flat_index([c as usize, r as usize, s as usize], 0)] = fill_value;
+        }
+    };
+
+    if mode != VoxelizationMode::Shell {
+        for ((r, s), crossings) in ray_crossings(mesh, &voxel_coords, [dim[1], dim[2]]) {
+            // An odd number of crossings means that the mesh has a hole along this ray, so entry and exit points cannot be paired.
+            if crossings.len() % 2 != 0 {
+                continue;
+            }
+            for pair in crossings.chunks_exact(2) {
+                for c in pair[0].ceil() as i64..=pair[1].floor() as i64 {
+                    set(c, r as i64, s as i64);
+                }
+            }
+        }
+    }
+    if mode != VoxelizationMode::Enclosed {
+        for face in mesh.iter_faces() {
+            let [a, b, c] = face.map(|v| voxel_coords[v]);
+            let edge_length = |p: [f32; 3], q: [f32; 3]| ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2)).sqrt();
+            let max_edge = edge_length(a, b).max(edge_length(b, c)).max(edge_length(c, a));
+            let n = ((max_edge * 2.0).ceil() as usize).max(1);
+            for i in 0..=n {
+                for j in 0..=(n - i) {
+                    let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+                    let p = [0, 1, 2].map(|axis| a[axis] + u * (b[axis] - a[axis]) + v * (c[axis] - a[axis]));
+                    set(p[0].round() as i64, p[1].round() as i64, p[2].round() as i64);
+                }
+            }
+        }
+    }
+    Ok(Array::from_shape_vec(Dim(dim), values).unwrap())
+}
+
+
+/// Compute the sorted positions along the first voxel axis at which the rays through the voxel centers of each (row, slice) cross the faces.
+///
+/// The rays are shifted by a tiny offset from the voxel centers, so they do not pass exactly through the vertices and edges of the
+/// faces, which would count a crossing twice or not at all.
+fn ray_crossings(mesh: &BrainMesh, voxel_coords: &[[f32; 3]], dim: [usize; 2]) -> HashMap<(usize, usize), Vec<f32>> {
+    const RAY_OFFSET: [f32; 2] = [1.37e-4, 2.91e-4];
+    let mut crossings: HashMap<(usize, usize), Vec<f32>> = HashMap::new();
+    for face in mesh.iter_faces() {
+        let [a, b, c] = face.map(|v| voxel_coords[v]);
+        let area = (b[1] - a[1]) * (c[2] - a[2]) - (c[1] - a[1]) * (b[2] - a[2]);
+        if area == 0.0 {
+            continue;
+        }
+        // The rows and slices of the rays which may cross the face, taking into account that the rays are shifted by the offset.
+        let range = |axis: usize, len: usize| {
+            let offset = RAY_OFFSET[axis - 1];
+            let min = (a[axis].min(b[axis]).min(c[axis]) - offset).ceil().max(0.0) as usize;
+            let max = (a[axis].max(b[axis]).max(c[axis]) - offset).floor().min(len as f32 - 1.0);
+            if max < 0.0 { min..min } else { min..(max as usize + 1) }
+        };
+        for r in range(1, dim[0]) {
+            for s in range(2, dim[1]) {
+                let (y, z) = (r as f32 + RAY_OFFSET[0], s as f32 + RAY_OFFSET[1]);
+                let wb = ((y - a[1]) * (c[2] - a[2]) - (c[1] - a[1]) * (z - a[2])) / area;
+                let wc = ((b[1] - a[1]) * (z - a[2]) - (y - a[1]) * (b[2] - a[2])) / area;
+                if wb >= 0.0 && wc >= 0.0 && wb + wc <= 1.0 {
+                    let x = a[0] + wb * (b[0] - a[0]) + wc * (c[0] - a[0]);
+                    crossings.entry((r, s)).or_default().push(x);
+                }
+            }
+        }
+    }
+    for xs in crossings.values_mut() {
+        xs.sort_unstable_by(|p, q| p.total_cmp(q));
+    }
+    crossings
+}
+
+
+/// Sample the first frame of a volume at the given surface RAS coordinates using trilinear interpolation.
+///
+/// # Parameters
+///
+/// * `vol`: the volume to sample. Must contain valid RAS information.
+/// * `coords`: the surface RAS coordinates of the points, as a flat vector of x,y,z triplets, like [`BrainMesh::vertices`].
+///
+/// # Return value
+///
+/// One interpolated value per point. Points outside of the volume get value `NAN`.
+pub fn sample_volume(vol: &FsMgh, coords: &[f32]) -> Result<Vec<f32>> {
+    let ras2vox = vol.header.ras2vox_tkr()?;
+    let values = vol.data_as_f32()?;
+    let dim = vol.header.dim();
+    Ok(coords.chunks_exact(3).map(|c| {
+        let voxel = apply_affine(&ras2vox, [c[0], c[1], c[2]]);
+        let outside = (0..3).any(|axis| voxel[axis] < 0.0 || voxel[axis] > (dim[axis] - 1) as f32);
+        if outside {
+            return f32::NAN;
+        }
+        voxel_weights(&vol.header, voxel, ProjectionWeighting::PartialVolume)
+            .iter()
+            .map(|(flat_idx, weight)| weight * values[*flat_idx])
+            .sum()
+    }).collect())
+}
+
+
+/// Per-vertex intensities and gray-white contrast at a white surface, see [`gray_white_contrast`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayWhiteContrast {
+    /// The intensity sampled at the given distance inside the white surface, in the white matter.
+    pub white_matter: Vec<f32>,
+    /// The intensity sampled at the white surface.
+    pub surface: Vec<f32>,
+    /// The intensity sampled at the given distance outside the white surface, in the gray matter.
+    pub gray_matter: Vec<f32>,
+    /// The gray-white contrast in percent, computed as `100 * (white_matter - gray_matter) / (0.5 * (white_matter + gray_matter))`.
+    pub contrast: Vec<f32>,
+}
+
+
+impl GrayWhiteContrast {
+    /// Compute the mean contrast over all vertices, ignoring `NAN` values.
+    ///
+    /// Returns `NAN` if there are no valid values.
+    pub fn mean_contrast(&self) -> f32 {
+        let valid: Vec<f32> = self.contrast.iter().copied().filter(|v| !v.is_nan()).collect();
+        valid.iter().sum::<f32>() / valid.len() as f32
+    }
+}
+
+
+/// Compute the per-vertex gray-white contrast along a white surface.
+///
+/// The volume is sampled at each vertex of the white surface, and at `distance` mm along the vertex normal to
+/// the inside (white matter) and outside (gray matter). The contrast is a standard metric to assess the placement
+/// of the white surface: low or negative contrast values indicate vertices where the surface is misplaced. This
+/// is similar to what FreeSurfer's `pctsurfcon` computes.
+///
+/// # Parameters
+///
+/// * `vol`: a volume of the same subject, typically `nu.mgz` or `brain.mgz`.
+/// * `white`: the white surface.
+/// * `distance`: the sampling distance from the surface in mm, FreeSurfer uses `1.0` for the white matter and 30 percent of the thickness for the gray matter.
+///
+/// # Examples
+///
+/// ```no_run
+/// let vol = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/brain.mgz").unwrap();
+/// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let gwc = neuroformats::projection::gray_white_contrast(&vol, &white.mesh, 1.0).unwrap();
+/// println!("Mean gray-white contrast: {} percent.", gwc.mean_contrast());
+/// ```
+pub fn gray_white_contrast(vol: &FsMgh, white: &BrainMesh, distance: f32) -> Result<GrayWhiteContrast> {
+    let normals = white.vertex_normals();
+    let shifted = |sign: f32| -> Vec<f32> {
+        white.vertices.iter().zip(normals.iter()).map(|(v, n)| v + sign * distance * n).collect()
+    };
+    let white_matter = sample_volume(vol, &shifted(-1.0))?;
+    let surface = sample_volume(vol, &white.vertices)?;
+    let gray_matter = sample_volume(vol, &shifted(1.0))?;
+    let contrast = white_matter.iter().zip(gray_matter.iter())
+        .map(|(w, g)| 100.0 * (w - g) / (0.5 * (w + g)))
+        .collect();
+    Ok(GrayWhiteContrast { white_matter, surface, gray_matter, contrast })
+}
+
+
+/// Apply a 4x4 affine matrix to a 3D point.
+pub(crate) fn apply_affine(m: &Array2<f32>, p: [f32; 3]) -> [f32; 3] {
+    let mut res = [0.0f32; 3];
+    for (i, r) in res.iter_mut().enumerate() {
+        *r = m[[i, 0]] * p[0] + m[[i, 1]] * p[1] + m[[i, 2]] * p[2] + m[[i, 3]];
+    }
+    res
+}
+
+
+/// Compute the flat voxel indices and weights that a point at the given (fractional) voxel coordinates contributes to.
+///
+/// Voxels outside of the volume are skipped.
+pub(crate) fn voxel_weights(header: &FsMghHeader, voxel: [f32; 3], weighting: ProjectionWeighting) -> Vec<(usize, f32)> {
+    let dim = header.dim();
+    let in_volume = |c: i64, r: i64, s: i64| c >= 0 && r >= 0 && s >= 0 && (c as usize) < dim[0] && (r as usize) < dim[1] && (s as usize) < dim[2];
+    match weighting {
+        ProjectionWeighting::NearestVoxel => {
+            let (c, r, s) = (voxel[0].round() as i64, voxel[1].round() as i64, voxel[2].round() as i64);
+            if in_volume(c, r, s) {
+                vec![(header.voxel_flat_index([c as usize, r as usize, s as usize], 0), 1.0)]
+            } else {
+                Vec::new()
+            }
+        },
+        ProjectionWeighting::PartialVolume => {
+            let base = [voxel[0].floor(), voxel[1].floor(), voxel[2].floor()];
+            let frac = [voxel[0] - base[0], voxel[1] - base[1], voxel[2] - base[2]];
+            let mut weights = Vec::with_capacity(8);
+            for corner in 0..8 {
+                let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+                let mut weight = 1.0;
+                for axis in 0..3 {
+                    weight *= if offset[axis] == 1 { frac[axis] } else { 1.0 - frac[axis] };
+                }
+                let (c, r, s) = (base[0] as i64 + offset[0] as i64, base[1] as i64 + offset[1] as i64, base[2] as i64 + offset[2] as i64);
+                if weight > 0.0 && in_volume(c, r, s) {
+                    weights.push((header.voxel_flat_index([c as usize, r as usize, s as usize], 0), weight));
+                }
+            }
+            weights
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs_mgh::MRI_FLOAT;
+
+    fn tiny_template() -> FsMghHeader {
+        FsMghHeader {
+            dim1len: 4,
+            dim2len: 4,
+            dim3len: 4,
+            dim4len: 1,
+            dtype: MRI_FLOAT,
+            is_ras_good: 1,
+            delta: [1.0; 3],
+            mdc_raw: [-1., 0., 0., 0., 0., -1., 0., 1., 0.],
+            ..FsMghHeader::default()
+        }
+    }
+
+    #[test]
+    fn a_closed_surface_can_be_voxelized() {
+        let template = FsMghHeader { dim1len: 16, dim2len: 16, dim3len: 16, ..tiny_template() };
+        let mut sphere = crate::subdivision::generate_icosphere(4);
+        sphere.vertices.iter_mut().for_each(|v| *v *= 5.0);
+        let center = apply_affine(&template.ras2vox_tkr().unwrap(), [0.0; 3]);
+        let radius = |idx: usize| {
+            let (c, r, s) = (idx % 16, idx / 16 % 16, idx / 256);
+            ((c as f32 - center[0]).powi(2) + (r as f32 - center[1]).powi(2) + (s as f32 - center[2]).powi(2)).sqrt()
+        };
+
+        let enclosed = surface_to_volume_mask(&sphere, &template, 2.0, VoxelizationMode::Enclosed).unwrap();
+        assert_eq!([16, 16, 16, 1], enclosed.shape());
+        let enclosed: Vec<f32> = enclosed.iter().copied().collect();
+        let num_enclosed = enclosed.iter().filter(|v| **v == 2.0).count();
+        let expected = 4.0 / 3.0 * std::f32::consts::PI * 125.0;
+        assert!((num_enclosed as f32 - expected).abs() < 0.1 * expected, "{} voxels enclosed", num_enclosed);
+        assert!(enclosed.iter().enumerate().all(|(idx, v)| (*v == 2.0) == (radius(idx) < 5.0) || (radius(idx) - 5.0).abs() < 0.1));
+
+        let shell: Vec<f32> = surface_to_volume_mask(&sphere, &template, 1.0, VoxelizationMode::Shell).unwrap().iter().copied().collect();
+        assert!(shell.iter().enumerate().filter(|(_, v)| **v == 1.0).all(|(idx, _)| (radius(idx) - 5.0).abs() < 0.9));
+        assert!(shell.iter().filter(|v| **v == 1.0).count() > 100);
+
+        let both: Vec<f32> = surface_to_volume_mask(&sphere, &template, 1.0, VoxelizationMode::ShellAndEnclosed).unwrap().iter().copied().collect();
+        assert!(both.iter().zip(shell.iter().zip(enclosed.iter())).all(|(b, (s, e))| (*b == 1.0) == (*s == 1.0 || *e == 2.0)));
+        assert!(surface_to_volume_mask(&sphere, &FsMghHeader { is_ras_good: 0, ..template }, 1.0, VoxelizationMode::Shell).is_err());
+    }
+
+    #[test]
+    fn rays_hit_faces_which_start_between_the_voxel_center_and_the_ray_offset() {
+        // The face in the plane x = 10 starts at y = 2.00005, which is between row 2 and the ray of row 2.
+        let mesh = BrainMesh {
+            vertices: vec![0.0, 1.0, 5.0, 10.0, 2.00005, 0.0, 10.0, 2.00005, 10.0, 10.0, 8.0, 5.0],
+            faces: vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3],
+        };
+        let voxel_coords: Vec<[f32; 3]> = mesh.iter_vertices().collect();
+        let crossings = ray_crossings(&mesh, &voxel_coords, [16, 16]);
+        assert_eq!(2, crossings[&(2, 5)].len());
+        assert!(crossings.values().all(|xs| xs.len() % 2 == 0));
+    }
+
+    #[test]
+    fn surface_data_can_be_projected_into_a_volume() {
+        let template = tiny_template();
+        let vox2ras = template.vox2ras_tkr().unwrap();
+        let center_of_voxel_1_2_3 = apply_affine(&vox2ras, [1.0, 2.0, 3.0]);
+        let mesh = BrainMesh { vertices: center_of_voxel_1_2_3.to_vec(), faces: vec![] };
+
+        let vol = surf_to_vol(&mesh, &[5.0], &template, ProjectionWeighting::NearestVoxel).unwrap();
+        let values = vol.data_as_f32().unwrap();
+        assert_eq!(64, values.len());
+        assert_eq!(5.0, values[template.voxel_flat_index([1, 2, 3], 0)]);
+        assert_eq!(5.0, values.iter().sum::<f32>());
+
+        let vol_pv = surf_to_vol(&mesh, &[5.0], &template, ProjectionWeighting::PartialVolume).unwrap();
+        assert_eq!(5.0, vol_pv.data_as_f32().unwrap()[template.voxel_flat_index([1, 2, 3], 0)]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_gray_white_contrast_can_be_computed_for_the_demo_subject() {
+        let vol = crate::read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let white = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let gwc = gray_white_contrast(&vol, &white.mesh, 1.0).unwrap();
+
+        assert_eq!(149244, gwc.contrast.len());
+        assert_eq!(149244, gwc.surface.len());
+        // White matter is brighter than gray matter in T1-weighted images.
+        assert!(gwc.mean_contrast() > 0.0);
+    }
+
+    #[test]
+    fn surface_data_can_be_projected_into_the_ribbon() {
+        let template = tiny_template();
+        let vox2ras = template.vox2ras_tkr().unwrap();
+        let white = BrainMesh { vertices: apply_affine(&vox2ras, [0.0, 1.0, 1.0]).to_vec(), faces: vec![] };
+        let pial = BrainMesh { vertices: apply_affine(&vox2ras, [3.0, 1.0, 1.0]).to_vec(), faces: vec![] };
+
+        let vol = ribbon_to_vol(&white, &pial, &[2.0], &template, 4, ProjectionWeighting::NearestVoxel).unwrap();
+        let values = vol.data_as_f32().unwrap();
+        for c in 0..4 {
+            assert_eq!(2.0, values[template.voxel_flat_index([c, 1, 1], 0)]);
+        }
+        assert_eq!(8.0, values.iter().sum::<f32>());
+
+        assert!(ribbon_to_vol(&white, &pial, &[1.0, 2.0], &template, 4, ProjectionWeighting::NearestVoxel).is_err());
+    }
+}
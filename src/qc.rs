@@ -0,0 +1,299 @@
+//! Quality control reports for subjects processed with FreeSurfer's `recon-all`.
+//!
+//! A [`QcReport`] collects the numbers which are typically checked after a run: the vertex counts and Euler characteristics
+//! of the surfaces, the cortical thickness overall and per region of the Desikan-Killiany atlas, and the volumes of the
+//! subcortical segmentation. Missing and unreadable files are listed instead of failing, so reports can be generated for
+//! incomplete runs. Reports can be written as JSON or as a basic HTML page.
+
+
+use std::fmt::Write as FmtWrite;
+use std::path::PathBuf;
+
+use crate::color_lut::ColorLut;
+use crate::describe::ValueSummary;
+use crate::error::Result;
+use crate::fs_annot::read_annot;
+use crate::fs_curv::read_curv;
+use crate::fs_label::read_label;
+use crate::fs_mgh::read_mgh;
+use crate::fs_surface::read_surf;
+use crate::hemi::Hemisphere;
+use crate::segmentation::{segmentation_stats, RegionVolume};
+use crate::subject::{Subject, SurfaceKind};
+use crate::tables::{RegionStats, RegionTableOptions};
+use crate::util::{json_array, json_number, json_string};
+
+
+/// The surfaces which are checked for each hemisphere.
+pub const QC_SURFACES: [SurfaceKind; 2] = [SurfaceKind::White, SurfaceKind::Pial];
+
+
+/// The mesh properties of a surface, see [`QcReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfaceQc {
+    /// The kind of the surface.
+    pub kind: SurfaceKind,
+    /// The number of vertices.
+    pub num_vertices: usize,
+    /// The number of faces.
+    pub num_faces: usize,
+    /// The Euler characteristic of the mesh, which is `2` for surfaces with the topology of a sphere, see [`crate::BrainMesh::euler_characteristic`].
+    pub euler_characteristic: i64,
+}
+
+
+/// The QC results of a hemisphere, see [`QcReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HemiQc {
+    /// The hemisphere.
+    pub hemi: Hemisphere,
+    /// The surfaces of [`QC_SURFACES`] which could be read.
+    pub surfaces: Vec<SurfaceQc>,
+    /// Statistics of the cortical thickness, or `None` if `surf/?h.thickness` could not be read.
+    pub thickness: Option<ValueSummary>,
+    /// The cortical thickness per region of `label/?h.aparc.annot`, or `None` if the parcellation or the thickness could not be read.
+    /// The medial wall is excluded if `label/?h.cortex.label` is available.
+    pub region_thickness: Option<Vec<RegionStats>>,
+}
+
+
+/// A quality control report for a subject, see [`qc_report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QcReport {
+    /// The identifier of the subject.
+    pub subject_id: String,
+    /// The results for both hemispheres, left first.
+    pub hemis: Vec<HemiQc>,
+    /// The volumes of the regions of `mri/aseg.mgz`, or `None` if it could not be read.
+    pub aseg_volumes: Option<Vec<RegionVolume>>,
+    /// The expected files which do not exist.
+    pub missing_files: Vec<PathBuf>,
+    /// The files which exist but could not be read or used, with the error message.
+    pub invalid_files: Vec<(PathBuf, String)>,
+}
+
+
+/// Generate a quality control report for a subject.
+///
+/// The report is based on the following files of the subject, for both hemispheres: `surf/?h.white`, `surf/?h.pial`, `surf/?h.thickness`,
+/// `label/?h.aparc.annot` and `label/?h.cortex.label`, and `mri/aseg.mgz`. Files which are missing or cannot be read are listed in the report.
+///
+/// # Parameters
+///
+/// * `subject`: the subject.
+/// * `lut`: the color lookup table used to name the regions of the segmentation, typically `FreeSurferColorLUT.txt`. If `None`, the regions have no names.
+///
+/// # Examples
+///
+/// ```no_run
+/// let subject = neuroformats::subject::Subject::new("/path/to/subjects_dir", "subject1");
+/// let report = neuroformats::qc::qc_report(&subject, None);
+/// if !report.is_complete() {
+///     println!("Missing files: {:?}", report.missing_files);
+/// }
+/// std::fs::write("/path/to/subject1_qc.html", report.to_html()).unwrap();
+/// ```
+pub fn qc_report(subject: &Subject, lut: Option<&ColorLut>) -> QcReport {
+    let mut report = QcReport { subject_id: subject.subject_id.clone(), hemis: Vec::new(), aseg_volumes: None, missing_files: Vec::new(), invalid_files: Vec::new() };
+
+    for hemi in Hemisphere::BOTH {
+        let surfaces = QC_SURFACES.iter().filter_map(|kind| {
+            load(&mut report, subject.surf_path(hemi, *kind), read_surf).map(|surf| SurfaceQc {
+                kind: *kind,
+                num_vertices: surf.mesh.num_vertices(),
+                num_faces: surf.mesh.num_faces(),
+                euler_characteristic: surf.mesh.euler_characteristic(),
+            })
+        }).collect();
+
+        let thickness = load(&mut report, subject.measure_path(hemi, "thickness"), read_curv);
+        let label_dir = subject.dir().join("label");
+        let annot_path = label_dir.join(format!("{}.aparc.annot", hemi));
+        let annot = load(&mut report, annot_path.clone(), read_annot);
+        let cortex = load(&mut report, label_dir.join(format!("{}.cortex.label", hemi)), read_label);
+
+        let region_thickness = match (&annot, &thickness) {
+            (Some(annot), Some(thickness)) => {
+                match annot.region_table_with_options(&thickness.data, &RegionTableOptions { exclude_medial_wall: cortex.as_ref() }) {
+                    Ok(table) => Some(table),
+                    Err(e) => {
+                        report.invalid_files.push((annot_path, e.to_string()));
+                        None
+                    },
+                }
+            },
+            _ => None,
+        };
+
+        report.hemis.push(HemiQc {
+            hemi,
+            surfaces,
+            thickness: thickness.map(|t| ValueSummary::from_values(t.data)),
+            region_thickness,
+        });
+    }
+
+    let aseg_path = subject.dir().join("mri").join("aseg.mgz");
+    let empty_lut = ColorLut { entries: Vec::new() };
+    report.aseg_volumes = load(&mut report, aseg_path, |p| segmentation_stats(&read_mgh(p)?, lut.unwrap_or(&empty_lut)));
+    report
+}
+
+
+/// Read a file for the report, recording it as missing or invalid on failure.
+fn load<T, F: FnOnce(PathBuf) -> Result<T>>(report: &mut QcReport, path: PathBuf, read: F) -> Option<T> {
+    if !path.is_file() {
+        report.missing_files.push(path);
+        return None;
+    }
+    match read(path.clone()) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            report.invalid_files.push((path, e.to_string()));
+            None
+        },
+    }
+}
+
+
+impl QcReport {
+    /// Whether all expected files exist and could be read.
+    pub fn is_complete(&self) -> bool {
+        self.missing_files.is_empty() && self.invalid_files.is_empty()
+    }
+
+
+    /// Get a JSON representation of this report. Missing results are written as `null`, and so are NaN values.
+    ///
+    /// This does not need the optional `serde` feature.
+    pub fn to_json(&self) -> String {
+        let hemis = json_array(self.hemis.iter().map(|h| {
+            let surfaces = json_array(h.surfaces.iter().map(|s| format!(r#"{{"kind":"{}","num_vertices":{},"num_faces":{},"euler_characteristic":{}}}"#,
+                s.kind, s.num_vertices, s.num_faces, s.euler_characteristic)));
+            let regions = h.region_thickness.as_ref().map(|table| json_array(table.iter().map(|r| format!(r#"{{"id":{},"name":{},"num_vertices":{},"mean":{},"std":{}}}"#,
+                r.id, json_string(&r.name), r.num_vertices, json_number(r.mean as f64), json_number(r.std as f64)))));
+            format!(r#"{{"hemi":"{}","surfaces":{},"thickness":{},"region_thickness":{}}}"#,
+                h.hemi, surfaces, json_or_null(h.thickness.as_ref().map(|t| t.to_json())), json_or_null(regions))
+        }));
+        let aseg = self.aseg_volumes.as_ref().map(|volumes| json_array(volumes.iter().map(|v| format!(r#"{{"label":{},"name":{},"num_voxels":{},"volume_mm3":{}}}"#,
+            v.label, json_or_null(v.name.as_deref().map(json_string)), v.num_voxels, json_number(v.volume_mm3)))));
+        let missing = json_array(self.missing_files.iter().map(|p| json_string(&p.display().to_string())));
+        let invalid = json_array(self.invalid_files.iter().map(|(p, e)| format!(r#"{{"path":{},"error":{}}}"#, json_string(&p.display().to_string()), json_string(e))));
+        format!(r#"{{"subject_id":{},"complete":{},"missing_files":{},"invalid_files":{},"hemispheres":{},"aseg_volumes":{}}}"#,
+            json_string(&self.subject_id), self.is_complete(), missing, invalid, hemis, json_or_null(aseg))
+    }
+
+
+    /// Get a basic, self-contained HTML page showing this report.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let title = format!("QC report for subject {}", html_escape(&self.subject_id));
+        writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>", title, title).unwrap();
+
+        if self.is_complete() {
+            writeln!(html, "<p>All files found.</p>").unwrap();
+        }
+        if !self.missing_files.is_empty() {
+            writeln!(html, "<h2>Missing files</h2>\n<ul>").unwrap();
+            for path in self.missing_files.iter() {
+                writeln!(html, "<li>{}</li>", html_escape(&path.display().to_string())).unwrap();
+            }
+            writeln!(html, "</ul>").unwrap();
+        }
+        if !self.invalid_files.is_empty() {
+            writeln!(html, "<h2>Invalid files</h2>\n<ul>").unwrap();
+            for (path, error) in self.invalid_files.iter() {
+                writeln!(html, "<li>{}: {}</li>", html_escape(&path.display().to_string()), html_escape(error)).unwrap();
+            }
+            writeln!(html, "</ul>").unwrap();
+        }
+
+        for h in self.hemis.iter() {
+            writeln!(html, "<h2>Hemisphere {}</h2>", h.hemi).unwrap();
+            if !h.surfaces.is_empty() {
+                writeln!(html, "<table>\n<tr><th>Surface</th><th>Vertices</th><th>Faces</th><th>Euler characteristic</th></tr>").unwrap();
+                for s in h.surfaces.iter() {
+                    writeln!(html, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>", s.kind, s.num_vertices, s.num_faces, s.euler_characteristic).unwrap();
+                }
+                writeln!(html, "</table>").unwrap();
+            }
+            if let Some(thickness) = &h.thickness {
+                writeln!(html, "<p>Cortical thickness: {}</p>", html_escape(&thickness.to_string())).unwrap();
+            }
+            if let Some(table) = &h.region_thickness {
+                writeln!(html, "<table>\n<tr><th>Region</th><th>Vertices</th><th>Mean thickness</th><th>Std</th></tr>").unwrap();
+                for r in table.iter() {
+                    writeln!(html, "<tr><td>{}</td><td>{}</td><td>{:.3}</td><td>{:.3}</td></tr>", html_escape(&r.name), r.num_vertices, r.mean, r.std).unwrap();
+                }
+                writeln!(html, "</table>").unwrap();
+            }
+        }
+
+        if let Some(volumes) = &self.aseg_volumes {
+            writeln!(html, "<h2>Segmentation volumes</h2>\n<table>\n<tr><th>Label</th><th>Region</th><th>Voxels</th><th>Volume (mm³)</th></tr>").unwrap();
+            for v in volumes.iter() {
+                writeln!(html, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td></tr>", v.label, html_escape(v.name.as_deref().unwrap_or("")), v.num_voxels, v.volume_mm3).unwrap();
+            }
+            writeln!(html, "</table>").unwrap();
+        }
+        writeln!(html, "</body>\n</html>").unwrap();
+        html
+    }
+}
+
+
+/// Use the given JSON value, or `null` if there is none.
+fn json_or_null(value: Option<String>) -> String {
+    value.unwrap_or_else(|| String::from("null"))
+}
+
+
+/// Escape the characters with special meaning in HTML.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_qc_report_lists_results_and_missing_files() {
+        let subject = Subject::new("resources/subjects_dir", "subject1");
+        let report = qc_report(&subject, None);
+        assert_eq!("subject1", report.subject_id);
+        assert!(!report.is_complete());
+        assert!(report.invalid_files.is_empty());
+        assert!(report.missing_files.contains(&subject.surf_path(Hemisphere::Left, SurfaceKind::Pial)));
+        assert!(report.missing_files.contains(&subject.dir().join("mri").join("aseg.mgz")));
+        assert!(report.aseg_volumes.is_none());
+
+        let lh = &report.hemis[0];
+        assert_eq!(Hemisphere::Left, lh.hemi);
+        assert_eq!(1, lh.surfaces.len());
+        assert_eq!(149244, lh.surfaces[0].num_vertices);
+        assert_eq!(2, lh.surfaces[0].euler_characteristic);
+        assert_eq!(149244, lh.thickness.as_ref().unwrap().count);
+        assert_eq!(36, lh.region_thickness.as_ref().unwrap().len());
+        assert!(report.hemis[1].thickness.is_none() && report.hemis[1].region_thickness.is_none());
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!("subject1", json["subject_id"]);
+        assert_eq!(false, json["complete"]);
+        assert_eq!("white", json["hemispheres"][0]["surfaces"][0]["kind"]);
+        assert!(json["aseg_volumes"].is_null());
+
+        let html = report.to_html();
+        assert!(html.contains("<h1>QC report for subject subject1</h1>"));
+        assert!(html.contains("bankssts"));
+    }
+
+    #[test]
+    fn a_qc_report_for_a_missing_subject_lists_all_files() {
+        let report = qc_report(&Subject::new("resources/subjects_dir", "no_such_subject"), None);
+        assert_eq!(2 * (QC_SURFACES.len() + 3) + 1, report.missing_files.len());
+        assert!(report.hemis.iter().all(|h| h.surfaces.is_empty()));
+        assert_eq!("a &lt;b&gt; &amp; &quot;c&quot;", html_escape("a <b> & \"c\""));
+    }
+}
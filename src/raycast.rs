@@ -0,0 +1,204 @@
+//! Ray-mesh intersection for picking vertices and regions on brain meshes.
+//!
+//! Interactive viewers map a mouse click to a ray from the camera into the scene. The first face of the mesh hit by the ray
+//! determines the picked point, and its closest vertex can be used to look up per-vertex data or the region of a parcellation.
+
+
+use crate::fs_surface::BrainMesh;
+use crate::bvh::MeshBvh;
+use crate::util::{vec3_sub, vec3_dot, vec3_cross};
+
+
+/// The intersection of a ray with a mesh, see [`MeshBvh::raycast`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    /// The index of the face that was hit.
+    pub face: usize,
+    /// The barycentric coordinates of the hit point with respect to the three vertices of the face, in the order of the face definition.
+    pub barycentric: [f32; 3],
+    /// The hit point.
+    pub point: [f32; 3],
+    /// The index of the vertex of the face closest to the hit point, i.e., the one with the largest barycentric coordinate.
+    pub vertex: usize,
+    /// The Euclidean distance from the origin of the ray to the hit point.
+    pub distance: f32,
+}
+
+
+impl MeshBvh {
+    /// Find the first intersection of a ray with the faces of the mesh.
+    ///
+    /// Faces are hit from both sides. The `direction` does not need to be normalized.
+    ///
+    /// # Return value
+    ///
+    /// The closest hit in front of the `origin`, or `None` if the ray misses the mesh or the `direction` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let bvh = surf.mesh.build_bvh();
+    /// if let Some(hit) = bvh.raycast([-200.0, 0.0, 20.0], [1.0, 0.0, 0.0]) {
+    ///     println!("Picked vertex {} in region {:?}.", hit.vertex, annot.vertex_region_info(hit.vertex).map(|r| &r.name));
+    /// }
+    /// ```
+    pub fn raycast(&self, origin: [f32; 3], direction: [f32; 3]) -> Option<Hit> {
+        let length = (direction[0] * direction[0] + direction[1] * direction[1] + direction[2] * direction[2]).sqrt();
+        if self.is_empty() || length == 0.0 || !length.is_finite() {
+            return None;
+        }
+        let direction = direction.map(|d| d / length);
+        let inv_direction = direction.map(|d| 1.0 / d);
+
+        let mut best: Option<(usize, f32, f32, f32)> = None;
+        let mut stack = vec![0];
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            let max_t = best.map(|b| b.1).unwrap_or(f32::INFINITY);
+            if !ray_hits_box(origin, inv_direction, node.min, node.max, max_t) {
+                continue;
+            }
+            if node.count > 0 {
+                for &face_idx in self.order[node.start..node.start + node.count].iter() {
+                    if let Some((t, u, v)) = intersect_triangle(origin, direction, &self.triangles[face_idx]) {
+                        if t < best.map(|b| b.1).unwrap_or(f32::INFINITY) {
+                            best = Some((face_idx, t, u, v));
+                        }
+                    }
+                }
+            } else {
+                stack.push(node.start);
+                stack.push(node.start + 1);
+            }
+        }
+
+        best.map(|(face, t, u, v)| {
+            let barycentric = [1.0 - u - v, u, v];
+            Hit {
+                face,
+                barycentric,
+                point: [origin[0] + t * direction[0], origin[1] + t * direction[1], origin[2] + t * direction[2]],
+                vertex: self.closest_corner(face, barycentric),
+                distance: t,
+            }
+        })
+    }
+
+}
+
+
+/// Whether the ray enters the box at a distance of at most `max_t`, using the slab method.
+fn ray_hits_box(origin: [f32; 3], inv_direction: [f32; 3], min: [f32; 3], max: [f32; 3], max_t: f32) -> bool {
+    let (mut t_enter, mut t_exit) = (0.0_f32, max_t);
+    for axis in 0..3 {
+        if inv_direction[axis].is_infinite() {
+            // The ray is parallel to the slab, so it either always or never lies within it.
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return false;
+            }
+            continue;
+        }
+        let t1 = (min[axis] - origin[axis]) * inv_direction[axis];
+        let t2 = (max[axis] - origin[axis]) * inv_direction[axis];
+        t_enter = t_enter.max(t1.min(t2));
+        t_exit = t_exit.min(t1.max(t2));
+    }
+    t_enter <= t_exit
+}
+
+
+/// Intersect a ray with a triangle using the Möller-Trumbore algorithm.
+///
+/// Returns the distance along the ray and the barycentric coordinates of the second and third corner, or `None` if the ray misses the triangle.
+fn intersect_triangle(origin: [f32; 3], direction: [f32; 3], triangle: &[[f32; 3]; 3]) -> Option<(f32, f32, f32)> {
+    let [a, b, c] = *triangle;
+    let (edge1, edge2) = (vec3_sub(b, a), vec3_sub(c, a));
+    let p = vec3_cross(direction, edge2);
+    let det = vec3_dot(edge1, p);
+    if det.abs() < f32::EPSILON * vec3_dot(edge1, edge1).max(vec3_dot(edge2, edge2)) {
+        return None;
+    }
+    let s = vec3_sub(origin, a);
+    let u = vec3_dot(s, p) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = vec3_cross(s, edge1);
+    let v = vec3_dot(direction, q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = vec3_dot(edge2, q) / det;
+    if t >= 0.0 { Some((t, u, v)) } else { None }
+}
+
+
+
+impl BrainMesh {
+    /// Find the first intersection of a ray with the faces of this mesh.
+    ///
+    /// This builds a [`MeshBvh`] for a single query. Use [`BrainMesh::build_bvh`] and [`MeshBvh::raycast`] for repeated queries.
+    pub fn raycast(&self, origin: [f32; 3], direction: [f32; 3]) -> Option<Hit> {
+        self.build_bvh().raycast(origin, direction)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use crate::subdivision::generate_icosphere;
+    #[cfg(feature = "fs")]
+    use crate::read_surf;
+
+    #[test]
+    fn a_ray_can_be_intersected_with_a_single_face() {
+        let mesh = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2] };
+        let hit = mesh.raycast([0.2, 0.6, 5.0], [0.0, 0.0, -2.0]).unwrap();
+        assert_eq!(0, hit.face);
+        assert_eq!(2, hit.vertex);
+        assert_abs_diff_eq!(5.0, hit.distance, epsilon = 1e-6);
+        assert_abs_diff_eq!(0.2, hit.barycentric[0], epsilon = 1e-6);
+        assert_abs_diff_eq!(0.6, hit.barycentric[2], epsilon = 1e-6);
+        assert_eq!([0.2, 0.6, 0.0], hit.point);
+
+        assert!(mesh.raycast([0.2, 0.6, 5.0], [0.0, 0.0, 1.0]).is_none());
+        assert!(mesh.raycast([0.8, 0.8, 5.0], [0.0, 0.0, -1.0]).is_none());
+        assert!(mesh.raycast([0.2, 0.6, 5.0], [0.0, 0.0, 0.0]).is_none());
+        assert!(BrainMesh { vertices: vec![], faces: vec![] }.raycast([0.0; 3], [1.0, 0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn a_ray_hits_the_near_side_of_a_sphere() {
+        let ico = generate_icosphere(4);
+        let bvh = ico.build_bvh();
+        assert_eq!(ico.num_faces(), bvh.len());
+        let hit = bvh.raycast([0.0, 0.0, -10.0], [0.0, 0.0, 1.0]).unwrap();
+        assert_abs_diff_eq!(9.0, hit.distance, epsilon = 0.01);
+        assert!(hit.point[2] < 0.0);
+
+        let inside = bvh.raycast([0.0; 3], [1.0, 1.0, 0.0]).unwrap();
+        assert_abs_diff_eq!(1.0, inside.distance, epsilon = 0.01);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_bvh_finds_the_same_hits_as_brute_force() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let bvh = surf.mesh.build_bvh();
+        let triangles: Vec<[[f32; 3]; 3]> = surf.mesh.faces.chunks_exact(3).map(|f| [f[0], f[1], f[2]].map(|v| surf.mesh.vertex_coords(v as usize))).collect();
+        for (i, target) in vec![[-30.0, 0.0, 10.0], [-40.0, -20.0, 40.0], [-20.0, 30.0, -10.0], [100.0, 100.0, 100.0]].into_iter().enumerate() {
+            let origin = [-150.0, 10.0 * i as f32, 0.0];
+            let direction = [target[0] - origin[0], target[1] - origin[1], target[2] - origin[2]];
+            let length = vec3_dot(direction, direction).sqrt();
+            let unit = direction.map(|d| d / length);
+            let brute_force = triangles.iter().enumerate()
+                .filter_map(|(face, tri)| intersect_triangle(origin, unit, tri).map(|(t, _, _)| (face, t)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            assert_eq!(brute_force.map(|b| b.0), bvh.raycast(origin, direction).map(|h| h.face));
+        }
+    }
+}
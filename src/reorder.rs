@@ -0,0 +1,433 @@
+//! Reordering of the vertices of meshes, and of the per-vertex data, parcellations and labels which belong to them.
+//!
+//! The order of the vertices of a mesh is arbitrary, but all data which refers to the vertices by their index must use the same order.
+//! [`BrainMesh::reorder_vertices`] returns a [`VertexPermutation`] with the new order, which can be applied to the curv data, parcellations
+//! and labels of the mesh to keep them consistent, e.g., after sorting the vertices for cache-friendly access with [`BrainMesh::vertex_order_by_faces`],
+//! or to match the vertex order used by an external tool.
+//!
+//! [`BrainMesh::optimize_vertex_cache`] reorders the faces and vertices of a mesh for the post-transform vertex cache of GPUs, which
+//! makes rendering the exported meshes, e.g., with [`BrainMesh::to_gltf`], faster. Use [`BrainMesh::average_cache_miss_ratio`] to
+//! measure the effect.
+
+
+use std::convert::TryFrom;
+
+use crate::fs_surface::BrainMesh;
+use crate::fs_curv::FsCurv;
+use crate::fs_annot::FsAnnot;
+use crate::fs_label::FsLabel;
+use crate::error::{NeuroformatsError, Result};
+
+
+/// The default size of the simulated vertex cache, in vertices. This is a common size of the post-transform vertex cache of current GPUs.
+pub const DEFAULT_VERTEX_CACHE_SIZE: usize = 32;
+
+
+/// A permutation of the vertices of a mesh, which maps between the old and the new vertex indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexPermutation {
+    new_to_old: Vec<usize>,
+    old_to_new: Vec<usize>,
+}
+
+
+impl VertexPermutation {
+    /// Create a permutation from the new order of the vertices, i.e., the old index of each new vertex.
+    ///
+    /// # Return value
+    ///
+    /// The permutation, or [`NeuroformatsError::InvalidVertexPermutation`] if `new_order` does not contain each index from `0` to its length
+    /// minus one exactly once.
+    pub fn new(new_order: Vec<usize>) -> Result<VertexPermutation> {
+        let mut old_to_new = vec![usize::MAX; new_order.len()];
+        for (new_idx, old_idx) in new_order.iter().enumerate() {
+            match old_to_new.get_mut(*old_idx) {
+                Some(entry) if *entry == usize::MAX => *entry = new_idx,
+                Some(_) => return Err(NeuroformatsError::InvalidVertexPermutation(format!("vertex {} occurs more than once", old_idx))),
+                None => return Err(NeuroformatsError::InvalidVertexPermutation(format!("vertex {} is out of range for {} vertices", old_idx, new_order.len()))),
+            }
+        }
+        Ok(VertexPermutation { new_to_old: new_order, old_to_new })
+    }
+
+
+    /// Create the identity permutation for the given number of vertices, which keeps the order.
+    pub fn identity(num_vertices: usize) -> VertexPermutation {
+        VertexPermutation { new_to_old: (0..num_vertices).collect(), old_to_new: (0..num_vertices).collect() }
+    }
+
+
+    /// The number of vertices.
+    pub fn len(&self) -> usize {
+        self.new_to_old.len()
+    }
+
+
+    /// Whether the permutation is for zero vertices.
+    pub fn is_empty(&self) -> bool {
+        self.new_to_old.is_empty()
+    }
+
+
+    /// The old index of each new vertex, i.e., the new order of the vertices.
+    pub fn new_to_old(&self) -> &[usize] {
+        &self.new_to_old
+    }
+
+
+    /// The new index of each old vertex.
+    pub fn old_to_new(&self) -> &[usize] {
+        &self.old_to_new
+    }
+
+
+    /// Get the inverse permutation, which restores the old order.
+    pub fn inverse(&self) -> VertexPermutation {
+        VertexPermutation { new_to_old: self.old_to_new.clone(), old_to_new: self.new_to_old.clone() }
+    }
+
+
+    /// Reorder per-vertex values, given in the old vertex order.
+    ///
+    /// # Return value
+    ///
+    /// The values in the new vertex order, or [`NeuroformatsError::VertexCountMismatch`] if `values` does not contain one value per vertex.
+    pub fn apply<T: Clone>(&self, values: &[T]) -> Result<Vec<T>> {
+        if values.len() != self.len() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.len(), values.len()));
+        }
+        Ok(self.new_to_old.iter().map(|old_idx| values[*old_idx].clone()).collect())
+    }
+
+
+    /// Reorder per-vertex curv data, given in the old vertex order. See [`VertexPermutation::apply`].
+    pub fn apply_to_curv(&self, curv: &FsCurv) -> Result<FsCurv> {
+        Ok(FsCurv { header: curv.header.clone(), data: self.apply(&curv.data)? })
+    }
+
+
+    /// Reorder a parcellation, given in the old vertex order. See [`VertexPermutation::apply`].
+    ///
+    /// The vertex indices of the result are `0` to the number of vertices minus one, as in annot files written by FreeSurfer.
+    pub fn apply_to_annot(&self, annot: &FsAnnot) -> Result<FsAnnot> {
+        let vertex_labels = self.apply(&annot.vertex_labels)?;
+        Ok(FsAnnot::new((0..self.len() as i32).collect(), vertex_labels, annot.colortable.clone()))
+    }
+
+
+    /// Change the vertex indices of a label from the old to the new vertex order. The order of the label vertices is kept.
+    ///
+    /// # Return value
+    ///
+    /// The label with the new vertex indices, or [`NeuroformatsError::VertexIndexOutOfRange`] if the label contains a vertex which is not
+    /// part of the permutation.
+    pub fn apply_to_label(&self, label: &FsLabel) -> Result<FsLabel> {
+        let mut label = label.clone();
+        for vertex in label.vertexes.iter_mut() {
+            let new_idx = usize::try_from(vertex.index).ok().and_then(|old_idx| self.old_to_new.get(old_idx))
+                .ok_or(NeuroformatsError::VertexIndexOutOfRange(vertex.index as i64, self.len()))?;
+            vertex.index = *new_idx as i32;
+        }
+        Ok(label)
+    }
+}
+
+
+impl BrainMesh {
+    /// Reorder the vertices of this mesh and re-index the faces accordingly. The order of the faces is kept.
+    ///
+    /// # Parameters
+    ///
+    /// * `new_order`: the old index of each new vertex, e.g., from [`BrainMesh::vertex_order_by_faces`].
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the reordered mesh and the [`VertexPermutation`], which can be used to reorder per-vertex data, parcellations and labels of
+    /// this mesh. Returns [`NeuroformatsError::InvalidVertexPermutation`] if `new_order` is not a permutation of the vertex indices of this mesh,
+    /// and an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// let (mesh, permutation) = surf.mesh.reorder_vertices(&surf.mesh.vertex_order_by_faces()).unwrap();
+    /// let thickness = permutation.apply_to_curv(&thickness).unwrap();
+    /// ```
+    pub fn reorder_vertices(&self, new_order: &[usize]) -> Result<(BrainMesh, VertexPermutation)> {
+        self.validate_faces()?;
+        if new_order.len() != self.num_vertices() {
+            return Err(NeuroformatsError::InvalidVertexPermutation(format!("expected {} vertices, but found {}", self.num_vertices(), new_order.len())));
+        }
+        let permutation = VertexPermutation::new(new_order.to_vec())?;
+        let vertices = permutation.new_to_old.iter().flat_map(|old_idx| self.vertices_3()[*old_idx]).collect();
+        let faces = self.faces.iter().map(|v| permutation.old_to_new[*v as usize] as i32).collect();
+        Ok((BrainMesh { vertices, faces }, permutation))
+    }
+
+
+    /// Compute a vertex order in which the vertices appear in the order of their first use by the faces, for use with [`BrainMesh::reorder_vertices`].
+    ///
+    /// In this order, the vertices of consecutive faces are close together in memory, which makes algorithms which iterate over the faces
+    /// more cache-friendly. Vertices which are not used by any face are placed at the end, in their current order.
+    ///
+    /// # Panics
+    ///
+    /// If the faces contain invalid vertex indices, see [`BrainMesh::validate_faces`].
+    pub fn vertex_order_by_faces(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.num_vertices()];
+        let mut order = Vec::with_capacity(self.num_vertices());
+        for v in self.faces.iter().map(|v| usize::try_from(*v).expect("Face contains negative vertex index.")) {
+            if !seen[v] {
+                seen[v] = true;
+                order.push(v);
+            }
+        }
+        order.extend((0..self.num_vertices()).filter(|v| !seen[*v]));
+        order
+    }
+
+
+    /// Compute a face order which optimizes the use of a GPU vertex cache, using Tom Forsyth's linear-speed vertex cache optimization.
+    ///
+    /// The algorithm greedily picks the next face from the faces of the vertices which are in a simulated LRU cache, preferring
+    /// vertices which are recently used and vertices with few remaining faces, so that consecutive faces share many vertices.
+    ///
+    /// # Parameters
+    ///
+    /// * `cache_size`: the size of the simulated vertex cache, in vertices, e.g., [`DEFAULT_VERTEX_CACHE_SIZE`]. Must be at least 4.
+    ///
+    /// # Return value
+    ///
+    /// The old index of each face in the new order.
+    ///
+    /// # Panics
+    ///
+    /// If `cache_size` is less than 4, or if the faces contain invalid vertex indices, see [`BrainMesh::validate_faces`].
+    pub fn face_order_for_vertex_cache(&self, cache_size: usize) -> Vec<usize> {
+        assert!(cache_size > 3, "The cache size must be at least 4.");
+        let num_faces = self.num_faces();
+        let faces: Vec<[usize; 3]> = self.faces_3().iter()
+            .map(|f| f.map(|v| usize::try_from(v).expect("Face contains negative vertex index."))).collect();
+
+        // The faces of each vertex, in compressed form: the faces of vertex `v` are `vertex_faces[offsets[v]..offsets[v + 1]]`.
+        let mut offsets = vec![0usize; self.num_vertices() + 1];
+        for v in faces.iter().flatten() {
+            offsets[*v + 1] += 1;
+        }
+        for v in 0..self.num_vertices() {
+            offsets[v + 1] += offsets[v];
+        }
+        let mut vertex_faces = vec![0usize; offsets[self.num_vertices()]];
+        let mut fill = offsets.clone();
+        for (face_idx, face) in faces.iter().enumerate() {
+            for v in face.iter() {
+                vertex_faces[fill[*v]] = face_idx;
+                fill[*v] += 1;
+            }
+        }
+
+        let mut remaining: Vec<usize> = (0..self.num_vertices()).map(|v| offsets[v + 1] - offsets[v]).collect();
+        let mut cache_pos: Vec<Option<usize>> = vec![None; self.num_vertices()];
+        let mut vertex_scores: Vec<f32> = remaining.iter().map(|r| forsyth_vertex_score(None, *r, cache_size)).collect();
+        let mut face_scores: Vec<f32> = faces.iter().map(|f| f.iter().map(|v| vertex_scores[*v]).sum()).collect();
+        let mut emitted = vec![false; num_faces];
+        let mut cache: Vec<usize> = Vec::with_capacity(cache_size + 3);
+        let mut order = Vec::with_capacity(num_faces);
+        let mut next_unemitted = 0;
+        let mut best: Option<usize> = None;
+
+        while order.len() < num_faces {
+            let face_idx = match best {
+                Some(face_idx) => face_idx,
+                None => {
+                    // No face of a cached vertex is left, so start over at the best face which has not been emitted yet.
+                    while emitted[next_unemitted] {
+                        next_unemitted += 1;
+                    }
+                    (next_unemitted..num_faces).filter(|f| !emitted[*f])
+                        .fold(next_unemitted, |best, f| if face_scores[f] > face_scores[best] { f } else { best })
+                }
+            };
+            emitted[face_idx] = true;
+            order.push(face_idx);
+
+            // Move the vertices of the face to the front of the cache.
+            let face = faces[face_idx];
+            for v in face.iter() {
+                remaining[*v] -= 1;
+            }
+            cache.retain(|v| !face.contains(v));
+            cache.splice(0..0, face.iter().copied());
+            for (pos, v) in cache.iter().enumerate() {
+                cache_pos[*v] = if pos < cache_size { Some(pos) } else { None };
+            }
+
+            // Update the scores of the cached and evicted vertices and their faces, and pick the best of these faces.
+            best = None;
+            for v in cache.iter() {
+                vertex_scores[*v] = forsyth_vertex_score(cache_pos[*v], remaining[*v], cache_size);
+            }
+            for v in cache.iter() {
+                for f in vertex_faces[offsets[*v]..offsets[*v + 1]].iter().filter(|f| !emitted[**f]) {
+                    face_scores[*f] = faces[*f].iter().map(|v| vertex_scores[*v]).sum();
+                    if best.is_none_or(|b| face_scores[*f] > face_scores[b]) {
+                        best = Some(*f);
+                    }
+                }
+            }
+            cache.truncate(cache_size);
+        }
+        order
+    }
+
+
+    /// Reorder the faces and vertices of this mesh for the post-transform vertex cache of GPUs, see [`BrainMesh::face_order_for_vertex_cache`].
+    ///
+    /// The faces are reordered first, and the vertices are then sorted by their first use in the new face order, see [`BrainMesh::vertex_order_by_faces`].
+    ///
+    /// # Parameters
+    ///
+    /// * `cache_size`: the size of the simulated vertex cache, in vertices, e.g., [`DEFAULT_VERTEX_CACHE_SIZE`]. Must be at least 4.
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the reordered mesh and the [`VertexPermutation`], which can be used to reorder per-vertex data, parcellations and labels of
+    /// this mesh, or an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Panics
+    ///
+    /// If `cache_size` is less than 4.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::reorder::DEFAULT_VERTEX_CACHE_SIZE;
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let (mesh, _) = surf.mesh.optimize_vertex_cache(DEFAULT_VERTEX_CACHE_SIZE).unwrap();
+    /// assert!(mesh.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE) < surf.mesh.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE));
+    /// ```
+    pub fn optimize_vertex_cache(&self, cache_size: usize) -> Result<(BrainMesh, VertexPermutation)> {
+        self.validate_faces()?;
+        let faces = self.face_order_for_vertex_cache(cache_size).iter().flat_map(|f| self.faces_3()[*f]).collect();
+        let mesh = BrainMesh { vertices: self.vertices.clone(), faces };
+        mesh.reorder_vertices(&mesh.vertex_order_by_faces())
+    }
+
+
+    /// Compute the average cache miss ratio (ACMR) of the faces of this mesh, i.e., the number of vertices which have to be transformed
+    /// per face when the faces are rendered in order with a FIFO vertex cache of the given size.
+    ///
+    /// The ratio is between `0.5` for very large regular meshes with an optimal face order and `3.0` if no vertex is ever reused from the cache.
+    /// Returns `0.0` for meshes without faces.
+    ///
+    /// # Panics
+    ///
+    /// If the faces contain invalid vertex indices, see [`BrainMesh::validate_faces`].
+    pub fn average_cache_miss_ratio(&self, cache_size: usize) -> f64 {
+        if self.num_faces() == 0 {
+            return 0.0;
+        }
+        // The number of misses before each vertex was last added to the cache. A vertex is in the FIFO cache if less than `cache_size`
+        // vertices have been added since.
+        let mut added_at: Vec<Option<usize>> = vec![None; self.num_vertices()];
+        let mut misses = 0usize;
+        for v in self.faces.iter().map(|v| usize::try_from(*v).expect("Face contains negative vertex index.")) {
+            if added_at[v].is_none_or(|added| misses - added >= cache_size) {
+                added_at[v] = Some(misses);
+                misses += 1;
+            }
+        }
+        misses as f64 / self.num_faces() as f64
+    }
+}
+
+
+/// Compute the score of a vertex for [`BrainMesh::face_order_for_vertex_cache`], from its position in the cache and its number of faces which
+/// have not been emitted yet. The constants are the ones suggested by Forsyth.
+fn forsyth_vertex_score(cache_pos: Option<usize>, remaining_faces: usize, cache_size: usize) -> f32 {
+    if remaining_faces == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_pos {
+        // The vertices of the last face get a fixed score, so that the algorithm does not prefer to reuse the same edge over and over.
+        Some(pos) if pos < 3 => 0.75,
+        Some(pos) => (1.0 - (pos - 3) as f32 / (cache_size - 3) as f32).powf(1.5),
+        None => 0.0,
+    };
+    cache_score + 2.0 * (remaining_faces as f32).powf(-0.5)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::{read_surf, read_curv, read_annot, read_label};
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_mesh_and_its_data_can_be_reordered_consistently() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let thickness = read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let label = read_label("resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").unwrap();
+
+        let order = surf.mesh.vertex_order_by_faces();
+        let (mesh, permutation) = surf.mesh.reorder_vertices(&order).unwrap();
+        assert_eq!(surf.mesh.num_vertices(), mesh.num_vertices());
+        assert_eq!(surf.mesh.face(100).map(|v| permutation.old_to_new()[v]), mesh.face(100));
+        assert_eq!(surf.mesh.vertex_coords(order[5]), mesh.vertex_coords(5));
+
+        let new_thickness = permutation.apply_to_curv(&thickness).unwrap();
+        let new_annot = permutation.apply_to_annot(&annot).unwrap();
+        let new_label = permutation.apply_to_label(&label).unwrap();
+        let v = label.vertexes[3].index as usize;
+        let new_v = new_label.vertexes[3].index as usize;
+        assert_eq!(permutation.old_to_new()[v], new_v);
+        assert_eq!(thickness.data[v], new_thickness.data[new_v]);
+        assert_eq!(annot.vertex_regions()[v], new_annot.vertex_regions()[new_v]);
+        assert_eq!(surf.mesh.vertex_coords(v), mesh.vertex_coords(new_v));
+
+        let (restored, _) = mesh.reorder_vertices(permutation.inverse().new_to_old()).unwrap();
+        assert_eq!(surf.mesh, restored);
+        assert_eq!(thickness, permutation.inverse().apply_to_curv(&new_thickness).unwrap());
+    }
+
+    #[test]
+    fn invalid_permutations_are_rejected() {
+        assert!(matches!(VertexPermutation::new(vec![0, 0, 1]), Err(NeuroformatsError::InvalidVertexPermutation(_))));
+        assert!(matches!(VertexPermutation::new(vec![0, 3, 1]), Err(NeuroformatsError::InvalidVertexPermutation(_))));
+        assert_eq!(VertexPermutation::identity(3), VertexPermutation::new(vec![0, 1, 2]).unwrap());
+
+        let mesh = BrainMesh { vertices: vec![0.0; 9], faces: vec![0, 1, 2] };
+        assert!(matches!(mesh.reorder_vertices(&[1, 0]), Err(NeuroformatsError::InvalidVertexPermutation(_))));
+        let permutation = VertexPermutation::new(vec![2, 0, 1]).unwrap();
+        assert!(matches!(permutation.apply(&[1.0, 2.0]), Err(NeuroformatsError::VertexCountMismatch(3, 2))));
+        assert_eq!(vec!['c', 'a', 'b'], permutation.apply(&['a', 'b', 'c']).unwrap());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_vertex_cache_optimization_reduces_cache_misses() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let (mesh, permutation) = surf.mesh.optimize_vertex_cache(DEFAULT_VERTEX_CACHE_SIZE).unwrap();
+        assert_eq!(surf.mesh.num_faces(), mesh.num_faces());
+        assert_eq!(surf.mesh.vertex_coords(permutation.new_to_old()[7]), mesh.vertex_coords(7));
+
+        let mut old_faces: Vec<[usize; 3]> = surf.mesh.iter_faces().map(|f| f.map(|v| permutation.old_to_new()[v])).collect();
+        let mut new_faces: Vec<[usize; 3]> = mesh.iter_faces().collect();
+        old_faces.sort_unstable();
+        new_faces.sort_unstable();
+        assert_eq!(old_faces, new_faces);
+
+        let acmr_before = surf.mesh.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE);
+        let acmr_after = mesh.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE);
+        assert!(acmr_after < acmr_before);
+        assert!(acmr_after < 0.8);
+
+        let triangle = BrainMesh { vertices: vec![0.0; 9], faces: vec![0, 1, 2] };
+        assert_eq!(3.0, triangle.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE));
+        assert_eq!(0.0, BrainMesh { vertices: Vec::new(), faces: Vec::new() }.average_cache_miss_ratio(DEFAULT_VERTEX_CACHE_SIZE));
+    }
+}
@@ -0,0 +1,160 @@
+//! Functions for resampling per-vertex data between the surfaces of different subjects.
+//!
+//! Resampling relies on spherical registration: each subject's `?h.sphere.reg` surface is aligned to
+//! a template sphere (e.g., fsaverage's `?h.sphere`), so the vertex coordinates on the two spheres
+//! are directly comparable. This is the core of what FreeSurfer's `mri_surf2surf` does.
+
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::{vec3_sub as sub, vec3_dot as dot, vec3_cross as cross};
+
+
+/// The interpolation method used when resampling data from one sphere to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleMethod {
+    /// Assign each target vertex the value of the closest source vertex.
+    NearestNeighbor,
+    /// Interpolate the values of the source triangle that contains the target vertex, using barycentric weights. Falls back to the nearest neighbor if no containing triangle is found.
+    Barycentric,
+}
+
+
+/// Resample per-vertex data from a source sphere onto a target sphere.
+///
+/// # Parameters
+///
+/// * `source_sphere`: the registered sphere of the source subject, typically `?h.sphere.reg`.
+/// * `target_sphere`: the sphere of the target subject, typically `?h.sphere` of fsaverage.
+/// * `source_data`: the per-vertex data for the source subject, one value per vertex of `source_sphere`.
+/// * `method`: the interpolation method, see [`ResampleMethod`].
+///
+/// Both spheres must be centered at the origin, as is the case for FreeSurfer spheres.
+///
+/// # Return value
+///
+/// The resampled data, one value per vertex of `target_sphere`. All values are `NAN` if the source sphere has no vertices. Returns [`NeuroformatsError::VertexCountMismatch`] if the length of `source_data` does not match the vertex count of `source_sphere`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let src_sphere = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.sphere.reg").unwrap();
+/// let fsavg_sphere = neuroformats::read_surf("/path/to/subjects_dir/fsaverage/surf/lh.sphere").unwrap();
+/// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let thickness_fsavg = neuroformats::resample::resample(&src_sphere.mesh, &fsavg_sphere.mesh, &thickness.data, neuroformats::resample::ResampleMethod::Barycentric).unwrap();
+/// ```
+pub fn resample(source_sphere: &BrainMesh, target_sphere: &BrainMesh, source_data: &[f32], method: ResampleMethod) -> Result<Vec<f32>> {
+    if source_data.len() != source_sphere.num_vertices() {
+        return Err(NeuroformatsError::VertexCountMismatch(source_sphere.num_vertices(), source_data.len()));
+    }
+
+    let index = source_sphere.build_spatial_index();
+    let vertex_faces = match method {
+        ResampleMethod::Barycentric => source_sphere.vertex_faces(),
+        ResampleMethod::NearestNeighbor => Vec::new(),
+    };
+    let vertex_neighbors = match method {
+        ResampleMethod::Barycentric => source_sphere.vertex_neighbors(),
+        ResampleMethod::NearestNeighbor => Vec::new(),
+    };
+
+    let mut resampled: Vec<f32> = Vec::with_capacity(target_sphere.num_vertices());
+    for target_vertex in 0..target_sphere.num_vertices() {
+        let point = target_sphere.vertex_coords(target_vertex);
+        let nearest = match index.nearest_vertex(point) {
+            Some((vertex, _)) => vertex,
+            None => { // The source sphere has no vertices.
+                resampled.push(f32::NAN);
+                continue;
+            },
+        };
+
+        let value = match method {
+            ResampleMethod::NearestNeighbor => source_data[nearest],
+            ResampleMethod::Barycentric => {
+                let candidate_faces = std::iter::once(&nearest)
+                    .chain(vertex_neighbors[nearest].iter())
+                    .flat_map(|v| vertex_faces[*v].iter());
+                let mut value = source_data[nearest];
+                for face in candidate_faces {
+                    let fv = &source_sphere.faces[face * 3..face * 3 + 3];
+                    let (v0, v1, v2) = (fv[0] as usize, fv[1] as usize, fv[2] as usize);
+                    if let Some(w) = ray_triangle_barycentric(point, source_sphere.vertex_coords(v0), source_sphere.vertex_coords(v1), source_sphere.vertex_coords(v2)) {
+                        value = w[0] * source_data[v0] + w[1] * source_data[v1] + w[2] * source_data[v2];
+                        break;
+                    }
+                }
+                value
+            },
+        };
+        resampled.push(value);
+    }
+    Ok(resampled)
+}
+
+
+/// Compute the barycentric weights of the point where the ray from the origin through `dir` hits the triangle `a`, `b`, `c`.
+///
+/// Returns `None` if the ray misses the triangle. Uses the Möller–Trumbore algorithm.
+pub(crate) fn ray_triangle_barycentric(dir: [f32; 3], a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Option<[f32; 3]> {
+    const EPS: f32 = 1e-9;
+    let e1 = sub(b, a);
+    let e2 = sub(c, a);
+    let p = cross(dir, e2);
+    let det = dot(e1, p);
+    if det.abs() < EPS {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = [-a[0], -a[1], -a[2]]; // ray origin minus a, the origin is (0, 0, 0).
+    let u = dot(s, p) * inv_det;
+    if !(-1e-6..=1.0 + 1e-6).contains(&u) {
+        return None;
+    }
+    let q = cross(s, e1);
+    let v = dot(dir, q) * inv_det;
+    if v < -1e-6 || u + v > 1.0 + 1e-6 {
+        return None;
+    }
+    let t = dot(e2, q) * inv_det;
+    if t <= 0.0 {
+        return None;
+    }
+    Some([1.0 - u - v, u, v])
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An octahedron with 6 vertices on the unit sphere.
+    fn octahedron() -> BrainMesh {
+        BrainMesh {
+            vertices: vec![1.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, -1.0],
+            faces: vec![0, 2, 4, 2, 1, 4, 1, 3, 4, 3, 0, 4, 2, 0, 5, 1, 2, 5, 3, 1, 5, 0, 3, 5],
+        }
+    }
+
+    #[test]
+    fn data_can_be_resampled_between_spheres() {
+        let source = octahedron();
+        let data: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let s = 1.0 / 3.0f32.sqrt();
+        let target = BrainMesh { vertices: vec![1.0, 0.0, 0.0, s, s, s], faces: vec![] };
+
+        let nn = resample(&source, &target, &data, ResampleMethod::NearestNeighbor).unwrap();
+        assert_eq!(1.0, nn[0]);
+
+        let bary = resample(&source, &target, &data, ResampleMethod::Barycentric).unwrap();
+        assert!((bary[0] - 1.0).abs() < 1e-5);
+        assert!((bary[1] - 3.0).abs() < 1e-5); // center of face (0, 2, 4): mean of 1, 3 and 5.
+    }
+
+    #[test]
+    fn resampling_with_wrong_data_length_fails() {
+        let source = octahedron();
+        let result = resample(&source, &source, &[1.0, 2.0], ResampleMethod::NearestNeighbor);
+        assert!(matches!(result, Err(NeuroformatsError::VertexCountMismatch(6, 2))));
+    }
+}
@@ -0,0 +1,267 @@
+//! Extraction of the mean time series of the regions of a parcellation from per-vertex time series, like resting-state fMRI data
+//! sampled to the surface.
+//!
+//! The region time series are the usual first step of surface-based connectivity analyses: correlating the time series of all pairs
+//! of regions gives a connectivity matrix, see [`connectivity_matrix`].
+
+
+use ndarray::{Array2, Axis};
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_annot::FsAnnot;
+use crate::fs_label::FsLabel;
+use crate::fs_mgh::FsMgh;
+
+
+/// Options for extracting region time series with [`extract_roi_timeseries_with_options`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoiTimeseriesOptions<'a> {
+    /// Optional cortex label, like `label/?h.cortex.label`. Vertices outside of it, i.e., the medial wall, are ignored.
+    pub exclude_medial_wall: Option<&'a FsLabel>,
+}
+
+
+/// The correlation coefficient used by [`connectivity_matrix`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorrelationMethod {
+    /// The Pearson correlation coefficient, which measures linear relations.
+    Pearson,
+    /// The Spearman rank correlation coefficient, which measures monotonic relations and is less sensitive to outliers. Tied values
+    /// get the mean of their ranks.
+    Spearman,
+}
+
+
+/// A symmetric matrix of the functional connectivity between the regions of a parcellation, see [`connectivity_matrix`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectivityMatrix {
+    /// The names of the regions, in the order of the rows and columns of the matrix.
+    pub regions: Vec<String>,
+    /// The correlation between the time series of each pair of regions. It is NaN for regions whose time series contain NaN values
+    /// or have no variance.
+    pub values: Array2<f32>,
+}
+
+
+impl ConnectivityMatrix {
+    /// Get the correlation between the regions with the given names, or `None` if there is no region with one of the names.
+    pub fn get(&self, region_a: &str, region_b: &str) -> Option<f32> {
+        let a = self.regions.iter().position(|name| name == region_a)?;
+        let b = self.regions.iter().position(|name| name == region_b)?;
+        Some(self.values[[a, b]])
+    }
+}
+
+
+/// Compute the mean time series of each region of a parcellation.
+///
+/// # Parameters
+///
+/// * `mgh`: the per-vertex time series, an MGH volume with dimensions (number of vertices, 1, 1, number of frames), like the output of
+///   FreeSurfer's `mri_vol2surf` for a 4D fMRI volume.
+/// * `annot`: the parcellation of the same surface.
+///
+/// # Return value
+///
+/// A matrix with one row per region of the colortable, in colortable order, and one column per frame. The mean of a region and frame
+/// ignores NaN values, and is NaN if the region has no vertices with values besides NaN values. Vertices with a label that does not
+/// match any region are ignored. Returns [`NeuroformatsError::VertexCountMismatch`] if the number of voxels per frame of `mgh` differs
+/// from the number of vertices of `annot`, and [`NeuroformatsError::UnsupportedMriDataTypeInMgh`] if the data of `mgh` is not set.
+///
+/// # Examples
+///
+/// ```no_run
+/// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+/// let bold = neuroformats::read_mgh("/path/to/subjects_dir/subject1/surf/lh.rest.fsnative.mgh").unwrap();
+/// let timeseries = neuroformats::roi::extract_roi_timeseries(&bold, &annot).unwrap();
+/// assert_eq!(annot.num_regions(), timeseries.nrows());
+/// ```
+pub fn extract_roi_timeseries(mgh: &FsMgh, annot: &FsAnnot) -> Result<Array2<f32>> {
+    extract_roi_timeseries_with_options(mgh, annot, &RoiTimeseriesOptions::default())
+}
+
+
+/// Compute the mean time series of each region of a parcellation with the given [`RoiTimeseriesOptions`].
+///
+/// See [`extract_roi_timeseries`] for details.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::roi::{extract_roi_timeseries_with_options, RoiTimeseriesOptions};
+/// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+/// let cortex = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.cortex.label").unwrap();
+/// let bold = neuroformats::read_mgh("/path/to/subjects_dir/subject1/surf/lh.rest.fsnative.mgh").unwrap();
+/// let options = RoiTimeseriesOptions { exclude_medial_wall: Some(&cortex) };
+/// let timeseries = extract_roi_timeseries_with_options(&bold, &annot, &options).unwrap();
+/// ```
+pub fn extract_roi_timeseries_with_options(mgh: &FsMgh, annot: &FsAnnot, options: &RoiTimeseriesOptions) -> Result<Array2<f32>> {
+    let num_vertices = annot.vertex_labels.len();
+    if mgh.header.num_voxels_per_frame() != num_vertices {
+        return Err(NeuroformatsError::VertexCountMismatch(num_vertices, mgh.header.num_voxels_per_frame()));
+    }
+    let in_cortex = options.exclude_medial_wall.map(|cortex| cortex.vertex_mask(num_vertices));
+    let lookup = annot.region_lookup();
+    let vertex_regions: Vec<Option<usize>> = annot.vertex_labels.iter().enumerate().map(|(vidx, label)| {
+        match in_cortex.as_ref() {
+            Some(in_cortex) if !in_cortex[vidx] => None,
+            _ => lookup.region_index(*label),
+        }
+    }).collect();
+
+    let frames = mgh.frames_as_f32()?;
+    let num_regions = annot.colortable.regions.len();
+    let mut timeseries = Array2::from_elem((num_regions, frames.len()), f32::NAN);
+    for (frame_idx, frame) in frames.iter().enumerate() {
+        let mut sums = vec![0.0f64; num_regions];
+        let mut counts = vec![0usize; num_regions];
+        for (region_idx, value) in vertex_regions.iter().zip(frame.iter()) {
+            if let Some(region_idx) = region_idx {
+                if !value.is_nan() {
+                    sums[*region_idx] += *value as f64;
+                    counts[*region_idx] += 1;
+                }
+            }
+        }
+        for (region_idx, (sum, count)) in sums.iter().zip(counts.iter()).enumerate() {
+            if *count > 0 {
+                timeseries[[region_idx, frame_idx]] = (sum / *count as f64) as f32;
+            }
+        }
+    }
+    Ok(timeseries)
+}
+
+
+/// Compute the functional connectivity matrix of region time series, i.e., the correlation of the time series of each pair of regions.
+///
+/// # Parameters
+///
+/// * `timeseries`: the time series of the regions, one row per region and one column per frame, like the result of [`extract_roi_timeseries`].
+/// * `regions`: the names of the regions, one per row of `timeseries`, like the result of [`FsAnnot::regions`].
+/// * `method`: the correlation coefficient to compute.
+///
+/// # Return value
+///
+/// The connectivity matrix, or [`NeuroformatsError::RegionCountMismatch`] if the number of `regions` differs from the number of rows of
+/// `timeseries`. Regions whose time series contain NaN values, e.g., regions without vertices, or have no variance get NaN correlations.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::roi::{connectivity_matrix, extract_roi_timeseries, CorrelationMethod};
+/// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+/// let bold = neuroformats::read_mgh("/path/to/subjects_dir/subject1/surf/lh.rest.fsnative.mgh").unwrap();
+/// let timeseries = extract_roi_timeseries(&bold, &annot).unwrap();
+/// let connectome = connectivity_matrix(&timeseries, annot.regions(), CorrelationMethod::Pearson).unwrap();
+/// println!("Precuneus - posterior cingulate: {:?}", connectome.get("precuneus", "posteriorcingulate"));
+/// ```
+pub fn connectivity_matrix(timeseries: &Array2<f32>, regions: Vec<String>, method: CorrelationMethod) -> Result<ConnectivityMatrix> {
+    if regions.len() != timeseries.nrows() {
+        return Err(NeuroformatsError::RegionCountMismatch(timeseries.nrows(), regions.len()));
+    }
+    // Standardize each row, so the correlation of two rows is the mean of the products of their values.
+    let standardized: Vec<Option<Vec<f64>>> = timeseries.axis_iter(Axis(0)).map(|row| {
+        if row.iter().any(|v| v.is_nan()) {
+            return None;
+        }
+        let values: Vec<f64> = match method {
+            CorrelationMethod::Pearson => row.iter().map(|v| *v as f64).collect(),
+            CorrelationMethod::Spearman => ranks(&row.to_vec()),
+        };
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+        (std > 0.0).then(|| values.iter().map(|v| (v - mean) / std).collect())
+    }).collect();
+
+    let num_regions = regions.len();
+    let mut values = Array2::from_elem((num_regions, num_regions), f32::NAN);
+    for a in 0..num_regions {
+        for b in a..num_regions {
+            if let (Some(row_a), Some(row_b)) = (&standardized[a], &standardized[b]) {
+                let r = row_a.iter().zip(row_b.iter()).map(|(x, y)| x * y).sum::<f64>() / row_a.len() as f64;
+                values[[a, b]] = r.clamp(-1.0, 1.0) as f32;
+                values[[b, a]] = values[[a, b]];
+            }
+        }
+    }
+    Ok(ConnectivityMatrix { regions, values })
+}
+
+
+/// Compute the ranks of values, starting at `1.0`. Tied values get the mean of their ranks.
+fn ranks(values: &[f32]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let end = (start..order.len()).find(|idx| values[order[*idx]] != values[order[start]]).unwrap_or(order.len());
+        let rank = (start + end + 1) as f64 / 2.0;
+        for idx in order[start..end].iter() {
+            ranks[*idx] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs_mgh::FsMghHeader;
+
+    #[test]
+    fn the_mean_time_series_of_regions_can_be_extracted() {
+        // Regions 0 and 1 and an unassigned vertex.
+        let annot = FsAnnot::from_label_map(&[0, 0, 1, 1, 1, -1], None).unwrap();
+        let header = FsMghHeader { dim1len: 6, dim2len: 1, dim3len: 1, dim4len: 2, ..FsMghHeader::default() };
+        let values = vec![1.0, 3.0, 2.0, f32::NAN, 4.0, 100.0, 10.0, 30.0, 20.0, 20.0, 20.0, 100.0];
+        let mgh = FsMgh::from_f32_values(&header, values).unwrap();
+
+        let timeseries = extract_roi_timeseries(&mgh, &annot).unwrap();
+        assert_eq!((2, 2), timeseries.dim());
+        assert_eq!(vec![2.0, 20.0, 3.0, 20.0], timeseries.iter().copied().collect::<Vec<f32>>());
+
+        let cortex = FsLabel::from_bytes(b"# label\n2\n0 0.0 0.0 0.0 0.0\n1 0.0 0.0 0.0 0.0\n").unwrap();
+        let options = RoiTimeseriesOptions { exclude_medial_wall: Some(&cortex) };
+        let timeseries = extract_roi_timeseries_with_options(&mgh, &annot, &options).unwrap();
+        assert_eq!(2.0, timeseries[[0, 0]]);
+        assert!(timeseries[[1, 0]].is_nan());
+
+        let short = FsMgh::from_f32_values(&FsMghHeader { dim1len: 5, ..header }, vec![0.0; 10]).unwrap();
+        assert!(matches!(extract_roi_timeseries(&short, &annot), Err(NeuroformatsError::VertexCountMismatch(6, 5))));
+    }
+
+    #[test]
+    fn connectivity_matrices_can_be_computed() {
+        let timeseries = ndarray::arr2(&[
+            [1.0, 2.0, 3.0, 4.0],
+            [2.0, 4.0, 6.0, 100.0],
+            [4.0, 3.0, 2.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0],
+            [1.0, f32::NAN, 1.0, 2.0],
+        ]);
+        let regions: Vec<String> = ["a", "b", "c", "flat", "empty"].iter().map(|name| name.to_string()).collect();
+
+        let pearson = connectivity_matrix(&timeseries, regions.clone(), CorrelationMethod::Pearson).unwrap();
+        let finite = pearson.values.slice(ndarray::s![..3, ..3]);
+        assert_eq!(finite, finite.t());
+        assert_eq!(Some(1.0), pearson.get("a", "a"));
+        assert_eq!(Some(-1.0), pearson.get("a", "c"));
+        let a_b = pearson.get("a", "b").unwrap();
+        assert!(a_b > 0.5 && a_b < 1.0);
+        assert!(pearson.get("a", "flat").unwrap().is_nan());
+        assert!(pearson.get("empty", "empty").unwrap().is_nan());
+        assert_eq!(None, pearson.get("a", "unknown"));
+
+        let spearman = connectivity_matrix(&timeseries, regions, CorrelationMethod::Spearman).unwrap();
+        assert_eq!(Some(1.0), spearman.get("a", "b"));
+        assert_eq!(Some(-1.0), spearman.get("b", "c"));
+
+        assert_eq!(vec![1.5, 1.5, 3.0], ranks(&[2.0, 2.0, 5.0]));
+        assert!(matches!(connectivity_matrix(&timeseries, Vec::new(), CorrelationMethod::Pearson), Err(NeuroformatsError::RegionCountMismatch(5, 0))));
+    }
+}
@@ -0,0 +1,112 @@
+//! Reproducible random sampling of mesh vertices.
+//!
+//! Many per-vertex algorithms, like geodesic distance computations, are too expensive to run for all vertices of a brain
+//! mesh, and visualizations often need a sparse set of markers. The functions in this module draw vertex subsets with a
+//! simple seeded random number generator, so the same seed always yields the same vertices on all platforms.
+
+
+use crate::fs_surface::BrainMesh;
+use crate::fs_annot::FsAnnot;
+use crate::util::SplitMix64;
+
+
+/// Draw `n` distinct elements from the candidates with a partial Fisher-Yates shuffle, and return them in ascending order.
+fn sample_from(mut candidates: Vec<usize>, n: usize, rng: &mut SplitMix64) -> Vec<usize> {
+    let n = n.min(candidates.len());
+    for i in 0..n {
+        let j = i + rng.next_below(candidates.len() - i);
+        candidates.swap(i, j);
+    }
+    candidates.truncate(n);
+    candidates.sort_unstable();
+    candidates
+}
+
+
+/// Draw a reproducible random subset of the vertices of a mesh.
+///
+/// Every vertex has the same probability of being drawn, and each vertex is drawn at most once.
+///
+/// # Parameters
+///
+/// * `mesh`: the mesh.
+/// * `n`: the number of vertices to draw. If this exceeds the vertex count of the mesh, all vertices are returned.
+/// * `seed`: the seed of the random number generator. The same seed always yields the same vertices for the same mesh.
+///
+/// # Return value
+///
+/// The indices of the drawn vertices, in ascending order.
+///
+/// # Examples
+///
+/// ```no_run
+/// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let markers = neuroformats::sampling::sample_vertices_uniform(&white.mesh, 100, 42);
+/// assert_eq!(100, markers.len());
+/// ```
+pub fn sample_vertices_uniform(mesh: &BrainMesh, n: usize, seed: u64) -> Vec<usize> {
+    sample_from((0..mesh.num_vertices()).collect(), n, &mut SplitMix64::new(seed))
+}
+
+
+/// Draw a reproducible random subset of vertices from each region of a parcellation.
+///
+/// This ensures that small regions are represented in the sample, which is not guaranteed by [`sample_vertices_uniform`].
+/// Vertices with a label that does not match any region are never drawn.
+///
+/// # Parameters
+///
+/// * `annot`: the parcellation.
+/// * `n_per_region`: the number of vertices to draw per region. Regions with fewer vertices are returned in full.
+/// * `seed`: the seed of the random number generator. The same seed always yields the same vertices for the same parcellation.
+///
+/// # Return value
+///
+/// The indices of the drawn vertices in ascending order for each region, in the order of the regions in the colortable.
+pub fn sample_vertices_stratified(annot: &FsAnnot, n_per_region: usize, seed: u64) -> Vec<Vec<usize>> {
+    let mut region_vertices: Vec<Vec<usize>> = vec![Vec::new(); annot.colortable.regions.len()];
+    let lookup = annot.region_lookup();
+    for (vidx, label) in annot.vertex_labels.iter().enumerate() {
+        if let Some(region) = lookup.region_index(*label) {
+            region_vertices[region].push(vidx);
+        }
+    }
+    let mut rng = SplitMix64::new(seed);
+    region_vertices.into_iter().map(|candidates| sample_from(candidates, n_per_region, &mut rng)).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::subdivision::generate_icosphere;
+    #[cfg(feature = "fs")]
+    use crate::read_annot;
+
+    #[test]
+    fn vertices_can_be_sampled_reproducibly() {
+        let mesh = generate_icosphere(3);
+        let sample = sample_vertices_uniform(&mesh, 50, 42);
+        assert_eq!(50, sample.len());
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        assert!(sample.iter().all(|v| *v < mesh.num_vertices()));
+        assert_eq!(sample, sample_vertices_uniform(&mesh, 50, 42));
+        assert_ne!(sample, sample_vertices_uniform(&mesh, 50, 43));
+        assert_eq!(mesh.num_vertices(), sample_vertices_uniform(&mesh, 1_000_000, 42).len());
+        assert!(sample_vertices_uniform(&mesh, 0, 42).is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn vertices_can_be_sampled_per_region() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let samples = sample_vertices_stratified(&annot, 10, 42);
+        assert_eq!(annot.colortable.regions.len(), samples.len());
+        for (region_idx, sample) in samples.iter().enumerate() {
+            let region_size = annot.vertex_labels.iter().filter(|l| annot.region_index_of_label(**l) == Some(region_idx)).count();
+            assert_eq!(region_size.min(10), sample.len());
+            assert!(sample.iter().all(|v| annot.region_index_of_label(annot.vertex_labels[*v]) == Some(region_idx)));
+        }
+        assert_eq!(samples, sample_vertices_stratified(&annot, 10, 42));
+    }
+}
@@ -0,0 +1,98 @@
+//! Utilities for segmentation volumes, like `aseg.mgz`, in which each voxel value is the integer label of a brain region.
+
+
+use std::collections::BTreeMap;
+
+use crate::color_lut::ColorLut;
+use crate::error::Result;
+use crate::fs_mgh::FsMgh;
+
+
+/// The voxel count and volume of a region in a segmentation volume.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RegionVolume {
+    /// The label of the region, i.e., the voxel value.
+    pub label: i32,
+    /// The name of the region from the color lookup table, or `None` if the label is not in the table.
+    pub name: Option<String>,
+    /// The number of voxels with the label.
+    pub num_voxels: usize,
+    /// The volume of the region in cubic millimeters.
+    pub volume_mm3: f64,
+}
+
+
+/// Compute the voxel counts and volumes of all regions of a segmentation volume, like `mri_segstats` does.
+///
+/// The voxel volume is computed from the voxel sizes in the header. If the header contains no RAS information, voxels of 1 mm³ are assumed.
+/// Only the first frame is used, and voxel values are rounded to the nearest integer label. NaN values are ignored.
+///
+/// # Parameters
+///
+/// * `mgh`: the segmentation volume, e.g., `aseg.mgz`.
+/// * `lut`: the color lookup table used to name the regions, e.g., `FreeSurferColorLUT.txt`.
+///
+/// # Return value
+///
+/// The regions with at least one voxel, sorted by label. This includes label `0`, which is typically the background.
+///
+/// # Examples
+///
+/// ```no_run
+/// let aseg = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/aseg.mgz").unwrap();
+/// let lut = neuroformats::read_color_lut("/path/to/freesurfer/FreeSurferColorLUT.txt").unwrap();
+/// for region in neuroformats::segmentation::segmentation_stats(&aseg, &lut).unwrap() {
+///     println!("{:?}: {} mm³", region.name, region.volume_mm3);
+/// }
+/// ```
+pub fn segmentation_stats(mgh: &FsMgh, lut: &ColorLut) -> Result<Vec<RegionVolume>> {
+    let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+    for value in mgh.frame_as_f32(0)? {
+        if !value.is_nan() {
+            *counts.entry(value.round() as i32).or_insert(0) += 1;
+        }
+    }
+    let voxel_volume = voxel_volume_mm3(mgh);
+    let names = lut.by_label();
+    Ok(counts.into_iter().map(|(label, num_voxels)| RegionVolume {
+        label,
+        name: names.get(&label).map(|entry| entry.name.clone()),
+        num_voxels,
+        volume_mm3: num_voxels as f64 * voxel_volume,
+    }).collect())
+}
+
+
+/// Get the volume of a voxel in cubic millimeters, assuming 1 mm³ if the header contains no RAS information.
+fn voxel_volume_mm3(mgh: &FsMgh) -> f64 {
+    if mgh.header.is_ras_good == 1 {
+        mgh.header.delta.iter().map(|d| *d as f64).product()
+    } else {
+        1.0
+    }
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+    use crate::read_mgh;
+
+    #[test]
+    fn region_volumes_can_be_computed_from_a_segmentation() {
+        let lut = ColorLut::from_bytes(b"0 Unknown 0 0 0 0\n17 Left-Hippocampus 220 216 20 0\n").unwrap();
+        let mut header = read_mgh("resources/mgh/tiny.mgh").unwrap().header;
+        let values: Vec<f32> = (0..27).map(|v| match v % 3 { 0 => 0.0, 1 => 17.0, _ => 99.0 }).collect();
+
+        let regions = segmentation_stats(&FsMgh::from_f32_values(&header, values.clone()).unwrap(), &lut).unwrap();
+        assert_eq!(3, regions.len());
+        assert_eq!(RegionVolume { label: 17, name: Some("Left-Hippocampus".to_string()), num_voxels: 9, volume_mm3: 9.0 }, regions[1]);
+        assert_eq!(None, regions[2].name);
+
+        header.is_ras_good = 1;
+        header.delta = [1.0, 1.0, 2.0];
+        let regions = segmentation_stats(&FsMgh::from_f32_values(&header, values).unwrap(), &lut).unwrap();
+        assert_eq!(18.0, regions[1].volume_mm3);
+    }
+}
@@ -0,0 +1,134 @@
+//! Approximations of the sulcal depth and local gyrification of brain surfaces.
+//!
+//! FreeSurfer computes the local gyrification index (LGI) with an optional pipeline that requires MATLAB. The per-vertex
+//! measures in this module are simple proxies that can be computed from the meshes alone. The results have one value per
+//! vertex and can be written in curv format with [`crate::FsCurv::from_data`] and [`crate::write_curv`].
+
+
+use std::collections::VecDeque;
+
+use crate::fs_surface::BrainMesh;
+use crate::util::{vec3_sub, vec3_dot, vec3_cross, vec3_norm};
+
+
+impl BrainMesh {
+    /// Compute a sulcal depth proxy for each vertex as the distance to the convex hull of the mesh.
+    ///
+    /// Vertices on the crowns of gyri lie on or close to the hull and get values near `0.0`, vertices in the fundi of sulci get large values.
+    /// The distance is computed to the planes of the faces of the hull, see [`BrainMesh::convex_hull`]. Typically computed on the pial surface.
+    /// If the mesh is flat, so that its hull has no volume, all values are `0.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let pial = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+    /// let depth = pial.mesh.hull_depth();
+    /// neuroformats::write_curv("/path/to/subjects_dir/subject1/surf/lh.hulldepth", &neuroformats::FsCurv::from_data(depth));
+    /// ```
+    pub fn hull_depth(&self) -> Vec<f32> {
+        let hull = match self.convex_hull() {
+            Ok(hull) => hull,
+            Err(_) => return vec![0.0; self.num_vertices()],
+        };
+        // For points inside a convex polyhedron, the distance to the surface is the smallest distance to the planes of its faces.
+        let planes: Vec<([f32; 3], f32)> = hull.faces.chunks_exact(3).map(|f| {
+            let p0 = hull.vertex_coords(f[0] as usize);
+            let n = vec3_cross(vec3_sub(hull.vertex_coords(f[1] as usize), p0), vec3_sub(hull.vertex_coords(f[2] as usize), p0));
+            let length = vec3_norm(n);
+            let normal = [n[0] / length, n[1] / length, n[2] / length];
+            (normal, vec3_dot(normal, p0))
+        }).collect();
+        (0..self.num_vertices()).map(|vidx| {
+            let coords = self.vertex_coords(vidx);
+            planes.iter().map(|(direction, support)| support - vec3_dot(*direction, coords)).fold(f32::INFINITY, f32::min).max(0.0)
+        }).collect()
+    }
+
+
+    /// Compute a local gyrification proxy for each vertex as the ratio of the surface area within the given radius to the area of a flat disk.
+    ///
+    /// The patch of a vertex is the part of the surface within the straight-line distance `radius` that is connected to the vertex, i.e., it is found by
+    /// walking along the edges of the mesh without leaving the ball around the vertex. Its area is the sum of the areas of its vertices, see
+    /// [`BrainMesh::vertex_areas`]. On flat parts of the surface, the ratio is close to `1.0`. Folding packs more surface into the ball, so the ratio
+    /// increases with the local gyrification, similar to the LGI. Typically computed on the pial surface with a radius of about 20 mm, which is slow
+    /// for full brain meshes.
+    ///
+    /// # Return value
+    ///
+    /// The ratio for each vertex, or NaN for all vertices if the radius is not positive.
+    pub fn local_surface_ratio(&self, radius: f32) -> Vec<f32> {
+        let areas = self.vertex_areas();
+        let neighbors = self.vertex_neighbors();
+        let disk_area = std::f32::consts::PI * radius * radius;
+        let mut visited = vec![usize::MAX; self.num_vertices()];
+        (0..self.num_vertices()).map(|vidx| {
+            if radius <= 0.0 {
+                return f32::NAN;
+            }
+            let coords = self.vertex_coords(vidx);
+            let mut patch_area = 0.0;
+            let mut queue = VecDeque::from(vec![vidx]);
+            visited[vidx] = vidx;
+            while let Some(v) = queue.pop_front() {
+                patch_area += areas[v];
+                for &n in neighbors[v].iter() {
+                    if visited[n] != vidx && vec3_norm(vec3_sub(self.vertex_coords(n), coords)) <= radius {
+                        visited[n] = vidx;
+                        queue.push_back(n);
+                    }
+                }
+            }
+            patch_area / disk_area
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use crate::subdivision::generate_icosphere;
+
+    /// A regular grid of `n` x `n` vertices with spacing 1 in the x-y plane, displaced along z by the given function of x.
+    fn grid(n: usize, z: fn(f32) -> f32) -> BrainMesh {
+        let vertices = (0..n * n).flat_map(|v| { let x = (v % n) as f32; vec![x, (v / n) as f32, z(x)] }).collect();
+        let faces = (0..(n - 1) * (n - 1)).flat_map(|c| {
+            let v = (c / (n - 1) * n + c % (n - 1)) as i32;
+            let n = n as i32;
+            vec![v, v + 1, v + n, v + 1, v + n + 1, v + n]
+        }).collect();
+        BrainMesh { vertices, faces }
+    }
+
+    #[test]
+    fn the_hull_depth_of_a_dented_sphere_is_the_depth_of_the_dent() {
+        let mut sphere = generate_icosphere(3);
+        sphere.vertices.iter_mut().for_each(|c| *c *= 100.0);
+        let depth = sphere.hull_depth();
+        assert!(depth.iter().all(|d| *d < 0.5));
+
+        for c in sphere.vertices[0..3].iter_mut() {
+            *c *= 0.9;
+        }
+        let depth = sphere.hull_depth();
+        // The hull over the dent is the plane through its neighbors, which lies slightly below the sphere.
+        assert_abs_diff_eq!(9.4, depth[0], epsilon = 0.5);
+        assert!(grid(5, |_| 0.0).hull_depth().iter().all(|d| *d == 0.0));
+        assert!(depth[1..].iter().all(|d| *d < 0.5));
+    }
+
+    #[test]
+    fn folded_surfaces_have_a_larger_local_surface_ratio() {
+        let flat = grid(41, |_| 0.0);
+        let folded = grid(41, |x| 3.0 * (x * 0.8).sin());
+        let center = 20 * 41 + 20;
+        let flat_ratio = flat.local_surface_ratio(8.0);
+        let folded_ratio = folded.local_surface_ratio(8.0);
+        assert_eq!(flat.num_vertices(), flat_ratio.len());
+        assert_abs_diff_eq!(1.0, flat_ratio[center], epsilon = 0.3);
+        assert!(folded_ratio[center] > 1.5 * flat_ratio[center], "Ratio {} for folded vs {} for flat surface.", folded_ratio[center], flat_ratio[center]);
+
+        assert!(flat.local_surface_ratio(0.0).iter().all(|r| r.is_nan()));
+    }
+}
@@ -0,0 +1,195 @@
+//! Extraction of 2D slices from MGH volumes, e.g., for quick visual quality control.
+//!
+//! Slices can be scaled to 8 bit gray values with [`window_to_u8`]. With the optional `image` feature, they can be
+//! converted to images with [`slice_to_image`] and saved as PNG files with [`write_slice_png`].
+
+
+use ndarray::Array2;
+#[cfg(all(feature = "image", feature = "fs"))]
+use std::path::Path;
+
+use crate::error::Result;
+use crate::fs_mgh::FsMgh;
+
+
+/// The anatomical plane of a slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plane {
+    /// A plane of constant x (left to right) coordinate.
+    Sagittal,
+    /// A plane of constant y (posterior to anterior) coordinate.
+    Coronal,
+    /// A plane of constant z (inferior to superior) coordinate.
+    Axial,
+}
+
+
+impl FsMgh {
+    /// Get the index of the voxel axis which is orthogonal to the given plane.
+    ///
+    /// The axis is determined from the direction cosines in the header. If the header contains no RAS information, the voxel
+    /// axes are assumed to be aligned with the x, y and z axes, in that order.
+    pub fn plane_axis(&self, plane: Plane) -> usize {
+        let ras_axis = match plane {
+            Plane::Sagittal => 0,
+            Plane::Coronal => 1,
+            Plane::Axial => 2,
+        };
+        if self.header.is_ras_good != 1 {
+            return ras_axis;
+        }
+        let mdc = &self.header.mdc_raw;
+        (0..3).max_by(|a, b| mdc[a * 3 + ras_axis].abs().total_cmp(&mdc[b * 3 + ras_axis].abs())).unwrap()
+    }
+
+
+    /// Extract a 2D slice from one frame of the volume.
+    ///
+    /// The dimensions of the slice are the remaining two voxel axes in the order column, row, slice, see [`FsMgh::plane_axis`].
+    /// The values are not flipped, so depending on the orientation of the volume, a slice may need to be transposed or flipped for display.
+    ///
+    /// # Parameters
+    ///
+    /// * `plane`: the anatomical plane of the slice.
+    /// * `index`: the index of the slice along the voxel axis orthogonal to the plane.
+    /// * `frame`: the frame, use `0` for 3D volumes.
+    ///
+    /// # Return value
+    ///
+    /// The voxel values of the slice, converted to `f32`, or [`crate::error::NeuroformatsError::UnsupportedMriDataTypeInMgh`] if the data part matching the `dtype` of the header is not set.
+    ///
+    /// # Panics
+    ///
+    /// If `index` or `frame` is out of range for this volume.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::slice::Plane;
+    /// let mgh = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/brain.mgz").unwrap();
+    /// let axial = mgh.slice(Plane::Axial, 128, 0).unwrap();
+    /// let gray = neuroformats::slice::window_to_u8(&axial, 0.0, 150.0);
+    /// ```
+    pub fn slice(&self, plane: Plane, index: usize, frame: usize) -> Result<Array2<f32>> {
+        let axis = self.plane_axis(plane);
+        let dim = self.dim();
+        assert!(index < dim[axis], "Slice index {} is out of range.", index);
+        let values = self.frame_as_f32(frame)?;
+        let (axis1, axis2) = match axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        Ok(Array2::from_shape_fn((dim[axis1], dim[axis2]), |(x, y)| {
+            let mut crs = [0; 3];
+            crs[axis] = index;
+            crs[axis1] = x;
+            crs[axis2] = y;
+            values[self.header.voxel_flat_index(crs, 0)]
+        }))
+    }
+}
+
+
+/// Scale values to 8 bit gray values, mapping the window [`min`, `max`] to the range 0 to 255.
+///
+/// Values outside of the window are clamped. NaN values are mapped to `0`. Use [`crate::vec32minmax`] to get the full value range
+/// of a slice as the window.
+pub fn window_to_u8(values: &Array2<f32>, min: f32, max: f32) -> Array2<u8> {
+    let range = max - min;
+    values.mapv(|v| {
+        if v.is_nan() || range <= 0.0 {
+            return 0;
+        }
+        ((v - min) / range * 255.0).clamp(0.0, 255.0).round() as u8
+    })
+}
+
+
+/// Convert a slice of 8 bit gray values to an image. The rows of the slice are the rows of the image.
+///
+/// Requires the `image` feature.
+#[cfg(feature = "image")]
+pub fn slice_to_image(slice: &Array2<u8>) -> image::GrayImage {
+    let (height, width) = slice.dim();
+    image::GrayImage::from_fn(width as u32, height as u32, |x, y| image::Luma([slice[[y as usize, x as usize]]]))
+}
+
+
+/// Write a slice of 8 bit gray values to a PNG file, see [`slice_to_image`].
+///
+/// Requires the `image` and `fs` features.
+#[cfg(all(feature = "image", feature = "fs"))]
+pub fn write_slice_png<P: AsRef<Path>>(path: P, slice: &Array2<u8>) -> std::io::Result<()> {
+    slice_to_image(slice).save_with_format(path, image::ImageFormat::Png).map_err(std::io::Error::other)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::read_mgh;
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_plane_axes_of_a_conformed_volume_can_be_determined() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        assert_eq!(0, mgh.plane_axis(Plane::Sagittal));
+        assert_eq!(2, mgh.plane_axis(Plane::Coronal));
+        assert_eq!(1, mgh.plane_axis(Plane::Axial));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn slices_can_be_extracted_from_a_volume() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let values = mgh.data_as_f32().unwrap();
+
+        let sagittal = mgh.slice(Plane::Sagittal, 99, 0).unwrap();
+        assert_eq!((256, 256), sagittal.dim());
+        assert_eq!(77.0, sagittal[[99, 99]]);
+
+        let axial = mgh.slice(Plane::Axial, 109, 0).unwrap();
+        assert_eq!(values[mgh.header.voxel_flat_index([30, 109, 60], 0)], axial[[30, 60]]);
+
+        let coronal = mgh.slice(Plane::Coronal, 99, 0).unwrap();
+        assert_eq!(values[mgh.header.voxel_flat_index([120, 80, 99], 0)], coronal[[120, 80]]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn slices_of_a_volume_with_several_frames_can_be_extracted() {
+        let mut header = read_mgh("resources/mgh/tiny.mgh").unwrap().header;
+        header.dim1len = 2;
+        header.dim2len = 3;
+        header.dim3len = 4;
+        header.dim4len = 2;
+        let mgh = FsMgh::from_f32_values(&header, (0..48).map(|v| v as f32).collect()).unwrap();
+
+        let axial = mgh.slice(Plane::Axial, 3, 1).unwrap();
+        assert_eq!((2, 3), axial.dim());
+        assert_eq!(header.voxel_flat_index([1, 2, 3], 1) as f32, axial[[1, 2]]);
+        assert_eq!((3, 4), mgh.slice(Plane::Sagittal, 1, 0).unwrap().dim());
+    }
+
+    #[test]
+    fn slices_can_be_scaled_to_gray_values() {
+        let values = Array2::from_shape_vec((2, 2), vec![-1.0, 0.5, 2.0, f32::NAN]).unwrap();
+        let gray = window_to_u8(&values, 0.0, 1.0);
+        assert_eq!(vec![0, 128, 255, 0], gray.iter().cloned().collect::<Vec<u8>>());
+    }
+
+    #[cfg(all(feature = "image", feature = "fs"))]
+    #[test]
+    fn a_slice_can_be_written_as_png() {
+        let slice = Array2::from_shape_fn((2, 3), |(row, col)| (row * 3 + col) as u8);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slice.png");
+        write_slice_png(&path, &slice).unwrap();
+
+        let img = image::open(&path).unwrap().to_luma8();
+        assert_eq!((3, 2), img.dimensions());
+        assert_eq!(5, img.get_pixel(2, 1)[0]);
+    }
+}
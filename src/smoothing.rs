@@ -0,0 +1,229 @@
+//! Estimation and control of the smoothness of per-vertex data.
+//!
+//! Smoothness is given as the full width at half maximum (FWHM) of a Gaussian kernel in mm, like in FreeSurfer's
+//! `mri_surf2surf --fwhm` and `mris_fwhm`. Distances are measured along the edges of the mesh, so the kernel does not
+//! leak across sulci into vertices which are close in space but far apart on the surface.
+
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::{vec3_sub, vec3_norm};
+
+
+/// The ratio between the FWHM and the standard deviation of a Gaussian, `sqrt(8 ln 2)`.
+const FWHM_PER_SIGMA: f32 = 2.354_82;
+
+
+/// Kernel weights are ignored beyond this multiple of the standard deviation.
+const KERNEL_CUTOFF_SIGMAS: f32 = 3.5;
+
+
+/// An entry in the queue of the Dijkstra search, see [`BrainMesh::geodesic_neighborhood`].
+struct Visit {
+    dist: f32,
+    vertex: usize,
+}
+
+
+impl PartialEq for Visit {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+
+impl Eq for Visit {}
+
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+impl Ord for Visit {
+    // Reversed, so that the max-heap `BinaryHeap` pops the closest vertex first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.dist.partial_cmp(&self.dist).unwrap_or(Ordering::Equal)
+    }
+}
+
+
+impl BrainMesh {
+    /// Find all vertices within the given distance along the edges of the mesh from a source vertex.
+    ///
+    /// # Return value
+    ///
+    /// A map from vertex index to the length of the shortest edge path from the source vertex, including the source vertex itself.
+    fn geodesic_neighborhood(&self, source: usize, max_dist: f32, neighbors: &[Vec<usize>]) -> HashMap<usize, f32> {
+        let mut dists: HashMap<usize, f32> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        queue.push(Visit { dist: 0.0, vertex: source });
+        while let Some(Visit { dist, vertex }) = queue.pop() {
+            if dists.contains_key(&vertex) {
+                continue;
+            }
+            dists.insert(vertex, dist);
+            let coords = self.vertex_coords(vertex);
+            for &neighbor in neighbors[vertex].iter() {
+                if dists.contains_key(&neighbor) {
+                    continue;
+                }
+                let next = dist + vec3_norm(vec3_sub(self.vertex_coords(neighbor), coords));
+                if next <= max_dist {
+                    queue.push(Visit { dist: next, vertex: neighbor });
+                }
+            }
+        }
+        dists
+    }
+
+
+    /// Smooth per-vertex data with a Gaussian kernel of the given FWHM.
+    ///
+    /// The value at each vertex is the weighted mean of the values of all vertices within 3.5 standard deviations of the kernel,
+    /// where the distance is the length of the shortest path along the edges of the mesh. NaN values are ignored. Edge paths are a bit
+    /// longer than the true geodesic distances, so the effective FWHM is about 10% lower than requested on regular meshes.
+    ///
+    /// # Parameters
+    ///
+    /// * `data`: the per-vertex data, one value per vertex.
+    /// * `fwhm`: the full width at half maximum of the kernel in mm, or the units of the vertex coordinates. Use `0.0` for no smoothing.
+    ///
+    /// # Return value
+    ///
+    /// The smoothed data, or [`NeuroformatsError::VertexCountMismatch`] if the length of `data` does not match the vertex count of the mesh.
+    /// Vertices without any non-NaN values in their neighborhood are NaN.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let white = neuroformats::read_surf("/path/to/subjects_dir/fsaverage/surf/lh.white").unwrap();
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness.fsaverage").unwrap();
+    /// let smoothed = white.mesh.smooth_data(&thickness.data, 10.0).unwrap();
+    /// ```
+    pub fn smooth_data(&self, data: &[f32], fwhm: f32) -> Result<Vec<f32>> {
+        if data.len() != self.num_vertices() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.num_vertices(), data.len()));
+        }
+        if fwhm <= 0.0 {
+            return Ok(data.to_vec());
+        }
+        let sigma = fwhm / FWHM_PER_SIGMA;
+        let neighbors = self.vertex_neighbors();
+        Ok((0..self.num_vertices()).map(|vidx| {
+            let (mut sum, mut weight_sum) = (0.0_f64, 0.0_f64);
+            for (neighbor, dist) in self.geodesic_neighborhood(vidx, KERNEL_CUTOFF_SIGMAS * sigma, &neighbors) {
+                let value = data[neighbor];
+                if !value.is_nan() {
+                    let weight = (-0.5 * (dist / sigma).powi(2)).exp() as f64;
+                    sum += weight * value as f64;
+                    weight_sum += weight;
+                }
+            }
+            if weight_sum > 0.0 { (sum / weight_sum) as f32 } else { f32::NAN }
+        }).collect())
+    }
+
+
+    /// Estimate the smoothness of per-vertex data as the FWHM of a Gaussian kernel in mm.
+    ///
+    /// This uses the spatial autocorrelation between neighboring vertices, like FreeSurfer's `mris_fwhm`: for data with autocorrelation `ar1`
+    /// between vertices at the mean edge length `d`, the FWHM is `sqrt(-2 ln(2) d^2 / ln(ar1))`. The input should be noise or residuals, e.g.,
+    /// the data of a subject minus the group mean, since real signal increases the estimate. NaN values are ignored.
+    ///
+    /// # Return value
+    ///
+    /// The estimated FWHM, `0.0` if the data is not positively autocorrelated, or [`NeuroformatsError::VertexCountMismatch`] if the length of `data`
+    /// does not match the vertex count of the mesh. The estimate is NaN if the data is constant.
+    pub fn estimate_fwhm(&self, data: &[f32]) -> Result<f32> {
+        if data.len() != self.num_vertices() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.num_vertices(), data.len()));
+        }
+        let valid: Vec<f64> = data.iter().filter(|v| !v.is_nan()).map(|v| *v as f64).collect();
+        let mean = valid.iter().sum::<f64>() / valid.len() as f64;
+        let variance = valid.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / valid.len() as f64;
+
+        let (mut covariance, mut edge_length, mut num_edges) = (0.0_f64, 0.0_f64, 0usize);
+        for (vidx, neighbors) in self.vertex_neighbors().iter().enumerate() {
+            for &neighbor in neighbors.iter().filter(|n| **n > vidx) {
+                if data[vidx].is_nan() || data[neighbor].is_nan() {
+                    continue;
+                }
+                covariance += (data[vidx] as f64 - mean) * (data[neighbor] as f64 - mean);
+                edge_length += vec3_norm(vec3_sub(self.vertex_coords(neighbor), self.vertex_coords(vidx))) as f64;
+                num_edges += 1;
+            }
+        }
+        let ar1 = covariance / num_edges as f64 / variance;
+        if ar1 <= 0.0 {
+            return Ok(if ar1.is_nan() { f32::NAN } else { 0.0 });
+        }
+        let d = edge_length / num_edges as f64;
+        Ok((-2.0 * std::f64::consts::LN_2 * d * d / ar1.ln()).sqrt() as f32)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::subdivision::generate_icosphere;
+
+    /// Generate reproducible uniform noise with a linear congruential generator.
+    fn noise(n: usize) -> Vec<f32> {
+        let mut state: u64 = 42;
+        (0..n).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        }).collect()
+    }
+
+    /// An icosphere with the radius of FreeSurfer spheres and a mean edge length of about 3.8 mm.
+    fn sphere() -> BrainMesh {
+        let mut ico = generate_icosphere(5);
+        ico.vertices.iter_mut().for_each(|c| *c *= 100.0);
+        ico
+    }
+
+    #[test]
+    fn data_can_be_smoothed_with_a_gaussian_kernel() {
+        let mesh = sphere();
+        let data = noise(mesh.num_vertices());
+        let smoothed = mesh.smooth_data(&data, 10.0).unwrap();
+        assert_eq!(data.len(), smoothed.len());
+
+        let variance = |values: &[f32]| values.iter().map(|v| v * v).sum::<f32>() / values.len() as f32;
+        assert!(variance(&smoothed) < 0.2 * variance(&data));
+        assert_eq!(data, mesh.smooth_data(&data, 0.0).unwrap());
+
+        let constant = vec![2.0; mesh.num_vertices()];
+        assert!(mesh.smooth_data(&constant, 10.0).unwrap().iter().all(|v| (v - 2.0).abs() < 1e-5));
+        assert!(mesh.smooth_data(&[1.0], 10.0).is_err());
+    }
+
+    #[test]
+    fn nan_values_are_ignored_when_smoothing() {
+        let mesh = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2] };
+        let smoothed = mesh.smooth_data(&[1.0, f32::NAN, 1.0], 5.0).unwrap();
+        assert_eq!(vec![1.0, 1.0, 1.0], smoothed);
+        assert!(mesh.smooth_data(&[f32::NAN; 3], 5.0).unwrap().iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn the_fwhm_of_smoothed_noise_can_be_estimated() {
+        let mesh = sphere();
+        let data = noise(mesh.num_vertices());
+        assert!(mesh.estimate_fwhm(&data).unwrap() < 2.0);
+
+        for fwhm in vec![10.0, 20.0].into_iter() {
+            let estimate = mesh.estimate_fwhm(&mesh.smooth_data(&data, fwhm).unwrap()).unwrap();
+            assert!((estimate - fwhm).abs() < 0.2 * fwhm, "Estimated FWHM {} for smoothing with FWHM {}.", estimate, fwhm);
+        }
+        assert!(mesh.estimate_fwhm(&[1.0]).is_err());
+    }
+}
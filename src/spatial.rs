@@ -0,0 +1,198 @@
+//! Spatial index structures for fast nearest-vertex queries on brain meshes.
+//!
+//! The main use case is mapping arbitrary coordinates (e.g., electrode positions or peak
+//! coordinates reported in a paper) to the closest vertices of a surface mesh.
+
+
+use std::cmp::Ordering;
+
+use crate::fs_surface::BrainMesh;
+
+
+/// A k-d tree over the vertex coordinates of a [`BrainMesh`].
+///
+/// The tree is balanced and stored implicitly: the vertex indices are permuted so that the median
+/// of each (sub)range along the split axis sits in the middle of that range. Use [`BrainMesh::build_spatial_index`] to create one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexIndex {
+    coords: Vec<[f32; 3]>,
+    order: Vec<usize>,
+}
+
+
+impl VertexIndex {
+
+    /// Build a k-d tree from a flat vector of x,y,z coordinates, as used in [`BrainMesh::vertices`].
+    pub fn new(coords: &[f32]) -> VertexIndex {
+        let coords: Vec<[f32; 3]> = coords.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+        let mut order: Vec<usize> = (0..coords.len()).collect();
+        build_recursive(&coords, &mut order, 0);
+        VertexIndex { coords, order }
+    }
+
+
+    /// Get the number of vertices in the index.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+
+    /// Whether the index contains no vertices.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+
+    /// Find the vertex closest to the given point.
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the vertex index and its Euclidean distance to `point`, or `None` if the index is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let index = surf.mesh.build_spatial_index();
+    /// let (vertex, dist) = index.nearest_vertex([-40.0, 10.0, 30.0]).unwrap();
+    /// ```
+    pub fn nearest_vertex(&self, point: [f32; 3]) -> Option<(usize, f32)> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut best = (self.order[0], f32::INFINITY);
+        self.nearest_recursive(&self.order, 0, &point, &mut best);
+        Some((best.0, best.1.sqrt()))
+    }
+
+
+    /// Find all vertices within the given Euclidean distance `radius` of the point.
+    ///
+    /// The returned vertex indices are sorted in ascending order.
+    pub fn vertices_within_radius(&self, point: [f32; 3], radius: f32) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.radius_recursive(&self.order, 0, &point, radius * radius, &mut found);
+        found.sort_unstable();
+        found
+    }
+
+
+    fn nearest_recursive(&self, order: &[usize], depth: usize, point: &[f32; 3], best: &mut (usize, f32)) {
+        if order.is_empty() {
+            return;
+        }
+        let mid = order.len() / 2;
+        let axis = depth % 3;
+        let vidx = order[mid];
+        let dist_sq = squared_distance(&self.coords[vidx], point);
+        if dist_sq < best.1 {
+            *best = (vidx, dist_sq);
+        }
+
+        let diff = point[axis] - self.coords[vidx][axis];
+        let (near, far) = if diff < 0.0 { (&order[..mid], &order[mid + 1..]) } else { (&order[mid + 1..], &order[..mid]) };
+        self.nearest_recursive(near, depth + 1, point, best);
+        if diff * diff < best.1 {
+            self.nearest_recursive(far, depth + 1, point, best);
+        }
+    }
+
+
+    fn radius_recursive(&self, order: &[usize], depth: usize, point: &[f32; 3], radius_sq: f32, found: &mut Vec<usize>) {
+        if order.is_empty() {
+            return;
+        }
+        let mid = order.len() / 2;
+        let axis = depth % 3;
+        let vidx = order[mid];
+        if squared_distance(&self.coords[vidx], point) <= radius_sq {
+            found.push(vidx);
+        }
+
+        let diff = point[axis] - self.coords[vidx][axis];
+        if diff <= 0.0 || diff * diff <= radius_sq {
+            self.radius_recursive(&order[..mid], depth + 1, point, radius_sq, found);
+        }
+        if diff >= 0.0 || diff * diff <= radius_sq {
+            self.radius_recursive(&order[mid + 1..], depth + 1, point, radius_sq, found);
+        }
+    }
+}
+
+
+/// Permute `order` so that it forms an implicit, balanced k-d tree over `coords`.
+fn build_recursive(coords: &[[f32; 3]], order: &mut [usize], depth: usize) {
+    if order.len() <= 1 {
+        return;
+    }
+    let mid = order.len() / 2;
+    let axis = depth % 3;
+    order.select_nth_unstable_by(mid, |a, b| coords[*a][axis].partial_cmp(&coords[*b][axis]).unwrap_or(Ordering::Equal));
+    let (left, right) = order.split_at_mut(mid);
+    build_recursive(coords, left, depth + 1);
+    build_recursive(coords, &mut right[1..], depth + 1);
+}
+
+
+fn squared_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+
+impl BrainMesh {
+    /// Build a k-d tree over the vertices of this mesh for fast nearest-vertex and radius queries.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let index = surf.mesh.build_spatial_index();
+    /// let neighborhood = index.vertices_within_radius([-40.0, 10.0, 30.0], 5.0);
+    /// ```
+    pub fn build_spatial_index(&self) -> VertexIndex {
+        VertexIndex::new(&self.vertices)
+    }
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+    use crate::read_surf;
+
+    #[test]
+    fn the_nearest_vertex_can_be_found_in_a_mesh() {
+        let mesh = BrainMesh::from_obj_file("resources/mesh/cube.obj").unwrap();
+        let index = mesh.build_spatial_index();
+        assert_eq!(8, index.len());
+
+        let (vertex, dist) = index.nearest_vertex([0.9, -1.2, 0.8]).unwrap();
+        assert_eq!(2, vertex);
+        assert!((dist - (0.01f32 + 0.04 + 0.04).sqrt()).abs() < 1e-6);
+
+        let within = index.vertices_within_radius([1.0, 1.0, 0.0], 1.5);
+        assert_eq!(vec![0, 1], within);
+    }
+
+    #[test]
+    fn spatial_index_queries_match_brute_force_search_on_demo_surface() {
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let index = surf.mesh.build_spatial_index();
+        let v = &surf.mesh.vertices[3000..3003];
+        let point = [v[0] + 0.3, v[1] - 0.2, v[2] + 0.1];
+
+        let brute_force_nearest = surf.mesh.vertices.chunks_exact(3)
+            .map(|c| squared_distance(&[c[0], c[1], c[2]], &point))
+            .enumerate()
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap();
+        assert_eq!(brute_force_nearest.0, index.nearest_vertex(point).unwrap().0);
+
+        let brute_force_within: Vec<usize> = surf.mesh.vertices.chunks_exact(3)
+            .enumerate()
+            .filter(|(_, c)| squared_distance(&[c[0], c[1], c[2]], &point) <= 25.0)
+            .map(|(idx, _)| idx)
+            .collect();
+        assert_eq!(brute_force_within, index.vertices_within_radius(point, 5.0));
+    }
+}
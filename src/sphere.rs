@@ -0,0 +1,143 @@
+//! Functions for working with spherical surfaces, like the `?h.sphere` and `?h.sphere.reg` files of FreeSurfer.
+//!
+//! FreeSurfer inflates each hemisphere to a sphere centered at the origin, with a radius of 100 mm. The sphere is registered
+//! to a template, and the resulting `?h.sphere.reg` surface defines the correspondence between the vertices of different subjects,
+//! see [`crate::resample`]. The vertex positions on a sphere can be described by their latitude and longitude.
+
+
+use crate::fs_surface::BrainMesh;
+use crate::util::vec3_norm;
+
+
+impl BrainMesh {
+    /// Compute the spherical coordinates of all vertices, relative to the origin.
+    ///
+    /// The latitude is the angle from the x-y plane in degrees, from `-90.0` at the bottom (inferior) to `90.0` at the top (superior).
+    /// The longitude is the angle in the x-y plane from the x axis in degrees, in the range `-180.0` to `180.0`. Vertices at the origin
+    /// get latitude and longitude `0.0`. The coordinates are independent of the radius of the sphere, and can be computed for any mesh.
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the latitudes and the longitudes, each with one value per vertex. They can be written in curv format with [`crate::FsCurv::from_data`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let sphere = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.sphere.reg").unwrap();
+    /// let (lat, lon) = sphere.mesh.spherical_coords();
+    /// ```
+    pub fn spherical_coords(&self) -> (Vec<f32>, Vec<f32>) {
+        (0..self.num_vertices()).map(|vidx| {
+            let [x, y, z] = self.vertex_coords(vidx);
+            let radius = vec3_norm([x, y, z]);
+            if radius == 0.0 {
+                return (0.0, 0.0);
+            }
+            ((z / radius).clamp(-1.0, 1.0).asin().to_degrees(), y.atan2(x).to_degrees())
+        }).unzip()
+    }
+
+
+    /// Compute the mean distance of the vertices from the origin, i.e., the radius of a sphere centered at the origin.
+    ///
+    /// This is about `100.0` for FreeSurfer spheres. Returns `0.0` for a mesh without vertices.
+    pub fn sphere_radius(&self) -> f32 {
+        if self.num_vertices() == 0 {
+            return 0.0;
+        }
+        let sum: f64 = (0..self.num_vertices()).map(|vidx| vec3_norm(self.vertex_coords(vidx)) as f64).sum();
+        (sum / self.num_vertices() as f64) as f32
+    }
+}
+
+
+/// Convert a latitude and longitude in degrees into the coordinates of the point with the given distance from the origin.
+///
+/// This is the inverse of [`BrainMesh::spherical_coords`].
+pub fn sphere_point(lat: f32, lon: f32, radius: f32) -> [f32; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [radius * lat.cos() * lon.cos(), radius * lat.cos() * lon.sin(), radius * lat.sin()]
+}
+
+
+/// Map each vertex of a target sphere to the closest vertex of a source sphere.
+///
+/// Vertices are compared by their direction from the origin, so the spheres may have different radii. This is the nearest neighbor mapping
+/// used for resampling data from the source to the target, see [`crate::resample`].
+///
+/// # Parameters
+///
+/// * `target_sphere`: the sphere onto which data is mapped, typically `?h.sphere` of fsaverage.
+/// * `source_sphere`: the registered sphere of the subject providing the data, typically `?h.sphere.reg`.
+///
+/// # Return value
+///
+/// For each vertex of the target sphere, the index of the closest vertex of the source sphere. Empty if the source sphere has no vertices.
+///
+/// # Examples
+///
+/// ```no_run
+/// let fsavg_sphere = neuroformats::read_surf("/path/to/subjects_dir/fsaverage/surf/lh.sphere").unwrap();
+/// let src_sphere = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.sphere.reg").unwrap();
+/// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let mapping = neuroformats::sphere::nearest_sphere_vertex(&fsavg_sphere.mesh, &src_sphere.mesh);
+/// let thickness_fsavg: Vec<f32> = mapping.iter().map(|v| thickness.data[*v]).collect();
+/// ```
+pub fn nearest_sphere_vertex(target_sphere: &BrainMesh, source_sphere: &BrainMesh) -> Vec<usize> {
+    if source_sphere.num_vertices() == 0 {
+        return Vec::new();
+    }
+    let index = BrainMesh { vertices: unit_vectors(source_sphere), faces: vec![] }.build_spatial_index();
+    unit_vectors(target_sphere).chunks_exact(3).map(|p| index.nearest_vertex([p[0], p[1], p[2]]).unwrap().0).collect()
+}
+
+
+/// Scale the vertex coordinates of the mesh to unit length. Vertices at the origin are kept.
+fn unit_vectors(mesh: &BrainMesh) -> Vec<f32> {
+    (0..mesh.num_vertices()).flat_map(|vidx| {
+        let p = mesh.vertex_coords(vidx);
+        let length = vec3_norm(p);
+        if length > 0.0 { p.map(|c| c / length) } else { p }
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use crate::subdivision::generate_icosphere;
+
+    #[test]
+    fn the_spherical_coordinates_of_vertices_can_be_computed() {
+        let mesh = BrainMesh { vertices: vec![100.0, 0.0, 0.0, 0.0, 0.0, -50.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0], faces: vec![] };
+        let (lat, lon) = mesh.spherical_coords();
+        assert_eq!(vec![0.0, -90.0, 45.0, 0.0], lat);
+        assert_eq!(vec![0.0, 0.0, 90.0, 0.0], lon);
+
+        let p = sphere_point(lat[2], lon[2], 2.0_f32.sqrt());
+        assert_abs_diff_eq!(0.0, p[0], epsilon = 1e-6);
+        assert_abs_diff_eq!(1.0, p[1], epsilon = 1e-6);
+        assert_abs_diff_eq!(1.0, p[2], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn the_radius_of_a_sphere_can_be_computed() {
+        let mut ico = generate_icosphere(3);
+        ico.vertices.iter_mut().for_each(|c| *c *= 100.0);
+        assert_abs_diff_eq!(100.0, ico.sphere_radius(), epsilon = 1e-3);
+        assert_eq!(0.0, BrainMesh { vertices: vec![], faces: vec![] }.sphere_radius());
+    }
+
+    #[test]
+    fn vertices_can_be_mapped_between_spheres_of_different_radii() {
+        let source = generate_icosphere(3);
+        let mut target = generate_icosphere(2);
+        target.vertices.iter_mut().for_each(|c| *c *= 100.0);
+        let mapping = nearest_sphere_vertex(&target, &source);
+        assert_eq!(target.num_vertices(), mapping.len());
+        // The vertices of an icosphere are the first vertices of all icospheres of higher orders.
+        assert_eq!((0..target.num_vertices()).collect::<Vec<usize>>(), mapping);
+        assert!(nearest_sphere_vertex(&target, &BrainMesh { vertices: vec![], faces: vec![] }).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+//! Recognition of standard space meshes and downsampling of per-vertex data between them.
+//!
+//! The fsaverage template subjects of FreeSurfer use icosphere meshes: fsaverage has 163842 vertices per hemisphere (order 7),
+//! fsaverage6 has 40962 (order 6), fsaverage5 has 10242 (order 5), and so on. Icospheres are nested, i.e., the first vertices
+//! of an icosphere are the vertices of the icosphere of the next lower order, so data can be downsampled by truncation.
+
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+
+
+/// The highest icosphere order which is recognized. FreeSurfer provides icospheres up to order 7.
+const MAX_ICO_ORDER: usize = 7;
+
+
+/// A standard space mesh, i.e., an icosphere of a certain order, like the meshes of the fsaverage subjects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandardSpace {
+    /// The order of the icosphere, `7` for fsaverage.
+    pub ico_order: usize,
+}
+
+
+impl StandardSpace {
+    /// Get the number of vertices of a mesh in this space.
+    pub fn num_vertices(&self) -> usize {
+        ico_num_vertices(self.ico_order)
+    }
+
+
+    /// Get the name of the FreeSurfer template subject for this space, like `fsaverage6` for order 6, or `None` if FreeSurfer has no template subject for it.
+    pub fn subject_name(&self) -> Option<&'static str> {
+        match self.ico_order {
+            7 => Some("fsaverage"),
+            6 => Some("fsaverage6"),
+            5 => Some("fsaverage5"),
+            4 => Some("fsaverage4"),
+            3 => Some("fsaverage3"),
+            _ => None,
+        }
+    }
+}
+
+
+/// Get the number of vertices of an icosphere of the given order, `10 * 4^order + 2`.
+pub fn ico_num_vertices(order: usize) -> usize {
+    10 * 4usize.pow(order as u32) + 2
+}
+
+
+/// Get the order of the icosphere with the given number of vertices, or `None` if there is no such icosphere up to order 7.
+pub fn ico_order(num_vertices: usize) -> Option<usize> {
+    (0..=MAX_ICO_ORDER).find(|order| ico_num_vertices(*order) == num_vertices)
+}
+
+
+/// Downsample per-vertex data of an icosphere to an icosphere of a lower order.
+///
+/// This uses the nested vertex property of icospheres: the value of each vertex of the target icosphere is the value of the same vertex in the input.
+///
+/// # Return value
+///
+/// The data for the target icosphere, or [`NeuroformatsError::InvalidIcoData`] if the length of `data` does not match an icosphere, or
+/// its order is lower than `target_order`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness.fsaverage").unwrap();
+/// let thickness_fsaverage5 = neuroformats::standard_space::downsample_ico_data(&thickness.data, 5).unwrap();
+/// assert_eq!(10242, thickness_fsaverage5.len());
+/// ```
+pub fn downsample_ico_data(data: &[f32], target_order: usize) -> Result<Vec<f32>> {
+    match ico_order(data.len()) {
+        Some(order) if order >= target_order => Ok(data[..ico_num_vertices(target_order)].to_vec()),
+        _ => Err(NeuroformatsError::InvalidIcoData(data.len(), target_order)),
+    }
+}
+
+
+impl BrainMesh {
+    /// Determine the standard space of the mesh from its vertex and face counts.
+    ///
+    /// # Return value
+    ///
+    /// The standard space, or `None` if the mesh is not an icosphere. Only the counts are checked, not the vertex positions.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/fsaverage/surf/lh.white").unwrap();
+    /// assert_eq!(Some("fsaverage"), surf.mesh.standard_space().and_then(|space| space.subject_name()));
+    /// ```
+    pub fn standard_space(&self) -> Option<StandardSpace> {
+        let order = ico_order(self.num_vertices())?;
+        if self.num_faces() == 20 * 4usize.pow(order as u32) {
+            Some(StandardSpace { ico_order: order })
+        } else {
+            None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::subdivision::generate_icosphere;
+    #[cfg(feature = "fs")]
+    use crate::read_surf;
+
+    #[test]
+    fn ico_orders_can_be_recognized_from_vertex_counts() {
+        assert_eq!(Some(7), ico_order(163842));
+        assert_eq!(Some(6), ico_order(40962));
+        assert_eq!(Some(5), ico_order(10242));
+        assert_eq!(None, ico_order(149244));
+        assert_eq!(163842, StandardSpace { ico_order: 7 }.num_vertices());
+        assert_eq!(Some("fsaverage"), StandardSpace { ico_order: 7 }.subject_name());
+        assert_eq!(None, StandardSpace { ico_order: 2 }.subject_name());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_standard_space_of_a_mesh_can_be_determined() {
+        let ico = generate_icosphere(3);
+        let space = ico.standard_space().unwrap();
+        assert_eq!(3, space.ico_order);
+        assert_eq!(Some("fsaverage3"), space.subject_name());
+
+        let surf = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        assert!(surf.mesh.standard_space().is_none());
+    }
+
+    #[test]
+    fn ico_data_can_be_downsampled() {
+        let ico4 = generate_icosphere(4);
+        let ico2 = generate_icosphere(2);
+        let x_coords: Vec<f32> = ico4.vertices.chunks_exact(3).map(|c| c[0]).collect();
+
+        let downsampled = downsample_ico_data(&x_coords, 2).unwrap();
+        assert_eq!(ico2.num_vertices(), downsampled.len());
+        for (vidx, value) in downsampled.iter().enumerate() {
+            assert_eq!(ico2.vertex_coords(vidx)[0], *value);
+        }
+
+        assert_eq!(x_coords, downsample_ico_data(&x_coords, 4).unwrap());
+        assert!(matches!(downsample_ico_data(&x_coords, 5), Err(NeuroformatsError::InvalidIcoData(2562, 5))));
+        assert!(downsample_ico_data(&[0.0; 100], 1).is_err());
+    }
+}
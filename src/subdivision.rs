@@ -0,0 +1,135 @@
+//! Mesh subdivision and generation of regular icosphere meshes.
+//!
+//! Icospheres are the regular meshes used by FreeSurfer for the `ico` template surfaces: an icosphere of order 7 has 163842 vertices,
+//! like the fsaverage sphere. They are useful to test resampling code without large surface files.
+
+
+use std::collections::HashMap;
+
+use crate::fs_surface::BrainMesh;
+
+
+impl BrainMesh {
+    /// Subdivide the mesh `n` times, splitting each triangle into 4 triangles by inserting a vertex at the midpoint of each edge.
+    ///
+    /// The existing vertices keep their indices and positions, and the new vertices are appended. Each subdivision multiplies
+    /// the number of faces by 4.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let finer = surf.mesh.subdivide(1);
+    /// assert_eq!(surf.mesh.num_faces() * 4, finer.num_faces());
+    /// ```
+    pub fn subdivide(&self, n: usize) -> BrainMesh {
+        let mut mesh = self.clone();
+        for _ in 0..n {
+            mesh = mesh.subdivide_once(|p| p);
+        }
+        mesh
+    }
+
+
+    /// Split each triangle into 4 triangles, placing the new vertices at the edge midpoints mapped through `place`.
+    fn subdivide_once<F: Fn([f32; 3]) -> [f32; 3]>(&self, place: F) -> BrainMesh {
+        let mut vertices = self.vertices.clone();
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        let mut midpoints: HashMap<(i32, i32), i32> = HashMap::with_capacity(self.faces.len() / 2 * 3);
+        let mut midpoint = |a: i32, b: i32, vertices: &mut Vec<f32>| -> i32 {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let (pa, pb) = (self.vertex_coords(a as usize), self.vertex_coords(b as usize));
+                let p = place([0.5 * (pa[0] + pb[0]), 0.5 * (pa[1] + pb[1]), 0.5 * (pa[2] + pb[2])]);
+                vertices.extend_from_slice(&p);
+                (vertices.len() / 3 - 1) as i32
+            })
+        };
+        for face in self.faces.chunks_exact(3) {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let ab = midpoint(a, b, &mut vertices);
+            let bc = midpoint(b, c, &mut vertices);
+            let ca = midpoint(c, a, &mut vertices);
+            faces.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+        }
+        BrainMesh { vertices, faces }
+    }
+}
+
+
+/// Generate an icosphere, i.e., a regular triangle mesh approximating the unit sphere, by repeatedly subdividing an icosahedron.
+///
+/// All vertices lie on the unit sphere, and the faces are oriented counter-clockwise when seen from outside, like in FreeSurfer
+/// surfaces. An icosphere of order `n` has `10 * 4^n + 2` vertices and `20 * 4^n` faces. Scale the vertex coordinates by 100 to get
+/// the radius of FreeSurfer spheres.
+///
+/// # Examples
+///
+/// ```
+/// let ico = neuroformats::subdivision::generate_icosphere(3);
+/// assert_eq!(642, ico.num_vertices());
+/// ```
+pub fn generate_icosphere(order: usize) -> BrainMesh {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let corners: [[f32; 3]; 12] = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ];
+    let mut mesh = BrainMesh {
+        vertices: corners.iter().flat_map(|&c| normalize(c).to_vec()).collect(),
+        faces: vec![
+            0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11,
+            1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8,
+            3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9,
+            4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1,
+        ],
+    };
+    for _ in 0..order {
+        mesh = mesh.subdivide_once(normalize);
+    }
+    mesh
+}
+
+
+fn normalize(p: [f32; 3]) -> [f32; 3] {
+    let len = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+    [p[0] / len, p[1] / len, p[2] / len]
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn icospheres_of_different_orders_can_be_generated() {
+        for order in 0..5 {
+            let ico = generate_icosphere(order);
+            assert_eq!(10 * 4usize.pow(order as u32) + 2, ico.num_vertices());
+            assert_eq!(20 * 4usize.pow(order as u32), ico.num_faces());
+            assert_eq!(2, ico.euler_characteristic());
+            assert!(ico.is_closed());
+            assert!(ico.vertices.chunks_exact(3).all(|c| ((c[0] * c[0] + c[1] * c[1] + c[2] * c[2]).sqrt() - 1.0).abs() < 1e-5));
+        }
+    }
+
+    #[test]
+    fn the_faces_of_an_icosphere_point_outwards() {
+        let ico = generate_icosphere(2);
+        let normals = ico.vertex_normals();
+        for (n, c) in normals.chunks_exact(3).zip(ico.vertices.chunks_exact(3)) {
+            assert!(n[0] * c[0] + n[1] * c[1] + n[2] * c[2] > 0.9);
+        }
+    }
+
+    #[test]
+    fn a_mesh_can_be_subdivided() {
+        let mesh = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0], faces: vec![0, 1, 2] };
+        let sub = mesh.subdivide(2);
+        assert_eq!(16, sub.num_faces());
+        assert_eq!(15, sub.num_vertices());
+        assert_eq!(mesh.vertices[..], sub.vertices[..9]);
+        assert_eq!(1, sub.euler_characteristic());
+        assert_eq!(mesh, mesh.subdivide(0));
+    }
+}
@@ -0,0 +1,531 @@
+//! Functions for loading the standard files of a subject from a FreeSurfer SUBJECTS_DIR.
+//!
+//! A FreeSurfer subjects directory contains one sub directory per subject, which in turn contains
+//! the `surf`, `label` and `mri` sub directories with the files produced by `recon-all`.
+
+
+use std::path::{Path, PathBuf};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fs_surface::{FsSurface, read_surf};
+use crate::fs_curv::{FsCurv, read_curv};
+use crate::fs_label::{FsLabel, read_label};
+use crate::fs_annot::{FsAnnot, read_annot};
+use crate::fs_mgh::FsMghHeader;
+use crate::transforms::{read_xfm, apply_affine_to_points};
+use crate::hemi::Hemisphere;
+use crate::error::{NeuroformatsError, Result};
+
+use ndarray::{Array2, arr2};
+
+
+/// A kind of surface produced by FreeSurfer's `recon-all`, stored in `surf/<hemi>.<kind>` of a subject.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SurfaceKind {
+    /// The white matter surface, `?h.white`.
+    White,
+    /// The pial surface, `?h.pial`.
+    Pial,
+    /// The original surface before topology correction and refinement, `?h.orig`.
+    Orig,
+    /// The smoothed white surface, `?h.smoothwm`.
+    Smoothwm,
+    /// The inflated surface, `?h.inflated`.
+    Inflated,
+    /// The sphere, `?h.sphere`.
+    Sphere,
+    /// The sphere registered to the fsaverage template, `?h.sphere.reg`.
+    SphereReg,
+}
+
+
+impl SurfaceKind {
+    /// All surface kinds.
+    pub const ALL: [SurfaceKind; 7] = [SurfaceKind::White, SurfaceKind::Pial, SurfaceKind::Orig, SurfaceKind::Smoothwm, SurfaceKind::Inflated, SurfaceKind::Sphere, SurfaceKind::SphereReg];
+
+
+    /// Get the file name suffix of the surface kind, e.g., `"white"` or `"sphere.reg"`.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            SurfaceKind::White => "white",
+            SurfaceKind::Pial => "pial",
+            SurfaceKind::Orig => "orig",
+            SurfaceKind::Smoothwm => "smoothwm",
+            SurfaceKind::Inflated => "inflated",
+            SurfaceKind::Sphere => "sphere",
+            SurfaceKind::SphereReg => "sphere.reg",
+        }
+    }
+}
+
+
+impl fmt::Display for SurfaceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.suffix())
+    }
+}
+
+
+impl FromStr for SurfaceKind {
+    type Err = NeuroformatsError;
+
+    /// Parse a surface kind from its file name suffix, e.g., `"white"`. Returns [`NeuroformatsError::InvalidSurfaceKind`] for unknown suffixes.
+    fn from_str(s: &str) -> Result<SurfaceKind> {
+        SurfaceKind::ALL.iter().copied().find(|kind| kind.suffix() == s).ok_or_else(|| NeuroformatsError::InvalidSurfaceKind(s.to_string()))
+    }
+}
+
+
+/// The infix of the identifiers of longitudinal timepoint subjects, which are named `<timepoint>.long.<base>` by `recon-all -long`.
+pub const LONG_INFIX: &str = ".long.";
+
+
+/// A subject in a FreeSurfer SUBJECTS_DIR, used to locate the files of the subject.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subject {
+    pub subjects_dir: PathBuf,
+    pub subject_id: String,
+}
+
+
+impl Subject {
+    /// Create a new subject with the given identifier in the given subjects directory. This does not access the file system.
+    pub fn new<P: AsRef<Path>>(subjects_dir: P, subject_id: &str) -> Subject {
+        Subject { subjects_dir: subjects_dir.as_ref().to_path_buf(), subject_id: subject_id.to_string() }
+    }
+
+
+    /// Get the path of the directory of this subject.
+    pub fn dir(&self) -> PathBuf {
+        self.subjects_dir.join(&self.subject_id)
+    }
+
+
+    /// Split the identifier of a longitudinal timepoint subject, like `subject_tp1.long.subject_base`, into the timepoint and the base.
+    ///
+    /// Returns `None` for cross-sectional and base subjects.
+    pub fn long_parts(&self) -> Option<(&str, &str)> {
+        let pos = self.subject_id.find(LONG_INFIX)?;
+        Some((&self.subject_id[..pos], &self.subject_id[pos + LONG_INFIX.len()..]))
+    }
+
+
+    /// Whether this is a longitudinal timepoint subject, i.e., its identifier has the form `<timepoint>.long.<base>`.
+    pub fn is_longitudinal(&self) -> bool {
+        self.long_parts().is_some()
+    }
+
+
+    /// Get the path of a surface file of this subject, e.g., `surf/lh.white` for [`Hemisphere::Left`] and [`SurfaceKind::White`].
+    pub fn surf_path(&self, hemi: Hemisphere, kind: SurfaceKind) -> PathBuf {
+        self.dir().join("surf").join(format!("{}.{}", hemi, kind))
+    }
+
+
+    /// Get the path of a per-vertex data file in curv format of this subject, e.g., `surf/lh.thickness` for [`Hemisphere::Left`] and measure `"thickness"`.
+    pub fn measure_path(&self, hemi: Hemisphere, measure: &str) -> PathBuf {
+        self.dir().join("surf").join(format!("{}.{}", hemi, measure))
+    }
+
+
+    /// Compute the matrix that maps surface RAS (tkregister) coordinates to scanner RAS coordinates for this subject.
+    ///
+    /// This requires the header of the `mri/orig.mgz` file of the subject.
+    pub fn tkr_to_scanner_ras(&self) -> Result<Array2<f32>> {
+        let orig = FsMghHeader::from_file(self.dir().join("mri").join("orig.mgz"))?;
+        Ok(orig.vox2ras()?.dot(&orig.ras2vox_tkr()?))
+    }
+
+
+    /// Compute the MNI305 coordinates of all vertices of a surface of this subject.
+    ///
+    /// Surface coordinates are mapped to scanner RAS with the geometry of `mri/orig.mgz`, and then to MNI305 space
+    /// with the `mri/transforms/talairach.xfm` transform. Use [`mni305_to_mni152`] to obtain MNI152 coordinates.
+    ///
+    /// # Parameters
+    ///
+    /// * `hemi`: the hemisphere.
+    /// * `surf`: the surface, e.g., [`SurfaceKind::White`] or [`SurfaceKind::Pial`].
+    ///
+    /// # Return value
+    ///
+    /// The MNI305 coordinates as a flat vector of x,y,z triplets, in the same layout as [`crate::BrainMesh::vertices`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::hemi::Hemisphere;
+    /// use neuroformats::subject::{Subject, SurfaceKind};
+    /// let subject = Subject::new("/path/to/subjects_dir", "subject1");
+    /// let mni305 = subject.vertex_mni_coords(Hemisphere::Left, SurfaceKind::White).unwrap();
+    /// let mni152 = neuroformats::subject::mni305_to_mni152(&mni305);
+    /// ```
+    pub fn vertex_mni_coords(&self, hemi: Hemisphere, surf: SurfaceKind) -> Result<Vec<f32>> {
+        let surface = read_surf(self.surf_path(hemi, surf))?;
+        let talairach = read_xfm(self.dir().join("mri").join("transforms").join("talairach.xfm"))?;
+        let tkr_to_mni305 = talairach.matrix.dot(&self.tkr_to_scanner_ras()?);
+        Ok(apply_affine_to_points(&tkr_to_mni305, &surface.mesh.vertices))
+    }
+}
+
+
+/// A subject processed with the longitudinal stream of FreeSurfer, used to locate and load the files of all timepoints.
+///
+/// The longitudinal stream creates a within-subject template, the base, from the cross-sectional runs of all timepoints, and then
+/// reprocesses each timepoint into a directory `<timepoint>.long.<base>`. The surfaces of all longitudinal timepoints are derived from
+/// the base and have the same number of vertices, so per-vertex data can be compared between timepoints directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LongitudinalSubject {
+    pub subjects_dir: PathBuf,
+    /// The identifier of the base subject.
+    pub base_id: String,
+    /// The identifiers of the cross-sectional timepoint subjects, in temporal order.
+    pub timepoints: Vec<String>,
+}
+
+
+impl LongitudinalSubject {
+    /// Create a new longitudinal subject with the given base and timepoints. This does not access the file system.
+    pub fn new<P: AsRef<Path>>(subjects_dir: P, base_id: &str, timepoints: &[&str]) -> LongitudinalSubject {
+        LongitudinalSubject {
+            subjects_dir: subjects_dir.as_ref().to_path_buf(),
+            base_id: base_id.to_string(),
+            timepoints: timepoints.iter().map(|tp| tp.to_string()).collect(),
+        }
+    }
+
+
+    /// Find the timepoints of the given base subject.
+    ///
+    /// The timepoints are read from the `base-tps` file in the directory of the base subject, which is written by `recon-all -base`.
+    /// If it does not exist, the subjects directory is searched for directories named `<timepoint>.long.<base>`, and the timepoints are
+    /// sorted by name.
+    ///
+    /// # Return value
+    ///
+    /// The longitudinal subject, or [`NeuroformatsError::NoTimepoints`] if no timepoints were found.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::hemi::Hemisphere;
+    /// let long = neuroformats::subject::LongitudinalSubject::discover("/path/to/subjects_dir", "subject_base").unwrap();
+    /// let thickness = long.read_measures(Hemisphere::Left, "thickness").unwrap();
+    /// println!("Found {} timepoints with {} vertices.", thickness.len(), thickness[0].data.len());
+    /// ```
+    pub fn discover<P: AsRef<Path>>(subjects_dir: P, base_id: &str) -> Result<LongitudinalSubject> {
+        let subjects_dir = subjects_dir.as_ref();
+        let base_tps = subjects_dir.join(base_id).join("base-tps");
+        let timepoints: Vec<String> = if base_tps.is_file() {
+            std::fs::read_to_string(base_tps)?.split_whitespace().map(|tp| tp.to_string()).collect()
+        } else {
+            let suffix = format!("{}{}", LONG_INFIX, base_id);
+            let mut timepoints = Vec::new();
+            for entry in std::fs::read_dir(subjects_dir)? {
+                let entry = entry?;
+                if let Some(name) = entry.file_name().to_str() {
+                    if name.len() > suffix.len() && name.ends_with(&suffix) && entry.path().is_dir() {
+                        timepoints.push(name[..name.len() - suffix.len()].to_string());
+                    }
+                }
+            }
+            timepoints.sort();
+            timepoints
+        };
+        if timepoints.is_empty() {
+            return Err(NeuroformatsError::NoTimepoints(base_id.to_string()));
+        }
+        Ok(LongitudinalSubject { subjects_dir: subjects_dir.to_path_buf(), base_id: base_id.to_string(), timepoints })
+    }
+
+
+    /// Get the base subject, i.e., the within-subject template.
+    pub fn base(&self) -> Subject {
+        Subject::new(&self.subjects_dir, &self.base_id)
+    }
+
+
+    /// Get the longitudinal subject of the given timepoint, i.e., the subject `<timepoint>.long.<base>`.
+    pub fn timepoint(&self, timepoint: &str) -> Subject {
+        Subject::new(&self.subjects_dir, &format!("{}{}{}", timepoint, LONG_INFIX, self.base_id))
+    }
+
+
+    /// Get the cross-sectional subject of the given timepoint, i.e., the independent run the longitudinal processing started from.
+    pub fn cross_sectional(&self, timepoint: &str) -> Subject {
+        Subject::new(&self.subjects_dir, timepoint)
+    }
+
+
+    /// Get the longitudinal subjects of all timepoints, in temporal order.
+    pub fn timepoint_subjects(&self) -> Vec<Subject> {
+        self.timepoints.iter().map(|tp| self.timepoint(tp)).collect()
+    }
+
+
+    /// Load a surface of all longitudinal timepoints, in temporal order.
+    ///
+    /// Returns [`NeuroformatsError::VertexCountMismatch`] if the surfaces do not have the same number of vertices.
+    pub fn read_surfaces(&self, hemi: Hemisphere, kind: SurfaceKind) -> Result<Vec<FsSurface>> {
+        let surfaces = self.timepoint_subjects().iter().map(|s| read_surf(s.surf_path(hemi, kind))).collect::<Result<Vec<FsSurface>>>()?;
+        check_matching_vertex_counts(surfaces.iter().map(|s| s.mesh.num_vertices()))?;
+        Ok(surfaces)
+    }
+
+
+    /// Load per-vertex data of all longitudinal timepoints, in temporal order, e.g., `surf/?h.thickness` for measure `"thickness"`.
+    ///
+    /// Returns [`NeuroformatsError::VertexCountMismatch`] if the data of the timepoints does not have the same length.
+    pub fn read_measures(&self, hemi: Hemisphere, measure: &str) -> Result<Vec<FsCurv>> {
+        let measures = self.timepoint_subjects().iter().map(|s| read_curv(s.measure_path(hemi, measure))).collect::<Result<Vec<FsCurv>>>()?;
+        check_matching_vertex_counts(measures.iter().map(|m| m.data.len()))?;
+        Ok(measures)
+    }
+
+
+    /// Compute the per-vertex change of a measure between two timepoints, i.e., the values at timepoint `to` minus the values at timepoint `from`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::hemi::Hemisphere;
+    /// let long = neuroformats::subject::LongitudinalSubject::new("/path/to/subjects_dir", "subject_base", &["subject_tp1", "subject_tp2"]);
+    /// let thinning = long.measure_change(Hemisphere::Left, "thickness", "subject_tp1", "subject_tp2").unwrap();
+    /// ```
+    pub fn measure_change(&self, hemi: Hemisphere, measure: &str, from: &str, to: &str) -> Result<Vec<f32>> {
+        let from = read_curv(self.timepoint(from).measure_path(hemi, measure))?;
+        let to = read_curv(self.timepoint(to).measure_path(hemi, measure))?;
+        if from.data.len() != to.data.len() {
+            return Err(NeuroformatsError::VertexCountMismatch(from.data.len(), to.data.len()));
+        }
+        Ok(from.data.iter().zip(to.data.iter()).map(|(f, t)| t - f).collect())
+    }
+}
+
+
+/// Check that all vertex counts are equal to the first one.
+fn check_matching_vertex_counts<I: Iterator<Item = usize>>(mut counts: I) -> Result<()> {
+    if let Some(expected) = counts.next() {
+        if let Some(found) = counts.find(|c| *c != expected) {
+            return Err(NeuroformatsError::VertexCountMismatch(expected, found));
+        }
+    }
+    Ok(())
+}
+
+
+/// Map coordinates from MNI305 space to MNI152 space.
+///
+/// This uses the linear transform given in the FreeSurfer documentation (see the CoordinateSystems page of the FreeSurfer
+/// wiki). The `coords` are a flat vector of x,y,z triplets.
+pub fn mni305_to_mni152(coords: &[f32]) -> Vec<f32> {
+    let m : Array2<f32> = arr2(&[
+        [0.9975, -0.0073, 0.0176, -0.0429],
+        [0.0146, 1.0009, -0.0024, 1.5496],
+        [-0.0130, -0.0093, 0.9971, 1.1840],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    apply_affine_to_points(&m, coords)
+}
+
+
+/// The files of a single hemisphere of a template subject, see [`TemplateSubject`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateHemi {
+    /// The white surface, from `surf/?h.white`.
+    pub white: FsSurface,
+    /// The inflated surface, from `surf/?h.inflated`.
+    pub inflated: FsSurface,
+    /// The sphere, from `surf/?h.sphere`. This is the target for resampling data of other subjects, see [`crate::resample`].
+    pub sphere: FsSurface,
+    /// The cortex label, from `label/?h.cortex.label`. It excludes the medial wall.
+    pub cortex: FsLabel,
+    /// The Desikan-Killiani parcellation, from `label/?h.aparc.annot`.
+    pub aparc: FsAnnot,
+}
+
+
+/// A template subject like fsaverage, with the files used in almost every group-level workflow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateSubject {
+    /// The subject identifier, i.e., the name of the subject directory.
+    pub subject_id: String,
+    /// The files of the left hemisphere.
+    pub lh: TemplateHemi,
+    /// The files of the right hemisphere.
+    pub rh: TemplateHemi,
+}
+
+
+impl TemplateHemi {
+    /// Load the template files for the hemisphere `hemi` from the given subject directory.
+    pub fn from_subject_dir<P: AsRef<Path>>(subject_dir: P, hemi: Hemisphere) -> Result<TemplateHemi> {
+        let surf_dir = subject_dir.as_ref().join("surf");
+        let label_dir = subject_dir.as_ref().join("label");
+        Ok(TemplateHemi {
+            white: read_surf(surf_dir.join(format!("{}.{}", hemi, SurfaceKind::White)))?,
+            inflated: read_surf(surf_dir.join(format!("{}.{}", hemi, SurfaceKind::Inflated)))?,
+            sphere: read_surf(surf_dir.join(format!("{}.{}", hemi, SurfaceKind::Sphere)))?,
+            cortex: read_label(label_dir.join(format!("{}.cortex.label", hemi)))?,
+            aparc: read_annot(label_dir.join(format!("{}.aparc.annot", hemi)))?,
+        })
+    }
+}
+
+
+impl TemplateSubject {
+    /// Get the path of the directory of the given subject in the given subjects directory.
+    pub fn subject_dir<P: AsRef<Path>>(subjects_dir: P, subject_id: &str) -> PathBuf {
+        subjects_dir.as_ref().join(subject_id)
+    }
+}
+
+
+impl fmt::Display for TemplateSubject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Template subject '{}' with {} lh and {} rh vertices.", self.subject_id, self.lh.white.mesh.num_vertices(), self.rh.white.mesh.num_vertices())
+    }
+}
+
+
+/// Load the files of a template subject from a FreeSurfer SUBJECTS_DIR.
+///
+/// For both hemispheres, this loads the white, inflated and sphere surfaces, the cortex label and the aparc parcellation.
+/// See [`load_fsaverage`] for the common case of the fsaverage template.
+pub fn load_template_subject<P: AsRef<Path>>(subjects_dir: P, subject_id: &str) -> Result<TemplateSubject> {
+    let subject_dir = TemplateSubject::subject_dir(subjects_dir, subject_id);
+    Ok(TemplateSubject {
+        subject_id: subject_id.to_string(),
+        lh: TemplateHemi::from_subject_dir(&subject_dir, Hemisphere::Left)?,
+        rh: TemplateHemi::from_subject_dir(&subject_dir, Hemisphere::Right)?,
+    })
+}
+
+
+/// Load the fsaverage template subject from a FreeSurfer SUBJECTS_DIR.
+///
+/// The fsaverage subject ships with FreeSurfer, in `$FREESURFER_HOME/subjects/fsaverage`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let fsavg = neuroformats::subject::load_fsaverage("/path/to/freesurfer/subjects").unwrap();
+/// println!("{}", fsavg);
+/// let num_lh_verts = fsavg.lh.white.mesh.num_vertices();
+/// ```
+pub fn load_fsaverage<P: AsRef<Path>>(subjects_dir: P) -> Result<TemplateSubject> {
+    load_template_subject(subjects_dir, "fsaverage")
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::{tempdir};
+
+    #[test]
+    fn a_template_subject_can_be_loaded() {
+        // The demo subject has no inflated surfaces, spheres and cortex labels, so we create fake ones.
+        let subjects_dir = tempdir().unwrap();
+        let subject_dir = subjects_dir.path().join("fsaverage");
+        std::fs::create_dir_all(subject_dir.join("surf")).unwrap();
+        std::fs::create_dir_all(subject_dir.join("label")).unwrap();
+        let src_dir = Path::new("resources/subjects_dir/subject1");
+        for hemi in ["lh", "rh"] {
+            for surf in ["white", "inflated", "sphere"] {
+                std::fs::copy(src_dir.join("surf").join(format!("{}.white", hemi)), subject_dir.join("surf").join(format!("{}.{}", hemi, surf))).unwrap();
+            }
+            std::fs::copy(src_dir.join("label").join(format!("{}.aparc.annot", hemi)), subject_dir.join("label").join(format!("{}.aparc.annot", hemi))).unwrap();
+            std::fs::copy(src_dir.join("label/lh.entorhinal_exvivo.label"), subject_dir.join("label").join(format!("{}.cortex.label", hemi))).unwrap();
+        }
+
+        let fsavg = load_fsaverage(subjects_dir.path()).unwrap();
+        assert_eq!("fsaverage", fsavg.subject_id);
+        assert_eq!(149244, fsavg.lh.white.mesh.num_vertices());
+        assert_eq!(149244, fsavg.lh.sphere.mesh.num_vertices());
+        assert_eq!(1085, fsavg.rh.cortex.vertexes.len());
+        assert_eq!(36, fsavg.rh.aparc.num_regions());
+
+        assert!(load_template_subject(subjects_dir.path(), "no_such_subject").is_err());
+    }
+
+    #[test]
+    fn surface_paths_are_built_from_typed_hemispheres_and_kinds() {
+        let subject = Subject::new("/subjects", "subject1");
+        assert_eq!(Path::new("/subjects/subject1/surf/rh.sphere.reg"), subject.surf_path(Hemisphere::Right, SurfaceKind::SphereReg));
+        assert_eq!(Path::new("/subjects/subject1/surf/lh.thickness"), subject.measure_path(Hemisphere::Left, "thickness"));
+        assert_eq!(SurfaceKind::SphereReg, "sphere.reg".parse().unwrap());
+        assert!(matches!("whtie".parse::<SurfaceKind>(), Err(NeuroformatsError::InvalidSurfaceKind(_))));
+        for kind in SurfaceKind::ALL.iter() {
+            assert_eq!(*kind, kind.to_string().parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn longitudinal_subject_ids_can_be_split() {
+        let subject = Subject::new("/subjects", "subject_tp1.long.subject_base");
+        assert!(subject.is_longitudinal());
+        assert_eq!(Some(("subject_tp1", "subject_base")), subject.long_parts());
+        assert!(!Subject::new("/subjects", "subject_tp1").is_longitudinal());
+
+        let long = LongitudinalSubject::new("/subjects", "subject_base", &["subject_tp1", "subject_tp2"]);
+        assert_eq!(subject, long.timepoint_subjects()[0]);
+        assert_eq!(Subject::new("/subjects", "subject_tp2"), long.cross_sectional("subject_tp2"));
+        assert_eq!(Path::new("/subjects/subject_base"), long.base().dir());
+    }
+
+    #[test]
+    fn longitudinal_timepoints_can_be_discovered_and_loaded() {
+        let subjects_dir = tempdir().unwrap();
+        let src_dir = Path::new("resources/subjects_dir/subject1");
+        let thickness = read_curv(src_dir.join("surf/lh.thickness")).unwrap();
+        for (i, tp) in ["subject_tp2", "subject_tp1"].iter().enumerate() {
+            let subject = Subject::new(subjects_dir.path(), &format!("{}.long.subject_base", tp));
+            std::fs::create_dir_all(subject.dir().join("surf")).unwrap();
+            std::fs::copy(src_dir.join("surf/lh.white"), subject.surf_path(Hemisphere::Left, SurfaceKind::White)).unwrap();
+            let shifted = FsCurv::from_data(thickness.data.iter().map(|v| v - i as f32).collect());
+            crate::write_curv(subject.measure_path(Hemisphere::Left, "thickness"), &shifted);
+        }
+        assert!(matches!(LongitudinalSubject::discover(subjects_dir.path(), "other_base"), Err(NeuroformatsError::NoTimepoints(_))));
+
+        let long = LongitudinalSubject::discover(subjects_dir.path(), "subject_base").unwrap();
+        assert_eq!(vec!["subject_tp1", "subject_tp2"], long.timepoints);
+        assert_eq!(2, long.read_surfaces(Hemisphere::Left, SurfaceKind::White).unwrap().len());
+        assert_eq!(2, long.read_measures(Hemisphere::Left, "thickness").unwrap().len());
+        let change = long.measure_change(Hemisphere::Left, "thickness", "subject_tp1", "subject_tp2").unwrap();
+        assert_eq!(149244, change.len());
+        assert!(change.iter().all(|c| (c - 1.0).abs() < 1e-5));
+
+        // The order given in the base-tps file is used if it exists.
+        std::fs::create_dir_all(long.base().dir()).unwrap();
+        std::fs::write(long.base().dir().join("base-tps"), "subject_tp2\nsubject_tp1\n").unwrap();
+        assert_eq!(vec!["subject_tp2", "subject_tp1"], LongitudinalSubject::discover(subjects_dir.path(), "subject_base").unwrap().timepoints);
+        assert!(long.read_surfaces(Hemisphere::Right, SurfaceKind::White).is_err());
+    }
+
+    #[test]
+    fn the_mni_coords_of_subject_vertices_can_be_computed() {
+        // The demo subject has no orig.mgz, so we use the brain.mgz, which has the same geometry.
+        let subjects_dir = tempdir().unwrap();
+        let subject = Subject::new(subjects_dir.path(), "subject1");
+        std::fs::create_dir_all(subject.dir().join("surf")).unwrap();
+        std::fs::create_dir_all(subject.dir().join("mri").join("transforms")).unwrap();
+        let src_dir = Path::new("resources/subjects_dir/subject1");
+        std::fs::copy(src_dir.join("surf/lh.white"), subject.surf_path(Hemisphere::Left, SurfaceKind::White)).unwrap();
+        std::fs::copy(src_dir.join("mri/brain.mgz"), subject.dir().join("mri/orig.mgz")).unwrap();
+        std::fs::copy(src_dir.join("mri/transforms/talairach.xfm"), subject.dir().join("mri/transforms/talairach.xfm")).unwrap();
+
+        let mni305 = subject.vertex_mni_coords(Hemisphere::Left, SurfaceKind::White).unwrap();
+        assert_eq!(149244 * 3, mni305.len());
+
+        // The tkr to scanner transform of the demo subject is a translation by the c_ras.
+        let white = read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        let scanner = apply_affine_to_points(&subject.tkr_to_scanner_ras().unwrap(), &white.mesh.vertices[0..3]);
+        assert!((scanner[0] - (white.mesh.vertices[0] - 0.49995422)).abs() < 1e-3);
+        assert!((scanner[1] - (white.mesh.vertices[1] + 29.372742)).abs() < 1e-3);
+
+        let mni152 = mni305_to_mni152(&mni305);
+        assert_eq!(mni305.len(), mni152.len());
+    }
+}
@@ -0,0 +1,272 @@
+//! An event-driven pull parser for FreeSurfer surf files.
+//!
+//! The parser reads the header first and then the vertex and face data in chunks, so consumers can stop
+//! early (e.g., after reading only the header or some vertices) or process files larger than the available memory.
+
+
+use byteordered::ByteOrdered;
+
+use std::convert::TryFrom;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::ops::Range;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::fs_surface::FsSurfaceHeader;
+use crate::error::{NeuroformatsError, Result};
+use crate::util::{prealloc_capacity, skip_bytes};
+
+
+/// The default number of vertices or faces per chunk event.
+pub const DEFAULT_CHUNK_SIZE: usize = 65536;
+
+
+/// An event emitted by the [`SurfEventReader`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SurfEvent {
+    /// The file header. This is always the first event.
+    Header(FsSurfaceHeader),
+    /// A chunk of vertices. The `coords` are x,y,z triplets, the first one is for vertex `first_vertex`.
+    Vertices { first_vertex: usize, coords: Vec<f32> },
+    /// A chunk of faces. The `vertex_indices` are triplets, the first one is for face `first_face`. All vertex events come before the first face event.
+    Faces { first_face: usize, vertex_indices: Vec<i32> },
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Header,
+    Vertices { next: usize, total: usize, num_faces: usize },
+    Faces { next: usize, total: usize },
+    Done,
+}
+
+
+/// A pull parser for surf files, which emits [`SurfEvent`]s.
+///
+/// The reader is an iterator over `Result<SurfEvent>`. It stops after the last face chunk or the first error.
+///
+/// # Examples
+///
+/// Count the vertices with positive x coordinate without loading the whole mesh:
+///
+/// ```no_run
+/// use neuroformats::surf_events::{SurfEventReader, SurfEvent};
+/// let mut num_right = 0;
+/// for event in SurfEventReader::from_file("/path/to/subjects_dir/subject1/surf/lh.white").unwrap() {
+///     match event.unwrap() {
+///         SurfEvent::Vertices { coords, .. } => num_right += coords.chunks(3).filter(|c| c[0] > 0.0).count(),
+///         SurfEvent::Faces { .. } => break,
+///         _ => {},
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SurfEventReader<R: BufRead> {
+    input: R,
+    chunk_size: usize,
+    state: State,
+}
+
+
+#[cfg(feature = "fs")]
+impl SurfEventReader<BufReader<File>> {
+    /// Create a parser for the surf file at the given path, using the [`DEFAULT_CHUNK_SIZE`].
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SurfEventReader<BufReader<File>>> {
+        Ok(SurfEventReader::new(BufReader::new(File::open(path)?), DEFAULT_CHUNK_SIZE))
+    }
+}
+
+
+impl<R: BufRead> SurfEventReader<R> {
+    /// Create a parser that reads from the given byte stream, which must be at the start of the header.
+    ///
+    /// The `chunk_size` is the maximal number of vertices or faces per event. A value of `0` is treated as `1`.
+    pub fn new(input: R, chunk_size: usize) -> SurfEventReader<R> {
+        SurfEventReader { input, chunk_size: chunk_size.max(1), state: State::Header }
+    }
+
+
+    /// Get the underlying reader, positioned after the data of the last emitted event.
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+
+
+    /// Skip the next `count` vertices (or fewer, if less are left) without reading them into memory.
+    ///
+    /// This is only possible after the header event and before the first face event. Returns the number of skipped vertices.
+    pub fn skip_vertices(&mut self, count: usize) -> Result<usize> {
+        if let State::Vertices { next, total, num_faces } = self.state {
+            let count = count.min(total - next);
+            skip_bytes(&mut self.input, count * 12)?;
+            self.state = State::Vertices { next: next + count, total, num_faces };
+            Ok(count)
+        } else {
+            Ok(0)
+        }
+    }
+
+
+    /// Parse the next event, or return `None` if the end of the data was reached.
+    pub fn next_event(&mut self) -> Result<Option<SurfEvent>> {
+        match self.state {
+            State::Header => {
+                let hdr = FsSurfaceHeader::from_reader(&mut self.input)?;
+                let total = usize::try_from(hdr.num_vertices).map_err(|_| NeuroformatsError::InvalidFsSurfaceFormat)?;
+                let num_faces = usize::try_from(hdr.num_faces).map_err(|_| NeuroformatsError::InvalidFsSurfaceFormat)?;
+                self.state = State::Vertices { next: 0, total, num_faces };
+                Ok(Some(SurfEvent::Header(hdr)))
+            },
+            State::Vertices { next, total, num_faces } if next < total => {
+                let count = self.chunk_size.min(total - next);
+                let mut input = ByteOrdered::be(&mut self.input);
+                let mut coords = Vec::with_capacity(prealloc_capacity(count * 3));
+                for _ in 0..count * 3 {
+                    coords.push(input.read_f32()?);
+                }
+                self.state = State::Vertices { next: next + count, total, num_faces };
+                Ok(Some(SurfEvent::Vertices { first_vertex: next, coords }))
+            },
+            State::Vertices { num_faces, .. } => {
+                self.state = State::Faces { next: 0, total: num_faces };
+                self.next_event()
+            },
+            State::Faces { next, total } if next < total => {
+                let count = self.chunk_size.min(total - next);
+                let mut input = ByteOrdered::be(&mut self.input);
+                let mut vertex_indices = Vec::with_capacity(prealloc_capacity(count * 3));
+                for _ in 0..count * 3 {
+                    vertex_indices.push(input.read_i32()?);
+                }
+                self.state = State::Faces { next: next + count, total };
+                Ok(Some(SurfEvent::Faces { first_face: next, vertex_indices }))
+            },
+            State::Faces { .. } | State::Done => {
+                self.state = State::Done;
+                Ok(None)
+            },
+        }
+    }
+}
+
+
+impl<R: BufRead> Iterator for SurfEventReader<R> {
+    type Item = Result<SurfEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.next_event();
+        if event.is_err() {
+            self.state = State::Done;
+        }
+        event.transpose()
+    }
+}
+
+
+/// Read the coordinates of a range of vertices from a surf file, without loading the whole mesh.
+///
+/// The vertices before the range are skipped, and the face data is not read at all.
+///
+/// # Return value
+///
+/// The coordinates of the vertices in the range, as x,y,z triplets. Returns [`NeuroformatsError::VertexIndexOutOfRange`] if the range
+/// end is larger than the vertex count of the file.
+///
+/// # Examples
+///
+/// ```no_run
+/// let coords = neuroformats::surf_events::read_surf_vertices("/path/to/subjects_dir/subject1/surf/lh.white", 1000..1010).unwrap();
+/// assert_eq!(30, coords.len());
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_surf_vertices<P: AsRef<Path>>(path: P, range: Range<usize>) -> Result<Vec<f32>> {
+    let mut events = SurfEventReader::new(BufReader::new(File::open(path)?), range.len());
+    if let Some(SurfEvent::Header(hdr)) = events.next_event()? {
+        if range.end > hdr.num_vertices as usize {
+            return Err(NeuroformatsError::VertexIndexOutOfRange(range.end as i64, hdr.num_vertices as usize));
+        }
+    }
+    if range.is_empty() {
+        return Ok(Vec::new());
+    }
+    events.skip_vertices(range.start)?;
+    match events.next_event()? {
+        Some(SurfEvent::Vertices { coords, .. }) => Ok(coords),
+        _ => Err(NeuroformatsError::InvalidFsSurfaceFormat),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SURF_FILE: &str = "resources/subjects_dir/subject1/surf/lh.white";
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_events_of_a_surf_file_match_the_mesh() {
+        let surf = crate::read_surf(SURF_FILE).unwrap();
+        let mut vertices = Vec::new();
+        let mut faces = Vec::new();
+        let mut events = SurfEventReader::new(BufReader::new(File::open(SURF_FILE).unwrap()), 10000);
+        assert_eq!(Some(SurfEvent::Header(surf.header.clone())), events.next().map(|e| e.unwrap()));
+        for event in events {
+            match event.unwrap() {
+                SurfEvent::Header(_) => panic!("Unexpected second header."),
+                SurfEvent::Vertices { first_vertex, coords } => {
+                    assert_eq!(vertices.len() / 3, first_vertex);
+                    assert!(faces.is_empty());
+                    vertices.extend(coords);
+                },
+                SurfEvent::Faces { first_face, vertex_indices } => {
+                    assert_eq!(faces.len() / 3, first_face);
+                    faces.extend(vertex_indices);
+                },
+            }
+        }
+        assert_eq!(surf.mesh.vertices, vertices);
+        assert_eq!(surf.mesh.faces, faces);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn vertices_can_be_skipped_and_parsing_can_stop_early() {
+        let surf = crate::read_surf(SURF_FILE).unwrap();
+        let mut events = SurfEventReader::from_file(SURF_FILE).unwrap();
+        events.next().unwrap().unwrap();
+        assert_eq!(100, events.skip_vertices(100).unwrap());
+        match events.next().unwrap().unwrap() {
+            SurfEvent::Vertices { first_vertex, coords } => {
+                assert_eq!(100, first_vertex);
+                assert_eq!(surf.mesh.vertices[300..303], coords[0..3]);
+            },
+            _ => panic!("Expected vertex event."),
+        }
+    }
+
+    #[test]
+    fn a_truncated_surf_file_yields_an_error_event() {
+        let bytes = std::fs::read(SURF_FILE).unwrap();
+        let events: Vec<Result<SurfEvent>> = SurfEventReader::new(&bytes[..1000], 10).collect();
+        assert!(events.last().unwrap().is_err());
+        assert!(events[..events.len() - 1].iter().all(|e| e.is_ok()));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn a_range_of_vertices_can_be_read() {
+        let surf = crate::read_surf(SURF_FILE).unwrap();
+        assert_eq!(surf.mesh.vertices[3000..3030], read_surf_vertices(SURF_FILE, 1000..1010).unwrap()[..]);
+        assert_eq!(surf.mesh.vertices[..6], read_surf_vertices(SURF_FILE, 0..2).unwrap()[..]);
+        assert!(read_surf_vertices(SURF_FILE, 10..10).unwrap().is_empty());
+        assert!(matches!(read_surf_vertices(SURF_FILE, 149240..149245), Err(NeuroformatsError::VertexIndexOutOfRange(149245, 149244))));
+    }
+}
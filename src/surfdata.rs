@@ -0,0 +1,172 @@
+//! NaN-aware utilities for per-vertex data, like the overlays read from curv and MGH files.
+//!
+//! Statistical maps often contain NaN values, e.g., for the vertices of the medial wall. The statistics in this module ignore
+//! NaN values, and the transforms keep them, so maps can be z-scored, thresholded or masked before they are written with
+//! [`crate::write_curv`] or mapped to colors with [`crate::colormap`] without handling NaN values first. Masks are given as
+//! one `bool` per vertex, which is `true` for vertices to include, e.g., from [`crate::FsLabel::is_surface_vertex_in_label`].
+
+
+use crate::error::{NeuroformatsError, Result};
+
+
+/// Count the NaN values in per-vertex data.
+pub fn count_nan(data: &[f32]) -> usize {
+    data.iter().filter(|v| v.is_nan()).count()
+}
+
+
+/// Compute the mean of per-vertex data, ignoring NaN values.
+///
+/// # Return value
+///
+/// The mean, or `None` if there are no values besides NaN values.
+pub fn nan_mean(data: &[f32]) -> Option<f64> {
+    let (sum, count) = data.iter().filter(|v| !v.is_nan()).fold((0.0f64, 0usize), |(sum, count), v| (sum + *v as f64, count + 1));
+    if count > 0 { Some(sum / count as f64) } else { None }
+}
+
+
+/// Compute the population standard deviation of per-vertex data, ignoring NaN values.
+///
+/// # Return value
+///
+/// The standard deviation, or `None` if there are no values besides NaN values.
+pub fn nan_std(data: &[f32]) -> Option<f64> {
+    let mean = nan_mean(data)?;
+    let (sum_sq, count) = data.iter().filter(|v| !v.is_nan()).fold((0.0f64, 0usize), |(sum_sq, count), v| (sum_sq + (*v as f64 - mean).powi(2), count + 1));
+    Some((sum_sq / count as f64).sqrt())
+}
+
+
+/// Compute the mean of the per-vertex data of the vertices in a mask, ignoring NaN values.
+///
+/// # Return value
+///
+/// The mean, `None` if the mask contains no vertices with values besides NaN values, or [`NeuroformatsError::VertexCountMismatch`] if the
+/// length of `mask` differs from the length of `data`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let cortex = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.cortex.label").unwrap();
+/// let mask = cortex.is_surface_vertex_in_label(thickness.data.len());
+/// let mean_cortical_thickness = neuroformats::surfdata::masked_mean(&thickness.data, &mask).unwrap();
+/// ```
+pub fn masked_mean(data: &[f32], mask: &[bool]) -> Result<Option<f64>> {
+    Ok(nan_mean(&mask_to_nan(data, mask)?))
+}
+
+
+/// Compute the population standard deviation of the per-vertex data of the vertices in a mask, ignoring NaN values.
+///
+/// # Return value
+///
+/// The standard deviation, `None` if the mask contains no vertices with values besides NaN values, or [`NeuroformatsError::VertexCountMismatch`]
+/// if the length of `mask` differs from the length of `data`.
+pub fn masked_std(data: &[f32], mask: &[bool]) -> Result<Option<f64>> {
+    Ok(nan_std(&mask_to_nan(data, mask)?))
+}
+
+
+/// Set the values of all vertices which are not in a mask to NaN.
+///
+/// # Return value
+///
+/// The masked data, or [`NeuroformatsError::VertexCountMismatch`] if the length of `mask` differs from the length of `data`.
+pub fn mask_to_nan(data: &[f32], mask: &[bool]) -> Result<Vec<f32>> {
+    if mask.len() != data.len() {
+        return Err(NeuroformatsError::VertexCountMismatch(data.len(), mask.len()));
+    }
+    Ok(data.iter().zip(mask.iter()).map(|(v, in_mask)| if *in_mask { *v } else { f32::NAN }).collect())
+}
+
+
+/// Replace the NaN values in per-vertex data with the given value, e.g., with `0.0` for software which cannot handle NaN values.
+pub fn fill_nan(data: &[f32], value: f32) -> Vec<f32> {
+    data.iter().map(|v| if v.is_nan() { value } else { *v }).collect()
+}
+
+
+/// Z-score per-vertex data, i.e., subtract the mean and divide by the standard deviation, see [`nan_mean`] and [`nan_std`].
+///
+/// NaN values are ignored for the mean and standard deviation and stay NaN. To z-score only the vertices in a mask, mask the data
+/// with [`mask_to_nan`] first.
+///
+/// # Return value
+///
+/// The z-scores. If the values have no variance, all values besides NaN values are mapped to `0.0`.
+pub fn zscore(data: &[f32]) -> Vec<f32> {
+    let (mean, std) = match (nan_mean(data), nan_std(data)) {
+        (Some(mean), Some(std)) => (mean, std),
+        _ => return data.to_vec(),
+    };
+    data.iter().map(|v| match (v.is_nan(), std > 0.0) {
+        (true, _) => f32::NAN,
+        (false, true) => ((*v as f64 - mean) / std) as f32,
+        (false, false) => 0.0,
+    }).collect()
+}
+
+
+/// Threshold per-vertex data, e.g., a statistical map, by replacing all values whose absolute value is below `min_abs` with `fill`.
+///
+/// Both positive and negative values are kept if their absolute value reaches the threshold. NaN values stay NaN.
+///
+/// # Examples
+///
+/// ```
+/// let sig = vec![-3.0, -1.0, 0.5, 2.5, f32::NAN];
+/// let thresholded = neuroformats::surfdata::threshold(&sig, 1.3, 0.0);
+/// assert_eq!(vec![-3.0, 0.0, 0.0, 2.5], thresholded[..4].to_vec());
+/// assert!(thresholded[4].is_nan());
+/// ```
+pub fn threshold(data: &[f32], min_abs: f32, fill: f32) -> Vec<f32> {
+    data.iter().map(|v| if v.abs() < min_abs { fill } else { *v }).collect()
+}
+
+
+/// Compute the absolute values of per-vertex data, e.g., to display the strength of a signed statistical map. NaN values stay NaN.
+pub fn abs(data: &[f32]) -> Vec<f32> {
+    data.iter().map(|v| v.abs()).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn statistics_of_data_with_nan_values_can_be_computed() {
+        let data = [2.0, f32::NAN, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(1, count_nan(&data));
+        assert_abs_diff_eq!(5.0, nan_mean(&data).unwrap());
+        assert_abs_diff_eq!(2.0, nan_std(&data).unwrap());
+        assert_eq!(None, nan_mean(&[f32::NAN]));
+        assert_eq!(None, nan_std(&[]));
+
+        let mask = [true, true, true, true, false, false, false, false, false];
+        assert_abs_diff_eq!(10.0 / 3.0, masked_mean(&data, &mask).unwrap().unwrap(), epsilon = 1e-9);
+        assert!(masked_std(&data, &mask).unwrap().unwrap() > 0.0);
+        assert_eq!(None, masked_mean(&data, &[false; 9]).unwrap());
+        assert!(matches!(masked_mean(&data, &[true]), Err(NeuroformatsError::VertexCountMismatch(9, 1))));
+    }
+
+    #[test]
+    fn data_with_nan_values_can_be_transformed() {
+        let data = [-2.0, f32::NAN, 1.0, 3.0];
+        assert_eq!(vec![-2.0, 0.0, 1.0, 3.0], fill_nan(&data, 0.0));
+        assert_eq!(vec![2.0, 1.0, 3.0], abs(&data).into_iter().filter(|v| !v.is_nan()).collect::<Vec<f32>>());
+        assert_eq!(vec![-2.0, 0.0, 3.0], threshold(&data, 1.5, 0.0).into_iter().filter(|v| !v.is_nan()).collect::<Vec<f32>>());
+
+        let masked = mask_to_nan(&data, &[true, true, false, true]).unwrap();
+        assert_eq!(2, count_nan(&masked));
+
+        let z = zscore(&data);
+        assert!(z[1].is_nan());
+        assert_abs_diff_eq!(0.0, nan_mean(&z).unwrap(), epsilon = 1e-6);
+        assert_abs_diff_eq!(1.0, nan_std(&z).unwrap(), epsilon = 1e-6);
+        assert_eq!(vec![0.0, 0.0], zscore(&[4.0, 4.0]));
+    }
+}
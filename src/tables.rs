@@ -0,0 +1,350 @@
+//! Export of per-vertex data and per-region statistics as flat CSV and JSON files, e.g., for statistical analyses in R or Python.
+//!
+//! NaN values are written as `NA` in CSV files, which R and pandas read as missing values, and as `null` in JSON files.
+
+
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_annot::FsAnnot;
+use crate::fs_curv::FsCurv;
+use crate::fs_label::FsLabel;
+use crate::util::{json_array, json_number, json_string};
+#[cfg(feature = "fs")]
+use crate::util::WriteOptions;
+
+
+/// Statistics of per-vertex data within a region of a parcellation, see [`FsAnnot::region_table`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionStats {
+    /// The id of the region in the colortable.
+    pub id: i32,
+    /// The name of the region.
+    pub name: String,
+    /// The number of vertices assigned to the region.
+    pub num_vertices: usize,
+    /// The mean of the non-NaN values of the region, or NaN if there are none.
+    pub mean: f32,
+    /// The sample standard deviation of the non-NaN values of the region, or NaN if there are less than 2.
+    pub std: f32,
+}
+
+
+/// Options for computing region statistics with [`FsAnnot::region_table_with_options`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionTableOptions<'a> {
+    /// Optional cortex label, like `label/?h.cortex.label`. Vertices outside of it, i.e., the medial wall, are ignored, so they count
+    /// neither towards the number of vertices nor the statistics of their region.
+    pub exclude_medial_wall: Option<&'a FsLabel>,
+}
+
+
+impl FsCurv {
+    /// Write the per-vertex values as CSV with the columns `vertex` and `value`.
+    pub fn write_csv<W: Write + ?Sized>(&self, out: &mut W) -> Result<()> {
+        writeln!(out, "vertex,value")?;
+        for (idx, value) in self.data.iter().enumerate() {
+            writeln!(out, "{},{}", idx, csv_number(*value))?;
+        }
+        Ok(())
+    }
+
+
+    /// Write the per-vertex values as a JSON object with the field `values`, an array with one value per vertex.
+    pub fn write_json<W: Write + ?Sized>(&self, out: &mut W) -> Result<()> {
+        writeln!(out, r#"{{"values":{}}}"#, json_array(self.data.iter().map(|v| json_number(*v as f64))))?;
+        Ok(())
+    }
+
+
+    /// Write the per-vertex values to a CSV file, see [`FsCurv::write_csv`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// thickness.to_csv("/tmp/lh_thickness.csv").unwrap();
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn to_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_file(path, |out| self.write_csv(out))
+    }
+
+
+    /// Write the per-vertex values to a JSON file, see [`FsCurv::write_json`].
+    #[cfg(feature = "fs")]
+    pub fn to_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_file(path, |out| self.write_json(out))
+    }
+}
+
+
+impl FsAnnot {
+    /// Compute statistics of per-vertex data for each region of the parcellation, like the mean cortical thickness of each region.
+    ///
+    /// # Return value
+    ///
+    /// The statistics of the regions, in colortable order. Returns [`NeuroformatsError::VertexCountMismatch`] if `data` does not
+    /// contain one value per vertex of the parcellation.
+    pub fn region_table(&self, data: &[f32]) -> Result<Vec<RegionStats>> {
+        self.region_table_with_options(data, &RegionTableOptions::default())
+    }
+
+
+    /// Compute statistics of per-vertex data for each region of the parcellation with the given [`RegionTableOptions`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use neuroformats::tables::RegionTableOptions;
+    /// let aparc = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// let cortex = neuroformats::read_label("/path/to/subjects_dir/subject1/label/lh.cortex.label").unwrap();
+    /// let table = aparc.region_table_with_options(&thickness.data, &RegionTableOptions { exclude_medial_wall: Some(&cortex) }).unwrap();
+    /// ```
+    pub fn region_table_with_options(&self, data: &[f32], options: &RegionTableOptions) -> Result<Vec<RegionStats>> {
+        let (region_values, num_vertices) = self.region_values(data, options)?;
+        Ok(self.colortable.regions.iter().zip(region_values.iter().zip(num_vertices)).map(|(region, (values, num_vertices))| {
+            region_stats(region.id, &region.name, values, num_vertices)
+        }).collect())
+    }
+
+
+    /// Compute statistics of per-vertex data for each region of the parcellation, processing the regions in parallel.
+    ///
+    /// Requires the `rayon` feature. The result is the same as for [`FsAnnot::region_table`], and can be written as CSV or JSON with
+    /// [`write_region_stats_csv`] and [`write_region_stats_json`]. This is useful for fine parcellations with many regions, like `aparc.a2009s`.
+    #[cfg(feature = "rayon")]
+    pub fn all_region_stats_parallel(&self, data: &[f32]) -> Result<Vec<RegionStats>> {
+        use rayon::prelude::*;
+        let (region_values, num_vertices) = self.region_values(data, &RegionTableOptions::default())?;
+        Ok(self.colortable.regions.par_iter().zip(region_values.par_iter().zip(num_vertices)).map(|(region, (values, num_vertices))| {
+            region_stats(region.id, &region.name, values, num_vertices)
+        }).collect())
+    }
+
+
+    /// Collect the non-NaN values and count the vertices of each region, in colortable order.
+    fn region_values(&self, data: &[f32], options: &RegionTableOptions) -> Result<(Vec<Vec<f64>>, Vec<usize>)> {
+        if data.len() != self.vertex_labels.len() {
+            return Err(NeuroformatsError::VertexCountMismatch(self.vertex_labels.len(), data.len()));
+        }
+        let in_cortex = options.exclude_medial_wall.map(|cortex| cortex.vertex_mask(data.len()));
+        let num_regions = self.colortable.regions.len();
+        let mut region_values: Vec<Vec<f64>> = vec![Vec::new(); num_regions];
+        let mut num_vertices = vec![0usize; num_regions];
+        let lookup = self.region_lookup();
+        for (vidx, (label, value)) in self.vertex_labels.iter().zip(data.iter()).enumerate() {
+            if in_cortex.as_ref().is_some_and(|in_cortex| !in_cortex[vidx]) {
+                continue;
+            }
+            if let Some(region_idx) = lookup.region_index(*label) {
+                num_vertices[region_idx] += 1;
+                if !value.is_nan() {
+                    region_values[region_idx].push(*value as f64);
+                }
+            }
+        }
+        Ok((region_values, num_vertices))
+    }
+
+
+    /// Write the statistics of per-vertex data for each region as CSV, with the columns `region`, `num_vertices`, `mean` and `std`.
+    ///
+    /// See [`FsAnnot::region_table`] for details.
+    pub fn write_region_table_csv<W: Write + ?Sized>(&self, out: &mut W, data: &[f32]) -> Result<()> {
+        write_region_stats_csv(out, &self.region_table(data)?)
+    }
+
+
+    /// Write the statistics of per-vertex data for each region as a JSON array, with one object per region with the fields `region`,
+    /// `num_vertices`, `mean` and `std`.
+    ///
+    /// See [`FsAnnot::region_table`] for details.
+    pub fn write_region_table_json<W: Write + ?Sized>(&self, out: &mut W, data: &[f32]) -> Result<()> {
+        write_region_stats_json(out, &self.region_table(data)?)
+    }
+
+
+    /// Write the statistics of per-vertex data for each region to a CSV file, see [`FsAnnot::write_region_table_csv`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// annot.region_table_csv("/tmp/lh_aparc_thickness.csv", &thickness.data).unwrap();
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn region_table_csv<P: AsRef<Path>>(&self, path: P, data: &[f32]) -> Result<()> {
+        write_file(path, |out| self.write_region_table_csv(out, data))
+    }
+
+
+    /// Write the statistics of per-vertex data for each region to a JSON file, see [`FsAnnot::write_region_table_json`].
+    #[cfg(feature = "fs")]
+    pub fn region_table_json<P: AsRef<Path>>(&self, path: P, data: &[f32]) -> Result<()> {
+        write_file(path, |out| self.write_region_table_json(out, data))
+    }
+}
+
+
+/// Write region statistics as CSV, with the columns `region`, `num_vertices`, `mean` and `std`. NaN values are written as `NA`.
+pub fn write_region_stats_csv<W: Write + ?Sized>(out: &mut W, table: &[RegionStats]) -> Result<()> {
+    writeln!(out, "region,num_vertices,mean,std")?;
+    for stats in table.iter() {
+        writeln!(out, "{},{},{},{}", csv_string(&stats.name), stats.num_vertices, csv_number(stats.mean), csv_number(stats.std))?;
+    }
+    Ok(())
+}
+
+
+/// Write region statistics as a JSON array, with one object per region with the fields `region`, `num_vertices`, `mean` and `std`.
+/// NaN values are written as `null`.
+pub fn write_region_stats_json<W: Write + ?Sized>(out: &mut W, table: &[RegionStats]) -> Result<()> {
+    let rows = table.iter().map(|stats| format!(r#"{{"region":{},"num_vertices":{},"mean":{},"std":{}}}"#,
+        json_string(&stats.name), stats.num_vertices, json_number(stats.mean as f64), json_number(stats.std as f64)));
+    writeln!(out, "{}", json_array(rows))?;
+    Ok(())
+}
+
+
+/// Compute the statistics of a region from its non-NaN values.
+fn region_stats(id: i32, name: &str, values: &[f64], num_vertices: usize) -> RegionStats {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+    RegionStats {
+        id,
+        name: name.to_string(),
+        num_vertices,
+        mean: if values.is_empty() { f32::NAN } else { mean as f32 },
+        std: if values.len() < 2 { f32::NAN } else { std as f32 },
+    }
+}
+
+
+/// Create the file at the given path and write its contents with the given function.
+#[cfg(feature = "fs")]
+fn write_file<P: AsRef<Path>, F: FnOnce(&mut dyn Write) -> Result<()>>(path: P, write: F) -> Result<()> {
+    let options = WriteOptions::default();
+    let mut writer = options.create(path)?;
+    write(&mut writer)?;
+    options.finish(writer)?;
+    Ok(())
+}
+
+
+/// Format a number for CSV output. NaN values are written as `NA`.
+fn csv_number(value: f32) -> String {
+    if value.is_nan() { String::from("NA") } else { value.to_string() }
+}
+
+
+/// Format a string for CSV output, quoting it if it contains separators, quotes or line breaks.
+fn csv_string(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::{read_annot, read_curv};
+
+    #[test]
+    fn curv_values_can_be_written_as_csv_and_json() {
+        let curv = FsCurv { header: Default::default(), data: vec![1.5, f32::NAN, 2.0] };
+        let mut csv = Vec::new();
+        curv.write_csv(&mut csv).unwrap();
+        assert_eq!("vertex,value\n0,1.5\n1,NA\n2,2\n", String::from_utf8(csv).unwrap());
+
+        let mut json = Vec::new();
+        curv.write_json(&mut json).unwrap();
+        assert_eq!("{\"values\":[1.5,null,2]}\n", String::from_utf8(json).unwrap());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn region_statistics_can_be_computed() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let thickness = read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        let table = annot.region_table(&thickness.data).unwrap();
+
+        assert_eq!(36, table.len());
+        assert_eq!("bankssts", table[1].name);
+        assert_eq!(1722, table[1].num_vertices);
+        let values: Vec<f32> = annot.region_vertices(String::from("bankssts")).iter().map(|v| thickness.data[*v]).collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        assert!((table[1].mean - mean).abs() < 1e-4);
+        assert!(table[1].std > 0.0 && table[1].std < 2.0);
+
+        assert!(matches!(annot.region_table(&[1.0]), Err(NeuroformatsError::VertexCountMismatch(149244, 1))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_medial_wall_can_be_excluded_from_region_statistics() {
+        let annot = read_annot("resources/annot/tiny.annot").unwrap();
+        let data = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let cortex = FsLabel::from_bytes(b"#!ascii label\n2\n1 0.0 0.0 0.0 0.0\n3 0.0 0.0 0.0 0.0\n").unwrap();
+        let all = annot.region_table(&data).unwrap();
+        let masked = annot.region_table_with_options(&data, &RegionTableOptions { exclude_medial_wall: Some(&cortex) }).unwrap();
+
+        assert_eq!(all.len(), masked.len());
+        assert_eq!(5, all.iter().map(|r| r.num_vertices).sum::<usize>());
+        assert_eq!(2, masked.iter().map(|r| r.num_vertices).sum::<usize>());
+        let unmasked = annot.region_table_with_options(&data, &RegionTableOptions::default()).unwrap();
+        assert!(all.iter().zip(unmasked.iter()).all(|(a, u)| a.num_vertices == u.num_vertices));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn region_statistics_can_be_written_as_csv_and_json() {
+        let annot = read_annot("resources/annot/tiny.annot").unwrap();
+        let data = [1.0, 2.0, 3.0, f32::NAN, 5.0];
+        let table = annot.region_table(&data).unwrap();
+
+        let mut csv = Vec::new();
+        annot.write_region_table_csv(&mut csv, &data).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(3, csv.lines().count());
+        assert_eq!("region,num_vertices,mean,std", csv.lines().next().unwrap());
+        assert!(csv.lines().nth(2).unwrap().starts_with(&format!("bankssts,{},", table[1].num_vertices)));
+
+        let mut json = Vec::new();
+        annot.write_region_table_json(&mut json, &data).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!("unknown", json[0]["region"]);
+        assert_eq!(table[1].num_vertices as u64, json[1]["num_vertices"].as_u64().unwrap());
+    }
+
+    #[cfg(all(feature = "rayon", feature = "fs"))]
+    #[test]
+    fn the_parallel_region_statistics_are_the_same() {
+        let annot = read_annot("resources/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+        let thickness = read_curv("resources/subjects_dir/subject1/surf/lh.thickness").unwrap();
+        let serial = annot.region_table(&thickness.data).unwrap();
+        let parallel = annot.all_region_stats_parallel(&thickness.data).unwrap();
+        let (mut serial_csv, mut parallel_csv) = (Vec::new(), Vec::new());
+        write_region_stats_csv(&mut serial_csv, &serial).unwrap();
+        write_region_stats_csv(&mut parallel_csv, &parallel).unwrap();
+        assert_eq!(serial_csv, parallel_csv);
+        assert!(annot.all_region_stats_parallel(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn csv_strings_are_quoted_if_needed() {
+        assert_eq!("bankssts", csv_string("bankssts"));
+        assert_eq!("\"a,b\"", csv_string("a,b"));
+        assert_eq!("\"say \"\"hi\"\"\"", csv_string("say \"hi\""));
+    }
+}
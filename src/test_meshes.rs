@@ -0,0 +1,13 @@
+//! Small meshes shared by the unit tests of several modules.
+
+
+use crate::fs_surface::BrainMesh;
+
+
+/// A tetrahedron, the smallest closed triangle mesh, with its vertices at the origin and on the axes and outward facing normals.
+pub(crate) fn tetrahedron() -> BrainMesh {
+    BrainMesh {
+        vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        faces: vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3],
+    }
+}
@@ -0,0 +1,107 @@
+//! Functions for estimating the cortical thickness from the white and pial surfaces.
+//!
+//! FreeSurfer computes the thickness with `mris_make_surfaces` and stores it in the `?h.thickness` files. The
+//! estimates computed here are simple approximations, intended for QC comparisons with those files.
+
+
+use crate::fs_surface::BrainMesh;
+use crate::error::{NeuroformatsError, Result};
+
+
+/// The method used to estimate the cortical thickness, see [`compute_thickness`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThicknessMethod {
+    /// The distance between corresponding vertices of the white and pial surface.
+    Corresponding,
+    /// The mean of the distance from each white vertex to the closest pial vertex and the distance from the pial vertex to the closest white vertex. This is closer to the FreeSurfer definition of thickness.
+    SymmetricClosest,
+    /// Like [`ThicknessMethod::SymmetricClosest`], but with the distances to the closest points on the faces of the other surface instead of its vertices,
+    /// see [`crate::bvh::MeshBvh::closest_point`]. This does not overestimate the distances on coarse meshes.
+    SymmetricClosestPoint,
+}
+
+
+/// Compute a per-vertex estimate of the cortical thickness from the white and pial surfaces of a hemisphere.
+///
+/// The surfaces must have the same number of vertices, and vertex `i` of the white surface must correspond to vertex `i` of the pial surface, which is the
+/// case for the surfaces of a hemisphere produced by FreeSurfer. Returns [`NeuroformatsError::VertexCountMismatch`] otherwise.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::thickness::{compute_thickness, ThicknessMethod};
+/// let white = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let pial = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.pial").unwrap();
+/// let thickness = compute_thickness(&white.mesh, &pial.mesh, ThicknessMethod::SymmetricClosest).unwrap();
+/// ```
+pub fn compute_thickness(white: &BrainMesh, pial: &BrainMesh, method: ThicknessMethod) -> Result<Vec<f32>> {
+    let num_vertices = white.num_vertices();
+    if pial.num_vertices() != num_vertices {
+        return Err(NeuroformatsError::VertexCountMismatch(num_vertices, pial.num_vertices()));
+    }
+
+    let thickness = match method {
+        ThicknessMethod::Corresponding => {
+            (0..num_vertices).map(|i| distance(white.vertex_coords(i), pial.vertex_coords(i))).collect()
+        },
+        ThicknessMethod::SymmetricClosest => {
+            let white_index = white.build_spatial_index();
+            let pial_index = pial.build_spatial_index();
+            (0..num_vertices).map(|i| {
+                let (_, white_to_pial) = pial_index.nearest_vertex(white.vertex_coords(i)).unwrap();
+                let (_, pial_to_white) = white_index.nearest_vertex(pial.vertex_coords(i)).unwrap();
+                0.5 * (white_to_pial + pial_to_white)
+            }).collect()
+        },
+        ThicknessMethod::SymmetricClosestPoint => {
+            let white_bvh = white.build_bvh();
+            let pial_bvh = pial.build_bvh();
+            let closest_distance = |bvh: &crate::bvh::MeshBvh, point| bvh.closest_point(point).map(|c| c.distance).unwrap_or(f32::NAN);
+            (0..num_vertices).map(|i| {
+                0.5 * (closest_distance(&pial_bvh, white.vertex_coords(i)) + closest_distance(&white_bvh, pial.vertex_coords(i)))
+            }).collect()
+        },
+    };
+    Ok(thickness)
+}
+
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_thickness_can_be_computed_from_white_and_pial_surfaces() {
+        let white = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap().mesh;
+        // The demo subject has no pial surface, so we move the white surface outwards along the vertex normals.
+        let normals = white.vertex_normals();
+        let pial = BrainMesh {
+            vertices: white.vertices.iter().zip(normals.iter()).map(|(v, n)| v + 2.5 * n).collect(),
+            faces: white.faces.clone(),
+        };
+
+        let corresponding = compute_thickness(&white, &pial, ThicknessMethod::Corresponding).unwrap();
+        assert_eq!(149244, corresponding.len());
+        assert!(corresponding.iter().all(|t| (t - 2.5).abs() < 1e-4));
+
+        let closest = compute_thickness(&white, &pial, ThicknessMethod::SymmetricClosest).unwrap();
+        assert!(closest.iter().all(|t| *t <= 2.5 + 1e-4));
+        let mean = closest.iter().sum::<f32>() / closest.len() as f32;
+        assert!(mean > 0.5 && mean <= 2.5);
+
+        let closest_point = compute_thickness(&white, &pial, ThicknessMethod::SymmetricClosestPoint).unwrap();
+        assert!(closest_point.iter().zip(closest.iter()).all(|(p, v)| *p <= v + 1e-4));
+    }
+
+    #[test]
+    fn computing_the_thickness_from_surfaces_with_different_vertex_counts_fails() {
+        let lh = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap().mesh;
+        let rh = crate::read_surf("resources/subjects_dir/subject1/surf/rh.white").unwrap().mesh;
+        assert!(matches!(compute_thickness(&lh, &rh, ThicknessMethod::Corresponding), Err(NeuroformatsError::VertexCountMismatch(149244, 153333))));
+    }
+}
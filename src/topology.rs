@@ -0,0 +1,127 @@
+//! Topology diagnostics for brain meshes.
+//!
+//! FreeSurfer surfaces of a hemisphere are closed 2-manifolds with the topology of a sphere, i.e., they have an
+//! Euler characteristic of 2. Deviations from that indicate topological defects, so these functions are useful for QC.
+
+
+use std::collections::{HashMap, HashSet};
+
+use crate::fs_surface::BrainMesh;
+
+
+impl BrainMesh {
+    /// Count how many faces share each undirected edge of the mesh. The keys are vertex index pairs with the smaller index first.
+    fn edge_face_counts(&self) -> HashMap<(usize, usize), usize> {
+        let mut counts = HashMap::with_capacity(self.faces.len());
+        for face in self.faces.chunks_exact(3) {
+            for corner in 0..3 {
+                let (a, b) = (face[corner] as usize, face[(corner + 1) % 3] as usize);
+                *counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+
+    /// Get the number of unique undirected edges of the mesh.
+    pub fn num_edges(&self) -> usize {
+        self.edge_face_counts().len()
+    }
+
+
+    /// Compute the Euler characteristic `V - E + F` of the mesh.
+    ///
+    /// A closed surface without handles, like a FreeSurfer white surface after topology correction, has an Euler characteristic of 2.
+    /// Each handle (hole through the surface) reduces it by 2.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let num_defects = (2 - surf.mesh.euler_characteristic()) / 2;
+    /// ```
+    pub fn euler_characteristic(&self) -> i64 {
+        self.num_vertices() as i64 - self.num_edges() as i64 + self.num_faces() as i64
+    }
+
+
+    /// Get the number of boundary edges, i.e., edges that are part of a single face only.
+    pub fn num_boundary_edges(&self) -> usize {
+        self.edge_face_counts().values().filter(|&&count| count == 1).count()
+    }
+
+
+    /// Determine whether the mesh is closed, i.e., has no boundary edges.
+    pub fn is_closed(&self) -> bool {
+        self.num_boundary_edges() == 0
+    }
+
+
+    /// Find the non-manifold edges of the mesh, i.e., edges shared by more than two faces.
+    ///
+    /// # Return value
+    ///
+    /// The edges as vertex index pairs with the smaller index first, sorted.
+    pub fn non_manifold_edges(&self) -> Vec<(usize, usize)> {
+        let mut edges: Vec<(usize, usize)> = self.edge_face_counts().into_iter().filter(|(_, count)| *count > 2).map(|(edge, _)| edge).collect();
+        edges.sort_unstable();
+        edges
+    }
+
+
+    /// Find duplicate faces, i.e., faces which consist of the same vertices as an earlier face, regardless of vertex order.
+    ///
+    /// # Return value
+    ///
+    /// The indices of the duplicate faces, in ascending order. The first occurrence of a face is not included.
+    pub fn duplicate_faces(&self) -> Vec<usize> {
+        let mut seen = HashSet::with_capacity(self.num_faces());
+        let mut duplicates = Vec::new();
+        for (face_idx, face) in self.faces.chunks_exact(3).enumerate() {
+            let mut key = [face[0], face[1], face[2]];
+            key.sort_unstable();
+            if !seen.insert(key) {
+                duplicates.push(face_idx);
+            }
+        }
+        duplicates
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::test_meshes::tetrahedron;
+
+    #[test]
+    fn the_topology_of_a_closed_mesh_can_be_checked() {
+        let mesh = tetrahedron();
+        assert_eq!(6, mesh.num_edges());
+        assert_eq!(2, mesh.euler_characteristic());
+        assert!(mesh.is_closed());
+        assert!(mesh.non_manifold_edges().is_empty());
+        assert!(mesh.duplicate_faces().is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn the_demo_surface_is_a_closed_mesh_with_sphere_topology() {
+        let surf = crate::read_surf("resources/subjects_dir/subject1/surf/lh.white").unwrap();
+        assert_eq!(2, surf.mesh.euler_characteristic());
+        assert!(surf.mesh.is_closed());
+    }
+
+    #[test]
+    fn topological_defects_are_detected() {
+        let mut mesh = tetrahedron();
+        mesh.faces.truncate(9);
+        assert_eq!(3, mesh.num_boundary_edges());
+        assert!(!mesh.is_closed());
+        assert_eq!(1, mesh.euler_characteristic());
+
+        let mut mesh = tetrahedron();
+        mesh.faces.extend_from_slice(&[2, 1, 0]);
+        assert_eq!(vec![4], mesh.duplicate_faces());
+        assert_eq!(vec![(0, 1), (0, 2), (1, 2)], mesh.non_manifold_edges());
+    }
+}
@@ -0,0 +1,331 @@
+//! Functions for reading FreeSurfer linear transform files.
+//!
+//! Supported are LTA (linear transform array) files, like `mri/transforms/talairach.lta`, and MNI
+//! transform files, like `mri/transforms/talairach.xfm`. Both store a 4x4 affine matrix that maps
+//! coordinates from a source space to a destination space. Non-linear GCAM morphs in `.m3z` format are not supported.
+
+
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::path::{Path};
+use std::fmt;
+
+use ndarray::{Array, Array2};
+
+use crate::error::{NeuroformatsError, Result};
+use crate::projection::apply_affine;
+#[cfg(feature = "fs")]
+use crate::util::open_maybe_compressed;
+
+
+/// LTA transform type: maps voxel indices of the source volume to voxel indices of the destination volume.
+pub const LINEAR_VOX_TO_VOX : i32 = 0;
+/// LTA transform type: maps scanner RAS coordinates of the source volume to scanner RAS coordinates of the destination volume.
+pub const LINEAR_RAS_TO_RAS : i32 = 1;
+/// LTA transform type: maps physical voxel coordinates (voxel indices scaled by voxel size).
+pub const LINEAR_PHYSVOX_TO_PHYSVOX : i32 = 2;
+/// LTA transform type: maps surface RAS (tkregister) coordinates, as used for FreeSurfer surfaces.
+pub const LINEAR_CORONAL_RAS_TO_CORONAL_RAS : i32 = 21;
+
+
+/// Geometry information on the source or destination volume of an LTA transform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LtaVolumeInfo {
+    pub valid: bool,
+    pub filename: String,
+    pub volume: [i32; 3],
+    pub voxelsize: [f32; 3],
+    pub xras: [f32; 3],
+    pub yras: [f32; 3],
+    pub zras: [f32; 3],
+    pub cras: [f32; 3],
+}
+
+
+impl Default for LtaVolumeInfo {
+    fn default() -> LtaVolumeInfo {
+        LtaVolumeInfo {
+            valid: false,
+            filename: String::new(),
+            volume: [0; 3],
+            voxelsize: [1.0; 3],
+            xras: [-1.0, 0.0, 0.0],
+            yras: [0.0, 0.0, -1.0],
+            zras: [0.0, 1.0, 0.0],
+            cras: [0.0; 3],
+        }
+    }
+}
+
+
+/// Models a FreeSurfer LTA (linear transform array) file. Only the first transform of the file is read, as FreeSurfer files contain exactly one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsLta {
+    /// The transform type, one of the `LINEAR_*` constants in this module, e.g., [`LINEAR_RAS_TO_RAS`].
+    pub transform_type: i32,
+    /// The 4x4 affine transformation matrix.
+    pub matrix: Array2<f32>,
+    pub src: LtaVolumeInfo,
+    pub dst: LtaVolumeInfo,
+    pub subject: Option<String>,
+    pub fscale: Option<f32>,
+}
+
+
+/// Models an MNI transform file (`.xfm`) containing a linear transform, like FreeSurfer's `talairach.xfm`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsXfm {
+    /// The 4x4 affine transformation matrix. It maps scanner RAS coordinates of the subject to MNI305 space for `talairach.xfm`.
+    pub matrix: Array2<f32>,
+}
+
+
+impl FsLta {
+    /// Read an LTA file, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsLta> {
+        FsLta::from_reader(open_maybe_compressed(path)?)
+    }
+
+
+    /// Read an LTA transform from the given text input.
+    pub fn from_reader<S: BufRead>(reader: S) -> Result<FsLta> {
+        let mut lta = FsLta {
+            transform_type: LINEAR_RAS_TO_RAS,
+            matrix: Array::eye(4),
+            src: LtaVolumeInfo::default(),
+            dst: LtaVolumeInfo::default(),
+            subject: None,
+            fscale: None,
+        };
+
+        let lines: Vec<String> = reader.lines().collect::<std::result::Result<_, _>>()?;
+        let mut lines = lines.iter().map(|l| strip_comment(l)).filter(|l| !l.is_empty());
+        let mut matrix_read = false;
+        let mut current_vol_info: Option<&mut LtaVolumeInfo> = None;
+
+        while let Some(line) = lines.next() {
+            if line == "src volume info" {
+                current_vol_info = Some(&mut lta.src);
+                continue;
+            }
+            if line == "dst volume info" {
+                current_vol_info = Some(&mut lta.dst);
+                continue;
+            }
+            if let Some(subject) = line.strip_prefix("subject ") {
+                lta.subject = Some(subject.trim().to_string());
+                continue;
+            }
+            if let Some(fscale) = line.strip_prefix("fscale ") {
+                lta.fscale = Some(parse_values::<f32>(fscale, 1)?[0]);
+                continue;
+            }
+
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    let (key, value) = (key.trim(), value.trim());
+                    if let Some(info) = current_vol_info.as_mut() {
+                        parse_volume_info_entry(info, key, value)?;
+                    } else if key == "type" {
+                        lta.transform_type = parse_values::<i32>(value, 1)?[0];
+                    }
+                },
+                None => {
+                    // The line with the matrix shape, e.g., '1 4 4', followed by the 4 rows of the matrix.
+                    if !matrix_read && parse_values::<i32>(line, 3)? == vec![1, 4, 4] {
+                        for row in 0..4 {
+                            let values = parse_values::<f32>(lines.next().unwrap_or(""), 4)?;
+                            for (col, value) in values.iter().enumerate() {
+                                lta.matrix[[row, col]] = *value;
+                            }
+                        }
+                        matrix_read = true;
+                    }
+                },
+            }
+        }
+
+        if !matrix_read {
+            return Err(NeuroformatsError::InvalidTransformFormat);
+        }
+        Ok(lta)
+    }
+
+
+    /// Apply the transformation matrix to the given coordinates.
+    ///
+    /// The `coords` are a flat vector of x,y,z triplets, like [`crate::BrainMesh::vertices`]. Note that the
+    /// space of the coordinates must match the [`FsLta::transform_type`].
+    pub fn apply_to_points(&self, coords: &[f32]) -> Vec<f32> {
+        apply_affine_to_points(&self.matrix, coords)
+    }
+}
+
+
+impl FsXfm {
+    /// Read an MNI transform file, which may be gzip-compressed.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FsXfm> {
+        FsXfm::from_reader(open_maybe_compressed(path)?)
+    }
+
+
+    /// Read an MNI transform from the given text input.
+    pub fn from_reader<S: BufRead>(reader: S) -> Result<FsXfm> {
+        let lines: Vec<String> = reader.lines().collect::<std::result::Result<_, _>>()?;
+
+        if lines.first().map(|l| l.trim()) != Some("MNI Transform File") {
+            return Err(NeuroformatsError::InvalidTransformFormat);
+        }
+        let start = lines.iter().position(|l| l.trim_start().starts_with("Linear_Transform")).ok_or(NeuroformatsError::InvalidTransformFormat)?;
+
+        let mut matrix : Array2<f32> = Array::eye(4);
+        let rows = lines.iter().skip(start + 1).map(|l| l.replace(';', "")).filter(|l| !l.trim().is_empty()).take(3);
+        let mut num_rows = 0;
+        for (row, line) in rows.enumerate() {
+            let values = parse_values::<f32>(&line, 4)?;
+            for (col, value) in values.iter().enumerate() {
+                matrix[[row, col]] = *value;
+            }
+            num_rows += 1;
+        }
+        if num_rows != 3 {
+            return Err(NeuroformatsError::InvalidTransformFormat);
+        }
+        Ok(FsXfm { matrix })
+    }
+
+
+    /// Apply the transformation matrix to the given coordinates.
+    ///
+    /// The `coords` are a flat vector of x,y,z triplets, like [`crate::BrainMesh::vertices`].
+    pub fn apply_to_points(&self, coords: &[f32]) -> Vec<f32> {
+        apply_affine_to_points(&self.matrix, coords)
+    }
+}
+
+
+impl fmt::Display for FsLta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Linear transform of type {} from '{}' to '{}'.", self.transform_type, self.src.filename, self.dst.filename)
+    }
+}
+
+
+impl fmt::Display for FsXfm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MNI linear transform with translation {}, {}, {}.", self.matrix[[0, 3]], self.matrix[[1, 3]], self.matrix[[2, 3]])
+    }
+}
+
+
+/// Read a FreeSurfer LTA transform file.
+///
+/// # Examples
+///
+/// ```no_run
+/// let lta = neuroformats::transforms::read_lta("/path/to/subjects_dir/subject1/mri/transforms/talairach.lta").unwrap();
+/// let mni_coords = lta.apply_to_points(&[10.0, 20.0, 30.0]);
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_lta<P: AsRef<Path>>(path: P) -> Result<FsLta> {
+    FsLta::from_file(path)
+}
+
+
+/// Read an MNI transform file, like FreeSurfer's `talairach.xfm`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let xfm = neuroformats::transforms::read_xfm("/path/to/subjects_dir/subject1/mri/transforms/talairach.xfm").unwrap();
+/// let mni305_coords = xfm.apply_to_points(&[10.0, 20.0, 30.0]);
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_xfm<P: AsRef<Path>>(path: P) -> Result<FsXfm> {
+    FsXfm::from_file(path)
+}
+
+
+/// Apply a 4x4 affine matrix to a flat vector of x,y,z coordinate triplets.
+pub fn apply_affine_to_points(matrix: &Array2<f32>, coords: &[f32]) -> Vec<f32> {
+    coords.chunks_exact(3).flat_map(|c| apply_affine(matrix, [c[0], c[1], c[2]])).collect()
+}
+
+
+/// Remove a trailing `#` comment and surrounding whitespace from a line.
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("").trim()
+}
+
+
+/// Parse exactly `count` whitespace-separated values from a string.
+fn parse_values<T: std::str::FromStr>(s: &str, count: usize) -> Result<Vec<T>> {
+    let values = s.split_whitespace().map(|v| v.parse::<T>()).collect::<std::result::Result<Vec<T>, _>>().map_err(|_| NeuroformatsError::InvalidTransformFormat)?;
+    if values.len() != count {
+        return Err(NeuroformatsError::InvalidTransformFormat);
+    }
+    Ok(values)
+}
+
+
+fn parse_volume_info_entry(info: &mut LtaVolumeInfo, key: &str, value: &str) -> Result<()> {
+    let as_vec3 = |value: &str| -> Result<[f32; 3]> {
+        let v = parse_values::<f32>(value, 3)?;
+        Ok([v[0], v[1], v[2]])
+    };
+    match key {
+        "valid" => info.valid = parse_values::<i32>(value, 1)?[0] == 1,
+        "filename" => info.filename = value.to_string(),
+        "volume" => {
+            let v = parse_values::<i32>(value, 3)?;
+            info.volume = [v[0], v[1], v[2]];
+        },
+        "voxelsize" => info.voxelsize = as_vec3(value)?,
+        "xras" => info.xras = as_vec3(value)?,
+        "yras" => info.yras = as_vec3(value)?,
+        "zras" => info.zras = as_vec3(value)?,
+        "cras" => info.cras = as_vec3(value)?,
+        _ => {}, // Ignore unknown entries.
+    }
+    Ok(())
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_demo_lta_file_can_be_read() {
+        let lta = read_lta("resources/subjects_dir/subject1/mri/transforms/talairach.lta").unwrap();
+        assert_eq!(LINEAR_RAS_TO_RAS, lta.transform_type);
+        assert_eq!(1.061234, lta.matrix[[0, 0]]);
+        assert_eq!(8.397247, lta.matrix[[2, 3]]);
+        assert_eq!(1.0, lta.matrix[[3, 3]]);
+        assert!(lta.src.valid);
+        assert_eq!([256, 256, 256], lta.src.volume);
+        assert_eq!("/usr/local/freesurfer/average/mni305.cor.mgz", lta.dst.filename);
+        assert_eq!([0.0, 1.0, 0.0], lta.dst.zras);
+        assert_eq!(Some(String::from("subject1")), lta.subject);
+        assert_eq!(Some(0.1), lta.fscale);
+
+        let moved = lta.apply_to_points(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(vec![-1.246452, -19.553802, 8.397247], moved[0..3].to_vec());
+        assert!((moved[3] - (1.061234 - 1.246452)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn the_demo_xfm_file_can_be_read() {
+        let xfm = read_xfm("resources/subjects_dir/subject1/mri/transforms/talairach.xfm").unwrap();
+        let lta = read_lta("resources/subjects_dir/subject1/mri/transforms/talairach.lta").unwrap();
+        assert_eq!(lta.matrix, xfm.matrix);
+    }
+
+    #[test]
+    fn reading_an_invalid_transform_file_fails() {
+        assert!(read_xfm("resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").is_err());
+        assert!(read_lta("resources/subjects_dir/subject1/label/lh.entorhinal_exvivo.label").is_err());
+    }
+}
@@ -1,13 +1,32 @@
 //! Utility functions used in all other neuroformats modules.
 
 use std::{path::Path};
-use std::io::{BufRead};
+use std::borrow::Cow;
+#[cfg(feature = "fs")]
+use std::convert::TryFrom;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::{BufRead, Read};
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::io::{BufWriter, Write};
+
+use flate2::bufread::GzDecoder;
+#[cfg(feature = "fs")]
+use byteordered::Endianness;
 
 use crate::error::{Result};
 
 use byteordered::byteorder::ReadBytesExt;
 
+/// The magic bytes at the start of gzip-compressed data.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+
 /// Check whether the file extension ends with ".gz".
+///
+/// The readers do not rely on the extension, see [`open_maybe_compressed`].
 pub fn is_gz_file<P>(path: P) -> bool where P: AsRef<Path>, {
     path.as_ref()
         .file_name()
@@ -16,6 +35,276 @@ pub fn is_gz_file<P>(path: P) -> bool where P: AsRef<Path>, {
 }
 
 
+/// Options that control how files are written by the `write_*_with_options` functions.
+///
+/// The default buffer of 1 MiB is larger than the 8 KiB default of [`std::io::BufWriter`], which reduces the
+/// number of write calls for full-resolution meshes and volumes. Run `cargo bench --bench write` to compare
+/// buffer sizes on your system.
+///
+/// # Examples
+///
+/// ```no_run
+/// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+/// let opts = neuroformats::WriteOptions::default().with_fsync(true);
+/// neuroformats::fs_surface::write_surf_with_options("/tmp/lh.white", &surf, &opts).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct WriteOptions {
+    /// The size of the write buffer in bytes.
+    pub buffer_size: usize,
+    /// Whether to call `fsync` on the file after writing, to ensure the data has reached the disk before the function returns.
+    pub fsync: bool,
+    /// The compression level for compressed files, from `0` (no compression) to `9` (best compression). Only used for gz-compressed formats like MGZ.
+    pub compression: u32,
+    /// Whether to write the data kept from the original file exactly as it was read, so that unmodified structs are written byte-identical
+    /// to the files they were read from. This writes the info line of surf files as raw bytes and includes the tags after the faces, see
+    /// [`crate::FsSurface::tags`], and writes the colortable size of annot files as read. Curv files are always written byte-identical.
+    pub strict: bool,
+    /// The byte order of MGH and MGZ files. FreeSurfer only reads big endian files, so only use [`ByteOrder::Little`] for tools which require it.
+    pub byte_order: ByteOrder,
+}
+
+
+/// The byte order of the numbers in a binary file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Big endian byte order, used by all FreeSurfer binary formats.
+    Big,
+    /// Little endian byte order.
+    Little,
+}
+
+
+impl ByteOrder {
+    /// Get the matching byte order of the `byteordered` crate used by the writers.
+    #[cfg(feature = "fs")]
+    pub(crate) fn endianness(self) -> Endianness {
+        match self {
+            ByteOrder::Big => Endianness::Big,
+            ByteOrder::Little => Endianness::Little,
+        }
+    }
+}
+
+
+impl Default for WriteOptions {
+    fn default() -> WriteOptions {
+        WriteOptions {
+            buffer_size: 1 << 20,
+            fsync: false,
+            compression: 6,
+            strict: false,
+            byte_order: ByteOrder::Big,
+        }
+    }
+}
+
+
+impl WriteOptions {
+    /// Set the size of the write buffer in bytes.
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> WriteOptions {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+
+    /// Set whether to call `fsync` on the file after writing.
+    pub fn with_fsync(mut self, fsync: bool) -> WriteOptions {
+        self.fsync = fsync;
+        self
+    }
+
+
+    /// Set the compression level for compressed files, from `0` (no compression) to `9` (best compression).
+    pub fn with_compression(mut self, compression: u32) -> WriteOptions {
+        self.compression = compression;
+        self
+    }
+
+
+    /// Set whether to write the data kept from the original file exactly as it was read, see [`WriteOptions::strict`].
+    pub fn with_strict(mut self, strict: bool) -> WriteOptions {
+        self.strict = strict;
+        self
+    }
+
+
+    /// Set the byte order of MGH and MGZ files.
+    pub fn with_byte_order(mut self, byte_order: ByteOrder) -> WriteOptions {
+        self.byte_order = byte_order;
+        self
+    }
+
+
+    /// Create a buffered writer for a new file at the given path, using the buffer size of these options.
+    #[cfg(feature = "fs")]
+    pub(crate) fn create<P: AsRef<Path>>(&self, path: P) -> std::io::Result<BufWriter<File>> {
+        Ok(BufWriter::with_capacity(self.buffer_size, File::create(path)?))
+    }
+
+
+    /// Flush the writer and, if requested in these options, sync the file to disk.
+    #[cfg(feature = "fs")]
+    pub(crate) fn finish(&self, writer: BufWriter<File>) -> std::io::Result<()> {
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        self.finish_file(file)
+    }
+
+
+    /// Sync the file to disk if requested in these options.
+    #[cfg(feature = "fs")]
+    pub(crate) fn finish_file(&self, mut file: File) -> std::io::Result<()> {
+        file.flush()?;
+        if self.fsync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Numbers which can be written in a given byte order by [`write_block`].
+#[cfg(feature = "fs")]
+pub(crate) trait BlockValue: Copy {
+    /// Append the bytes of this value in the given byte order.
+    fn push_bytes(self, endianness: Endianness, out: &mut Vec<u8>);
+}
+
+
+macro_rules! impl_block_value {
+    ($($t:ty),*) => {
+        $(
+            #[cfg(feature = "fs")]
+            impl BlockValue for $t {
+                fn push_bytes(self, endianness: Endianness, out: &mut Vec<u8>) {
+                    match endianness {
+                        Endianness::Big => out.extend_from_slice(&self.to_be_bytes()),
+                        Endianness::Little => out.extend_from_slice(&self.to_le_bytes()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_block_value!(u8, i16, i32, f32);
+
+
+/// The size in bytes of the blocks written by [`write_block`].
+#[cfg(feature = "fs")]
+const WRITE_BLOCK_SIZE: usize = 1 << 16;
+
+
+/// Write values in the given byte order, converting them in blocks instead of issuing one write call per value.
+///
+/// Returns the number of values written, so callers can check it against the count written to a file header.
+#[cfg(feature = "fs")]
+pub(crate) fn write_block<W, T, I>(out: &mut W, values: I, endianness: Endianness) -> std::io::Result<usize>
+where
+    W: Write + ?Sized,
+    T: BlockValue,
+    I: IntoIterator<Item = T>,
+{
+    let mut block = Vec::with_capacity(WRITE_BLOCK_SIZE + 8);
+    let mut count = 0;
+    for value in values {
+        value.push_bytes(endianness, &mut block);
+        count += 1;
+        if block.len() >= WRITE_BLOCK_SIZE {
+            out.write_all(&block)?;
+            block.clear();
+        }
+    }
+    out.write_all(&block)?;
+    Ok(count)
+}
+
+
+/// The maximal number of elements that the readers preallocate based on a count read from a file header.
+///
+/// Larger vectors grow while the data is actually read, so a corrupt header cannot trigger huge allocations.
+pub(crate) const MAX_PREALLOC: usize = 1 << 22;
+
+
+/// Get the capacity to preallocate for a number of elements read from a file header, see [`MAX_PREALLOC`].
+pub(crate) fn prealloc_capacity(num_elements: usize) -> usize {
+    num_elements.min(MAX_PREALLOC)
+}
+
+
+/// Read `count` values with the given function into a vector whose capacity matches `count`.
+///
+/// The vector is preallocated with [`prealloc_capacity`]. Beyond [`MAX_PREALLOC`] elements, it grows with the default amortized
+/// strategy while the data is read, and its excess capacity is released once at the end.
+pub(crate) fn read_values<T, F>(count: usize, mut read: F) -> std::io::Result<Vec<T>>
+where
+    F: FnMut() -> std::io::Result<T>,
+{
+    let mut values = Vec::with_capacity(prealloc_capacity(count));
+    for _ in 0..count {
+        values.push(read()?);
+    }
+    values.shrink_to_fit();
+    Ok(values)
+}
+
+
+/// Check whether the given data starts with the gzip magic bytes.
+pub(crate) fn is_gz_data(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_MAGIC)
+}
+
+
+/// Open a file for reading, decompressing it on the fly if it is gzip-compressed.
+///
+/// Compression is detected from the magic bytes at the start of the file, not from its name, so this works for `.mgz` and `.gz` files
+/// as well as for compressed files with any other name. All `from_file` functions of the readers use this.
+///
+/// # Examples
+///
+/// ```no_run
+/// let mut reader = neuroformats::util::open_maybe_compressed("/path/to/subjects_dir/subject1/surf/lh.white.gz").unwrap();
+/// let surf = neuroformats::FsSurface::from_reader(&mut reader).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn open_maybe_compressed<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>> {
+    let mut file = BufReader::new(File::open(path)?);
+    if is_gz_data(file.fill_buf()?) {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+
+/// Decompress the given bytes if they start with the gzip magic bytes, and return them unchanged otherwise.
+pub(crate) fn gunzip_if_compressed(bytes: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if is_gz_data(bytes) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+        Ok(Cow::Owned(decompressed))
+    } else {
+        Ok(Cow::Borrowed(bytes))
+    }
+}
+
+
+/// Skip the given number of bytes in the input without copying them, returning an error if the input ends before.
+pub(crate) fn skip_bytes<R: BufRead>(input: &mut R, mut count: usize) -> Result<()> {
+    while count > 0 {
+        let available = input.fill_buf()?.len();
+        if available == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let consumed = available.min(count);
+        input.consume(consumed);
+        count -= consumed;
+    }
+    Ok(())
+}
+
+
 /// Read a variable length Freesurfer-style byte string from the input.
 ///
 /// A FreeSurfer-style variable length string is a string terminated by two `\x0A`, or 'Unix line feed' ASCII characters.
@@ -42,6 +331,13 @@ pub fn read_fs_variable_length_string<S>(input: &mut S) -> Result<String>
     }
 
 
+/// Convert a string read from a file with one char per byte back to these bytes. Chars which do not fit into a byte are written as `?`.
+#[cfg(feature = "fs")]
+pub(crate) fn latin1_bytes(value: &str) -> Vec<u8> {
+    value.chars().map(|c| u8::try_from(c as u32).unwrap_or(b'?')).collect()
+}
+
+
 /// Read fixed length NUL-terminated string.
 /// 
 /// Read a fixed length zero-terminated byte string of the given length from the input. The `len` value must include the trailing NUL byte position, if any. Embedded '\0' chars are allowed, and the trailing one (if any) is read but not added to the returned String (all others are).
@@ -49,7 +345,7 @@ pub fn read_fixed_length_string<S>(input: &mut S, len: usize) -> Result<String>
 where
     S: BufRead,
 {
-    let mut info_line = String::with_capacity(len);
+    let mut info_line = String::with_capacity(prealloc_capacity(len));
     for char_idx  in 0..len   {
         let cur_char = input.read_u8()? as char;
         if char_idx == (len -1) {
@@ -101,12 +397,338 @@ where
 }
 
 
+/// Compute a percentile of an `f32` sequence by linear interpolation between the closest ranks. NaN values are ignored.
+///
+/// # Panics
+///
+/// If `p` is not in the range 0 to 100.
+///
+/// # Return value
+///
+/// The percentile, or `None` if `data` contains no values besides NaN values. The 50th percentile is the median.
+pub fn vec32percentile(data: &[f32], p: f32) -> Option<f32> {
+    assert!((0.0..=100.0).contains(&p), "Percentile must be in range 0 to 100.");
+    percentile_of_sorted(&sorted_without_nan(data), p)
+}
+
+
+/// Determine a robust value range of an `f32` sequence, given by two percentiles. NaN values are ignored.
+///
+/// Unlike the full range computed by [`vec32minmax`], this range is not sensitive to outliers, which makes it suitable
+/// to scale colormaps, like FreeView does by default.
+///
+/// # Panics
+///
+/// If `p_low` or `p_high` is not in the range 0 to 100, or if `p_low` is larger than `p_high`.
+///
+/// # Return value
+///
+/// A tuple of length 2 with the `p_low` and `p_high` percentiles, or `None` if `data` contains no values besides NaN values.
+///
+/// # Examples
+///
+/// ```no_run
+/// let curv = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+/// let (min, max) = neuroformats::util::vec32robust_range(&curv.data, 2.0, 98.0).unwrap();
+/// let colors = neuroformats::colormap::values_to_colors(&curv.data, min, max);
+/// ```
+pub fn vec32robust_range(data: &[f32], p_low: f32, p_high: f32) -> Option<(f32, f32)> {
+    assert!((0.0..=100.0).contains(&p_low) && (0.0..=100.0).contains(&p_high), "Percentiles must be in range 0 to 100.");
+    assert!(p_low <= p_high, "Lower percentile must not be larger than upper percentile.");
+    let sorted = sorted_without_nan(data);
+    Some((percentile_of_sorted(&sorted, p_low)?, percentile_of_sorted(&sorted, p_high)?))
+}
+
+
+/// Copy the values which are not NaN and sort them in ascending order.
+pub(crate) fn sorted_without_nan(data: &[f32]) -> Vec<f32> {
+    let mut sorted: Vec<f32> = data.iter().copied().filter(|v| !v.is_nan()).collect();
+    sorted.sort_unstable_by(f32::total_cmp);
+    sorted
+}
+
+
+/// Compute a percentile (0 to 100) of values sorted in ascending order, see [`vec32percentile`].
+pub(crate) fn percentile_of_sorted(sorted: &[f32], p: f32) -> Option<f32> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = p as f64 / 100.0 * last as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let frac = rank - lower as f64;
+    Some((sorted[lower] as f64 + (sorted[upper] as f64 - sorted[lower] as f64) * frac) as f32)
+}
+
+
+/// Subtract the 3D vector `b` from `a`.
+pub(crate) fn vec3_sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+
+/// Compute the dot product of two 3D vectors.
+pub(crate) fn vec3_dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+
+/// Compute the cross product of two 3D vectors.
+pub(crate) fn vec3_cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+
+/// Compute the Euclidean norm of a 3D vector.
+pub(crate) fn vec3_norm(a: [f32; 3]) -> f32 {
+    vec3_dot(a, a).sqrt()
+}
+
+
+/// Format a number for JSON output. NaN and infinite values are not valid JSON numbers and are written as `null`.
+pub(crate) fn json_number(value: f64) -> String {
+    if value.is_finite() { value.to_string() } else { String::from("null") }
+}
+
+
+/// Format already formatted JSON values as a JSON array.
+pub(crate) fn json_array<I: Iterator<Item = String>>(values: I) -> String {
+    format!("[{}]", values.collect::<Vec<_>>().join(","))
+}
+
+
+/// Format a string as a JSON string, escaping quotes, backslashes and control characters.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+
+/// Mix the bits of a value with the SplitMix64 function, so that similar values give very different results.
+pub(crate) fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+
+/// A SplitMix64 random number generator, see [`splitmix64`]. It is not suitable for cryptography, but fast and reproducible.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let value = splitmix64(self.state);
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        value
+    }
+
+
+    /// Draw an integer in the range `0..bound`, which must not be empty.
+    pub(crate) fn next_below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+}
+
+
+/// Compute the eigenvalues and eigenvectors of a small dense symmetric matrix with the cyclic Jacobi method.
+///
+/// The matrix is given as a vector of rows. Returns the eigenvalues in ascending order, and the eigenvector of each eigenvalue.
+pub(crate) fn symmetric_eigen(mut a: Vec<Vec<f64>>) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = a.len();
+    let mut v: Vec<Vec<f64>> = (0..n).map(|row| (0..n).map(|col| if row == col { 1.0 } else { 0.0 }).collect()).collect();
+    let norm: f64 = a.iter().flatten().map(|x| x * x).sum::<f64>().sqrt();
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n).flat_map(|p| (0..n).filter(move |q| *q != p).map(move |q| (p, q))).map(|(p, q)| a[p][q] * a[p][q]).sum::<f64>().sqrt();
+        if off_diagonal <= 1e-15 * norm {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (rows_before_q, rows_from_q) = a.split_at_mut(q);
+                for (apk, aqk) in rows_before_q[p].iter_mut().zip(rows_from_q[0].iter_mut()) {
+                    let (vp, vq) = (*apk, *aqk);
+                    *apk = c * vp - s * vq;
+                    *aqk = s * vp + c * vq;
+                }
+                for row in v.iter_mut() {
+                    let (vp, vq) = (row[p], row[q]);
+                    row[p] = c * vp - s * vq;
+                    row[q] = s * vp + c * vq;
+                }
+            }
+        }
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|x, y| a[*x][*x].total_cmp(&a[*y][*y]));
+    let values = order.iter().map(|idx| a[*idx][*idx]).collect();
+    let vectors = order.iter().map(|idx| v.iter().map(|row| row[*idx]).collect()).collect();
+    (values, vectors)
+}
+
+
+
+/// Generate corrupted variants of a valid file for testing that the readers do not panic on arbitrary input.
+///
+/// The variants include all truncations of the header region, random byte flips and overwritten header counts. A simple
+/// deterministic pseudo random number generator is used, so the tests are reproducible.
+#[cfg(test)]
+pub(crate) fn corrupted_variants(valid: &[u8]) -> Vec<Vec<u8>> {
+    let mut variants: Vec<Vec<u8>> = (0..valid.len().min(400)).map(|len| valid[..len].to_vec()).collect();
+    let mut state: u64 = 0x2545F4914F6CDD1D;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..300 {
+        let mut variant = valid.to_vec();
+        for _ in 0..(next() % 8 + 1) {
+            let pos = (next() % valid.len().min(400) as u64) as usize;
+            variant[pos] = next() as u8;
+        }
+        variants.push(variant);
+    }
+    for pos in 0..valid.len().min(400).saturating_sub(4) {
+        for value in [i32::MIN, -1, i32::MAX] {
+            let mut variant = valid.to_vec();
+            variant[pos..pos + 4].copy_from_slice(&value.to_be_bytes());
+            variants.push(variant);
+        }
+    }
+    variants
+}
+
 
 #[cfg(test)]
 mod test {
     use super::*;
     use approx::assert_abs_diff_eq;
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn files_can_be_written_with_custom_write_options() {
+        let mgh = crate::read_mgh("resources/mgh/tiny.mgh").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let options = WriteOptions::default().with_buffer_size(16).with_fsync(true).with_compression(9).with_byte_order(ByteOrder::Big);
+
+        let tfile_path = dir.path().join("tiny.mgz");
+        crate::fs_mgh::write_mgh_with_options(&tfile_path, &mgh, &options).unwrap();
+        let mgh_re = crate::read_mgh(&tfile_path).unwrap();
+        assert_eq!(mgh.data, mgh_re.data);
+    }
+
+    #[test]
+    fn gz_files_are_detected_for_windows_style_and_non_utf8_paths() {
+        assert!(is_gz_file("C:\\Users\\me\\subjects\\bert\\surf\\lh.thickness.gz"));
+        assert!(is_gz_file(std::path::PathBuf::from("subjects").join("lh.thickness.gz")));
+        assert!(!is_gz_file("C:\\Users\\me\\subjects\\bert\\surf\\lh.thickness"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            assert!(is_gz_file(std::ffi::OsStr::from_bytes(b"/tmp/lh.thick\xffness.gz")));
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    /// Gzip-compress a file into the given path, which does not need to have a `.gz` extension.
+    fn gzip_file(src: &str, dest: &Path) {
+        let mut encoder = flate2::write::GzEncoder::new(File::create(dest).unwrap(), flate2::Compression::default());
+        encoder.write_all(&std::fs::read(src).unwrap()).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn compressed_files_are_detected_by_their_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let subject_dir = "resources/subjects_dir/subject1";
+
+        let path = dir.path().join("lh.white");
+        gzip_file(&format!("{}/surf/lh.tinysurface", subject_dir), &path);
+        assert_eq!(crate::read_surf(format!("{}/surf/lh.tinysurface", subject_dir)).unwrap(), crate::read_surf(&path).unwrap());
+
+        let path = dir.path().join("lh.aparc.annot");
+        gzip_file(&format!("{}/label/lh.aparc.annot", subject_dir), &path);
+        assert_eq!(36, crate::read_annot(&path).unwrap().num_regions());
+
+        let path = dir.path().join("lh.entorhinal_exvivo.label.gz");
+        gzip_file(&format!("{}/label/lh.entorhinal_exvivo.label", subject_dir), &path);
+        assert_eq!(crate::read_label(format!("{}/label/lh.entorhinal_exvivo.label", subject_dir)).unwrap(), crate::read_label(&path).unwrap());
+
+        // A curv file with a .gz extension which is not compressed, and an MGZ file which is not compressed.
+        let path = dir.path().join("lh.thickness.gz");
+        std::fs::copy("resources/curv/tiny.curv", &path).unwrap();
+        assert_eq!(5, crate::read_curv(&path).unwrap().data.len());
+        assert_eq!(vec![2.0, 0.5], crate::fs_curv::read_curv_subset(&path, &[1, 4]).unwrap());
+        let path = dir.path().join("tiny.mgz");
+        std::fs::copy("resources/mgh/tiny.mgh", &path).unwrap();
+        assert_eq!(crate::read_mgh("resources/mgh/tiny.mgh").unwrap().data, crate::read_mgh(&path).unwrap().data);
+
+        let mut reader = open_maybe_compressed("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let mut start = [0u8; 4];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!([0, 0, 0, 1], start);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    #[cfg(unix)]
+    fn files_with_non_utf8_names_can_be_written_and_read() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempfile::tempdir().unwrap();
+        let surf = crate::read_surf("resources/subjects_dir/subject1/surf/lh.tinysurface").unwrap();
+        let path = dir.path().join(std::ffi::OsStr::from_bytes(b"lh.\xff\xfewhite"));
+        assert!(path.to_str().is_none());
+        crate::write_surf(&path, &surf).unwrap();
+        assert_eq!(surf.mesh, crate::read_surf(&path).unwrap().mesh);
+
+        let mgh = crate::read_mgh("resources/mgh/tiny.mgh").unwrap();
+        let path = dir.path().join(std::ffi::OsStr::from_bytes(b"\xffbrain.mgz"));
+        crate::write_mgh(&path, &mgh).unwrap();
+        assert_eq!(mgh.data, crate::read_mgh(path).unwrap().data);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn files_can_be_written_and_read_with_windows_verbatim_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let curv = crate::read_curv("resources\\curv\\tiny.curv").unwrap();
+        let path = std::path::PathBuf::from(format!("\\\\?\\{}", dir.path().display())).join("lh.tiny.curv");
+        crate::write_curv(&path, &curv);
+        assert_eq!(curv, crate::read_curv(&path).unwrap());
+    }
+
     #[test]
     fn the_min_and_max_of_an_f32_vector_without_nan_values_can_be_computed() {
 
@@ -165,7 +787,7 @@ mod test {
         c.read_to_end(&mut out).unwrap();
 
         assert_eq!(s, "test\n\ntest");
-        assert_eq!(out, &[]);
+        assert_eq!(out, &[0u8; 0]);
         assert_eq!(11, c.position());
     }
 
@@ -190,4 +812,16 @@ mod test {
         c.read_to_end(&mut out).unwrap();
         assert_eq!(23, c.position());
     }
+
+    #[test]
+    fn percentiles_and_robust_ranges_ignore_nan_values() {
+        let data = [4.0, f32::NAN, 1.0, 3.0, 2.0, 100.0];
+        assert_eq!(Some(3.0), vec32percentile(&data, 50.0));
+        assert_eq!(Some(1.0), vec32percentile(&data, 0.0));
+        assert_eq!(Some(100.0), vec32percentile(&data, 100.0));
+        assert_abs_diff_eq!(1.5, vec32percentile(&data, 12.5).unwrap());
+        assert_eq!(Some((2.0, 4.0)), vec32robust_range(&data, 25.0, 75.0));
+        assert_eq!(None, vec32percentile(&[f32::NAN], 50.0));
+        assert_eq!(None, vec32robust_range(&[], 2.0, 98.0));
+    }
 }
@@ -0,0 +1,332 @@
+//! Descriptive and inferential vertex-wise statistics for group data matrices.
+//!
+//! The functions in this module work on matrices with one row per subject and one column per vertex, as built by
+//! [`crate::group::build_group_matrix`]. The resulting maps have one value per vertex and can be written with [`crate::write_curv`]
+//! for visualization. This covers simple designs which would otherwise require FreeSurfer's `mri_glmfit`, but there is no
+//! correction for multiple comparisons.
+
+
+use ndarray::{Array2, ArrayView1, Axis};
+
+use crate::error::{NeuroformatsError, Result};
+
+
+/// The result of a vertex-wise test, with one value per vertex.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StatMaps {
+    /// The t statistic of each vertex. NaN for vertices with NaN values or without variance.
+    pub t: Vec<f32>,
+    /// The two-sided p value of each vertex. NaN where the t statistic is NaN.
+    pub p: Vec<f32>,
+    /// The degrees of freedom of the t statistics.
+    pub dof: f64,
+}
+
+
+impl StatMaps {
+    /// Compute the signed significance map used by FreeSurfer in `sig.mgh` files, i.e., `-log10(p)` with the sign of the t statistic.
+    pub fn sig(&self) -> Vec<f32> {
+        self.t.iter().zip(self.p.iter()).map(|(t, p)| -p.log10() * t.signum()).collect()
+    }
+}
+
+
+/// Compute the mean of each column, i.e., vertex, of a group data matrix.
+pub fn vertexwise_mean(data: &Array2<f32>) -> Vec<f32> {
+    data.axis_iter(Axis(1)).map(|column| mean(column) as f32).collect()
+}
+
+
+/// Compute the sample standard deviation of each column, i.e., vertex, of a group data matrix. The values are NaN if there are less than 2 rows.
+pub fn vertexwise_std(data: &Array2<f32>) -> Vec<f32> {
+    data.axis_iter(Axis(1)).map(|column| {
+        let m = mean(column);
+        (column.iter().map(|v| (*v as f64 - m).powi(2)).sum::<f64>() / (column.len() as f64 - 1.0)).sqrt() as f32
+    }).collect()
+}
+
+
+fn mean(values: ArrayView1<f32>) -> f64 {
+    values.iter().map(|v| *v as f64).sum::<f64>() / values.len() as f64
+}
+
+
+/// Compare two groups of subjects at each vertex with a two-sample Student's t-test, assuming equal variances.
+///
+/// Positive t values mean that the mean of `group_a` is higher than the mean of `group_b`.
+///
+/// # Parameters
+///
+/// * `group_a`: the data of the first group, with one row per subject and one column per vertex.
+/// * `group_b`: the data of the second group, with the same number of columns.
+///
+/// # Return value
+///
+/// The t and p maps with `n_a + n_b - 2` degrees of freedom. Returns [`NeuroformatsError::VertexCountMismatch`] if the number of vertices
+/// differs, and [`NeuroformatsError::InvalidDesign`] if there are less than 3 subjects or a group is empty.
+///
+/// # Examples
+///
+/// ```no_run
+/// use neuroformats::group::{build_group_matrix, GroupTarget};
+/// use neuroformats::hemi::Hemisphere;
+/// let patients = build_group_matrix("/path/to/subjects_dir", &["p1", "p2", "p3"], Hemisphere::Left, "thickness.fwhm10.fsaverage", &GroupTarget::Native).unwrap();
+/// let controls = build_group_matrix("/path/to/subjects_dir", &["c1", "c2", "c3"], Hemisphere::Left, "thickness.fwhm10.fsaverage", &GroupTarget::Native).unwrap();
+/// let maps = neuroformats::vertexwise::vertexwise_ttest(&patients.data, &controls.data).unwrap();
+/// ```
+pub fn vertexwise_ttest(group_a: &Array2<f32>, group_b: &Array2<f32>) -> Result<StatMaps> {
+    if group_a.ncols() != group_b.ncols() {
+        return Err(NeuroformatsError::VertexCountMismatch(group_a.ncols(), group_b.ncols()));
+    }
+    let (n_a, n_b) = (group_a.nrows() as f64, group_b.nrows() as f64);
+    if n_a < 1.0 || n_b < 1.0 || n_a + n_b < 3.0 {
+        return Err(NeuroformatsError::InvalidDesign(String::from("a t-test requires at least one subject per group and three subjects in total")));
+    }
+    let dof = n_a + n_b - 2.0;
+
+    let t: Vec<f32> = group_a.axis_iter(Axis(1)).zip(group_b.axis_iter(Axis(1))).map(|(a, b)| {
+        let (mean_a, mean_b) = (mean(a), mean(b));
+        let sum_sq = a.iter().map(|v| (*v as f64 - mean_a).powi(2)).sum::<f64>() + b.iter().map(|v| (*v as f64 - mean_b).powi(2)).sum::<f64>();
+        let se = (sum_sq / dof * (1.0 / n_a + 1.0 / n_b)).sqrt();
+        t_or_nan(mean_a - mean_b, se)
+    }).collect();
+    let p = t.iter().map(|t| t_test_p_value(*t as f64, dof) as f32).collect();
+    Ok(StatMaps { t, p, dof })
+}
+
+
+/// Fit a general linear model at each vertex and test a contrast of its parameters.
+///
+/// The model is `y = X b + e` with normally distributed errors, fitted by ordinary least squares. The t statistic of the contrast `c`
+/// is `c'b / se(c'b)`.
+///
+/// # Parameters
+///
+/// * `data`: the data, with one row per subject and one column per vertex.
+/// * `design`: the design matrix `X`, with one row per subject and one column per regressor. Include a column of ones for the intercept.
+/// * `contrast`: the contrast vector `c`, with one weight per column of the design matrix.
+///
+/// # Return value
+///
+/// The t and p maps with `n - k` degrees of freedom, where `k` is the number of regressors. Returns [`NeuroformatsError::InvalidDesign`] if the
+/// dimensions do not match, the design matrix is singular, or there are not more subjects than regressors.
+///
+/// # Examples
+///
+/// Test for an effect of age on thickness, controlling for sex:
+///
+/// ```no_run
+/// use ndarray::array;
+/// use neuroformats::group::{build_group_matrix, GroupTarget};
+/// use neuroformats::hemi::Hemisphere;
+/// let group = build_group_matrix("/path/to/subjects_dir", &["s1", "s2", "s3", "s4"], Hemisphere::Left, "thickness.fwhm10.fsaverage", &GroupTarget::Native).unwrap();
+/// // The columns are intercept, age and sex.
+/// let design = array![[1.0, 25.0, 0.0], [1.0, 31.0, 1.0], [1.0, 47.0, 0.0], [1.0, 62.0, 1.0]];
+/// let maps = neuroformats::vertexwise::vertexwise_glm(&group.data, &design, &[0.0, 1.0, 0.0]).unwrap();
+/// neuroformats::write_curv("lh.age_t", &neuroformats::FsCurv::from_data(maps.t));
+/// ```
+pub fn vertexwise_glm(data: &Array2<f32>, design: &Array2<f64>, contrast: &[f64]) -> Result<StatMaps> {
+    let (n, k) = design.dim();
+    if data.nrows() != n || contrast.len() != k {
+        return Err(NeuroformatsError::InvalidDesign(format!("{} subjects in the data, {} rows and {} columns in the design matrix and {} contrast weights", data.nrows(), n, k, contrast.len())));
+    }
+    if n <= k {
+        return Err(NeuroformatsError::InvalidDesign(format!("{} subjects are not enough for {} regressors", n, k)));
+    }
+    let dof = (n - k) as f64;
+
+    let xtx_inv = invert(&design.t().dot(design))
+        .ok_or_else(|| NeuroformatsError::InvalidDesign(String::from("the design matrix is singular")))?;
+    // The variance of c'b is sigma^2 c'(X'X)^-1 c, and only sigma^2 depends on the vertex.
+    let c = ndarray::Array1::from(contrast.to_vec());
+    let var_factor = c.dot(&xtx_inv).dot(&c);
+    let pinv = xtx_inv.dot(&design.t());
+
+    let t: Vec<f32> = data.axis_iter(Axis(1)).map(|column| {
+        let y = column.mapv(|v| v as f64);
+        let beta = pinv.dot(&y);
+        let rss = (&y - &design.dot(&beta)).mapv(|r| r * r).sum();
+        t_or_nan(c.dot(&beta), (rss / dof * var_factor).sqrt())
+    }).collect();
+    let p = t.iter().map(|t| t_test_p_value(*t as f64, dof) as f32).collect();
+    Ok(StatMaps { t, p, dof })
+}
+
+
+/// Compute a t statistic, or NaN if the standard error is zero or NaN.
+fn t_or_nan(effect: f64, se: f64) -> f32 {
+    if se > 0.0 { (effect / se) as f32 } else { f32::NAN }
+}
+
+
+/// Invert a small square matrix by Gauss-Jordan elimination with partial pivoting. Returns `None` if it is singular.
+fn invert(m: &Array2<f64>) -> Option<Array2<f64>> {
+    let k = m.nrows();
+    let mut a = m.clone();
+    let mut inv = Array2::eye(k);
+    let scale = m.iter().fold(0.0_f64, |acc, v| acc.max(v.abs()));
+    for col in 0..k {
+        let pivot = (col..k).max_by(|r1, r2| a[[*r1, col]].abs().total_cmp(&a[[*r2, col]].abs()))?;
+        if a[[pivot, col]].abs() <= scale * 1e-12 {
+            return None;
+        }
+        for j in 0..k {
+            a.swap([col, j], [pivot, j]);
+            inv.swap([col, j], [pivot, j]);
+        }
+        let d = a[[col, col]];
+        for j in 0..k {
+            a[[col, j]] /= d;
+            inv[[col, j]] /= d;
+        }
+        for row in (0..k).filter(|row| *row != col) {
+            let factor = a[[row, col]];
+            for j in 0..k {
+                a[[row, j]] -= factor * a[[col, j]];
+                inv[[row, j]] -= factor * inv[[col, j]];
+            }
+        }
+    }
+    Some(inv)
+}
+
+
+/// Compute the two-sided p value of a t statistic with the given degrees of freedom.
+pub fn t_test_p_value(t: f64, dof: f64) -> f64 {
+    if t.is_nan() {
+        return f64::NAN;
+    }
+    regularized_incomplete_beta(dof / (dof + t * t), dof / 2.0, 0.5)
+}
+
+
+/// Compute the regularized incomplete beta function `I_x(a, b)`, using its continued fraction representation.
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly only for x < (a + 1) / (a + b + 2), so use the symmetry relation otherwise.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+
+/// Evaluate the continued fraction of the incomplete beta function with the modified Lentz method.
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    d = 1.0 / if d.abs() < TINY { TINY } else { d };
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)), -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0))] {
+            d = 1.0 + numerator * d;
+            d = 1.0 / if d.abs() < TINY { TINY } else { d };
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+
+/// Compute the natural logarithm of the gamma function for positive arguments, using the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [76.18009172947146, -86.50532032941677, 24.01409824083091, -1.231739572450155, 0.1208650973866179e-2, -0.5395239384953e-5];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series: f64 = 1.000000000190015 + COEFFICIENTS.iter().enumerate().map(|(j, coef)| coef / (x + 1.0 + j as f64)).sum::<f64>();
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::array;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn p_values_of_t_statistics_can_be_computed() {
+        // Reference values computed by numerical integration of the density of the t distribution.
+        assert_abs_diff_eq!(1.0, t_test_p_value(0.0, 10.0), epsilon = 1e-10);
+        assert_abs_diff_eq!(0.073388034, t_test_p_value(2.0, 10.0), epsilon = 1e-7);
+        assert_abs_diff_eq!(0.073388034, t_test_p_value(-2.0, 10.0), epsilon = 1e-7);
+        assert_abs_diff_eq!(0.5, t_test_p_value(1.0, 1.0), epsilon = 1e-7);
+        assert_abs_diff_eq!(0.0034079153, t_test_p_value(3.0, 100.0), epsilon = 1e-8);
+        assert!(t_test_p_value(f64::NAN, 10.0).is_nan());
+    }
+
+    #[test]
+    fn descriptive_vertexwise_statistics_can_be_computed() {
+        let data = array![[1.0, 2.0], [3.0, 2.0], [5.0, 2.0]];
+        assert_eq!(vec![3.0, 2.0], vertexwise_mean(&data));
+        assert_eq!(vec![2.0, 0.0], vertexwise_std(&data));
+    }
+
+    #[test]
+    fn two_groups_can_be_compared_with_a_ttest() {
+        let group_a = array![[1.0, 5.0, 2.0], [2.0, 6.0, 2.0], [3.0, 7.0, 2.0]];
+        let group_b = array![[2.0, 1.0, 2.0], [3.0, 2.0, 2.0], [4.0, 3.0, 2.0], [5.0, 4.0, 2.0]];
+        let maps = vertexwise_ttest(&group_a, &group_b).unwrap();
+        assert_eq!(5.0, maps.dof);
+        // The pooled variance is 7 / 5 for both vertices, so the standard error is sqrt(1.4 * (1 / 3 + 1 / 4)).
+        assert_abs_diff_eq!(-1.65985, maps.t[0], epsilon = 1e-5);
+        assert_abs_diff_eq!(0.1578388, maps.p[0], epsilon = 1e-6);
+        assert_abs_diff_eq!(3.8729833, maps.t[1], epsilon = 1e-5);
+        assert_abs_diff_eq!(0.0117248, maps.p[1], epsilon = 1e-6);
+        assert!(maps.t[2].is_nan() && maps.p[2].is_nan());
+        let sig = maps.sig();
+        assert_abs_diff_eq!(-0.8017, sig[0], epsilon = 1e-3);
+        assert_abs_diff_eq!(-maps.p[1].log10(), sig[1], epsilon = 1e-6);
+        assert!(sig[2].is_nan());
+
+        assert!(matches!(vertexwise_ttest(&group_a, &array![[1.0, 2.0]]), Err(NeuroformatsError::VertexCountMismatch(3, 2))));
+        assert!(matches!(vertexwise_ttest(&array![[1.0]], &array![[2.0]]), Err(NeuroformatsError::InvalidDesign(_))));
+    }
+
+    #[test]
+    fn a_glm_with_a_group_regressor_is_equivalent_to_a_ttest() {
+        let group_a = array![[1.0, 5.0], [2.0, 6.0], [3.0, 7.0]];
+        let group_b = array![[2.0, 1.0], [3.0, 2.0], [4.0, 3.0], [5.0, 4.0]];
+        let data = ndarray::concatenate(ndarray::Axis(0), &[group_a.view(), group_b.view()]).unwrap();
+        let design = array![[1.0, 1.0], [1.0, 1.0], [1.0, 1.0], [1.0, 0.0], [1.0, 0.0], [1.0, 0.0], [1.0, 0.0]];
+
+        let glm = vertexwise_glm(&data, &design, &[0.0, 1.0]).unwrap();
+        let ttest = vertexwise_ttest(&group_a, &group_b).unwrap();
+        assert_eq!(ttest.dof, glm.dof);
+        for vidx in 0..2 {
+            assert_abs_diff_eq!(ttest.t[vidx], glm.t[vidx], epsilon = 1e-4);
+            assert_abs_diff_eq!(ttest.p[vidx], glm.p[vidx], epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn a_glm_with_a_covariate_can_be_fitted() {
+        // The data is 2 * age plus noise, and does not depend on the second covariate.
+        let design = array![[1.0, 20.0, 0.0], [1.0, 30.0, 1.0], [1.0, 40.0, 0.0], [1.0, 50.0, 1.0], [1.0, 60.0, 0.0]];
+        let data = array![[40.5], [59.0], [80.2], [100.1], [119.7]];
+        let maps = vertexwise_glm(&data, &design, &[0.0, 1.0, 0.0]).unwrap();
+        assert_eq!(2.0, maps.dof);
+        assert!(maps.t[0] > 50.0);
+        assert!(maps.p[0] < 0.001);
+
+        let singular = array![[1.0, 2.0], [1.0, 2.0], [1.0, 2.0]];
+        assert!(matches!(vertexwise_glm(&array![[1.0], [2.0], [3.0]], &singular, &[0.0, 1.0]), Err(NeuroformatsError::InvalidDesign(_))));
+        assert!(vertexwise_glm(&data, &design, &[0.0, 1.0]).is_err());
+    }
+}
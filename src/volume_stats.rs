@@ -0,0 +1,165 @@
+//! Intensity statistics and histograms of MGH volumes, similar to the basic functionality of `fslstats`.
+//!
+//! Voxels with a given background value, typically `0` for skull-stripped or masked volumes, can be ignored. NaN values are always ignored.
+
+
+use crate::error::Result;
+use crate::fs_mgh::FsMgh;
+
+
+/// Intensity statistics of the voxels of a volume frame.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VolumeStats {
+    /// The number of voxels used for the statistics, i.e., all voxels which are not NaN and do not have the background value.
+    pub num_voxels: usize,
+    /// The minimal value, or `None` if no voxels were used.
+    pub min: Option<f32>,
+    /// The maximal value, or `None` if no voxels were used.
+    pub max: Option<f32>,
+    /// The mean value, or `None` if no voxels were used.
+    pub mean: Option<f64>,
+    /// The sample standard deviation, or `None` if less than 2 voxels were used.
+    pub std: Option<f64>,
+}
+
+
+/// A histogram of voxel values with bins of equal width.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Histogram {
+    /// The edges of the bins, one more than the number of bins. Bin `i` contains values in [`bin_edges[i]`, `bin_edges[i + 1]`), the last bin also contains its upper edge.
+    pub bin_edges: Vec<f32>,
+    /// The number of voxels in each bin.
+    pub counts: Vec<usize>,
+}
+
+
+/// Get the values of a frame which are not NaN and not equal to the background value.
+fn foreground_values(mgh: &FsMgh, frame: usize, background: Option<f32>) -> Result<Vec<f32>> {
+    let mut values = mgh.frame_as_f32(frame)?;
+    values.retain(|v| !v.is_nan() && Some(*v) != background);
+    Ok(values)
+}
+
+
+impl FsMgh {
+    /// Compute intensity statistics of one frame of the volume.
+    ///
+    /// # Parameters
+    ///
+    /// * `frame`: the frame, use `0` for 3D volumes.
+    /// * `background`: an optional background value. Voxels with this value are ignored, like with the `-n` option of `fslstats`.
+    ///
+    /// # Panics
+    ///
+    /// If `frame` is out of range for this volume.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mgh = neuroformats::read_mgh("/path/to/subjects_dir/subject1/mri/brain.mgz").unwrap();
+    /// let stats = mgh.stats(0, Some(0.0)).unwrap();
+    /// println!("Mean brain intensity: {:?}", stats.mean);
+    /// ```
+    pub fn stats(&self, frame: usize, background: Option<f32>) -> Result<VolumeStats> {
+        let values = foreground_values(self, frame, background)?;
+        let num_voxels = values.len();
+        if num_voxels == 0 {
+            return Ok(VolumeStats { num_voxels, min: None, max: None, mean: None, std: None });
+        }
+        let (min, max) = values.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| (min.min(*v), max.max(*v)));
+        let mean = values.iter().map(|v| *v as f64).sum::<f64>() / num_voxels as f64;
+        let std = if num_voxels > 1 {
+            let sum_sq: f64 = values.iter().map(|v| (*v as f64 - mean).powi(2)).sum();
+            Some((sum_sq / (num_voxels - 1) as f64).sqrt())
+        } else {
+            None
+        };
+        Ok(VolumeStats { num_voxels, min: Some(min), max: Some(max), mean: Some(mean), std })
+    }
+
+
+    /// Compute a histogram of the values of one frame of the volume, with bins of equal width between the minimal and maximal value.
+    ///
+    /// # Parameters
+    ///
+    /// * `frame`: the frame, use `0` for 3D volumes.
+    /// * `bins`: the number of bins.
+    /// * `background`: an optional background value. Voxels with this value are ignored.
+    ///
+    /// # Return value
+    ///
+    /// The histogram. If no voxels are used, all bin edges are `0.0`. If all voxels have the same value, all of them are in the first bin.
+    ///
+    /// # Panics
+    ///
+    /// If `bins` is `0` or `frame` is out of range for this volume.
+    pub fn histogram(&self, frame: usize, bins: usize, background: Option<f32>) -> Result<Histogram> {
+        assert!(bins > 0, "The number of bins must be positive.");
+        let values = foreground_values(self, frame, background)?;
+        let (min, max) = if values.is_empty() {
+            (0.0, 0.0)
+        } else {
+            values.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| (min.min(*v), max.max(*v)))
+        };
+        let width = (max - min) / bins as f32;
+        let bin_edges = (0..=bins).map(|idx| if idx == bins { max } else { min + idx as f32 * width }).collect();
+        let mut counts = vec![0; bins];
+        for v in values {
+            let bin = if width > 0.0 { ((v - min) / width) as usize } else { 0 };
+            counts[bin.min(bins - 1)] += 1;
+        }
+        Ok(Histogram { bin_edges, counts })
+    }
+}
+
+
+#[cfg(all(test, feature = "fs"))]
+mod test {
+    use super::*;
+    use crate::read_mgh;
+
+    #[test]
+    fn the_stats_of_the_brain_volume_can_be_computed() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let all = mgh.stats(0, None).unwrap();
+        assert_eq!(256 * 256 * 256, all.num_voxels);
+        assert_eq!(Some(0.0), all.min);
+        assert_eq!(Some(156.0), all.max);
+        assert!((all.mean.unwrap() - 121035479.0 / (256.0 * 256.0 * 256.0)).abs() < 1e-6);
+
+        let brain = mgh.stats(0, Some(0.0)).unwrap();
+        assert!(brain.num_voxels < all.num_voxels);
+        assert!(brain.min.unwrap() > 0.0);
+        assert!(brain.mean.unwrap() > all.mean.unwrap());
+        assert!(brain.std.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn the_stats_of_a_tiny_volume_can_be_computed() {
+        let mgh = FsMgh::from_f32_values(&read_mgh("resources/mgh/tiny.mgh").unwrap().header, (0..27).map(|v| (v % 3) as f32).collect()).unwrap();
+        let stats = mgh.stats(0, Some(0.0)).unwrap();
+        assert_eq!(18, stats.num_voxels);
+        assert_eq!(Some(1.5), stats.mean);
+        assert!((stats.std.unwrap() - (0.25f64 * 18.0 / 17.0).sqrt()).abs() < 1e-9);
+
+        let background_only = FsMgh::from_f32_values(&mgh.header, vec![0.0; 27]).unwrap();
+        let empty = background_only.stats(0, Some(0.0)).unwrap();
+        assert_eq!(0, empty.num_voxels);
+        assert!(empty.mean.is_none());
+        assert_eq!(vec![27], background_only.histogram(0, 1, None).unwrap().counts);
+    }
+
+    #[test]
+    fn a_histogram_of_a_volume_can_be_computed() {
+        let mgh = read_mgh("resources/subjects_dir/subject1/mri/brain.mgz").unwrap();
+        let hist = mgh.histogram(0, 10, Some(0.0)).unwrap();
+        let stats = mgh.stats(0, Some(0.0)).unwrap();
+
+        assert_eq!(11, hist.bin_edges.len());
+        assert_eq!(stats.min.unwrap(), hist.bin_edges[0]);
+        assert_eq!(156.0, hist.bin_edges[10]);
+        assert_eq!(stats.num_voxels, hist.counts.iter().sum::<usize>());
+    }
+}
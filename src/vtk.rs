@@ -0,0 +1,143 @@
+//! Export of brain meshes to the VTK formats, as ASCII legacy VTK files (`.vtk`) and XML PolyData files (`.vtp`).
+//!
+//! Both formats can be read by ParaView, 3D Slicer and other tools based on VTK. Per-vertex data, like cortical thickness,
+//! is stored as point data scalars.
+
+
+use std::fmt::Write;
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_surface::BrainMesh;
+
+
+/// The name of the point data array in the exported files.
+const POINT_DATA_NAME: &str = "data";
+
+
+impl BrainMesh {
+    /// Export this mesh in the ASCII legacy VTK format, as POLYDATA.
+    ///
+    /// # Parameters
+    ///
+    /// * `point_data`: optional per-vertex values, e.g., the `data` of an [`FsCurv`](crate::FsCurv). They are stored as `POINT_DATA` scalars named `data`.
+    ///
+    /// # Return value
+    ///
+    /// The VTK file contents. Returns [`NeuroformatsError::VertexCountMismatch`] if `point_data` is given and does not contain one value
+    /// per vertex, and an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let thickness = neuroformats::read_curv("/path/to/subjects_dir/subject1/surf/lh.thickness").unwrap();
+    /// std::fs::write("/tmp/lh_thickness.vtk", surf.mesh.to_vtk(Some(&thickness.data)).unwrap()).expect("Unable to write VTK file");
+    /// ```
+    pub fn to_vtk(&self, point_data: Option<&[f32]>) -> Result<String> {
+        self.check_export(point_data)?;
+        let mut vtk = String::with_capacity(self.vertices.len() * 12 + self.faces.len() * 8);
+        writeln!(vtk, "# vtk DataFile Version 3.0\nneuroformats {}\nASCII\nDATASET POLYDATA", env!("CARGO_PKG_VERSION")).unwrap();
+        writeln!(vtk, "POINTS {} float", self.num_vertices()).unwrap();
+        for v in self.vertices.chunks_exact(3) {
+            writeln!(vtk, "{} {} {}", v[0], v[1], v[2]).unwrap();
+        }
+        writeln!(vtk, "POLYGONS {} {}", self.num_faces(), self.num_faces() * 4).unwrap();
+        for f in self.faces.chunks_exact(3) {
+            writeln!(vtk, "3 {} {} {}", f[0], f[1], f[2]).unwrap();
+        }
+        if let Some(data) = point_data {
+            writeln!(vtk, "POINT_DATA {}\nSCALARS {} float 1\nLOOKUP_TABLE default", data.len(), POINT_DATA_NAME).unwrap();
+            for value in data {
+                writeln!(vtk, "{}", format_value(*value)).unwrap();
+            }
+        }
+        Ok(vtk)
+    }
+
+
+    /// Export this mesh in the VTK XML PolyData format (`.vtp`), with ASCII data arrays.
+    ///
+    /// See [`BrainMesh::to_vtk`] for the parameters and errors.
+    pub fn to_vtp(&self, point_data: Option<&[f32]>) -> Result<String> {
+        self.check_export(point_data)?;
+        let mut vtp = String::with_capacity(self.vertices.len() * 12 + self.faces.len() * 8);
+        vtp.push_str("<?xml version=\"1.0\"?>\n<VTKFile type=\"PolyData\" version=\"0.1\" byte_order=\"LittleEndian\">\n<PolyData>\n");
+        writeln!(vtp, "<Piece NumberOfPoints=\"{}\" NumberOfVerts=\"0\" NumberOfLines=\"0\" NumberOfStrips=\"0\" NumberOfPolys=\"{}\">", self.num_vertices(), self.num_faces()).unwrap();
+        if let Some(data) = point_data {
+            writeln!(vtp, "<PointData Scalars=\"{}\">", POINT_DATA_NAME).unwrap();
+            writeln!(vtp, "<DataArray type=\"Float32\" Name=\"{}\" format=\"ascii\">{}</DataArray>", POINT_DATA_NAME, join(data.iter().map(|v| format_value(*v)))).unwrap();
+            vtp.push_str("</PointData>\n");
+        }
+        writeln!(vtp, "<Points>\n<DataArray type=\"Float32\" NumberOfComponents=\"3\" format=\"ascii\">{}</DataArray>\n</Points>", join(self.vertices.iter().map(|v| v.to_string()))).unwrap();
+        writeln!(vtp, "<Polys>\n<DataArray type=\"Int32\" Name=\"connectivity\" format=\"ascii\">{}</DataArray>", join(self.faces.iter().map(|f| f.to_string()))).unwrap();
+        writeln!(vtp, "<DataArray type=\"Int32\" Name=\"offsets\" format=\"ascii\">{}</DataArray>\n</Polys>", join((1..=self.num_faces()).map(|f| (f * 3).to_string()))).unwrap();
+        vtp.push_str("</Piece>\n</PolyData>\n</VTKFile>\n");
+        Ok(vtp)
+    }
+
+
+    /// Check that the faces are valid and the point data contains one value per vertex.
+    fn check_export(&self, point_data: Option<&[f32]>) -> Result<()> {
+        self.validate_faces()?;
+        match point_data {
+            Some(data) if data.len() != self.num_vertices() => Err(NeuroformatsError::VertexCountMismatch(self.num_vertices(), data.len())),
+            _ => Ok(()),
+        }
+    }
+}
+
+
+/// Join the values of a data array with spaces.
+fn join<I: Iterator<Item = String>>(values: I) -> String {
+    values.collect::<Vec<String>>().join(" ")
+}
+
+
+/// Format a value for VTK files. NaN values are written as `nan`.
+fn format_value(value: f32) -> String {
+    if value.is_nan() { String::from("nan") } else { value.to_string() }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_meshes::tetrahedron;
+
+    #[test]
+    fn a_mesh_can_be_exported_to_legacy_vtk() {
+        let vtk = tetrahedron().to_vtk(Some(&[0.5, 1.0, f32::NAN, 2.5])).unwrap();
+        let lines: Vec<&str> = vtk.lines().collect();
+
+        assert_eq!("# vtk DataFile Version 3.0", lines[0]);
+        assert_eq!("POINTS 4 float", lines[4]);
+        assert_eq!("1 0 0", lines[6]);
+        assert_eq!("POLYGONS 4 16", lines[9]);
+        assert_eq!("3 0 2 1", lines[10]);
+        assert_eq!("POINT_DATA 4", lines[14]);
+        assert_eq!(vec!["0.5", "1", "nan", "2.5"], lines[17..].to_vec());
+
+        assert!(!tetrahedron().to_vtk(None).unwrap().contains("POINT_DATA"));
+    }
+
+    #[test]
+    fn a_mesh_can_be_exported_to_vtp() {
+        let vtp = tetrahedron().to_vtp(Some(&[0.5, 1.0, 1.5, 2.5])).unwrap();
+        assert!(vtp.contains("NumberOfPoints=\"4\""));
+        assert!(vtp.contains("NumberOfPolys=\"4\""));
+        assert!(vtp.contains(">0.5 1 1.5 2.5</DataArray>"));
+        assert!(vtp.contains("Name=\"offsets\" format=\"ascii\">3 6 9 12</DataArray>"));
+        assert_eq!(vtp.matches("<DataArray").count(), vtp.matches("</DataArray>").count());
+
+        assert!(!tetrahedron().to_vtp(None).unwrap().contains("PointData"));
+    }
+
+    #[test]
+    fn exporting_a_mesh_to_vtk_with_the_wrong_number_of_values_or_invalid_faces_fails() {
+        assert!(matches!(tetrahedron().to_vtk(Some(&[1.0])), Err(NeuroformatsError::VertexCountMismatch(4, 1))));
+        assert!(matches!(tetrahedron().to_vtp(Some(&[1.0])), Err(NeuroformatsError::VertexCountMismatch(4, 1))));
+        let mesh = BrainMesh { faces: vec![0, 1, 4], ..tetrahedron() };
+        assert!(mesh.to_vtk(None).is_err());
+        assert!(mesh.to_vtp(None).is_err());
+    }
+}
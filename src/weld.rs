@@ -0,0 +1,143 @@
+//! Merging of duplicate vertices and removal of unused vertices.
+//!
+//! Meshes imported from OBJ or PLY files, or exported per face by other software, often contain several copies of the same
+//! vertex. Faces which use different copies are not connected, which breaks adjacency-based algorithms like smoothing or
+//! the [`topology`](crate::topology) diagnostics. The functions in this module repair such meshes.
+
+
+use crate::fs_surface::BrainMesh;
+
+
+impl BrainMesh {
+    /// Merge vertices which are at most `epsilon` apart and re-index the faces accordingly.
+    ///
+    /// Vertices are processed in order: each vertex which has not been merged yet is kept at its position, and all vertices
+    /// within `epsilon` of it which have not been merged yet are merged into it. Use an `epsilon` of `0.0` to merge only exact
+    /// duplicates. Faces which become degenerate, i.e., which use a vertex more than once after merging, are removed.
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the welded mesh and a vector which contains, for each vertex of this mesh, the index of the vertex it was merged into.
+    /// Use it to transfer per-vertex data to the welded mesh.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mesh = neuroformats::BrainMesh::from_obj_file("/path/to/mesh.obj").unwrap();
+    /// let (welded, vertex_map) = mesh.weld_vertices(1e-5);
+    /// assert_eq!(mesh.num_vertices(), vertex_map.len());
+    /// ```
+    pub fn weld_vertices(&self, epsilon: f32) -> (BrainMesh, Vec<usize>) {
+        let index = self.build_spatial_index();
+        let mut vertex_map = vec![usize::MAX; self.num_vertices()];
+        let mut vertices = Vec::new();
+        for vidx in 0..self.num_vertices() {
+            if vertex_map[vidx] != usize::MAX {
+                continue;
+            }
+            let new_idx = vertices.len() / 3;
+            let coords = self.vertex_coords(vidx);
+            vertices.extend_from_slice(&coords);
+            vertex_map[vidx] = new_idx;
+            for neighbor in index.vertices_within_radius(coords, epsilon) {
+                if vertex_map[neighbor] == usize::MAX {
+                    vertex_map[neighbor] = new_idx;
+                }
+            }
+        }
+
+        let mut faces = Vec::with_capacity(self.faces.len());
+        for face in self.faces.chunks_exact(3) {
+            let (a, b, c) = (vertex_map[face[0] as usize], vertex_map[face[1] as usize], vertex_map[face[2] as usize]);
+            if a != b && b != c && a != c {
+                faces.extend_from_slice(&[a as i32, b as i32, c as i32]);
+            }
+        }
+        (BrainMesh { vertices, faces }, vertex_map)
+    }
+
+
+    /// Remove all vertices which are not used by any face, and re-index the faces accordingly.
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the new mesh and a vector which contains, for each vertex of this mesh, its index in the new mesh, or `None` if it was removed.
+    pub fn remove_unreferenced_vertices(&self) -> (BrainMesh, Vec<Option<usize>>) {
+        let mut referenced = vec![false; self.num_vertices()];
+        for vidx in self.faces.iter() {
+            referenced[*vidx as usize] = true;
+        }
+
+        let mut vertex_map = vec![None; self.num_vertices()];
+        let mut vertices = Vec::new();
+        for (vidx, _) in referenced.iter().enumerate().filter(|(_, is_referenced)| **is_referenced) {
+            vertex_map[vidx] = Some(vertices.len() / 3);
+            vertices.extend_from_slice(&self.vertex_coords(vidx));
+        }
+
+        let faces = self.faces.iter().map(|vidx| vertex_map[*vidx as usize].unwrap() as i32).collect();
+        (BrainMesh { vertices, faces }, vertex_map)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::subdivision::generate_icosphere;
+
+    #[test]
+    fn duplicate_and_close_vertices_can_be_welded() {
+        let mesh = BrainMesh {
+            vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.001],
+            faces: vec![0, 1, 2, 3, 4, 5],
+        };
+
+        let (exact, vertex_map) = mesh.weld_vertices(0.0);
+        assert_eq!(5, exact.num_vertices());
+        assert_eq!(vec![0, 1, 2, 1, 3, 4], vertex_map);
+        assert_eq!(vec![0, 1, 2, 1, 3, 4], exact.faces);
+
+        let (welded, vertex_map) = mesh.weld_vertices(0.01);
+        assert_eq!(4, welded.num_vertices());
+        assert_eq!(vec![0, 1, 2, 1, 3, 2], vertex_map);
+        assert_eq!([0.0, 1.0, 0.0], welded.vertex_coords(2));
+        assert_eq!(2, welded.num_faces());
+    }
+
+    #[test]
+    fn faces_which_become_degenerate_are_removed_when_welding() {
+        let mesh = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0005], faces: vec![0, 1, 2] };
+        let (welded, _) = mesh.weld_vertices(0.001);
+        assert_eq!(2, welded.num_vertices());
+        assert_eq!(0, welded.num_faces());
+    }
+
+    #[test]
+    fn welding_a_triangle_soup_restores_a_closed_mesh() {
+        let ico = generate_icosphere(2);
+        let soup = BrainMesh {
+            vertices: ico.faces.iter().flat_map(|vidx| ico.vertex_coords(*vidx as usize).to_vec()).collect(),
+            faces: (0..ico.faces.len() as i32).collect(),
+        };
+        assert!(!soup.is_closed());
+
+        let (welded, _) = soup.weld_vertices(1e-5);
+        assert_eq!(ico.num_vertices(), welded.num_vertices());
+        assert_eq!(ico.num_faces(), welded.num_faces());
+        assert_eq!(2, welded.euler_characteristic());
+    }
+
+    #[test]
+    fn unreferenced_vertices_can_be_removed() {
+        let mesh = BrainMesh {
+            vertices: vec![9.0, 9.0, 9.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 8.0, 8.0, 8.0, 0.0, 1.0, 0.0],
+            faces: vec![1, 2, 4],
+        };
+        let (cleaned, vertex_map) = mesh.remove_unreferenced_vertices();
+        assert_eq!(3, cleaned.num_vertices());
+        assert_eq!(vec![0, 1, 2], cleaned.faces);
+        assert_eq!(vec![None, Some(0), Some(1), None, Some(2)], vertex_map);
+        assert_eq!([0.0, 1.0, 0.0], cleaned.vertex_coords(2));
+    }
+}
@@ -0,0 +1,76 @@
+//! Export of brain meshes to the X3D format, the XML encoding of the ISO standard for 3D scenes.
+//!
+//! X3D files can be displayed in web browsers with X3DOM and imported into tools like Blender and MeshLab.
+
+
+use std::fmt::Write;
+
+use crate::error::{NeuroformatsError, Result};
+use crate::fs_surface::BrainMesh;
+
+
+impl BrainMesh {
+    /// Export this mesh in the X3D format, as an `IndexedFaceSet` with optional per-vertex colors.
+    ///
+    /// # Parameters
+    ///
+    /// * `colors`: optional RGB colors for the vertices, 3 values per vertex, like the output of [`crate::FsAnnot::vertex_colors`] with `alpha` set to `false`
+    ///   or of [`crate::colormap::values_to_colors`].
+    ///
+    /// # Return value
+    ///
+    /// The X3D file contents. Returns [`NeuroformatsError::ColorCountMismatch`] if `colors` is given and does not contain 3 values per vertex,
+    /// and an error if the faces are invalid, see [`BrainMesh::validate_faces`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let surf = neuroformats::read_surf("/path/to/subjects_dir/subject1/surf/lh.white").unwrap();
+    /// let annot = neuroformats::read_annot("/path/to/subjects_dir/subject1/label/lh.aparc.annot").unwrap();
+    /// let x3d = surf.mesh.to_x3d(Some(&annot.vertex_colors(false, 0))).unwrap();
+    /// std::fs::write("/tmp/lh_aparc.x3d", x3d).expect("Unable to write X3D file");
+    /// ```
+    pub fn to_x3d(&self, colors: Option<&[u8]>) -> Result<String> {
+        self.validate_faces()?;
+        if let Some(colors) = colors {
+            if colors.len() != self.vertices.len() {
+                return Err(NeuroformatsError::ColorCountMismatch(self.vertices.len(), colors.len()));
+            }
+        }
+        let mut x3d = String::with_capacity(self.vertices.len() * 12 + self.faces.len() * 8);
+        x3d.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<X3D profile=\"Interchange\" version=\"3.3\">\n<Scene>\n<Shape>\n");
+        x3d.push_str("<IndexedFaceSet solid=\"false\" colorPerVertex=\"true\" coordIndex=\"");
+        for (idx, f) in self.faces.chunks_exact(3).enumerate() {
+            write!(x3d, "{}{} {} {} -1", if idx > 0 { " " } else { "" }, f[0], f[1], f[2]).unwrap();
+        }
+        x3d.push_str("\">\n<Coordinate point=\"");
+        x3d.push_str(&self.vertices.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(" "));
+        x3d.push_str("\"/>\n");
+        if let Some(colors) = colors {
+            x3d.push_str("<Color color=\"");
+            x3d.push_str(&colors.iter().map(|c| format!("{:.4}", *c as f32 / 255.0)).collect::<Vec<String>>().join(" "));
+            x3d.push_str("\"/>\n");
+        }
+        x3d.push_str("</IndexedFaceSet>\n</Shape>\n</Scene>\n</X3D>\n");
+        Ok(x3d)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_mesh_can_be_exported_to_x3d() {
+        let mesh = BrainMesh { vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], faces: vec![0, 1, 2] };
+        let x3d = mesh.to_x3d(Some(&[255, 0, 0, 0, 255, 0, 0, 0, 51])).unwrap();
+
+        assert!(x3d.contains("coordIndex=\"0 1 2 -1\""));
+        assert!(x3d.contains("<Coordinate point=\"0 0 0 1 0 0 0 1 0\"/>"));
+        assert!(x3d.contains("<Color color=\"1.0000 0.0000 0.0000 0.0000 1.0000 0.0000 0.0000 0.0000 0.2000\"/>"));
+        assert!(!mesh.to_x3d(None).unwrap().contains("<Color"));
+        assert!(matches!(mesh.to_x3d(Some(&[0, 0, 0])), Err(NeuroformatsError::ColorCountMismatch(9, 3))));
+        assert!(BrainMesh { faces: vec![0, 1], ..mesh }.to_x3d(None).is_err());
+    }
+}
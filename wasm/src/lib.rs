@@ -0,0 +1,203 @@
+//! WebAssembly bindings for the neuroformats crate.
+//!
+//! The readers take the raw bytes of a file, e.g., from a `fetch` response or a file input element, and return
+//! objects whose data is exposed as JavaScript typed arrays. Gzip-compressed files like MGZ are detected automatically.
+//!
+//! ```js
+//! import init, { readSurf, readCurv } from "./pkg/neuroformats_wasm.js";
+//! await init();
+//! const surf = readSurf(new Uint8Array(await (await fetch("lh.white")).arrayBuffer()));
+//! const thickness = readCurv(new Uint8Array(await (await fetch("lh.thickness")).arrayBuffer()));
+//! console.log(surf.numVertices, surf.vertices, surf.faces, thickness);
+//! ```
+
+
+use wasm_bindgen::prelude::*;
+
+use neuroformats::{FsSurface, FsCurv, FsAnnot, FsLabel, FsMgh};
+
+
+/// A brain mesh read from a FreeSurfer surf file.
+#[wasm_bindgen]
+pub struct Surface {
+    surf: FsSurface,
+}
+
+#[wasm_bindgen]
+impl Surface {
+    /// The number of vertices.
+    #[wasm_bindgen(getter, js_name = numVertices)]
+    pub fn num_vertices(&self) -> usize {
+        self.surf.mesh.num_vertices()
+    }
+
+    /// The number of faces.
+    #[wasm_bindgen(getter, js_name = numFaces)]
+    pub fn num_faces(&self) -> usize {
+        self.surf.mesh.num_faces()
+    }
+
+    /// The vertex coordinates as a `Float32Array` of x,y,z triplets.
+    #[wasm_bindgen(getter)]
+    pub fn vertices(&self) -> Vec<f32> {
+        self.surf.mesh.vertices.clone()
+    }
+
+    /// The faces as an `Int32Array` of vertex index triplets. The indices are 0-based.
+    #[wasm_bindgen(getter)]
+    pub fn faces(&self) -> Vec<i32> {
+        self.surf.mesh.faces.clone()
+    }
+
+    /// The per-vertex normals as a `Float32Array` of x,y,z triplets, e.g., for lighting in WebGL.
+    #[wasm_bindgen(js_name = vertexNormals)]
+    pub fn vertex_normals(&self) -> Vec<f32> {
+        self.surf.mesh.vertex_normals()
+    }
+}
+
+
+/// A brain surface parcellation read from a FreeSurfer annot file.
+#[wasm_bindgen]
+pub struct Annot {
+    annot: FsAnnot,
+}
+
+#[wasm_bindgen]
+impl Annot {
+    /// The label of each vertex as an `Int32Array`.
+    #[wasm_bindgen(getter, js_name = vertexLabels)]
+    pub fn vertex_labels(&self) -> Vec<i32> {
+        self.annot.vertex_labels.clone()
+    }
+
+    /// The names of the regions in the colortable.
+    #[wasm_bindgen(getter, js_name = regionNames)]
+    pub fn region_names(&self) -> Vec<String> {
+        self.annot.regions()
+    }
+
+    /// The path of the colortable file the parcellation was created from, as stored in the annot file.
+    #[wasm_bindgen(getter, js_name = colortableFilename)]
+    pub fn colortable_filename(&self) -> String {
+        self.annot.colortable.orig_filename.clone()
+    }
+
+    /// The region name for each vertex.
+    #[wasm_bindgen(js_name = vertexRegions)]
+    pub fn vertex_regions(&self) -> Vec<String> {
+        self.annot.vertex_regions()
+    }
+
+    /// The color of each vertex as a `Uint8Array` of RGB or RGBA values. Vertices which are not assigned to any region get the color of the first region.
+    #[wasm_bindgen(js_name = vertexColors)]
+    pub fn vertex_colors(&self, alpha: bool) -> Vec<u8> {
+        if self.annot.num_regions() == 0 {
+            return vec![0; self.annot.vertex_labels.len() * if alpha { 4 } else { 3 }];
+        }
+        self.annot.vertex_colors(alpha, 0)
+    }
+}
+
+
+/// A label read from a FreeSurfer label file.
+#[wasm_bindgen]
+pub struct Label {
+    label: FsLabel,
+}
+
+#[wasm_bindgen]
+impl Label {
+    /// The vertex indices of the label as an `Int32Array`.
+    #[wasm_bindgen(getter, js_name = vertexIndices)]
+    pub fn vertex_indices(&self) -> Vec<i32> {
+        self.label.vertexes.iter().map(|v| v.index).collect()
+    }
+
+    /// The value of each label vertex as a `Float32Array`.
+    #[wasm_bindgen(getter)]
+    pub fn values(&self) -> Vec<f32> {
+        self.label.vertexes.iter().map(|v| v.value).collect()
+    }
+
+    /// Get the per-vertex data for a surface with `num_vertices` vertices as a `Float32Array`: the label values for the
+    /// vertices in the label, and `NaN` for all others.
+    #[wasm_bindgen(js_name = asSurfaceData)]
+    pub fn as_surface_data(&self, num_vertices: usize) -> Vec<f32> {
+        self.label.as_surface_data(num_vertices, f32::NAN)
+    }
+}
+
+
+/// A volume read from a FreeSurfer MGH or MGZ file.
+#[wasm_bindgen]
+pub struct Volume {
+    mgh: FsMgh,
+}
+
+#[wasm_bindgen]
+impl Volume {
+    /// The 4 dimensions of the volume as a `Uint32Array`: columns, rows, slices and frames.
+    #[wasm_bindgen(getter)]
+    pub fn dims(&self) -> Vec<u32> {
+        self.mgh.header.dim().iter().map(|&d| d as u32).collect()
+    }
+
+    /// The voxel values as a `Float32Array`, converted from the data type of the file, in the memory order of [`neuroformats::FsMghData`].
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<f32> {
+        let data = &self.mgh.data;
+        if let Some(d) = &data.mri_uchar {
+            d.iter().map(|&v| v as f32).collect()
+        } else if let Some(d) = &data.mri_int {
+            d.iter().map(|&v| v as f32).collect()
+        } else if let Some(d) = &data.mri_float {
+            d.iter().cloned().collect()
+        } else if let Some(d) = &data.mri_short {
+            d.iter().map(|&v| v as f32).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// The vox2ras matrix as a `Float32Array` of 16 values in row-major order, or `undefined` if the file does not contain valid RAS information.
+    #[wasm_bindgen(getter)]
+    pub fn vox2ras(&self) -> Option<Vec<f32>> {
+        self.mgh.header.vox2ras().ok().map(|m| m.iter().cloned().collect())
+    }
+}
+
+
+/// Read a brain mesh from the bytes of a FreeSurfer surf file, like `lh.white`.
+#[wasm_bindgen(js_name = readSurf)]
+pub fn read_surf(bytes: &[u8]) -> Result<Surface, JsError> {
+    Ok(Surface { surf: FsSurface::from_bytes(bytes)? })
+}
+
+
+/// Read per-vertex data from the bytes of a FreeSurfer curv file, like `lh.thickness`, as a `Float32Array`.
+#[wasm_bindgen(js_name = readCurv)]
+pub fn read_curv(bytes: &[u8]) -> Result<Vec<f32>, JsError> {
+    Ok(FsCurv::from_bytes(bytes)?.data)
+}
+
+
+/// Read a brain surface parcellation from the bytes of a FreeSurfer annot file, like `lh.aparc.annot`.
+#[wasm_bindgen(js_name = readAnnot)]
+pub fn read_annot(bytes: &[u8]) -> Result<Annot, JsError> {
+    Ok(Annot { annot: FsAnnot::from_bytes(bytes)? })
+}
+
+
+/// Read a label from the bytes of a FreeSurfer label file, like `lh.cortex.label`.
+#[wasm_bindgen(js_name = readLabel)]
+pub fn read_label(bytes: &[u8]) -> Result<Label, JsError> {
+    Ok(Label { label: FsLabel::from_bytes(bytes)? })
+}
+
+
+/// Read a volume from the bytes of a FreeSurfer MGH or MGZ file, like `brain.mgz`.
+#[wasm_bindgen(js_name = readMgh)]
+pub fn read_mgh(bytes: &[u8]) -> Result<Volume, JsError> {
+    Ok(Volume { mgh: FsMgh::from_bytes(bytes)? })
+}